@@ -722,9 +722,22 @@ impl Accounts {
             .load_with_fixed_root(ancestors, &address_table_lookup.account_key)
             .map(|(account, _rent)| account)
             .ok_or(AddressLookupError::LookupTableAccountNotFound)?;
+        Self::lookup_table_addresses(
+            &table_account,
+            ancestors.max_slot(),
+            address_table_lookup,
+            slot_hashes,
+        )
+    }
 
+    /// Looks up the addresses of `address_table_lookup` in `table_account` at `current_slot`
+    pub fn lookup_table_addresses(
+        table_account: &AccountSharedData,
+        current_slot: Slot,
+        address_table_lookup: &MessageAddressTableLookup,
+        slot_hashes: &SlotHashes,
+    ) -> std::result::Result<LoadedAddresses, AddressLookupError> {
         if table_account.owner() == &address_lookup_table::program::id() {
-            let current_slot = ancestors.max_slot();
             let lookup_table = AddressLookupTable::deserialize(table_account.data())
                 .map_err(|_ix_err| AddressLookupError::InvalidAccountData)?;
 
@@ -1465,6 +1478,8 @@ mod tests {
                 durable_nonce_fee: nonce.map(DurableNonceFee::from),
                 return_data: None,
                 executed_units: 0,
+                instruction_consumed_units: None,
+                execution_traces: None,
                 accounts_data_len_delta: 0,
             },
             programs_modified_by_tx: Box::<LoadedProgramsForTxBatch>::default(),
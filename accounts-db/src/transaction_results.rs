@@ -5,6 +5,7 @@ use {
     },
     solana_program_runtime::loaded_programs::LoadedProgramsForTxBatch,
     solana_sdk::{
+        execution_trace::ExecutionTraceList,
         instruction::{CompiledInstruction, TRANSACTION_LEVEL_STACK_HEIGHT},
         transaction::{self, TransactionError},
         transaction_context::{TransactionContext, TransactionReturnData},
@@ -77,6 +78,11 @@ pub struct TransactionExecutionDetails {
     pub durable_nonce_fee: Option<DurableNonceFee>,
     pub return_data: Option<TransactionReturnData>,
     pub executed_units: u64,
+    /// The compute units consumed by each instruction, recorded along with the log messages
+    pub instruction_consumed_units: Option<Vec<u64>>,
+    /// The execution traces of the SBF programs that each instruction invoked, recorded along
+    /// with the log messages by runtimes that compile programs with instruction tracing
+    pub execution_traces: Option<ExecutionTraceList>,
     /// The change in accounts data len for this transaction.
     /// NOTE: This value is valid IFF `status` is `Ok`.
     pub accounts_data_len_delta: i64,
@@ -6,6 +6,7 @@ use {
         account::Account,
         clock::Slot,
         commitment_config::CommitmentLevel,
+        execution_trace::ExecutionTraceList,
         fee_calculator::FeeCalculator,
         hash::Hash,
         message::Message,
@@ -37,6 +38,9 @@ pub struct TransactionSimulationDetails {
     pub logs: Vec<String>,
     pub units_consumed: u64,
     pub return_data: Option<TransactionReturnData>,
+    /// The execution traces of the SBF programs that each instruction invoked, if the server
+    /// compiles programs with instruction tracing
+    pub execution_traces: Option<ExecutionTraceList>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -24,13 +24,16 @@ use {
         message::{Message, SanitizedMessage},
         pubkey::Pubkey,
         signature::Signature,
-        transaction::{self, MessageHash, SanitizedTransaction, VersionedTransaction},
+        transaction::{
+            self, MessageHash, SanitizedTransaction, TransactionError, VersionedTransaction,
+        },
     },
     solana_send_transaction_service::{
         send_transaction_service::{SendTransactionService, TransactionInfo},
         tpu_info::NullTpuInfo,
     },
     std::{
+        collections::HashSet,
         convert::TryFrom,
         io,
         net::{Ipv4Addr, SocketAddr},
@@ -55,6 +58,8 @@ struct BanksServer {
     block_commitment_cache: Arc<RwLock<BlockCommitmentCache>>,
     transaction_sender: Sender<TransactionInfo>,
     poll_signature_status_sleep_duration: Duration,
+    /// Signers whose signatures are not verified, for tests to impersonate them
+    impersonated_signers: Arc<RwLock<HashSet<Pubkey>>>,
 }
 
 impl BanksServer {
@@ -67,12 +72,14 @@ impl BanksServer {
         block_commitment_cache: Arc<RwLock<BlockCommitmentCache>>,
         transaction_sender: Sender<TransactionInfo>,
         poll_signature_status_sleep_duration: Duration,
+        impersonated_signers: Arc<RwLock<HashSet<Pubkey>>>,
     ) -> Self {
         Self {
             bank_forks,
             block_commitment_cache,
             transaction_sender,
             poll_signature_status_sleep_duration,
+            impersonated_signers,
         }
     }
 
@@ -106,6 +113,7 @@ impl BanksServer {
         bank_forks: Arc<RwLock<BankForks>>,
         block_commitment_cache: Arc<RwLock<BlockCommitmentCache>>,
         poll_signature_status_sleep_duration: Duration,
+        impersonated_signers: Arc<RwLock<HashSet<Pubkey>>>,
     ) -> Self {
         let (transaction_sender, transaction_receiver) = unbounded();
         let bank = bank_forks.read().unwrap().working_bank();
@@ -125,6 +133,7 @@ impl BanksServer {
             block_commitment_cache,
             transaction_sender,
             poll_signature_status_sleep_duration,
+            impersonated_signers,
         )
     }
 
@@ -162,11 +171,24 @@ impl BanksServer {
 }
 
 fn verify_transaction(
-    transaction: &SanitizedTransaction,
+    transaction: &VersionedTransaction,
+    sanitized_transaction: &SanitizedTransaction,
     feature_set: &Arc<FeatureSet>,
+    impersonated_signers: &HashSet<Pubkey>,
 ) -> transaction::Result<()> {
-    transaction.verify()?;
-    transaction.verify_precompiles(feature_set)?;
+    if impersonated_signers.is_empty() {
+        sanitized_transaction.verify()?;
+    } else {
+        let verified = transaction
+            .verify_with_results()
+            .into_iter()
+            .zip(transaction.message.static_account_keys())
+            .all(|(verified, signer)| verified || impersonated_signers.contains(signer));
+        if !verified {
+            return Err(TransactionError::SignatureFailure);
+        }
+    }
+    sanitized_transaction.verify_precompiles(feature_set)?;
     Ok(())
 }
 
@@ -194,11 +216,15 @@ fn simulate_transaction(
         post_simulation_accounts: _,
         units_consumed,
         return_data,
+        inner_instructions: _,
+        instruction_units_consumed: _,
+        execution_traces,
     } = bank.simulate_transaction_unchecked(sanitized_transaction);
     let simulation_details = TransactionSimulationDetails {
         logs,
         units_consumed,
         return_data,
+        execution_traces,
     };
     BanksTransactionResultWithSimulation {
         result: Some(result),
@@ -335,7 +361,12 @@ impl Banks for BanksServer {
             Err(err) => return Some(Err(err)),
         };
 
-        if let Err(err) = verify_transaction(&sanitized_transaction, &bank.feature_set) {
+        if let Err(err) = verify_transaction(
+            &transaction,
+            &sanitized_transaction,
+            &bank.feature_set,
+            &self.impersonated_signers.read().unwrap(),
+        ) {
             return Some(Err(err));
         }
 
@@ -424,11 +455,29 @@ pub async fn start_local_server(
     bank_forks: Arc<RwLock<BankForks>>,
     block_commitment_cache: Arc<RwLock<BlockCommitmentCache>>,
     poll_signature_status_sleep_duration: Duration,
+) -> UnboundedChannel<Response<BanksResponse>, ClientMessage<BanksRequest>> {
+    start_local_server_with_impersonated_signers(
+        bank_forks,
+        block_commitment_cache,
+        poll_signature_status_sleep_duration,
+        Arc::default(),
+    )
+    .await
+}
+
+/// Like `start_local_server`, but transactions do not need valid signatures from the signers in
+/// `impersonated_signers`, which tests can change while the server runs
+pub async fn start_local_server_with_impersonated_signers(
+    bank_forks: Arc<RwLock<BankForks>>,
+    block_commitment_cache: Arc<RwLock<BlockCommitmentCache>>,
+    poll_signature_status_sleep_duration: Duration,
+    impersonated_signers: Arc<RwLock<HashSet<Pubkey>>>,
 ) -> UnboundedChannel<Response<BanksResponse>, ClientMessage<BanksRequest>> {
     let banks_server = BanksServer::new_loopback(
         bank_forks,
         block_commitment_cache,
         poll_signature_status_sleep_duration,
+        impersonated_signers,
     );
     let (client_transport, server_transport) = transport::channel::unbounded();
     let server = server::BaseChannel::with_defaults(server_transport).execute(banks_server.serve());
@@ -478,6 +527,7 @@ pub async fn start_tcp_server(
                 block_commitment_cache.clone(),
                 sender,
                 Duration::from_millis(200),
+                Arc::default(),
             );
             chan.execute(server.serve())
         })
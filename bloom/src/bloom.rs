@@ -79,10 +79,19 @@ impl<T: BloomHashIndex> Bloom<T> {
     ///
     /// See <https://hur.st/bloomfilter/>.
     pub fn random(num_items: usize, false_rate: f64, max_bits: usize) -> Self {
+        Self::random_with_rng(&mut rand::thread_rng(), num_items, false_rate, max_bits)
+    }
+    /// Same as `random`, but the keys are drawn from the given rng.
+    pub fn random_with_rng<R: Rng>(
+        rng: &mut R,
+        num_items: usize,
+        false_rate: f64,
+        max_bits: usize,
+    ) -> Self {
         let m = Self::num_bits(num_items as f64, false_rate);
         let num_bits = cmp::max(1, cmp::min(m as usize, max_bits));
         let num_keys = Self::num_keys(num_bits as f64, num_items as f64) as usize;
-        let keys: Vec<u64> = (0..num_keys).map(|_| rand::thread_rng().gen()).collect();
+        let keys: Vec<u64> = (0..num_keys).map(|_| rng.gen()).collect();
         Self::new(num_bits, keys)
     }
     fn num_bits(num_items: f64, false_rate: f64) -> f64 {
@@ -15,7 +15,10 @@ use {
         input_validators::{is_valid_pubkey, is_valid_signer},
         keypair::{DefaultSigner, SignerIndex},
     },
-    solana_cli_output::{CliProgramId, CliProgramV4, CliProgramsV4, OutputFormat},
+    solana_cli_output::{
+        CliProgramAccountType, CliProgramAuthority, CliProgramBuffer, CliProgramId, CliProgramV4,
+        CliProgramsV4, OutputFormat,
+    },
     solana_client::{
         connection_cache::ConnectionCache,
         send_and_confirm_transactions_in_parallel::{
@@ -33,6 +36,8 @@ use {
     },
     solana_sdk::{
         account::Account,
+        account_utils::StateMut,
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
         commitment_config::CommitmentConfig,
         hash::Hash,
         instruction::Instruction,
@@ -52,6 +57,7 @@ use {
         io::{Read, Write},
         mem::size_of,
         rc::Rc,
+        slice,
         sync::Arc,
     },
 };
@@ -64,9 +70,15 @@ pub enum ProgramV4CliCommand {
         authority_signer_index: SignerIndex,
     },
     Redeploy {
-        program_location: String,
+        program_location: Option<String>,
         program_address: Pubkey,
         buffer_signer_index: Option<SignerIndex>,
+        buffer_address: Option<Pubkey>,
+        authority_signer_index: SignerIndex,
+    },
+    WriteBuffer {
+        program_location: String,
+        buffer_signer_index: SignerIndex,
         authority_signer_index: SignerIndex,
     },
     Undeploy {
@@ -77,6 +89,19 @@ pub enum ProgramV4CliCommand {
         program_address: Pubkey,
         authority_signer_index: SignerIndex,
     },
+    TransferAuthority {
+        program_address: Pubkey,
+        authority_signer_index: SignerIndex,
+        new_authority_signer_index: SignerIndex,
+    },
+    DeployCopy {
+        program_location: Option<String>,
+        upgradeable_program_address: Pubkey,
+        program_signer_index: SignerIndex,
+        authority_signer_index: SignerIndex,
+        finalize_upgradeable_program: bool,
+        close_upgradeable_program: bool,
+    },
     Show {
         account_pubkey: Option<Pubkey>,
         authority: Pubkey,
@@ -133,6 +158,7 @@ impl ProgramV4SubCommands for App<'_, '_> {
                                 .index(1)
                                 .value_name("PROGRAM_FILEPATH")
                                 .takes_value(true)
+                                .required_unless("buffer")
                                 .help("/path/to/program.so"),
                         )
                         .arg(
@@ -148,7 +174,8 @@ impl ProgramV4SubCommands for App<'_, '_> {
                                 .value_name("BUFFER_SIGNER")
                                 .takes_value(true)
                                 .validator(is_valid_signer)
-                                .help("Optional intermediate buffer account to write data to, which can be used to resume a failed deploy")
+                                .help("Optional intermediate buffer account to write data to, which can be used to resume a failed deploy. \
+                                    Without PROGRAM_FILEPATH, the buffer written by `write-buffer` to deploy the program from")
                         )
                         .arg(
                             Arg::with_name("authority")
@@ -159,6 +186,35 @@ impl ProgramV4SubCommands for App<'_, '_> {
                                 .help("Program authority [default: the default configured keypair]")
                         ),
                 )
+                .subcommand(
+                    SubCommand::with_name("write-buffer")
+                        .about("Write a program into a buffer account, to redeploy a program from")
+                        .arg(
+                            Arg::with_name("program_location")
+                                .index(1)
+                                .value_name("PROGRAM_FILEPATH")
+                                .takes_value(true)
+                                .required(true)
+                                .help("/path/to/program.so"),
+                        )
+                        .arg(
+                            Arg::with_name("buffer")
+                                .long("buffer")
+                                .value_name("BUFFER_SIGNER")
+                                .takes_value(true)
+                                .required(true)
+                                .validator(is_valid_signer)
+                                .help("Buffer account signer. Writing to the same buffer again only writes the data which differs, which resumes a failed write")
+                        )
+                        .arg(
+                            Arg::with_name("authority")
+                                .long("authority")
+                                .value_name("AUTHORITY_SIGNER")
+                                .takes_value(true)
+                                .validator(is_valid_signer)
+                                .help("Buffer authority [default: the default configured keypair]")
+                        ),
+                )
                 .subcommand(
                     SubCommand::with_name("undeploy")
                         .about("Undeploy/close a program")
@@ -197,6 +253,89 @@ impl ProgramV4SubCommands for App<'_, '_> {
                                 .help("Program authority [default: the default configured keypair]")
                         ),
                 )
+                .subcommand(
+                    SubCommand::with_name("set-authority")
+                        .about("Transfer the authority over a program")
+                        .arg(
+                            Arg::with_name("program-id")
+                                .long("program-id")
+                                .value_name("PROGRAM_ID")
+                                .takes_value(true)
+                                .help("Executable program's address")
+                        )
+                        .arg(
+                            Arg::with_name("authority")
+                                .long("authority")
+                                .value_name("AUTHORITY_SIGNER")
+                                .takes_value(true)
+                                .validator(is_valid_signer)
+                                .help("Program authority [default: the default configured keypair]")
+                        )
+                        .arg(
+                            Arg::with_name("new_authority")
+                                .long("new-authority")
+                                .value_name("NEW_AUTHORITY_SIGNER")
+                                .takes_value(true)
+                                .required(true)
+                                .validator(is_valid_signer)
+                                .help("New program authority, which has to sign as well")
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("deploy-copy")
+                        .about("Deploy a copy of the program of an upgradeable loader program at a new address. \
+                            The upgradeable loader program stays live and upgradeable unless it is finalized or closed. \
+                            Migrating a program to the v4 loader at its own address needs support from the runtime")
+                        .arg(
+                            Arg::with_name("program_location")
+                                .index(1)
+                                .value_name("PROGRAM_FILEPATH")
+                                .takes_value(true)
+                                .help("/path/to/program.so, rebuilt for the v4 loader [default: the program of the upgradeable loader program]"),
+                        )
+                        .arg(
+                            Arg::with_name("program-id")
+                                .long("program-id")
+                                .value_name("PROGRAM_ID")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Address of the upgradeable loader program")
+                        )
+                        .arg(
+                            Arg::with_name("program")
+                                .long("program")
+                                .value_name("PROGRAM_SIGNER")
+                                .takes_value(true)
+                                .required(true)
+                                .validator(is_valid_signer)
+                                .help("Program account signer of the new program, which is deployed at its own address. \
+                                    The upgradeable loader program stays live and upgradeable at the old address, \
+                                    unless --finalize-upgradeable-program or --close-upgradeable-program is used")
+                        )
+                        .arg(
+                            Arg::with_name("authority")
+                                .long("authority")
+                                .value_name("AUTHORITY_SIGNER")
+                                .takes_value(true)
+                                .validator(is_valid_signer)
+                                .help("Program authority, which has to be the upgrade authority of the upgradeable loader program. \
+                                    Immutable programs are finalized after deployment [default: the default configured keypair]")
+                        )
+                        .arg(
+                            Arg::with_name("finalize-upgradeable-program")
+                                .long("finalize-upgradeable-program")
+                                .takes_value(false)
+                                .conflicts_with("close-upgradeable-program")
+                                .help("Make the upgradeable loader program immutable after the new program is deployed")
+                        )
+                        .arg(
+                            Arg::with_name("close-upgradeable-program")
+                                .long("close-upgradeable-program")
+                                .takes_value(false)
+                                .help("Close the upgradeable loader program after the new program is deployed, \
+                                    returning its lamports to the fee payer. Closed programs can not be invoked or redeployed")
+                        ),
+                )
                 .subcommand(
                     SubCommand::with_name("show")
                         .about("Display information about a buffer or program")
@@ -316,10 +455,42 @@ pub fn parse_program_v4_subcommand(
 
             CliCommandInfo {
                 command: CliCommand::ProgramV4(ProgramV4CliCommand::Redeploy {
-                    program_location: program_location.expect("Program location is missing"),
+                    program_location,
                     program_address: pubkey_of(matches, "program-id")
                         .expect("Program address is missing"),
                     buffer_signer_index: signer_info.index_of_or_none(buffer_pubkey),
+                    buffer_address: buffer_pubkey,
+                    authority_signer_index: signer_info
+                        .index_of(authority_pubkey)
+                        .expect("Authority signer is missing"),
+                }),
+                signers: signer_info.signers,
+            }
+        }
+        ("write-buffer", Some(matches)) => {
+            let mut bulk_signers = vec![Some(
+                default_signer.signer_from_path(matches, wallet_manager)?,
+            )];
+
+            let program_location = matches
+                .value_of("program_location")
+                .map(|location| location.to_string());
+
+            let (buffer, buffer_pubkey) = signer_of(matches, "buffer", wallet_manager)?;
+            bulk_signers.push(buffer);
+
+            let (authority, authority_pubkey) = signer_of(matches, "authority", wallet_manager)?;
+            bulk_signers.push(authority);
+
+            let signer_info =
+                default_signer.generate_unique_signers(bulk_signers, matches, wallet_manager)?;
+
+            CliCommandInfo {
+                command: CliCommand::ProgramV4(ProgramV4CliCommand::WriteBuffer {
+                    program_location: program_location.expect("Program location is missing"),
+                    buffer_signer_index: signer_info
+                        .index_of(buffer_pubkey)
+                        .expect("Buffer signer is missing"),
                     authority_signer_index: signer_info
                         .index_of(authority_pubkey)
                         .expect("Authority signer is missing"),
@@ -371,6 +542,71 @@ pub fn parse_program_v4_subcommand(
                 signers: signer_info.signers,
             }
         }
+        ("set-authority", Some(matches)) => {
+            let mut bulk_signers = vec![Some(
+                default_signer.signer_from_path(matches, wallet_manager)?,
+            )];
+
+            let (authority, authority_pubkey) = signer_of(matches, "authority", wallet_manager)?;
+            bulk_signers.push(authority);
+
+            let (new_authority, new_authority_pubkey) =
+                signer_of(matches, "new_authority", wallet_manager)?;
+            bulk_signers.push(new_authority);
+
+            let signer_info =
+                default_signer.generate_unique_signers(bulk_signers, matches, wallet_manager)?;
+
+            CliCommandInfo {
+                command: CliCommand::ProgramV4(ProgramV4CliCommand::TransferAuthority {
+                    program_address: pubkey_of(matches, "program-id")
+                        .expect("Program address is missing"),
+                    authority_signer_index: signer_info
+                        .index_of(authority_pubkey)
+                        .expect("Authority signer is missing"),
+                    new_authority_signer_index: signer_info
+                        .index_of(new_authority_pubkey)
+                        .expect("New authority signer is missing"),
+                }),
+                signers: signer_info.signers,
+            }
+        }
+        ("deploy-copy", Some(matches)) => {
+            let mut bulk_signers = vec![Some(
+                default_signer.signer_from_path(matches, wallet_manager)?,
+            )];
+
+            let program_location = matches
+                .value_of("program_location")
+                .map(|location| location.to_string());
+
+            let (program, program_pubkey) = signer_of(matches, "program", wallet_manager)?;
+            bulk_signers.push(program);
+
+            let (authority, authority_pubkey) = signer_of(matches, "authority", wallet_manager)?;
+            bulk_signers.push(authority);
+
+            let signer_info =
+                default_signer.generate_unique_signers(bulk_signers, matches, wallet_manager)?;
+
+            CliCommandInfo {
+                command: CliCommand::ProgramV4(ProgramV4CliCommand::DeployCopy {
+                    program_location,
+                    upgradeable_program_address: pubkey_of(matches, "program-id")
+                        .expect("Program address is missing"),
+                    program_signer_index: signer_info
+                        .index_of(program_pubkey)
+                        .expect("Program signer is missing"),
+                    authority_signer_index: signer_info
+                        .index_of(authority_pubkey)
+                        .expect("Authority signer is missing"),
+                    finalize_upgradeable_program: matches
+                        .is_present("finalize-upgradeable-program"),
+                    close_upgradeable_program: matches.is_present("close-upgradeable-program"),
+                }),
+                signers: signer_info.signers,
+            }
+        }
         ("show", Some(matches)) => {
             let authority =
                 if let Some(authority) = pubkey_of_signer(matches, "authority", wallet_manager)? {
@@ -409,21 +645,86 @@ pub fn read_and_verify_elf(program_location: &str) -> Result<Vec<u8>, Box<dyn st
     file.read_to_end(&mut program_data)
         .map_err(|err| format!("Unable to read program file: {err}"))?;
 
-    // Verify the program
+    verify_elf(&program_data)?;
+
+    Ok(program_data)
+}
+
+fn verify_elf(program_data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
     let program_runtime_environment =
         solana_loader_v4_program::create_program_runtime_environment_v2(
             &ComputeBudget::default(),
             false,
         );
     let executable =
-        Executable::<InvokeContext>::from_elf(&program_data, Arc::new(program_runtime_environment))
+        Executable::<InvokeContext>::from_elf(program_data, Arc::new(program_runtime_environment))
             .map_err(|err| format!("ELF error: {err}"))?;
 
     executable
         .verify::<RequisiteVerifier>()
         .map_err(|err| format!("ELF error: {err}"))?;
 
-    Ok(program_data)
+    Ok(())
+}
+
+// The upgradeable loader pads programs with zeros up to their maximum length. The ELF ends where
+// the last of the program header table, the section header table and the segments ends.
+fn trim_elf_padding(program_data: &[u8]) -> &[u8] {
+    let read_u64 = |offset: usize| {
+        program_data
+            .get(offset..offset.saturating_add(8))
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u64::from_le_bytes)
+    };
+    let read_u16 = |offset: usize| {
+        program_data
+            .get(offset..offset.saturating_add(2))
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u16::from_le_bytes)
+    };
+    let (
+        Some(program_header_offset),
+        Some(section_header_offset),
+        Some(program_header_size),
+        Some(program_header_count),
+        Some(section_header_size),
+        Some(section_header_count),
+    ) = (
+        read_u64(0x20),
+        read_u64(0x28),
+        read_u16(0x36),
+        read_u16(0x38),
+        read_u16(0x3A),
+        read_u16(0x3C),
+    )
+    else {
+        return program_data;
+    };
+    let mut elf_len = section_header_offset
+        .saturating_add(u64::from(section_header_size) * u64::from(section_header_count))
+        .max(
+            program_header_offset
+                .saturating_add(u64::from(program_header_size) * u64::from(program_header_count)),
+        );
+    for index in 0..u64::from(program_header_count) {
+        let program_header =
+            program_header_offset.saturating_add(index * u64::from(program_header_size));
+        let Some(program_header) = usize::try_from(program_header).ok() else {
+            return program_data;
+        };
+        // p_offset and p_filesz of the segment
+        let (Some(segment_offset), Some(segment_size)) = (
+            read_u64(program_header.saturating_add(0x08)),
+            read_u64(program_header.saturating_add(0x20)),
+        ) else {
+            return program_data;
+        };
+        elf_len = elf_len.max(segment_offset.saturating_add(segment_size));
+    }
+    usize::try_from(elf_len)
+        .ok()
+        .and_then(|elf_len| program_data.get(..elf_len))
+        .unwrap_or(program_data)
 }
 
 pub struct ProgramV4CommandConfig<'a> {
@@ -475,21 +776,48 @@ pub fn process_program_v4_subcommand(
             program_location,
             program_address,
             buffer_signer_index,
+            buffer_address,
             authority_signer_index,
         } => {
+            let command_config =
+                ProgramV4CommandConfig::new_from_cli_config(config, authority_signer_index);
+            let Some(program_location) = program_location else {
+                let buffer_address = buffer_address.ok_or("Buffer address is missing")?;
+                return process_deploy_from_buffer(
+                    rpc_client,
+                    &command_config,
+                    program_address,
+                    &buffer_address,
+                );
+            };
+
             let program_data = read_and_verify_elf(program_location)?;
             let program_len = program_data.len() as u32;
             let buffer_signer = buffer_signer_index.map(|index| config.signers[index]);
 
             process_deploy_program(
                 rpc_client,
-                &ProgramV4CommandConfig::new_from_cli_config(config, authority_signer_index),
+                &command_config,
                 &program_data,
                 program_len,
                 program_address,
                 buffer_signer,
             )
         }
+        ProgramV4CliCommand::WriteBuffer {
+            program_location,
+            buffer_signer_index,
+            authority_signer_index,
+        } => {
+            let program_data = read_and_verify_elf(program_location)?;
+
+            process_write_buffer(
+                rpc_client,
+                &ProgramV4CommandConfig::new_from_cli_config(config, authority_signer_index),
+                &program_data,
+                config.signers[*buffer_signer_index],
+            )
+        }
         ProgramV4CliCommand::Undeploy {
             program_address,
             authority_signer_index,
@@ -506,6 +834,39 @@ pub fn process_program_v4_subcommand(
             &ProgramV4CommandConfig::new_from_cli_config(config, authority_signer_index),
             program_address,
         ),
+        ProgramV4CliCommand::TransferAuthority {
+            program_address,
+            authority_signer_index,
+            new_authority_signer_index,
+        } => process_transfer_authority(
+            rpc_client,
+            &ProgramV4CommandConfig::new_from_cli_config(config, authority_signer_index),
+            program_address,
+            config.signers[*new_authority_signer_index],
+        ),
+        ProgramV4CliCommand::DeployCopy {
+            program_location,
+            upgradeable_program_address,
+            program_signer_index,
+            authority_signer_index,
+            finalize_upgradeable_program,
+            close_upgradeable_program,
+        } => {
+            let program_data = program_location
+                .as_deref()
+                .map(read_and_verify_elf)
+                .transpose()?;
+
+            process_deploy_copy_program(
+                rpc_client,
+                &ProgramV4CommandConfig::new_from_cli_config(config, authority_signer_index),
+                program_data,
+                upgradeable_program_address,
+                config.signers[*program_signer_index],
+                *finalize_upgradeable_program,
+                *close_upgradeable_program,
+            )
+        }
         ProgramV4CliCommand::Show {
             account_pubkey,
             authority,
@@ -570,18 +931,13 @@ pub fn process_deploy_program(
             .map(|(messages, balance_needed)| (messages, balance_needed, *program_address))?
         };
 
-    // Create and add write messages
-    let create_msg = |offset: u32, bytes: Vec<u8>| {
-        let instruction =
-            loader_v4::write(&buffer_address, &config.authority.pubkey(), offset, bytes);
-        Message::new_with_blockhash(&[instruction], Some(&payer_pubkey), &blockhash)
-    };
-
-    let mut write_messages = vec![];
-    let chunk_size = calculate_max_chunk_size(&create_msg);
-    for (chunk, i) in program_data.chunks(chunk_size).zip(0..) {
-        write_messages.push(create_msg((i * chunk_size) as u32, chunk.to_vec()));
-    }
+    let write_messages = build_write_messages(
+        &rpc_client,
+        config,
+        program_data,
+        &buffer_address,
+        &blockhash,
+    )?;
 
     let final_messages = if *program_address != buffer_address {
         build_retract_and_deploy_messages(
@@ -626,36 +982,158 @@ pub fn process_deploy_program(
     Ok(config.output_format.formatted_string(&program_id))
 }
 
-fn process_undeploy_program(
+fn process_write_buffer(
     rpc_client: Arc<RpcClient>,
     config: &ProgramV4CommandConfig,
-    program_address: &Pubkey,
+    program_data: &[u8],
+    buffer_signer: &dyn Signer,
 ) -> ProcessResult {
     let blockhash = rpc_client.get_latest_blockhash()?;
-    let payer_pubkey = config.payer.pubkey();
+    let buffer_address = buffer_signer.pubkey();
+    let program_data_len = program_data.len() as u32;
 
-    let Some(program_account) = rpc_client
-        .get_account_with_commitment(program_address, config.commitment)?
+    let (initial_messages, balance_needed) = if let Some(account) = rpc_client
+        .get_account_with_commitment(&buffer_address, config.commitment)?
         .value
-    else {
-        return Err("Program account does not exist".into());
-    };
+    {
+        let (truncate_instructions, balance_needed) = build_truncate_instructions(
+            rpc_client.clone(),
+            config,
+            &account,
+            &buffer_address,
+            program_data_len,
+        )?;
 
-    let retract_instruction = build_retract_instruction(
-        &program_account,
-        program_address,
-        &config.authority.pubkey(),
-    )?;
+        if let Ok(state) = solana_loader_v4_program::get_state(&account.data) {
+            if matches!(state.status, LoaderV4Status::Deployed) {
+                return Err(
+                    "Buffer account is a deployed program, use `redeploy` to write to it".into(),
+                );
+            }
+        }
 
-    let mut initial_messages = if let Some(instruction) = retract_instruction {
-        vec![Message::new_with_blockhash(
-            &[instruction],
-            Some(&payer_pubkey),
-            &blockhash,
-        )]
+        if truncate_instructions.is_empty() {
+            (vec![], 0)
+        } else {
+            (
+                vec![Message::new_with_blockhash(
+                    &truncate_instructions,
+                    Some(&config.payer.pubkey()),
+                    &blockhash,
+                )],
+                balance_needed,
+            )
+        }
     } else {
-        vec![]
-    };
+        let (create_buffer_message, balance_needed) = build_create_buffer_message(
+            rpc_client.clone(),
+            config,
+            &buffer_address,
+            &buffer_address,
+            program_data_len,
+            &blockhash,
+        )?;
+        (create_buffer_message.into_iter().collect(), balance_needed)
+    };
+
+    let write_messages = build_write_messages(
+        &rpc_client,
+        config,
+        program_data,
+        &buffer_address,
+        &blockhash,
+    )?;
+
+    check_payer(
+        &rpc_client,
+        config,
+        balance_needed,
+        &initial_messages,
+        &write_messages,
+        &[],
+    )?;
+
+    send_messages(
+        rpc_client,
+        config,
+        &initial_messages,
+        &write_messages,
+        &[],
+        Some(buffer_signer),
+    )?;
+
+    let buffer = CliProgramBuffer {
+        buffer: buffer_address.to_string(),
+    };
+    Ok(config.output_format.formatted_string(&buffer))
+}
+
+fn process_deploy_from_buffer(
+    rpc_client: Arc<RpcClient>,
+    config: &ProgramV4CommandConfig,
+    program_address: &Pubkey,
+    buffer_address: &Pubkey,
+) -> ProcessResult {
+    let Some(buffer_account) = rpc_client
+        .get_account_with_commitment(buffer_address, config.commitment)?
+        .value
+    else {
+        return Err("Buffer account does not exist".into());
+    };
+
+    if build_retract_instruction(&buffer_account, buffer_address, &config.authority.pubkey())?
+        .is_some()
+    {
+        return Err("Buffer account is a deployed program".into());
+    }
+
+    let final_messages = build_retract_and_deploy_messages(
+        rpc_client.clone(),
+        config,
+        program_address,
+        buffer_address,
+    )?;
+
+    check_payer(&rpc_client, config, 0, &[], &[], &final_messages)?;
+
+    send_messages(rpc_client, config, &[], &[], &final_messages, None)?;
+
+    let program_id = CliProgramId {
+        program_id: program_address.to_string(),
+    };
+    Ok(config.output_format.formatted_string(&program_id))
+}
+
+fn process_undeploy_program(
+    rpc_client: Arc<RpcClient>,
+    config: &ProgramV4CommandConfig,
+    program_address: &Pubkey,
+) -> ProcessResult {
+    let blockhash = rpc_client.get_latest_blockhash()?;
+    let payer_pubkey = config.payer.pubkey();
+
+    let Some(program_account) = rpc_client
+        .get_account_with_commitment(program_address, config.commitment)?
+        .value
+    else {
+        return Err("Program account does not exist".into());
+    };
+
+    let retract_instruction = build_retract_instruction(
+        &program_account,
+        program_address,
+        &config.authority.pubkey(),
+    )?;
+
+    let mut initial_messages = if let Some(instruction) = retract_instruction {
+        vec![Message::new_with_blockhash(
+            &[instruction],
+            Some(&payer_pubkey),
+            &blockhash,
+        )]
+    } else {
+        vec![]
+    };
 
     let truncate_instruction = loader_v4::truncate(
         program_address,
@@ -706,6 +1184,177 @@ fn process_finalize_program(
     Ok(config.output_format.formatted_string(&program_id))
 }
 
+fn process_transfer_authority(
+    rpc_client: Arc<RpcClient>,
+    config: &ProgramV4CommandConfig,
+    program_address: &Pubkey,
+    new_authority: &dyn Signer,
+) -> ProcessResult {
+    let Some(program_account) = rpc_client
+        .get_account_with_commitment(program_address, config.commitment)?
+        .value
+    else {
+        return Err("Program account does not exist".into());
+    };
+
+    // Checks the owner and the authority of the program, and that it is not finalized
+    build_retract_instruction(
+        &program_account,
+        program_address,
+        &config.authority.pubkey(),
+    )?;
+
+    let blockhash = rpc_client.get_latest_blockhash()?;
+    let message = Message::new_with_blockhash(
+        &[loader_v4::transfer_authority(
+            program_address,
+            &config.authority.pubkey(),
+            Some(&new_authority.pubkey()),
+        )],
+        Some(&config.payer.pubkey()),
+        &blockhash,
+    );
+    check_payer(&rpc_client, config, 0, slice::from_ref(&message), &[], &[])?;
+
+    let mut transaction = Transaction::new_unsigned(message);
+    transaction.try_sign(&[config.payer, config.authority, new_authority], blockhash)?;
+    rpc_client
+        .send_and_confirm_transaction_with_spinner(&transaction)
+        .map_err(|err| format!("Setting authority failed: {err}"))?;
+
+    let authority = CliProgramAuthority {
+        authority: new_authority.pubkey().to_string(),
+        account_type: CliProgramAccountType::Program,
+    };
+    Ok(config.output_format.formatted_string(&authority))
+}
+
+fn process_deploy_copy_program(
+    rpc_client: Arc<RpcClient>,
+    config: &ProgramV4CommandConfig,
+    program_data: Option<Vec<u8>>,
+    upgradeable_program_address: &Pubkey,
+    program_signer: &dyn Signer,
+    finalize_upgradeable_program: bool,
+    close_upgradeable_program: bool,
+) -> ProcessResult {
+    let Some(program_account) = rpc_client
+        .get_account_with_commitment(upgradeable_program_address, config.commitment)?
+        .value
+    else {
+        return Err(format!("Unable to find the account {upgradeable_program_address}").into());
+    };
+    let programdata_address = match program_account.state() {
+        Ok(UpgradeableLoaderState::Program {
+            programdata_address,
+        }) if bpf_loader_upgradeable::check_id(&program_account.owner) => programdata_address,
+        _ => {
+            return Err(format!(
+                "{upgradeable_program_address} is not an upgradeable loader program"
+            )
+            .into())
+        }
+    };
+
+    let Some(programdata_account) = rpc_client
+        .get_account_with_commitment(&programdata_address, config.commitment)?
+        .value
+    else {
+        return Err(format!("Program {upgradeable_program_address} has been closed").into());
+    };
+    let Ok(UpgradeableLoaderState::ProgramData {
+        slot: _,
+        upgrade_authority_address,
+    }) = programdata_account.state()
+    else {
+        return Err(format!("Program {upgradeable_program_address} has been closed").into());
+    };
+    if let Some(upgrade_authority_address) = upgrade_authority_address {
+        if upgrade_authority_address != config.authority.pubkey() {
+            return Err(format!(
+                "Program's authority {upgrade_authority_address} does not match authority provided {}",
+                config.authority.pubkey(),
+            )
+            .into());
+        }
+    } else if finalize_upgradeable_program || close_upgradeable_program {
+        return Err(format!(
+            "Program {upgradeable_program_address} is immutable, it can not be finalized or closed"
+        )
+        .into());
+    }
+
+    let program_data = if let Some(program_data) = program_data {
+        program_data
+    } else {
+        let program_data = trim_elf_padding(
+            programdata_account
+                .data
+                .get(UpgradeableLoaderState::size_of_programdata_metadata()..)
+                .unwrap_or_default(),
+        )
+        .to_vec();
+        verify_elf(&program_data).map_err(|err| {
+            format!("{err}, the program has to be rebuilt for the v4 loader and passed as PROGRAM_FILEPATH")
+        })?;
+        program_data
+    };
+
+    process_deploy_program(
+        rpc_client.clone(),
+        config,
+        &program_data,
+        program_data.len() as u32,
+        &program_signer.pubkey(),
+        Some(program_signer),
+    )?;
+
+    // Immutable programs stay immutable
+    if upgrade_authority_address.is_none() {
+        process_finalize_program(rpc_client.clone(), config, &program_signer.pubkey())?;
+    }
+
+    // The upgradeable loader program is left live and upgradeable unless asked otherwise
+    let upgradeable_program_instruction = if close_upgradeable_program {
+        Some(bpf_loader_upgradeable::close_any(
+            &programdata_address,
+            &config.payer.pubkey(),
+            Some(&config.authority.pubkey()),
+            Some(upgradeable_program_address),
+        ))
+    } else if finalize_upgradeable_program {
+        Some(bpf_loader_upgradeable::set_upgrade_authority(
+            upgradeable_program_address,
+            &config.authority.pubkey(),
+            None,
+        ))
+    } else {
+        None
+    };
+    if let Some(instruction) = upgradeable_program_instruction {
+        let blockhash = rpc_client.get_latest_blockhash()?;
+        let message =
+            Message::new_with_blockhash(&[instruction], Some(&config.payer.pubkey()), &blockhash);
+        check_payer(&rpc_client, config, 0, slice::from_ref(&message), &[], &[])?;
+
+        let mut transaction = Transaction::new_unsigned(message);
+        transaction.try_sign(&[config.payer, config.authority], blockhash)?;
+        rpc_client
+            .send_and_confirm_transaction_with_spinner(&transaction)
+            .map_err(|err| {
+                format!(
+                    "Program {} was deployed, but updating {upgradeable_program_address} failed: {err}",
+                    program_signer.pubkey(),
+                )
+            })?;
+    }
+
+    let program_id = CliProgramId {
+        program_id: program_signer.pubkey().to_string(),
+    };
+    Ok(config.output_format.formatted_string(&program_id))
+}
+
 fn process_show(
     rpc_client: Arc<RpcClient>,
     config: &CliConfig,
@@ -932,6 +1581,48 @@ fn send_messages(
     Ok(())
 }
 
+fn build_write_messages(
+    rpc_client: &RpcClient,
+    config: &ProgramV4CommandConfig,
+    program_data: &[u8],
+    buffer_address: &Pubkey,
+    blockhash: &Hash,
+) -> Result<Vec<Message>, Box<dyn std::error::Error>> {
+    let payer_pubkey = config.payer.pubkey();
+    let create_msg = |offset: u32, bytes: Vec<u8>| {
+        let instruction =
+            loader_v4::write(buffer_address, &config.authority.pubkey(), offset, bytes);
+        Message::new_with_blockhash(&[instruction], Some(&payer_pubkey), blockhash)
+    };
+    let chunk_size = calculate_max_chunk_size(&create_msg);
+
+    // Skip the chunks which a previous, failed write has written already
+    let written_data = rpc_client
+        .get_account_with_commitment(buffer_address, config.commitment)?
+        .value
+        .filter(|account| loader_v4::check_id(&account.owner))
+        .and_then(|account| {
+            account
+                .data
+                .get(LoaderV4State::program_data_offset()..)
+                .map(|data| data.to_vec())
+        })
+        .unwrap_or_default();
+
+    Ok(loader_v4::write_chunks(
+        buffer_address,
+        &config.authority.pubkey(),
+        program_data,
+        &written_data,
+        chunk_size,
+    )
+    .iter()
+    .map(|instruction| {
+        Message::new_with_blockhash(slice::from_ref(instruction), Some(&payer_pubkey), blockhash)
+    })
+    .collect())
+}
+
 fn build_create_buffer_message(
     rpc_client: Arc<RpcClient>,
     config: &ProgramV4CommandConfig,
@@ -1271,15 +1962,25 @@ mod tests {
     use {
         super::*,
         crate::{clap_app::get_clap_app, cli::parse_command},
+        async_trait::async_trait,
         serde_json::json,
+        solana_account_decoder::UiAccount,
+        solana_rpc_client::{
+            mock_sender::MockSender,
+            rpc_client::RpcClientConfig,
+            rpc_sender::{RpcSender, RpcTransportStats},
+        },
         solana_rpc_client_api::{
+            client_error::Result as ClientResult,
             request::RpcRequest,
             response::{Response, RpcResponseContext},
         },
-        solana_sdk::signature::{
-            keypair_from_seed, read_keypair_file, write_keypair_file, Keypair,
+        solana_sdk::{
+            bpf_loader_upgradeable::get_program_data_address,
+            signature::{keypair_from_seed, read_keypair_file, write_keypair_file, Keypair},
         },
-        std::collections::HashMap,
+        solana_transaction_status::{EncodedTransaction, TransactionBinaryEncoding},
+        std::{collections::HashMap, path::PathBuf, str::FromStr, sync::RwLock},
     };
 
     fn program_authority() -> solana_sdk::signature::Keypair {
@@ -1349,6 +2050,124 @@ mod tests {
         )
     }
 
+    /// Answers `getAccountInfo` from `accounts` and records the sent transactions, otherwise
+    /// answers like `MockSender`
+    struct AccountsSender {
+        accounts: HashMap<Pubkey, Account>,
+        transactions: Arc<RwLock<Vec<Transaction>>>,
+        mock_sender: MockSender,
+    }
+
+    #[async_trait]
+    impl RpcSender for AccountsSender {
+        async fn send(
+            &self,
+            request: RpcRequest,
+            params: serde_json::Value,
+        ) -> ClientResult<serde_json::Value> {
+            match request {
+                RpcRequest::GetAccountInfo => {
+                    let pubkey = Pubkey::from_str(params[0].as_str().unwrap()).unwrap();
+                    let value = self.accounts.get(&pubkey).map(|account| {
+                        UiAccount::encode(&pubkey, account, UiAccountEncoding::Base64, None, None)
+                    });
+                    return Ok(serde_json::to_value(Response {
+                        context: RpcResponseContext {
+                            slot: 1,
+                            api_version: None,
+                        },
+                        value,
+                    })?);
+                }
+                RpcRequest::SendTransaction => {
+                    let encoding = if params[1]["encoding"] == "base58" {
+                        TransactionBinaryEncoding::Base58
+                    } else {
+                        TransactionBinaryEncoding::Base64
+                    };
+                    let transaction = EncodedTransaction::Binary(
+                        params[0].as_str().unwrap().to_string(),
+                        encoding,
+                    )
+                    .decode()
+                    .and_then(|transaction| transaction.into_legacy_transaction())
+                    .unwrap();
+                    self.transactions.write().unwrap().push(transaction);
+                }
+                _ => {}
+            }
+            self.mock_sender.send(request, params).await
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            self.mock_sender.get_transport_stats()
+        }
+
+        fn url(&self) -> String {
+            self.mock_sender.url()
+        }
+    }
+
+    /// Returns a client with the upgradeable loader program at `program_address`, and the
+    /// transactions it sent
+    fn rpc_client_with_upgradeable_program(
+        program_address: &Pubkey,
+        upgrade_authority_address: Option<Pubkey>,
+    ) -> (RpcClient, Arc<RwLock<Vec<Transaction>>>) {
+        let programdata_address = get_program_data_address(program_address);
+        let program_account = Account {
+            lamports: 42,
+            data: bincode::serialize(&UpgradeableLoaderState::Program {
+                programdata_address,
+            })
+            .unwrap(),
+            owner: bpf_loader_upgradeable::id(),
+            executable: true,
+            rent_epoch: 1,
+        };
+        let programdata_account = Account {
+            lamports: 42,
+            data: bincode::serialize(&UpgradeableLoaderState::ProgramData {
+                slot: 1,
+                upgrade_authority_address,
+            })
+            .unwrap(),
+            owner: bpf_loader_upgradeable::id(),
+            executable: false,
+            rent_epoch: 1,
+        };
+        let transactions = Arc::default();
+        let sender = AccountsSender {
+            accounts: HashMap::from([
+                (*program_address, program_account),
+                (programdata_address, programdata_account),
+            ]),
+            transactions: Arc::clone(&transactions),
+            mock_sender: MockSender::new("succeeds"),
+        };
+        let rpc_client = RpcClient::new_sender(
+            sender,
+            RpcClientConfig::with_commitment(CommitmentConfig::default()),
+        );
+        (rpc_client, transactions)
+    }
+
+    /// Returns whether `instruction` was sent in one of `transactions`
+    fn sent_instruction(transactions: &[Transaction], instruction: &Instruction) -> bool {
+        transactions.iter().any(|transaction| {
+            let account_keys = &transaction.message.account_keys;
+            transaction.message.instructions.iter().any(|compiled| {
+                account_keys[usize::from(compiled.program_id_index)] == instruction.program_id
+                    && compiled.data == instruction.data
+                    && compiled
+                        .accounts
+                        .iter()
+                        .map(|index| account_keys[usize::from(*index)])
+                        .eq(instruction.accounts.iter().map(|meta| meta.pubkey))
+            })
+        })
+    }
+
     #[test]
     fn test_deploy() {
         let mut config = CliConfig::default();
@@ -1594,41 +2413,447 @@ mod tests {
         .is_ok());
     }
 
-    fn make_tmp_path(name: &str) -> String {
-        let out_dir = std::env::var("FARF_DIR").unwrap_or_else(|_| "farf".to_string());
-        let keypair = Keypair::new();
-
-        let path = format!("{}/tmp/{}-{}", out_dir, name, keypair.pubkey());
+    #[test]
+    fn test_write_buffer() {
+        let mut config = CliConfig::default();
+        let data = [5u8; 2048];
 
-        // whack any possible collision
-        let _ignored = std::fs::remove_dir_all(&path);
-        // whack any possible collision
-        let _ignored = std::fs::remove_file(&path);
+        let payer = keypair_from_seed(&[1u8; 32]).unwrap();
+        let buffer_signer = keypair_from_seed(&[2u8; 32]).unwrap();
+        let authority_signer = program_authority();
 
-        path
-    }
+        config.signers.push(&payer);
+        config.signers.push(&authority_signer);
 
-    #[test]
-    #[allow(clippy::cognitive_complexity)]
-    fn test_cli_parse_deploy() {
-        let test_commands = get_clap_app("test", "desc", "version");
+        let config = ProgramV4CommandConfig::new_from_cli_config(&config, &1);
 
-        let default_keypair = Keypair::new();
-        let keypair_file = make_tmp_path("keypair_file");
-        write_keypair_file(&default_keypair, &keypair_file).unwrap();
-        let default_signer = DefaultSigner::new("", &keypair_file);
+        assert!(process_write_buffer(
+            Arc::new(rpc_client_no_existing_program()),
+            &config,
+            &data,
+            &buffer_signer,
+        )
+        .is_ok());
 
-        let program_keypair = Keypair::new();
-        let program_keypair_file = make_tmp_path("program_keypair_file");
-        write_keypair_file(&program_keypair, &program_keypair_file).unwrap();
+        // Writing to the buffer again resumes the write
+        assert!(process_write_buffer(
+            Arc::new(rpc_client_with_program_retracted()),
+            &config,
+            &data,
+            &buffer_signer,
+        )
+        .is_ok());
 
-        let authority_keypair = Keypair::new();
-        let authority_keypair_file = make_tmp_path("authority_keypair_file");
-        write_keypair_file(&authority_keypair, &authority_keypair_file).unwrap();
+        assert!(process_write_buffer(
+            Arc::new(rpc_client_with_program_deployed()),
+            &config,
+            &data,
+            &buffer_signer,
+        )
+        .is_err());
 
-        let test_command = test_commands.clone().get_matches_from(vec![
-            "test",
-            "program-v4",
+        assert!(process_write_buffer(
+            Arc::new(rpc_client_with_program_finalized()),
+            &config,
+            &data,
+            &buffer_signer,
+        )
+        .is_err());
+
+        assert!(process_write_buffer(
+            Arc::new(rpc_client_wrong_account_owner()),
+            &config,
+            &data,
+            &buffer_signer,
+        )
+        .is_err());
+
+        assert!(process_write_buffer(
+            Arc::new(rpc_client_wrong_authority()),
+            &config,
+            &data,
+            &buffer_signer,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_redeploy_from_buffer() {
+        let mut config = CliConfig::default();
+
+        let payer = keypair_from_seed(&[1u8; 32]).unwrap();
+        let program_address = Pubkey::new_unique();
+        let buffer_address = Pubkey::new_unique();
+        let authority_signer = program_authority();
+
+        config.signers.push(&payer);
+        config.signers.push(&authority_signer);
+
+        let config = ProgramV4CommandConfig::new_from_cli_config(&config, &1);
+
+        assert!(process_deploy_from_buffer(
+            Arc::new(rpc_client_no_existing_program()),
+            &config,
+            &program_address,
+            &buffer_address,
+        )
+        .is_err());
+
+        // Only retracted buffers can be deployed from
+        assert!(process_deploy_from_buffer(
+            Arc::new(rpc_client_with_program_deployed()),
+            &config,
+            &program_address,
+            &buffer_address,
+        )
+        .is_err());
+
+        assert!(process_deploy_from_buffer(
+            Arc::new(rpc_client_with_program_finalized()),
+            &config,
+            &program_address,
+            &buffer_address,
+        )
+        .is_err());
+
+        assert!(process_deploy_from_buffer(
+            Arc::new(rpc_client_wrong_account_owner()),
+            &config,
+            &program_address,
+            &buffer_address,
+        )
+        .is_err());
+
+        assert!(process_deploy_from_buffer(
+            Arc::new(rpc_client_wrong_authority()),
+            &config,
+            &program_address,
+            &buffer_address,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_transfer_authority() {
+        let mut config = CliConfig::default();
+
+        let payer = keypair_from_seed(&[1u8; 32]).unwrap();
+        let program_address = Pubkey::new_unique();
+        let authority_signer = program_authority();
+        let new_authority_signer = keypair_from_seed(&[4u8; 32]).unwrap();
+
+        config.signers.push(&payer);
+        config.signers.push(&authority_signer);
+
+        let config = ProgramV4CommandConfig::new_from_cli_config(&config, &1);
+
+        assert!(process_transfer_authority(
+            Arc::new(rpc_client_no_existing_program()),
+            &config,
+            &program_address,
+            &new_authority_signer,
+        )
+        .is_err());
+
+        assert!(process_transfer_authority(
+            Arc::new(rpc_client_with_program_retracted()),
+            &config,
+            &program_address,
+            &new_authority_signer,
+        )
+        .is_ok());
+
+        assert!(process_transfer_authority(
+            Arc::new(rpc_client_with_program_deployed()),
+            &config,
+            &program_address,
+            &new_authority_signer,
+        )
+        .is_ok());
+
+        assert!(process_transfer_authority(
+            Arc::new(rpc_client_with_program_finalized()),
+            &config,
+            &program_address,
+            &new_authority_signer,
+        )
+        .is_err());
+
+        assert!(process_transfer_authority(
+            Arc::new(rpc_client_wrong_account_owner()),
+            &config,
+            &program_address,
+            &new_authority_signer,
+        )
+        .is_err());
+
+        assert!(process_transfer_authority(
+            Arc::new(rpc_client_wrong_authority()),
+            &config,
+            &program_address,
+            &new_authority_signer,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_deploy_copy() {
+        let mut config = CliConfig::default();
+
+        let payer = keypair_from_seed(&[1u8; 32]).unwrap();
+        let program_signer = keypair_from_seed(&[2u8; 32]).unwrap();
+        let upgradeable_program_address = Pubkey::new_unique();
+        let authority_signer = program_authority();
+
+        config.signers.push(&payer);
+        config.signers.push(&authority_signer);
+
+        let config = ProgramV4CommandConfig::new_from_cli_config(&config, &1);
+
+        assert!(process_deploy_copy_program(
+            Arc::new(rpc_client_no_existing_program()),
+            &config,
+            None,
+            &upgradeable_program_address,
+            &program_signer,
+            false,
+            false,
+        )
+        .is_err());
+
+        // Loader-v4 programs can not be copied
+        assert!(process_deploy_copy_program(
+            Arc::new(rpc_client_with_program_deployed()),
+            &config,
+            None,
+            &upgradeable_program_address,
+            &program_signer,
+            false,
+            false,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_deploy_copy_of_upgradeable_program() {
+        let mut config = CliConfig::default();
+        let data = [5u8; 2048];
+
+        let payer = keypair_from_seed(&[1u8; 32]).unwrap();
+        let program_signer = keypair_from_seed(&[2u8; 32]).unwrap();
+        let upgradeable_program_address = Pubkey::new_unique();
+        let programdata_address = get_program_data_address(&upgradeable_program_address);
+        let authority_signer = program_authority();
+
+        config.signers.push(&payer);
+        config.signers.push(&authority_signer);
+
+        let config = ProgramV4CommandConfig::new_from_cli_config(&config, &1);
+
+        for (finalize_upgradeable_program, close_upgradeable_program) in
+            [(false, false), (true, false), (false, true)]
+        {
+            let (rpc_client, transactions) = rpc_client_with_upgradeable_program(
+                &upgradeable_program_address,
+                Some(authority_signer.pubkey()),
+            );
+            assert!(process_deploy_copy_program(
+                Arc::new(rpc_client),
+                &config,
+                Some(data.to_vec()),
+                &upgradeable_program_address,
+                &program_signer,
+                finalize_upgradeable_program,
+                close_upgradeable_program,
+            )
+            .is_ok());
+
+            let transactions = transactions.read().unwrap();
+            // The copy of an upgradeable program stays upgradeable
+            assert!(!sent_instruction(
+                &transactions,
+                &loader_v4::transfer_authority(
+                    &program_signer.pubkey(),
+                    &authority_signer.pubkey(),
+                    None,
+                ),
+            ));
+            assert_eq!(
+                sent_instruction(
+                    &transactions,
+                    &bpf_loader_upgradeable::set_upgrade_authority(
+                        &upgradeable_program_address,
+                        &authority_signer.pubkey(),
+                        None,
+                    ),
+                ),
+                finalize_upgradeable_program,
+            );
+            assert_eq!(
+                sent_instruction(
+                    &transactions,
+                    &bpf_loader_upgradeable::close_any(
+                        &programdata_address,
+                        &payer.pubkey(),
+                        Some(&authority_signer.pubkey()),
+                        Some(&upgradeable_program_address),
+                    ),
+                ),
+                close_upgradeable_program,
+            );
+        }
+
+        // Only the upgrade authority can copy the program
+        let (rpc_client, _transactions) = rpc_client_with_upgradeable_program(
+            &upgradeable_program_address,
+            Some(Pubkey::new_unique()),
+        );
+        assert!(process_deploy_copy_program(
+            Arc::new(rpc_client),
+            &config,
+            Some(data.to_vec()),
+            &upgradeable_program_address,
+            &program_signer,
+            false,
+            false,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_deploy_copy_of_immutable_program() {
+        let mut config = CliConfig::default();
+        let data = [5u8; 2048];
+
+        let payer = keypair_from_seed(&[1u8; 32]).unwrap();
+        let program_signer = keypair_from_seed(&[2u8; 32]).unwrap();
+        let upgradeable_program_address = Pubkey::new_unique();
+        let authority_signer = program_authority();
+
+        config.signers.push(&payer);
+        config.signers.push(&authority_signer);
+
+        let config = ProgramV4CommandConfig::new_from_cli_config(&config, &1);
+
+        let (rpc_client, transactions) =
+            rpc_client_with_upgradeable_program(&upgradeable_program_address, None);
+        assert!(process_deploy_copy_program(
+            Arc::new(rpc_client),
+            &config,
+            Some(data.to_vec()),
+            &upgradeable_program_address,
+            &program_signer,
+            false,
+            false,
+        )
+        .is_ok());
+
+        // The copy of an immutable program is finalized
+        assert!(sent_instruction(
+            &transactions.read().unwrap(),
+            &loader_v4::transfer_authority(
+                &program_signer.pubkey(),
+                &authority_signer.pubkey(),
+                None,
+            ),
+        ));
+
+        // Immutable programs can neither be finalized nor closed again
+        for (finalize_upgradeable_program, close_upgradeable_program) in
+            [(true, false), (false, true)]
+        {
+            let (rpc_client, transactions) =
+                rpc_client_with_upgradeable_program(&upgradeable_program_address, None);
+            assert!(process_deploy_copy_program(
+                Arc::new(rpc_client),
+                &config,
+                Some(data.to_vec()),
+                &upgradeable_program_address,
+                &program_signer,
+                finalize_upgradeable_program,
+                close_upgradeable_program,
+            )
+            .is_err());
+            assert!(transactions.read().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_trim_elf_padding() {
+        let mut pathbuf = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        pathbuf.push("tests");
+        pathbuf.push("fixtures");
+        pathbuf.push("noop");
+        pathbuf.set_extension("so");
+        let program_data = std::fs::read(pathbuf).unwrap();
+
+        let mut padded_program_data = program_data.clone();
+        padded_program_data.resize(program_data.len() * 2, 0);
+        assert_eq!(trim_elf_padding(&padded_program_data), program_data);
+        assert_eq!(trim_elf_padding(&program_data), program_data);
+
+        // Data which is too short to be an ELF is left as is
+        assert_eq!(trim_elf_padding(&[1, 2, 3]), [1, 2, 3]);
+
+        // Segments placed after the section header table are kept
+        let mut program_data = program_data;
+        let segment_end = program_data.len() as u64 + 16;
+        let program_header_offset =
+            u64::from_le_bytes(program_data[0x20..0x28].try_into().unwrap()) as usize;
+        let segment_offset_offset = program_header_offset + 0x08;
+        let segment_size_offset = program_header_offset + 0x20;
+        let segment_offset = u64::from_le_bytes(
+            program_data[segment_offset_offset..segment_offset_offset + 8]
+                .try_into()
+                .unwrap(),
+        );
+        program_data[segment_size_offset..segment_size_offset + 8]
+            .copy_from_slice(&(segment_end - segment_offset).to_le_bytes());
+        let mut padded_program_data = program_data.clone();
+        padded_program_data.resize(program_data.len() * 2, 0);
+        assert_eq!(
+            trim_elf_padding(&padded_program_data),
+            &padded_program_data[..segment_end as usize]
+        );
+        // Segments past the end of the data leave it as is
+        assert_eq!(trim_elf_padding(&program_data), program_data);
+    }
+
+    fn make_tmp_path(name: &str) -> String {
+        let out_dir = std::env::var("FARF_DIR").unwrap_or_else(|_| "farf".to_string());
+        let keypair = Keypair::new();
+
+        let path = format!("{}/tmp/{}-{}", out_dir, name, keypair.pubkey());
+
+        // whack any possible collision
+        let _ignored = std::fs::remove_dir_all(&path);
+        // whack any possible collision
+        let _ignored = std::fs::remove_file(&path);
+
+        path
+    }
+
+    #[test]
+    #[allow(clippy::cognitive_complexity)]
+    fn test_cli_parse_deploy() {
+        let test_commands = get_clap_app("test", "desc", "version");
+
+        let default_keypair = Keypair::new();
+        let keypair_file = make_tmp_path("keypair_file");
+        write_keypair_file(&default_keypair, &keypair_file).unwrap();
+        let default_signer = DefaultSigner::new("", &keypair_file);
+
+        let program_keypair = Keypair::new();
+        let program_keypair_file = make_tmp_path("program_keypair_file");
+        write_keypair_file(&program_keypair, &program_keypair_file).unwrap();
+
+        let authority_keypair = Keypair::new();
+        let authority_keypair_file = make_tmp_path("authority_keypair_file");
+        write_keypair_file(&authority_keypair, &authority_keypair_file).unwrap();
+
+        let test_command = test_commands.clone().get_matches_from(vec![
+            "test",
+            "program-v4",
             "deploy",
             "/Users/test/program.so",
             "--program",
@@ -1685,10 +2910,11 @@ mod tests {
             parse_command(&test_command, &default_signer, &mut None).unwrap(),
             CliCommandInfo {
                 command: CliCommand::ProgramV4(ProgramV4CliCommand::Redeploy {
-                    program_location: "/Users/test/program.so".to_string(),
+                    program_location: Some("/Users/test/program.so".to_string()),
                     program_address: program_keypair.pubkey(),
                     authority_signer_index: 1,
                     buffer_signer_index: None,
+                    buffer_address: None,
                 }),
                 signers: vec![
                     read_keypair_file(&keypair_file).unwrap().into(),
@@ -1717,9 +2943,97 @@ mod tests {
             parse_command(&test_command, &default_signer, &mut None).unwrap(),
             CliCommandInfo {
                 command: CliCommand::ProgramV4(ProgramV4CliCommand::Redeploy {
-                    program_location: "/Users/test/program.so".to_string(),
+                    program_location: Some("/Users/test/program.so".to_string()),
                     program_address: program_keypair.pubkey(),
                     buffer_signer_index: Some(1),
+                    buffer_address: Some(buffer_keypair.pubkey()),
+                    authority_signer_index: 2,
+                }),
+                signers: vec![
+                    read_keypair_file(&keypair_file).unwrap().into(),
+                    read_keypair_file(&buffer_keypair_file).unwrap().into(),
+                    read_keypair_file(&authority_keypair_file).unwrap().into()
+                ],
+            }
+        );
+
+        // Deploy from a buffer written by `write-buffer`
+        let buffer_pubkey = buffer_keypair.pubkey().to_string();
+        let test_command = test_commands.clone().get_matches_from(vec![
+            "test",
+            "program-v4",
+            "redeploy",
+            "--program-id",
+            &program_keypair_file,
+            "--buffer",
+            &buffer_pubkey,
+            "--authority",
+            &authority_keypair_file,
+        ]);
+        assert_eq!(
+            parse_command(&test_command, &default_signer, &mut None).unwrap(),
+            CliCommandInfo {
+                command: CliCommand::ProgramV4(ProgramV4CliCommand::Redeploy {
+                    program_location: None,
+                    program_address: program_keypair.pubkey(),
+                    buffer_signer_index: None,
+                    buffer_address: Some(buffer_keypair.pubkey()),
+                    authority_signer_index: 1,
+                }),
+                signers: vec![
+                    read_keypair_file(&keypair_file).unwrap().into(),
+                    read_keypair_file(&authority_keypair_file).unwrap().into()
+                ],
+            }
+        );
+
+        // Either a program or a buffer is required
+        assert!(test_commands
+            .clone()
+            .get_matches_from_safe(vec![
+                "test",
+                "program-v4",
+                "redeploy",
+                "--program-id",
+                &program_keypair_file,
+            ])
+            .is_err());
+    }
+
+    #[test]
+    #[allow(clippy::cognitive_complexity)]
+    fn test_cli_parse_write_buffer() {
+        let test_commands = get_clap_app("test", "desc", "version");
+
+        let default_keypair = Keypair::new();
+        let keypair_file = make_tmp_path("keypair_file");
+        write_keypair_file(&default_keypair, &keypair_file).unwrap();
+        let default_signer = DefaultSigner::new("", &keypair_file);
+
+        let buffer_keypair = Keypair::new();
+        let buffer_keypair_file = make_tmp_path("buffer_keypair_file");
+        write_keypair_file(&buffer_keypair, &buffer_keypair_file).unwrap();
+
+        let authority_keypair = Keypair::new();
+        let authority_keypair_file = make_tmp_path("authority_keypair_file");
+        write_keypair_file(&authority_keypair, &authority_keypair_file).unwrap();
+
+        let test_command = test_commands.clone().get_matches_from(vec![
+            "test",
+            "program-v4",
+            "write-buffer",
+            "/Users/test/program.so",
+            "--buffer",
+            &buffer_keypair_file,
+            "--authority",
+            &authority_keypair_file,
+        ]);
+        assert_eq!(
+            parse_command(&test_command, &default_signer, &mut None).unwrap(),
+            CliCommandInfo {
+                command: CliCommand::ProgramV4(ProgramV4CliCommand::WriteBuffer {
+                    program_location: "/Users/test/program.so".to_string(),
+                    buffer_signer_index: 1,
                     authority_signer_index: 2,
                 }),
                 signers: vec![
@@ -1814,4 +3128,180 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    #[allow(clippy::cognitive_complexity)]
+    fn test_cli_parse_set_authority() {
+        let test_commands = get_clap_app("test", "desc", "version");
+
+        let default_keypair = Keypair::new();
+        let keypair_file = make_tmp_path("keypair_file");
+        write_keypair_file(&default_keypair, &keypair_file).unwrap();
+        let default_signer = DefaultSigner::new("", &keypair_file);
+
+        let program_keypair = Keypair::new();
+        let program_keypair_file = make_tmp_path("program_keypair_file");
+        write_keypair_file(&program_keypair, &program_keypair_file).unwrap();
+
+        let authority_keypair = Keypair::new();
+        let authority_keypair_file = make_tmp_path("authority_keypair_file");
+        write_keypair_file(&authority_keypair, &authority_keypair_file).unwrap();
+
+        let new_authority_keypair = Keypair::new();
+        let new_authority_keypair_file = make_tmp_path("new_authority_keypair_file");
+        write_keypair_file(&new_authority_keypair, &new_authority_keypair_file).unwrap();
+
+        let test_command = test_commands.clone().get_matches_from(vec![
+            "test",
+            "program-v4",
+            "set-authority",
+            "--program-id",
+            &program_keypair_file,
+            "--authority",
+            &authority_keypair_file,
+            "--new-authority",
+            &new_authority_keypair_file,
+        ]);
+        assert_eq!(
+            parse_command(&test_command, &default_signer, &mut None).unwrap(),
+            CliCommandInfo {
+                command: CliCommand::ProgramV4(ProgramV4CliCommand::TransferAuthority {
+                    program_address: program_keypair.pubkey(),
+                    authority_signer_index: 1,
+                    new_authority_signer_index: 2,
+                }),
+                signers: vec![
+                    read_keypair_file(&keypair_file).unwrap().into(),
+                    read_keypair_file(&authority_keypair_file).unwrap().into(),
+                    read_keypair_file(&new_authority_keypair_file)
+                        .unwrap()
+                        .into()
+                ],
+            }
+        );
+    }
+
+    #[test]
+    #[allow(clippy::cognitive_complexity)]
+    fn test_cli_parse_deploy_copy() {
+        let test_commands = get_clap_app("test", "desc", "version");
+
+        let default_keypair = Keypair::new();
+        let keypair_file = make_tmp_path("keypair_file");
+        write_keypair_file(&default_keypair, &keypair_file).unwrap();
+        let default_signer = DefaultSigner::new("", &keypair_file);
+
+        let upgradeable_program_address = Pubkey::new_unique();
+        let upgradeable_program_id = upgradeable_program_address.to_string();
+
+        let program_keypair = Keypair::new();
+        let program_keypair_file = make_tmp_path("program_keypair_file");
+        write_keypair_file(&program_keypair, &program_keypair_file).unwrap();
+
+        let authority_keypair = Keypair::new();
+        let authority_keypair_file = make_tmp_path("authority_keypair_file");
+        write_keypair_file(&authority_keypair, &authority_keypair_file).unwrap();
+
+        let test_command = test_commands.clone().get_matches_from(vec![
+            "test",
+            "program-v4",
+            "deploy-copy",
+            "--program-id",
+            &upgradeable_program_id,
+            "--program",
+            &program_keypair_file,
+            "--authority",
+            &authority_keypair_file,
+        ]);
+        assert_eq!(
+            parse_command(&test_command, &default_signer, &mut None).unwrap(),
+            CliCommandInfo {
+                command: CliCommand::ProgramV4(ProgramV4CliCommand::DeployCopy {
+                    program_location: None,
+                    upgradeable_program_address,
+                    program_signer_index: 1,
+                    authority_signer_index: 2,
+                    finalize_upgradeable_program: false,
+                    close_upgradeable_program: false,
+                }),
+                signers: vec![
+                    read_keypair_file(&keypair_file).unwrap().into(),
+                    read_keypair_file(&program_keypair_file).unwrap().into(),
+                    read_keypair_file(&authority_keypair_file).unwrap().into()
+                ],
+            }
+        );
+
+        let test_command = test_commands.clone().get_matches_from(vec![
+            "test",
+            "program-v4",
+            "deploy-copy",
+            "/Users/test/program.so",
+            "--program-id",
+            &upgradeable_program_id,
+            "--program",
+            &program_keypair_file,
+        ]);
+        assert_eq!(
+            parse_command(&test_command, &default_signer, &mut None).unwrap(),
+            CliCommandInfo {
+                command: CliCommand::ProgramV4(ProgramV4CliCommand::DeployCopy {
+                    program_location: Some("/Users/test/program.so".to_string()),
+                    upgradeable_program_address,
+                    program_signer_index: 1,
+                    authority_signer_index: 0,
+                    finalize_upgradeable_program: false,
+                    close_upgradeable_program: false,
+                }),
+                signers: vec![
+                    read_keypair_file(&keypair_file).unwrap().into(),
+                    read_keypair_file(&program_keypair_file).unwrap().into()
+                ],
+            }
+        );
+
+        let test_command = test_commands.clone().get_matches_from(vec![
+            "test",
+            "program-v4",
+            "deploy-copy",
+            "--program-id",
+            &upgradeable_program_id,
+            "--program",
+            &program_keypair_file,
+            "--close-upgradeable-program",
+        ]);
+        assert_eq!(
+            parse_command(&test_command, &default_signer, &mut None).unwrap(),
+            CliCommandInfo {
+                command: CliCommand::ProgramV4(ProgramV4CliCommand::DeployCopy {
+                    program_location: None,
+                    upgradeable_program_address,
+                    program_signer_index: 1,
+                    authority_signer_index: 0,
+                    finalize_upgradeable_program: false,
+                    close_upgradeable_program: true,
+                }),
+                signers: vec![
+                    read_keypair_file(&keypair_file).unwrap().into(),
+                    read_keypair_file(&program_keypair_file).unwrap().into()
+                ],
+            }
+        );
+
+        // The upgradeable loader program can either be finalized or closed
+        assert!(test_commands
+            .clone()
+            .get_matches_from_safe(vec![
+                "test",
+                "program-v4",
+                "deploy-copy",
+                "--program-id",
+                &upgradeable_program_id,
+                "--program",
+                &program_keypair_file,
+                "--finalize-upgradeable-program",
+                "--close-upgradeable-program",
+            ])
+            .is_err());
+    }
 }
@@ -217,7 +217,8 @@ mod tests {
         crossbeam_channel::unbounded,
         solana_sdk::{net::DEFAULT_TPU_COALESCE, signature::Keypair},
         solana_streamer::{
-            nonblocking::quic::DEFAULT_WAIT_FOR_CHUNK_TIMEOUT, streamer::StakedNodes,
+            nonblocking::quic::DEFAULT_WAIT_FOR_CHUNK_TIMEOUT, quic::QuicServerOptions,
+            quic_qos::StakeWeightedQos, streamer::StakedNodes,
         },
         std::{
             net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
@@ -254,10 +255,10 @@ mod tests {
             response_recv_exit.clone(),
             1,
             staked_nodes,
-            10,
-            10,
+            Arc::new(StakeWeightedQos::new(10, 10)),
             DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
             DEFAULT_TPU_COALESCE,
+            QuicServerOptions::default(),
         )
         .unwrap();
 
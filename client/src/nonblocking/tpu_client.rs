@@ -1,4 +1,6 @@
-pub use solana_tpu_client::nonblocking::tpu_client::{LeaderTpuService, TpuSenderError};
+pub use solana_tpu_client::nonblocking::tpu_client::{
+    LeaderSendStats, LeaderTpuService, TpuSenderError,
+};
 use {
     crate::{connection_cache::ConnectionCache, tpu_client::TpuClientConfig},
     solana_connection_cache::connection_cache::{
@@ -14,7 +16,7 @@ use {
         transport::Result as TransportResult,
     },
     solana_tpu_client::nonblocking::tpu_client::{Result, TpuClient as BackendTpuClient},
-    std::sync::Arc,
+    std::{collections::HashMap, net::SocketAddr, sync::Arc},
 };
 
 /// Client which sends transactions directly to the current leader's TPU port over UDP.
@@ -135,6 +137,10 @@ where
         self.tpu_client.rpc_client()
     }
 
+    pub fn leader_send_stats(&self) -> HashMap<SocketAddr, LeaderSendStats> {
+        self.tpu_client.leader_send_stats()
+    }
+
     pub async fn shutdown(&mut self) {
         self.tpu_client.shutdown().await
     }
@@ -13,11 +13,13 @@ use {
         transport::Result as TransportResult,
     },
     solana_tpu_client::tpu_client::{Result, TpuClient as BackendTpuClient},
-    std::sync::Arc,
+    std::{collections::HashMap, net::SocketAddr, sync::Arc},
 };
 pub use {
-    crate::nonblocking::tpu_client::TpuSenderError,
-    solana_tpu_client::tpu_client::{TpuClientConfig, DEFAULT_FANOUT_SLOTS, MAX_FANOUT_SLOTS},
+    crate::nonblocking::tpu_client::{LeaderSendStats, TpuSenderError},
+    solana_tpu_client::tpu_client::{
+        TpuClientConfig, DEFAULT_FANOUT_SLOTS, DEFAULT_NUM_WARM_LEADERS, MAX_FANOUT_SLOTS,
+    },
 };
 
 /// Client which sends transactions directly to the current leader's TPU port over UDP.
@@ -123,4 +125,8 @@ where
     pub fn rpc_client(&self) -> &RpcClient {
         self.tpu_client.rpc_client()
     }
+
+    pub fn leader_send_stats(&self) -> HashMap<SocketAddr, LeaderSendStats> {
+        self.tpu_client.leader_send_stats()
+    }
 }
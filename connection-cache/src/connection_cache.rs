@@ -11,9 +11,14 @@ use {
     solana_measure::measure::Measure,
     solana_sdk::timing::AtomicInterval,
     std::{
+        collections::HashSet,
         net::SocketAddr,
-        sync::{atomic::Ordering, Arc, RwLock},
-        thread::{Builder, JoinHandle},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex, RwLock, Weak,
+        },
+        thread::{sleep, Builder, JoinHandle},
+        time::Duration,
     },
     thiserror::Error,
 };
@@ -24,6 +29,10 @@ const MAX_CONNECTIONS: usize = 1024;
 /// Default connection pool size per remote address
 pub const DEFAULT_CONNECTION_POOL_SIZE: usize = 2;
 
+// Interval at which the set of warm connections is refreshed from the
+// upcoming leaders; a leader's slots last at least 1.6s.
+const WARM_CONNECTIONS_INTERVAL: Duration = Duration::from_millis(200);
+
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
 pub enum Protocol {
     UDP,
@@ -40,6 +49,14 @@ pub trait ConnectionManager: Send + Sync + 'static {
     fn new_connection_config(&self) -> Self::NewConnectionConfig;
 }
 
+/// Source of the addresses of upcoming leaders, used to keep connections to
+/// them warm ahead of their leader slots.
+pub trait UpcomingLeaders: Send + Sync + 'static {
+    /// Returns the addresses of the next `num_leaders` distinct leaders in
+    /// leader schedule order, starting with the current leader.
+    fn upcoming_leader_addrs(&self, num_leaders: usize) -> Vec<SocketAddr>;
+}
+
 pub struct ConnectionCache<
     R, // ConnectionPool
     S, // ConnectionManager
@@ -53,6 +70,8 @@ pub struct ConnectionCache<
     connection_pool_size: usize,
     connection_config: Arc<T>,
     sender: Sender<(usize, SocketAddr)>,
+    // Addresses whose connections were opened by warm_connections.
+    warm_addrs: Mutex<HashSet<SocketAddr>>,
 }
 
 impl<P, M, C> ConnectionCache<P, M, C>
@@ -102,7 +121,74 @@ where
             connection_pool_size,
             connection_config: config,
             sender,
+            warm_addrs: Mutex::default(),
+        }
+    }
+
+    /// Opens the connections to the given addresses ahead of their use, and
+    /// drops the connections previously warmed by this method to addresses
+    /// which are not in `addrs`. Connections are established asynchronously.
+    pub fn warm_connections(&self, addrs: &[SocketAddr]) {
+        let addrs: HashSet<SocketAddr> = addrs.iter().copied().collect();
+        let mut warm_addrs = self.warm_addrs.lock().unwrap();
+        let stale_addrs: Vec<SocketAddr> = warm_addrs.difference(&addrs).copied().collect();
+        if !stale_addrs.is_empty() {
+            let mut map = self.map.write().unwrap();
+            for addr in &stale_addrs {
+                debug!("Dropping stale warm connection for {addr}");
+                map.swap_remove(addr);
+            }
+            self.stats
+                .stale_warm_evictions
+                .fetch_add(stale_addrs.len() as u64, Ordering::Relaxed);
         }
+        for addr in &addrs {
+            // Connections may have been evicted since they were warmed.
+            let is_warm = warm_addrs.contains(addr) && self.map.read().unwrap().contains_key(addr);
+            if is_warm {
+                continue;
+            }
+            // Adds the pool for the address if it does not exist yet.
+            self.get_connection_and_log_stats(addr);
+            let map = self.map.read().unwrap();
+            if let Some(pool) = map.get(addr) {
+                for idx in 0..pool.num_connections() {
+                    debug!("Warming connection {idx} for {addr}");
+                    self.sender.send((idx, *addr)).unwrap();
+                }
+                self.stats
+                    .warmed_connections
+                    .fetch_add(pool.num_connections() as u64, Ordering::Relaxed);
+            }
+        }
+        *warm_addrs = addrs;
+    }
+
+    /// Spawns a thread which keeps the connections to the next `num_leaders`
+    /// upcoming leaders warm, so that transactions sent at the start of
+    /// their leader slots do not wait on connection handshakes. The thread
+    /// exits once `exit` is set or the cache is dropped.
+    pub fn spawn_warm_upcoming_leaders(
+        self: &Arc<Self>,
+        upcoming_leaders: Arc<dyn UpcomingLeaders>,
+        num_leaders: usize,
+        exit: Arc<AtomicBool>,
+    ) -> JoinHandle<()> {
+        let connection_cache: Weak<Self> = Arc::downgrade(self);
+        Builder::new()
+            .name("solConnWarm".to_string())
+            .spawn(move || {
+                while !exit.load(Ordering::Relaxed) {
+                    let Some(connection_cache) = connection_cache.upgrade() else {
+                        break;
+                    };
+                    let addrs = upcoming_leaders.upcoming_leader_addrs(num_leaders);
+                    connection_cache.warm_connections(&addrs);
+                    drop(connection_cache);
+                    sleep(WARM_CONNECTIONS_INTERVAL);
+                }
+            })
+            .unwrap()
     }
 
     /// This actually triggers the connection creation by sending empty data
@@ -736,6 +822,42 @@ mod tests {
         let _conn = map.get(&addr_with_quic_port).expect("Address not found");
     }
 
+    #[test]
+    fn test_warm_connections() {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let connection_manager = MockConnectionManager::default();
+        let connection_cache = ConnectionCache::new(
+            "connection_cache_test",
+            connection_manager,
+            DEFAULT_CONNECTION_POOL_SIZE,
+        )
+        .unwrap();
+        let other = get_addr(&mut rng);
+        connection_cache.get_connection(&other);
+        let addrs: Vec<_> = (0..4).map(|_| get_addr(&mut rng)).collect();
+        connection_cache.warm_connections(&addrs[..3]);
+        {
+            let map = connection_cache.map.read().unwrap();
+            assert_eq!(map.len(), 4);
+            assert!(addrs[..3].iter().all(|addr| map.contains_key(addr)));
+        }
+        // Connections to leaders which are no longer upcoming are dropped,
+        // while connections which were not warmed are kept.
+        connection_cache.warm_connections(&addrs[1..]);
+        let map = connection_cache.map.read().unwrap();
+        assert_eq!(map.len(), 4);
+        assert!(!map.contains_key(&addrs[0]));
+        assert!(addrs[1..].iter().all(|addr| map.contains_key(addr)));
+        assert!(map.contains_key(&other));
+        assert_eq!(
+            connection_cache
+                .stats
+                .stale_warm_evictions
+                .load(Ordering::Relaxed),
+            1
+        );
+    }
+
     // Test that we can get_connection with a connection cache configured
     // on an address with a port that would overflow to
     // an invalid port.
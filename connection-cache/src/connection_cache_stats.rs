@@ -9,6 +9,8 @@ pub struct ConnectionCacheStats {
     pub cache_misses: AtomicU64,
     pub cache_evictions: AtomicU64,
     pub eviction_time_ms: AtomicU64,
+    pub warmed_connections: AtomicU64,
+    pub stale_warm_evictions: AtomicU64,
     pub sent_packets: AtomicU64,
     pub total_batches: AtomicU64,
     pub batch_success: AtomicU64,
@@ -125,6 +127,16 @@ impl ConnectionCacheStats {
                 self.eviction_time_ms.swap(0, Ordering::Relaxed),
                 i64
             ),
+            (
+                "warmed_connections",
+                self.warmed_connections.swap(0, Ordering::Relaxed),
+                i64
+            ),
+            (
+                "stale_warm_evictions",
+                self.stale_warm_evictions.swap(0, Ordering::Relaxed),
+                i64
+            ),
             (
                 "get_connection_ms",
                 self.get_connection_ms.swap(0, Ordering::Relaxed),
@@ -38,6 +38,7 @@ use {
     solana_poh::poh_recorder::{PohRecorder, TransactionRecorder},
     solana_runtime::{bank_forks::BankForks, prioritization_fee_cache::PrioritizationFeeCache},
     solana_sdk::timing::AtomicInterval,
+    solana_streamer::quic_qos::PeerPacketValidity,
     solana_vote::vote_sender_types::ReplayVoteSender,
     std::{
         cmp, env,
@@ -336,6 +337,7 @@ impl BankingStage {
         connection_cache: Arc<ConnectionCache>,
         bank_forks: Arc<RwLock<BankForks>>,
         prioritization_fee_cache: &Arc<PrioritizationFeeCache>,
+        peer_packet_validity: Option<Arc<PeerPacketValidity>>,
     ) -> Self {
         Self::new_num_threads(
             block_production_method,
@@ -351,6 +353,7 @@ impl BankingStage {
             connection_cache,
             bank_forks,
             prioritization_fee_cache,
+            peer_packet_validity,
         )
     }
 
@@ -369,6 +372,7 @@ impl BankingStage {
         connection_cache: Arc<ConnectionCache>,
         bank_forks: Arc<RwLock<BankForks>>,
         prioritization_fee_cache: &Arc<PrioritizationFeeCache>,
+        peer_packet_validity: Option<Arc<PeerPacketValidity>>,
     ) -> Self {
         match block_production_method {
             BlockProductionMethod::ThreadLocalMultiIterator => {
@@ -385,6 +389,7 @@ impl BankingStage {
                     connection_cache,
                     bank_forks,
                     prioritization_fee_cache,
+                    peer_packet_validity,
                 )
             }
             BlockProductionMethod::CentralScheduler => Self::new_central_scheduler(
@@ -418,6 +423,7 @@ impl BankingStage {
         connection_cache: Arc<ConnectionCache>,
         bank_forks: Arc<RwLock<BankForks>>,
         prioritization_fee_cache: &Arc<PrioritizationFeeCache>,
+        peer_packet_validity: Option<Arc<PeerPacketValidity>>,
     ) -> Self {
         assert!(num_threads >= MIN_TOTAL_THREADS);
         // Single thread to generate entries from many banks.
@@ -482,6 +488,7 @@ impl BankingStage {
                     log_messages_bytes_limit,
                     forwarder,
                     unprocessed_transaction_storage,
+                    peer_packet_validity.clone(),
                 )
             })
             .collect();
@@ -546,6 +553,7 @@ impl BankingStage {
                     latest_unprocessed_votes.clone(),
                     vote_source,
                 ),
+                None, // peer_packet_validity
             ));
         }
 
@@ -606,6 +614,7 @@ impl BankingStage {
         Self { bank_thread_hdls }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn spawn_thread_local_multi_iterator_thread(
         id: u32,
         packet_receiver: BankingPacketReceiver,
@@ -616,13 +625,15 @@ impl BankingStage {
         log_messages_bytes_limit: Option<usize>,
         forwarder: Forwarder,
         unprocessed_transaction_storage: UnprocessedTransactionStorage,
+        peer_packet_validity: Option<Arc<PeerPacketValidity>>,
     ) -> JoinHandle<()> {
         let mut packet_receiver = PacketReceiver::new(id, packet_receiver, bank_forks);
-        let consumer = Consumer::new(
+        let consumer = Consumer::new_with_peer_packet_validity(
             committer,
             transaction_recorder,
             QosService::new(id),
             log_messages_bytes_limit,
+            peer_packet_validity,
         );
 
         Builder::new()
@@ -868,6 +879,7 @@ mod tests {
                 Arc::new(ConnectionCache::new("connection_cache_test")),
                 bank_forks,
                 &Arc::new(PrioritizationFeeCache::new(0u64)),
+                None,
             );
             drop(non_vote_sender);
             drop(tpu_vote_sender);
@@ -925,6 +937,7 @@ mod tests {
                 Arc::new(ConnectionCache::new("connection_cache_test")),
                 bank_forks,
                 &Arc::new(PrioritizationFeeCache::new(0u64)),
+                None,
             );
             trace!("sending bank");
             drop(non_vote_sender);
@@ -1006,6 +1019,7 @@ mod tests {
                 Arc::new(ConnectionCache::new("connection_cache_test")),
                 bank_forks,
                 &Arc::new(PrioritizationFeeCache::new(0u64)),
+                None,
             );
 
             // fund another account so we can send 2 good transactions in a single batch.
@@ -1178,6 +1192,7 @@ mod tests {
                     Arc::new(ConnectionCache::new("connection_cache_test")),
                     bank_forks,
                     &Arc::new(PrioritizationFeeCache::new(0u64)),
+                    None,
                 );
 
                 // wait for banking_stage to eat the packets
@@ -1372,6 +1387,7 @@ mod tests {
                 Arc::new(ConnectionCache::new("connection_cache_test")),
                 bank_forks,
                 &Arc::new(PrioritizationFeeCache::new(0u64)),
+                None,
             );
 
             let keypairs = (0..100).map(|_| Keypair::new()).collect_vec();
@@ -11,7 +11,7 @@ use {
     itertools::Itertools,
     solana_accounts_db::{
         transaction_error_metrics::TransactionErrorMetrics,
-        transaction_results::TransactionCheckResult,
+        transaction_results::{TransactionCheckResult, TransactionExecutionResult},
     },
     solana_ledger::token_balances::collect_token_balances,
     solana_measure::{measure::Measure, measure_us},
@@ -30,6 +30,7 @@ use {
         timing::timestamp,
         transaction::{self, AddressLoader, SanitizedTransaction, TransactionError},
     },
+    solana_streamer::quic_qos::PeerPacketValidity,
     std::{
         sync::{atomic::Ordering, Arc},
         time::Instant,
@@ -59,6 +60,8 @@ pub struct ExecuteAndCommitTransactionsOutput {
     // Transactions that either were not executed, or were executed and failed to be committed due
     // to the block ending.
     pub(crate) retryable_transaction_indexes: Vec<usize>,
+    // Transactions that were not executed because the fee payer could not pay the fee.
+    fee_payer_failed_transaction_indexes: Vec<usize>,
     // A result that indicates whether transactions were successfully
     // committed into the Poh stream.
     pub commit_transactions_result: Result<Vec<CommitTransactionDetails>, PohRecorderError>,
@@ -71,6 +74,7 @@ pub struct Consumer {
     transaction_recorder: TransactionRecorder,
     qos_service: QosService,
     log_messages_bytes_limit: Option<usize>,
+    peer_packet_validity: Option<Arc<PeerPacketValidity>>,
 }
 
 impl Consumer {
@@ -79,12 +83,31 @@ impl Consumer {
         transaction_recorder: TransactionRecorder,
         qos_service: QosService,
         log_messages_bytes_limit: Option<usize>,
+    ) -> Self {
+        Self::new_with_peer_packet_validity(
+            committer,
+            transaction_recorder,
+            qos_service,
+            log_messages_bytes_limit,
+            None,
+        )
+    }
+
+    /// Reports transactions whose fee payer cannot pay the fee as invalid
+    /// packets from the peer which sent them.
+    pub fn new_with_peer_packet_validity(
+        committer: Committer,
+        transaction_recorder: TransactionRecorder,
+        qos_service: QosService,
+        log_messages_bytes_limit: Option<usize>,
+        peer_packet_validity: Option<Arc<PeerPacketValidity>>,
     ) -> Self {
         Self {
             committer,
             transaction_recorder,
             qos_service,
             log_messages_bytes_limit,
+            peer_packet_validity,
         }
     }
 
@@ -176,9 +199,20 @@ impl Consumer {
         let ProcessTransactionsSummary {
             reached_max_poh_height,
             retryable_transaction_indexes,
+            fee_payer_failed_transaction_indexes,
             ..
         } = process_transactions_summary;
 
+        if let Some(peer_packet_validity) = &self.peer_packet_validity {
+            peer_packet_validity.record(
+                fee_payer_failed_transaction_indexes
+                    .iter()
+                    .map(|&index| packets_to_process[index].original_packet())
+                    .filter(|packet| !packet.meta().forwarded())
+                    .map(|packet| (packet.meta().socket_addr(), /*valid:*/ false)),
+            );
+        }
+
         if reached_max_poh_height || !bank_start.should_working_bank_still_be_processing_txs() {
             payload.reached_end_of_slot = true;
         }
@@ -271,6 +305,7 @@ impl Consumer {
     ) -> ProcessTransactionsSummary {
         let mut chunk_start = 0;
         let mut all_retryable_tx_indexes = vec![];
+        let mut all_fee_payer_failed_tx_indexes = vec![];
         // All the transactions that attempted execution. See description of
         // struct ProcessTransactionsSummary above for possible outcomes.
         let mut total_transactions_attempted_execution_count: usize = 0;
@@ -313,6 +348,7 @@ impl Consumer {
                 executed_transactions_count: new_executed_transactions_count,
                 executed_with_successful_result_count: new_executed_with_successful_result_count,
                 retryable_transaction_indexes: new_retryable_transaction_indexes,
+                fee_payer_failed_transaction_indexes: new_fee_payer_failed_transaction_indexes,
                 commit_transactions_result: new_commit_transactions_result,
                 execute_and_commit_timings: new_execute_and_commit_timings,
                 error_counters: new_error_counters,
@@ -347,6 +383,8 @@ impl Consumer {
             // Add the retryable txs (transactions that errored in a way that warrants a retry)
             // to the list of unprocessed txs.
             all_retryable_tx_indexes.extend_from_slice(&new_retryable_transaction_indexes);
+            all_fee_payer_failed_tx_indexes
+                .extend_from_slice(&new_fee_payer_failed_transaction_indexes);
 
             let should_bank_still_be_processing_txs =
                 Bank::should_bank_still_be_processing_txs(bank_creation_time, bank.ns_per_slot);
@@ -381,6 +419,7 @@ impl Consumer {
                 total_committed_transactions_with_successful_result_count,
             failed_commit_count: total_failed_commit_count,
             retryable_transaction_indexes: all_retryable_tx_indexes,
+            fee_payer_failed_transaction_indexes: all_fee_payer_failed_tx_indexes,
             cost_model_throttled_transactions_count: total_cost_model_throttled_transactions_count,
             cost_model_us: total_cost_model_us,
             execute_and_commit_timings: total_execute_and_commit_timings,
@@ -470,6 +509,7 @@ impl Consumer {
 
         let ExecuteAndCommitTransactionsOutput {
             ref mut retryable_transaction_indexes,
+            ref mut fee_payer_failed_transaction_indexes,
             ref execute_and_commit_timings,
             ref commit_transactions_result,
             ..
@@ -500,6 +540,7 @@ impl Consumer {
 
         retryable_transaction_indexes
             .iter_mut()
+            .chain(fee_payer_failed_transaction_indexes.iter_mut())
             .for_each(|x| *x += chunk_offset);
 
         let (cu, us) =
@@ -552,6 +593,7 @@ impl Consumer {
                 transaction_status_sender_enabled,
                 transaction_status_sender_enabled,
                 transaction_status_sender_enabled,
+                false, // enable_execution_trace_recording
                 &mut execute_and_commit_timings.execute_timings,
                 None, // account_overrides
                 self.log_messages_bytes_limit
@@ -571,6 +613,21 @@ impl Consumer {
         } = load_and_execute_transactions_output;
 
         let transactions_attempted_execution_count = execution_results.len();
+        let fee_payer_failed_transaction_indexes = execution_results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, execution_result)| {
+                matches!(
+                    execution_result,
+                    TransactionExecutionResult::NotExecuted(
+                        TransactionError::AccountNotFound
+                            | TransactionError::InsufficientFundsForFee
+                            | TransactionError::InvalidAccountForFee
+                    )
+                )
+                .then_some(index)
+            })
+            .collect();
         let (executed_transactions, execution_results_to_transactions_us) =
             measure_us!(execution_results
                 .iter()
@@ -612,6 +669,7 @@ impl Consumer {
                 executed_transactions_count,
                 executed_with_successful_result_count,
                 retryable_transaction_indexes,
+                fee_payer_failed_transaction_indexes,
                 commit_transactions_result: Err(recorder_err),
                 execute_and_commit_timings,
                 error_counters,
@@ -665,6 +723,7 @@ impl Consumer {
             executed_transactions_count,
             executed_with_successful_result_count,
             retryable_transaction_indexes,
+            fee_payer_failed_transaction_indexes,
             commit_transactions_result: Ok(commit_transaction_statuses),
             execute_and_commit_timings,
             error_counters,
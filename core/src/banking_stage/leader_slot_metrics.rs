@@ -41,6 +41,10 @@ pub(crate) struct ProcessTransactionsSummary {
     // Indexes of transactions in the transactions slice that were not committed but are retryable
     pub retryable_transaction_indexes: Vec<usize>,
 
+    // Indexes of transactions in the transactions slice that were not executed because the fee
+    // payer could not pay the fee
+    pub fee_payer_failed_transaction_indexes: Vec<usize>,
+
     // The number of transactions filtered out by the cost model
     pub cost_model_throttled_transactions_count: usize,
 
@@ -1398,6 +1398,9 @@ pub enum TowerError {
 
     #[error("The tower is useless because of new hard fork: {0}")]
     HardFork(Slot),
+
+    #[error("The tower lease is held by another instance: {0}")]
+    LostLease(String),
 }
 
 // Tower1_14_11 is the persisted data format for the Tower, decoupling it from VoteState::Current
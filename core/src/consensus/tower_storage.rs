@@ -6,11 +6,14 @@ use {
     solana_sdk::{
         pubkey::Pubkey,
         signature::{Signature, Signer},
+        timing::timestamp,
     },
     std::{
-        fs::{self, File},
+        fs::{self, File, OpenOptions},
         io::{self, BufReader},
         path::PathBuf,
+        sync::RwLock,
+        time::{Duration, Instant},
     },
 };
 
@@ -110,6 +113,13 @@ impl SavedTower {
 pub trait TowerStorage: Sync + Send {
     fn load(&self, node_pubkey: &Pubkey) -> Result<Tower>;
     fn store(&self, saved_tower: &SavedTowerVersions) -> Result<()>;
+
+    /// Checked before every vote. Storage that fences off validators sharing
+    /// it returns `TowerError::LostLease` once another instance has taken
+    /// over voting as `node_pubkey`.
+    fn check_fence(&self, _node_pubkey: &Pubkey) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
@@ -366,6 +376,246 @@ impl TowerStorage for EtcdTowerStorage {
     }
 }
 
+/// Issued with every newly granted lease, and larger than every token
+/// previously issued for the same key
+pub type FencingToken = u64;
+
+/// A key-value store that grants expiring, exclusive leases on its keys.
+/// Writes to a key are only accepted from the holder of the most recently
+/// issued fencing token for that key, so a holder whose lease was taken over
+/// can never overwrite the state of its successor.
+pub trait LeaseStore: Sync + Send {
+    /// Grant `holder` the lease on `key` for `duration`, or extend it if
+    /// `holder` already owns it. Fails while another holder's lease is live.
+    fn acquire(&self, key: &str, holder: u64, duration: Duration) -> Result<FencingToken>;
+
+    /// Write `value` under `key` if `token` is the most recently issued token
+    fn put(&self, key: &str, token: FencingToken, value: &[u8]) -> Result<()>;
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LeaseRecord {
+    holder: u64,
+    token: FencingToken,
+    /// Milliseconds since the UNIX epoch
+    expiration: u64,
+}
+
+/// `LeaseStore` kept in a directory, such as one on a filesystem shared by
+/// the validators. Updates are serialized with an advisory lock on a file in
+/// that directory, and lease expiration relies on the clocks of all holders
+/// being in sync.
+#[derive(Debug, Clone)]
+pub struct FileLeaseStore {
+    path: PathBuf,
+}
+
+impl FileLeaseStore {
+    pub fn new(path: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    fn lease_filename(&self, key: &str) -> PathBuf {
+        self.path.join(format!("{key}.lease"))
+    }
+
+    fn value_filename(&self, key: &str) -> PathBuf {
+        self.path.join(format!("{key}.bin"))
+    }
+
+    fn with_lock<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(self.path.join("lease.lock"))?;
+        let mut lock = fd_lock::RwLock::new(file);
+        let _guard = lock.write()?;
+        f()
+    }
+
+    fn read_lease(&self, key: &str) -> Result<Option<LeaseRecord>> {
+        match File::open(self.lease_filename(key)) {
+            Ok(file) => Ok(Some(bincode::deserialize_from(BufReader::new(file))?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write_file(filename: PathBuf, contents: &[u8]) -> Result<()> {
+        let new_filename = filename.with_extension("new");
+        fs::write(&new_filename, contents)?;
+        fs::rename(&new_filename, &filename)?;
+        Ok(())
+    }
+}
+
+impl LeaseStore for FileLeaseStore {
+    fn acquire(&self, key: &str, holder: u64, duration: Duration) -> Result<FencingToken> {
+        self.with_lock(|| {
+            let now = timestamp();
+            let lease = self.read_lease(key)?.unwrap_or_default();
+            let token = if lease.token > 0 && lease.holder == holder {
+                lease.token
+            } else if lease.expiration <= now {
+                lease.token.saturating_add(1)
+            } else {
+                return Err(TowerError::LostLease(format!(
+                    "{key} is leased to another instance for {}ms",
+                    lease.expiration.saturating_sub(now)
+                )));
+            };
+            let lease = LeaseRecord {
+                holder,
+                token,
+                expiration: now.saturating_add(duration.as_millis() as u64),
+            };
+            Self::write_file(self.lease_filename(key), &bincode::serialize(&lease)?)?;
+            Ok(token)
+        })
+    }
+
+    fn put(&self, key: &str, token: FencingToken, value: &[u8]) -> Result<()> {
+        self.with_lock(|| {
+            let latest_token = self.read_lease(key)?.map(|lease| lease.token);
+            if latest_token != Some(token) {
+                return Err(TowerError::LostLease(format!(
+                    "fencing token {token} for {key} was superseded by {latest_token:?}"
+                )));
+            }
+            Self::write_file(self.value_filename(key), value)
+        })
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.with_lock(|| match fs::read(self.value_filename(key)) {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        })
+    }
+}
+
+/// Tower storage for validators in an active/passive setup. Loading the tower
+/// takes the lease on it, votes renew the lease once half of it has run out,
+/// and a validator that has lost the lease to another instance is refused
+/// both votes and tower writes.
+pub struct LeasedTowerStorage {
+    lease_store: Box<dyn LeaseStore>,
+    holder: u64,
+    lease_duration: Duration,
+    /// The lease taken when the tower was last loaded
+    lease: RwLock<Option<HeldLease>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct HeldLease {
+    node_pubkey: Pubkey,
+    token: FencingToken,
+    /// No other instance can take over the lease before then
+    expiration: Instant,
+}
+
+impl LeasedTowerStorage {
+    pub fn new(lease_store: Box<dyn LeaseStore>, lease_duration: Duration) -> Self {
+        Self {
+            lease_store,
+            holder: rand::random(),
+            lease_duration,
+            lease: RwLock::default(),
+        }
+    }
+
+    pub fn fencing_token(&self) -> Option<FencingToken> {
+        self.lease.read().unwrap().map(|lease| lease.token)
+    }
+
+    fn held_lease(&self, node_pubkey: &Pubkey) -> Result<HeldLease> {
+        match *self.lease.read().unwrap() {
+            Some(lease) if lease.node_pubkey == *node_pubkey => Ok(lease),
+            _ => Err(TowerError::WrongTower(format!(
+                "the tower for {node_pubkey} has not been loaded"
+            ))),
+        }
+    }
+
+    // Takes the lease, or renews it if this instance already holds it
+    fn acquire(&self, node_pubkey: &Pubkey) -> Result<HeldLease> {
+        // The lease expires lease_duration after it was written at the latest
+        let now = Instant::now();
+        let token =
+            self.lease_store
+                .acquire(&node_pubkey.to_string(), self.holder, self.lease_duration)?;
+        Ok(HeldLease {
+            node_pubkey: *node_pubkey,
+            token,
+            expiration: now + self.lease_duration,
+        })
+    }
+}
+
+impl TowerStorage for LeasedTowerStorage {
+    fn load(&self, node_pubkey: &Pubkey) -> Result<Tower> {
+        let key = node_pubkey.to_string();
+        let lease = self.acquire(node_pubkey)?;
+        *self.lease.write().unwrap() = Some(lease);
+        info!(
+            "Acquired tower lease for {node_pubkey} with fencing token {}",
+            lease.token
+        );
+
+        match self.lease_store.get(&key)? {
+            Some(value) => bincode::deserialize(&value)
+                .map_err(|e| e.into())
+                .and_then(|t: SavedTowerVersions| t.try_into_tower(node_pubkey)),
+            None => Err(TowerError::IoError(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No saved tower for {node_pubkey}"),
+            ))),
+        }
+    }
+
+    fn store(&self, saved_tower: &SavedTowerVersions) -> Result<()> {
+        let node_pubkey = saved_tower.pubkey();
+        let lease = self.held_lease(&node_pubkey)?;
+        self.lease_store.put(
+            &node_pubkey.to_string(),
+            lease.token,
+            &bincode::serialize(saved_tower)?,
+        )
+    }
+
+    fn check_fence(&self, node_pubkey: &Pubkey) -> Result<()> {
+        let lease = self.held_lease(node_pubkey)?;
+        let remaining = lease.expiration.saturating_duration_since(Instant::now());
+        if remaining > self.lease_duration / 2 {
+            return Ok(());
+        }
+        let renewed_lease = match self.acquire(node_pubkey) {
+            Ok(renewed_lease) => renewed_lease,
+            Err(err @ TowerError::LostLease(_)) => {
+                *self.lease.write().unwrap() = None;
+                return Err(err);
+            }
+            Err(err) => return Err(err),
+        };
+        if renewed_lease.token != lease.token {
+            // Another instance held the lease in the meantime; the tower this
+            // instance has in memory may be behind the one it saved. Stop
+            // renewing the lease until the tower is loaded again.
+            *self.lease.write().unwrap() = None;
+            return Err(TowerError::LostLease(format!(
+                "fencing token for {node_pubkey} changed from {} to {}",
+                lease.token, renewed_lease.token
+            )));
+        }
+        *self.lease.write().unwrap() = Some(renewed_lease);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use {
@@ -379,9 +629,34 @@ pub mod test {
             BlockTimestamp, LandedVote, Vote, VoteState, VoteState1_14_11, VoteTransaction,
             MAX_LOCKOUT_HISTORY,
         },
+        std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
         tempfile::TempDir,
     };
 
+    // Counts the leases acquired from the wrapped store
+    struct CountingLeaseStore {
+        lease_store: FileLeaseStore,
+        acquired: Arc<AtomicUsize>,
+    }
+
+    impl LeaseStore for CountingLeaseStore {
+        fn acquire(&self, key: &str, holder: u64, duration: Duration) -> Result<FencingToken> {
+            self.acquired.fetch_add(1, Ordering::Relaxed);
+            self.lease_store.acquire(key, holder, duration)
+        }
+
+        fn put(&self, key: &str, token: FencingToken, value: &[u8]) -> Result<()> {
+            self.lease_store.put(key, token, value)
+        }
+
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+            self.lease_store.get(key)
+        }
+    }
+
     #[test]
     fn test_tower_migration() {
         let tower_path = TempDir::new().unwrap();
@@ -419,4 +694,94 @@ pub mod test {
         assert_eq!(loaded.vote_state.root_slot, Some(1));
         assert_eq!(loaded.stray_restored_slot(), None);
     }
+
+    #[test]
+    fn test_leased_tower_storage_fencing() {
+        let lease_path = TempDir::new().unwrap();
+        let new_tower_storage = |lease_duration| {
+            let lease_store = FileLeaseStore::new(lease_path.path().to_path_buf()).unwrap();
+            LeasedTowerStorage::new(Box::new(lease_store), lease_duration)
+        };
+        let identity_keypair = Keypair::new();
+        let node_pubkey = identity_keypair.pubkey();
+        let mut tower = Tower::new_for_tests(10, 0.9);
+        tower.node_pubkey = node_pubkey;
+
+        // The primary's lease expires as soon as it stops renewing it
+        let primary = new_tower_storage(Duration::ZERO);
+        assert!(Tower::restore(&primary, &node_pubkey)
+            .err()
+            .unwrap()
+            .is_file_missing());
+        assert_eq!(primary.fencing_token(), Some(1));
+        tower.save(&primary, &identity_keypair).unwrap();
+        primary.check_fence(&node_pubkey).unwrap();
+
+        // The standby takes over the expired lease along with the saved tower
+        let standby = new_tower_storage(Duration::from_secs(60));
+        let loaded = Tower::restore(&standby, &node_pubkey).unwrap();
+        assert_eq!(loaded.node_pubkey, node_pubkey);
+        assert_eq!(standby.fencing_token(), Some(2));
+        standby.check_fence(&node_pubkey).unwrap();
+
+        // The primary is now fenced off from both voting and saving its tower
+        assert!(matches!(
+            primary.check_fence(&node_pubkey),
+            Err(TowerError::LostLease(_))
+        ));
+        assert!(matches!(
+            tower.save(&primary, &identity_keypair),
+            Err(TowerError::LostLease(_))
+        ));
+
+        // Nobody else can take over while the standby's lease is live
+        let other = new_tower_storage(Duration::from_secs(60));
+        assert!(matches!(
+            Tower::restore(&other, &node_pubkey),
+            Err(TowerError::LostLease(_))
+        ));
+        assert_eq!(other.fencing_token(), None);
+        tower.save(&standby, &identity_keypair).unwrap();
+        standby.check_fence(&node_pubkey).unwrap();
+    }
+
+    #[test]
+    fn test_leased_tower_storage_renewal() {
+        let lease_path = TempDir::new().unwrap();
+        let acquired = Arc::<AtomicUsize>::default();
+        let lease_duration = Duration::from_millis(400);
+        let tower_storage = LeasedTowerStorage::new(
+            Box::new(CountingLeaseStore {
+                lease_store: FileLeaseStore::new(lease_path.path().to_path_buf()).unwrap(),
+                acquired: acquired.clone(),
+            }),
+            lease_duration,
+        );
+        let identity_keypair = Keypair::new();
+        let node_pubkey = identity_keypair.pubkey();
+
+        // Votes are refused until the tower is loaded
+        assert!(matches!(
+            tower_storage.check_fence(&node_pubkey),
+            Err(TowerError::WrongTower(_))
+        ));
+        assert!(Tower::restore(&tower_storage, &node_pubkey)
+            .err()
+            .unwrap()
+            .is_file_missing());
+        assert_eq!(acquired.load(Ordering::Relaxed), 1);
+
+        // The lease isn't renewed while more than half of it is left
+        for _ in 0..10 {
+            tower_storage.check_fence(&node_pubkey).unwrap();
+        }
+        assert_eq!(acquired.load(Ordering::Relaxed), 1);
+
+        std::thread::sleep(lease_duration / 2);
+        tower_storage.check_fence(&node_pubkey).unwrap();
+        assert_eq!(acquired.load(Ordering::Relaxed), 2);
+        tower_storage.check_fence(&node_pubkey).unwrap();
+        assert_eq!(acquired.load(Ordering::Relaxed), 2);
+        assert_eq!(tower_storage.fencing_token(), Some(1));
+    }
 }
@@ -29,7 +29,6 @@ use {
     solana_runtime::bank::Bank,
     solana_sdk::{
         clock::{Slot, DEFAULT_MS_PER_SLOT},
-        genesis_config::ClusterType,
         pubkey::Pubkey,
         signature::Signable,
         signer::keypair::Keypair,
@@ -657,7 +656,7 @@ impl AncestorHashesService {
         request_throttle: &mut Vec<u64>,
     ) {
         let root_bank = repair_info.bank_forks.read().unwrap().root_bank();
-        let cluster_type = root_bank.cluster_type();
+        let repair_protocol = serve_repair::get_repair_protocol(&root_bank);
         for (slot, request_type) in retryable_slots_receiver.try_iter() {
             datapoint_info!("ancestor-repair-retry", ("slot", slot, i64));
             if request_type.is_pruned() {
@@ -753,7 +752,7 @@ impl AncestorHashesService {
                 outstanding_requests,
                 identity_keypair,
                 request_type,
-                cluster_type,
+                repair_protocol,
             ) {
                 request_throttle.push(timestamp());
                 if request_type.is_pruned() {
@@ -832,9 +831,8 @@ impl AncestorHashesService {
         outstanding_requests: &RwLock<OutstandingAncestorHashesRepairs>,
         identity_keypair: &Keypair,
         request_type: AncestorRequestType,
-        cluster_type: ClusterType,
+        repair_protocol: Protocol,
     ) -> bool {
-        let repair_protocol = serve_repair::get_repair_protocol(cluster_type);
         let Ok(sampled_validators) = serve_repair.repair_request_ancestor_hashes_sample_peers(
             duplicate_slot,
             cluster_slots,
@@ -1512,7 +1510,7 @@ mod test {
             &outstanding_requests,
             &requester_cluster_info.keypair(),
             AncestorRequestType::DeadDuplicateConfirmed,
-            ClusterType::Development,
+            Protocol::UDP,
         );
         assert!(ancestor_hashes_request_statuses.is_empty());
 
@@ -1563,7 +1561,7 @@ mod test {
             &outstanding_requests,
             &requester_cluster_info.keypair(),
             AncestorRequestType::DeadDuplicateConfirmed,
-            ClusterType::Development,
+            Protocol::UDP,
         );
 
         assert_eq!(ancestor_hashes_request_statuses.len(), 1);
@@ -1626,7 +1624,7 @@ mod test {
             &outstanding_requests,
             &requester_cluster_info.keypair(),
             AncestorRequestType::PopularPruned,
-            ClusterType::Development,
+            Protocol::UDP,
         );
 
         assert_eq!(ancestor_hashes_request_statuses.len(), 1);
@@ -12,4 +12,5 @@ pub mod repair_weighted_traversal;
 pub mod request_response;
 pub mod result;
 pub mod serve_repair;
+mod serve_repair_qos;
 pub mod serve_repair_service;
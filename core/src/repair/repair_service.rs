@@ -15,7 +15,10 @@ use {
             outstanding_requests::OutstandingRequests,
             quic_endpoint::LocalRequest,
             repair_weight::RepairWeight,
-            serve_repair::{self, ServeRepair, ShredRepairType, REPAIR_PEERS_CACHE_CAPACITY},
+            serve_repair::{
+                self, ServeRepair, ShredRepairType, MAX_SHRED_BATCH_REPAIR_RESPONSES,
+                REPAIR_PEERS_CACHE_CAPACITY,
+            },
         },
     },
     crossbeam_channel::{Receiver as CrossbeamReceiver, Sender as CrossbeamSender},
@@ -30,6 +33,7 @@ use {
     solana_sdk::{
         clock::{Slot, DEFAULT_TICKS_PER_SECOND, MS_PER_TICK},
         epoch_schedule::EpochSchedule,
+        feature_set,
         hash::Hash,
         pubkey::Pubkey,
         signer::keypair::Keypair,
@@ -111,6 +115,7 @@ pub struct RepairStats {
     pub shred: RepairStatsGroup,
     pub highest_shred: RepairStatsGroup,
     pub orphan: RepairStatsGroup,
+    pub shred_batch: RepairStatsGroup,
     pub get_best_orphans_us: u64,
     pub get_best_shreds_us: u64,
 }
@@ -323,7 +328,7 @@ impl RepairService {
             let mut add_votes_elapsed;
 
             let root_bank = repair_info.bank_forks.read().unwrap().root_bank();
-            let repair_protocol = serve_repair::get_repair_protocol(root_bank.cluster_type());
+            let repair_protocol = serve_repair::get_repair_protocol(&root_bank);
             let repairs = {
                 let new_root = root_bank.slot();
 
@@ -427,6 +432,15 @@ impl RepairService {
                 repairs
             };
 
+            let repairs = if root_bank
+                .feature_set
+                .is_active(&feature_set::batch_repair_requests::id())
+            {
+                Self::batch_shred_repairs(repairs)
+            } else {
+                repairs
+            };
+
             let identity_keypair: &Keypair = &repair_info.cluster_info.keypair().clone();
 
             let mut build_repairs_batch_elapsed = Measure::start("build_repairs_batch_elapsed");
@@ -484,13 +498,15 @@ impl RepairService {
             if last_stats.elapsed().as_secs() > 2 {
                 let repair_total = repair_stats.shred.count
                     + repair_stats.highest_shred.count
-                    + repair_stats.orphan.count;
+                    + repair_stats.orphan.count
+                    + repair_stats.shred_batch.count;
                 let slot_to_count: Vec<_> = repair_stats
                     .shred
                     .slot_pubkeys
                     .iter()
                     .chain(repair_stats.highest_shred.slot_pubkeys.iter())
                     .chain(repair_stats.orphan.slot_pubkeys.iter())
+                    .chain(repair_stats.shred_batch.slot_pubkeys.iter())
                     .map(|(slot, slot_repairs)| {
                         (slot, slot_repairs.pubkey_repairs.values().sum::<u64>())
                     })
@@ -504,6 +520,7 @@ impl RepairService {
                         ("shred-count", repair_stats.shred.count, i64),
                         ("highest-shred-count", repair_stats.highest_shred.count, i64),
                         ("orphan-count", repair_stats.orphan.count, i64),
+                        ("shred-batch-count", repair_stats.shred_batch.count, i64),
                         ("shred-slot-max", nonzero_num(repair_stats.shred.max), Option<i64>),
                         ("shred-slot-min", nonzero_num(repair_stats.shred.min), Option<i64>),
                         ("repair-highest-slot", repair_stats.highest_shred.max, i64), // deprecated
@@ -652,6 +669,37 @@ impl RepairService {
         }
     }
 
+    /// Combines `Shred` repairs of the same slot into `ShredBatch` repairs
+    /// covering up to `MAX_SHRED_BATCH_REPAIR_RESPONSES` consecutive indices
+    /// each. The batches take the place of the first repair of their slot.
+    pub(crate) fn batch_shred_repairs(repairs: Vec<ShredRepairType>) -> Vec<ShredRepairType> {
+        let mut shred_indices = HashMap::<Slot, Vec<u64>>::new();
+        let mut out = Vec::with_capacity(repairs.len());
+        for repair in repairs {
+            match repair {
+                ShredRepairType::Shred(slot, shred_index) => {
+                    let indices = shred_indices.entry(slot).or_default();
+                    if indices.is_empty() {
+                        out.push(repair);
+                    }
+                    indices.push(shred_index);
+                }
+                ShredRepairType::Orphan(_)
+                | ShredRepairType::HighestShred(_, _)
+                | ShredRepairType::ShredBatch(_, _, _) => out.push(repair),
+            }
+        }
+        out.into_iter()
+            .flat_map(|repair| match repair {
+                ShredRepairType::Shred(slot, _) => {
+                    let indices = shred_indices.remove(&slot).unwrap_or_default();
+                    new_shred_batches(slot, indices)
+                }
+                _ => vec![repair],
+            })
+            .collect()
+    }
+
     /// Repairs any fork starting at the input slot (uses blockstore for fork info)
     pub fn generate_repairs_for_fork(
         blockstore: &Blockstore,
@@ -847,6 +895,32 @@ impl RepairService {
     }
 }
 
+fn new_shred_batches(slot: Slot, mut indices: Vec<u64>) -> Vec<ShredRepairType> {
+    indices.sort_unstable();
+    indices.dedup();
+    let mut batches: Vec<(/*start_index:*/ u64, /*bitmap:*/ u64)> = Vec::new();
+    for shred_index in indices {
+        match batches.last_mut() {
+            Some((start_index, bitmap))
+                if shred_index - *start_index < MAX_SHRED_BATCH_REPAIR_RESPONSES =>
+            {
+                *bitmap |= 1 << (shred_index - *start_index);
+            }
+            _ => batches.push((shred_index, 1)),
+        }
+    }
+    batches
+        .into_iter()
+        .map(|(start_index, bitmap)| {
+            if bitmap == 1 {
+                ShredRepairType::Shred(slot, start_index)
+            } else {
+                ShredRepairType::ShredBatch(slot, start_index, bitmap)
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 pub(crate) fn sleep_shred_deferment_period() {
     // sleep to bypass shred deferment window
@@ -1103,6 +1177,30 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_batch_shred_repairs() {
+        let repairs = vec![
+            ShredRepairType::Orphan(3),
+            ShredRepairType::Shred(5, 71),
+            ShredRepairType::HighestShred(4, 2),
+            ShredRepairType::Shred(5, 3),
+            ShredRepairType::Shred(6, 1),
+            ShredRepairType::Shred(5, 4),
+            ShredRepairType::Shred(5, 67),
+            ShredRepairType::Shred(5, 4),
+        ];
+        assert_eq!(
+            RepairService::batch_shred_repairs(repairs),
+            vec![
+                ShredRepairType::Orphan(3),
+                ShredRepairType::ShredBatch(5, 3, 0b11),
+                ShredRepairType::ShredBatch(5, 67, 0b10001),
+                ShredRepairType::HighestShred(4, 2),
+                ShredRepairType::Shred(6, 1),
+            ]
+        );
+    }
+
     #[test]
     pub fn test_repair_range_highest() {
         let ledger_path = get_tmp_ledger_path_auto_delete!();
@@ -8,6 +8,10 @@ use {
             repair_service::{OutstandingShredRepairs, RepairStats, REPAIR_MS},
             request_response::RequestResponse,
             result::{Error, RepairVerifyError, Result},
+            serve_repair_qos::{
+                peek_request_sender, verify_request_sender, DroppedRequestStats, RequestClass,
+                ServeRepairQos,
+            },
         },
     },
     bincode::{serialize, Options},
@@ -32,10 +36,10 @@ use {
         data_budget::DataBudget,
         packet::{Packet, PacketBatch, PacketBatchRecycler},
     },
-    solana_runtime::bank_forks::BankForks,
+    solana_runtime::{bank::Bank, bank_forks::BankForks},
     solana_sdk::{
         clock::Slot,
-        genesis_config::ClusterType,
+        feature_set,
         hash::{Hash, HASH_BYTES},
         packet::PACKET_DATA_SIZE,
         pubkey::{Pubkey, PUBKEY_BYTES},
@@ -64,6 +68,8 @@ use {
 
 /// the number of slots to respond with when responding to `Orphan` requests
 pub const MAX_ORPHAN_REPAIR_RESPONSES: usize = 11;
+/// the number of consecutive shred indices covered by a `ShredBatch` request
+pub const MAX_SHRED_BATCH_REPAIR_RESPONSES: u64 = u64::BITS as u64;
 // Number of slots to cache their respective repair peers and sampling weights.
 pub(crate) const REPAIR_PEERS_CACHE_CAPACITY: usize = 128;
 // Limit cache entries ttl in order to avoid re-using outdated data.
@@ -99,6 +105,9 @@ pub enum ShredRepairType {
     HighestShred(Slot, u64),
     /// Requesting the missing shred at a particular index
     Shred(Slot, u64),
+    /// Requesting the missing shreds at `start_index + i` for each bit `i`
+    /// set in the bitmap
+    ShredBatch(Slot, /*start_index:*/ u64, /*bitmap:*/ u64),
 }
 
 impl ShredRepairType {
@@ -106,17 +115,26 @@ impl ShredRepairType {
         match self {
             ShredRepairType::Orphan(slot)
             | ShredRepairType::HighestShred(slot, _)
-            | ShredRepairType::Shred(slot, _) => *slot,
+            | ShredRepairType::Shred(slot, _)
+            | ShredRepairType::ShredBatch(slot, _, _) => *slot,
         }
     }
 }
 
+/// Shred indices requested by a `ShredBatch` request.
+pub(crate) fn shred_batch_indices(start_index: u64, bitmap: u64) -> impl Iterator<Item = u64> {
+    (0..MAX_SHRED_BATCH_REPAIR_RESPONSES)
+        .filter(move |i| bitmap & (1 << i) != 0)
+        .map(move |i| start_index.saturating_add(i))
+}
+
 impl RequestResponse for ShredRepairType {
     type Response = Shred;
     fn num_expected_responses(&self) -> u32 {
         match self {
             ShredRepairType::Orphan(_) => MAX_ORPHAN_REPAIR_RESPONSES as u32,
             ShredRepairType::Shred(_, _) | ShredRepairType::HighestShred(_, _) => 1,
+            ShredRepairType::ShredBatch(_, _, bitmap) => bitmap.count_ones(),
         }
     }
     fn verify_response(&self, response_shred: &Shred) -> bool {
@@ -128,6 +146,13 @@ impl RequestResponse for ShredRepairType {
             ShredRepairType::Shred(slot, index) => {
                 response_shred.slot() == *slot && response_shred.index() as u64 == *index
             }
+            ShredRepairType::ShredBatch(slot, start_index, bitmap) => {
+                let index = response_shred.index() as u64;
+                response_shred.slot() == *slot
+                    && index >= *start_index
+                    && index - start_index < MAX_SHRED_BATCH_REPAIR_RESPONSES
+                    && bitmap & (1 << (index - start_index)) != 0
+            }
         }
     }
 }
@@ -174,6 +199,7 @@ struct ServeRepairStats {
     processed: usize,
     window_index: usize,
     highest_window_index: usize,
+    window_index_batch: usize,
     orphan: usize,
     pong: usize,
     ancestor_hashes: usize,
@@ -190,6 +216,17 @@ struct ServeRepairStats {
     err_sig_verify: usize,
     err_unsigned: usize,
     err_id_mismatch: usize,
+    dropped_requests_staked: DroppedRequestStats,
+    dropped_requests_unstaked: DroppedRequestStats,
+}
+
+impl ServeRepairStats {
+    fn dropped_requests(&mut self, class: RequestClass) -> &mut DroppedRequestStats {
+        match class {
+            RequestClass::Staked => &mut self.dropped_requests_staked,
+            RequestClass::Unstaked => &mut self.dropped_requests_unstaked,
+        }
+    }
 }
 
 #[derive(Debug, AbiExample, Deserialize, Serialize)]
@@ -217,7 +254,7 @@ pub(crate) type Ping = ping_pong::Ping<[u8; REPAIR_PING_TOKEN_SIZE]>;
 
 /// Window protocol messages
 #[derive(Debug, AbiEnumVisitor, AbiExample, Deserialize, Serialize)]
-#[frozen_abi(digest = "3VzVe3kMrG6ijkVPyCGeJVA9hQjWcFEZbAQPc5Zizrjm")]
+#[frozen_abi(digest = "CcbD4TcGDBX3h8DKPLbH6EzGdTNmy7qbsEbUSzQoUeQu")]
 pub enum RepairProtocol {
     LegacyWindowIndex(LegacyContactInfo, Slot, u64),
     LegacyHighestWindowIndex(LegacyContactInfo, Slot, u64),
@@ -245,6 +282,14 @@ pub enum RepairProtocol {
         header: RepairRequestHeader,
         slot: Slot,
     },
+    // Only sent once the batch_repair_requests feature is active, since
+    // older nodes fail to deserialize it.
+    WindowIndexBatch {
+        header: RepairRequestHeader,
+        slot: Slot,
+        start_index: u64,
+        bitmap: u64,
+    },
 }
 
 const REPAIR_REQUEST_PONG_SERIALIZED_BYTES: usize = PUBKEY_BYTES + HASH_BYTES + SIGNATURE_BYTES;
@@ -281,6 +326,7 @@ impl RepairProtocol {
             Self::HighestWindowIndex { header, .. } => &header.sender,
             Self::Orphan { header, .. } => &header.sender,
             Self::AncestorHashes { header, .. } => &header.sender,
+            Self::WindowIndexBatch { header, .. } => &header.sender,
         }
     }
 
@@ -297,7 +343,8 @@ impl RepairProtocol {
             | Self::WindowIndex { .. }
             | Self::HighestWindowIndex { .. }
             | Self::Orphan { .. }
-            | Self::AncestorHashes { .. } => true,
+            | Self::AncestorHashes { .. }
+            | Self::WindowIndexBatch { .. } => true,
         }
     }
 
@@ -307,6 +354,7 @@ impl RepairProtocol {
             | RepairProtocol::HighestWindowIndex { .. }
             | RepairProtocol::AncestorHashes { .. } => 1,
             RepairProtocol::Orphan { .. } => MAX_ORPHAN_REPAIR_RESPONSES,
+            RepairProtocol::WindowIndexBatch { bitmap, .. } => bitmap.count_ones() as usize,
             RepairProtocol::Pong(_) => 0, // no response
             RepairProtocol::LegacyWindowIndex(_, _, _)
             | RepairProtocol::LegacyHighestWindowIndex(_, _, _)
@@ -385,6 +433,16 @@ struct RepairRequestWithMeta {
     response_sender: Option<OneShotSender<Vec<Vec<u8>>>>,
 }
 
+impl RepairRequestWithMeta {
+    fn class(&self) -> RequestClass {
+        if self.whitelisted || self.stake > 0 {
+            RequestClass::Staked
+        } else {
+            RequestClass::Unstaked
+        }
+    }
+}
+
 impl ServeRepair {
     pub fn new(
         cluster_info: Arc<ClusterInfo>,
@@ -467,6 +525,27 @@ impl ServeRepair {
                         "OrphanWithNonce",
                     )
                 }
+                RepairProtocol::WindowIndexBatch {
+                    header: RepairRequestHeader { nonce, .. },
+                    slot,
+                    start_index,
+                    bitmap,
+                } => {
+                    stats.window_index_batch += 1;
+                    let batch = Self::run_window_batch_request(
+                        recycler,
+                        from_addr,
+                        blockstore,
+                        *slot,
+                        *start_index,
+                        *bitmap,
+                        *nonce,
+                    );
+                    if batch.is_none() {
+                        stats.window_index_misses += 1;
+                    }
+                    (batch, "WindowIndexBatch")
+                }
                 RepairProtocol::AncestorHashes {
                     header: RepairRequestHeader { nonce, .. },
                     slot,
@@ -510,8 +589,11 @@ impl ServeRepair {
         }
     }
 
+    // signature_verified is true if the request's sender was already verified
+    // when the request was queued, in which case it is not verified again.
     fn decode_request(
         remote_request: RemoteRequest,
+        signature_verified: bool,
         epoch_staked_nodes: &Option<Arc<HashMap<Pubkey, u64>>>,
         whitelist: &HashSet<Pubkey>,
         my_id: &Pubkey,
@@ -524,7 +606,14 @@ impl ServeRepair {
         if !ContactInfo::is_valid_address(&from_addr, socket_addr_space) {
             return Err(Error::from(RepairVerifyError::Malformed));
         }
-        Self::verify_signed_packet(my_id, &remote_request.bytes, &request)?;
+        // A QUIC request was verified against the remote pubkey, so it still
+        // has to be verified if it claims to be from some other node.
+        let signature_verified = signature_verified
+            && remote_request
+                .remote_pubkey
+                .map(|remote_pubkey| &remote_pubkey == request.sender())
+                .unwrap_or(true);
+        Self::verify_signed_packet(my_id, &remote_request.bytes, &request, signature_verified)?;
         if let Some(remote_pubkey) = remote_request.remote_pubkey {
             if &remote_pubkey != request.sender() {
                 error!(
@@ -580,16 +669,17 @@ impl ServeRepair {
     }
 
     fn decode_requests(
-        requests: Vec<RemoteRequest>,
+        requests: Vec<(RemoteRequest, /*signature_verified:*/ bool)>,
         epoch_staked_nodes: &Option<Arc<HashMap<Pubkey, u64>>>,
         whitelist: &HashSet<Pubkey>,
         my_id: &Pubkey,
         socket_addr_space: &SocketAddrSpace,
         stats: &mut ServeRepairStats,
     ) -> Vec<RepairRequestWithMeta> {
-        let decode_request = |request| {
+        let decode_request = |(request, signature_verified)| {
             let result = Self::decode_request(
                 request,
+                signature_verified,
                 epoch_staked_nodes,
                 whitelist,
                 my_id,
@@ -616,6 +706,7 @@ impl ServeRepair {
     fn run_listen(
         &self,
         ping_cache: &mut PingCache,
+        qos: &mut ServeRepairQos,
         recycler: &PacketBatchRecycler,
         blockstore: &Blockstore,
         requests_receiver: &Receiver<RemoteRequest>,
@@ -626,77 +717,146 @@ impl ServeRepair {
         const TIMEOUT: Duration = Duration::from_secs(1);
         let mut requests = vec![requests_receiver.recv_timeout(TIMEOUT)?];
         const MAX_REQUESTS_PER_ITERATION: usize = 1024;
-        let mut total_requests = requests.len();
+        // When there are more requests than can be served in one iteration,
+        // unstaked requests are only served with whatever capacity is left
+        // over by staked requests, and at most this many.
+        const MAX_UNSTAKED_REQUESTS_PER_ITERATION: usize = MAX_REQUESTS_PER_ITERATION / 4;
 
         let socket_addr_space = *self.cluster_info.socket_addr_space();
         let root_bank = self.bank_forks.read().unwrap().root_bank();
         let epoch_staked_nodes = root_bank.epoch_staked_nodes(root_bank.epoch());
         let identity_keypair = self.cluster_info.keypair().clone();
         let my_id = identity_keypair.pubkey();
+        qos.update_staked_nodes(&epoch_staked_nodes);
 
-        let max_buffered_packets = if self.repair_whitelist.read().unwrap().len() > 0 {
+        let whitelist = self.repair_whitelist.read().unwrap();
+        let max_buffered_packets = if !whitelist.is_empty() {
             4 * MAX_REQUESTS_PER_ITERATION
         } else {
             2 * MAX_REQUESTS_PER_ITERATION
         };
+        let claims_staked = |request: &RemoteRequest| {
+            peek_request_sender(request)
+                .map(|sender| {
+                    whitelist.contains(&sender)
+                        || epoch_staked_nodes
+                            .as_ref()
+                            .and_then(|stakes| stakes.get(&sender))
+                            .map(|&stake| stake > 0)
+                            .unwrap_or_default()
+                })
+                .unwrap_or_default()
+        };
 
-        let mut dropped_requests = 0;
-        let mut well_formed_requests = discard_malformed_repair_requests(&mut requests, stats);
+        // Requests wait in a queue for their class until they are decoded,
+        // along with whether their signature was already verified. Requests
+        // claiming to be from staked nodes are verified before being queued,
+        // so that other nodes can't get their requests served first, but only
+        // if there is room left in the queue for them.
+        let mut total_requests = 0;
+        let mut staked_requests = Vec::new();
+        let mut unstaked_requests = Vec::new();
         loop {
-            let mut more: Vec<_> = requests_receiver.try_iter().collect();
-            if more.is_empty() {
-                break;
-            }
-            total_requests += more.len();
-            if well_formed_requests > max_buffered_packets {
-                // Already exceeded max. Don't waste time discarding
-                dropped_requests += more.len();
-                continue;
+            total_requests += requests.len();
+            discard_malformed_repair_requests(&mut requests, stats);
+            for request in requests {
+                if claims_staked(&request) {
+                    if staked_requests.len() >= max_buffered_packets {
+                        stats.dropped_requests_load_shed += 1;
+                        stats.dropped_requests_staked.load_shed += 1;
+                        continue;
+                    }
+                    if verify_request_sender(&request).is_none() {
+                        stats.err_sig_verify += 1;
+                        continue;
+                    }
+                    staked_requests.push((request, true));
+                } else if unstaked_requests.len() < max_buffered_packets / 2 {
+                    unstaked_requests.push((request, false));
+                } else {
+                    stats.dropped_requests_load_shed += 1;
+                    stats.dropped_requests_unstaked.load_shed += 1;
+                }
             }
-            let retained = discard_malformed_repair_requests(&mut more, stats);
-            well_formed_requests += retained;
-            if retained > 0 && well_formed_requests <= max_buffered_packets {
-                requests.extend(more);
-            } else {
-                dropped_requests += more.len();
+            requests = requests_receiver.try_iter().collect();
+            if requests.is_empty() {
+                break;
             }
         }
-
-        stats.dropped_requests_load_shed += dropped_requests;
         stats.total_requests += total_requests;
 
         let decode_start = Instant::now();
-        let mut decoded_requests = {
-            let whitelist = self.repair_whitelist.read().unwrap();
-            Self::decode_requests(
-                requests,
-                &epoch_staked_nodes,
-                &whitelist,
-                &my_id,
-                &socket_addr_space,
-                stats,
-            )
-        };
+        let decoded_requests = Self::decode_requests(
+            staked_requests
+                .into_iter()
+                .chain(unstaked_requests)
+                .collect(),
+            &epoch_staked_nodes,
+            &whitelist,
+            &my_id,
+            &socket_addr_space,
+            stats,
+        );
+        drop(whitelist);
         let whitelisted_request_count = decoded_requests.iter().filter(|r| r.whitelisted).count();
         stats.decode_time_us += decode_start.elapsed().as_micros() as u64;
         stats.whitelisted_requests += whitelisted_request_count.min(MAX_REQUESTS_PER_ITERATION);
 
-        if decoded_requests.len() > MAX_REQUESTS_PER_ITERATION {
-            stats.dropped_requests_low_stake += decoded_requests.len() - MAX_REQUESTS_PER_ITERATION;
-            decoded_requests.sort_unstable_by_key(|r| Reverse((r.whitelisted, r.stake)));
-            decoded_requests.truncate(MAX_REQUESTS_PER_ITERATION);
+        // Sort requests into queues by their verified sender, dropping the
+        // ones from nodes exceeding their requests per second.
+        let now = Instant::now();
+        let (mut staked_requests, mut unstaked_requests): (Vec<_>, Vec<_>) = decoded_requests
+            .into_iter()
+            .filter(|request| {
+                let admitted = request.whitelisted
+                    || qos.check(
+                        now,
+                        request.request.sender(),
+                        request.from_addr.ip(),
+                        request.stake,
+                    );
+                if !admitted {
+                    stats.dropped_requests(request.class()).rate_limited += 1;
+                }
+                admitted
+            })
+            .partition(|request| request.class() == RequestClass::Staked);
+
+        if staked_requests.len() > MAX_REQUESTS_PER_ITERATION {
+            let num_dropped = staked_requests.len() - MAX_REQUESTS_PER_ITERATION;
+            stats.dropped_requests_low_stake += num_dropped;
+            stats.dropped_requests_staked.queue_full += num_dropped;
+            staked_requests.sort_unstable_by_key(|r| Reverse((r.whitelisted, r.stake)));
+            staked_requests.truncate(MAX_REQUESTS_PER_ITERATION);
+        }
+        let max_unstaked_requests =
+            if staked_requests.len() + unstaked_requests.len() <= MAX_REQUESTS_PER_ITERATION {
+                unstaked_requests.len()
+            } else {
+                MAX_UNSTAKED_REQUESTS_PER_ITERATION
+                    .min(MAX_REQUESTS_PER_ITERATION - staked_requests.len())
+            };
+        if unstaked_requests.len() > max_unstaked_requests {
+            let num_dropped = unstaked_requests.len() - max_unstaked_requests;
+            stats.dropped_requests_low_stake += num_dropped;
+            stats.dropped_requests_unstaked.queue_full += num_dropped;
+            unstaked_requests.truncate(max_unstaked_requests);
         }
 
         let handle_requests_start = Instant::now();
-        self.handle_requests(
-            ping_cache,
-            recycler,
-            blockstore,
-            decoded_requests,
-            response_sender,
-            stats,
-            data_budget,
-        );
+        // Staked requests are handled first so that they get the outbound
+        // data budget ahead of unstaked requests.
+        for requests in [staked_requests, unstaked_requests] {
+            self.handle_requests(
+                ping_cache,
+                recycler,
+                blockstore,
+                requests,
+                response_sender,
+                stats,
+                data_budget,
+            );
+        }
         stats.handle_requests_time_us += handle_requests_start.elapsed().as_micros() as u64;
 
         Ok(())
@@ -760,6 +920,7 @@ impl ServeRepair {
                 stats.highest_window_index,
                 i64
             ),
+            ("window_index_batch", stats.window_index_batch, i64),
             ("orphan", stats.orphan, i64),
             (
                 "serve_repair-request-ancestor-hashes",
@@ -786,6 +947,22 @@ impl ServeRepair {
             ("err_unsigned", stats.err_unsigned, i64),
             ("err_id_mismatch", stats.err_id_mismatch, i64),
         );
+        for (class, dropped) in [
+            ("staked", &stats.dropped_requests_staked),
+            ("unstaked", &stats.dropped_requests_unstaked),
+        ] {
+            if dropped.is_empty() {
+                continue;
+            }
+            datapoint_info!(
+                "serve_repair-dropped_requests",
+                "class" => class,
+                ("load_shed", dropped.load_shed, i64),
+                ("rate_limited", dropped.rate_limited, i64),
+                ("queue_full", dropped.queue_full, i64),
+                ("outbound_bandwidth", dropped.outbound_bandwidth, i64),
+            );
+        }
 
         *stats = ServeRepairStats::default();
     }
@@ -816,10 +993,12 @@ impl ServeRepair {
             .spawn(move || {
                 let mut last_print = Instant::now();
                 let mut stats = ServeRepairStats::default();
+                let mut qos = ServeRepairQos::default();
                 let data_budget = DataBudget::default();
                 while !exit.load(Ordering::Relaxed) {
                     let result = self.run_listen(
                         &mut ping_cache,
+                        &mut qos,
                         &recycler,
                         &blockstore,
                         &requests_receiver,
@@ -844,7 +1023,14 @@ impl ServeRepair {
             .unwrap()
     }
 
-    fn verify_signed_packet(my_id: &Pubkey, bytes: &[u8], request: &RepairProtocol) -> Result<()> {
+    // The signature, and for pongs the whole pong, is not verified again if
+    // signature_verified is true.
+    fn verify_signed_packet(
+        my_id: &Pubkey,
+        bytes: &[u8],
+        request: &RepairProtocol,
+        signature_verified: bool,
+    ) -> Result<()> {
         match request {
             RepairProtocol::LegacyWindowIndex(_, _, _)
             | RepairProtocol::LegacyHighestWindowIndex(_, _, _)
@@ -856,14 +1042,15 @@ impl ServeRepair {
                 return Err(Error::from(RepairVerifyError::Unsigned));
             }
             RepairProtocol::Pong(pong) => {
-                if !pong.verify() {
+                if !signature_verified && !pong.verify() {
                     return Err(Error::from(RepairVerifyError::SigVerify));
                 }
             }
             RepairProtocol::WindowIndex { header, .. }
             | RepairProtocol::HighestWindowIndex { header, .. }
             | RepairProtocol::Orphan { header, .. }
-            | RepairProtocol::AncestorHashes { header, .. } => {
+            | RepairProtocol::AncestorHashes { header, .. }
+            | RepairProtocol::WindowIndexBatch { header, .. } => {
                 if &header.recipient != my_id {
                     return Err(Error::from(RepairVerifyError::IdMismatch));
                 }
@@ -871,6 +1058,9 @@ impl ServeRepair {
                 if u128::from(time_diff_ms) > SIGNED_REPAIR_TIME_WINDOW.as_millis() {
                     return Err(Error::from(RepairVerifyError::TimeSkew));
                 }
+                if signature_verified {
+                    return Ok(());
+                }
                 let Some(leading_buf) = bytes.get(..4) else {
                     debug_assert!(
                         false,
@@ -909,7 +1099,8 @@ impl ServeRepair {
             match request {
                 RepairProtocol::WindowIndex { .. }
                 | RepairProtocol::HighestWindowIndex { .. }
-                | RepairProtocol::Orphan { .. } => {
+                | RepairProtocol::Orphan { .. }
+                | RepairProtocol::WindowIndexBatch { .. } => {
                     let ping = RepairResponse::Ping(ping);
                     Packet::from_data(Some(from_addr), ping).ok()
                 }
@@ -953,16 +1144,18 @@ impl ServeRepair {
         let identity_keypair = self.cluster_info.keypair().clone();
         let mut pending_pings = Vec::default();
 
-        for RepairRequestWithMeta {
-            request,
-            from_addr,
-            stake,
-            whitelisted: _,
-            response_sender,
-        } in requests.into_iter()
-        {
+        for request in requests.into_iter() {
+            let class = request.class();
+            let RepairRequestWithMeta {
+                request,
+                from_addr,
+                stake,
+                whitelisted: _,
+                response_sender,
+            } = request;
             if !data_budget.check(request.max_response_bytes()) {
                 stats.dropped_requests_outbound_bandwidth += 1;
+                stats.dropped_requests(class).outbound_bandwidth += 1;
                 continue;
             }
             // Bypass ping/pong check for requests comming from QUIC endpoint.
@@ -995,6 +1188,7 @@ impl ServeRepair {
                 }
             } else {
                 stats.dropped_requests_outbound_bandwidth += 1;
+                stats.dropped_requests(class).outbound_bandwidth += 1;
                 stats.total_dropped_response_packets += num_response_packets;
             }
         }
@@ -1181,6 +1375,19 @@ impl ServeRepair {
                     slot: *slot,
                 }
             }
+            ShredRepairType::ShredBatch(slot, start_index, bitmap) => {
+                for shred_index in shred_batch_indices(*start_index, *bitmap) {
+                    repair_stats
+                        .shred_batch
+                        .update(repair_peer_id, *slot, shred_index);
+                }
+                RepairProtocol::WindowIndexBatch {
+                    header,
+                    slot: *slot,
+                    start_index: *start_index,
+                    bitmap: *bitmap,
+                }
+            }
         };
         Self::repair_proto_to_bytes(&request_proto, identity_keypair)
     }
@@ -1288,6 +1495,36 @@ impl ServeRepair {
         ))
     }
 
+    fn run_window_batch_request(
+        recycler: &PacketBatchRecycler,
+        from_addr: &SocketAddr,
+        blockstore: &Blockstore,
+        slot: Slot,
+        start_index: u64,
+        bitmap: u64,
+        nonce: Nonce,
+    ) -> Option<PacketBatch> {
+        // Respond with whichever of the requested shreds are available
+        let packets: Vec<_> = shred_batch_indices(start_index, bitmap)
+            .filter_map(|shred_index| {
+                repair_response::repair_response_packet(
+                    blockstore,
+                    slot,
+                    shred_index,
+                    from_addr,
+                    nonce,
+                )
+            })
+            .collect();
+        (!packets.is_empty()).then(|| {
+            PacketBatch::new_unpinned_with_recycler_data(
+                recycler,
+                "run_window_batch_request",
+                packets,
+            )
+        })
+    }
+
     fn run_highest_window_request(
         recycler: &PacketBatchRecycler,
         from_addr: &SocketAddr,
@@ -1379,9 +1616,18 @@ impl ServeRepair {
     }
 }
 
+// Repair requests are sent over UDP until the repair_over_quic feature is
+// active, so that nodes keep repairing from nodes which are not upgraded yet.
 #[inline]
-pub(crate) fn get_repair_protocol(_: ClusterType) -> Protocol {
-    Protocol::UDP
+pub(crate) fn get_repair_protocol(root_bank: &Bank) -> Protocol {
+    if root_bank
+        .feature_set
+        .is_active(&feature_set::repair_over_quic::id())
+    {
+        Protocol::QUIC
+    } else {
+        Protocol::UDP
+    }
 }
 
 fn deserialize_request<T>(request: &RemoteRequest) -> std::result::Result<T, bincode::Error>
@@ -1765,7 +2011,8 @@ mod tests {
             ServeRepair::verify_signed_packet(
                 &other_keypair.pubkey(),
                 packet.data(..).unwrap(),
-                &request
+                &request,
+                false,
             ),
             Ok(())
         );
@@ -1789,7 +2036,8 @@ mod tests {
             ServeRepair::verify_signed_packet(
                 &my_keypair.pubkey(),
                 packet.data(..).unwrap(),
-                &request
+                &request,
+                false,
             ),
             Err(Error::RepairVerify(RepairVerifyError::IdMismatch))
         );
@@ -1815,7 +2063,8 @@ mod tests {
             ServeRepair::verify_signed_packet(
                 &other_keypair.pubkey(),
                 packet.data(..).unwrap(),
-                &request
+                &request,
+                false,
             ),
             Err(Error::RepairVerify(RepairVerifyError::TimeSkew))
         );
@@ -1839,10 +2088,30 @@ mod tests {
             ServeRepair::verify_signed_packet(
                 &other_keypair.pubkey(),
                 packet.data(..).unwrap(),
-                &request
+                &request,
+                false,
             ),
             Err(Error::RepairVerify(RepairVerifyError::SigVerify))
         );
+        // The signature is not verified again if it already was.
+        assert_matches!(
+            ServeRepair::verify_signed_packet(
+                &other_keypair.pubkey(),
+                packet.data(..).unwrap(),
+                &request,
+                true,
+            ),
+            Ok(())
+        );
+        assert_matches!(
+            ServeRepair::verify_signed_packet(
+                &my_keypair.pubkey(),
+                packet.data(..).unwrap(),
+                &request,
+                true,
+            ),
+            Err(Error::RepairVerify(RepairVerifyError::IdMismatch))
+        );
     }
 
     #[test]
@@ -1959,6 +2228,54 @@ mod tests {
         assert_eq!(rv[0].slot(), slot);
     }
 
+    #[test]
+    fn test_run_window_batch_request() {
+        let recycler = PacketBatchRecycler::default();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(Blockstore::open(ledger_path.path()).unwrap());
+        let (slot, nonce) = (2, 9);
+        let shreds: Vec<_> = [1, 2, 4]
+            .into_iter()
+            .map(|index| Shred::new_from_data(slot, index, 1, &[], ShredFlags::empty(), 0, 2, 0))
+            .collect();
+        blockstore
+            .insert_shreds(shreds, None, false)
+            .expect("Expect successful ledger write");
+        // Shreds 1, 3 and 4 are requested, and shred 3 is missing.
+        let request = ShredRepairType::ShredBatch(slot, 1, 0b1101);
+        let rv = ServeRepair::run_window_batch_request(
+            &recycler,
+            &socketaddr_any!(),
+            &blockstore,
+            slot,
+            1,
+            0b1101,
+            nonce,
+        )
+        .expect("packets");
+        verify_responses(&request, rv.iter());
+        let indices: Vec<u32> = rv
+            .iter()
+            .map(|packet| {
+                assert_eq!(repair_response::nonce(packet).unwrap(), nonce);
+                let shred = Shred::new_from_serialized_shred(packet.data(..).unwrap().to_vec());
+                shred.unwrap().index()
+            })
+            .collect();
+        assert_eq!(indices, vec![1, 4]);
+        // None of the requested shreds are available.
+        assert!(ServeRepair::run_window_batch_request(
+            &recycler,
+            &socketaddr_any!(),
+            &blockstore,
+            slot,
+            8,
+            0b11,
+            nonce,
+        )
+        .is_none());
+    }
+
     fn new_test_cluster_info() -> ClusterInfo {
         let keypair = Arc::new(Keypair::new());
         let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), timestamp());
@@ -2404,7 +2721,8 @@ mod tests {
         match repair {
             ShredRepairType::Orphan(_)
             | ShredRepairType::HighestShred(_, _)
-            | ShredRepairType::Shred(_, _) => (),
+            | ShredRepairType::Shred(_, _)
+            | ShredRepairType::ShredBatch(_, _, _) => (),
         };
 
         let slot = 9;
@@ -2440,6 +2758,22 @@ mod tests {
         assert!(!request.verify_response(&shred));
         let shred = new_test_data_shred(slot + 1, index);
         assert!(!request.verify_response(&shred));
+
+        // ShredBatch
+        let request = ShredRepairType::ShredBatch(slot, index as u64, 0b1001);
+        assert_eq!(request.num_expected_responses(), 2);
+        let shred = new_test_data_shred(slot, index);
+        assert!(request.verify_response(&shred));
+        let shred = new_test_data_shred(slot, index + 3);
+        assert!(request.verify_response(&shred));
+        let shred = new_test_data_shred(slot, index + 1);
+        assert!(!request.verify_response(&shred));
+        let shred = new_test_data_shred(slot, index - 1);
+        assert!(!request.verify_response(&shred));
+        let shred = new_test_data_shred(slot, index + 64);
+        assert!(!request.verify_response(&shred));
+        let shred = new_test_data_shred(slot + 1, index);
+        assert!(!request.verify_response(&shred));
     }
 
     fn verify_responses<'a>(request: &ShredRepairType, packets: impl Iterator<Item = &'a Packet>) {
@@ -0,0 +1,410 @@
+//! Quality of service for repair requests served by `ServeRepair`, over both
+//! UDP and QUIC. Requests from staked (or whitelisted) nodes are queued
+//! separately from and served ahead of unstaked requests, and each node is
+//! limited to a stake-weighted number of requests per second so that a few
+//! nodes, e.g. unstaked RPC nodes catching up, can't take up all of the
+//! repair capacity. Unstaked requests are rate limited by source IP address,
+//! since unstaked nodes can send requests under any number of identities.
+
+use {
+    crate::repair::quic_endpoint::RemoteRequest,
+    lru::LruCache,
+    solana_gossip::ping_pong::Pong,
+    solana_sdk::{
+        pubkey::{Pubkey, PUBKEY_BYTES},
+        signature::{Signable, Signature, SIGNATURE_BYTES},
+    },
+    std::{collections::HashMap, net::IpAddr, sync::Arc, time::Instant},
+};
+
+// Number of nodes and addresses for which request rates are tracked.
+const MAX_TRACKED_NODES: usize = 8192;
+// Requests per second allowed from each IP address sending unstaked requests.
+const UNSTAKED_REQUESTS_PER_SECOND: f64 = 200.0;
+// Requests per second allowed from each staked node, regardless of stake.
+const MIN_STAKED_REQUESTS_PER_SECOND: f64 = 1_000.0;
+// Requests per second additionally shared among staked nodes in proportion
+// to their stake.
+const STAKE_WEIGHTED_REQUESTS_PER_SECOND: f64 = 10_000.0;
+// Bincode enum discriminants of RepairProtocol. Requests from
+// WINDOW_INDEX_DISCRIMINANT to WINDOW_INDEX_BATCH_DISCRIMINANT start with a
+// RepairRequestHeader.
+const PONG_DISCRIMINANT: u32 = 7;
+const WINDOW_INDEX_DISCRIMINANT: u32 = 8;
+const WINDOW_INDEX_BATCH_DISCRIMINANT: u32 = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RequestClass {
+    // WindowIndex, HighestWindowIndex, Orphan, AncestorHashes and
+    // WindowIndexBatch requests, and pongs, from staked or whitelisted nodes.
+    Staked,
+    Unstaked,
+}
+
+#[derive(Default)]
+pub(crate) struct DroppedRequestStats {
+    // Dropped before being decoded because the class's queue was full.
+    pub(crate) load_shed: usize,
+    // Dropped because the node exceeded its requests per second.
+    pub(crate) rate_limited: usize,
+    // Dropped after being decoded because there were more than can be served
+    // in one iteration.
+    pub(crate) queue_full: usize,
+    // Dropped because of the outbound data budget.
+    pub(crate) outbound_bandwidth: usize,
+}
+
+impl DroppedRequestStats {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.load_shed == 0
+            && self.rate_limited == 0
+            && self.queue_full == 0
+            && self.outbound_bandwidth == 0
+    }
+}
+
+// Token bucket holding at most one second worth of requests.
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn take(&mut self, now: Instant, requests_per_second: f64) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * requests_per_second).min(requests_per_second);
+        self.last_refill = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+// What request rates are tracked by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum RateLimitKey {
+    // Staked nodes, whose identity is verified before rate limiting.
+    Node(Pubkey),
+    // Unstaked nodes, by the IP address the request is from.
+    Address(IpAddr),
+}
+
+pub(crate) struct ServeRepairQos {
+    buckets: LruCache<RateLimitKey, TokenBucket>,
+    // Staked nodes for which total_stake was computed.
+    staked_nodes: Option<Arc<HashMap<Pubkey, u64>>>,
+    total_stake: u64,
+}
+
+impl Default for ServeRepairQos {
+    fn default() -> Self {
+        Self {
+            buckets: LruCache::new(MAX_TRACKED_NODES),
+            staked_nodes: None,
+            total_stake: 0,
+        }
+    }
+}
+
+impl ServeRepairQos {
+    pub(crate) fn update_staked_nodes(&mut self, staked_nodes: &Option<Arc<HashMap<Pubkey, u64>>>) {
+        let unchanged = match (&self.staked_nodes, staked_nodes) {
+            (None, None) => true,
+            (Some(cached), Some(staked_nodes)) => Arc::ptr_eq(cached, staked_nodes),
+            _ => false,
+        };
+        if !unchanged {
+            self.total_stake = staked_nodes
+                .iter()
+                .flat_map(|staked_nodes| staked_nodes.values())
+                .sum();
+            self.staked_nodes = staked_nodes.clone();
+        }
+    }
+
+    fn requests_per_second(&self, stake: u64) -> f64 {
+        if stake == 0 || self.total_stake == 0 {
+            return UNSTAKED_REQUESTS_PER_SECOND;
+        }
+        MIN_STAKED_REQUESTS_PER_SECOND
+            + STAKE_WEIGHTED_REQUESTS_PER_SECOND * stake as f64 / self.total_stake as f64
+    }
+
+    /// Returns true if the node, or for unstaked nodes the IP address the
+    /// request is from, has not exceeded its requests per second, in which
+    /// case one request is accounted for.
+    pub(crate) fn check(&mut self, now: Instant, node: &Pubkey, addr: IpAddr, stake: u64) -> bool {
+        let requests_per_second = self.requests_per_second(stake);
+        let key = if stake == 0 {
+            RateLimitKey::Address(addr)
+        } else {
+            RateLimitKey::Node(*node)
+        };
+        match self.buckets.get_mut(&key) {
+            Some(bucket) => bucket.take(now, requests_per_second),
+            None => {
+                let mut bucket = TokenBucket {
+                    tokens: requests_per_second,
+                    last_refill: now,
+                };
+                let out = bucket.take(now, requests_per_second);
+                self.buckets.put(key, bucket);
+                out
+            }
+        }
+    }
+}
+
+fn peek_discriminant(bytes: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?))
+}
+
+/// Returns the node which the request claims to be from, without decoding or
+/// verifying the request. QUIC requests use the authenticated remote pubkey.
+pub(crate) fn peek_request_sender(request: &RemoteRequest) -> Option<Pubkey> {
+    if let Some(remote_pubkey) = request.remote_pubkey {
+        return Some(remote_pubkey);
+    }
+    let offset = match peek_discriminant(&request.bytes)? {
+        // Pong starts with the pubkey of the node it is from.
+        PONG_DISCRIMINANT => 4,
+        // RepairRequestHeader starts with the signature and then the sender.
+        WINDOW_INDEX_DISCRIMINANT..=WINDOW_INDEX_BATCH_DISCRIMINANT => 4 + SIGNATURE_BYTES,
+        _ => return None,
+    };
+    let bytes = request.bytes.get(offset..offset + PUBKEY_BYTES)?;
+    Pubkey::try_from(bytes).ok()
+}
+
+/// Returns the node which the request is from if its signature verifies,
+/// without decoding the rest of the request. Used to pick the queue a request
+/// waits in until it is decoded, so that nodes can't claim to be staked. QUIC
+/// requests are from the authenticated remote pubkey and are not verified.
+pub(crate) fn verify_request_sender(request: &RemoteRequest) -> Option<Pubkey> {
+    let sender = peek_request_sender(request)?;
+    if request.remote_pubkey.is_some() {
+        return Some(sender);
+    }
+    let bytes = &request.bytes;
+    let verified = match peek_discriminant(bytes)? {
+        PONG_DISCRIMINANT => bincode::deserialize::<Pong>(&bytes[4..])
+            .map(|pong| pong.verify())
+            .unwrap_or_default(),
+        WINDOW_INDEX_DISCRIMINANT..=WINDOW_INDEX_BATCH_DISCRIMINANT => {
+            let signature = bytes.get(4..4 + SIGNATURE_BYTES)?;
+            let signature = Signature::try_from(signature).ok()?;
+            // The signature is over the request without the signature.
+            let signed_data = [&bytes[..4], &bytes[4 + SIGNATURE_BYTES..]].concat();
+            signature.verify(sender.as_ref(), &signed_data)
+        }
+        _ => false,
+    };
+    verified.then_some(sender)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::repair::serve_repair::{RepairProtocol, RepairRequestHeader, ServeRepair},
+        solana_gossip::{contact_info::LegacyContactInfo, ping_pong::Ping},
+        solana_sdk::{signature::Keypair, signer::Signer, timing::timestamp},
+        std::{
+            net::{IpAddr, Ipv4Addr, SocketAddr},
+            time::Duration,
+        },
+    };
+
+    fn new_remote_request(bytes: Vec<u8>) -> RemoteRequest {
+        RemoteRequest {
+            remote_pubkey: None,
+            remote_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8000),
+            bytes,
+            response_sender: None,
+        }
+    }
+
+    #[test]
+    fn test_peek_request_sender() {
+        let keypair = Keypair::new();
+        let header =
+            RepairRequestHeader::new(keypair.pubkey(), Pubkey::new_unique(), timestamp(), 7);
+        let request = RepairProtocol::Orphan { header, slot: 13 };
+        let bytes = ServeRepair::repair_proto_to_bytes(&request, &keypair).unwrap();
+        assert_eq!(
+            peek_request_sender(&new_remote_request(bytes)),
+            Some(keypair.pubkey())
+        );
+        let ping = Ping::new([5u8; 32], &Keypair::new()).unwrap();
+        let pong = RepairProtocol::Pong(Pong::new(&ping, &keypair).unwrap());
+        let bytes = bincode::serialize(&pong).unwrap();
+        assert_eq!(
+            peek_request_sender(&new_remote_request(bytes.clone())),
+            Some(keypair.pubkey())
+        );
+        assert_eq!(
+            peek_request_sender(&new_remote_request(bytes[..20].to_vec())),
+            None
+        );
+        let mut request = new_remote_request(vec![0u8; 128]);
+        assert_eq!(peek_request_sender(&request), None);
+        request.remote_pubkey = Some(keypair.pubkey());
+        assert_eq!(peek_request_sender(&request), Some(keypair.pubkey()));
+    }
+
+    #[test]
+    fn test_repair_protocol_discriminants() {
+        let discriminant =
+            |request: &RepairProtocol| peek_discriminant(&bincode::serialize(request).unwrap());
+        let keypair = Keypair::new();
+        let header =
+            || RepairRequestHeader::new(keypair.pubkey(), Pubkey::new_unique(), timestamp(), 7);
+        let ping = Ping::new([5u8; 32], &Keypair::new()).unwrap();
+        let pong = RepairProtocol::Pong(Pong::new(&ping, &keypair).unwrap());
+        assert_eq!(discriminant(&pong), Some(PONG_DISCRIMINANT));
+        // All requests starting with a RepairRequestHeader, and only those.
+        let requests = [
+            RepairProtocol::WindowIndex {
+                header: header(),
+                slot: 13,
+                shred_index: 5,
+            },
+            RepairProtocol::HighestWindowIndex {
+                header: header(),
+                slot: 13,
+                shred_index: 5,
+            },
+            RepairProtocol::Orphan {
+                header: header(),
+                slot: 13,
+            },
+            RepairProtocol::AncestorHashes {
+                header: header(),
+                slot: 13,
+            },
+            RepairProtocol::WindowIndexBatch {
+                header: header(),
+                slot: 13,
+                start_index: 5,
+                bitmap: 0b101,
+            },
+        ];
+        assert_eq!(
+            requests.iter().map(discriminant).collect::<Vec<_>>(),
+            (WINDOW_INDEX_DISCRIMINANT..=WINDOW_INDEX_BATCH_DISCRIMINANT)
+                .map(Some)
+                .collect::<Vec<_>>()
+        );
+        // The signature and then the sender follow the discriminant.
+        for request in &requests {
+            let bytes = ServeRepair::repair_proto_to_bytes(request, &keypair).unwrap();
+            assert_eq!(
+                bytes[4 + SIGNATURE_BYTES..4 + SIGNATURE_BYTES + PUBKEY_BYTES],
+                keypair.pubkey().to_bytes()
+            );
+            assert_eq!(
+                verify_request_sender(&new_remote_request(bytes)),
+                Some(keypair.pubkey())
+            );
+        }
+        let contact_info = LegacyContactInfo::default();
+        for request in [
+            RepairProtocol::LegacyWindowIndex(contact_info.clone(), 13, 5),
+            RepairProtocol::LegacyHighestWindowIndex(contact_info.clone(), 13, 5),
+            RepairProtocol::LegacyOrphan(contact_info.clone(), 13),
+            RepairProtocol::LegacyWindowIndexWithNonce(contact_info.clone(), 13, 5, 7),
+            RepairProtocol::LegacyHighestWindowIndexWithNonce(contact_info.clone(), 13, 5, 7),
+            RepairProtocol::LegacyOrphanWithNonce(contact_info.clone(), 13, 7),
+            RepairProtocol::LegacyAncestorHashes(contact_info, 13, 7),
+        ] {
+            assert!(discriminant(&request).unwrap() < PONG_DISCRIMINANT);
+        }
+    }
+
+    #[test]
+    fn test_verify_request_sender() {
+        let keypair = Keypair::new();
+        let header =
+            RepairRequestHeader::new(keypair.pubkey(), Pubkey::new_unique(), timestamp(), 7);
+        let request = RepairProtocol::Orphan { header, slot: 13 };
+        let bytes = ServeRepair::repair_proto_to_bytes(&request, &keypair).unwrap();
+        assert_eq!(
+            verify_request_sender(&new_remote_request(bytes.clone())),
+            Some(keypair.pubkey())
+        );
+        // Requests signed by another node, or modified after being signed,
+        // don't verify.
+        let other_bytes = ServeRepair::repair_proto_to_bytes(&request, &Keypair::new()).unwrap();
+        assert_eq!(
+            peek_request_sender(&new_remote_request(other_bytes.clone())),
+            Some(keypair.pubkey())
+        );
+        assert_eq!(
+            verify_request_sender(&new_remote_request(other_bytes)),
+            None
+        );
+        let mut modified_bytes = bytes;
+        *modified_bytes.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            verify_request_sender(&new_remote_request(modified_bytes)),
+            None
+        );
+
+        let ping = Ping::new([5u8; 32], &Keypair::new()).unwrap();
+        let pong = RepairProtocol::Pong(Pong::new(&ping, &keypair).unwrap());
+        let mut bytes = bincode::serialize(&pong).unwrap();
+        assert_eq!(
+            verify_request_sender(&new_remote_request(bytes.clone())),
+            Some(keypair.pubkey())
+        );
+        *bytes.last_mut().unwrap() ^= 1;
+        assert_eq!(verify_request_sender(&new_remote_request(bytes)), None);
+
+        // QUIC requests are from the authenticated remote pubkey.
+        let mut request = new_remote_request(vec![0u8; 128]);
+        assert_eq!(verify_request_sender(&request), None);
+        request.remote_pubkey = Some(keypair.pubkey());
+        assert_eq!(verify_request_sender(&request), Some(keypair.pubkey()));
+    }
+
+    #[test]
+    fn test_serve_repair_qos() {
+        let staked_node = Pubkey::new_unique();
+        let unstaked_node = Pubkey::new_unique();
+        let staked_nodes = Some(Arc::new(HashMap::from([
+            (staked_node, 100),
+            (Pubkey::new_unique(), 900),
+        ])));
+        let mut qos = ServeRepairQos::default();
+        qos.update_staked_nodes(&staked_nodes);
+        assert_eq!(qos.total_stake, 1000);
+        assert_eq!(qos.requests_per_second(0), UNSTAKED_REQUESTS_PER_SECOND);
+        assert_eq!(
+            qos.requests_per_second(100),
+            MIN_STAKED_REQUESTS_PER_SECOND + STAKE_WEIGHTED_REQUESTS_PER_SECOND / 10.0
+        );
+        let now = Instant::now();
+        let addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let other_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        let count = |qos: &mut ServeRepairQos, now, node, addr, stake| {
+            (0..5_000)
+                .filter(|_| qos.check(now, node, addr, stake))
+                .count()
+        };
+        assert_eq!(count(&mut qos, now, &unstaked_node, addr, 0), 200);
+        // Unstaked requests are limited by address, regardless of the sender.
+        assert_eq!(count(&mut qos, now, &Pubkey::new_unique(), addr, 0), 0);
+        assert_eq!(count(&mut qos, now, &unstaked_node, other_addr, 0), 200);
+        // Staked requests are limited by node, regardless of the address.
+        assert_eq!(count(&mut qos, now, &staked_node, addr, 100), 2_000);
+        assert_eq!(count(&mut qos, now, &staked_node, other_addr, 100), 0);
+        // Buckets refill over time, up to one second worth of requests.
+        let now = now + Duration::from_millis(500);
+        assert_eq!(count(&mut qos, now, &unstaked_node, addr, 0), 100);
+        let now = now + Duration::from_secs(10);
+        assert_eq!(count(&mut qos, now, &unstaked_node, addr, 0), 200);
+    }
+}
@@ -14,6 +14,8 @@ use {
     },
     solana_perf::{cuda_runtime::PinnedVec, packet::PacketBatch, recycler::Recycler, sigverify},
     solana_sdk::{packet::Packet, saturating_add_assign},
+    solana_streamer::quic_qos::PeerPacketValidity,
+    std::sync::Arc,
 };
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, AbiExample)]
@@ -60,6 +62,7 @@ pub struct TransactionSigVerifier {
     recycler: Recycler<TxOffset>,
     recycler_out: Recycler<PinnedVec<u8>>,
     reject_non_vote: bool,
+    peer_packet_validity: Option<Arc<PeerPacketValidity>>,
 }
 
 impl TransactionSigVerifier {
@@ -77,8 +80,20 @@ impl TransactionSigVerifier {
             recycler: Recycler::warmed(50, 4096),
             recycler_out: Recycler::warmed(50, 4096),
             reject_non_vote: false,
+            peer_packet_validity: None,
         }
     }
+
+    /// Reports whether each verified packet passed signature verification to
+    /// `peer_packet_validity`.
+    pub fn new_with_peer_packet_validity(
+        packet_sender: BankingPacketSender,
+        peer_packet_validity: Option<Arc<PeerPacketValidity>>,
+    ) -> Self {
+        let mut new_self = Self::new(packet_sender);
+        new_self.peer_packet_validity = peer_packet_validity;
+        new_self
+    }
 }
 
 impl SigVerifier for TransactionSigVerifier {
@@ -138,6 +153,14 @@ impl SigVerifier for TransactionSigVerifier {
         mut batches: Vec<PacketBatch>,
         valid_packets: usize,
     ) -> Vec<PacketBatch> {
+        // Packets which were not already discarded before verification.
+        let verified: Option<Vec<bool>> = self.peer_packet_validity.as_ref().map(|_| {
+            batches
+                .iter()
+                .flatten()
+                .map(|packet| !packet.meta().discard())
+                .collect()
+        });
         sigverify::ed25519_verify(
             &mut batches,
             &self.recycler,
@@ -145,6 +168,17 @@ impl SigVerifier for TransactionSigVerifier {
             self.reject_non_vote,
             valid_packets,
         );
+        if let (Some(peer_packet_validity), Some(verified)) = (&self.peer_packet_validity, verified)
+        {
+            peer_packet_validity.record(
+                batches
+                    .iter()
+                    .flatten()
+                    .zip(verified)
+                    .filter(|(packet, verified)| *verified && !packet.meta().forwarded())
+                    .map(|(packet, _)| (packet.meta().socket_addr(), !packet.meta().discard())),
+            );
+        }
         batches
     }
 }
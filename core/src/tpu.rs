@@ -15,7 +15,7 @@ use {
         sigverify_stage::SigVerifyStage,
         staked_nodes_updater_service::StakedNodesUpdaterService,
         tpu_entry_notifier::TpuEntryNotifier,
-        validator::{BlockProductionMethod, GeneratorConfig},
+        validator::{BlockProductionMethod, GeneratorConfig, TpuQosPolicy},
     },
     bytes::Bytes,
     crossbeam_channel::{unbounded, Receiver},
@@ -34,13 +34,14 @@ use {
     solana_sdk::{clock::Slot, pubkey::Pubkey, signature::Keypair},
     solana_streamer::{
         nonblocking::quic::DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
-        quic::{spawn_server, MAX_STAKED_CONNECTIONS, MAX_UNSTAKED_CONNECTIONS},
+        quic::{spawn_server, QuicServerOptions, MAX_STAKED_CONNECTIONS, MAX_UNSTAKED_CONNECTIONS},
+        quic_qos::{PacketValidityQos, PeerPacketValidity, QosPolicy, StakeWeightedQos},
         streamer::StakedNodes,
     },
     solana_turbine::broadcast_stage::{BroadcastStage, BroadcastStageType},
     solana_vote::vote_sender_types::{ReplayVoteReceiver, ReplayVoteSender},
     std::{
-        collections::HashMap,
+        collections::{HashMap, HashSet},
         net::{SocketAddr, UdpSocket},
         sync::{atomic::AtomicBool, Arc, RwLock},
         thread,
@@ -110,6 +111,9 @@ impl Tpu {
         tpu_enable_udp: bool,
         prioritization_fee_cache: &Arc<PrioritizationFeeCache>,
         block_production_method: BlockProductionMethod,
+        tpu_qos_policy: TpuQosPolicy,
+        tpu_qos_allowed_peers: HashSet<Pubkey>,
+        tpu_quic_server_options: QuicServerOptions,
         _generator_config: Option<GeneratorConfig>, /* vestigial code for replay invalidator */
     ) -> Self {
         let TpuSockets {
@@ -148,6 +152,19 @@ impl Tpu {
 
         let (non_vote_sender, non_vote_receiver) = banking_tracer.create_channel_non_vote();
 
+        // Validity of the packets from each peer is fed back from sigverify and
+        // banking into the QoS policy of the TPU QUIC server.
+        let peer_packet_validity = matches!(tpu_qos_policy, TpuQosPolicy::PacketValidity)
+            .then(Arc::<PeerPacketValidity>::default);
+        let tpu_qos_policy: Arc<dyn QosPolicy> = match &peer_packet_validity {
+            None => Arc::<StakeWeightedQos>::default(),
+            Some(peer_packet_validity) => Arc::new(PacketValidityQos::new(
+                StakeWeightedQos::default(),
+                tpu_qos_allowed_peers,
+                peer_packet_validity.clone(),
+            )),
+        };
+
         let (_, tpu_quic_t) = spawn_server(
             "quic_streamer_tpu",
             transactions_quic_sockets,
@@ -161,10 +178,10 @@ impl Tpu {
             exit.clone(),
             MAX_QUIC_CONNECTIONS_PER_PEER,
             staked_nodes.clone(),
-            MAX_STAKED_CONNECTIONS,
-            MAX_UNSTAKED_CONNECTIONS,
+            tpu_qos_policy,
             DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
             tpu_coalesce,
+            tpu_quic_server_options,
         )
         .unwrap();
 
@@ -181,15 +198,21 @@ impl Tpu {
             exit.clone(),
             MAX_QUIC_CONNECTIONS_PER_PEER,
             staked_nodes.clone(),
-            MAX_STAKED_CONNECTIONS.saturating_add(MAX_UNSTAKED_CONNECTIONS),
-            0, // Prevent unstaked nodes from forwarding transactions
+            Arc::new(StakeWeightedQos::new(
+                MAX_STAKED_CONNECTIONS.saturating_add(MAX_UNSTAKED_CONNECTIONS),
+                0, // Prevent unstaked nodes from forwarding transactions
+            )),
             DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
             tpu_coalesce,
+            tpu_quic_server_options,
         )
         .unwrap();
 
         let sigverify_stage = {
-            let verifier = TransactionSigVerifier::new(non_vote_sender);
+            let verifier = TransactionSigVerifier::new_with_peer_packet_validity(
+                non_vote_sender,
+                peer_packet_validity.clone(),
+            );
             SigVerifyStage::new(packet_receiver, verifier, "tpu-verifier")
         };
 
@@ -231,6 +254,7 @@ impl Tpu {
             connection_cache.clone(),
             bank_forks.clone(),
             prioritization_fee_cache,
+            peer_packet_validity,
         );
 
         let (entry_receiver, tpu_entry_notifier) =
@@ -45,7 +45,7 @@ use {
         commitment::BlockCommitmentCache, prioritization_fee_cache::PrioritizationFeeCache,
     },
     solana_sdk::{clock::Slot, pubkey::Pubkey, signature::Keypair},
-    solana_turbine::retransmit_stage::RetransmitStage,
+    solana_turbine::{quic_endpoint::TurbinePeerStats, retransmit_stage::RetransmitStage},
     solana_vote::vote_sender_types::ReplayVoteSender,
     std::{
         collections::HashSet,
@@ -137,6 +137,7 @@ impl Tvu {
         banking_tracer: Arc<BankingTracer>,
         turbine_quic_endpoint_sender: AsyncSender<(SocketAddr, Bytes)>,
         turbine_quic_endpoint_receiver: Receiver<(Pubkey, SocketAddr, Bytes)>,
+        turbine_quic_peer_stats: Arc<TurbinePeerStats>,
         repair_quic_endpoint_sender: AsyncSender<LocalRequest>,
     ) -> Result<Self, String> {
         let TvuSockets {
@@ -183,6 +184,7 @@ impl Tvu {
             cluster_info.clone(),
             Arc::new(retransmit_sockets),
             turbine_quic_endpoint_sender,
+            turbine_quic_peer_stats,
             retransmit_receiver,
             max_slots.clone(),
             Some(rpc_subscriptions.clone()),
@@ -490,6 +492,7 @@ pub mod tests {
             BankingTracer::new_disabled(),
             turbine_quic_endpoint_sender,
             turbine_quic_endpoint_receiver,
+            Arc::<TurbinePeerStats>::default(),
             repair_quic_endpoint_sender,
         )
         .expect("assume success");
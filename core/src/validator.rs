@@ -46,7 +46,9 @@ use {
             ClusterInfo, Node, DEFAULT_CONTACT_DEBUG_INTERVAL_MILLIS,
             DEFAULT_CONTACT_SAVE_INTERVAL_MILLIS,
         },
+        crds_data_budget::CrdsDataBudgetConfig,
         crds_gossip_pull::CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS,
+        gossip_capture::GossipCaptureConfig,
         gossip_service::GossipService,
         legacy_contact_info::LegacyContactInfo as ContactInfo,
     },
@@ -62,6 +64,9 @@ use {
         entry_notifier_service::{EntryNotifierSender, EntryNotifierService},
         leader_schedule::FixedSchedule,
         leader_schedule_cache::LeaderScheduleCache,
+        transaction_history_purge_service::{
+            TransactionHistoryPurgeConfig, TransactionHistoryPurgeService,
+        },
         use_snapshot_archives_at_startup::UseSnapshotArchivesAtStartup,
     },
     solana_measure::measure::Measure,
@@ -116,8 +121,8 @@ use {
         timing::timestamp,
     },
     solana_send_transaction_service::send_transaction_service,
-    solana_streamer::{socket::SocketAddrSpace, streamer::StakedNodes},
-    solana_turbine::{self, broadcast_stage::BroadcastStageType},
+    solana_streamer::{quic::QuicServerOptions, socket::SocketAddrSpace, streamer::StakedNodes},
+    solana_turbine::{self, broadcast_stage::BroadcastStageType, quic_endpoint::TurbinePeerStats},
     solana_vote_program::vote_state,
     solana_wen_restart::wen_restart::wait_for_wen_restart,
     std::{
@@ -188,6 +193,31 @@ impl BlockProductionMethod {
     }
 }
 
+#[derive(Clone, EnumString, EnumVariantNames, Default, IntoStaticStr, Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TpuQosPolicy {
+    #[default]
+    StakeWeighted,
+    PacketValidity,
+}
+
+impl TpuQosPolicy {
+    pub const fn cli_names() -> &'static [&'static str] {
+        Self::VARIANTS
+    }
+
+    pub fn cli_message() -> &'static str {
+        lazy_static! {
+            static ref MESSAGE: String = format!(
+                "Switch how the TPU QUIC server prioritizes connections from peers [default: {}]",
+                TpuQosPolicy::default()
+            );
+        };
+
+        &MESSAGE
+    }
+}
+
 /// Configuration for the block generator invalidator for replay.
 #[derive(Clone, Debug)]
 pub struct GeneratorConfig {
@@ -211,6 +241,9 @@ pub struct ValidatorConfig {
     pub pubsub_config: PubSubConfig,
     pub snapshot_config: SnapshotConfig,
     pub max_ledger_shreds: Option<u64>,
+    /// Drops the transaction history of selected transactions once they fall
+    /// out of the retention window
+    pub transaction_history_purge: Option<TransactionHistoryPurgeConfig>,
     pub broadcast_stage_type: BroadcastStageType,
     pub turbine_disabled: Arc<AtomicBool>,
     pub enforce_ulimit_nofile: bool,
@@ -233,6 +266,8 @@ pub struct ValidatorConfig {
     pub debug_keys: Option<Arc<HashSet<Pubkey>>>,
     pub contact_debug_interval: u64,
     pub contact_save_interval: u64,
+    pub gossip_capture: Option<GossipCaptureConfig>,
+    pub gossip_crds_data_budget: CrdsDataBudgetConfig,
     pub send_transaction_service_config: send_transaction_service::Config,
     pub no_poh_speed_test: bool,
     pub no_os_memory_stats_reporting: bool,
@@ -260,6 +295,10 @@ pub struct ValidatorConfig {
     pub banking_trace_dir_byte_limit: banking_trace::DirByteLimit,
     pub block_verification_method: BlockVerificationMethod,
     pub block_production_method: BlockProductionMethod,
+    pub tpu_qos_policy: TpuQosPolicy,
+    /// Peers, e.g. known RPC partners, exempt from the packet-validity TPU
+    /// QoS policy and served as if they had the stake of the most staked node
+    pub tpu_qos_allowed_peers: HashSet<Pubkey>,
     pub generator_config: Option<GeneratorConfig>,
     pub use_snapshot_archives_at_startup: UseSnapshotArchivesAtStartup,
     pub wen_restart_proto_path: Option<PathBuf>,
@@ -274,6 +313,7 @@ impl Default for ValidatorConfig {
             expected_shred_version: None,
             voting_disabled: false,
             max_ledger_shreds: None,
+            transaction_history_purge: None,
             account_paths: Vec::new(),
             account_snapshot_paths: Vec::new(),
             account_shrink_paths: None,
@@ -302,6 +342,8 @@ impl Default for ValidatorConfig {
             debug_keys: None,
             contact_debug_interval: DEFAULT_CONTACT_DEBUG_INTERVAL_MILLIS,
             contact_save_interval: DEFAULT_CONTACT_SAVE_INTERVAL_MILLIS,
+            gossip_capture: None,
+            gossip_crds_data_budget: CrdsDataBudgetConfig::default(),
             send_transaction_service_config: send_transaction_service::Config::default(),
             no_poh_speed_test: true,
             no_os_memory_stats_reporting: true,
@@ -329,6 +371,9 @@ impl Default for ValidatorConfig {
             banking_trace_dir_byte_limit: 0,
             block_verification_method: BlockVerificationMethod::default(),
             block_production_method: BlockProductionMethod::default(),
+            tpu_qos_policy: TpuQosPolicy::default(),
+            tpu_qos_allowed_peers: HashSet::default(),
+            tpu_quic_server_options: QuicServerOptions::default(),
             generator_config: None,
             use_snapshot_archives_at_startup: UseSnapshotArchivesAtStartup::default(),
             wen_restart_proto_path: None,
@@ -467,6 +512,7 @@ pub struct Validator {
     pub blockstore: Arc<Blockstore>,
     geyser_plugin_service: Option<GeyserPluginService>,
     blockstore_metric_report_service: BlockstoreMetricReportService,
+    transaction_history_purge_service: Option<TransactionHistoryPurgeService>,
     accounts_background_service: AccountsBackgroundService,
     accounts_hash_verifier: AccountsHashVerifier,
     turbine_quic_endpoint: Endpoint,
@@ -737,6 +783,10 @@ impl Validator {
             socket_addr_space,
         );
         cluster_info.set_contact_debug_interval(config.contact_debug_interval);
+        if let Some(gossip_capture) = &config.gossip_capture {
+            cluster_info.set_gossip_capture(gossip_capture.clone());
+        }
+        cluster_info.set_crds_data_budget(&config.gossip_crds_data_budget);
         cluster_info.set_entrypoints(cluster_entrypoints);
         cluster_info.restore_contact_info(ledger_path, config.contact_save_interval);
         let cluster_info = Arc::new(cluster_info);
@@ -1105,6 +1155,17 @@ impl Validator {
 
         let blockstore_metric_report_service =
             BlockstoreMetricReportService::new(blockstore.clone(), exit.clone());
+        let transaction_history_purge_service =
+            config
+                .transaction_history_purge
+                .clone()
+                .map(|purge_config| {
+                    TransactionHistoryPurgeService::new(
+                        blockstore.clone(),
+                        purge_config,
+                        exit.clone(),
+                    )
+                });
 
         let wait_for_vote_to_start_leader =
             !waited_for_supermajority && !config.no_wait_for_vote_to_start_leader;
@@ -1171,6 +1232,7 @@ impl Validator {
                 .unwrap()
         });
         let (turbine_quic_endpoint_sender, turbine_quic_endpoint_receiver) = unbounded();
+        let turbine_quic_peer_stats = Arc::<TurbinePeerStats>::default();
         let (
             turbine_quic_endpoint,
             turbine_quic_endpoint_sender,
@@ -1188,6 +1250,7 @@ impl Validator {
                 .ip(),
             turbine_quic_endpoint_sender,
             bank_forks.clone(),
+            turbine_quic_peer_stats.clone(),
         )
         .unwrap();
 
@@ -1283,6 +1346,7 @@ impl Validator {
             banking_tracer.clone(),
             turbine_quic_endpoint_sender.clone(),
             turbine_quic_endpoint_receiver,
+            turbine_quic_peer_stats,
             repair_quic_endpoint_sender,
         )?;
 
@@ -1341,6 +1405,9 @@ impl Validator {
             tpu_enable_udp,
             &prioritization_fee_cache,
             config.block_production_method.clone(),
+            config.tpu_qos_policy.clone(),
+            config.tpu_qos_allowed_peers.clone(),
+            config.tpu_quic_server_options,
             config.generator_config.clone(),
         );
 
@@ -1380,6 +1447,7 @@ impl Validator {
             blockstore,
             geyser_plugin_service,
             blockstore_metric_report_service,
+            transaction_history_purge_service,
             accounts_background_service,
             accounts_hash_verifier,
             turbine_quic_endpoint,
@@ -1511,6 +1579,11 @@ impl Validator {
         self.blockstore_metric_report_service
             .join()
             .expect("ledger_metric_report_service");
+        if let Some(transaction_history_purge_service) = self.transaction_history_purge_service {
+            transaction_history_purge_service
+                .join()
+                .expect("transaction_history_purge_service");
+        }
         self.accounts_background_service
             .join()
             .expect("accounts_background_service");
@@ -1647,6 +1720,13 @@ fn post_process_restored_tower(
                     ("error", format!("Unable to restore tower: {err}"), String),
                 );
             }
+            if let crate::consensus::TowerError::LostLease(_) = err {
+                // Voting with a rebuilt tower would be refused anyway
+                return Err(format!(
+                    "Unable to restore tower: {err}. \
+                     Another validator holds the lease on the tower of this identity"
+                ));
+            }
             if should_require_tower && voting_has_been_active {
                 return Err(format!(
                     "Requested mandatory tower restore failed: {err}. \
@@ -1,6 +1,9 @@
 use {
     crate::{
-        consensus::tower_storage::{SavedTowerVersions, TowerStorage},
+        consensus::{
+            tower_storage::{SavedTowerVersions, TowerStorage},
+            TowerError,
+        },
         next_leader::next_leader_tpu_vote,
     },
     crossbeam_channel::Receiver,
@@ -68,6 +71,25 @@ impl VotingService {
         tower_storage: &dyn TowerStorage,
         vote_op: VoteOp,
     ) {
+        match tower_storage.check_fence(&cluster_info.id()) {
+            Ok(()) => (),
+            Err(err @ TowerError::LostLease(_)) => {
+                // Another validator now votes with this identity and owns the
+                // tower, this one must not vote ever again.
+                error!("Lost the tower lease, exiting: {err}");
+                datapoint_error!("tower_fence-lost", ("error", err.to_string(), String));
+                std::process::exit(1);
+            }
+            Err(err) => {
+                error!(
+                    "Dropping vote, tower storage fencing check failed: {:?}",
+                    err
+                );
+                inc_new_counter_error!("tower_fence-rejected", 1);
+                return;
+            }
+        }
+
         if let VoteOp::PushVote { saved_tower, .. } = &vote_op {
             let mut measure = Measure::start("tower_save-ms");
             if let Err(err) = tower_storage.store(saved_tower) {
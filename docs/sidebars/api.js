@@ -293,6 +293,11 @@ module.exports = {
           href: "#sendtransaction",
           label: "sendTransaction",
         },
+        {
+          type: "link",
+          href: "#simulatebundle",
+          label: "simulateBundle",
+        },
         {
           type: "link",
           href: "#simulatetransaction",
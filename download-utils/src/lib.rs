@@ -1,4 +1,9 @@
 #![allow(clippy::arithmetic_side_effects)]
+pub use parallel_download::{
+    download_file_from_peers, download_file_from_peers_with_reader, DownloadingFileReader,
+    DownloadingFileReaderCallback, ParallelDownloadConfig, DEFAULT_DOWNLOAD_CHUNK_SIZE,
+    DEFAULT_MAX_DOWNLOAD_PEERS,
+};
 use {
     console::Emoji,
     indicatif::{ProgressBar, ProgressStyle},
@@ -19,6 +24,8 @@ use {
     },
 };
 
+mod parallel_download;
+
 static TRUCK: Emoji = Emoji("🚚 ", "");
 static SPARKLE: Emoji = Emoji("✨ ", "");
 
@@ -34,6 +41,31 @@ fn new_spinner_progress_bar() -> ProgressBar {
     progress_bar
 }
 
+fn set_download_progress_style(progress_bar: &ProgressBar) {
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green}{msg_wide}[{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})",
+            )
+            .expect("ProgresStyle::template direct input to be correct")
+            .progress_chars("=> "),
+    );
+}
+
+/// Path of the partial file that `destination_file` is downloaded into
+fn temp_download_path(destination_file: &Path) -> PathBuf {
+    let mut temp_destination_file = destination_file.to_path_buf();
+    temp_destination_file.set_file_name(format!(
+        "tmp-{}",
+        destination_file
+            .file_name()
+            .expect("file_name")
+            .to_str()
+            .expect("to_str")
+    ));
+    temp_destination_file
+}
+
 /// Structure modeling information about download progress
 #[derive(Debug)]
 pub struct DownloadProgressRecord {
@@ -77,15 +109,7 @@ pub fn download_file<'a, 'b>(
     fs::create_dir_all(destination_file.parent().expect("parent"))
         .map_err(|err| err.to_string())?;
 
-    let mut temp_destination_file = destination_file.to_path_buf();
-    temp_destination_file.set_file_name(format!(
-        "tmp-{}",
-        destination_file
-            .file_name()
-            .expect("file_name")
-            .to_str()
-            .expect("to_str")
-    ));
+    let temp_destination_file = temp_download_path(destination_file);
 
     let progress_bar = new_spinner_progress_bar();
     if use_progress_bar {
@@ -112,14 +136,7 @@ pub fn download_file<'a, 'b>(
 
     if use_progress_bar {
         progress_bar.set_length(download_size);
-        progress_bar.set_style(
-            ProgressStyle::default_bar()
-                .template(
-                    "{spinner:.green}{msg_wide}[{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})",
-                )
-                .expect("ProgresStyle::template direct input to be correct")
-                .progress_chars("=> "),
-        );
+        set_download_progress_style(&progress_bar);
         progress_bar.set_message(format!("{TRUCK}Downloading~ {url}"));
     } else {
         info!("Downloading {} bytes from {}", download_size, url);
@@ -268,6 +285,97 @@ pub fn download_snapshot_archive(
     maximum_incremental_snapshot_archives_to_retain: NonZeroUsize,
     use_progress_bar: bool,
     progress_notify_callback: &mut DownloadProgressCallbackOption<'_>,
+) -> Result<(), String> {
+    download_snapshot_archive_with(
+        full_snapshot_archives_dir,
+        incremental_snapshot_archives_dir,
+        desired_snapshot_hash,
+        snapshot_kind,
+        maximum_full_snapshot_archives_to_retain,
+        maximum_incremental_snapshot_archives_to_retain,
+        |archive_file_name, destination_path, _archive_format| {
+            download_file(
+                &format!("http://{rpc_addr}/{archive_file_name}"),
+                destination_path,
+                use_progress_bar,
+                progress_notify_callback,
+            )
+        },
+    )
+    .map_err(|err| format!("{err} from {rpc_addr}"))
+}
+
+/// Download a snapshot archive from all of `rpc_addrs` at once, see `download_file_from_peers`.
+/// The peers must all advertise `desired_snapshot_hash`, and the first peer is preferred.  The
+/// hash of the archive itself is not known ahead of time, so the other peers must be trusted as
+/// much as the first one.
+///
+/// If `stage_unpack_account_paths` is set, the archive is also unpacked while it is downloaded,
+/// see `snapshot_utils::stage_snapshot_archive_unpack`.  Failing to unpack it does not fail the
+/// download, the archive is then unpacked when the bank is loaded from it.
+#[allow(clippy::too_many_arguments)]
+pub fn download_snapshot_archive_from_peers(
+    rpc_addrs: &[SocketAddr],
+    full_snapshot_archives_dir: &Path,
+    incremental_snapshot_archives_dir: &Path,
+    desired_snapshot_hash: (Slot, SnapshotHash),
+    snapshot_kind: SnapshotKind,
+    maximum_full_snapshot_archives_to_retain: NonZeroUsize,
+    maximum_incremental_snapshot_archives_to_retain: NonZeroUsize,
+    parallel_download_config: &ParallelDownloadConfig,
+    stage_unpack_account_paths: Option<&[PathBuf]>,
+    use_progress_bar: bool,
+    progress_notify_callback: &mut DownloadProgressCallbackOption<'_>,
+) -> Result<(), String> {
+    download_snapshot_archive_with(
+        full_snapshot_archives_dir,
+        incremental_snapshot_archives_dir,
+        desired_snapshot_hash,
+        snapshot_kind,
+        maximum_full_snapshot_archives_to_retain,
+        maximum_incremental_snapshot_archives_to_retain,
+        |archive_file_name, destination_path, archive_format| {
+            let urls: Vec<_> = rpc_addrs
+                .iter()
+                .map(|rpc_addr| format!("http://{rpc_addr}/{archive_file_name}"))
+                .collect();
+            let reader_callback = stage_unpack_account_paths.map(|account_paths| {
+                let destination_path = destination_path.to_path_buf();
+                Box::new(move |reader: DownloadingFileReader| {
+                    if let Err(err) = snapshot_utils::stage_snapshot_archive_unpack(
+                        reader,
+                        archive_format,
+                        &destination_path,
+                        account_paths,
+                    ) {
+                        warn!("Failed to unpack {destination_path:?} while downloading it: {err}");
+                    }
+                }) as DownloadingFileReaderCallback
+            });
+            download_file_from_peers_with_reader(
+                &urls,
+                destination_path,
+                parallel_download_config,
+                use_progress_bar,
+                progress_notify_callback,
+                reader_callback,
+            )
+        },
+    )
+    .map_err(|err| format!("{err} from {rpc_addrs:?}"))
+}
+
+/// Download the snapshot archive in the first archive format that `download` succeeds with,
+/// passing it the archive file name and the path to download it to.  Partial downloads of any
+/// other snapshot archive are removed.
+fn download_snapshot_archive_with(
+    full_snapshot_archives_dir: &Path,
+    incremental_snapshot_archives_dir: &Path,
+    desired_snapshot_hash: (Slot, SnapshotHash),
+    snapshot_kind: SnapshotKind,
+    maximum_full_snapshot_archives_to_retain: NonZeroUsize,
+    maximum_incremental_snapshot_archives_to_retain: NonZeroUsize,
+    mut download: impl FnMut(&str, &Path, ArchiveFormat) -> Result<(), String>,
 ) -> Result<(), String> {
     snapshot_utils::purge_old_snapshot_archives(
         full_snapshot_archives_dir,
@@ -283,14 +391,16 @@ pub fn download_snapshot_archive(
         });
     fs::create_dir_all(&snapshot_archives_remote_dir).unwrap();
 
-    for archive_format in [
+    let archive_formats = [
         ArchiveFormat::TarZstd,
         ArchiveFormat::TarGzip,
         ArchiveFormat::TarBzip2,
         ArchiveFormat::TarLz4,
         ArchiveFormat::Tar,
-    ] {
-        let destination_path = match snapshot_kind {
+    ];
+    let destination_paths: Vec<_> = archive_formats
+        .into_iter()
+        .map(|archive_format| match snapshot_kind {
             SnapshotKind::FullSnapshot => snapshot_utils::build_full_snapshot_archive_path(
                 &snapshot_archives_remote_dir,
                 desired_snapshot_hash.0,
@@ -306,28 +416,50 @@ pub fn download_snapshot_archive(
                     archive_format,
                 )
             }
-        };
+        })
+        .collect();
+    remove_stale_partial_downloads(&snapshot_archives_remote_dir, &destination_paths);
 
+    for (archive_format, destination_path) in archive_formats.into_iter().zip(destination_paths) {
         if destination_path.is_file() {
             return Ok(());
         }
 
-        match download_file(
-            &format!(
-                "http://{}/{}",
-                rpc_addr,
-                destination_path.file_name().unwrap().to_str().unwrap()
-            ),
+        match download(
+            destination_path.file_name().unwrap().to_str().unwrap(),
             &destination_path,
-            use_progress_bar,
-            progress_notify_callback,
+            archive_format,
         ) {
             Ok(()) => return Ok(()),
             Err(err) => info!("{}", err),
         }
     }
     Err(format!(
-        "Failed to download a snapshot archive for slot {} from {}",
-        desired_snapshot_hash.0, rpc_addr
+        "Failed to download a snapshot archive for slot {}",
+        desired_snapshot_hash.0
     ))
 }
+
+/// Remove the partial downloads in `dir` other than those of `destination_paths`, which are kept
+/// to resume from
+fn remove_stale_partial_downloads(dir: &Path, destination_paths: &[PathBuf]) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_partial_download = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .map(|file_name| file_name.starts_with("tmp-"))
+            .unwrap_or(false);
+        let is_resumable = destination_paths.iter().any(|destination_path| {
+            let temp_path = temp_download_path(destination_path);
+            path == temp_path || path == parallel_download::journal_path(&temp_path)
+        });
+        if is_partial_download && !is_resumable {
+            info!("Removing stale partial download {path:?}");
+            let _ignored = fs::remove_file(path);
+        }
+    }
+}
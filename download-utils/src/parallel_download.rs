@@ -0,0 +1,1241 @@
+//! Download a file from several peers at once.
+//!
+//! The file is split into chunks which are fetched with HTTP range requests from every peer
+//! serving an identical copy, so a slow or failing peer only costs the chunk it was working on.
+//! The hash of each completed chunk is journaled next to the partial file, and an interrupted
+//! download resumes from the chunks whose hash still matches.  Once all chunks are downloaded,
+//! the file is read back and every chunk is verified against its hash, as well as the whole file
+//! against the expected hash if one is given.  The file can also be read in order
+//! while it is being downloaded, as far as the chunks are complete.
+use {
+    crate::{
+        download_file, new_spinner_progress_bar, set_download_progress_style, temp_download_path,
+        DownloadProgressCallbackOption, DownloadProgressRecord, SPARKLE, TRUCK,
+    },
+    log::*,
+    reqwest::{
+        blocking::{Client, Response},
+        header, StatusCode,
+    },
+    solana_sdk::hash::{hashv, Hash, Hasher},
+    std::{
+        collections::VecDeque,
+        fs::{self, File, OpenOptions},
+        io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+            Arc, Condvar, Mutex,
+        },
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+pub const DEFAULT_MAX_DOWNLOAD_PEERS: usize = 4;
+pub const DEFAULT_DOWNLOAD_CHUNK_SIZE: u64 = 32 * 1024 * 1024;
+
+// Number of bytes at the start and at the end of the file compared to tell whether two peers
+// serve the same copy of the file.
+const FINGERPRINT_SIZE: u64 = 64 * 1024;
+// Consecutive failed chunks after which a peer is no longer downloaded from.
+const MAX_PEER_FAILURES: usize = 3;
+// A peer is no longer downloaded from once its throughput falls below this fraction of the
+// throughput of the fastest peer.
+const MIN_RELATIVE_PEER_THROUGHPUT: f64 = 0.25;
+const PROGRESS_NOTIFICATION_INTERVAL: Duration = Duration::from_secs(5);
+const JOURNAL_SUFFIX: &str = ".journal";
+
+#[derive(Clone, Debug)]
+pub struct ParallelDownloadConfig {
+    /// Maximum number of peers downloaded from at once
+    pub max_peers: usize,
+    /// Number of bytes requested from a peer at a time
+    pub chunk_size: u64,
+    /// The hash of the contents of the file, if known, which the download is verified against
+    pub expected_hash: Option<Hash>,
+}
+
+impl Default for ParallelDownloadConfig {
+    fn default() -> Self {
+        Self {
+            max_peers: DEFAULT_MAX_DOWNLOAD_PEERS,
+            chunk_size: DEFAULT_DOWNLOAD_CHUNK_SIZE,
+            expected_hash: None,
+        }
+    }
+}
+
+/// The size and fingerprint of the copy of a file served by a peer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RemoteFile {
+    size: u64,
+    fingerprint: Hash,
+}
+
+enum ProbeError {
+    /// The peer serves the file, but not ranges of it
+    RangesUnsupported,
+    Failed(String),
+}
+
+/// Called on another thread with a reader of the file being downloaded
+pub type DownloadingFileReaderCallback<'a> = Box<dyn FnOnce(DownloadingFileReader) + Send + 'a>;
+
+/// The length of the prefix of the file being downloaded which is complete
+#[derive(Debug)]
+struct DownloadedPrefix {
+    size: u64,
+    // The length of the prefix, and whether the download has ended
+    state: Mutex<(u64, bool)>,
+    changed: Condvar,
+}
+
+impl DownloadedPrefix {
+    fn new(size: u64, len: u64) -> Self {
+        Self {
+            size,
+            state: Mutex::new((len, false)),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn set_len(&self, len: u64) {
+        self.state.lock().unwrap().0 = len;
+        self.changed.notify_all();
+    }
+
+    fn end(&self) {
+        self.state.lock().unwrap().1 = true;
+        self.changed.notify_all();
+    }
+}
+
+/// Reads a file in order while it is being downloaded by `download_file_from_peers_with_reader`,
+/// waiting for the chunks it reaches to be downloaded.  Reading fails once the download fails.
+pub struct DownloadingFileReader {
+    file: File,
+    position: u64,
+    downloaded_prefix: Arc<DownloadedPrefix>,
+}
+
+impl Read for DownloadingFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let downloaded_len = {
+            let mut state = self.downloaded_prefix.state.lock().unwrap();
+            while state.0 <= self.position && !state.1 {
+                state = self.downloaded_prefix.changed.wait(state).unwrap();
+            }
+            state.0
+        };
+        if downloaded_len <= self.position {
+            return if self.position >= self.downloaded_prefix.size {
+                Ok(0)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "the download of the file failed",
+                ))
+            };
+        }
+        let len = (downloaded_len - self.position).min(buf.len() as u64) as usize;
+        let read = self.file.read(&mut buf[..len])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+pub(crate) fn journal_path(temp_file: &Path) -> PathBuf {
+    let mut journal_path = temp_file.as_os_str().to_owned();
+    journal_path.push(JOURNAL_SUFFIX);
+    PathBuf::from(journal_path)
+}
+
+/// Download `urls`, which must all serve the same file, into `destination_file`.
+///
+/// The first url is the primary peer; the other peers are only downloaded from if they serve a
+/// copy of the file with the same size and fingerprint.  The fingerprint only covers the ends of
+/// the file, so unless `config.expected_hash` is set, the other peers must be trusted as much as
+/// the primary peer to serve the rest of it.  If the primary peer does not support
+/// range requests, the file is downloaded from it over a single stream like `download_file`.
+/// Peers are downloaded from in order of how fast they answered the initial probe, and those
+/// which become much slower than the fastest peer are dropped.
+///
+/// If the download fails or is aborted by `progress_notify_callback`, the partial file is kept,
+/// and the next download of the same file from peers serving the same copy resumes from it.
+pub fn download_file_from_peers(
+    urls: &[String],
+    destination_file: &Path,
+    config: &ParallelDownloadConfig,
+    use_progress_bar: bool,
+    progress_notify_callback: &mut DownloadProgressCallbackOption<'_>,
+) -> Result<(), String> {
+    download_file_from_peers_with_reader(
+        urls,
+        destination_file,
+        config,
+        use_progress_bar,
+        progress_notify_callback,
+        None,
+    )
+}
+
+/// Like `download_file_from_peers`, but `reader_callback` is called on another thread with a
+/// reader of the file while it is being downloaded, and the file is only moved to
+/// `destination_file` once the callback returns.  The callback is not called if the file is
+/// downloaded over a single stream.
+pub fn download_file_from_peers_with_reader(
+    urls: &[String],
+    destination_file: &Path,
+    config: &ParallelDownloadConfig,
+    use_progress_bar: bool,
+    progress_notify_callback: &mut DownloadProgressCallbackOption<'_>,
+    reader_callback: Option<DownloadingFileReaderCallback<'_>>,
+) -> Result<(), String> {
+    let Some(primary_url) = urls.first() else {
+        return Err("No peers to download from".to_string());
+    };
+    if destination_file.is_file() {
+        return Err(format!("{destination_file:?} already exists"));
+    }
+    let download_start = Instant::now();
+    let client = Client::new();
+
+    let progress_bar = new_spinner_progress_bar();
+    if use_progress_bar {
+        progress_bar.set_message(format!("{TRUCK}Probing {} peers...", urls.len()));
+    }
+    let probes: Vec<_> = thread::scope(|scope| {
+        let client = &client;
+        urls.iter()
+            .map(|url| scope.spawn(move || probe_peer(client, url)))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|probe| probe.join().unwrap())
+            .collect()
+    });
+    let remote_file = match &probes[0] {
+        Ok((remote_file, _)) => *remote_file,
+        Err(ProbeError::RangesUnsupported) => {
+            progress_bar.finish_and_clear();
+            info!("{primary_url} does not support range requests, downloading over one stream");
+            return download_file(
+                primary_url,
+                destination_file,
+                use_progress_bar,
+                progress_notify_callback,
+            );
+        }
+        Err(ProbeError::Failed(err)) => {
+            progress_bar.finish_and_clear();
+            return Err(err.clone());
+        }
+    };
+    let mut other_peers: Vec<_> = urls
+        .iter()
+        .zip(probes)
+        .skip(1)
+        .filter_map(|(url, probe)| match probe {
+            Ok((peer_file, probe_time)) if peer_file == remote_file => Some((url, probe_time)),
+            Ok(_) => {
+                info!("Not downloading from {url}, it serves a different copy of the file");
+                None
+            }
+            Err(ProbeError::RangesUnsupported) => {
+                info!("Not downloading from {url}, it does not support range requests");
+                None
+            }
+            Err(ProbeError::Failed(err)) => {
+                info!("Not downloading from {url}: {err}");
+                None
+            }
+        })
+        .collect();
+    other_peers.sort_by_key(|(_url, probe_time)| *probe_time);
+    let peers: Vec<&str> = std::iter::once(primary_url)
+        .chain(other_peers.into_iter().map(|(url, _probe_time)| url))
+        .take(config.max_peers.max(1))
+        .map(String::as_str)
+        .collect();
+
+    fs::create_dir_all(destination_file.parent().expect("parent"))
+        .map_err(|err| err.to_string())?;
+    let temp_destination_file = temp_download_path(destination_file);
+    let journal_path = journal_path(&temp_destination_file);
+    let download = ChunkedDownload::new(
+        &temp_destination_file,
+        &journal_path,
+        remote_file,
+        config.chunk_size.max(1),
+        peers.len(),
+    )
+    .map_err(|err| format!("Unable to resume {temp_destination_file:?}: {err}"))?;
+    let resumed_bytes = download.downloaded_bytes.load(Ordering::Relaxed);
+    let reader = reader_callback
+        .map(|reader_callback| {
+            File::open(&temp_destination_file).map(|file| {
+                let reader = DownloadingFileReader {
+                    file,
+                    position: 0,
+                    downloaded_prefix: download.downloaded_prefix.clone(),
+                };
+                (reader_callback, reader)
+            })
+        })
+        .transpose()
+        .map_err(|err| format!("Unable to open {temp_destination_file:?}: {err}"))?;
+
+    if use_progress_bar {
+        progress_bar.set_length(remote_file.size);
+        progress_bar.set_position(resumed_bytes);
+        set_download_progress_style(&progress_bar);
+        progress_bar.set_message(format!(
+            "{TRUCK}Downloading~ {primary_url} from {} peers",
+            peers.len()
+        ));
+    }
+    info!(
+        "Downloading {} bytes from {:?}, resuming from {} bytes",
+        remote_file.size, peers, resumed_bytes
+    );
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = peers
+            .iter()
+            .enumerate()
+            .map(|(peer, url)| {
+                let download = &download;
+                let client = &client;
+                thread::Builder::new()
+                    .name(format!("solDownload{peer:02}"))
+                    .spawn_scoped(scope, move || {
+                        download.download_from_peer(client, peer, url)
+                    })
+                    .unwrap()
+            })
+            .collect();
+        if let Some((reader_callback, reader)) = reader {
+            thread::Builder::new()
+                .name("solDownloadRead".to_string())
+                .spawn_scoped(scope, move || reader_callback(reader))
+                .unwrap();
+        }
+
+        let start_time = Instant::now();
+        let mut last_print = Instant::now();
+        let mut last_print_bytes = resumed_bytes;
+        let mut notification_count = 0;
+        loop {
+            let finished = workers.iter().all(|worker| worker.is_finished());
+            let current_bytes = download.downloaded_bytes.load(Ordering::Relaxed);
+            if use_progress_bar {
+                progress_bar.set_position(current_bytes);
+            }
+            if finished {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+            if last_print.elapsed() <= PROGRESS_NOTIFICATION_INTERVAL {
+                continue;
+            }
+
+            let last_throughput = current_bytes.saturating_sub(last_print_bytes) as f32
+                / last_print.elapsed().as_secs_f32();
+            let download_size = (remote_file.size as f32).max(1f32);
+            notification_count += 1;
+            let progress_record = DownloadProgressRecord {
+                elapsed_time: start_time.elapsed(),
+                last_elapsed_time: last_print.elapsed(),
+                last_throughput,
+                total_throughput: current_bytes.saturating_sub(resumed_bytes) as f32
+                    / start_time.elapsed().as_secs_f32(),
+                total_bytes: remote_file.size as usize,
+                current_bytes: current_bytes as usize,
+                percentage_done: 100f32 * (current_bytes as f32 / download_size),
+                estimated_remaining_time: if last_throughput > 0_f32 {
+                    (download_size - current_bytes as f32) / last_throughput
+                } else {
+                    f32::MAX
+                },
+                notification_count,
+            };
+            last_print = Instant::now();
+            last_print_bytes = current_bytes;
+            if !use_progress_bar {
+                info!(
+                    "downloaded {} bytes {:.1}% {:.1} bytes/s from {} peers",
+                    current_bytes,
+                    progress_record.percentage_done,
+                    progress_record.last_throughput,
+                    download.active_peers.load(Ordering::Relaxed),
+                );
+            }
+            if let Some(callback) = progress_notify_callback {
+                if !callback(&progress_record) {
+                    info!("Download is aborted by the caller");
+                    download.abort();
+                }
+            }
+        }
+        // Let the reader finish or fail before the scope waits for it
+        download.downloaded_prefix.end();
+    });
+    progress_bar.finish_and_clear();
+
+    if download.abort.load(Ordering::Relaxed) {
+        return Err("Download is aborted by the caller".to_string());
+    }
+    let chunk_hashes = download.into_chunk_hashes();
+    let missing_chunks = chunk_hashes.iter().filter(|hash| hash.is_none()).count();
+    if missing_chunks > 0 {
+        return Err(format!(
+            "Failed to download {missing_chunks} of {} chunks of {primary_url}, keeping \
+             {temp_destination_file:?} to resume from",
+            chunk_hashes.len(),
+        ));
+    }
+    let chunk_hashes: Vec<_> = chunk_hashes.into_iter().flatten().collect();
+
+    File::open(&temp_destination_file)
+        .and_then(|file| file.sync_all())
+        .map_err(|err| format!("Unable to sync {temp_destination_file:?}: {err:?}"))?;
+    let file_hash = verify_chunks(
+        &temp_destination_file,
+        &chunk_hashes,
+        config.chunk_size.max(1),
+        remote_file.size,
+    )
+    .map_err(|err| format!("Unable to verify {temp_destination_file:?}: {err}"))?;
+    if let Some(expected_hash) = config.expected_hash {
+        if file_hash != expected_hash {
+            // None of the chunks can be trusted, so they are not resumed from
+            let _ignored = fs::remove_file(&journal_path);
+            return Err(format!(
+                "{primary_url} has hash {file_hash}, expected {expected_hash}"
+            ));
+        }
+    }
+    info!(
+        "  {}{}",
+        SPARKLE,
+        format!(
+            "Downloaded {} ({} bytes, hash {}) from {} peers in {:?}",
+            primary_url,
+            remote_file.size,
+            file_hash,
+            peers.len(),
+            download_start.elapsed(),
+        )
+    );
+    fs::rename(temp_destination_file, destination_file)
+        .map_err(|err| format!("Unable to rename: {err:?}"))?;
+    let _ignored = fs::remove_file(journal_path);
+    Ok(())
+}
+
+/// Request `range` of `url`, returning the response and the start, end and total size from its
+/// `Content-Range` header
+fn get_range(
+    client: &Client,
+    url: &str,
+    range: &str,
+) -> Result<(Response, (u64, u64, u64)), ProbeError> {
+    let response = client
+        .get(url)
+        .header(header::RANGE, range)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| ProbeError::Failed(err.to_string()))?;
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(ProbeError::RangesUnsupported);
+    }
+    let content_range = response
+        .headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|content_range| content_range.to_str().ok())
+        .and_then(parse_content_range)
+        .ok_or_else(|| ProbeError::Failed(format!("Invalid Content-Range from {url}")))?;
+    Ok((response, content_range))
+}
+
+/// Parse a `Content-Range: bytes start-end/size` header value
+fn parse_content_range(content_range: &str) -> Option<(u64, u64, u64)> {
+    let (range, size) = content_range.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    let (start, end, size) = (start.parse().ok()?, end.parse().ok()?, size.parse().ok()?);
+    (start <= end && end < size).then_some((start, end, size))
+}
+
+/// Fetch the size of the file served at `url` and fingerprint its first and last bytes
+fn probe_peer(client: &Client, url: &str) -> Result<(RemoteFile, Duration), ProbeError> {
+    let probe_start = Instant::now();
+    let read_range = |range: &str| {
+        let (response, (start, end, size)) = get_range(client, url, range)?;
+        let mut bytes = vec![];
+        response
+            .take(end - start + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|err| ProbeError::Failed(err.to_string()))?;
+        if bytes.len() as u64 != end - start + 1 {
+            return Err(ProbeError::Failed(format!("Truncated response from {url}")));
+        }
+        Ok((bytes, size))
+    };
+    let (head, size) = read_range(&format!("bytes=0-{}", FINGERPRINT_SIZE - 1))?;
+    let tail = if size > FINGERPRINT_SIZE {
+        let (tail, tail_size) = read_range(&format!("bytes=-{FINGERPRINT_SIZE}"))?;
+        if tail_size != size {
+            return Err(ProbeError::Failed(format!("{url} changed while probing")));
+        }
+        tail
+    } else {
+        vec![]
+    };
+    let remote_file = RemoteFile {
+        size,
+        fingerprint: hashv(&[&size.to_le_bytes(), &head, &tail]),
+    };
+    Ok((remote_file, probe_start.elapsed()))
+}
+
+/// Byte offset and length of chunk `index`
+fn chunk_range(index: usize, chunk_size: u64, file_size: u64) -> (u64, u64) {
+    let start = index as u64 * chunk_size;
+    (start, chunk_size.min(file_size - start))
+}
+
+/// The length of the prefix of the file covered by the chunks which have a hash
+fn downloaded_prefix_len(chunk_hashes: &[Option<Hash>], chunk_size: u64, file_size: u64) -> u64 {
+    chunk_hashes
+        .iter()
+        .position(Option::is_none)
+        .map_or(file_size, |index| {
+            chunk_range(index, chunk_size, file_size).0
+        })
+}
+
+/// Read the downloaded file back and check that each chunk matches the hash it was downloaded
+/// with, returning the hash of the whole file.  Chunks which do not match are downloaded again
+/// when the download is resumed.
+fn verify_chunks(
+    path: &Path,
+    chunk_hashes: &[Hash],
+    chunk_size: u64,
+    file_size: u64,
+) -> io::Result<Hash> {
+    let mut reader = BufReader::with_capacity(256 * 1024, File::open(path)?);
+    let mut file_hasher = Hasher::default();
+    let mut mismatched_chunks = vec![];
+    let mut buf = vec![0; 256 * 1024];
+    for (index, hash) in chunk_hashes.iter().enumerate() {
+        let (_start, len) = chunk_range(index, chunk_size, file_size);
+        let mut chunk_hasher = Hasher::default();
+        let mut chunk = (&mut reader).take(len);
+        loop {
+            match chunk.read(&mut buf)? {
+                0 => break,
+                n => {
+                    chunk_hasher.hash(&buf[..n]);
+                    file_hasher.hash(&buf[..n]);
+                }
+            }
+        }
+        if chunk_hasher.result() != *hash {
+            mismatched_chunks.push(index);
+        }
+    }
+    if !mismatched_chunks.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("chunks {mismatched_chunks:?} do not match their hash"),
+        ));
+    }
+    Ok(file_hasher.result())
+}
+
+fn hash_file_range(file: &mut File, start: u64, len: u64) -> io::Result<Hash> {
+    file.seek(SeekFrom::Start(start))?;
+    let mut hasher = Hasher::default();
+    let mut buf = vec![0; 256 * 1024];
+    let mut reader = file.take(len);
+    loop {
+        match reader.read(&mut buf)? {
+            0 => break,
+            n => hasher.hash(&buf[..n]),
+        }
+    }
+    Ok(hasher.result())
+}
+
+/// Records the hash of each completed chunk of a partial download
+struct DownloadJournal {
+    file: File,
+}
+
+impl DownloadJournal {
+    fn header(remote_file: &RemoteFile, chunk_size: u64) -> String {
+        format!(
+            "{} {} {}",
+            remote_file.size, chunk_size, remote_file.fingerprint
+        )
+    }
+
+    /// Read the chunks recorded in the journal at `path`, if it was written for the same copy of
+    /// the file and chunk size
+    fn read(path: &Path, remote_file: &RemoteFile, chunk_size: u64) -> Vec<(usize, Hash)> {
+        let Ok(file) = File::open(path) else {
+            return vec![];
+        };
+        let mut lines = BufReader::new(file).lines().map_while(Result::ok);
+        if lines.next() != Some(Self::header(remote_file, chunk_size)) {
+            return vec![];
+        }
+        // A line may have been cut short when the download was interrupted
+        lines
+            .filter_map(|line| {
+                let (index, hash) = line.split_once(' ')?;
+                Some((index.parse().ok()?, hash.parse().ok()?))
+            })
+            .collect()
+    }
+
+    /// Start a new journal at `path` with the chunks completed so far
+    fn create(
+        path: &Path,
+        remote_file: &RemoteFile,
+        chunk_size: u64,
+        chunk_hashes: &[Option<Hash>],
+    ) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", Self::header(remote_file, chunk_size))?;
+        for (index, hash) in chunk_hashes.iter().enumerate() {
+            if let Some(hash) = hash {
+                writeln!(file, "{index} {hash}")?;
+            }
+        }
+        file.sync_data()?;
+        Ok(Self { file })
+    }
+
+    fn record(&mut self, index: usize, hash: &Hash) -> io::Result<()> {
+        writeln!(self.file, "{index} {hash}")
+    }
+}
+
+/// The chunks left to download
+#[derive(Debug, Default)]
+struct PendingChunks {
+    /// The chunks which no peer is downloading
+    queued: VecDeque<usize>,
+    /// The number of chunks being downloaded, which are queued again if their download fails
+    in_flight: usize,
+}
+
+struct ChunkedDownload<'a> {
+    temp_file: &'a Path,
+    remote_file: RemoteFile,
+    chunk_size: u64,
+    pending_chunks: Mutex<PendingChunks>,
+    pending_chunks_changed: Condvar,
+    completed_chunks: Mutex<(DownloadJournal, Vec<Option<Hash>>)>,
+    downloaded_bytes: AtomicU64,
+    // Throughput in bytes/s of the last chunk downloaded from each peer
+    peer_throughputs: Vec<AtomicU64>,
+    active_peers: AtomicUsize,
+    abort: AtomicBool,
+    downloaded_prefix: Arc<DownloadedPrefix>,
+}
+
+impl<'a> ChunkedDownload<'a> {
+    /// Prepare `temp_file` to download `remote_file` into, keeping the chunks recorded in the
+    /// journal whose hash still matches
+    fn new(
+        temp_file: &'a Path,
+        journal_path: &Path,
+        remote_file: RemoteFile,
+        chunk_size: u64,
+        num_peers: usize,
+    ) -> io::Result<Self> {
+        let num_chunks = ((remote_file.size + chunk_size - 1) / chunk_size) as usize;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(temp_file)?;
+        let mut chunk_hashes = vec![None; num_chunks];
+        let mut downloaded_bytes = 0;
+        for (index, hash) in DownloadJournal::read(journal_path, &remote_file, chunk_size) {
+            if index >= num_chunks || chunk_hashes[index].is_some() {
+                continue;
+            }
+            let (start, len) = chunk_range(index, chunk_size, remote_file.size);
+            if hash_file_range(&mut file, start, len)? == hash {
+                chunk_hashes[index] = Some(hash);
+                downloaded_bytes += len;
+            } else {
+                warn!(
+                    "Chunk {index} of {temp_file:?} does not match its hash, downloading it again"
+                );
+            }
+        }
+        file.set_len(remote_file.size)?;
+        let journal =
+            DownloadJournal::create(journal_path, &remote_file, chunk_size, &chunk_hashes)?;
+        let downloaded_prefix = Arc::new(DownloadedPrefix::new(
+            remote_file.size,
+            downloaded_prefix_len(&chunk_hashes, chunk_size, remote_file.size),
+        ));
+        let pending_chunks = PendingChunks {
+            queued: chunk_hashes
+                .iter()
+                .enumerate()
+                .filter(|(_index, hash)| hash.is_none())
+                .map(|(index, _hash)| index)
+                .collect(),
+            in_flight: 0,
+        };
+        Ok(Self {
+            temp_file,
+            remote_file,
+            chunk_size,
+            pending_chunks: Mutex::new(pending_chunks),
+            pending_chunks_changed: Condvar::new(),
+            completed_chunks: Mutex::new((journal, chunk_hashes)),
+            downloaded_bytes: AtomicU64::new(downloaded_bytes),
+            peer_throughputs: (0..num_peers).map(|_| AtomicU64::default()).collect(),
+            active_peers: AtomicUsize::new(num_peers),
+            abort: AtomicBool::default(),
+            downloaded_prefix,
+        })
+    }
+
+    fn into_chunk_hashes(self) -> Vec<Option<Hash>> {
+        self.completed_chunks.into_inner().unwrap().1
+    }
+
+    /// Take the next chunk to download from `peer`, or `None` if there are none left or the peer
+    /// has become too slow compared to the fastest peer.  While other peers are downloading the
+    /// last chunks, waits for them to complete, so that a chunk whose download fails is still
+    /// downloaded again by another peer.
+    fn next_chunk(&self, peer: usize) -> Option<usize> {
+        let mut pending_chunks = self.pending_chunks.lock().unwrap();
+        let throughput = self.peer_throughputs[peer].load(Ordering::Relaxed);
+        let fastest_throughput = self
+            .peer_throughputs
+            .iter()
+            .map(|throughput| throughput.load(Ordering::Relaxed))
+            .max()
+            .unwrap_or_default();
+        if throughput > 0
+            && (throughput as f64) < fastest_throughput as f64 * MIN_RELATIVE_PEER_THROUGHPUT
+            && self.active_peers.load(Ordering::Relaxed) > 1
+        {
+            // Stop this peer from taking another chunk before the lock is released
+            self.active_peers.fetch_sub(1, Ordering::Relaxed);
+            info!(
+                "Dropping peer {peer}, its throughput of {throughput} bytes/s is far below the \
+                 {fastest_throughput} bytes/s of the fastest peer"
+            );
+            return None;
+        }
+        loop {
+            if let Some(chunk) = pending_chunks.queued.pop_front() {
+                pending_chunks.in_flight += 1;
+                return Some(chunk);
+            }
+            if pending_chunks.in_flight == 0 || self.abort.load(Ordering::Relaxed) {
+                self.active_peers.fetch_sub(1, Ordering::Relaxed);
+                return None;
+            }
+            pending_chunks = self.pending_chunks_changed.wait(pending_chunks).unwrap();
+        }
+    }
+
+    /// Stop the download, waking up the peers waiting for a chunk
+    fn abort(&self) {
+        let _pending_chunks = self.pending_chunks.lock().unwrap();
+        self.abort.store(true, Ordering::Relaxed);
+        self.pending_chunks_changed.notify_all();
+    }
+
+    /// Release chunk `index` taken with `next_chunk`, queueing it again unless it was downloaded
+    fn release_chunk(&self, index: usize, downloaded: bool) {
+        let mut pending_chunks = self.pending_chunks.lock().unwrap();
+        pending_chunks.in_flight -= 1;
+        if !downloaded {
+            pending_chunks.queued.push_front(index);
+        }
+        self.pending_chunks_changed.notify_all();
+    }
+
+    fn download_from_peer(&self, client: &Client, peer: usize, url: &str) {
+        let mut failures = 0;
+        while let Some(index) = self.next_chunk(peer) {
+            let chunk_start = Instant::now();
+            match self.download_chunk(client, url, index) {
+                Ok(hash) => {
+                    failures = 0;
+                    let (_start, len) = chunk_range(index, self.chunk_size, self.remote_file.size);
+                    let throughput = len as f64 / chunk_start.elapsed().as_secs_f64().max(1e-3);
+                    self.peer_throughputs[peer].store(throughput as u64, Ordering::Relaxed);
+                    let mut completed_chunks = self.completed_chunks.lock().unwrap();
+                    let (journal, chunk_hashes) = &mut *completed_chunks;
+                    chunk_hashes[index] = Some(hash);
+                    self.downloaded_prefix.set_len(downloaded_prefix_len(
+                        chunk_hashes,
+                        self.chunk_size,
+                        self.remote_file.size,
+                    ));
+                    if let Err(err) = journal.record(index, &hash) {
+                        warn!(
+                            "Unable to record chunk {index} of {:?}: {err}",
+                            self.temp_file
+                        );
+                    }
+                    drop(completed_chunks);
+                    self.release_chunk(index, true);
+                }
+                Err(err) => {
+                    self.release_chunk(index, false);
+                    if self.abort.load(Ordering::Relaxed) {
+                        self.active_peers.fetch_sub(1, Ordering::Relaxed);
+                        break;
+                    }
+                    failures += 1;
+                    warn!("Failed to download chunk {index} from {url}: {err}");
+                    if failures >= MAX_PEER_FAILURES {
+                        warn!("Dropping {url} after {failures} failed chunks");
+                        self.active_peers.fetch_sub(1, Ordering::Relaxed);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Download chunk `index` from `url` into its place in the temp file, returning its hash
+    fn download_chunk(&self, client: &Client, url: &str, index: usize) -> Result<Hash, String> {
+        let (start, len) = chunk_range(index, self.chunk_size, self.remote_file.size);
+        let end = start + len - 1;
+        let (response, content_range) = get_range(client, url, &format!("bytes={start}-{end}"))
+            .map_err(|err| match err {
+                ProbeError::RangesUnsupported => "range requests not supported".to_string(),
+                ProbeError::Failed(err) => err,
+            })?;
+        if content_range != (start, end, self.remote_file.size) {
+            return Err(format!("unexpected range {content_range:?}"));
+        }
+
+        let mut received = 0;
+        self.write_chunk(response.take(len), start, len, &mut received)
+            .map_err(|err| {
+                self.downloaded_bytes.fetch_sub(received, Ordering::Relaxed);
+                err.to_string()
+            })
+    }
+
+    /// Write the `len` bytes of `chunk` at `start` in the temp file while hashing them, counting
+    /// the bytes written so far in `received`
+    fn write_chunk(
+        &self,
+        mut chunk: impl Read,
+        start: u64,
+        len: u64,
+        received: &mut u64,
+    ) -> io::Result<Hash> {
+        let mut file = OpenOptions::new().write(true).open(self.temp_file)?;
+        file.seek(SeekFrom::Start(start))?;
+        let mut hasher = Hasher::default();
+        let mut buf = vec![0; 256 * 1024];
+        loop {
+            if self.abort.load(Ordering::Relaxed) {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "aborted"));
+            }
+            let n = chunk.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.hash(&buf[..n]);
+            file.write_all(&buf[..n])?;
+            *received += n as u64;
+            self.downloaded_bytes.fetch_add(n as u64, Ordering::Relaxed);
+        }
+        if *received != len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("received {received} of {len} bytes"),
+            ));
+        }
+        Ok(hasher.result())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{net::TcpListener, sync::Arc},
+    };
+
+    fn test_content(len: usize, seed: u8) -> Vec<u8> {
+        (0..len)
+            .map(|i| ((i as u64 * 2_654_435_761) >> 7) as u8 ^ seed)
+            .collect()
+    }
+
+    /// Serve `content` over HTTP, recording the range of every request
+    fn serve(content: Vec<u8>, supports_ranges: bool) -> (String, Arc<Mutex<Vec<Option<String>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/file", listener.local_addr().unwrap());
+        let requests = Arc::<Mutex<Vec<Option<String>>>>::default();
+        {
+            let requests = requests.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let mut range = None;
+                    for line in BufReader::new(stream.try_clone().unwrap()).lines() {
+                        let line = line.unwrap();
+                        if line.is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("range") {
+                                range = Some(value.trim().to_string());
+                            }
+                        }
+                    }
+                    requests.lock().unwrap().push(range.clone());
+                    let size = content.len();
+                    let (start, end) = match range.filter(|_| supports_ranges) {
+                        None => (0, size - 1),
+                        Some(range) => {
+                            let range = range.strip_prefix("bytes=").unwrap();
+                            match range.split_once('-').unwrap() {
+                                ("", suffix) => {
+                                    (size.saturating_sub(suffix.parse().unwrap()), size - 1)
+                                }
+                                (start, end) => (
+                                    start.parse().unwrap(),
+                                    end.parse::<usize>().unwrap().min(size - 1),
+                                ),
+                            }
+                        }
+                    };
+                    let status = if supports_ranges {
+                        format!("206 Partial Content\r\nContent-Range: bytes {start}-{end}/{size}")
+                    } else {
+                        "200 OK".to_string()
+                    };
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        end - start + 1
+                    )
+                    .and_then(|()| stream.write_all(&content[start..=end]));
+                }
+            });
+        }
+        (url, requests)
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(parse_content_range("bytes 0-9/100"), Some((0, 9, 100)));
+        assert_eq!(parse_content_range("bytes 90-99/100"), Some((90, 99, 100)));
+        assert_eq!(parse_content_range("bytes 90-100/100"), None);
+        assert_eq!(parse_content_range("bytes 9-0/100"), None);
+        assert_eq!(parse_content_range("bytes */100"), None);
+        assert_eq!(parse_content_range("0-9/100"), None);
+    }
+
+    #[test]
+    fn test_chunk_range() {
+        assert_eq!(chunk_range(0, 10, 25), (0, 10));
+        assert_eq!(chunk_range(1, 10, 25), (10, 10));
+        assert_eq!(chunk_range(2, 10, 25), (20, 5));
+    }
+
+    #[test]
+    fn test_download_file_from_peers() {
+        let content = test_content(1_000_000, 0);
+        let (url0, requests0) = serve(content.clone(), true);
+        let (url1, requests1) = serve(content.clone(), true);
+        let (url2, requests2) = serve(test_content(1_000_000, 1), true);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let destination_file = temp_dir.path().join("file");
+
+        let config = ParallelDownloadConfig {
+            max_peers: 3,
+            chunk_size: 64 * 1024,
+            expected_hash: Some(solana_sdk::hash::hash(&content)),
+        };
+        download_file_from_peers(
+            &[url0, url1, url2],
+            &destination_file,
+            &config,
+            false,
+            &mut None,
+        )
+        .unwrap();
+        assert_eq!(fs::read(&destination_file).unwrap(), content);
+        let temp_file = temp_download_path(&destination_file);
+        assert!(!temp_file.exists());
+        assert!(!journal_path(&temp_file).exists());
+
+        // Both peers serving the same copy take part, the third only gets probed
+        let num_chunks = 1_000_000 / (64 * 1024) + 1;
+        let num_requests0 = requests0.lock().unwrap().len();
+        let num_requests1 = requests1.lock().unwrap().len();
+        assert_eq!(num_requests0 + num_requests1, 2 * 2 + num_chunks);
+        assert_eq!(requests2.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_download_file_from_peers_resume() {
+        let content = test_content(1_000_000, 0);
+        let (url, requests) = serve(content.clone(), true);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let destination_file = temp_dir.path().join("file");
+        let temp_file = temp_download_path(&destination_file);
+        let chunk_size = 100_000;
+
+        // A previous download completed chunks 0, 3 and 7, and chunk 3 got corrupted since
+        let (remote_file, _) = probe_peer(&Client::new(), &url).ok().unwrap();
+        let size = remote_file.size;
+        let mut partial = vec![0; content.len()];
+        let mut chunk_hashes = vec![None; 10];
+        for index in [0, 3, 7] {
+            let (start, len) = chunk_range(index, chunk_size, size);
+            let chunk = &content[start as usize..(start + len) as usize];
+            partial[start as usize..(start + len) as usize].copy_from_slice(chunk);
+            chunk_hashes[index] = Some(solana_sdk::hash::hash(chunk));
+        }
+        partial[300_000] ^= 1;
+        fs::write(&temp_file, &partial).unwrap();
+        DownloadJournal::create(
+            &journal_path(&temp_file),
+            &remote_file,
+            chunk_size,
+            &chunk_hashes,
+        )
+        .unwrap();
+        requests.lock().unwrap().clear();
+
+        let config = ParallelDownloadConfig {
+            max_peers: 1,
+            chunk_size,
+            ..ParallelDownloadConfig::default()
+        };
+        download_file_from_peers(&[url], &destination_file, &config, false, &mut None).unwrap();
+        assert_eq!(fs::read(&destination_file).unwrap(), content);
+        let requests = requests.lock().unwrap();
+        for index in [0, 7] {
+            let (start, len) = chunk_range(index, chunk_size, size);
+            let range = format!("bytes={start}-{}", start + len - 1);
+            assert!(!requests.contains(&Some(range)));
+        }
+        // Two probes and the eight chunks which were missing or corrupted
+        assert_eq!(requests.len(), 2 + 8);
+    }
+
+    #[test]
+    fn test_download_file_from_peers_with_reader() {
+        let content = test_content(1_000_000, 0);
+        let (url0, _requests0) = serve(content.clone(), true);
+        let (url1, _requests1) = serve(content.clone(), true);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let destination_file = temp_dir.path().join("file");
+
+        let config = ParallelDownloadConfig {
+            max_peers: 2,
+            chunk_size: 64 * 1024,
+            ..ParallelDownloadConfig::default()
+        };
+        let read_content = Arc::new(Mutex::new(vec![]));
+        let reader_callback = {
+            let read_content = read_content.clone();
+            Box::new(move |mut reader: DownloadingFileReader| {
+                // Read in small pieces so reads have to wait for chunks to complete
+                let mut buf = [0; 1000];
+                loop {
+                    let read = reader.read(&mut buf).unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    read_content.lock().unwrap().extend_from_slice(&buf[..read]);
+                }
+            })
+        };
+        download_file_from_peers_with_reader(
+            &[url0, url1],
+            &destination_file,
+            &config,
+            false,
+            &mut None,
+            Some(reader_callback),
+        )
+        .unwrap();
+        assert_eq!(fs::read(&destination_file).unwrap(), content);
+        assert_eq!(*read_content.lock().unwrap(), content);
+    }
+
+    #[test]
+    fn test_downloading_file_reader_fails_with_download() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("file");
+        fs::write(&path, [7; 100]).unwrap();
+        let downloaded_prefix = Arc::new(DownloadedPrefix::new(100, 40));
+        let mut reader = DownloadingFileReader {
+            file: File::open(&path).unwrap(),
+            position: 0,
+            downloaded_prefix: downloaded_prefix.clone(),
+        };
+
+        let mut buf = [0; 100];
+        assert_eq!(reader.read(&mut buf).unwrap(), 40);
+        downloaded_prefix.set_len(60);
+        assert_eq!(reader.read(&mut buf).unwrap(), 20);
+        downloaded_prefix.end();
+        assert_eq!(
+            reader.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::Other
+        );
+
+        let downloaded_prefix = Arc::new(DownloadedPrefix::new(100, 100));
+        downloaded_prefix.end();
+        let mut reader = DownloadingFileReader {
+            file: File::open(&path).unwrap(),
+            position: 0,
+            downloaded_prefix,
+        };
+        let mut content = vec![];
+        reader.read_to_end(&mut content).unwrap();
+        assert_eq!(content, [7; 100]);
+    }
+
+    #[test]
+    fn test_download_file_from_peers_without_ranges() {
+        let content = test_content(100_000, 0);
+        let (url, requests) = serve(content.clone(), false);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let destination_file = temp_dir.path().join("file");
+
+        download_file_from_peers(
+            &[url],
+            &destination_file,
+            &ParallelDownloadConfig::default(),
+            false,
+            &mut None,
+        )
+        .unwrap();
+        assert_eq!(fs::read(&destination_file).unwrap(), content);
+        // The probe, then the download over a single stream
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_next_chunk_drops_slow_peers() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let temp_file = temp_dir.path().join("tmp-file");
+        let remote_file = RemoteFile {
+            size: 100,
+            fingerprint: Hash::new_unique(),
+        };
+        let download =
+            ChunkedDownload::new(&temp_file, &journal_path(&temp_file), remote_file, 10, 3)
+                .unwrap();
+        assert_eq!(download.pending_chunks.lock().unwrap().queued.len(), 10);
+
+        // Peers without a measured throughput yet are not dropped
+        assert_eq!(download.next_chunk(0), Some(0));
+        assert_eq!(download.next_chunk(1), Some(1));
+        download.peer_throughputs[0].store(1_000, Ordering::Relaxed);
+        download.peer_throughputs[1].store(200, Ordering::Relaxed);
+        download.peer_throughputs[2].store(300, Ordering::Relaxed);
+        assert_eq!(download.next_chunk(1), None);
+        assert_eq!(download.next_chunk(2), Some(2));
+        assert_eq!(download.active_peers.load(Ordering::Relaxed), 2);
+
+        // The last remaining peer is never dropped
+        download.active_peers.store(1, Ordering::Relaxed);
+        download.peer_throughputs[2].store(1, Ordering::Relaxed);
+        assert_eq!(download.next_chunk(2), Some(3));
+    }
+
+    #[test]
+    fn test_next_chunk_waits_for_chunks_in_flight() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let temp_file = temp_dir.path().join("tmp-file");
+        let remote_file = RemoteFile {
+            size: 20,
+            fingerprint: Hash::new_unique(),
+        };
+        let download =
+            ChunkedDownload::new(&temp_file, &journal_path(&temp_file), remote_file, 10, 2)
+                .unwrap();
+        assert_eq!(download.next_chunk(0), Some(0));
+        assert_eq!(download.next_chunk(0), Some(1));
+
+        thread::scope(|scope| {
+            // The other peer waits for the last chunk, and takes it once its download fails
+            let waiting_peer = scope.spawn(|| download.next_chunk(1));
+            thread::sleep(Duration::from_millis(100));
+            assert!(!waiting_peer.is_finished());
+            download.release_chunk(1, false);
+            assert_eq!(waiting_peer.join().unwrap(), Some(1));
+
+            // Once every chunk is downloaded, waiting peers are done
+            let waiting_peer = scope.spawn(|| download.next_chunk(0));
+            thread::sleep(Duration::from_millis(100));
+            assert!(!waiting_peer.is_finished());
+            download.release_chunk(0, true);
+            download.release_chunk(1, true);
+            assert_eq!(waiting_peer.join().unwrap(), None);
+        });
+        assert_eq!(download.active_peers.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_download_file_from_peers_verifies_hash() {
+        let content = test_content(200_000, 0);
+        let (url, _requests) = serve(content.clone(), true);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let destination_file = temp_dir.path().join("file");
+
+        let config = ParallelDownloadConfig {
+            max_peers: 1,
+            chunk_size: 64 * 1024,
+            expected_hash: Some(solana_sdk::hash::hash(&test_content(200_000, 1))),
+        };
+        let err = download_file_from_peers(&[url], &destination_file, &config, false, &mut None)
+            .unwrap_err();
+        assert!(err.contains("expected"), "{err}");
+        assert!(!destination_file.exists());
+        assert!(!journal_path(&temp_download_path(&destination_file)).exists());
+    }
+
+    #[test]
+    fn test_verify_chunks() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("file");
+        let content = test_content(25, 0);
+        fs::write(&path, &content).unwrap();
+        let chunk_hashes: Vec<_> = content.chunks(10).map(solana_sdk::hash::hash).collect();
+        assert_eq!(
+            verify_chunks(&path, &chunk_hashes, 10, 25).unwrap(),
+            solana_sdk::hash::hash(&content)
+        );
+
+        // A chunk changed after it was downloaded
+        let mut corrupted = content;
+        corrupted[12] ^= 1;
+        fs::write(&path, corrupted).unwrap();
+        assert_eq!(
+            verify_chunks(&path, &chunk_hashes, 10, 25)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
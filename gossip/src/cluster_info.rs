@@ -26,6 +26,7 @@ use {
         },
         contact_info::{self, ContactInfo, Error as ContactInfoError, LegacyContactInfo},
         crds::{Crds, Cursor, GossipRoute},
+        crds_data_budget::{CrdsDataBudgetConfig, CrdsDataBudgets},
         crds_gossip::CrdsGossip,
         crds_gossip_error::CrdsGossipError,
         crds_gossip_pull::{
@@ -37,6 +38,7 @@ use {
         },
         duplicate_shred::DuplicateShred,
         epoch_slots::EpochSlots,
+        gossip_capture::GossipCaptureConfig,
         gossip_error::GossipError,
         ping_pong::{self, PingCache, Pong},
         socketaddr, socketaddr_any,
@@ -163,6 +165,7 @@ pub struct ClusterInfo {
     /// Network entrypoints
     entrypoints: RwLock<Vec<LegacyContactInfo>>,
     outbound_budget: DataBudget,
+    crds_data_budgets: CrdsDataBudgets,
     my_contact_info: RwLock<ContactInfo>,
     ping_cache: Mutex<PingCache>,
     stats: GossipStats,
@@ -173,6 +176,7 @@ pub struct ClusterInfo {
     instance: RwLock<NodeInstance>,
     contact_info_path: PathBuf,
     socket_addr_space: SocketAddrSpace,
+    gossip_capture: Option<GossipCaptureConfig>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize, AbiExample)]
@@ -180,11 +184,11 @@ pub(crate) struct PruneData {
     /// Pubkey of the node that sent this prune data
     pubkey: Pubkey,
     /// Pubkeys of nodes that should be pruned
-    prunes: Vec<Pubkey>,
+    pub(crate) prunes: Vec<Pubkey>,
     /// Signature of this Prune Message
     signature: Signature,
     /// The Pubkey of the intended node/destination for this message
-    destination: Pubkey,
+    pub(crate) destination: Pubkey,
     /// Wallclock of the node that generated this message
     wallclock: u64,
 }
@@ -375,31 +379,33 @@ impl Sanitize for Protocol {
 // Retains only CRDS values associated with nodes with enough stake.
 // (some crds types are exempted)
 fn retain_staked(values: &mut Vec<CrdsValue>, stakes: &HashMap<Pubkey, u64>) {
-    values.retain(|value| {
-        match value.data {
-            CrdsData::ContactInfo(_) => true,
-            CrdsData::LegacyContactInfo(_) => true,
-            // May Impact new validators starting up without any stake yet.
-            CrdsData::Vote(_, _) => true,
-            // Unstaked nodes can still help repair.
-            CrdsData::EpochSlots(_, _) => true,
-            // Unstaked nodes can still serve snapshots.
-            CrdsData::LegacySnapshotHashes(_) | CrdsData::SnapshotHashes(_) => true,
-            // Otherwise unstaked voting nodes will show up with no version in
-            // the various dashboards.
-            CrdsData::Version(_) => true,
-            CrdsData::NodeInstance(_) => true,
-            // getHealth fails if account hashes are not propagated.
-            CrdsData::AccountsHashes(_) => true,
-            CrdsData::LowestSlot(_, _)
-            | CrdsData::LegacyVersion(_)
-            | CrdsData::DuplicateShred(_, _)
-            | CrdsData::RestartLastVotedForkSlots(_) => {
-                let stake = stakes.get(&value.pubkey()).copied();
-                stake.unwrap_or_default() >= MIN_STAKE_FOR_GOSSIP
-            }
+    values.retain(|value| should_retain_crds_value(value, stakes))
+}
+
+fn should_retain_crds_value(value: &CrdsValue, stakes: &HashMap<Pubkey, u64>) -> bool {
+    match value.data {
+        CrdsData::ContactInfo(_) => true,
+        CrdsData::LegacyContactInfo(_) => true,
+        // May Impact new validators starting up without any stake yet.
+        CrdsData::Vote(_, _) => true,
+        // Unstaked nodes can still help repair.
+        CrdsData::EpochSlots(_, _) => true,
+        // Unstaked nodes can still serve snapshots.
+        CrdsData::LegacySnapshotHashes(_) | CrdsData::SnapshotHashes(_) => true,
+        // Otherwise unstaked voting nodes will show up with no version in
+        // the various dashboards.
+        CrdsData::Version(_) => true,
+        CrdsData::NodeInstance(_) => true,
+        // getHealth fails if account hashes are not propagated.
+        CrdsData::AccountsHashes(_) => true,
+        CrdsData::LowestSlot(_, _)
+        | CrdsData::LegacyVersion(_)
+        | CrdsData::DuplicateShred(_, _)
+        | CrdsData::RestartLastVotedForkSlots(_) => {
+            let stake = stakes.get(&value.pubkey()).copied();
+            stake.unwrap_or_default() >= MIN_STAKE_FOR_GOSSIP
         }
-    })
+    }
 }
 
 impl ClusterInfo {
@@ -415,6 +421,7 @@ impl ClusterInfo {
             keypair: RwLock::new(keypair),
             entrypoints: RwLock::default(),
             outbound_budget: DataBudget::default(),
+            crds_data_budgets: CrdsDataBudgets::default(),
             my_contact_info: RwLock::new(contact_info),
             ping_cache: Mutex::new(PingCache::new(
                 GOSSIP_PING_CACHE_TTL,
@@ -429,6 +436,7 @@ impl ClusterInfo {
             contact_info_path: PathBuf::default(),
             contact_save_interval: 0, // disabled
             socket_addr_space,
+            gossip_capture: None,
         };
         me.insert_self();
         me.push_self();
@@ -439,6 +447,22 @@ impl ClusterInfo {
         self.contact_debug_interval = new;
     }
 
+    /// Records all gossip packets sent and received once the gossip service
+    /// starts.
+    pub fn set_gossip_capture(&mut self, config: GossipCaptureConfig) {
+        self.gossip_capture = Some(config);
+    }
+
+    pub(crate) fn gossip_capture(&self) -> Option<&GossipCaptureConfig> {
+        self.gossip_capture.as_ref()
+    }
+
+    /// Limits the bandwidth used by each kind of crds value, and the order
+    /// in which they are sent and processed.
+    pub fn set_crds_data_budget(&mut self, config: &CrdsDataBudgetConfig) {
+        self.crds_data_budgets = CrdsDataBudgets::new(config);
+    }
+
     pub fn socket_addr_space(&self) -> &SocketAddrSpace {
         &self.socket_addr_space
     }
@@ -1525,10 +1549,22 @@ impl ClusterInfo {
     }
     fn new_push_requests(&self, stakes: &HashMap<Pubkey, u64>) -> Vec<(SocketAddr, Protocol)> {
         let self_id = self.id();
+        let require_stake_for_gossip = self.require_stake_for_gossip(stakes);
+        // Values dropped below for lack of stake are not charged against the
+        // egress budget.
+        let take_egress = |value: &CrdsValue, num_nodes| {
+            (require_stake_for_gossip && !should_retain_crds_value(value, stakes))
+                || self.crds_data_budgets.take_egress(
+                    value,
+                    num_nodes,
+                    &self.stats.crds_data_budget,
+                )
+        };
         let (mut push_messages, num_entries, num_nodes) = {
             let _st = ScopedTimer::from(&self.stats.new_push_requests);
             self.flush_push_queue();
-            self.gossip.new_push_messages(&self_id, timestamp(), stakes)
+            self.gossip
+                .new_push_messages(&self_id, timestamp(), stakes, take_egress)
         };
         self.stats
             .push_fanout_num_entries
@@ -1536,12 +1572,15 @@ impl ClusterInfo {
         self.stats
             .push_fanout_num_nodes
             .add_relaxed(num_nodes as u64);
-        if self.require_stake_for_gossip(stakes) {
+        if require_stake_for_gossip {
             push_messages.retain(|_, data| {
                 retain_staked(data, stakes);
                 !data.is_empty()
             })
         }
+        for data in push_messages.values_mut() {
+            self.crds_data_budgets.sort_by_priority(data, |value| value);
+        }
         let push_messages: Vec<_> = {
             let gossip_crds =
                 self.time_gossip_read_lock("push_req_lookup", &self.stats.new_push_requests2);
@@ -2056,15 +2095,31 @@ impl ClusterInfo {
             return packet_batch;
         }
         let mut rng = rand::thread_rng();
-        let shuffle = WeightedShuffle::new("handle-pull-requests", &scores).shuffle(&mut rng);
+        let mut shuffle: Vec<_> = WeightedShuffle::new("handle-pull-requests", &scores)
+            .shuffle(&mut rng)
+            .collect();
+        self.crds_data_budgets
+            .sort_by_priority(&mut shuffle, |&i| &responses[i].1);
         let mut total_bytes = 0;
         let mut sent = 0;
-        for (addr, response) in shuffle.map(|i| &responses[i]) {
-            let response = vec![response.clone()];
+        for (addr, value) in shuffle.into_iter().map(|i| &responses[i]) {
+            let response = vec![value.clone()];
             let response = Protocol::PullResponse(self_id, response);
             match Packet::from_data(Some(addr), response) {
                 Err(err) => error!("failed to write pull-response packet: {:?}", err),
                 Ok(packet) => {
+                    // The budget of the variant is only charged for values
+                    // which fit in the outbound budget.
+                    if !self.outbound_budget.check(packet.meta().size) {
+                        self.stats.gossip_pull_request_no_budget.add_relaxed(1);
+                        break;
+                    }
+                    if !self
+                        .crds_data_budgets
+                        .take_egress(value, 1, &self.stats.crds_data_budget)
+                    {
+                        continue;
+                    }
                     if self.outbound_budget.take(packet.meta().size) {
                         total_bytes += packet.meta().size;
                         packet_batch.push(packet);
@@ -2411,6 +2466,20 @@ impl ClusterInfo {
             }
             push_messages.retain(|(_, data)| !data.is_empty());
         }
+        {
+            let budgets = &self.crds_data_budgets;
+            let stats = &self.stats.crds_data_budget;
+            let total_stake: u64 = stakes.values().sum();
+            let take_ingress =
+                |value: &CrdsValue| budgets.take_ingress(value, stakes, total_stake, stats);
+            budgets.sort_by_priority(&mut pull_responses, |value| value);
+            pull_responses.retain(&take_ingress);
+            for (_, data) in &mut push_messages {
+                budgets.sort_by_priority(data, |value| value);
+                data.retain(&take_ingress);
+            }
+            push_messages.retain(|(_, data)| !data.is_empty());
+        }
         self.handle_batch_ping_messages(ping_messages, recycler, response_sender);
         self.handle_batch_prune_messages(prune_messages, stakes);
         self.handle_batch_push_messages(
@@ -3592,10 +3661,12 @@ mod tests {
         );
         //check that all types of gossip messages are signed correctly
         cluster_info.flush_push_queue();
-        let (push_messages, _, _) =
-            cluster_info
-                .gossip
-                .new_push_messages(&cluster_info.id(), timestamp(), &stakes);
+        let (push_messages, _, _) = cluster_info.gossip.new_push_messages(
+            &cluster_info.id(),
+            timestamp(),
+            &stakes,
+            |_, _| true,
+        );
         // there should be some pushes ready
         assert!(!push_messages.is_empty());
         push_messages
@@ -1,5 +1,8 @@
 use {
-    crate::crds_gossip::CrdsGossip,
+    crate::{
+        crds_gossip::CrdsGossip,
+        crds_value::{CRDS_DATA_NAMES, NUM_CRDS_DATA_VARIANTS},
+    },
     itertools::Itertools,
     solana_measure::measure::Measure,
     solana_sdk::{clock::Slot, pubkey::Pubkey},
@@ -23,7 +26,7 @@ impl Counter {
     pub(crate) fn add_relaxed(&self, x: u64) {
         self.0.fetch_add(x, Ordering::Relaxed);
     }
-    fn clear(&self) -> u64 {
+    pub(crate) fn clear(&self) -> u64 {
         self.0.swap(0, Ordering::Relaxed)
     }
 }
@@ -87,10 +90,21 @@ impl<'a, T> Drop for TimedGuard<'a, T> {
     }
 }
 
+/// Number of crds values of each variant sent or received, and dropped for
+/// being over their variant's budget, indexed by CrdsData::ordinal.
+#[derive(Default)]
+pub(crate) struct CrdsDataBudgetStats {
+    pub(crate) egress: [Counter; NUM_CRDS_DATA_VARIANTS],
+    pub(crate) egress_dropped: [Counter; NUM_CRDS_DATA_VARIANTS],
+    pub(crate) ingress: [Counter; NUM_CRDS_DATA_VARIANTS],
+    pub(crate) ingress_dropped: [Counter; NUM_CRDS_DATA_VARIANTS],
+}
+
 #[derive(Default)]
 pub struct GossipStats {
     pub(crate) all_tvu_peers: Counter,
     pub(crate) bad_prune_destination: Counter,
+    pub(crate) crds_data_budget: CrdsDataBudgetStats,
     pub(crate) entrypoint2: Counter,
     pub(crate) entrypoint: Counter,
     pub(crate) epoch_slots_filled: Counter,
@@ -687,6 +701,7 @@ pub(crate) fn submit_gossip_stats(
         ("all-push", crds_stats.push.fails.iter().sum::<usize>(), i64),
         ("all-pull", crds_stats.pull.fails.iter().sum::<usize>(), i64),
     );
+    submit_crds_data_budget_stats(&stats.crds_data_budget);
     if !log::log_enabled!(log::Level::Trace) {
         return;
     }
@@ -702,6 +717,26 @@ pub(crate) fn submit_gossip_stats(
     submit_vote_stats("cluster_info_crds_stats_votes", &votes);
 }
 
+fn submit_crds_data_budget_stats(stats: &CrdsDataBudgetStats) {
+    for (k, name) in CRDS_DATA_NAMES.iter().enumerate() {
+        let egress = stats.egress[k].clear();
+        let egress_dropped = stats.egress_dropped[k].clear();
+        let ingress = stats.ingress[k].clear();
+        let ingress_dropped = stats.ingress_dropped[k].clear();
+        if egress == 0 && egress_dropped == 0 && ingress == 0 && ingress_dropped == 0 {
+            continue;
+        }
+        datapoint_info!(
+            "cluster_info_crds_data_budget",
+            "variant" => *name,
+            ("egress", egress, i64),
+            ("egress_dropped", egress_dropped, i64),
+            ("ingress", ingress, i64),
+            ("ingress_dropped", ingress_dropped, i64),
+        );
+    }
+}
+
 fn submit_vote_stats<'a, I>(name: &'static str, votes: I)
 where
     I: IntoIterator<Item = (&'a Slot, /*num-votes:*/ &'a usize)>,
@@ -29,7 +29,7 @@ use {
         crds_entry::CrdsEntry,
         crds_gossip_pull::CrdsTimeouts,
         crds_shards::CrdsShards,
-        crds_value::{CrdsData, CrdsValue, CrdsValueLabel},
+        crds_value::{CrdsData, CrdsValue, CrdsValueLabel, NUM_CRDS_DATA_VARIANTS},
         legacy_contact_info::LegacyContactInfo as ContactInfo,
     },
     assert_matches::debug_assert_matches,
@@ -103,7 +103,7 @@ pub enum GossipRoute<'a> {
     PushMessage(/*from:*/ &'a Pubkey),
 }
 
-type CrdsCountsArray = [usize; 13];
+type CrdsCountsArray = [usize; NUM_CRDS_DATA_VARIANTS];
 
 pub(crate) struct CrdsDataStats {
     pub(crate) counts: CrdsCountsArray,
@@ -147,6 +147,12 @@ impl Cursor {
 }
 
 impl VersionedCrdsValue {
+    /// Returns a cursor positioned at this value, so that iterating from the
+    /// cursor yields this value again.
+    pub(crate) fn cursor(&self) -> Cursor {
+        Cursor(self.ordinal)
+    }
+
     fn new(value: CrdsValue, cursor: Cursor, local_timestamp: u64) -> Self {
         let value_hash = hash(&serialize(&value).unwrap());
         VersionedCrdsValue {
@@ -708,22 +714,7 @@ impl CrdsDataStats {
     }
 
     fn ordinal(entry: &VersionedCrdsValue) -> usize {
-        match &entry.value.data {
-            CrdsData::LegacyContactInfo(_) => 0,
-            CrdsData::Vote(_, _) => 1,
-            CrdsData::LowestSlot(_, _) => 2,
-            CrdsData::LegacySnapshotHashes(_) => 3,
-            CrdsData::AccountsHashes(_) => 4,
-            CrdsData::EpochSlots(_, _) => 5,
-            CrdsData::LegacyVersion(_) => 6,
-            CrdsData::Version(_) => 7,
-            CrdsData::NodeInstance(_) => 8,
-            CrdsData::DuplicateShred(_, _) => 9,
-            CrdsData::SnapshotHashes(_) => 10,
-            CrdsData::ContactInfo(_) => 11,
-            CrdsData::RestartLastVotedForkSlots(_) => 12,
-            // Update CrdsCountsArray if new items are added here.
-        }
+        entry.value.data.ordinal()
     }
 }
 
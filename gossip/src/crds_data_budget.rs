@@ -0,0 +1,424 @@
+//! Per CrdsData variant bandwidth budgets and priorities.
+//!
+//! All kinds of crds values compete for the same push and pull bandwidth, so
+//! a burst of one kind, e.g. EpochSlots churn while the cluster restarts, can
+//! crowd out ContactInfo propagation and delay peer discovery. Each variant
+//! may be given:
+//!   * an egress budget, bounding the bytes per second of the variant sent in
+//!     push messages and pull responses,
+//!   * an ingress budget, bounding the bytes per second of the variant
+//!     accepted from push messages and pull responses. All unstaked origins
+//!     share a tenth of it, and staked origins split the rest by stake, so
+//!     that one origin cannot use up the budget of the others.
+//! Values over budget are dropped, except in push messages: those stop at the
+//! first value over budget, which is pushed once the budget refills. Values
+//! are also sent and processed in priority order, so that the variants listed
+//! first get the shared pull-response budget ahead of the others.
+
+use {
+    crate::{
+        cluster_info_metrics::CrdsDataBudgetStats,
+        crds_value::{CrdsValue, CRDS_DATA_NAMES, NUM_CRDS_DATA_VARIANTS},
+    },
+    bincode::serialized_size,
+    lru::LruCache,
+    solana_perf::{data_budget::DataBudget, packet::PACKET_DATA_SIZE},
+    solana_sdk::pubkey::Pubkey,
+    std::{collections::HashMap, sync::Mutex, time::Instant},
+};
+
+const BUDGET_UPDATE_INTERVAL_MS: u64 = 100;
+// Percentage of the ingress budget of a variant shared by all unstaked origins.
+const UNSTAKED_INGRESS_PERCENT: u64 = 10;
+// Lower bound of the ingress budget of each staked origin, so that origins
+// with little stake can still send the occasional value.
+const MIN_STAKED_INGRESS_BYTES_PER_SEC: u64 = PACKET_DATA_SIZE as u64;
+// Number of staked origins whose ingress budgets are tracked per variant.
+const MAX_STAKED_INGRESS_ORIGINS: usize = 8192;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CrdsDataBudgetConfig {
+    /// Bytes per second of each variant which may be sent in push messages
+    /// and pull responses, keyed by variant name. Unlisted variants are not
+    /// limited.
+    pub egress_bytes_per_sec: HashMap<String, u64>,
+    /// Bytes per second of each variant which are accepted from push
+    /// messages and pull responses, keyed by variant name. Unlisted variants
+    /// are not limited.
+    pub ingress_bytes_per_sec: HashMap<String, u64>,
+    /// Variants sent and processed ahead of all others, highest priority
+    /// first. Without any, values keep their usual order.
+    pub priority: Vec<String>,
+}
+
+/// Parses a `VARIANT:BYTES_PER_SEC` budget, e.g. `EpochSlots:65536`.
+pub fn parse_crds_data_budget(arg: &str) -> Result<(String, u64), String> {
+    let (name, bytes_per_sec) = arg
+        .split_once(':')
+        .ok_or_else(|| format!("expected VARIANT:BYTES_PER_SEC, got {arg}"))?;
+    let name = parse_crds_data_name(name)?;
+    let bytes_per_sec = bytes_per_sec
+        .parse()
+        .map_err(|err| format!("invalid bytes per second {bytes_per_sec}: {err}"))?;
+    Ok((name, bytes_per_sec))
+}
+
+/// Checks that the name is one of the CrdsData variants.
+pub fn parse_crds_data_name(name: &str) -> Result<String, String> {
+    match crds_data_ordinal(name) {
+        Some(_) => Ok(name.to_string()),
+        None => Err(format!(
+            "unknown crds data variant {name}, expected one of {}",
+            CRDS_DATA_NAMES.join(", ")
+        )),
+    }
+}
+
+fn crds_data_ordinal(name: &str) -> Option<usize> {
+    CRDS_DATA_NAMES.iter().position(|other| *other == name)
+}
+
+// Token bucket refilled every BUDGET_UPDATE_INTERVAL_MS, holding at most one
+// second worth of bytes.
+struct Budget {
+    bytes_per_sec: usize,
+    budget: DataBudget,
+}
+
+impl Budget {
+    fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: usize::try_from(bytes_per_sec).unwrap_or(usize::MAX),
+            budget: DataBudget::default(),
+        }
+    }
+
+    fn take(&self, size: usize) -> bool {
+        let bytes_per_interval =
+            (self.bytes_per_sec / (1000 / BUDGET_UPDATE_INTERVAL_MS as usize)).max(1);
+        // Allow for at least one full packet, otherwise large values would
+        // never fit in small budgets.
+        let max_bytes = self.bytes_per_sec.max(PACKET_DATA_SIZE);
+        self.budget.update(BUDGET_UPDATE_INTERVAL_MS, |bytes| {
+            bytes.saturating_add(bytes_per_interval).min(max_bytes)
+        });
+        self.budget.take(size)
+    }
+}
+
+// Token bucket which is refilled continuously, so that it also fills up for
+// origins which send rarely, holding at most one second worth of bytes or one
+// packet, whichever is more.
+struct TokenBucket {
+    bytes: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(bytes_per_sec: u64, now: Instant) -> Self {
+        Self {
+            bytes: Self::max_bytes(bytes_per_sec),
+            last_refill: now,
+        }
+    }
+
+    fn max_bytes(bytes_per_sec: u64) -> f64 {
+        bytes_per_sec.max(PACKET_DATA_SIZE as u64) as f64
+    }
+
+    fn take(&mut self, bytes_per_sec: u64, size: usize, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.last_refill = self.last_refill.max(now);
+        self.bytes = (self.bytes + elapsed.as_secs_f64() * bytes_per_sec as f64)
+            .min(Self::max_bytes(bytes_per_sec));
+        if self.bytes < size as f64 {
+            return false;
+        }
+        self.bytes -= size as f64;
+        true
+    }
+}
+
+// Ingress budget of a variant, split among the origins of the values.
+struct IngressBudget {
+    bytes_per_sec: u64,
+    buckets: Mutex<IngressBuckets>,
+}
+
+struct IngressBuckets {
+    // Shared by all unstaked origins.
+    unstaked: Option<TokenBucket>,
+    staked: LruCache<Pubkey, TokenBucket>,
+}
+
+impl IngressBudget {
+    fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            buckets: Mutex::new(IngressBuckets {
+                unstaked: None,
+                staked: LruCache::new(MAX_STAKED_INGRESS_ORIGINS),
+            }),
+        }
+    }
+
+    fn take(
+        &self,
+        origin: &Pubkey,
+        stake: u64,
+        total_stake: u64,
+        size: usize,
+        now: Instant,
+    ) -> bool {
+        let unstaked_bytes_per_sec = self.bytes_per_sec / 100 * UNSTAKED_INGRESS_PERCENT;
+        let mut buckets = self.buckets.lock().unwrap();
+        if stake == 0 || total_stake == 0 {
+            return buckets
+                .unstaked
+                .get_or_insert_with(|| TokenBucket::new(unstaked_bytes_per_sec, now))
+                .take(unstaked_bytes_per_sec, size, now);
+        }
+        let staked_bytes_per_sec = self.bytes_per_sec - unstaked_bytes_per_sec;
+        let bytes_per_sec = u128::from(staked_bytes_per_sec) * u128::from(stake.min(total_stake))
+            / u128::from(total_stake);
+        let bytes_per_sec = (bytes_per_sec as u64).max(MIN_STAKED_INGRESS_BYTES_PER_SEC);
+        if let Some(bucket) = buckets.staked.get_mut(origin) {
+            return bucket.take(bytes_per_sec, size, now);
+        }
+        let mut bucket = TokenBucket::new(bytes_per_sec, now);
+        let taken = bucket.take(bytes_per_sec, size, now);
+        buckets.staked.put(*origin, bucket);
+        taken
+    }
+}
+
+pub(crate) struct CrdsDataBudgets {
+    // Position of each variant in the priority ordering; lower goes first.
+    ranks: [usize; NUM_CRDS_DATA_VARIANTS],
+    has_priority: bool,
+    egress: [Option<Budget>; NUM_CRDS_DATA_VARIANTS],
+    ingress: [Option<IngressBudget>; NUM_CRDS_DATA_VARIANTS],
+}
+
+impl CrdsDataBudgets {
+    pub(crate) fn new(config: &CrdsDataBudgetConfig) -> Self {
+        fn new_budgets<T>(
+            bytes_per_sec: &HashMap<String, u64>,
+            new_budget: impl Fn(u64) -> T,
+        ) -> [Option<T>; NUM_CRDS_DATA_VARIANTS] {
+            let mut budgets: [Option<T>; NUM_CRDS_DATA_VARIANTS] = Default::default();
+            for (name, bytes_per_sec) in bytes_per_sec {
+                match crds_data_ordinal(name) {
+                    Some(k) => budgets[k] = Some(new_budget(*bytes_per_sec)),
+                    None => warn!("ignoring budget for unknown crds data variant {name}"),
+                }
+            }
+            budgets
+        }
+        let mut ranks = [config.priority.len(); NUM_CRDS_DATA_VARIANTS];
+        // Iterate in reverse so that the first occurrence of a name wins.
+        for (rank, name) in config.priority.iter().enumerate().rev() {
+            match crds_data_ordinal(name) {
+                Some(k) => ranks[k] = rank,
+                None => warn!("ignoring priority of unknown crds data variant {name}"),
+            }
+        }
+        Self {
+            ranks,
+            has_priority: !config.priority.is_empty(),
+            egress: new_budgets(&config.egress_bytes_per_sec, Budget::new),
+            ingress: new_budgets(&config.ingress_bytes_per_sec, IngressBudget::new),
+        }
+    }
+
+    /// Sorts the values by the priority of their variant, otherwise
+    /// preserving their relative order.
+    pub(crate) fn sort_by_priority<T, F>(&self, values: &mut [T], value: F)
+    where
+        F: Fn(&T) -> &CrdsValue,
+    {
+        if self.has_priority {
+            values.sort_by_key(|entry| self.ranks[value(entry).data.ordinal()]);
+        }
+    }
+
+    /// Returns true if the given number of copies of the value fit within
+    /// the egress budget of its variant, in which case the budget is
+    /// consumed.
+    pub(crate) fn take_egress(
+        &self,
+        value: &CrdsValue,
+        num_copies: usize,
+        stats: &CrdsDataBudgetStats,
+    ) -> bool {
+        let k = value.data.ordinal();
+        let taken = match &self.egress[k] {
+            None => true,
+            Some(budget) => match serialized_size(value) {
+                Ok(size) => budget.take((size as usize).saturating_mul(num_copies)),
+                Err(_) => false,
+            },
+        };
+        if taken {
+            stats.egress[k].add_relaxed(num_copies as u64);
+        } else {
+            stats.egress_dropped[k].add_relaxed(num_copies as u64);
+        }
+        taken
+    }
+
+    /// Returns true if the value fits within the ingress budget of its
+    /// variant left to its origin, in which case the budget is consumed.
+    pub(crate) fn take_ingress(
+        &self,
+        value: &CrdsValue,
+        stakes: &HashMap<Pubkey, u64>,
+        total_stake: u64,
+        stats: &CrdsDataBudgetStats,
+    ) -> bool {
+        let k = value.data.ordinal();
+        let taken = match &self.ingress[k] {
+            None => true,
+            Some(budget) => match serialized_size(value) {
+                Ok(size) => {
+                    let origin = value.pubkey();
+                    let stake = stakes.get(&origin).copied().unwrap_or_default();
+                    budget.take(&origin, stake, total_stake, size as usize, Instant::now())
+                }
+                Err(_) => false,
+            },
+        };
+        if taken {
+            stats.ingress[k].add_relaxed(1);
+        } else {
+            stats.ingress_dropped[k].add_relaxed(1);
+        }
+        taken
+    }
+}
+
+impl Default for CrdsDataBudgets {
+    fn default() -> Self {
+        Self::new(&CrdsDataBudgetConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            crds_value::{CrdsData, NodeInstance},
+            legacy_contact_info::LegacyContactInfo,
+        },
+        solana_sdk::timing::timestamp,
+        std::time::Duration,
+    };
+
+    fn new_node_instance() -> CrdsValue {
+        let node = NodeInstance::new(&mut rand::thread_rng(), Pubkey::new_unique(), timestamp());
+        CrdsValue::new_unsigned(CrdsData::NodeInstance(node))
+    }
+
+    fn new_contact_info() -> CrdsValue {
+        let node = LegacyContactInfo::new_localhost(&Pubkey::new_unique(), timestamp());
+        CrdsValue::new_unsigned(CrdsData::LegacyContactInfo(node))
+    }
+
+    #[test]
+    fn test_parse_crds_data_budget() {
+        assert_eq!(
+            parse_crds_data_budget("EpochSlots:65536"),
+            Ok(("EpochSlots".to_string(), 65536))
+        );
+        assert!(parse_crds_data_budget("EpochSlots").is_err());
+        assert!(parse_crds_data_budget("EpochSlots:lots").is_err());
+        assert!(parse_crds_data_budget("EpochSlot:65536").is_err());
+        assert_eq!(parse_crds_data_name("Vote"), Ok("Vote".to_string()));
+        assert!(parse_crds_data_name("vote").is_err());
+    }
+
+    #[test]
+    fn test_crds_data_budgets_priority() {
+        let budgets = CrdsDataBudgets::new(&CrdsDataBudgetConfig {
+            priority: vec!["ContactInfo".to_string(), "LegacyContactInfo".to_string()],
+            ..CrdsDataBudgetConfig::default()
+        });
+        let mut values: Vec<_> = (0..4)
+            .flat_map(|_| [new_node_instance(), new_contact_info()])
+            .collect();
+        let expected: Vec<_> = values
+            .iter()
+            .filter(|value| value.data.name() == "LegacyContactInfo")
+            .chain(
+                values
+                    .iter()
+                    .filter(|value| value.data.name() == "NodeInstance"),
+            )
+            .cloned()
+            .collect();
+        budgets.sort_by_priority(&mut values, |value| value);
+        assert_eq!(values, expected);
+        // Without any priorities the order is unchanged.
+        let budgets = CrdsDataBudgets::default();
+        let mut other: Vec<_> = values.iter().rev().cloned().collect();
+        budgets.sort_by_priority(&mut other, |value| value);
+        assert!(other.into_iter().eq(values.into_iter().rev()));
+    }
+
+    #[test]
+    fn test_crds_data_budgets_take() {
+        let value = new_node_instance();
+        let size = serialized_size(&value).unwrap() as usize;
+        let config = CrdsDataBudgetConfig {
+            egress_bytes_per_sec: HashMap::from([(
+                "NodeInstance".to_string(),
+                // Refills 3 values worth of bytes each interval.
+                3 * size as u64 * 1000 / BUDGET_UPDATE_INTERVAL_MS,
+            )]),
+            ..CrdsDataBudgetConfig::default()
+        };
+        let budgets = CrdsDataBudgets::new(&config);
+        let stats = CrdsDataBudgetStats::default();
+        let k = value.data.ordinal();
+        let num_sent = (0..10)
+            .filter(|_| budgets.take_egress(&value, 1, &stats))
+            .count();
+        assert_eq!(num_sent, 3);
+        assert_eq!(stats.egress_dropped[k].clear(), 7);
+        // Ingress and other variants are not limited.
+        let stakes = HashMap::default();
+        assert!((0..10).all(|_| budgets.take_ingress(&value, &stakes, 0, &stats)));
+        let node = new_contact_info();
+        assert!((0..10).all(|_| budgets.take_egress(&node, 2, &stats)));
+        assert_eq!(stats.egress[node.data.ordinal()].clear(), 20);
+    }
+
+    #[test]
+    fn test_ingress_budget() {
+        const SIZE: usize = 100;
+        let budget = IngressBudget::new(100_000);
+        let now = Instant::now();
+        let take_all = |origin: &Pubkey, stake: u64, now: Instant| {
+            (0..10_000)
+                .take_while(|_| budget.take(origin, stake, 10, SIZE, now))
+                .count()
+        };
+        // Staked origins get budgets in proportion to their stake, which do
+        // not run out when another origin uses up its own.
+        let (origin, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(take_all(&origin, 2, now), 18_000 / SIZE);
+        assert_eq!(take_all(&other, 1, now), 9_000 / SIZE);
+        assert_eq!(take_all(&origin, 2, now), 0);
+        // Half a second later, half of the budget is refilled.
+        let later = now + Duration::from_millis(500);
+        assert_eq!(take_all(&origin, 2, later), 9_000 / SIZE);
+        // Unstaked origins share a tenth of the budget.
+        assert_eq!(take_all(&Pubkey::new_unique(), 0, now), 10_000 / SIZE);
+        assert_eq!(take_all(&Pubkey::new_unique(), 0, now), 0);
+        // Origins with little stake still get one packet worth of bytes.
+        let small = Pubkey::new_unique();
+        assert!(budget.take(&small, 1, 1_000_000, PACKET_DATA_SIZE, now));
+        assert!(!budget.take(&small, 1, 1_000_000, 1, now));
+    }
+}
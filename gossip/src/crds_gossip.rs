@@ -69,17 +69,22 @@ impl CrdsGossip {
         self.push.prune_received_cache(self_pubkey, origins, stakes)
     }
 
-    pub fn new_push_messages(
+    pub fn new_push_messages<F>(
         &self,
         pubkey: &Pubkey, // This node.
         now: u64,
         stakes: &HashMap<Pubkey, u64>,
+        take_egress: F,
     ) -> (
         HashMap<Pubkey, Vec<CrdsValue>>,
         usize, // number of values
         usize, // number of push messages
-    ) {
-        self.push.new_push_messages(pubkey, &self.crds, now, stakes)
+    )
+    where
+        F: FnMut(&CrdsValue, /*num_nodes:*/ usize) -> bool,
+    {
+        self.push
+            .new_push_messages(pubkey, &self.crds, now, stakes, take_egress)
     }
 
     pub(crate) fn push_duplicate_shred<F>(
@@ -158,7 +158,8 @@ impl CrdsFilterSet {
         for _ in 0..MAX_NUM_FILTERS.min(size) {
             let k = rng.gen_range(0..indices.len());
             let k = indices.swap_remove(k);
-            let filter = Bloom::random(max_items as usize, FALSE_RATE, max_bits as usize);
+            let filter =
+                Bloom::random_with_rng(rng, max_items as usize, FALSE_RATE, max_bits as usize);
             filters[k] = Some(AtomicBloom::<Hash>::from(filter));
         }
         Self { filters, mask_bits }
@@ -416,6 +417,18 @@ impl CrdsGossipPull {
         thread_pool: &ThreadPool,
         crds: &RwLock<Crds>,
         bloom_size: usize,
+    ) -> Vec<CrdsFilter> {
+        self.build_crds_filters_with_rng(&mut rand::thread_rng(), thread_pool, crds, bloom_size)
+    }
+
+    // Same as build_crds_filters, but the sampled filters and their keys are
+    // drawn from the given rng.
+    pub(crate) fn build_crds_filters_with_rng<R: Rng>(
+        &self,
+        rng: &mut R,
+        thread_pool: &ThreadPool,
+        crds: &RwLock<Crds>,
+        bloom_size: usize,
     ) -> Vec<CrdsFilter> {
         const PAR_MIN_LENGTH: usize = 512;
         #[cfg(debug_assertions)]
@@ -427,7 +440,7 @@ impl CrdsGossipPull {
         let crds = crds.read().unwrap();
         let num_items = crds.len() + crds.num_purged() + failed_inserts.len();
         let num_items = MIN_NUM_BLOOM_ITEMS.max(num_items);
-        let filters = CrdsFilterSet::new(&mut rand::thread_rng(), num_items, bloom_size);
+        let filters = CrdsFilterSet::new(rng, num_items, bloom_size);
         thread_pool.install(|| {
             crds.par_values()
                 .with_min_len(PAR_MIN_LENGTH)
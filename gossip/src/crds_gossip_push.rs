@@ -23,6 +23,7 @@ use {
     },
     bincode::serialized_size,
     itertools::Itertools,
+    rand::Rng,
     solana_sdk::{
         packet::PACKET_DATA_SIZE,
         pubkey::Pubkey,
@@ -63,7 +64,10 @@ pub struct CrdsGossipPush {
     /// This cache represents a lagging view of which validators
     /// currently have this node in their `active_set`
     received_cache: Mutex<ReceivedCache>,
-    push_fanout: usize,
+    /// Number of active set nodes each value is pushed to
+    pub(crate) push_fanout: usize,
+    /// Number of nodes retained in each push active set entry
+    pub(crate) active_set_size: usize,
     pub(crate) msg_timeout: u64,
     pub prune_timeout: u64,
     pub num_total: AtomicUsize,
@@ -80,6 +84,7 @@ impl Default for CrdsGossipPush {
             crds_cursor: Mutex::default(),
             received_cache: Mutex::new(ReceivedCache::new(2 * CRDS_UNIQUE_PUBKEY_CAPACITY)),
             push_fanout: CRDS_GOSSIP_PUSH_FANOUT,
+            active_set_size: CRDS_GOSSIP_PUSH_ACTIVE_SET_SIZE,
             msg_timeout: CRDS_GOSSIP_PUSH_MSG_TIMEOUT_MS,
             prune_timeout: CRDS_GOSSIP_PRUNE_MSG_TIMEOUT_MS,
             num_total: AtomicUsize::default(),
@@ -169,17 +174,24 @@ impl CrdsGossipPush {
     /// peers.
     /// The list of push messages is created such that all the randomly selected peers have not
     /// pruned the source addresses.
-    pub(crate) fn new_push_messages(
+    /// `take_egress` is called with each value and the number of peers it is
+    /// pushed to; the push messages stop at the first value for which it
+    /// returns false, and that value is the first one pushed next time.
+    pub(crate) fn new_push_messages<F>(
         &self,
         pubkey: &Pubkey, // This node.
         crds: &RwLock<Crds>,
         now: u64,
         stakes: &HashMap<Pubkey, u64>,
+        mut take_egress: F,
     ) -> (
         HashMap<Pubkey, Vec<CrdsValue>>,
         usize, // number of values
         usize, // number of push messages
-    ) {
+    )
+    where
+        F: FnMut(&CrdsValue, /*num_nodes:*/ usize) -> bool,
+    {
         let active_set = self.active_set.read().unwrap();
         let mut num_pushes = 0;
         let mut num_values = 0;
@@ -189,6 +201,8 @@ impl CrdsGossipPush {
         let mut crds_cursor = self.crds_cursor.lock().unwrap();
         // crds should be locked last after self.{active_set,crds_cursor}.
         let crds = crds.read().unwrap();
+        // Cursor to resume from if a value is over the egress budget.
+        let mut over_budget = None;
         let entries = crds
             .get_entries(crds_cursor.deref_mut())
             .filter(|entry| {
@@ -196,27 +210,37 @@ impl CrdsGossipPush {
                 // until the cluster has upgraded.
                 !matches!(&entry.value.data, CrdsData::ContactInfo(_))
             })
-            .map(|entry| &entry.value)
-            .filter(|value| wallclock_window.contains(&value.wallclock()));
-        for value in entries {
+            .filter(|entry| wallclock_window.contains(&entry.value.wallclock()));
+        for entry in entries {
+            let value = &entry.value;
             let serialized_size = serialized_size(&value).unwrap();
             total_bytes = total_bytes.saturating_add(serialized_size as usize);
             if total_bytes > self.max_bytes {
                 break;
             }
-            num_values += 1;
             let origin = value.pubkey();
-            let nodes = active_set.get_nodes(
-                pubkey,
-                &origin,
-                |node| value.should_force_push(node),
-                stakes,
-            );
-            for node in nodes.take(self.push_fanout) {
+            let nodes: Vec<_> = active_set
+                .get_nodes(
+                    pubkey,
+                    &origin,
+                    |node| value.should_force_push(node),
+                    stakes,
+                )
+                .take(self.push_fanout)
+                .collect();
+            if !nodes.is_empty() && !take_egress(value, nodes.len()) {
+                over_budget = Some(entry.cursor());
+                break;
+            }
+            num_values += 1;
+            for node in nodes {
                 push_messages.entry(*node).or_default().push(value.clone());
                 num_pushes += 1;
             }
         }
+        if let Some(cursor) = over_budget {
+            *crds_cursor = cursor;
+        }
         drop(crds);
         drop(crds_cursor);
         drop(active_set);
@@ -274,16 +298,36 @@ impl CrdsGossipPush {
             .into_values()
             .map(|(_stake, node)| *node.pubkey())
             .collect::<Vec<_>>();
+        let cluster_size = crds.read().unwrap().num_pubkeys().max(stakes.len());
+        self.rotate_push_active_set(
+            &mut rng,
+            &mut rand::thread_rng(),
+            cluster_size,
+            &nodes,
+            stakes,
+        )
+    }
+
+    /// Rotates the push active set over the given gossip nodes. The keys of
+    /// new bloom filters are drawn from `bloom_rng`.
+    pub(crate) fn rotate_push_active_set<R: Rng, B: Rng>(
+        &self,
+        rng: &mut R,
+        bloom_rng: &mut B,
+        cluster_size: usize,
+        nodes: &[Pubkey],
+        stakes: &HashMap<Pubkey, u64>,
+    ) {
         if nodes.is_empty() {
             return;
         }
-        let cluster_size = crds.read().unwrap().num_pubkeys().max(stakes.len());
         let mut active_set = self.active_set.write().unwrap();
         active_set.rotate(
-            &mut rng,
-            CRDS_GOSSIP_PUSH_ACTIVE_SET_SIZE,
+            rng,
+            bloom_rng,
+            self.active_set_size,
             cluster_size,
-            &nodes,
+            nodes,
             stakes,
         )
     }
@@ -433,6 +477,7 @@ mod tests {
                 &crds,
                 0,
                 &HashMap::<Pubkey, u64>::default(), // stakes
+                |_, _| true,                        // take_egress
             )
             .0,
             expected
@@ -497,6 +542,7 @@ mod tests {
                 &crds,
                 now,
                 &HashMap::<Pubkey, u64>::default(), // stakes
+                |_, _| true,                        // take_egress
             )
             .0,
             expected
@@ -548,6 +594,7 @@ mod tests {
                 &crds,
                 0,
                 &HashMap::<Pubkey, u64>::default(), // stakes
+                |_, _| true,                        // take_egress
             )
             .0,
             expected
@@ -589,6 +636,7 @@ mod tests {
                 &crds,
                 0,
                 &HashMap::<Pubkey, u64>::default(), // stakes
+                |_, _| true,                        // take_egress
             )
             .0,
             expected
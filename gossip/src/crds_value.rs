@@ -36,6 +36,24 @@ pub type VoteIndex = u8;
 // the fleet is updated to the new ClusterInfo::push_vote code.
 pub const MAX_VOTES: VoteIndex = 32;
 
+pub const NUM_CRDS_DATA_VARIANTS: usize = 13;
+/// Names of the CrdsData variants, indexed by CrdsData::ordinal.
+pub const CRDS_DATA_NAMES: [&str; NUM_CRDS_DATA_VARIANTS] = [
+    "LegacyContactInfo",
+    "Vote",
+    "LowestSlot",
+    "LegacySnapshotHashes",
+    "AccountsHashes",
+    "EpochSlots",
+    "LegacyVersion",
+    "Version",
+    "NodeInstance",
+    "DuplicateShred",
+    "SnapshotHashes",
+    "ContactInfo",
+    "RestartLastVotedForkSlots",
+];
+
 pub type EpochSlotsIndex = u8;
 pub const MAX_EPOCH_SLOTS: EpochSlotsIndex = 255;
 
@@ -145,6 +163,32 @@ pub(crate) fn new_rand_timestamp<R: Rng>(rng: &mut R) -> u64 {
 }
 
 impl CrdsData {
+    /// Name of the variant, as used on the command line and in metrics.
+    pub fn name(&self) -> &'static str {
+        CRDS_DATA_NAMES[self.ordinal()]
+    }
+
+    /// Index of the variant, for per-variant stats and budgets.
+    pub(crate) fn ordinal(&self) -> usize {
+        match self {
+            CrdsData::LegacyContactInfo(_) => 0,
+            CrdsData::Vote(..) => 1,
+            CrdsData::LowestSlot(..) => 2,
+            CrdsData::LegacySnapshotHashes(_) => 3,
+            CrdsData::AccountsHashes(_) => 4,
+            CrdsData::EpochSlots(..) => 5,
+            CrdsData::LegacyVersion(_) => 6,
+            CrdsData::Version(_) => 7,
+            CrdsData::NodeInstance(_) => 8,
+            CrdsData::DuplicateShred(..) => 9,
+            CrdsData::SnapshotHashes(_) => 10,
+            CrdsData::ContactInfo(_) => 11,
+            CrdsData::RestartLastVotedForkSlots(_) => 12,
+            // Update NUM_CRDS_DATA_VARIANTS and CRDS_DATA_NAMES if new items
+            // are added here.
+        }
+    }
+
     /// New random CrdsData for tests and benchmarks.
     fn new_rand<R: Rng>(rng: &mut R, pubkey: Option<Pubkey>) -> CrdsData {
         let kind = rng.gen_range(0..8);
@@ -0,0 +1,564 @@
+//! The `gossip_capture` module records the gossip packets a node sends and
+//! receives into a rotating set of files, and reads them back for offline
+//! analysis with `solana-gossip capture`.
+//!
+//! Each capture file is a sequence of bincode serialized `CapturedPacket`s.
+//! Packets are captured before they are sanitized or verified, so that
+//! malformed traffic shows up as well.
+
+use {
+    crate::{
+        cluster_info::{ClusterInfo, Protocol},
+        crds::GossipRoute,
+        crds_value::CrdsValue,
+    },
+    crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender},
+    solana_perf::packet::PacketBatch,
+    solana_sdk::{pubkey::Pubkey, signature::Signable, timing::timestamp},
+    solana_streamer::streamer::{PacketBatchReceiver, PacketBatchSender},
+    std::{
+        collections::BTreeMap,
+        fs::{self, File},
+        io::{self, BufReader, BufWriter, Write},
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        thread::{Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+const CAPTURE_FILE_EXTENSION: &str = "capture";
+// Packets are dropped from the capture, rather than holding up gossip, once
+// this many are waiting to be written.
+const MAX_PENDING_PACKETS: usize = 65_536;
+const STATS_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub struct GossipCaptureConfig {
+    /// Directory the capture files are written to
+    pub dir: PathBuf,
+    /// A new file is started once the current one reaches this size
+    pub max_file_size: u64,
+    /// The oldest files are removed to keep at most this many
+    pub max_files: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Direction {
+    Received,
+    Sent,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CapturedPacket {
+    /// Wallclock in milliseconds at which the packet was captured
+    pub timestamp: u64,
+    pub direction: Direction,
+    /// Source address of received packets, destination of sent ones
+    pub addr: SocketAddr,
+    pub data: Vec<u8>,
+}
+
+/// The parts of a gossip `Protocol` message relevant to offline analysis.
+#[derive(Clone, Debug)]
+pub enum CapturedMessage {
+    PullRequest {
+        caller: CrdsValue,
+    },
+    PullResponse {
+        from: Pubkey,
+        values: Vec<CrdsValue>,
+    },
+    PushMessage {
+        from: Pubkey,
+        values: Vec<CrdsValue>,
+    },
+    PruneMessage {
+        from: Pubkey,
+        destination: Pubkey,
+        prunes: Vec<Pubkey>,
+    },
+    PingMessage {
+        from: Pubkey,
+    },
+    PongMessage {
+        from: Pubkey,
+    },
+}
+
+impl CapturedPacket {
+    /// Returns None if the packet is not a valid gossip message.
+    pub fn decode(&self) -> Option<CapturedMessage> {
+        let protocol: Protocol = bincode::deserialize(&self.data).ok()?;
+        let message = match protocol {
+            Protocol::PullRequest(_, caller) => CapturedMessage::PullRequest { caller },
+            Protocol::PullResponse(from, values) => CapturedMessage::PullResponse { from, values },
+            Protocol::PushMessage(from, values) => CapturedMessage::PushMessage { from, values },
+            Protocol::PruneMessage(from, data) => CapturedMessage::PruneMessage {
+                from,
+                destination: data.destination,
+                prunes: data.prunes,
+            },
+            Protocol::PingMessage(ping) => CapturedMessage::PingMessage { from: *ping.from() },
+            Protocol::PongMessage(pong) => CapturedMessage::PongMessage { from: *pong.from() },
+        };
+        Some(message)
+    }
+}
+
+impl CapturedMessage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PullRequest { .. } => "PullRequest",
+            Self::PullResponse { .. } => "PullResponse",
+            Self::PushMessage { .. } => "PushMessage",
+            Self::PruneMessage { .. } => "PruneMessage",
+            Self::PingMessage { .. } => "PingMessage",
+            Self::PongMessage { .. } => "PongMessage",
+        }
+    }
+
+    /// The node which sent the message.
+    pub fn from(&self) -> Pubkey {
+        match self {
+            Self::PullRequest { caller } => caller.pubkey(),
+            Self::PullResponse { from, .. }
+            | Self::PushMessage { from, .. }
+            | Self::PruneMessage { from, .. }
+            | Self::PingMessage { from }
+            | Self::PongMessage { from } => *from,
+        }
+    }
+
+    /// Crds values carried by the message; for pull requests this is the
+    /// contact-info of the caller.
+    pub fn values(&self) -> &[CrdsValue] {
+        match self {
+            Self::PullRequest { caller } => std::slice::from_ref(caller),
+            Self::PullResponse { values, .. } | Self::PushMessage { values, .. } => values,
+            Self::PruneMessage { .. } | Self::PingMessage { .. } | Self::PongMessage { .. } => &[],
+        }
+    }
+
+    /// Drops the crds values which do not satisfy the predicate. Returns
+    /// false if the message carried values and none of them are left.
+    pub fn retain_values<F>(&mut self, mut predicate: F) -> bool
+    where
+        F: FnMut(&CrdsValue) -> bool,
+    {
+        match self {
+            Self::PullRequest { caller } => predicate(caller),
+            Self::PullResponse { values, .. } | Self::PushMessage { values, .. } => {
+                values.retain(predicate);
+                !values.is_empty()
+            }
+            Self::PruneMessage { .. } | Self::PingMessage { .. } | Self::PongMessage { .. } => {
+                false
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct CaptureStats {
+    captured: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Taps the gossip packet channels and forwards a copy of every packet to
+/// the capture writer thread.
+pub(crate) struct GossipCapture {
+    sender: Sender<CapturedPacket>,
+    stats: Arc<CaptureStats>,
+}
+
+impl GossipCapture {
+    /// Starts the writer thread, which exits once the returned capture and
+    /// all its taps are dropped.
+    pub(crate) fn new(config: GossipCaptureConfig) -> io::Result<(Self, JoinHandle<()>)> {
+        let mut writer = CaptureWriter::new(config)?;
+        let (sender, receiver) = bounded(MAX_PENDING_PACKETS);
+        let stats = Arc::<CaptureStats>::default();
+        let thread_stats = stats.clone();
+        let t_writer = Builder::new()
+            .name("solGossipCapWr".to_string())
+            .spawn(move || writer.run(receiver, &thread_stats))?;
+        Ok((Self { sender, stats }, t_writer))
+    }
+
+    /// Forwards packet batches from the receiver to the sender, capturing
+    /// each packet along the way.
+    pub(crate) fn tap(
+        &self,
+        direction: Direction,
+        receiver: PacketBatchReceiver,
+        sender: PacketBatchSender,
+    ) -> JoinHandle<()> {
+        let capture = self.sender.clone();
+        let stats = self.stats.clone();
+        let name = match direction {
+            Direction::Received => "solGossipCapRx",
+            Direction::Sent => "solGossipCapTx",
+        };
+        Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                for packet_batch in receiver.iter() {
+                    capture_packets(&packet_batch, direction, &capture, &stats);
+                    if sender.send(packet_batch).is_err() {
+                        break;
+                    }
+                }
+            })
+            .unwrap()
+    }
+}
+
+fn capture_packets(
+    packet_batch: &PacketBatch,
+    direction: Direction,
+    capture: &Sender<CapturedPacket>,
+    stats: &CaptureStats,
+) {
+    let now = timestamp();
+    for packet in packet_batch
+        .iter()
+        .filter(|packet| !packet.meta().discard())
+    {
+        let Some(data) = packet.data(..) else {
+            continue;
+        };
+        let packet = CapturedPacket {
+            timestamp: now,
+            direction,
+            addr: packet.meta().socket_addr(),
+            data: data.to_vec(),
+        };
+        match capture.try_send(packet) {
+            Ok(()) => stats.captured.fetch_add(1, Ordering::Relaxed),
+            Err(_) => stats.dropped.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+}
+
+struct CaptureWriter {
+    config: GossipCaptureConfig,
+    file: BufWriter<File>,
+    file_size: u64,
+    num_files: u64,
+}
+
+impl CaptureWriter {
+    fn new(config: GossipCaptureConfig) -> io::Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+        let file = Self::create_file(&config.dir, 0)?;
+        let mut writer = Self {
+            config,
+            file,
+            file_size: 0,
+            num_files: 1,
+        };
+        writer.remove_old_files()?;
+        Ok(writer)
+    }
+
+    fn create_file(dir: &Path, num_files: u64) -> io::Result<BufWriter<File>> {
+        let path = dir.join(format!(
+            "gossip-{:013}-{num_files:06}.{CAPTURE_FILE_EXTENSION}",
+            timestamp()
+        ));
+        info!("writing gossip capture to {}", path.display());
+        File::create(path).map(BufWriter::new)
+    }
+
+    fn run(&mut self, receiver: Receiver<CapturedPacket>, stats: &CaptureStats) {
+        let mut last_report = Instant::now();
+        loop {
+            match receiver.recv_timeout(STATS_REPORT_INTERVAL) {
+                Ok(packet) => {
+                    if let Err(err) = self.write(&packet) {
+                        error!("gossip capture failed: {err}");
+                        break;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if last_report.elapsed() >= STATS_REPORT_INTERVAL {
+                if let Err(err) = self.file.flush() {
+                    error!("gossip capture failed: {err}");
+                    break;
+                }
+                datapoint_info!(
+                    "gossip_capture",
+                    ("captured", stats.captured.swap(0, Ordering::Relaxed), i64),
+                    ("dropped", stats.dropped.swap(0, Ordering::Relaxed), i64),
+                );
+                last_report = Instant::now();
+            }
+        }
+        if let Err(err) = self.file.flush() {
+            error!("gossip capture failed: {err}");
+        }
+    }
+
+    fn write(&mut self, packet: &CapturedPacket) -> io::Result<()> {
+        let size = bincode::serialized_size(packet)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        bincode::serialize_into(&mut self.file, packet)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        self.file_size += size;
+        if self.file_size >= self.config.max_file_size {
+            self.file.flush()?;
+            self.file = Self::create_file(&self.config.dir, self.num_files)?;
+            self.file_size = 0;
+            self.num_files += 1;
+            self.remove_old_files()?;
+        }
+        Ok(())
+    }
+
+    fn remove_old_files(&self) -> io::Result<()> {
+        let files = list_capture_files(&self.config.dir)?;
+        let num_old_files = files.len().saturating_sub(self.config.max_files.max(1));
+        for path in &files[..num_old_files] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Capture files in the directory, oldest first.
+pub fn list_capture_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.is_file()
+                && path.extension().and_then(|ext| ext.to_str()) == Some(CAPTURE_FILE_EXTENSION)
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Reads captured packets from the given files, and from the capture files
+/// inside the given directories, in order.
+pub fn read_captured_packets(
+    paths: &[PathBuf],
+) -> io::Result<impl Iterator<Item = CapturedPacket>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(list_capture_files(path)?);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    let readers = files
+        .into_iter()
+        .map(|path| Ok((BufReader::new(File::open(&path)?), path)))
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(readers.into_iter().flat_map(|(mut reader, path)| {
+        std::iter::from_fn(move || match bincode::deserialize_from(&mut reader) {
+            Ok(packet) => Some(packet),
+            Err(err) => {
+                // The last record is cut short if the node exited mid write.
+                match *err {
+                    bincode::ErrorKind::Io(ref err)
+                        if err.kind() == io::ErrorKind::UnexpectedEof => {}
+                    _ => warn!("invalid gossip capture {}: {err}", path.display()),
+                }
+                None
+            }
+        })
+    }))
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    pub num_messages: usize,
+    pub num_values: usize,
+    pub num_inserted: usize,
+    pub num_failed_verify: usize,
+}
+
+/// Inserts the crds values carried by the messages into the cluster-info's
+/// crds table, as of the time each packet was captured. Values which the
+/// captured node sent out are inserted too, since they came from its table.
+pub fn replay_captured_messages<I>(cluster_info: &ClusterInfo, messages: I) -> ReplayStats
+where
+    I: IntoIterator<Item = (CapturedPacket, CapturedMessage)>,
+{
+    let mut stats = ReplayStats::default();
+    let mut crds = cluster_info.gossip.crds.write().unwrap();
+    for (packet, message) in messages {
+        let (from, values) = match message {
+            CapturedMessage::PullRequest { caller } => (None, vec![caller]),
+            CapturedMessage::PullResponse { values, .. } => (None, values),
+            CapturedMessage::PushMessage { from, values } => (Some(from), values),
+            CapturedMessage::PruneMessage { .. }
+            | CapturedMessage::PingMessage { .. }
+            | CapturedMessage::PongMessage { .. } => continue,
+        };
+        stats.num_messages += 1;
+        for value in values {
+            stats.num_values += 1;
+            if !value.verify() {
+                stats.num_failed_verify += 1;
+                continue;
+            }
+            let route = match (packet.direction, &from) {
+                (Direction::Sent, _) => GossipRoute::LocalMessage,
+                (Direction::Received, Some(from)) => GossipRoute::PushMessage(from),
+                (Direction::Received, None) => GossipRoute::PullResponse,
+            };
+            if crds.insert(value, packet.timestamp, route).is_ok() {
+                stats.num_inserted += 1;
+            }
+        }
+    }
+    stats
+}
+
+/// Number of crds values in the cluster-info's table, by CrdsData variant.
+pub fn crds_values_by_name(cluster_info: &ClusterInfo) -> BTreeMap<&'static str, usize> {
+    let crds = cluster_info.gossip.crds.read().unwrap();
+    crds.values().fold(BTreeMap::new(), |mut counts, entry| {
+        *counts.entry(entry.value.data.name()).or_default() += 1;
+        counts
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            contact_info::ContactInfo, crds_value::CrdsData, legacy_contact_info::LegacyContactInfo,
+        },
+        solana_perf::packet::Packet,
+        solana_sdk::signature::{Keypair, Signer},
+        solana_streamer::socket::SocketAddrSpace,
+        std::net::{IpAddr, Ipv4Addr},
+    };
+
+    fn new_push_packet(keypair: &Keypair, now: u64) -> (CrdsValue, Packet) {
+        let contact_info = LegacyContactInfo::new_localhost(&keypair.pubkey(), now);
+        let value = CrdsValue::new_signed(CrdsData::LegacyContactInfo(contact_info), keypair);
+        let protocol = Protocol::PushMessage(keypair.pubkey(), vec![value.clone()]);
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8001);
+        (value, Packet::from_data(Some(&addr), protocol).unwrap())
+    }
+
+    #[test]
+    fn test_capture_rotate_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = GossipCaptureConfig {
+            dir: dir.path().to_path_buf(),
+            max_file_size: 1024,
+            max_files: 3,
+        };
+        let (capture, t_writer) = GossipCapture::new(config).unwrap();
+        let (in_sender, in_receiver) = crossbeam_channel::unbounded();
+        let (out_sender, out_receiver) = crossbeam_channel::unbounded();
+        let t_tap = capture.tap(Direction::Received, in_receiver, out_sender);
+        drop(capture);
+        let keypairs: Vec<_> = (0..20).map(|_| Keypair::new()).collect();
+        let now = timestamp();
+        let values: Vec<_> = keypairs
+            .iter()
+            .map(|keypair| {
+                let (value, packet) = new_push_packet(keypair, now);
+                in_sender.send(PacketBatch::new(vec![packet])).unwrap();
+                value
+            })
+            .collect();
+        drop(in_sender);
+        t_tap.join().unwrap();
+        t_writer.join().unwrap();
+        // Every batch is forwarded unchanged.
+        assert_eq!(out_receiver.try_iter().count(), keypairs.len());
+        // Old files are removed, so only the most recent packets are left.
+        let files = list_capture_files(dir.path()).unwrap();
+        assert_eq!(files.len(), 3);
+        let packets: Vec<_> = read_captured_packets(&[dir.path().to_path_buf()])
+            .unwrap()
+            .collect();
+        assert!(!packets.is_empty());
+        assert!(packets.len() < keypairs.len());
+        let offset = keypairs.len() - packets.len();
+        for (packet, value) in packets.iter().zip(&values[offset..]) {
+            assert_eq!(packet.direction, Direction::Received);
+            let message = packet.decode().unwrap();
+            assert_eq!(message.name(), "PushMessage");
+            assert_eq!(message.from(), value.pubkey());
+            assert_eq!(message.values(), std::slice::from_ref(value));
+        }
+    }
+
+    #[test]
+    fn test_replay_captured_messages() {
+        let keypair = Arc::new(Keypair::new());
+        let cluster_info = ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), timestamp()),
+            keypair,
+            SocketAddrSpace::Unspecified,
+        );
+        let now = timestamp();
+        let packets: Vec<_> = (0..4)
+            .map(|_| {
+                let (_, packet) = new_push_packet(&Keypair::new(), now);
+                CapturedPacket {
+                    timestamp: now,
+                    direction: Direction::Received,
+                    addr: packet.meta().socket_addr(),
+                    data: packet.data(..).unwrap().to_vec(),
+                }
+            })
+            .collect();
+        // Replaying the same messages twice only inserts the values once.
+        let messages: Vec<_> = packets
+            .into_iter()
+            .map(|packet| {
+                let message = packet.decode().unwrap();
+                (packet, message)
+            })
+            .collect();
+        let stats =
+            replay_captured_messages(&cluster_info, messages.iter().cloned().chain(messages));
+        assert_eq!(
+            stats,
+            ReplayStats {
+                num_messages: 8,
+                num_values: 8,
+                num_inserted: 4,
+                num_failed_verify: 0,
+            }
+        );
+        // The node's own contact-info plus the replayed ones.
+        assert_eq!(
+            crds_values_by_name(&cluster_info).get("LegacyContactInfo"),
+            Some(&5)
+        );
+    }
+
+    #[test]
+    fn test_retain_values() {
+        let keypair = Keypair::new();
+        let (value, packet) = new_push_packet(&keypair, timestamp());
+        let packet = CapturedPacket {
+            timestamp: 0,
+            direction: Direction::Sent,
+            addr: packet.meta().socket_addr(),
+            data: packet.data(..).unwrap().to_vec(),
+        };
+        let mut message = packet.decode().unwrap();
+        assert!(message.retain_values(|v| v.pubkey() == value.pubkey()));
+        assert!(!message.retain_values(|v| v.data.name() == "Vote"));
+        assert!(message.values().is_empty());
+    }
+}
@@ -1,7 +1,11 @@
 //! The `gossip_service` module implements the network control plane.
 
 use {
-    crate::{cluster_info::ClusterInfo, legacy_contact_info::LegacyContactInfo as ContactInfo},
+    crate::{
+        cluster_info::ClusterInfo,
+        gossip_capture::{Direction, GossipCapture},
+        legacy_contact_info::LegacyContactInfo as ContactInfo,
+    },
     crossbeam_channel::{unbounded, Sender},
     rand::{thread_rng, Rng},
     solana_client::{connection_cache::ConnectionCache, thin_client::ThinClient},
@@ -49,6 +53,32 @@ impl GossipService {
             gossip_socket.local_addr().unwrap()
         );
         let socket_addr_space = *cluster_info.socket_addr_space();
+        let mut thread_hdls = Vec::new();
+        // Opt-in capture of the gossip traffic; the packets are tapped in
+        // between the sockets and the cluster-info.
+        let capture = cluster_info.gossip_capture().and_then(|config| {
+            match GossipCapture::new(config.clone()) {
+                Ok((capture, t_writer)) => {
+                    thread_hdls.push(t_writer);
+                    Some(capture)
+                }
+                Err(err) => {
+                    error!(
+                        "Unable to capture gossip to {}: {err}",
+                        config.dir.display()
+                    );
+                    None
+                }
+            }
+        });
+        let request_sender = match &capture {
+            None => request_sender,
+            Some(capture) => {
+                let (sender, receiver) = unbounded();
+                thread_hdls.push(capture.tap(Direction::Received, receiver, request_sender));
+                sender
+            }
+        };
         let t_receiver = streamer::receiver(
             gossip_socket.clone(),
             exit.clone(),
@@ -66,6 +96,14 @@ impl GossipService {
             exit.clone(),
         );
         let (response_sender, response_receiver) = unbounded();
+        let response_receiver = match &capture {
+            None => response_receiver,
+            Some(capture) => {
+                let (sender, receiver) = unbounded();
+                thread_hdls.push(capture.tap(Direction::Sent, response_receiver, sender));
+                receiver
+            }
+        };
         let t_listen = cluster_info.clone().listen(
             bank_forks.clone(),
             listen_receiver,
@@ -84,13 +122,13 @@ impl GossipService {
             socket_addr_space,
             stats_reporter_sender,
         );
-        let thread_hdls = vec![
+        thread_hdls.extend([
             t_receiver,
             t_responder,
             t_socket_consume,
             t_listen,
             t_gossip,
-        ];
+        ]);
         Self { thread_hdls }
     }
 
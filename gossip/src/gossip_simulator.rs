@@ -0,0 +1,1009 @@
+//! A single-process simulator for the gossip push and pull planes.
+//!
+//! Runs many `CrdsGossip` instances on a virtual clock and connects them
+//! through a simulated network with configurable latency, packet loss and
+//! partitions. Nodes periodically publish new contact-infos, and pull the
+//! values they are missing from random peers; the simulator tracks how long
+//! those take to reach the rest of the cluster, how many bytes each node
+//! sends and receives, and what fraction of the cluster holds the latest
+//! value of every origin.
+//!
+//! All randomness in the network, the stake distribution, the push active
+//! set rotation and the pull requests, including the keys of bloom filters,
+//! is drawn from seeded rngs, and messages are processed in a fixed order on
+//! a single thread, so two runs with the same seed report the same stats.
+//! Pull requests do not insert the contact-info of the requesting node, pull
+//! responses are not limited by a data budget, and crds values are not
+//! purged.
+
+use {
+    crate::{
+        cluster_info::MAX_BLOOM_SIZE,
+        cluster_info_metrics::GossipStats,
+        crds::GossipRoute,
+        crds_gossip::CrdsGossip,
+        crds_gossip_pull::{CrdsFilter, ProcessPullStats},
+        crds_value::{CrdsData, CrdsValue},
+        legacy_contact_info::LegacyContactInfo as ContactInfo,
+    },
+    bincode::serialized_size,
+    itertools::Itertools,
+    rand::{
+        distributions::{Distribution, WeightedIndex},
+        seq::index::sample,
+        Rng, SeedableRng,
+    },
+    rand_chacha::ChaChaRng,
+    rayon::{ThreadPool, ThreadPoolBuilder},
+    solana_sdk::{
+        clock::{DEFAULT_MS_PER_SLOT, DEFAULT_SLOTS_PER_EPOCH},
+        native_token::LAMPORTS_PER_SOL,
+        pubkey::Pubkey,
+        signature::{keypair_from_seed, Keypair, Signer},
+    },
+    std::{
+        cmp::Ordering,
+        collections::{BinaryHeap, HashMap},
+        fmt,
+        net::Ipv4Addr,
+        str::FromStr,
+        time::Duration,
+    },
+};
+
+// Virtual wallclock at which every simulation starts.
+const SIMULATION_EPOCH_MS: u64 = 1_600_000_000_000;
+// Each node advertises a unique gossip address in this range.
+const GOSSIP_PORT: u16 = 8001;
+// Same interval as ClusterInfo uses between active set rotations.
+const REFRESH_ACTIVE_SET_INTERVAL_MS: u64 = 7_500;
+// Pull responses from staked origins expire after an epoch, as in ClusterInfo
+// without a bank.
+const EPOCH_DURATION: Duration =
+    Duration::from_millis(DEFAULT_SLOTS_PER_EPOCH * DEFAULT_MS_PER_SLOT);
+
+/// How stake is distributed across the simulated nodes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StakeDistribution {
+    /// Every node has the same stake, up to a few lamports.
+    Equal,
+    /// Stakes are drawn uniformly at random.
+    Uniform,
+    /// The stake of the i-th node is proportional to 1 / (i + 1)^exponent.
+    PowerLaw { exponent: f64 },
+}
+
+impl FromStr for StakeDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "equal" => Ok(Self::Equal),
+            None if s == "uniform" => Ok(Self::Uniform),
+            Some(("power-law", exponent)) => exponent
+                .parse()
+                .map(|exponent| Self::PowerLaw { exponent })
+                .map_err(|err| format!("invalid power-law exponent {exponent}: {err}")),
+            _ => Err(format!(
+                "invalid stake distribution {s}, expected equal, uniform or power-law:EXPONENT"
+            )),
+        }
+    }
+}
+
+/// A fraction of the nodes is cut off from the rest of the cluster for the
+/// given interval of the simulation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Partition {
+    pub start: Duration,
+    pub end: Duration,
+    pub fraction: f64,
+}
+
+impl FromStr for Partition {
+    type Err = String;
+
+    /// Parses START_SECS:END_SECS:FRACTION.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid partition {s}, expected START_SECS:END_SECS:FRACTION");
+        let mut parts = s.split(':');
+        let (Some(start), Some(end), Some(fraction), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(err());
+        };
+        let start: f64 = start.parse().map_err(|_| err())?;
+        let end: f64 = end.parse().map_err(|_| err())?;
+        let fraction: f64 = fraction.parse().map_err(|_| err())?;
+        if start < 0.0 || end <= start || !(0.0..=1.0).contains(&fraction) {
+            return Err(err());
+        }
+        Ok(Self {
+            start: Duration::from_secs_f64(start),
+            end: Duration::from_secs_f64(end),
+            fraction,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SimulatorConfig {
+    pub num_nodes: usize,
+    pub stake_distribution: StakeDistribution,
+    /// One way latency of each message is drawn uniformly from this range.
+    pub min_latency: Duration,
+    pub max_latency: Duration,
+    /// Probability that a message is dropped.
+    pub packet_loss: f64,
+    pub partitions: Vec<Partition>,
+    pub duration: Duration,
+    /// Granularity of the virtual clock; nodes generate push messages once
+    /// per tick.
+    pub tick: Duration,
+    /// A randomly chosen node publishes a new contact-info at this interval.
+    pub update_interval: Duration,
+    /// Each node sends pull requests at this interval; zero disables pull.
+    pub pull_interval: Duration,
+    /// Convergence of the crds tables is sampled at this interval.
+    pub sample_interval: Duration,
+    /// Number of random peers each node knows about when the run starts.
+    pub num_initial_peers: usize,
+    /// Overrides the number of nodes each value is pushed to.
+    pub push_fanout: Option<usize>,
+    /// Overrides the number of nodes in each push active set entry.
+    pub active_set_size: Option<usize>,
+    pub seed: u64,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            num_nodes: 1000,
+            stake_distribution: StakeDistribution::PowerLaw { exponent: 1.0 },
+            min_latency: Duration::from_millis(10),
+            max_latency: Duration::from_millis(150),
+            packet_loss: 0.0,
+            partitions: Vec::default(),
+            duration: Duration::from_secs(60),
+            tick: Duration::from_millis(100),
+            update_interval: Duration::from_millis(500),
+            // ClusterInfo pulls every other gossip loop.
+            pull_interval: Duration::from_millis(200),
+            sample_interval: Duration::from_secs(5),
+            num_initial_peers: 8,
+            push_fanout: None,
+            active_set_size: None,
+            seed: 0,
+        }
+    }
+}
+
+/// Distribution summary of a set of samples.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Percentiles {
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl Percentiles {
+    fn new(mut samples: Vec<u64>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let percentile = |p: usize| samples[(samples.len() - 1) * p / 100];
+        Some(Self {
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: percentile(100),
+        })
+    }
+}
+
+impl fmt::Display for Percentiles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "p50={} p90={} p99={} max={}",
+            self.p50, self.p90, self.p99, self.max
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulatorReport {
+    pub num_nodes: usize,
+    pub duration: Duration,
+    pub num_updates: usize,
+    /// Number of updates which reached every node before they were
+    /// superseded or the run ended.
+    pub num_updates_fully_propagated: usize,
+    /// Delay between publishing an update and each node receiving it, in
+    /// milliseconds.
+    pub propagation_delay_ms: Option<Percentiles>,
+    pub num_push_messages: usize,
+    pub num_prune_messages: usize,
+    /// Number of crds filters sent in pull requests.
+    pub num_pull_requests: usize,
+    pub num_pull_responses: usize,
+    /// Prune messages rejected by their destination.
+    pub num_failed_prunes: usize,
+    pub num_dropped_loss: usize,
+    pub num_dropped_partition: usize,
+    /// Egress and ingress of each node, in bytes per second.
+    pub egress_bytes_per_sec: Option<Percentiles>,
+    pub ingress_bytes_per_sec: Option<Percentiles>,
+    /// Fraction of (node, origin) pairs for which the node holds the most
+    /// recent contact-info of the origin, sampled over time.
+    pub convergence: Vec<(Duration, f64)>,
+}
+
+impl fmt::Display for SimulatorReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "nodes: {}, simulated time: {:?}",
+            self.num_nodes, self.duration
+        )?;
+        writeln!(
+            f,
+            "updates: {}, fully propagated: {}",
+            self.num_updates, self.num_updates_fully_propagated
+        )?;
+        if let Some(delay) = &self.propagation_delay_ms {
+            writeln!(f, "propagation delay (ms): {delay}")?;
+        }
+        writeln!(
+            f,
+            "push messages: {}, prune messages: {}, failed prunes: {}",
+            self.num_push_messages, self.num_prune_messages, self.num_failed_prunes
+        )?;
+        writeln!(
+            f,
+            "pull requests: {}, pull responses: {}",
+            self.num_pull_requests, self.num_pull_responses
+        )?;
+        writeln!(
+            f,
+            "dropped messages: {} lost, {} partitioned",
+            self.num_dropped_loss, self.num_dropped_partition
+        )?;
+        if let Some(egress) = &self.egress_bytes_per_sec {
+            writeln!(f, "egress per node (bytes/s): {egress}")?;
+        }
+        if let Some(ingress) = &self.ingress_bytes_per_sec {
+            writeln!(f, "ingress per node (bytes/s): {ingress}")?;
+        }
+        writeln!(f, "convergence:")?;
+        for (time, convergence) in &self.convergence {
+            writeln!(
+                f,
+                "  {:>8.1}s {:>7.3}%",
+                time.as_secs_f64(),
+                convergence * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct Node {
+    keypair: Keypair,
+    gossip: CrdsGossip,
+    partition: Vec<bool>,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+enum Payload {
+    Push(Vec<CrdsValue>),
+    Prune {
+        origins: Vec<Pubkey>,
+        wallclock: u64,
+    },
+    PullRequest(Vec<CrdsFilter>),
+    PullResponse(Vec<CrdsValue>),
+}
+
+struct Message {
+    deliver_at: u64,
+    seq: u64,
+    from: usize,
+    to: usize,
+    payload: Payload,
+}
+
+// Ordered so that the BinaryHeap pops the earliest message first, and
+// messages delivered at the same time in the order they were sent.
+impl Ord for Message {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.deliver_at, other.seq).cmp(&(self.deliver_at, self.seq))
+    }
+}
+
+impl PartialOrd for Message {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Message {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Message {}
+
+struct Update {
+    published: u64,
+    num_received: usize,
+}
+
+pub struct GossipSimulator {
+    config: SimulatorConfig,
+    rng: ChaChaRng,
+    // Draws the keys of the bloom filters in push active sets and pull
+    // requests.
+    bloom_rng: ChaChaRng,
+    thread_pool: ThreadPool,
+    stats: GossipStats,
+    nodes: Vec<Node>,
+    index: HashMap<Pubkey, usize>,
+    stakes: HashMap<Pubkey, u64>,
+    queue: BinaryHeap<Message>,
+    num_sent: u64,
+    // Wallclock of the most recent contact-info of each origin, and the
+    // number of nodes which hold it.
+    latest: Vec<u64>,
+    num_holders: Vec<usize>,
+    // Updates published during the run, keyed by origin and wallclock.
+    updates: HashMap<(usize, u64), Update>,
+    propagation_delays: Vec<u64>,
+    report: SimulatorReport,
+}
+
+impl GossipSimulator {
+    pub fn new(config: SimulatorConfig) -> Self {
+        assert!(config.num_nodes > 1, "need at least two nodes");
+        assert!(config.min_latency <= config.max_latency);
+        assert!(!config.tick.is_zero());
+        let mut rng = ChaChaRng::seed_from_u64(config.seed);
+        let num_nodes = config.num_nodes;
+        let keypairs: Vec<_> = (0..num_nodes)
+            .map(|_| keypair_from_seed(&rng.gen::<[u8; 32]>()).unwrap())
+            .collect();
+        let stakes: Vec<u64> = match config.stake_distribution {
+            StakeDistribution::Equal => vec![LAMPORTS_PER_SOL; num_nodes],
+            StakeDistribution::Uniform => (0..num_nodes)
+                .map(|_| rng.gen_range(1..=1_000_000) * LAMPORTS_PER_SOL)
+                .collect(),
+            StakeDistribution::PowerLaw { exponent } => (0..num_nodes)
+                .map(|k| {
+                    let stake = 1_000_000.0 / ((k + 1) as f64).powf(exponent);
+                    (stake * LAMPORTS_PER_SOL as f64).max(1.0) as u64
+                })
+                .collect(),
+        };
+        // Stakes are made distinct, so that the nodes which received caches
+        // prune, ranked by score and stake, do not depend on the iteration
+        // order of hash maps.
+        let stakes: Vec<u64> = stakes
+            .into_iter()
+            .enumerate()
+            .map(|(k, stake)| {
+                let modulus = num_nodes as u64 + 1;
+                stake / modulus * modulus + k as u64 + 1
+            })
+            .collect();
+        // Partition membership is drawn independently of the stake rank.
+        let partitions: Vec<Vec<bool>> = config
+            .partitions
+            .iter()
+            .map(|partition| {
+                let mut members = vec![false; num_nodes];
+                let amount = (partition.fraction * num_nodes as f64).round() as usize;
+                for k in sample(&mut rng, num_nodes, amount).iter() {
+                    members[k] = true;
+                }
+                members
+            })
+            .collect();
+        let now = SIMULATION_EPOCH_MS;
+        let contact_infos: Vec<_> = keypairs
+            .iter()
+            .enumerate()
+            .map(|(k, keypair)| new_contact_info(k, keypair, now))
+            .collect();
+        let mut num_holders = vec![0; num_nodes];
+        let nodes: Vec<_> = keypairs
+            .into_iter()
+            .enumerate()
+            .map(|(k, keypair)| {
+                let mut gossip = CrdsGossip::default();
+                if let Some(push_fanout) = config.push_fanout {
+                    gossip.push.push_fanout = push_fanout;
+                }
+                if let Some(active_set_size) = config.active_set_size {
+                    gossip.push.active_set_size = active_set_size;
+                }
+                let peers = sample(&mut rng, num_nodes, config.num_initial_peers.min(num_nodes))
+                    .into_iter()
+                    .filter(|&peer| peer != k);
+                {
+                    let mut crds = gossip.crds.write().unwrap();
+                    for peer in std::iter::once(k).chain(peers) {
+                        crds.insert(contact_infos[peer].clone(), now, GossipRoute::LocalMessage)
+                            .unwrap();
+                        num_holders[peer] += 1;
+                    }
+                }
+                Node {
+                    keypair,
+                    gossip,
+                    partition: partitions.iter().map(|members| members[k]).collect(),
+                    bytes_sent: 0,
+                    bytes_received: 0,
+                }
+            })
+            .collect();
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(k, node)| (node.keypair.pubkey(), k))
+            .collect();
+        let stakes = nodes
+            .iter()
+            .zip(stakes)
+            .map(|(node, stake)| (node.keypair.pubkey(), stake))
+            .collect();
+        let report = SimulatorReport {
+            num_nodes,
+            duration: config.duration,
+            ..SimulatorReport::default()
+        };
+        let bloom_rng = ChaChaRng::from_seed(rng.gen());
+        let thread_pool = ThreadPoolBuilder::new()
+            .num_threads(1)
+            .thread_name(|i| format!("solGossipSim{i:02}"))
+            .build()
+            .unwrap();
+        Self {
+            config,
+            rng,
+            bloom_rng,
+            thread_pool,
+            stats: GossipStats::default(),
+            nodes,
+            index,
+            stakes,
+            queue: BinaryHeap::new(),
+            num_sent: 0,
+            latest: vec![now; num_nodes],
+            num_holders,
+            updates: HashMap::new(),
+            propagation_delays: Vec::new(),
+            report,
+        }
+    }
+
+    /// Runs the simulation to the end and returns the collected stats.
+    pub fn run(mut self) -> SimulatorReport {
+        let tick = self.config.tick.as_millis() as u64;
+        let duration = self.config.duration.as_millis() as u64;
+        let update_interval = self.config.update_interval.as_millis() as u64;
+        let sample_interval = self.config.sample_interval.as_millis() as u64;
+        let pull_interval = self.config.pull_interval.as_millis() as u64;
+        let (mut next_update, mut next_sample, mut next_refresh) = (update_interval, 0, 0);
+        let mut next_pull = 0;
+        let mut elapsed = 0;
+        while elapsed <= duration {
+            if elapsed >= next_refresh {
+                self.refresh_active_sets();
+                next_refresh += REFRESH_ACTIVE_SET_INTERVAL_MS;
+            }
+            while update_interval > 0 && elapsed >= next_update {
+                self.publish_update(elapsed);
+                next_update += update_interval;
+            }
+            self.deliver_messages(elapsed);
+            self.new_push_messages(elapsed);
+            if pull_interval > 0 && elapsed >= next_pull {
+                self.new_pull_requests(elapsed);
+                next_pull += pull_interval;
+            }
+            if elapsed >= next_sample {
+                self.sample_convergence(elapsed);
+                next_sample += sample_interval.max(tick);
+            }
+            elapsed += tick;
+        }
+        self.finish()
+    }
+
+    fn refresh_active_sets(&mut self) {
+        let cluster_size = self.nodes.len();
+        for node in &self.nodes {
+            // Every simulated peer is reachable, so there is no need to ping
+            // them first. Sorted so that the rotation only depends on the rng.
+            let nodes: Vec<_> = node
+                .gossip
+                .crds
+                .read()
+                .unwrap()
+                .get_nodes_contact_info()
+                .map(|peer| *peer.pubkey())
+                .filter(|peer| peer != &node.keypair.pubkey())
+                .sorted_unstable()
+                .collect();
+            node.gossip.push.rotate_push_active_set(
+                &mut self.rng,
+                &mut self.bloom_rng,
+                cluster_size,
+                &nodes,
+                &self.stakes,
+            );
+        }
+    }
+
+    fn publish_update(&mut self, elapsed: u64) {
+        let now = SIMULATION_EPOCH_MS + elapsed;
+        let origin = self.rng.gen_range(0..self.nodes.len());
+        if self.latest[origin] == now {
+            return;
+        }
+        let node = &self.nodes[origin];
+        let value = new_contact_info(origin, &node.keypair, now);
+        node.gossip
+            .crds
+            .write()
+            .unwrap()
+            .insert(value, now, GossipRoute::LocalMessage)
+            .unwrap();
+        self.retire_update(origin);
+        self.latest[origin] = now;
+        self.num_holders[origin] = 1;
+        self.updates.insert(
+            (origin, now),
+            Update {
+                published: now,
+                num_received: 1,
+            },
+        );
+        self.report.num_updates += 1;
+    }
+
+    // Stops tracking the current update of the origin once it is superseded.
+    fn retire_update(&mut self, origin: usize) {
+        if let Some(update) = self.updates.remove(&(origin, self.latest[origin])) {
+            if update.num_received == self.nodes.len() {
+                self.report.num_updates_fully_propagated += 1;
+            }
+        }
+    }
+
+    fn deliver_messages(&mut self, elapsed: u64) {
+        let now = SIMULATION_EPOCH_MS + elapsed;
+        while self
+            .queue
+            .peek()
+            .map(|message| message.deliver_at <= elapsed)
+            .unwrap_or_default()
+        {
+            let message = self.queue.pop().unwrap();
+            match message.payload {
+                Payload::Push(values) => {
+                    self.process_push(message.from, message.to, values, elapsed)
+                }
+                Payload::Prune { origins, wallclock } => {
+                    let node = &self.nodes[message.to];
+                    let result = node.gossip.process_prune_msg(
+                        &node.keypair.pubkey(),
+                        &self.nodes[message.from].keypair.pubkey(),
+                        &node.keypair.pubkey(), // destination
+                        &origins,
+                        wallclock,
+                        now,
+                        &self.stakes,
+                    );
+                    if result.is_err() {
+                        self.report.num_failed_prunes += 1;
+                    }
+                }
+                Payload::PullRequest(filters) => {
+                    self.process_pull_request(message.from, message.to, filters, elapsed)
+                }
+                Payload::PullResponse(values) => {
+                    self.process_pull_response(message.to, values, elapsed)
+                }
+            }
+        }
+    }
+
+    fn process_push(&mut self, from: usize, to: usize, values: Vec<CrdsValue>, elapsed: u64) {
+        let now = SIMULATION_EPOCH_MS + elapsed;
+        let from_pubkey = self.nodes[from].keypair.pubkey();
+        let wallclocks: HashMap<usize, u64> = values
+            .iter()
+            .filter_map(|value| Some((self.index.get(&value.pubkey())?, value)))
+            .filter(|(_, value)| matches!(value.data, CrdsData::LegacyContactInfo(_)))
+            .map(|(&origin, value)| (origin, value.wallclock()))
+            .collect();
+        let node = &self.nodes[to];
+        let origins = node
+            .gossip
+            .process_push_message(vec![(from_pubkey, values)], now);
+        let received: Vec<_> = origins
+            .iter()
+            .filter_map(|origin| {
+                let origin = *self.index.get(origin)?;
+                Some((origin, *wallclocks.get(&origin)?))
+            })
+            .collect();
+        let prunes = node.gossip.prune_received_cache(
+            &node.keypair.pubkey(),
+            origins.into_iter(),
+            &self.stakes,
+        );
+        self.record_received(received, now);
+        let mut prunes: Vec<_> = prunes
+            .into_iter()
+            .filter_map(|(peer, origins)| Some((*self.index.get(&peer)?, origins)))
+            .collect();
+        prunes.sort_unstable_by_key(|(peer, _)| *peer);
+        for (peer, mut origins) in prunes {
+            origins.sort_unstable();
+            let payload = Payload::Prune {
+                origins,
+                wallclock: now,
+            };
+            self.report.num_prune_messages += 1;
+            self.send(to, peer, payload, elapsed);
+        }
+    }
+
+    // Answers the pull request with the values which the filters do not hold.
+    fn process_pull_request(
+        &mut self,
+        from: usize,
+        to: usize,
+        filters: Vec<CrdsFilter>,
+        elapsed: u64,
+    ) {
+        let now = SIMULATION_EPOCH_MS + elapsed;
+        // Responders only check the wallclock of the caller's contact-info.
+        let caller = ContactInfo::new_localhost(&self.nodes[from].keypair.pubkey(), now);
+        let caller = CrdsValue::new_unsigned(CrdsData::LegacyContactInfo(caller));
+        let filters: Vec<_> = filters
+            .into_iter()
+            .map(|filter| (caller.clone(), filter))
+            .collect();
+        let values: Vec<_> = self.nodes[to]
+            .gossip
+            .generate_pull_responses(
+                &self.thread_pool,
+                &filters,
+                usize::MAX, // output_size_limit
+                now,
+                &self.stats,
+            )
+            .into_iter()
+            .flatten()
+            .collect();
+        if !values.is_empty() {
+            self.report.num_pull_responses += 1;
+            self.send(to, from, Payload::PullResponse(values), elapsed);
+        }
+    }
+
+    fn process_pull_response(&mut self, to: usize, values: Vec<CrdsValue>, elapsed: u64) {
+        let now = SIMULATION_EPOCH_MS + elapsed;
+        let node = &self.nodes[to];
+        let timeouts =
+            node.gossip
+                .make_timeouts(node.keypair.pubkey(), &self.stakes, EPOCH_DURATION);
+        let mut stats = ProcessPullStats::default();
+        let (values, expired_values, failed_inserts) = node
+            .gossip
+            .filter_pull_responses(&timeouts, values, now, &mut stats);
+        // All of the values which pass the filter are inserted below.
+        let received: Vec<_> = values
+            .iter()
+            .chain(&expired_values)
+            .filter(|value| matches!(value.data, CrdsData::LegacyContactInfo(_)))
+            .filter_map(|value| Some((*self.index.get(&value.pubkey())?, value.wallclock())))
+            .collect();
+        node.gossip
+            .process_pull_responses(values, expired_values, failed_inserts, now, &mut stats);
+        self.record_received(received, now);
+    }
+
+    // Counts the node which received the contact-infos as a holder of those
+    // which are the latest of their origin.
+    fn record_received(
+        &mut self,
+        received: Vec<(/*origin:*/ usize, /*wallclock:*/ u64)>,
+        now: u64,
+    ) {
+        for (origin, wallclock) in received {
+            if wallclock != self.latest[origin] {
+                continue;
+            }
+            self.num_holders[origin] += 1;
+            if let Some(update) = self.updates.get_mut(&(origin, wallclock)) {
+                update.num_received += 1;
+                self.propagation_delays.push(now - update.published);
+            }
+        }
+    }
+
+    fn new_push_messages(&mut self, elapsed: u64) {
+        let now = SIMULATION_EPOCH_MS + elapsed;
+        for from in 0..self.nodes.len() {
+            let node = &self.nodes[from];
+            let (push_messages, _num_entries, _num_nodes) =
+                node.gossip
+                    .new_push_messages(&node.keypair.pubkey(), now, &self.stakes, |_, _| true);
+            let mut push_messages: Vec<_> = push_messages
+                .into_iter()
+                .filter_map(|(peer, values)| Some((*self.index.get(&peer)?, values)))
+                .collect();
+            push_messages.sort_unstable_by_key(|(peer, _)| *peer);
+            for (to, values) in push_messages {
+                self.report.num_push_messages += 1;
+                self.send(from, to, Payload::Push(values), elapsed);
+            }
+        }
+    }
+
+    // Each node sends its crds filters to peers sampled by stake, with the same
+    // weights as CrdsGossipPull::new_pull_request.
+    fn new_pull_requests(&mut self, elapsed: u64) {
+        for from in 0..self.nodes.len() {
+            let node = &self.nodes[from];
+            let stake_cap = self.stakes[&node.keypair.pubkey()];
+            let (weights, peers): (Vec<u64>, Vec<usize>) = node
+                .gossip
+                .crds
+                .read()
+                .unwrap()
+                .get_nodes_contact_info()
+                .filter_map(|peer| self.index.get(peer.pubkey()).copied())
+                .filter(|&peer| peer != from)
+                .sorted_unstable()
+                .map(|peer| {
+                    let stake = self.stakes[&self.nodes[peer].keypair.pubkey()];
+                    let stake = stake.min(stake_cap) / LAMPORTS_PER_SOL;
+                    let weight = u64::from(u64::BITS - stake.leading_zeros());
+                    (weight.saturating_add(1).saturating_pow(2), peer)
+                })
+                .unzip();
+            if peers.is_empty() {
+                continue;
+            }
+            let filters = node.gossip.pull.build_crds_filters_with_rng(
+                &mut self.bloom_rng,
+                &self.thread_pool,
+                &node.gossip.crds,
+                MAX_BLOOM_SIZE,
+            );
+            let dist = WeightedIndex::new(weights).unwrap();
+            let requests: Vec<_> = filters
+                .into_iter()
+                .map(|filter| (peers[dist.sample(&mut self.rng)], filter))
+                .into_group_map()
+                .into_iter()
+                .sorted_unstable_by_key(|(peer, _)| *peer)
+                .collect();
+            for (to, filters) in requests {
+                self.report.num_pull_requests += filters.len();
+                self.send(from, to, Payload::PullRequest(filters), elapsed);
+            }
+        }
+    }
+
+    // Accounts for the message and queues it, unless the network drops it.
+    fn send(&mut self, from: usize, to: usize, payload: Payload, elapsed: u64) {
+        let num_bytes = match &payload {
+            Payload::Push(values) => serialized_size(values),
+            Payload::Prune { origins, .. } => serialized_size(origins),
+            Payload::PullRequest(filters) => serialized_size(filters),
+            Payload::PullResponse(values) => serialized_size(values),
+        }
+        .unwrap_or_default();
+        self.nodes[from].bytes_sent += num_bytes;
+        if self.is_partitioned(from, to, elapsed) {
+            self.report.num_dropped_partition += 1;
+            return;
+        }
+        if self.config.packet_loss > 0.0 && self.rng.gen_bool(self.config.packet_loss) {
+            self.report.num_dropped_loss += 1;
+            return;
+        }
+        let latency = self.rng.gen_range(
+            self.config.min_latency.as_millis() as u64..=self.config.max_latency.as_millis() as u64,
+        );
+        self.nodes[to].bytes_received += num_bytes;
+        self.num_sent += 1;
+        self.queue.push(Message {
+            deliver_at: elapsed + latency,
+            seq: self.num_sent,
+            from,
+            to,
+            payload,
+        });
+    }
+
+    fn is_partitioned(&self, from: usize, to: usize, elapsed: u64) -> bool {
+        let elapsed = Duration::from_millis(elapsed);
+        self.config
+            .partitions
+            .iter()
+            .enumerate()
+            .filter(|(_, partition)| partition.start <= elapsed && elapsed < partition.end)
+            .any(|(k, _)| self.nodes[from].partition[k] != self.nodes[to].partition[k])
+    }
+
+    fn sample_convergence(&mut self, elapsed: u64) {
+        let num_nodes = self.nodes.len();
+        let num_holders: usize = self.num_holders.iter().sum();
+        let convergence = num_holders as f64 / (num_nodes * num_nodes) as f64;
+        self.report
+            .convergence
+            .push((Duration::from_millis(elapsed), convergence));
+    }
+
+    fn finish(mut self) -> SimulatorReport {
+        for origin in 0..self.nodes.len() {
+            self.retire_update(origin);
+        }
+        let duration = self.config.duration.as_secs_f64().max(1.0);
+        let per_sec = |bytes: u64| (bytes as f64 / duration) as u64;
+        self.report.egress_bytes_per_sec = Percentiles::new(
+            self.nodes
+                .iter()
+                .map(|node| per_sec(node.bytes_sent))
+                .collect(),
+        );
+        self.report.ingress_bytes_per_sec = Percentiles::new(
+            self.nodes
+                .iter()
+                .map(|node| per_sec(node.bytes_received))
+                .collect(),
+        );
+        self.report.propagation_delay_ms = Percentiles::new(self.propagation_delays);
+        self.report
+    }
+}
+
+fn new_contact_info(k: usize, keypair: &Keypair, now: u64) -> CrdsValue {
+    let mut contact_info = ContactInfo::new_localhost(&keypair.pubkey(), now);
+    let [_, a, b, c] = (k as u32).to_be_bytes();
+    contact_info
+        .set_gossip((Ipv4Addr::new(10, a, b, c), GOSSIP_PORT))
+        .unwrap();
+    CrdsValue::new_signed(CrdsData::LegacyContactInfo(contact_info), keypair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!("equal".parse(), Ok(StakeDistribution::Equal));
+        assert_eq!(
+            "power-law:1.5".parse(),
+            Ok(StakeDistribution::PowerLaw { exponent: 1.5 })
+        );
+        assert!("power-law".parse::<StakeDistribution>().is_err());
+        assert_eq!(
+            "10:20.5:0.3".parse(),
+            Ok(Partition {
+                start: Duration::from_secs(10),
+                end: Duration::from_millis(20_500),
+                fraction: 0.3,
+            })
+        );
+        assert!("20:10:0.3".parse::<Partition>().is_err());
+        assert!("10:20:1.3".parse::<Partition>().is_err());
+    }
+
+    #[test]
+    fn test_gossip_simulator_converges() {
+        let config = SimulatorConfig {
+            num_nodes: 64,
+            duration: Duration::from_secs(20),
+            update_interval: Duration::from_secs(1),
+            sample_interval: Duration::from_secs(1),
+            ..SimulatorConfig::default()
+        };
+        let report = GossipSimulator::new(config).run();
+        assert_eq!(report.num_updates, 20);
+        assert!(report.num_updates_fully_propagated > 0);
+        assert!(report.num_push_messages > 0);
+        assert_eq!(report.num_dropped_loss, 0);
+        let (_, first) = report.convergence.first().unwrap();
+        let (_, last) = report.convergence.last().unwrap();
+        assert!(first < last);
+        assert!(*last > 0.9, "convergence: {last}");
+        assert!(report.propagation_delay_ms.unwrap().max <= 20_000);
+    }
+
+    #[test]
+    fn test_gossip_simulator_is_repeatable() {
+        let config = SimulatorConfig {
+            num_nodes: 32,
+            duration: Duration::from_secs(10),
+            stake_distribution: StakeDistribution::Uniform,
+            packet_loss: 0.1,
+            seed: 42,
+            ..SimulatorConfig::default()
+        };
+        let report = GossipSimulator::new(config.clone()).run();
+        assert!(report.num_push_messages > 0);
+        assert!(report.num_dropped_loss > 0);
+        assert_eq!(GossipSimulator::new(config.clone()).run(), report);
+        let config = SimulatorConfig { seed: 43, ..config };
+        assert_ne!(GossipSimulator::new(config).run(), report);
+    }
+
+    #[test]
+    fn test_gossip_simulator_partition_heals() {
+        let config = SimulatorConfig {
+            num_nodes: 32,
+            duration: Duration::from_secs(30),
+            partitions: vec![Partition {
+                start: Duration::ZERO,
+                end: Duration::from_secs(10),
+                fraction: 0.5,
+            }],
+            update_interval: Duration::ZERO,
+            sample_interval: Duration::from_secs(10),
+            // Few enough that some contact-infos are only known on one side
+            // of the partition.
+            num_initial_peers: 2,
+            ..SimulatorConfig::default()
+        };
+        let report = GossipSimulator::new(config.clone()).run();
+        assert!(report.num_dropped_partition > 0);
+        assert!(report.num_pull_requests > 0);
+        assert!(report.num_pull_responses > 0);
+        let (_, partitioned) = report.convergence[1];
+        let (_, last) = report.convergence.last().unwrap();
+        assert!(partitioned < 1.0);
+        assert_eq!(*last, 1.0);
+
+        // Without pull, the values which were lost to the partition are
+        // not pushed again once it ends.
+        let config = SimulatorConfig {
+            pull_interval: Duration::ZERO,
+            ..config
+        };
+        let report = GossipSimulator::new(config).run();
+        assert_eq!(report.num_pull_requests, 0);
+        let (_, last) = report.convergence.last().unwrap();
+        assert!(*last < 1.0);
+    }
+
+    #[test]
+    fn test_gossip_simulator_partition() {
+        let config = SimulatorConfig {
+            num_nodes: 32,
+            duration: Duration::from_secs(10),
+            partitions: vec![Partition {
+                start: Duration::ZERO,
+                end: Duration::from_secs(10),
+                fraction: 0.5,
+            }],
+            ..SimulatorConfig::default()
+        };
+        let report = GossipSimulator::new(config).run();
+        assert!(report.num_dropped_partition > 0);
+        let (_, last) = report.convergence.last().unwrap();
+        assert!(*last < 1.0);
+    }
+}
@@ -5,6 +5,7 @@ pub mod cluster_info;
 pub mod cluster_info_metrics;
 pub mod contact_info;
 pub mod crds;
+pub mod crds_data_budget;
 pub mod crds_entry;
 pub mod crds_gossip;
 pub mod crds_gossip_error;
@@ -17,8 +18,10 @@ pub mod duplicate_shred;
 pub mod duplicate_shred_handler;
 pub mod duplicate_shred_listener;
 pub mod epoch_slots;
+pub mod gossip_capture;
 pub mod gossip_error;
 pub mod gossip_service;
+pub mod gossip_simulator;
 #[macro_use]
 pub mod legacy_contact_info;
 pub mod ping_pong;
@@ -2,28 +2,73 @@
 
 use {
     clap::{
-        crate_description, crate_name, value_t, value_t_or_exit, App, AppSettings, Arg, ArgMatches,
-        SubCommand,
+        crate_description, crate_name, value_t, value_t_or_exit, values_t_or_exit, App,
+        AppSettings, Arg, ArgMatches, SubCommand,
     },
     solana_clap_utils::{
         hidden_unless_forced,
         input_parsers::{keypair_of, pubkeys_of},
-        input_validators::{is_keypair_or_ask_keyword, is_port, is_pubkey},
+        input_validators::{is_keypair_or_ask_keyword, is_parsable, is_port, is_pubkey},
     },
     solana_gossip::{
-        gossip_service::discover, legacy_contact_info::LegacyContactInfo as ContactInfo,
+        cluster_info::ClusterInfo,
+        contact_info::ContactInfo as NodeContactInfo,
+        gossip_capture::{
+            crds_values_by_name, read_captured_packets, replay_captured_messages, CapturedMessage,
+            CapturedPacket, Direction,
+        },
+        gossip_service::discover,
+        gossip_simulator::{GossipSimulator, Partition, SimulatorConfig, StakeDistribution},
+        legacy_contact_info::LegacyContactInfo as ContactInfo,
+    },
+    solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        timing::timestamp,
     },
-    solana_sdk::pubkey::Pubkey,
     solana_streamer::socket::SocketAddrSpace,
     std::{
+        collections::{BTreeMap, HashMap, HashSet},
         error,
         net::{IpAddr, Ipv4Addr, SocketAddr},
+        path::PathBuf,
         process::exit,
+        sync::Arc,
         time::Duration,
     },
 };
 
 fn parse_matches() -> ArgMatches<'static> {
+    let capture_args = [
+        Arg::with_name("path")
+            .index(1)
+            .value_name("PATH")
+            .takes_value(true)
+            .multiple(true)
+            .required(true)
+            .help("Capture files, or directories of capture files"),
+        Arg::with_name("origin")
+            .long("origin")
+            .value_name("PUBKEY")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .validator(is_pubkey)
+            .help("Only include crds values originating from this node"),
+        Arg::with_name("crds_data")
+            .long("crds-data")
+            .value_name("VARIANT")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Only include crds values of this variant, e.g. ContactInfo or Vote"),
+        Arg::with_name("direction")
+            .long("direction")
+            .value_name("DIRECTION")
+            .takes_value(true)
+            .possible_values(&["sent", "received"])
+            .help("Only include packets sent or received by the captured node"),
+    ];
     let shred_version_arg = Arg::with_name("shred_version")
         .long("shred-version")
         .value_name("VERSION")
@@ -154,6 +199,174 @@ fn parse_matches() -> ArgMatches<'static> {
                         .help("Maximum time to wait in seconds [default: wait forever]"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("capture")
+                .about("Inspect gossip traffic recorded with solana-validator --gossip-capture-dir")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("summary")
+                        .about("Summarize the captured messages")
+                        .args(&capture_args)
+                        .arg(
+                            Arg::with_name("top")
+                                .long("top")
+                                .value_name("NUM")
+                                .takes_value(true)
+                                .default_value("10")
+                                .validator(is_parsable::<usize>)
+                                .help("Number of origins with the most crds values to list"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("decode")
+                        .about("Print the captured messages")
+                        .args(&capture_args),
+                )
+                .subcommand(
+                    SubCommand::with_name("replay")
+                        .about(
+                            "Feed the captured crds values into a new cluster-info and print \
+                             the resulting crds table",
+                        )
+                        .args(&capture_args),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("simulate")
+                .about("Simulate the gossip push and pull planes of a cluster in a single process")
+                .setting(AppSettings::DisableVersion)
+                .arg(
+                    Arg::with_name("num_nodes")
+                        .long("num-nodes")
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .default_value("1000")
+                        .validator(is_parsable::<usize>)
+                        .help("Number of simulated nodes"),
+                )
+                .arg(
+                    Arg::with_name("stake_distribution")
+                        .long("stake-distribution")
+                        .value_name("DISTRIBUTION")
+                        .takes_value(true)
+                        .default_value("power-law:1.0")
+                        .validator(is_parsable::<StakeDistribution>)
+                        .help("Stake distribution: equal, uniform or power-law:EXPONENT"),
+                )
+                .arg(
+                    Arg::with_name("min_latency")
+                        .long("min-latency")
+                        .value_name("MILLISECONDS")
+                        .takes_value(true)
+                        .default_value("10")
+                        .validator(is_parsable::<u64>)
+                        .help("Minimum one way latency of a message"),
+                )
+                .arg(
+                    Arg::with_name("max_latency")
+                        .long("max-latency")
+                        .value_name("MILLISECONDS")
+                        .takes_value(true)
+                        .default_value("150")
+                        .validator(is_parsable::<u64>)
+                        .help("Maximum one way latency of a message"),
+                )
+                .arg(
+                    Arg::with_name("packet_loss")
+                        .long("packet-loss")
+                        .value_name("PROBABILITY")
+                        .takes_value(true)
+                        .default_value("0")
+                        .validator(|value| match value.parse::<f64>() {
+                            Ok(loss) if (0.0..=1.0).contains(&loss) => Ok(()),
+                            _ => Err(format!("invalid packet loss {value}")),
+                        })
+                        .help("Probability that a message is dropped"),
+                )
+                .arg(
+                    Arg::with_name("partition")
+                        .long("partition")
+                        .value_name("START_SECS:END_SECS:FRACTION")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .validator(is_parsable::<Partition>)
+                        .help("Cut off a random fraction of the nodes for the given interval"),
+                )
+                .arg(
+                    Arg::with_name("duration")
+                        .long("duration")
+                        .value_name("SECONDS")
+                        .takes_value(true)
+                        .default_value("60")
+                        .validator(is_parsable::<u64>)
+                        .help("Simulated time"),
+                )
+                .arg(
+                    Arg::with_name("update_interval")
+                        .long("update-interval")
+                        .value_name("MILLISECONDS")
+                        .takes_value(true)
+                        .default_value("500")
+                        .validator(is_parsable::<u64>)
+                        .help(
+                            "A random node publishes a new contact-info at this interval; \
+                             0 disables updates",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("pull_interval")
+                        .long("pull-interval")
+                        .value_name("MILLISECONDS")
+                        .takes_value(true)
+                        .default_value("200")
+                        .validator(is_parsable::<u64>)
+                        .help("Each node sends pull requests at this interval; 0 disables pull"),
+                )
+                .arg(
+                    Arg::with_name("sample_interval")
+                        .long("sample-interval")
+                        .value_name("SECONDS")
+                        .takes_value(true)
+                        .default_value("5")
+                        .validator(is_parsable::<u64>)
+                        .help("Report the convergence of the crds tables at this interval"),
+                )
+                .arg(
+                    Arg::with_name("num_initial_peers")
+                        .long("initial-peers")
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .default_value("8")
+                        .validator(is_parsable::<usize>)
+                        .help("Number of random peers each node knows about at start"),
+                )
+                .arg(
+                    Arg::with_name("push_fanout")
+                        .long("push-fanout")
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .validator(is_parsable::<usize>)
+                        .help("Override the number of nodes each value is pushed to"),
+                )
+                .arg(
+                    Arg::with_name("active_set_size")
+                        .long("active-set-size")
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .validator(is_parsable::<usize>)
+                        .help("Override the number of nodes in each push active set entry"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .value_name("SEED")
+                        .takes_value(true)
+                        .default_value("0")
+                        .validator(is_parsable::<u64>)
+                        .help("Seed for the simulation; runs with the same seed are repeatable"),
+                ),
+        )
         .get_matches()
 }
 
@@ -325,6 +538,184 @@ fn process_rpc_url(
     Ok(())
 }
 
+// Decodes the captured packets, dropping those and the crds values within
+// which do not match the filters on the command line.
+fn read_captured_messages(
+    matches: &ArgMatches,
+) -> std::io::Result<impl Iterator<Item = (CapturedPacket, CapturedMessage)>> {
+    let paths = values_t_or_exit!(matches, "path", PathBuf);
+    let origins: Option<HashSet<Pubkey>> =
+        pubkeys_of(matches, "origin").map(|origins| origins.into_iter().collect());
+    let crds_data: Option<HashSet<String>> = matches
+        .values_of("crds_data")
+        .map(|names| names.map(String::from).collect());
+    let direction = matches
+        .value_of("direction")
+        .map(|direction| match direction {
+            "sent" => Direction::Sent,
+            _ => Direction::Received,
+        });
+    let packets = read_captured_packets(&paths)?;
+    Ok(packets.filter_map(move |packet| {
+        if direction.map(|direction| direction != packet.direction) == Some(true) {
+            return None;
+        }
+        let mut message = packet.decode()?;
+        if let Some(origins) = &origins {
+            let keep = if message.values().is_empty() {
+                origins.contains(&message.from())
+            } else {
+                message.retain_values(|value| origins.contains(&value.pubkey()))
+            };
+            if !keep {
+                return None;
+            }
+        }
+        if let Some(crds_data) = &crds_data {
+            if !message.retain_values(|value| crds_data.contains(value.data.name())) {
+                return None;
+            }
+        }
+        Some((packet, message))
+    }))
+}
+
+fn process_capture_summary(matches: &ArgMatches) -> std::io::Result<()> {
+    let top = value_t_or_exit!(matches, "top", usize);
+    let mut num_messages = 0usize;
+    let mut time_span: Option<(u64, u64)> = None;
+    // (message, direction) -> (count, bytes)
+    let mut messages = BTreeMap::<(&str, String), (usize, usize)>::new();
+    // crds data -> (count, bytes)
+    let mut values = BTreeMap::<&str, (usize, usize)>::new();
+    let mut origins = HashMap::<Pubkey, usize>::new();
+    for (packet, message) in read_captured_messages(matches)? {
+        num_messages += 1;
+        time_span = Some(match time_span {
+            None => (packet.timestamp, packet.timestamp),
+            Some((first, last)) => (first.min(packet.timestamp), last.max(packet.timestamp)),
+        });
+        let entry = messages
+            .entry((message.name(), format!("{:?}", packet.direction)))
+            .or_default();
+        entry.0 += 1;
+        entry.1 += packet.data.len();
+        for value in message.values() {
+            let entry = values.entry(value.data.name()).or_default();
+            entry.0 += 1;
+            entry.1 += bincode::serialized_size(value).unwrap_or_default() as usize;
+            *origins.entry(value.pubkey()).or_default() += 1;
+        }
+    }
+    println!("Messages: {num_messages}");
+    if let Some((first, last)) = time_span {
+        println!(
+            "Captured between {first} and {last} ({:?})",
+            Duration::from_millis(last - first)
+        );
+    }
+    println!(
+        "\n{:<14} {:<9} {:>10} {:>14}",
+        "Message", "Direction", "Count", "Bytes"
+    );
+    for ((message, direction), (count, bytes)) in &messages {
+        println!("{message:<14} {direction:<9} {count:>10} {bytes:>14}");
+    }
+    println!("\n{:<26} {:>10} {:>14}", "Crds data", "Count", "Bytes");
+    for (name, (count, bytes)) in &values {
+        println!("{name:<26} {count:>10} {bytes:>14}");
+    }
+    let mut origins: Vec<_> = origins.into_iter().collect();
+    origins.sort_unstable_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    println!("\n{:<44} {:>10}", "Origin", "Values");
+    for (origin, count) in origins.into_iter().take(top) {
+        println!("{origin:<44} {count:>10}");
+    }
+    Ok(())
+}
+
+fn process_capture_decode(matches: &ArgMatches) -> std::io::Result<()> {
+    for (packet, message) in read_captured_messages(matches)? {
+        let details = match &message {
+            CapturedMessage::PruneMessage {
+                destination,
+                prunes,
+                ..
+            } => format!(" destination={destination} prunes={prunes:?}"),
+            _ => message
+                .values()
+                .iter()
+                .map(|value| format!(" {}@{}", value.label(), value.wallclock()))
+                .collect(),
+        };
+        println!(
+            "{} {:?} {} {} from={}{details}",
+            packet.timestamp,
+            packet.direction,
+            packet.addr,
+            message.name(),
+            message.from(),
+        );
+    }
+    Ok(())
+}
+
+fn process_capture_replay(matches: &ArgMatches) -> std::io::Result<()> {
+    let keypair = Arc::new(Keypair::new());
+    let cluster_info = ClusterInfo::new(
+        NodeContactInfo::new_localhost(&keypair.pubkey(), timestamp()),
+        keypair,
+        SocketAddrSpace::Unspecified,
+    );
+    let stats = replay_captured_messages(&cluster_info, read_captured_messages(matches)?);
+    println!(
+        "Replayed {} messages with {} crds values: {} inserted, {} failed to verify",
+        stats.num_messages, stats.num_values, stats.num_inserted, stats.num_failed_verify,
+    );
+    println!("\n{:<26} {:>10}", "Crds data", "Count");
+    for (name, count) in crds_values_by_name(&cluster_info) {
+        println!("{name:<26} {count:>10}");
+    }
+    println!("\n{}", cluster_info.contact_info_trace());
+    Ok(())
+}
+
+fn process_simulate(matches: &ArgMatches) {
+    let min_latency = value_t_or_exit!(matches, "min_latency", u64);
+    let max_latency = value_t_or_exit!(matches, "max_latency", u64);
+    if min_latency > max_latency {
+        eprintln!("Error: --min-latency is larger than --max-latency");
+        exit(1);
+    }
+    let config = SimulatorConfig {
+        num_nodes: value_t_or_exit!(matches, "num_nodes", usize),
+        stake_distribution: value_t_or_exit!(matches, "stake_distribution", StakeDistribution),
+        min_latency: Duration::from_millis(min_latency),
+        max_latency: Duration::from_millis(max_latency),
+        packet_loss: value_t_or_exit!(matches, "packet_loss", f64),
+        partitions: if matches.is_present("partition") {
+            values_t_or_exit!(matches, "partition", Partition)
+        } else {
+            Vec::default()
+        },
+        duration: Duration::from_secs(value_t_or_exit!(matches, "duration", u64)),
+        update_interval: Duration::from_millis(value_t_or_exit!(matches, "update_interval", u64)),
+        pull_interval: Duration::from_millis(value_t_or_exit!(matches, "pull_interval", u64)),
+        sample_interval: Duration::from_secs(value_t_or_exit!(matches, "sample_interval", u64)),
+        num_initial_peers: value_t_or_exit!(matches, "num_initial_peers", usize),
+        push_fanout: value_t!(matches, "push_fanout", usize).ok(),
+        active_set_size: value_t!(matches, "active_set_size", usize).ok(),
+        seed: value_t_or_exit!(matches, "seed", u64),
+        ..SimulatorConfig::default()
+    };
+    if config.num_nodes < 2 {
+        eprintln!("Error: need at least two nodes");
+        exit(1);
+    }
+    let report = GossipSimulator::new(config).run();
+    print!("{report}");
+}
+
 fn main() -> Result<(), Box<dyn error::Error>> {
     solana_logger::setup_with_default("solana=info");
 
@@ -337,6 +728,15 @@ fn main() -> Result<(), Box<dyn error::Error>> {
         ("rpc-url", Some(matches)) => {
             process_rpc_url(matches, socket_addr_space)?;
         }
+        ("capture", Some(matches)) => match matches.subcommand() {
+            ("summary", Some(matches)) => process_capture_summary(matches)?,
+            ("decode", Some(matches)) => process_capture_decode(matches)?,
+            ("replay", Some(matches)) => process_capture_replay(matches)?,
+            _ => unreachable!(),
+        },
+        ("simulate", Some(matches)) => {
+            process_simulate(matches);
+        }
         _ => unreachable!(),
     }
 
@@ -60,6 +60,10 @@ impl<T: Serialize> Ping<T> {
         };
         Ok(ping)
     }
+
+    pub fn from(&self) -> &Pubkey {
+        &self.from
+    }
 }
 
 impl<T> Ping<T>
@@ -58,10 +58,11 @@ impl PushActiveSet {
         }
     }
 
-    pub(crate) fn rotate<R: Rng>(
+    pub(crate) fn rotate<R: Rng, B: Rng>(
         &mut self,
         rng: &mut R,
-        size: usize, // Number of nodes to retain in each active-set entry.
+        bloom_rng: &mut B, // Draws the keys of new bloom filters.
+        size: usize,       // Number of nodes to retain in each active-set entry.
         cluster_size: usize,
         // Gossip nodes to be sampled for each push active set.
         nodes: &[Pubkey],
@@ -95,7 +96,14 @@ impl PushActiveSet {
                     bucket.saturating_add(1).saturating_pow(2)
                 })
                 .collect();
-            entry.rotate(rng, size, num_bloom_filter_items, nodes, &weights);
+            entry.rotate(
+                rng,
+                bloom_rng,
+                size,
+                num_bloom_filter_items,
+                nodes,
+                &weights,
+            );
         }
     }
 
@@ -132,9 +140,10 @@ impl PushActiveSetEntry {
         }
     }
 
-    fn rotate<R: Rng>(
+    fn rotate<R: Rng, B: Rng>(
         &mut self,
         rng: &mut R,
+        bloom_rng: &mut B,
         size: usize, // Number of nodes to retain.
         num_bloom_filter_items: usize,
         nodes: &[Pubkey],
@@ -151,7 +160,8 @@ impl PushActiveSetEntry {
             if self.0.contains_key(node) {
                 continue;
             }
-            let bloom = AtomicBloom::from(Bloom::random(
+            let bloom = AtomicBloom::from(Bloom::random_with_rng(
+                bloom_rng,
                 num_bloom_filter_items,
                 Self::BLOOM_FALSE_RATE,
                 Self::BLOOM_MAX_BITS,
@@ -212,7 +222,8 @@ mod tests {
         stakes.insert(pubkey, rng.gen_range(1..MAX_STAKE));
         let mut active_set = PushActiveSet::default();
         assert!(active_set.0.iter().all(|entry| entry.0.is_empty()));
-        active_set.rotate(&mut rng, 5, CLUSTER_SIZE, &nodes, &stakes);
+        let mut bloom_rng = rand::thread_rng();
+        active_set.rotate(&mut rng, &mut bloom_rng, 5, CLUSTER_SIZE, &nodes, &stakes);
         assert!(active_set.0.iter().all(|entry| entry.0.len() == 5));
         // Assert that for all entries, each filter already prunes the key.
         for entry in &active_set.0 {
@@ -237,7 +248,7 @@ mod tests {
         assert!(active_set
             .get_nodes(&pubkey, other, |_| false, &stakes)
             .eq([13, 18, 16, 0].into_iter().map(|k| &nodes[k])));
-        active_set.rotate(&mut rng, 7, CLUSTER_SIZE, &nodes, &stakes);
+        active_set.rotate(&mut rng, &mut bloom_rng, 7, CLUSTER_SIZE, &nodes, &stakes);
         assert!(active_set.0.iter().all(|entry| entry.0.len() == 7));
         assert!(active_set
             .get_nodes(&pubkey, origin, |_| false, &stakes)
@@ -263,9 +274,11 @@ mod tests {
         let mut rng = ChaChaRng::from_seed([147u8; 32]);
         let nodes: Vec<_> = repeat_with(Pubkey::new_unique).take(20).collect();
         let weights: Vec<_> = repeat_with(|| rng.gen_range(1..1000)).take(20).collect();
+        let mut bloom_rng = rand::thread_rng();
         let mut entry = PushActiveSetEntry::default();
         entry.rotate(
             &mut rng,
+            &mut bloom_rng,
             5, // size
             NUM_BLOOM_FILTER_ITEMS,
             &nodes,
@@ -304,15 +317,36 @@ mod tests {
             .into_iter()
             .filter(|&&node| node != nodes[11] && node != nodes[14])));
         // Assert that rotate adds new nodes.
-        entry.rotate(&mut rng, 5, NUM_BLOOM_FILTER_ITEMS, &nodes, &weights);
+        entry.rotate(
+            &mut rng,
+            &mut bloom_rng,
+            5,
+            NUM_BLOOM_FILTER_ITEMS,
+            &nodes,
+            &weights,
+        );
         let keys = [&nodes[11], &nodes[17], &nodes[14], &nodes[5], &nodes[7]];
         assert!(entry.0.keys().eq(keys));
-        entry.rotate(&mut rng, 6, NUM_BLOOM_FILTER_ITEMS, &nodes, &weights);
+        entry.rotate(
+            &mut rng,
+            &mut bloom_rng,
+            6,
+            NUM_BLOOM_FILTER_ITEMS,
+            &nodes,
+            &weights,
+        );
         let keys = [
             &nodes[17], &nodes[14], &nodes[5], &nodes[7], &nodes[1], &nodes[13],
         ];
         assert!(entry.0.keys().eq(keys));
-        entry.rotate(&mut rng, 4, NUM_BLOOM_FILTER_ITEMS, &nodes, &weights);
+        entry.rotate(
+            &mut rng,
+            &mut bloom_rng,
+            4,
+            NUM_BLOOM_FILTER_ITEMS,
+            &nodes,
+            &weights,
+        );
         let keys = [&nodes[5], &nodes[7], &nodes[1], &nodes[13]];
         assert!(entry.0.keys().eq(keys));
     }
@@ -351,7 +351,9 @@ fn network_run_push(
                 node.gossip.purge(&node_pubkey, thread_pool, now, &timeouts);
                 (
                     node_pubkey,
-                    node.gossip.new_push_messages(&node_pubkey, now, &stakes).0,
+                    node.gossip
+                        .new_push_messages(&node_pubkey, now, &stakes, |_, _| true)
+                        .0,
                 )
             })
             .collect();
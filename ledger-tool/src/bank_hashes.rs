@@ -0,0 +1,203 @@
+//! Record the bank hash of every replayed slot, or check replayed slots
+//! against a previous recording, to find the first slot where two replays
+//! of a ledger diverge.
+
+use {
+    log::*,
+    solana_runtime::bank::{bank_hash_details, Bank},
+    solana_sdk::clock::Slot,
+    std::{
+        collections::BTreeMap,
+        fs::File,
+        io::{BufReader, BufWriter},
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+    },
+};
+
+/// Bank hashes keyed by slot, serialized as a json object
+pub type SlotBankHashes = BTreeMap<Slot, String>;
+
+pub fn read_bank_hashes_file(path: &Path) -> Result<SlotBankHashes, String> {
+    let file = File::open(path)
+        .map_err(|err| format!("Unable to open bank hashes file {}: {err}", path.display()))?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|err| format!("Unable to parse bank hashes file {}: {err}", path.display()))
+}
+
+pub fn write_bank_hashes_file(path: &Path, bank_hashes: &SlotBankHashes) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| {
+        format!(
+            "Unable to create bank hashes file {}: {err}",
+            path.display()
+        )
+    })?;
+    serde_json::to_writer_pretty(BufWriter::new(file), bank_hashes)
+        .map_err(|err| format!("Unable to write bank hashes file {}: {err}", path.display()))
+}
+
+pub struct BankHashRecorder {
+    bank_hashes: Mutex<SlotBankHashes>,
+    /// Also write the bank hash details of every slot so that a later
+    /// verification run can produce a diff at the first mismatch
+    write_bank_hash_details: bool,
+}
+
+impl BankHashRecorder {
+    pub fn new(write_bank_hash_details: bool) -> Self {
+        Self {
+            bank_hashes: Mutex::default(),
+            write_bank_hash_details,
+        }
+    }
+
+    pub fn record(&self, bank: &Bank) {
+        if self.write_bank_hash_details {
+            if let Err(err) = bank_hash_details::write_bank_hash_details_file(bank) {
+                warn!(
+                    "Unable to write bank hash details for slot {}: {err}",
+                    bank.slot()
+                );
+            }
+        }
+        self.bank_hashes
+            .lock()
+            .unwrap()
+            .insert(bank.slot(), bank.hash().to_string());
+    }
+
+    pub fn write(&self, path: &Path) -> Result<usize, String> {
+        let bank_hashes = self.bank_hashes.lock().unwrap();
+        write_bank_hashes_file(path, &bank_hashes)?;
+        Ok(bank_hashes.len())
+    }
+}
+
+pub struct BankHashVerifier {
+    reference: SlotBankHashes,
+    /// Directory holding the reference bank hash details files, named
+    /// `<slot>-<bank_hash>.json`
+    reference_bank_hash_details_dir: Option<PathBuf>,
+    matched: AtomicUsize,
+    unknown: AtomicUsize,
+    mismatched: AtomicUsize,
+    /// The first mismatching slot with its expected and actual bank hashes
+    first_mismatch: Mutex<Option<(Slot, String, String)>>,
+}
+
+impl BankHashVerifier {
+    pub fn new(
+        reference: SlotBankHashes,
+        reference_bank_hash_details_dir: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            reference,
+            reference_bank_hash_details_dir,
+            matched: AtomicUsize::default(),
+            unknown: AtomicUsize::default(),
+            mismatched: AtomicUsize::default(),
+            first_mismatch: Mutex::default(),
+        }
+    }
+
+    /// Compare `bank` against the reference. At the first mismatch, the
+    /// details of `bank` (and a diff against the reference details, when
+    /// available) are written out. Mismatches are reported by `summary` once
+    /// replay is done.
+    pub fn verify(&self, bank: &Bank) {
+        let slot = bank.slot();
+        let Some(expected_hash) = self.reference.get(&slot) else {
+            warn!("Slot {slot} is not present in the reference bank hashes");
+            self.unknown.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        let actual_hash = bank.hash().to_string();
+        if *expected_hash == actual_hash {
+            self.matched.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.mismatched.fetch_add(1, Ordering::Relaxed);
+        let mut first_mismatch = self.first_mismatch.lock().unwrap();
+        if first_mismatch.is_some() {
+            // Descendants of a mismatching slot mismatch as well
+            debug!(
+                "Bank hash mismatch at slot {slot}: expected {expected_hash}, got {actual_hash}"
+            );
+            return;
+        }
+        *first_mismatch = Some((slot, expected_hash.clone(), actual_hash.clone()));
+        drop(first_mismatch);
+
+        eprintln!(
+            "Bank hash mismatch at slot {slot}: expected {expected_hash}, got {actual_hash} \
+            (parent slot {}, {} earlier slots matched)",
+            bank.parent_slot(),
+            self.matched.load(Ordering::Relaxed),
+        );
+        if let Err(err) = bank_hash_details::write_bank_hash_details_file(bank) {
+            eprintln!("Unable to write bank hash details: {err}");
+        }
+        match &self.reference_bank_hash_details_dir {
+            Some(dir) => {
+                let path = dir.join(format!("{slot}-{expected_hash}.json"));
+                match bank_hash_details::read_bank_hash_details_file(&path).and_then(|expected| {
+                    bank_hash_details::write_bank_hash_details_diff_file(bank, &expected)
+                }) {
+                    Ok(diff_path) => eprintln!("Bank hash diff written to {}", diff_path.display()),
+                    Err(err) => eprintln!("Unable to diff against the reference: {err}"),
+                }
+            }
+            None => eprintln!(
+                "To get a diff, replay the reference with --halt-at-slot {slot} \
+                --write-bank-file and pass its bank_hash_details directory to \
+                --reference-bank-hash-details"
+            ),
+        }
+    }
+
+    /// Returns the number of slots that matched the reference and the number
+    /// of slots that were absent from it, or an error if any slot mismatched
+    pub fn summary(&self) -> Result<(usize, usize), String> {
+        let matched = self.matched.load(Ordering::Relaxed);
+        match &*self.first_mismatch.lock().unwrap() {
+            None => Ok((matched, self.unknown.load(Ordering::Relaxed))),
+            Some((slot, expected_hash, actual_hash)) => Err(format!(
+                "Bank hash mismatch at slot {slot}: expected {expected_hash}, got {actual_hash}; \
+                {} replayed slots mismatched, {matched} matched",
+                self.mismatched.load(Ordering::Relaxed),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_slot_bank_hashes() {
+        let bank_hashes: SlotBankHashes = [
+            (
+                0,
+                "4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZAMdL4VZHirAn".to_string(),
+            ),
+            (7, "8opHzTAnfzRpPEx21XtnrVTX28YQuCpAjcn1PczScKh".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let serialized = serde_json::to_string(&bank_hashes).unwrap();
+        let deserialized: SlotBankHashes = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, bank_hashes);
+
+        // Reference files may also be written by hand
+        let deserialized: SlotBankHashes = serde_json::from_str(
+            r#"{"0": "4sGjMW1sUnHzSxGspuhpqLDx6wiyjNtZAMdL4VZHirAn",
+                "7": "8opHzTAnfzRpPEx21XtnrVTX28YQuCpAjcn1PczScKh"}"#,
+        )
+        .unwrap();
+        assert_eq!(deserialized, bank_hashes);
+    }
+}
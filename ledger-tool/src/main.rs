@@ -1,9 +1,12 @@
 #![allow(clippy::arithmetic_side_effects)]
 use {
-    crate::{args::*, bigtable::*, ledger_path::*, ledger_utils::*, output::*, program::*},
+    crate::{
+        args::*, bank_hashes::*, bigtable::*, ledger_path::*, ledger_utils::*, output::*,
+        program::*, replay_transaction::*, turbine_tree::*,
+    },
     chrono::{DateTime, Utc},
     clap::{
-        crate_description, crate_name, value_t, value_t_or_exit, values_t_or_exit, App,
+        crate_description, crate_name, value_t, value_t_or_exit, values_t, values_t_or_exit, App,
         AppSettings, Arg, ArgMatches, SubCommand,
     },
     dashmap::DashMap,
@@ -25,7 +28,7 @@ use {
         input_parsers::{cluster_type_of, pubkey_of, pubkeys_of},
         input_validators::{
             is_parsable, is_pow2, is_pubkey, is_pubkey_or_keypair, is_slot, is_valid_percentage,
-            validate_maximum_full_snapshot_archives_to_retain,
+            is_within_range, validate_maximum_full_snapshot_archives_to_retain,
             validate_maximum_incremental_snapshot_archives_to_retain,
         },
     },
@@ -38,14 +41,15 @@ use {
     solana_entry::entry::Entry,
     solana_ledger::{
         ancestor_iterator::AncestorIterator,
-        blockstore::{create_new_ledger, Blockstore, PurgeType},
+        blockstore::{create_new_ledger, Blockstore, PurgeType, TransactionHistoryFilter},
         blockstore_db::{self, columns as cf, Column, ColumnName, Database},
         blockstore_options::{
             AccessType, BlockstoreRecoveryMode, LedgerColumnOptions,
             BLOCKSTORE_DIRECTORY_ROCKS_FIFO,
         },
-        blockstore_processor::ProcessOptions,
-        shred::Shred,
+        blockstore_processor::{ProcessCallback, ProcessOptions},
+        leader_schedule_utils,
+        shred::{Shred, ShredType},
         use_snapshot_archives_at_startup::{self, UseSnapshotArchivesAtStartup},
     },
     solana_measure::{measure, measure::Measure},
@@ -55,7 +59,7 @@ use {
         runtime_config::RuntimeConfig,
         snapshot_archive_info::SnapshotArchiveInfoGetter,
         snapshot_bank_utils,
-        snapshot_minimizer::SnapshotMinimizer,
+        snapshot_minimizer::{PdaSeedPattern, SnapshotMinimizer, SnapshotMinimizerFilters},
         snapshot_utils::{
             ArchiveFormat, SnapshotVersion, DEFAULT_ARCHIVE_COMPRESSION,
             SUPPORTED_ARCHIVE_COMPRESSION,
@@ -74,6 +78,7 @@ use {
         pubkey::Pubkey,
         rent::Rent,
         shred_version::compute_shred_version,
+        signature::Signature,
         stake::{self, state::StakeStateV2},
         system_program,
         transaction::{
@@ -81,6 +86,7 @@ use {
         },
     },
     solana_stake_program::stake_state::{self, PointValue},
+    solana_turbine::cluster_nodes::DATA_PLANE_FANOUT,
     solana_vote_program::{
         self,
         vote_state::{self, VoteState},
@@ -103,11 +109,14 @@ use {
 };
 
 mod args;
+mod bank_hashes;
 mod bigtable;
 mod ledger_path;
 mod ledger_utils;
 mod output;
 mod program;
+mod replay_transaction;
+mod turbine_tree;
 
 #[derive(PartialEq, Eq)]
 enum LedgerOutputMethod {
@@ -1030,6 +1039,7 @@ fn minimize_bank_for_snapshot(
     bank: &Bank,
     snapshot_slot: Slot,
     ending_slot: Slot,
+    filters: &SnapshotMinimizerFilters,
 ) -> bool {
     let ((transaction_account_set, possibly_incomplete), transaction_accounts_measure) = measure!(
         blockstore.get_accounts_used_in_range(bank, snapshot_slot, ending_slot),
@@ -1038,7 +1048,13 @@ fn minimize_bank_for_snapshot(
     let total_accounts_len = transaction_account_set.len();
     info!("Added {total_accounts_len} accounts from transactions. {transaction_accounts_measure}");
 
-    SnapshotMinimizer::minimize(bank, snapshot_slot, ending_slot, transaction_account_set);
+    SnapshotMinimizer::minimize(
+        bank,
+        snapshot_slot,
+        ending_slot,
+        transaction_account_set,
+        filters,
+    );
     possibly_incomplete
 }
 
@@ -1687,6 +1703,223 @@ fn main() {
                         that went into computing the completed bank's bank hash. The file will be \
                         written within <LEDGER_DIR>/bank_hash_details/"),
             )
+            .arg(
+                Arg::with_name("record_bank_hashes")
+                    .long("record-bank-hashes")
+                    .value_name("FILENAME")
+                    .takes_value(true)
+                    .conflicts_with("verify_bank_hashes")
+                    .help("Write the bank hash of every replayed slot to FILENAME, for use with \
+                        --verify-bank-hashes"),
+            )
+            .arg(
+                Arg::with_name("record_bank_hash_details")
+                    .long("record-bank-hash-details")
+                    .takes_value(false)
+                    .requires("record_bank_hashes")
+                    .help("Along with --record-bank-hashes, write the bank hash details of every \
+                        replayed slot within <LEDGER_DIR>/bank_hash_details/"),
+            )
+            .arg(
+                Arg::with_name("verify_bank_hashes")
+                    .long("verify-bank-hashes")
+                    .value_name("FILENAME")
+                    .takes_value(true)
+                    .help("Compare the bank hash of every replayed slot against FILENAME, a json \
+                        object of slot to bank hash as written by --record-bank-hashes. The bank \
+                        hash details of the first mismatching slot are written within \
+                        <LEDGER_DIR>/bank_hash_details/, and the command fails after replay if \
+                        any slot mismatched"),
+            )
+            .arg(
+                Arg::with_name("reference_bank_hash_details")
+                    .long("reference-bank-hash-details")
+                    .value_name("DIR")
+                    .takes_value(true)
+                    .requires("verify_bank_hashes")
+                    .help("Directory of bank hash details files for the reference, such as the \
+                        bank_hash_details/ directory of another ledger. On a mismatch, the \
+                        accounts, transaction counts, fees and rent of the mismatching slot are \
+                        diffed against the reference"),
+            )
+        ).subcommand(
+            SubCommand::with_name("replay-transaction")
+            .about("Re-execute a transaction against the state of the slot it landed in, \
+                    without committing it, and print its logs, compute units, inner \
+                    instructions and account changes")
+            .arg(&no_snapshot_arg)
+            .arg(&account_paths_arg)
+            .arg(&accounts_hash_cache_path_arg)
+            .arg(&accounts_index_bins)
+            .arg(&accounts_index_limit)
+            .arg(&disable_disk_index)
+            .arg(&accountsdb_skip_shrink)
+            .arg(&hard_forks_arg)
+            .arg(&max_genesis_archive_unpacked_size_arg)
+            .arg(&use_snapshot_archives_at_startup)
+            .arg(
+                Arg::with_name("signature")
+                    .index(1)
+                    .value_name("SIGNATURE")
+                    .takes_value(true)
+                    .required(true)
+                    .validator(is_parsable::<Signature>)
+                    .help("Signature of the transaction to replay"),
+            )
+            .arg(
+                Arg::with_name("slot")
+                    .long("slot")
+                    .value_name("SLOT")
+                    .takes_value(true)
+                    .validator(is_slot)
+                    .help("Slot the transaction landed in. Required unless the ledger was \
+                        written with transaction history enabled"),
+            )
+            .arg(
+                Arg::with_name("program")
+                    .long("program")
+                    .value_names(&["PROGRAM_ID", "FILE"])
+                    .takes_value(true)
+                    .number_of_values(2)
+                    .multiple(true)
+                    .help("Execute the ELF in FILE in place of the on-chain program PROGRAM_ID, \
+                        keeping the loader that owns the program. The program is replaced \
+                        before any transaction of the slot executes. May be specified \
+                        multiple times"),
+            )
+            .arg(
+                Arg::with_name("activate_feature")
+                    .long("activate-feature")
+                    .value_name("FEATURE_ID")
+                    .takes_value(true)
+                    .validator(is_pubkey)
+                    .multiple(true)
+                    .help("Treat FEATURE_ID as active. Features that only take effect at an \
+                        epoch boundary are not applied"),
+            )
+            .arg(
+                Arg::with_name("deactivate_feature")
+                    .long("deactivate-feature")
+                    .value_name("FEATURE_ID")
+                    .takes_value(true)
+                    .validator(is_pubkey)
+                    .multiple(true)
+                    .help("Treat FEATURE_ID as inactive. Features that only take effect at an \
+                        epoch boundary are not applied"),
+            )
+        ).subcommand(
+            SubCommand::with_name("turbine-tree")
+            .about("Print the turbine retransmit tree of a shred, with the expected number \
+                    of hops and the fraction of stake reached when nodes fail")
+            .arg(&no_snapshot_arg)
+            .arg(&account_paths_arg)
+            .arg(&accounts_hash_cache_path_arg)
+            .arg(&accounts_index_bins)
+            .arg(&accounts_index_limit)
+            .arg(&disable_disk_index)
+            .arg(&halt_at_slot_arg)
+            .arg(&hard_forks_arg)
+            .arg(&max_genesis_archive_unpacked_size_arg)
+            .arg(&use_snapshot_archives_at_startup)
+            .arg(
+                Arg::with_name("nodes_file")
+                    .long("nodes-file")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .requires_all(&["slot", "slot_leader"])
+                    .help("Read the stake of each node from a JSON list of \
+                        {\"pubkey\": ..., \"stake\": LAMPORTS} objects, or from the output of \
+                        `solana validators --output json`, instead of the ledger"),
+            )
+            .arg(
+                Arg::with_name("slot")
+                    .long("slot")
+                    .value_name("SLOT")
+                    .takes_value(true)
+                    .validator(is_slot)
+                    .help("Slot of the shred [default: the slot of the loaded bank]"),
+            )
+            .arg(
+                Arg::with_name("slot_leader")
+                    .long("slot-leader")
+                    .value_name("PUBKEY")
+                    .takes_value(true)
+                    .validator(is_pubkey)
+                    .help("Leader of the slot [default: the leader in the leader schedule]"),
+            )
+            .arg(
+                Arg::with_name("shred_index")
+                    .long("shred-index")
+                    .value_name("INDEX")
+                    .takes_value(true)
+                    .default_value("0")
+                    .validator(is_parsable::<u32>)
+                    .help("Index of the shred"),
+            )
+            .arg(
+                Arg::with_name("shred_type")
+                    .long("shred-type")
+                    .value_name("TYPE")
+                    .takes_value(true)
+                    .possible_values(&["data", "code"])
+                    .default_value("data")
+                    .help("Type of the shred"),
+            )
+            .arg(
+                Arg::with_name("num_shreds")
+                    .long("num-shreds")
+                    .value_name("NUM")
+                    .takes_value(true)
+                    .default_value("32")
+                    .validator(|value| is_within_range(value, 1..=u32::MAX as usize))
+                    .help("Average hop counts and failures over this many consecutive shreds, \
+                        starting at --shred-index"),
+            )
+            .arg(
+                Arg::with_name("fanout")
+                    .long("fanout")
+                    .value_name("FANOUT")
+                    .takes_value(true)
+                    .multiple(true)
+                    .validator(|value| is_within_range(value, 1..))
+                    .help("Lay out the tree with this fanout. May be specified multiple times \
+                        to compare fanouts [default: the turbine data plane fanout]"),
+            )
+            .arg(
+                Arg::with_name("node")
+                    .long("node")
+                    .value_name("PUBKEY")
+                    .takes_value(true)
+                    .multiple(true)
+                    .validator(is_pubkey)
+                    .help("Only print the position of this node in the tree, along with all \
+                        of its children. May be specified multiple times"),
+            )
+            .arg(
+                Arg::with_name("failure_rate")
+                    .long("failure-rate")
+                    .value_name("RATE")
+                    .takes_value(true)
+                    .multiple(true)
+                    .default_value("0.01,0.05,0.1")
+                    .use_delimiter(true)
+                    .validator(|value| match value.parse::<f64>() {
+                        Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(()),
+                        _ => Err(format!("Unable to parse failure rate {value}, \
+                            expected a number between 0 and 1")),
+                    })
+                    .help("Probability that each node independently fails to receive and \
+                        retransmit shreds"),
+            )
+            .arg(
+                Arg::with_name("num_trials")
+                    .long("num-trials")
+                    .value_name("NUM")
+                    .takes_value(true)
+                    .default_value("100")
+                    .validator(|value| is_within_range(value, 1..))
+                    .help("Number of random failure trials per shred and failure rate"),
+            )
         ).subcommand(
             SubCommand::with_name("graph")
             .about("Create a Graphviz rendering of the ledger")
@@ -1898,9 +2131,10 @@ fn main() {
                     .takes_value(false)
                     .help("Create a minimized snapshot instead of a full snapshot. This snapshot \
                           will only include information needed to replay the ledger from the \
-                          snapshot slot to the ending slot.")
+                          snapshot slot to the ending slot, and the accounts included by the \
+                          --include-* arguments. The ending slot is required unless accounts \
+                          are included.")
                     .conflicts_with("incremental")
-                    .requires("ending_slot")
             )
             .arg(
                 Arg::with_name("ending_slot")
@@ -1909,6 +2143,72 @@ fn main() {
                     .value_name("ENDING_SLOT")
                     .help("Ending slot for minimized snapshot creation")
             )
+            .arg(
+                Arg::with_name("minimize_include_program")
+                    .long("include-program")
+                    .takes_value(true)
+                    .value_name("PROGRAM_ID")
+                    .validator(is_pubkey)
+                    .multiple(true)
+                    .requires("minimized")
+                    .help("Keep this program and all the accounts it owns in the minimized snapshot")
+            )
+            .arg(
+                Arg::with_name("minimize_include_pda")
+                    .long("include-pda")
+                    .takes_value(true)
+                    .value_name("PROGRAM_ID:SEED,...")
+                    .validator(|pattern| PdaSeedPattern::from_str(&pattern).map(|_| ()))
+                    .multiple(true)
+                    .requires("minimized")
+                    .help("Keep the program derived address of PROGRAM_ID with these seeds in the \
+                          minimized snapshot. Each seed is str:TEXT, pubkey:PUBKEY, u64:NUMBER \
+                          or *, which stands for each of the other accounts kept in the \
+                          snapshot, e.g. the wallets of kept token accounts.")
+            )
+            .arg(
+                Arg::with_name("minimize_include_token_mint")
+                    .long("include-token-mint")
+                    .takes_value(true)
+                    .value_name("MINT")
+                    .validator(is_pubkey)
+                    .multiple(true)
+                    .requires("minimized")
+                    .help("Keep this mint and all of its token accounts in the minimized snapshot")
+            )
+            .arg(
+                Arg::with_name("minimize_include_account")
+                    .long("include-account")
+                    .takes_value(true)
+                    .value_name("PUBKEY")
+                    .validator(is_pubkey)
+                    .multiple(true)
+                    .requires("minimized")
+                    .help("Keep this account in the minimized snapshot")
+            )
+            .arg(
+                Arg::with_name("minimize_exclude_program")
+                    .long("exclude-program")
+                    .takes_value(true)
+                    .value_name("PROGRAM_ID")
+                    .validator(is_pubkey)
+                    .multiple(true)
+                    .requires("minimized")
+                    .help("Remove this program and the accounts it owns from the minimized \
+                          snapshot, unless they are needed to replay the slot range or to load \
+                          the snapshot")
+            )
+            .arg(
+                Arg::with_name("minimize_exclude_account")
+                    .long("exclude-account")
+                    .takes_value(true)
+                    .value_name("PUBKEY")
+                    .validator(is_pubkey)
+                    .multiple(true)
+                    .requires("minimized")
+                    .help("Remove this account from the minimized snapshot, unless it is \
+                          needed to replay the slot range or to load the snapshot")
+            )
             .arg(
                 Arg::with_name("snapshot_archive_format")
                     .long("snapshot-archive-format")
@@ -2063,6 +2363,24 @@ fn main() {
                     .takes_value(false)
                     .help("Limit purging to dead slots only")
             )
+            .arg(
+                Arg::with_name("filter")
+                    .long("filter")
+                    .value_name("vote|PROGRAM_ID")
+                    .takes_value(true)
+                    .multiple(true)
+                    .validator(|value| {
+                        if value == "vote" {
+                            Ok(())
+                        } else {
+                            is_pubkey(value)
+                        }
+                    })
+                    .conflicts_with("dead_slots_only")
+                    .help("Keep the slots, and only delete the transaction status, address \
+                           signatures and memos of vote transactions, or of transactions that \
+                           only invoke PROGRAM_ID. May be specified multiple times")
+            )
         )
         .subcommand(
             SubCommand::with_name("list-roots")
@@ -2206,6 +2524,89 @@ fn main() {
         bigtable_process_command(&ledger_path, arg_matches)
     } else if let ("program", Some(arg_matches)) = matches.subcommand() {
         program(&ledger_path, arg_matches)
+    } else if let ("turbine-tree", Some(arg_matches)) = matches.subcommand() {
+        let (stakes, slot, slot_leader) = match arg_matches.value_of("nodes_file") {
+            Some(nodes_file) => {
+                let stakes = read_nodes_file(Path::new(nodes_file)).unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    exit(1);
+                });
+                let slot = value_t_or_exit!(arg_matches, "slot", Slot);
+                let slot_leader = pubkey_of(arg_matches, "slot_leader").unwrap();
+                (stakes, slot, slot_leader)
+            }
+            None => {
+                let ledger_path = canonicalize_ledger_path(&ledger_path);
+                let process_options = ProcessOptions {
+                    new_hard_forks: hardforks_of(arg_matches, "hard_forks"),
+                    halt_at_slot: value_t!(arg_matches, "halt_at_slot", Slot).ok(),
+                    run_verification: false,
+                    accounts_db_config: Some(get_accounts_db_config(&ledger_path, arg_matches)),
+                    use_snapshot_archives_at_startup: value_t_or_exit!(
+                        arg_matches,
+                        use_snapshot_archives_at_startup::cli::NAME,
+                        UseSnapshotArchivesAtStartup
+                    ),
+                    ..ProcessOptions::default()
+                };
+                let genesis_config = open_genesis_config_by(&ledger_path, arg_matches);
+                let blockstore = open_blockstore(
+                    &ledger_path,
+                    get_access_type(&process_options),
+                    wal_recovery_mode,
+                    force_update_to_open,
+                    enforce_ulimit_nofile,
+                );
+                let (bank_forks, ..) = load_and_process_ledger(
+                    arg_matches,
+                    &genesis_config,
+                    Arc::new(blockstore),
+                    process_options,
+                    snapshot_archive_path,
+                    incremental_snapshot_archive_path,
+                )
+                .unwrap_or_else(|err| {
+                    eprintln!("Failed to load ledger: {err:?}");
+                    exit(1);
+                });
+                let bank = bank_forks.read().unwrap().working_bank();
+                let slot = value_t!(arg_matches, "slot", Slot).unwrap_or_else(|_| bank.slot());
+                let epoch = bank.epoch_schedule().get_epoch(slot);
+                let Some(stakes) = bank.epoch_staked_nodes(epoch) else {
+                    eprintln!(
+                        "Stakes of epoch {epoch} are not known as of slot {}",
+                        bank.slot()
+                    );
+                    exit(1);
+                };
+                let slot_leader = pubkey_of(arg_matches, "slot_leader")
+                    .or_else(|| leader_schedule_utils::slot_leader_at(slot, &bank))
+                    .unwrap_or_else(|| {
+                        eprintln!(
+                            "Unable to find the leader of slot {slot}, specify it with \
+                            --slot-leader"
+                        );
+                        exit(1);
+                    });
+                (stakes.as_ref().clone(), slot, slot_leader)
+            }
+        };
+        let config = TurbineTreeConfig {
+            slot,
+            slot_leader,
+            shred_index: value_t_or_exit!(arg_matches, "shred_index", u32),
+            shred_type: match arg_matches.value_of("shred_type") {
+                Some("code") => ShredType::Code,
+                _ => ShredType::Data,
+            },
+            num_shreds: value_t_or_exit!(arg_matches, "num_shreds", u32),
+            fanouts: values_t!(arg_matches, "fanout", usize)
+                .unwrap_or_else(|_| vec![DATA_PLANE_FANOUT]),
+            nodes: pubkeys_of(arg_matches, "node").unwrap_or_default(),
+            failure_rates: values_t_or_exit!(arg_matches, "failure_rate", f64),
+            num_trials: value_t_or_exit!(arg_matches, "num_trials", usize),
+        };
+        print_turbine_trees(&stakes, &config);
     } else {
         let ledger_path = canonicalize_ledger_path(&ledger_path);
 
@@ -2647,6 +3048,39 @@ fn main() {
                     );
                 }
 
+                let bank_hash_recorder = value_t!(arg_matches, "record_bank_hashes", PathBuf)
+                    .ok()
+                    .map(|path| {
+                        let recorder = BankHashRecorder::new(
+                            arg_matches.is_present("record_bank_hash_details"),
+                        );
+                        (path, Arc::new(recorder))
+                    });
+                let bank_hash_verifier = value_t!(arg_matches, "verify_bank_hashes", PathBuf)
+                    .ok()
+                    .map(|path| {
+                        let reference = read_bank_hashes_file(&path).unwrap_or_else(|err| {
+                            eprintln!("{err}");
+                            exit(1);
+                        });
+                        let reference_bank_hash_details_dir =
+                            value_t!(arg_matches, "reference_bank_hash_details", PathBuf).ok();
+                        Arc::new(BankHashVerifier::new(
+                            reference,
+                            reference_bank_hash_details_dir,
+                        ))
+                    });
+                let slot_callback: Option<ProcessCallback> =
+                    if let Some((_, recorder)) = &bank_hash_recorder {
+                        let recorder = recorder.clone();
+                        Some(Arc::new(move |bank: &Bank| recorder.record(bank)))
+                    } else if let Some(verifier) = &bank_hash_verifier {
+                        let verifier = verifier.clone();
+                        Some(Arc::new(move |bank: &Bank| verifier.verify(bank)))
+                    } else {
+                        None
+                    };
+
                 let process_options = ProcessOptions {
                     new_hard_forks: hardforks_of(arg_matches, "hard_forks"),
                     run_verification: !(arg_matches.is_present("skip_poh_verify")
@@ -2674,6 +3108,7 @@ fn main() {
                         use_snapshot_archives_at_startup::cli::NAME,
                         UseSnapshotArchivesAtStartup
                     ),
+                    slot_callback,
                     ..ProcessOptions::default()
                 };
                 let print_accounts_stats = arg_matches.is_present("print_accounts_stats");
@@ -2708,8 +3143,133 @@ fn main() {
                     let working_bank = bank_forks.read().unwrap().working_bank();
                     let _ = bank_hash_details::write_bank_hash_details_file(&working_bank);
                 }
+                if let Some((path, recorder)) = bank_hash_recorder {
+                    match recorder.write(&path) {
+                        Ok(count) => {
+                            println!("Recorded {count} bank hashes to {}", path.display())
+                        }
+                        Err(err) => {
+                            eprintln!("{err}");
+                            exit(1);
+                        }
+                    }
+                }
+                let verify_result = bank_hash_verifier.map(|verifier| verifier.summary());
                 exit_signal.store(true, Ordering::Relaxed);
                 system_monitor_service.join().unwrap();
+                match verify_result {
+                    Some(Ok((matched, unknown))) => println!(
+                        "All {matched} replayed slots found in the reference matched, \
+                        {unknown} replayed slots were not in the reference"
+                    ),
+                    Some(Err(err)) => {
+                        eprintln!("{err}");
+                        exit(1);
+                    }
+                    None => (),
+                }
+            }
+            ("replay-transaction", Some(arg_matches)) => {
+                let signature = value_t_or_exit!(arg_matches, "signature", Signature);
+                let program_overrides = arg_matches
+                    .values_of("program")
+                    .map(|values| values.collect::<Vec<_>>())
+                    .unwrap_or_default()
+                    .chunks(2)
+                    .map(|program| {
+                        let program_id = Pubkey::from_str(program[0]).unwrap_or_else(|err| {
+                            eprintln!("Invalid program id {}: {err}", program[0]);
+                            exit(1);
+                        });
+                        let elf = std::fs::read(program[1]).unwrap_or_else(|err| {
+                            eprintln!("Unable to read program file {}: {err}", program[1]);
+                            exit(1);
+                        });
+                        ProgramOverride { program_id, elf }
+                    })
+                    .collect::<Vec<_>>();
+                let activate_features =
+                    pubkeys_of(arg_matches, "activate_feature").unwrap_or_default();
+                let deactivate_features =
+                    pubkeys_of(arg_matches, "deactivate_feature").unwrap_or_default();
+
+                let blockstore = Arc::new(open_blockstore(
+                    &ledger_path,
+                    AccessType::Secondary,
+                    wal_recovery_mode,
+                    force_update_to_open,
+                    enforce_ulimit_nofile,
+                ));
+                let slot = value_t!(arg_matches, "slot", Slot)
+                    .or_else(|_| find_transaction_slot(&blockstore, &signature))
+                    .unwrap_or_else(|err| {
+                        eprintln!("{err}");
+                        exit(1);
+                    });
+                let Some(parent_slot) = blockstore
+                    .meta(slot)
+                    .ok()
+                    .flatten()
+                    .and_then(|meta| meta.parent_slot)
+                else {
+                    eprintln!("Unable to find the parent of slot {slot}");
+                    exit(1);
+                };
+                let (preceding_batches, transaction) =
+                    split_slot_transactions(&blockstore, slot, &signature).unwrap_or_else(|err| {
+                        eprintln!("{err}");
+                        exit(1);
+                    });
+
+                let process_options = ProcessOptions {
+                    new_hard_forks: hardforks_of(arg_matches, "hard_forks"),
+                    halt_at_slot: Some(parent_slot),
+                    run_verification: false,
+                    accounts_db_config: Some(get_accounts_db_config(&ledger_path, arg_matches)),
+                    accounts_db_skip_shrink: arg_matches.is_present("accounts_db_skip_shrink"),
+                    use_snapshot_archives_at_startup: value_t_or_exit!(
+                        arg_matches,
+                        use_snapshot_archives_at_startup::cli::NAME,
+                        UseSnapshotArchivesAtStartup
+                    ),
+                    ..ProcessOptions::default()
+                };
+                let genesis_config = open_genesis_config_by(&ledger_path, arg_matches);
+                let (bank_forks, ..) = load_and_process_ledger(
+                    arg_matches,
+                    &genesis_config,
+                    blockstore,
+                    process_options,
+                    snapshot_archive_path,
+                    incremental_snapshot_archive_path,
+                )
+                .unwrap_or_else(|err| {
+                    eprintln!("Failed to load ledger: {err:?}");
+                    exit(1);
+                });
+                let Some(parent_bank) = bank_forks.read().unwrap().get(parent_slot) else {
+                    eprintln!(
+                        "Parent slot {parent_slot} could not be replayed; the snapshot used \
+                        may be newer than it"
+                    );
+                    exit(1);
+                };
+                let bank = new_replay_bank(
+                    parent_bank,
+                    slot,
+                    &activate_features,
+                    &deactivate_features,
+                    &program_overrides,
+                    preceding_batches,
+                )
+                .unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    exit(1);
+                });
+                if let Err(err) = execute_and_print(&bank, transaction) {
+                    eprintln!("{err}");
+                    exit(1);
+                }
             }
             ("graph", Some(arg_matches)) => {
                 let output_file = value_t_or_exit!(arg_matches, "graph_filename", String);
@@ -2897,7 +3457,33 @@ fn main() {
                 }
                 process_options.halt_at_slot = Some(snapshot_slot);
 
-                let ending_slot = if is_minimized {
+                let minimizer_filters = SnapshotMinimizerFilters {
+                    include_programs: pubkeys_of(arg_matches, "minimize_include_program")
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                    include_pdas: values_t!(arg_matches, "minimize_include_pda", PdaSeedPattern)
+                        .unwrap_or_default(),
+                    include_token_mints: pubkeys_of(arg_matches, "minimize_include_token_mint")
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                    include_accounts: pubkeys_of(arg_matches, "minimize_include_account")
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                    exclude_programs: pubkeys_of(arg_matches, "minimize_exclude_program")
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                    exclude_accounts: pubkeys_of(arg_matches, "minimize_exclude_account")
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                };
+                let ending_slot = if is_minimized
+                    && (arg_matches.is_present("ending_slot") || minimizer_filters.is_empty())
+                {
                     let ending_slot = value_t_or_exit!(arg_matches, "ending_slot", Slot);
                     if ending_slot <= snapshot_slot {
                         eprintln!(
@@ -2907,6 +3493,9 @@ fn main() {
                     }
 
                     Some(ending_slot)
+                } else if is_minimized {
+                    // Only the accounts included by the filters are kept
+                    Some(snapshot_slot)
                 } else {
                     None
                 };
@@ -3179,6 +3768,7 @@ fn main() {
                                 &bank,
                                 snapshot_slot,
                                 ending_slot.unwrap(),
+                                &minimizer_filters,
                             )
                         } else {
                             false
@@ -3951,6 +4541,34 @@ fn main() {
                     eprintln!("end slot {end_slot} is less than start slot {start_slot}");
                     exit(1);
                 }
+                if let Some(filters) = arg_matches.values_of("filter") {
+                    let mut filter = TransactionHistoryFilter::default();
+                    for value in filters {
+                        if value == "vote" {
+                            filter.votes = true;
+                        } else {
+                            filter.program_ids.insert(Pubkey::from_str(value).unwrap());
+                        }
+                    }
+                    let mut num_purged = 0;
+                    for slots in &(start_slot..=end_slot).chunks(batch_size) {
+                        let slots = slots.collect::<Vec<_>>();
+                        let start_slot = *slots.first().unwrap();
+                        let end_slot = *slots.last().unwrap();
+                        info!("Purging transaction history in slots {start_slot} to {end_slot}");
+                        num_purged += blockstore
+                            .purge_transaction_history(start_slot, end_slot, &filter)
+                            .unwrap_or_else(|err| {
+                                eprintln!("Failed to purge transaction history: {err:?}");
+                                exit(1);
+                            });
+                    }
+                    println!(
+                        "Purged the transaction history of {num_purged} transactions in slots \
+                        {start_slot} to {end_slot}"
+                    );
+                    return;
+                }
                 info!(
                 "Purging data from slots {} to {} ({} slots) (do compaction: {}) (dead slot only: {})",
                 start_slot,
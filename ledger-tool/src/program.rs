@@ -5,8 +5,8 @@ use {
     serde::{Deserialize, Serialize},
     serde_json::Result,
     solana_bpf_loader_program::{
-        create_vm, load_program_from_bytes, serialization::serialize_parameters,
-        syscalls::create_program_runtime_environment_v1,
+        create_vm, load_program_from_bytes, program_execution_trace,
+        serialization::serialize_parameters, syscalls::create_program_runtime_environment_v1,
     },
     solana_clap_utils::input_parsers::pubkeys_of,
     solana_ledger::{
@@ -27,6 +27,7 @@ use {
         account::AccountSharedData,
         account_utils::StateMut,
         bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        execution_trace::write_collapsed_stacks,
         feature_set,
         pubkey::Pubkey,
         slot_history::Slot,
@@ -254,6 +255,17 @@ and the following fields are required
                         .takes_value(true)
                         .value_name("FILE"),
                 )
+                .arg(
+                    Arg::with_name("profile")
+                        .help(
+                            "Output the compute units consumed by each stack of functions of the \
+                             programs executed, in the collapsed stack format of flamegraphs. \
+                             Requires --mode interpreter or debugger",
+                        )
+                        .long("profile")
+                        .takes_value(true)
+                        .value_name("FILE"),
+                )
                 .arg(&program_arg)
         )
         )
@@ -440,6 +452,11 @@ pub fn program(ledger_path: &Path, matches: &ArgMatches<'_>) {
         ("run", Some(arg_matches)) => arg_matches,
         _ => unreachable!(),
     };
+    // Only the interpreter records the execution traces that profiles are built from
+    if matches.is_present("profile") && matches.value_of("mode").unwrap() == "jit" {
+        eprintln!("--profile requires --mode interpreter or --mode debugger");
+        exit(1);
+    }
     let ledger_path = canonicalize_ledger_path(ledger_path);
     let bank = load_blockstore(&ledger_path, matches);
     let loader_id = bpf_loader_upgradeable::id();
@@ -556,6 +573,9 @@ pub fn program(ledger_path: &Path, matches: &ArgMatches<'_>) {
         debug!("Loaded program {}", key);
     }
     invoke_context.programs_loaded_for_tx_batch = &loaded_programs;
+    if matches.occurrences_of("trace") > 0 || matches.is_present("profile") {
+        invoke_context.enable_execution_trace();
+    }
 
     invoke_context
         .transaction_context
@@ -608,6 +628,18 @@ pub fn program(ledger_path: &Path, matches: &ArgMatches<'_>) {
         }
     }
     drop(vm);
+    if let Some(profile) = matches.value_of("profile") {
+        // The programs invoked by the program are traced by the loader when they return
+        let execution_trace =
+            program_execution_trace(&invoke_context, &verified_executable, program_id).unwrap();
+        let invoked_execution_traces = invoke_context.take_execution_traces();
+        let mut file = File::create(profile).unwrap();
+        write_collapsed_stacks(
+            &mut file,
+            std::iter::once(&execution_trace).chain(&invoked_execution_traces),
+        )
+        .unwrap();
+    }
 
     let output = Output {
         result: format!("{result:?}"),
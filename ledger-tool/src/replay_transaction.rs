@@ -0,0 +1,416 @@
+//! Re-execute a single historical transaction against the state it originally
+//! executed on, optionally with overridden programs or features.
+
+use {
+    log::*,
+    solana_accounts_db::transaction_results::TransactionExecutionResult,
+    solana_bpf_loader_program::syscalls::create_program_runtime_environment_v1,
+    solana_ledger::{blockstore::Blockstore, leader_schedule_utils},
+    solana_program_runtime::{compute_budget::ComputeBudget, timings::ExecuteTimings},
+    solana_runtime::bank::{Bank, LoadAndExecuteTransactionsOutput},
+    solana_sdk::{
+        account::{Account, AccountSharedData, ReadableAccount, WritableAccount},
+        account_utils::StateMut,
+        bpf_loader,
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        clock::{Slot, MAX_PROCESSING_AGE},
+        loader_v4::{self, LoaderV4State},
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::{MessageHash, SanitizedTransaction, VersionedTransaction},
+    },
+    std::sync::Arc,
+};
+
+/// An executable to load in place of an on-chain program
+pub struct ProgramOverride {
+    pub program_id: Pubkey,
+    pub elf: Vec<u8>,
+}
+
+/// Returns the slot that `signature` landed in. Looking it up requires the
+/// ledger to have been written with transaction history enabled.
+pub fn find_transaction_slot(
+    blockstore: &Blockstore,
+    signature: &Signature,
+) -> Result<Slot, String> {
+    match blockstore.get_rooted_transaction(*signature) {
+        Ok(Some(confirmed_transaction)) => Ok(confirmed_transaction.slot),
+        Ok(None) => Err(format!(
+            "Transaction {signature} not found in the transaction status column, specify its \
+            slot with --slot"
+        )),
+        Err(err) => Err(format!(
+            "Failed to look up transaction {signature}: {err:?}"
+        )),
+    }
+}
+
+/// Splits the transactions of `slot` into the batches that executed before
+/// the transaction with `signature`, and that transaction itself
+pub fn split_slot_transactions(
+    blockstore: &Blockstore,
+    slot: Slot,
+    signature: &Signature,
+) -> Result<(Vec<Vec<VersionedTransaction>>, VersionedTransaction), String> {
+    let entries = blockstore
+        .get_slot_entries(slot, 0)
+        .map_err(|err| format!("Failed to load entries of slot {slot}: {err:?}"))?;
+    let mut preceding_batches = vec![];
+    for entry in entries {
+        let mut transactions = entry.transactions;
+        if let Some(index) = transactions
+            .iter()
+            .position(|transaction| transaction.signatures.first() == Some(signature))
+        {
+            let transaction = transactions.swap_remove(index);
+            // Transactions within an entry never conflict with each other,
+            // so the other transactions of this entry can't affect the result
+            return Ok((preceding_batches, transaction));
+        }
+        if !transactions.is_empty() {
+            preceding_batches.push(transactions);
+        }
+    }
+    Err(format!("Transaction {signature} not found in slot {slot}"))
+}
+
+/// Replaces the executable of the program, keeping the loader that owns it and
+/// its loader metadata. Programs that don't exist are created with the
+/// `bpf_loader`.
+fn override_program(bank: &Bank, program_id: &Pubkey, elf: &[u8]) -> Result<(), String> {
+    let Some(program_account) = bank.get_account(program_id) else {
+        let account = AccountSharedData::from(Account {
+            lamports: bank.get_minimum_balance_for_rent_exemption(elf.len()),
+            data: elf.to_vec(),
+            owner: bpf_loader::id(),
+            executable: true,
+            rent_epoch: 0,
+        });
+        bank.store_account(program_id, &account);
+        return Ok(());
+    };
+    // The account holding the executable and the length of the loader
+    // metadata preceding it
+    let (address, mut account, metadata_len) =
+        if bpf_loader_upgradeable::check_id(program_account.owner()) {
+            let Ok(UpgradeableLoaderState::Program {
+                programdata_address,
+            }) = program_account.state()
+            else {
+                return Err(format!("{program_id} is not an upgradeable loader program"));
+            };
+            let programdata_account = bank
+                .get_account(&programdata_address)
+                .ok_or_else(|| format!("Program {program_id} has been closed"))?;
+            (
+                programdata_address,
+                programdata_account,
+                UpgradeableLoaderState::size_of_programdata_metadata(),
+            )
+        } else if loader_v4::check_id(program_account.owner()) {
+            (
+                *program_id,
+                program_account,
+                LoaderV4State::program_data_offset(),
+            )
+        } else if program_account.executable() {
+            (*program_id, program_account, 0)
+        } else {
+            return Err(format!("{program_id} is not a program"));
+        };
+    let Some(metadata) = account.data().get(..metadata_len) else {
+        return Err(format!("Program {program_id} has been closed"));
+    };
+    let mut data = metadata.to_vec();
+    data.extend_from_slice(elf);
+    account.set_lamports(
+        account
+            .lamports()
+            .max(bank.get_minimum_balance_for_rent_exemption(data.len())),
+    );
+    account.set_data(data);
+    bank.store_account(&address, &account);
+    Ok(())
+}
+
+/// Creates a bank for `slot` on top of `parent`, applies the overrides, and
+/// executes `preceding_batches` so that the bank holds the state the replayed
+/// transaction originally saw. The overrides are applied before any of the
+/// preceding transactions execute. Features gating builtin programs or bank
+/// state other than the program runtime environment are not overridden.
+pub fn new_replay_bank(
+    parent: Arc<Bank>,
+    slot: Slot,
+    activate_features: &[Pubkey],
+    deactivate_features: &[Pubkey],
+    program_overrides: &[ProgramOverride],
+    preceding_batches: Vec<Vec<VersionedTransaction>>,
+) -> Result<Bank, String> {
+    let collector_id =
+        leader_schedule_utils::slot_leader_at(slot, &parent).unwrap_or_else(Pubkey::new_unique);
+    let mut bank = Bank::new_from_parent(parent, &collector_id, slot);
+    for feature_id in activate_features {
+        bank.activate_feature(feature_id);
+    }
+    for feature_id in deactivate_features {
+        bank.deactivate_feature(feature_id);
+    }
+    if !activate_features.is_empty() || !deactivate_features.is_empty() {
+        // The program runtime environment was created for the original
+        // feature set, rebuild it so that programs are verified and executed
+        // with the overridden features
+        let program_runtime_environment_v1 = Arc::new(
+            create_program_runtime_environment_v1(
+                &bank.feature_set,
+                &ComputeBudget::default(),
+                false, /* deployment */
+                false, /* debugging_features */
+            )
+            .map_err(|err| format!("Failed to create the program runtime environment: {err}"))?,
+        );
+        let mut loaded_programs_cache = bank.loaded_programs_cache.write().unwrap();
+        loaded_programs_cache.environments.program_runtime_v1 =
+            program_runtime_environment_v1.clone();
+        if let Some(upcoming_environments) = loaded_programs_cache.upcoming_environments.as_mut() {
+            upcoming_environments.program_runtime_v1 = program_runtime_environment_v1;
+        }
+    }
+
+    for ProgramOverride { program_id, elf } in program_overrides {
+        override_program(&bank, program_id, elf)?;
+    }
+    // Make sure the overridden programs are reloaded from their new accounts
+    bank.loaded_programs_cache
+        .write()
+        .unwrap()
+        .remove_programs(program_overrides.iter().map(|program| program.program_id));
+
+    for batch in preceding_batches {
+        for (index, result) in bank
+            .try_process_entry_transactions(batch)
+            .map_err(|err| format!("Failed to sanitize a preceding transaction: {err}"))?
+            .into_iter()
+            .enumerate()
+        {
+            if let Err(err) = result {
+                debug!("preceding transaction {index} failed: {err}");
+            }
+        }
+    }
+
+    Ok(bank)
+}
+
+/// Per top level instruction compute units, recovered from the
+/// "Program <id> consumed <n> of <m> compute units" log messages. Only
+/// programs that report consumption (i.e. not builtins) have an entry.
+pub fn instruction_compute_units(log_messages: &[String]) -> Vec<Option<u64>> {
+    let mut compute_units = vec![];
+    let mut depth = 0usize;
+    for message in log_messages {
+        let Some(rest) = message.strip_prefix("Program ") else {
+            continue;
+        };
+        let mut words = rest.split_whitespace().skip(1);
+        match words.next() {
+            Some("invoke") => {
+                depth = depth.saturating_add(1);
+                if depth == 1 {
+                    compute_units.push(None);
+                }
+            }
+            Some("consumed") if depth == 1 => {
+                if let Some(units) = words.next().and_then(|units| units.parse().ok()) {
+                    if let Some(last) = compute_units.last_mut() {
+                        *last = Some(units);
+                    }
+                }
+            }
+            Some("success") | Some("failed:") => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    compute_units
+}
+
+/// Executes `transaction` on `bank` without committing it, and prints its
+/// result, logs, compute units, inner instructions and account changes
+pub fn execute_and_print(bank: &Bank, transaction: VersionedTransaction) -> Result<(), String> {
+    let signature = transaction.signatures[0];
+    let transaction =
+        SanitizedTransaction::try_create(transaction, MessageHash::Compute, None, bank)
+            .map_err(|err| format!("Failed to sanitize transaction {signature}: {err}"))?;
+    let account_keys: Vec<Pubkey> = transaction
+        .message()
+        .account_keys()
+        .iter()
+        .copied()
+        .collect();
+    let pre_accounts: Vec<AccountSharedData> = account_keys
+        .iter()
+        .map(|pubkey| bank.get_account(pubkey).unwrap_or_default())
+        .collect();
+
+    let transactions = [transaction];
+    let batch = bank.prepare_sanitized_batch(&transactions);
+    let mut timings = ExecuteTimings::default();
+    let LoadAndExecuteTransactionsOutput {
+        loaded_transactions,
+        mut execution_results,
+        ..
+    } = bank.load_and_execute_transactions(
+        &batch,
+        MAX_PROCESSING_AGE,
+        true,
+        true,
+        true,
+        false,
+        &mut timings,
+        None,
+        Some(usize::MAX),
+    );
+
+    println!("Transaction: {signature}");
+    println!("Slot: {}", bank.slot());
+    let details = match execution_results.pop().unwrap() {
+        TransactionExecutionResult::Executed { details, .. } => details,
+        TransactionExecutionResult::NotExecuted(err) => {
+            println!("Result: not executed: {err}");
+            return Ok(());
+        }
+    };
+    match &details.status {
+        Ok(()) => println!("Result: success"),
+        Err(err) => println!("Result: {err}"),
+    }
+    println!("Compute units consumed: {}", details.executed_units);
+
+    let log_messages = details.log_messages.unwrap_or_default();
+    println!("Instructions:");
+    let message = transactions[0].message();
+    let compute_units = instruction_compute_units(&log_messages);
+    let inner_instructions = details.inner_instructions.unwrap_or_default();
+    for (index, (program_id, _)) in message.program_instructions_iter().enumerate() {
+        let units = compute_units
+            .get(index)
+            .copied()
+            .flatten()
+            .map(|units| units.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        println!("  #{index} {program_id}: {units} compute units");
+        for inner_instruction in inner_instructions.get(index).into_iter().flatten() {
+            let inner_program_id = account_keys
+                .get(usize::from(inner_instruction.instruction.program_id_index))
+                .map(|pubkey| pubkey.to_string())
+                .unwrap_or_else(|| "<unknown>".to_string());
+            println!(
+                "  {:indent$}{inner_program_id} ({} accounts, {} bytes of data)",
+                "",
+                inner_instruction.instruction.accounts.len(),
+                inner_instruction.instruction.data.len(),
+                indent = usize::from(inner_instruction.stack_height).saturating_mul(2),
+            );
+        }
+    }
+
+    println!("Per program compute units:");
+    for (program_id, program_timing) in timings.details.per_program_timings.iter() {
+        println!(
+            "  {program_id}: {} compute units over {} invocations",
+            program_timing.accumulated_units, program_timing.count,
+        );
+    }
+
+    println!("Logs:");
+    for log_message in &log_messages {
+        println!("  {log_message}");
+    }
+    if let Some(return_data) = &details.return_data {
+        println!(
+            "Return data from {}: {} bytes",
+            return_data.program_id,
+            return_data.data.len()
+        );
+    }
+
+    println!("Account changes:");
+    let post_accounts = match loaded_transactions.into_iter().next() {
+        Some((Ok(loaded_transaction), _)) => loaded_transaction.accounts,
+        _ => vec![],
+    };
+    for ((pubkey, pre_account), (_, post_account)) in account_keys
+        .iter()
+        .zip(pre_accounts.iter())
+        .zip(post_accounts.iter())
+    {
+        if pre_account == post_account {
+            continue;
+        }
+        println!("  {pubkey}:");
+        if pre_account.lamports() != post_account.lamports() {
+            println!(
+                "    lamports: {} -> {}",
+                pre_account.lamports(),
+                post_account.lamports()
+            );
+        }
+        if pre_account.owner() != post_account.owner() {
+            println!(
+                "    owner: {} -> {}",
+                pre_account.owner(),
+                post_account.owner()
+            );
+        }
+        if pre_account.executable() != post_account.executable() {
+            println!(
+                "    executable: {} -> {}",
+                pre_account.executable(),
+                post_account.executable()
+            );
+        }
+        if pre_account.data() != post_account.data() {
+            let changed_bytes = pre_account
+                .data()
+                .iter()
+                .zip(post_account.data())
+                .filter(|(pre, post)| pre != post)
+                .count();
+            println!(
+                "    data: {} -> {} bytes, {changed_bytes} overlapping bytes changed",
+                pre_account.data().len(),
+                post_account.data().len(),
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruction_compute_units() {
+        let log_messages: Vec<String> = [
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+            "Program ComputeBudget111111111111111111111111111111 success",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [1]",
+            "Program log: Instruction: Transfer",
+            "Program 11111111111111111111111111111111 invoke [2]",
+            "Program 11111111111111111111111111111111 success",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA consumed 4645 of 200000 compute units",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA success",
+            "Program MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr invoke [1]",
+            "Program MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr consumed 39 of 195355 compute units",
+            "Program MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr failed: invalid instruction data",
+        ]
+        .iter()
+        .map(|message| message.to_string())
+        .collect();
+        assert_eq!(
+            instruction_compute_units(&log_messages),
+            vec![None, Some(4645), Some(39)]
+        );
+    }
+}
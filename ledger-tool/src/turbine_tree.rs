@@ -0,0 +1,182 @@
+//! Print the turbine retransmit tree of shreds for a stake distribution, and
+//! how fast and how reliably shreds propagate through it.
+
+use {
+    serde::Deserialize,
+    solana_ledger::shred::{ShredId, ShredType},
+    solana_sdk::{clock::Slot, native_token::lamports_to_sol, pubkey::Pubkey},
+    solana_turbine::retransmit_tree::RetransmitTree,
+    std::{collections::HashMap, fs::File, path::Path, str::FromStr},
+};
+
+pub struct TurbineTreeConfig {
+    pub slot: Slot,
+    pub slot_leader: Pubkey,
+    pub shred_index: u32,
+    pub shred_type: ShredType,
+    /// Number of consecutive shreds, starting at shred_index, over which
+    /// hop counts and failures are averaged
+    pub num_shreds: u32,
+    pub fanouts: Vec<usize>,
+    /// Nodes to print, or all nodes if empty
+    pub nodes: Vec<Pubkey>,
+    pub failure_rates: Vec<f64>,
+    pub num_trials: usize,
+}
+
+// Either a plain list of nodes or the output of
+// `solana validators --output json`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NodesFile {
+    Nodes(Vec<NodeStake>),
+    Validators { validators: Vec<NodeStake> },
+}
+
+#[derive(Deserialize)]
+struct NodeStake {
+    #[serde(alias = "identityPubkey")]
+    pubkey: String,
+    /// Lamports
+    #[serde(alias = "activatedStake", default)]
+    stake: u64,
+}
+
+/// Reads the stake of each node from a JSON file, either a list of
+/// `{"pubkey": ..., "stake": ...}` objects or the output of
+/// `solana validators --output json`. Nodes listed with zero stake are
+/// included in the tree as unstaked nodes.
+pub fn read_nodes_file(path: &Path) -> Result<HashMap<Pubkey, u64>, String> {
+    let file =
+        File::open(path).map_err(|err| format!("Unable to open {}: {err}", path.display()))?;
+    let nodes: NodesFile = serde_json::from_reader(file)
+        .map_err(|err| format!("Unable to parse {}: {err}", path.display()))?;
+    let (NodesFile::Nodes(nodes) | NodesFile::Validators { validators: nodes }) = nodes;
+    nodes
+        .into_iter()
+        .map(|node| {
+            let pubkey = Pubkey::from_str(&node.pubkey)
+                .map_err(|err| format!("Invalid pubkey {}: {err}", node.pubkey))?;
+            Ok((pubkey, node.stake))
+        })
+        .collect()
+}
+
+fn format_parent(tree: &RetransmitTree, parent: Option<usize>) -> String {
+    match parent {
+        None => format!("{} (leader)", tree.slot_leader()),
+        Some(parent) => tree.nodes()[parent].pubkey.to_string(),
+    }
+}
+
+fn print_tree(tree: &RetransmitTree, nodes: &[Pubkey]) {
+    if nodes.is_empty() {
+        println!(
+            "{:>5}  {:>16}  {:<44}  {:<44}  {:>8}",
+            "Layer", "Stake (SOL)", "Node", "Parent", "Children"
+        );
+        for node in tree.nodes() {
+            println!(
+                "{:>5}  {:>16.2}  {:<44}  {:<44}  {:>8}",
+                node.layer,
+                lamports_to_sol(node.stake),
+                node.pubkey.to_string(),
+                format_parent(tree, node.parent),
+                node.children.len(),
+            );
+        }
+        return;
+    }
+    for pubkey in nodes {
+        let Some(node) = tree.get_node(pubkey) else {
+            if pubkey == tree.slot_leader() {
+                println!("{pubkey}: slot leader");
+            } else {
+                println!("{pubkey}: not in the tree");
+            }
+            continue;
+        };
+        println!("{pubkey}:");
+        println!("  Layer: {}", node.layer);
+        println!("  Stake: {} SOL", lamports_to_sol(node.stake));
+        println!("  Parent: {}", format_parent(tree, node.parent));
+        println!("  Children: {}", node.children.len());
+        for &child in &node.children {
+            println!("    {}", tree.nodes()[child].pubkey);
+        }
+    }
+}
+
+pub fn print_turbine_trees(stakes: &HashMap<Pubkey, u64>, config: &TurbineTreeConfig) {
+    let mut rng = rand::thread_rng();
+    // Shred indices past u32::MAX do not exist.
+    let shred_ids: Vec<ShredId> = (0..config.num_shreds)
+        .map_while(|k| config.shred_index.checked_add(k))
+        .map(|index| ShredId::new(config.slot, index, config.shred_type))
+        .collect();
+    for &fanout in &config.fanouts {
+        let trees: Vec<RetransmitTree> = shred_ids
+            .iter()
+            .map(|shred| RetransmitTree::new(stakes, &config.slot_leader, shred, fanout))
+            .collect();
+        println!(
+            "Retransmit tree of slot {} shred {} ({:?}) from leader {}, fanout {fanout}:",
+            config.slot, config.shred_index, config.shred_type, config.slot_leader,
+        );
+        print_tree(&trees[0], &config.nodes);
+        println!();
+
+        let num_trees = trees.len() as f64;
+        let stats: Vec<_> = trees.iter().map(RetransmitTree::stats).collect();
+        println!("Over {} shreds with fanout {fanout}:", trees.len());
+        println!(
+            "  Layers: {}",
+            stats
+                .iter()
+                .map(|stats| stats.num_layers)
+                .max()
+                .unwrap_or(0)
+        );
+        println!(
+            "  Mean hops: {:.3}",
+            stats.iter().map(|stats| stats.mean_hops).sum::<f64>() / num_trees
+        );
+        println!(
+            "  Stake weighted mean hops: {:.3}",
+            stats
+                .iter()
+                .map(|stats| stats.stake_weighted_mean_hops)
+                .sum::<f64>()
+                / num_trees
+        );
+        for pubkey in &config.nodes {
+            let hops: Vec<usize> = trees
+                .iter()
+                .filter_map(|tree| Some(tree.get_node(pubkey)?.layer + 1))
+                .collect();
+            if !hops.is_empty() {
+                println!(
+                    "  Mean hops to {pubkey}: {:.3}",
+                    hops.iter().sum::<usize>() as f64 / hops.len() as f64
+                );
+            }
+        }
+        for &failure_rate in &config.failure_rates {
+            let mut samples = Vec::with_capacity(trees.len() * config.num_trials);
+            for tree in &trees {
+                for _ in 0..config.num_trials {
+                    samples.push(tree.sample_stake_reached(&mut rng, failure_rate));
+                }
+            }
+            let mean = samples.iter().sum::<f64>() / samples.len().max(1) as f64;
+            let min = samples.iter().copied().reduce(f64::min).unwrap_or(0.0);
+            println!(
+                "  Stake reached with {:.1}% of nodes failing: {:.2}% on average, {:.2}% at worst",
+                failure_rate * 100.0,
+                mean * 100.0,
+                min * 100.0,
+            );
+        }
+        println!();
+    }
+}
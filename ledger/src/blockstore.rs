@@ -89,7 +89,7 @@ pub use {
         blockstore_meta::{OptimisticSlotMetaVersioned, SlotMeta},
         blockstore_metrics::BlockstoreInsertionMetrics,
     },
-    blockstore_purge::PurgeType,
+    blockstore_purge::{PurgeType, TransactionHistoryFilter},
     rocksdb::properties as RocksProperties,
 };
 
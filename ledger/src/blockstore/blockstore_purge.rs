@@ -1,7 +1,7 @@
 use {
     super::*,
     crate::blockstore_db::ColumnIndexDeprecation,
-    solana_sdk::message::AccountKeys,
+    solana_sdk::{compute_budget, message::AccountKeys, vote},
     std::{cmp::max, time::Instant},
 };
 
@@ -23,6 +23,42 @@ pub enum PurgeType {
     CompactionFilter,
 }
 
+/// Selects the transactions whose history (`TransactionStatus`,
+/// `AddressSignatures` and `TransactionMemos` entries) is dropped by
+/// `Blockstore::purge_transaction_history`, while their blocks are kept.
+#[derive(Clone, Debug, Default)]
+pub struct TransactionHistoryFilter {
+    /// Match vote transactions
+    pub votes: bool,
+    /// Match transactions that only invoke these programs
+    pub program_ids: HashSet<Pubkey>,
+}
+
+impl TransactionHistoryFilter {
+    pub fn is_empty(&self) -> bool {
+        !self.votes && self.program_ids.is_empty()
+    }
+
+    /// Returns true if every instruction of `transaction`, ignoring compute
+    /// budget instructions, invokes a matched program. Transactions that also
+    /// invoke any other program are kept.
+    pub fn matches(&self, transaction: &VersionedTransaction) -> bool {
+        let account_keys = transaction.message.static_account_keys();
+        let mut program_ids = transaction
+            .message
+            .instructions()
+            .iter()
+            .filter_map(|instruction| account_keys.get(usize::from(instruction.program_id_index)))
+            .filter(|program_id| !compute_budget::check_id(program_id))
+            .peekable();
+        program_ids.peek().is_some()
+            && program_ids.all(|program_id| {
+                (self.votes && vote::program::check_id(program_id))
+                    || self.program_ids.contains(program_id)
+            })
+    }
+}
+
 impl Blockstore {
     /// Performs cleanup based on the specified deletion range.  After this
     /// function call, entries within \[`from_slot`, `to_slot`\] will become
@@ -85,6 +121,52 @@ impl Blockstore {
         self.purge_slots(from_slot, to_slot, PurgeType::Exact);
     }
 
+    /// Deletes the `TransactionStatus`, `AddressSignatures` and
+    /// `TransactionMemos` entries of the transactions in \[`from_slot`,
+    /// `to_slot`\] that match `filter`. Unlike `purge_slots`, the blocks
+    /// themselves are kept.
+    ///
+    /// Returns the number of transactions whose history was deleted.
+    pub fn purge_transaction_history(
+        &self,
+        from_slot: Slot,
+        to_slot: Slot,
+        filter: &TransactionHistoryFilter,
+    ) -> Result<usize> {
+        if filter.is_empty() || self.special_columns_empty()? {
+            return Ok(0);
+        }
+
+        let index0 = self.transaction_status_index_cf.get(0)?.unwrap_or_default();
+        let index1 = self.transaction_status_index_cf.get(1)?.unwrap_or_default();
+        let highest_primary_index_slot = self.get_highest_primary_index_slot();
+
+        let mut num_purged = 0;
+        for slot in from_slot..=to_slot {
+            let primary_indexes =
+                deprecated_primary_indexes(highest_primary_index_slot, &index0, &index1, slot);
+            let mut write_batch = self.db.batch()?;
+            let transactions = self
+                .get_any_valid_slot_entries(slot, 0)
+                .into_iter()
+                .flat_map(|entry| entry.transactions);
+            for (i, transaction) in transactions.enumerate() {
+                if filter.matches(&transaction) {
+                    self.delete_transaction_history(
+                        &mut write_batch,
+                        slot,
+                        i,
+                        &transaction,
+                        &primary_indexes,
+                    )?;
+                    num_purged += 1;
+                }
+            }
+            self.db.write(write_batch)?;
+        }
+        Ok(num_purged)
+    }
+
     /// Ensures that the SlotMeta::next_slots vector for all slots contain no references in the
     /// \[from_slot,to_slot\] range
     ///
@@ -379,74 +461,17 @@ impl Blockstore {
         let mut index0 = self.transaction_status_index_cf.get(0)?.unwrap_or_default();
         let mut index1 = self.transaction_status_index_cf.get(1)?.unwrap_or_default();
         let highest_primary_index_slot = self.get_highest_primary_index_slot();
-        let slot_indexes = |slot: Slot| -> Vec<u64> {
-            let mut indexes = vec![];
-            if highest_primary_index_slot.is_none() {
-                return indexes;
-            }
-            if slot <= index0.max_slot && (index0.frozen || slot >= index1.max_slot) {
-                indexes.push(0);
-            }
-            if slot <= index1.max_slot && (index1.frozen || slot >= index0.max_slot) {
-                indexes.push(1);
-            }
-            indexes
-        };
 
         for slot in from_slot..=to_slot {
-            let primary_indexes = slot_indexes(slot);
+            let primary_indexes =
+                deprecated_primary_indexes(highest_primary_index_slot, &index0, &index1, slot);
 
             let slot_entries = self.get_any_valid_slot_entries(slot, 0);
             let transactions = slot_entries
                 .into_iter()
                 .flat_map(|entry| entry.transactions);
             for (i, transaction) in transactions.enumerate() {
-                if let Some(&signature) = transaction.signatures.first() {
-                    batch.delete::<cf::TransactionStatus>((signature, slot))?;
-                    batch.delete::<cf::TransactionMemos>((signature, slot))?;
-                    if !primary_indexes.is_empty() {
-                        batch.delete_raw::<cf::TransactionMemos>(
-                            &cf::TransactionMemos::deprecated_key(signature),
-                        )?;
-                    }
-                    for primary_index in &primary_indexes {
-                        batch.delete_raw::<cf::TransactionStatus>(
-                            &cf::TransactionStatus::deprecated_key((
-                                *primary_index,
-                                signature,
-                                slot,
-                            )),
-                        )?;
-                    }
-
-                    let meta = self.read_transaction_status((signature, slot))?;
-                    let loaded_addresses = meta.map(|meta| meta.loaded_addresses);
-                    let account_keys = AccountKeys::new(
-                        transaction.message.static_account_keys(),
-                        loaded_addresses.as_ref(),
-                    );
-
-                    let transaction_index =
-                        u32::try_from(i).map_err(|_| BlockstoreError::TransactionIndexOverflow)?;
-                    for pubkey in account_keys.iter() {
-                        batch.delete::<cf::AddressSignatures>((
-                            *pubkey,
-                            slot,
-                            transaction_index,
-                            signature,
-                        ))?;
-                        for primary_index in &primary_indexes {
-                            batch.delete_raw::<cf::AddressSignatures>(
-                                &cf::AddressSignatures::deprecated_key((
-                                    *primary_index,
-                                    *pubkey,
-                                    slot,
-                                    signature,
-                                )),
-                            )?;
-                        }
-                    }
-                }
+                self.delete_transaction_history(batch, slot, i, &transaction, &primary_indexes)?;
             }
         }
         let mut update_highest_primary_index_slot = false;
@@ -465,6 +490,81 @@ impl Blockstore {
         }
         Ok(())
     }
+
+    /// Adds the deletion of the `TransactionStatus`, `AddressSignatures` and
+    /// `TransactionMemos` entries of `transaction`, the `transaction_index`th
+    /// transaction of `slot`, to `batch`
+    fn delete_transaction_history(
+        &self,
+        batch: &mut WriteBatch,
+        slot: Slot,
+        transaction_index: usize,
+        transaction: &VersionedTransaction,
+        primary_indexes: &[u64],
+    ) -> Result<()> {
+        let Some(&signature) = transaction.signatures.first() else {
+            return Ok(());
+        };
+        batch.delete::<cf::TransactionStatus>((signature, slot))?;
+        batch.delete::<cf::TransactionMemos>((signature, slot))?;
+        if !primary_indexes.is_empty() {
+            batch.delete_raw::<cf::TransactionMemos>(&cf::TransactionMemos::deprecated_key(
+                signature,
+            ))?;
+        }
+        for primary_index in primary_indexes {
+            batch.delete_raw::<cf::TransactionStatus>(&cf::TransactionStatus::deprecated_key((
+                *primary_index,
+                signature,
+                slot,
+            )))?;
+        }
+
+        let meta = self.read_transaction_status((signature, slot))?;
+        let loaded_addresses = meta.map(|meta| meta.loaded_addresses);
+        let account_keys = AccountKeys::new(
+            transaction.message.static_account_keys(),
+            loaded_addresses.as_ref(),
+        );
+
+        let transaction_index = u32::try_from(transaction_index)
+            .map_err(|_| BlockstoreError::TransactionIndexOverflow)?;
+        for pubkey in account_keys.iter() {
+            batch.delete::<cf::AddressSignatures>((*pubkey, slot, transaction_index, signature))?;
+            for primary_index in primary_indexes {
+                batch.delete_raw::<cf::AddressSignatures>(
+                    &cf::AddressSignatures::deprecated_key((
+                        *primary_index,
+                        *pubkey,
+                        slot,
+                        signature,
+                    )),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the deprecated primary indexes of the special columns that may
+/// hold entries for `slot`
+fn deprecated_primary_indexes(
+    highest_primary_index_slot: Option<Slot>,
+    index0: &TransactionStatusIndexMeta,
+    index1: &TransactionStatusIndexMeta,
+    slot: Slot,
+) -> Vec<u64> {
+    let mut indexes = vec![];
+    if highest_primary_index_slot.is_none() {
+        return indexes;
+    }
+    if slot <= index0.max_slot && (index0.frozen || slot >= index1.max_slot) {
+        indexes.push(0);
+    }
+    if slot <= index1.max_slot && (index1.frozen || slot >= index0.max_slot) {
+        indexes.push(1);
+    }
+    indexes
 }
 
 #[cfg(test)]
@@ -477,7 +577,9 @@ pub mod tests {
         bincode::serialize,
         solana_entry::entry::next_entry_mut,
         solana_sdk::{
+            compute_budget::ComputeBudgetInstruction,
             hash::{hash, Hash},
+            instruction::Instruction,
             message::Message,
             transaction::Transaction,
         },
@@ -924,6 +1026,121 @@ pub mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn test_purge_transaction_history() {
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Blockstore::open(ledger_path.path()).unwrap();
+
+        let slot = 1;
+        let payer = Pubkey::new_unique();
+        let other_program_id = Pubkey::new_unique();
+        let new_transaction = |program_id: Pubkey| {
+            let instructions = [
+                ComputeBudgetInstruction::set_compute_unit_limit(1_000),
+                Instruction::new_with_bytes(program_id, &[], vec![]),
+            ];
+            let mut transaction =
+                Transaction::new_unsigned(Message::new(&instructions, Some(&payer)));
+            transaction.signatures = vec![Signature::new_unique()];
+            transaction
+        };
+        let vote_transaction = new_transaction(vote::program::id());
+        let other_transaction = new_transaction(other_program_id);
+        let entries = vec![next_entry_mut(
+            &mut Hash::default(),
+            0,
+            vec![vote_transaction.clone(), other_transaction.clone()],
+        )];
+        let shreds = entries_to_test_shreds(
+            &entries,
+            slot,
+            slot - 1, // parent_slot
+            true,     // is_full_slot
+            0,        // version
+            true,     // merkle_variant
+        );
+        blockstore.insert_shreds(shreds, None, false).unwrap();
+
+        let signatures = [
+            vote_transaction.signatures[0],
+            other_transaction.signatures[0],
+        ];
+        for (transaction_index, signature) in signatures.iter().enumerate() {
+            blockstore
+                .write_transaction_status(
+                    slot,
+                    *signature,
+                    vec![&payer],
+                    vec![],
+                    TransactionStatusMeta::default(),
+                    transaction_index,
+                )
+                .unwrap();
+            blockstore
+                .write_transaction_memos(signature, slot, "memo".to_string())
+                .unwrap();
+        }
+        let history_exists = |transaction_index: usize| {
+            let signature = signatures[transaction_index];
+            let status = blockstore
+                .read_transaction_status((signature, slot))
+                .unwrap();
+            let memos = blockstore.read_transaction_memos(signature, slot).unwrap();
+            let address_signature = blockstore
+                .address_signatures_cf
+                .get((payer, slot, transaction_index as u32, signature))
+                .unwrap();
+            assert_eq!(status.is_some(), memos.is_some());
+            assert_eq!(status.is_some(), address_signature.is_some());
+            status.is_some()
+        };
+        assert!(history_exists(0));
+        assert!(history_exists(1));
+
+        // An empty filter matches nothing
+        let filter = TransactionHistoryFilter::default();
+        assert_eq!(
+            blockstore
+                .purge_transaction_history(slot, slot, &filter)
+                .unwrap(),
+            0
+        );
+
+        let filter = TransactionHistoryFilter {
+            votes: true,
+            ..TransactionHistoryFilter::default()
+        };
+        assert!(filter.matches(&vote_transaction.clone().into()));
+        assert!(!filter.matches(&other_transaction.clone().into()));
+        assert_eq!(
+            blockstore
+                .purge_transaction_history(slot, slot, &filter)
+                .unwrap(),
+            1
+        );
+        assert!(!history_exists(0));
+        assert!(history_exists(1));
+
+        let filter = TransactionHistoryFilter {
+            votes: false,
+            program_ids: HashSet::from([other_program_id]),
+        };
+        assert_eq!(
+            blockstore
+                .purge_transaction_history(slot, slot, &filter)
+                .unwrap(),
+            1
+        );
+        assert!(!history_exists(0));
+        assert!(!history_exists(1));
+
+        // The block itself is kept
+        assert_eq!(
+            blockstore.get_slot_entries(slot, 0).unwrap().len(),
+            entries.len()
+        );
+    }
+
     #[test]
     fn test_purge_special_columns_compaction_filter() {
         let ledger_path = get_tmp_ledger_path_auto_delete!();
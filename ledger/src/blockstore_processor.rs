@@ -700,6 +700,8 @@ pub struct ProcessOptions {
     /// This is useful for debugging.
     pub run_final_accounts_hash_calc: bool,
     pub use_snapshot_archives_at_startup: UseSnapshotArchivesAtStartup,
+    /// Invoked with every bank once it has been replayed and frozen
+    pub slot_callback: Option<ProcessCallback>,
 }
 
 pub fn test_process_blockstore(
@@ -1406,6 +1408,9 @@ fn process_bank_0(
         blockstore.insert_bank_hash(bank0.slot(), bank0.hash(), false);
     }
     cache_block_meta(bank0, cache_block_meta_sender);
+    if let Some(slot_callback) = &opts.slot_callback {
+        slot_callback(bank0);
+    }
 }
 
 // Given a bank, add its children to the pending slots queue if those children slots are
@@ -1579,6 +1584,9 @@ fn load_frozen_forks(
             // Block must be frozen by this point; otherwise,
             // process_single_slot() would have errored above.
             assert!(bank.is_frozen());
+            if let Some(slot_callback) = &opts.slot_callback {
+                slot_callback(&bank);
+            }
             all_banks.insert(bank.slot(), bank.clone_with_scheduler());
             m.stop();
             process_single_slot_us += m.as_us();
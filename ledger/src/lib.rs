@@ -31,6 +31,7 @@ pub mod slot_stats;
 mod staking_utils;
 pub mod token_balances;
 mod transaction_address_lookup_table_scanner;
+pub mod transaction_history_purge_service;
 pub mod use_snapshot_archives_at_startup;
 
 #[macro_use]
@@ -0,0 +1,133 @@
+//! The `transaction_history_purge_service` drops the transaction history of
+//! selected transactions, such as votes, once their slot is older than a
+//! retention window. Unlike the `blockstore_cleanup_service`, the blocks
+//! themselves are kept.
+
+use {
+    crate::blockstore::{Blockstore, TransactionHistoryFilter},
+    solana_measure::measure::Measure,
+    solana_sdk::clock::Slot,
+    std::{
+        fs,
+        path::Path,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread::{self, Builder, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+// Check for new roots at this interval
+const PURGE_INTERVAL: Duration = Duration::from_secs(10);
+// Check for exit at this interval while waiting to purge
+const EXIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+// Purge at most this many slots at a time so that exit isn't held up
+const MAX_SLOTS_PER_PURGE: Slot = 512;
+// Records the highest slot purged so far, to resume from after a restart
+const PURGED_SLOT_FILE: &str = "transaction_history_purged_slot";
+
+#[derive(Clone, Debug, Default)]
+pub struct TransactionHistoryPurgeConfig {
+    pub filter: TransactionHistoryFilter,
+    /// The history of matched transactions is kept for this many slots
+    /// behind the root
+    pub retention_slots: Slot,
+}
+
+pub struct TransactionHistoryPurgeService {
+    t_purge: JoinHandle<()>,
+}
+
+impl TransactionHistoryPurgeService {
+    pub fn new(
+        blockstore: Arc<Blockstore>,
+        config: TransactionHistoryPurgeConfig,
+        exit: Arc<AtomicBool>,
+    ) -> Self {
+        info!(
+            "TransactionHistoryPurgeService active. votes={} program ids={:?} retention slots={}",
+            config.filter.votes, config.filter.program_ids, config.retention_slots,
+        );
+        let purged_slot_path = blockstore.ledger_path().join(PURGED_SLOT_FILE);
+        let t_purge = Builder::new()
+            .name("solTxHistPurge".to_string())
+            .spawn(move || {
+                let mut purged_slot = read_purged_slot(&purged_slot_path)
+                    .unwrap_or_else(|| blockstore.lowest_slot().saturating_sub(1));
+                while !exit.load(Ordering::Relaxed) {
+                    let purge_slot = blockstore.max_root().saturating_sub(config.retention_slots);
+                    if purge_slot <= purged_slot {
+                        sleep_unless_exit(&exit, PURGE_INTERVAL);
+                        continue;
+                    }
+                    let from_slot = purged_slot + 1;
+                    let to_slot = purge_slot.min(purged_slot + MAX_SLOTS_PER_PURGE);
+                    if Self::purge(&blockstore, &config.filter, from_slot, to_slot) {
+                        purged_slot = to_slot;
+                        write_purged_slot(&purged_slot_path, purged_slot);
+                    } else {
+                        sleep_unless_exit(&exit, PURGE_INTERVAL);
+                    }
+                }
+            })
+            .unwrap();
+        Self { t_purge }
+    }
+
+    fn purge(
+        blockstore: &Blockstore,
+        filter: &TransactionHistoryFilter,
+        from_slot: Slot,
+        to_slot: Slot,
+    ) -> bool {
+        let mut purge_time = Measure::start("purge_transaction_history");
+        let result = blockstore.purge_transaction_history(from_slot, to_slot, filter);
+        purge_time.stop();
+        match result {
+            Ok(num_purged) => {
+                datapoint_info!(
+                    "transaction_history_purge",
+                    ("from_slot", from_slot, i64),
+                    ("to_slot", to_slot, i64),
+                    ("num_purged", num_purged, i64),
+                    ("purge_us", purge_time.as_us(), i64),
+                );
+                true
+            }
+            Err(err) => {
+                error!(
+                    "Failed to purge transaction history in slots {from_slot} to {to_slot}: {err:?}"
+                );
+                false
+            }
+        }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.t_purge.join()
+    }
+}
+
+// Sleeps for `duration`, returning early once `exit` is set
+fn sleep_unless_exit(exit: &AtomicBool, duration: Duration) {
+    let deadline = Instant::now() + duration;
+    while !exit.load(Ordering::Relaxed) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        thread::sleep(remaining.min(EXIT_CHECK_INTERVAL));
+    }
+}
+
+fn read_purged_slot(path: &Path) -> Option<Slot> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn write_purged_slot(path: &Path, slot: Slot) {
+    if let Err(err) = fs::write(path, slot.to_string()) {
+        warn!("Unable to write {}: {err}", path.display());
+    }
+}
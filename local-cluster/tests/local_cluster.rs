@@ -1618,6 +1618,104 @@ fn test_no_voting() {
     }
 }
 
+#[test]
+#[serial]
+fn test_turbine_over_quic_shred_latency() {
+    solana_logger::setup_with_default(RUST_LOG_FILTER);
+    // The turbine_over_quic feature is active from genesis and so effective
+    // from epoch 1; shreds of epoch 0 are sent over UDP.
+    let slots_per_epoch = MINIMUM_SLOTS_PER_EPOCH;
+    let num_nodes = 4;
+    let mut config = ClusterConfig {
+        cluster_lamports: DEFAULT_CLUSTER_LAMPORTS,
+        node_stakes: vec![DEFAULT_NODE_STAKE; num_nodes],
+        validator_configs: make_identical_validator_configs(
+            &ValidatorConfig::default_for_test(),
+            num_nodes,
+        ),
+        slots_per_epoch,
+        stakers_slot_offset: slots_per_epoch,
+        skip_warmup_slots: true,
+        ..ClusterConfig::default()
+    };
+    let mut cluster = LocalCluster::new(&mut config, SocketAddrSpace::Unspecified);
+    let client = cluster
+        .get_validator_client(cluster.entry_point_info.pubkey())
+        .unwrap();
+    loop {
+        let last_slot = client
+            .get_slot_with_commitment(CommitmentConfig::processed())
+            .expect("Couldn't get slot");
+        if last_slot > 3 * slots_per_epoch {
+            break;
+        }
+        sleep(Duration::from_secs(1));
+    }
+
+    cluster.close_preserve_ledgers();
+    let blockstores: Vec<Blockstore> = cluster
+        .validators
+        .values()
+        .map(|validator| Blockstore::open(&validator.info.ledger_path).unwrap())
+        .collect();
+    // Spread of the time the first shred of each slot arrived across nodes,
+    // in milliseconds, for slots sent over UDP and over QUIC respectively.
+    let mut udp_latencies = Vec::new();
+    let mut quic_latencies = Vec::new();
+    // Number of slots which some node has shreds of, and number of those
+    // which every node received in full, sent over QUIC.
+    let mut num_quic_slots = 0;
+    let mut num_quic_slots_delivered = 0;
+    for slot in 1..3 * slots_per_epoch {
+        let metas: Vec<_> = blockstores
+            .iter()
+            .filter_map(|blockstore| blockstore.meta(slot).unwrap())
+            .filter(|meta| meta.first_shred_timestamp != 0)
+            .collect();
+        if metas.is_empty() {
+            // Skipped slot.
+            continue;
+        }
+        let delivered = metas.len() == num_nodes && metas.iter().all(|meta| meta.is_full());
+        if slot >= slots_per_epoch {
+            num_quic_slots += 1;
+            num_quic_slots_delivered += usize::from(delivered);
+        }
+        if !delivered {
+            continue;
+        }
+        let timestamps = metas.iter().map(|meta| meta.first_shred_timestamp);
+        let latency = timestamps.clone().max().unwrap() - timestamps.min().unwrap();
+        if slot < slots_per_epoch {
+            udp_latencies.push(latency);
+        } else {
+            quic_latencies.push(latency);
+        }
+    }
+    let mean = |latencies: &[u64]| latencies.iter().sum::<u64>() as f64 / latencies.len() as f64;
+    let udp_latency = mean(&udp_latencies);
+    let quic_latency = mean(&quic_latencies);
+    let quic_delivery_rate = num_quic_slots_delivered as f64 / num_quic_slots as f64;
+    info!(
+        "shred arrival spread: udp: {udp_latency:.1}ms over {} slots, quic: {quic_latency:.1}ms \
+         over {} slots, quic delivery rate: {quic_delivery_rate:.2}",
+        udp_latencies.len(),
+        quic_latencies.len(),
+    );
+    assert!(!udp_latencies.is_empty());
+    assert!(!quic_latencies.is_empty());
+    // Every node receives nearly all slots sent over QUIC, and not much later
+    // than over UDP.
+    assert!(
+        quic_delivery_rate >= 0.9,
+        "quic delivery rate: {quic_delivery_rate:.2}"
+    );
+    assert!(
+        quic_latency <= 2.0 * udp_latency + 100.0,
+        "shred arrival spread: udp: {udp_latency:.1}ms, quic: {quic_latency:.1}ms"
+    );
+}
+
 #[test]
 #[serial]
 fn test_optimistic_confirmation_violation_detection() {
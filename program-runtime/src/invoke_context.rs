@@ -20,6 +20,7 @@ use {
     solana_sdk::{
         account::AccountSharedData,
         bpf_loader_deprecated,
+        execution_trace::ProgramExecutionTrace,
         feature_set::{check_slice_translation_size, native_programs_consume_cu, FeatureSet},
         hash::Hash,
         instruction::{AccountMeta, InstructionError},
@@ -35,6 +36,7 @@ use {
         alloc::Layout,
         cell::RefCell,
         fmt::{self, Debug},
+        mem,
         rc::Rc,
         sync::{atomic::Ordering, Arc},
     },
@@ -85,13 +87,17 @@ macro_rules! declare_process_instruction {
 
 impl<'a> ContextObject for InvokeContext<'a> {
     fn trace(&mut self, state: [u64; 12]) {
-        self.syscall_context
-            .last_mut()
-            .unwrap()
-            .as_mut()
-            .unwrap()
-            .trace_log
-            .push(state);
+        if self.execution_traces.is_none() {
+            return;
+        }
+        let compute_meter = *self.compute_meter.borrow();
+        let syscall_context = self.syscall_context.last_mut().unwrap().as_mut().unwrap();
+        syscall_context.trace_log.push(state);
+        syscall_context.trace_meter_log.push(
+            compute_meter
+                .saturating_add(syscall_context.vm_compute_units)
+                .saturating_add(syscall_context.invoked_compute_units),
+        );
     }
 
     fn consume(&mut self, amount: u64) {
@@ -99,6 +105,12 @@ impl<'a> ContextObject for InvokeContext<'a> {
         // ignore overflow, Ebpf will bail if exceeded
         let mut compute_meter = self.compute_meter.borrow_mut();
         *compute_meter = compute_meter.saturating_sub(amount);
+        if self.execution_traces.is_some() {
+            if let Some(Some(syscall_context)) = self.syscall_context.last_mut() {
+                syscall_context.vm_compute_units =
+                    syscall_context.vm_compute_units.saturating_add(amount);
+            }
+        }
     }
 
     fn get_remaining(&self) -> u64 {
@@ -146,6 +158,16 @@ pub struct SyscallContext {
     pub allocator: BpfAllocator,
     pub accounts_metadata: Vec<SerializedAccountMetadata>,
     pub trace_log: Vec<[u64; 12]>,
+    /// The compute units that the VM consumed for the instructions of the program, if execution
+    /// traces are recorded
+    pub vm_compute_units: u64,
+    /// The compute units consumed by the traced programs that the program invoked, which are
+    /// profiled in their own execution traces
+    pub invoked_compute_units: u64,
+    /// For each entry of `trace_log`, the remaining compute units plus `vm_compute_units` and
+    /// `invoked_compute_units`, which only decreases by the compute units that syscalls consume
+    /// themselves
+    pub trace_meter_log: Vec<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -174,6 +196,9 @@ pub struct InvokeContext<'a> {
     pub lamports_per_signature: u64,
     pub syscall_context: Vec<Option<SyscallContext>>,
     traces: Vec<Vec<[u64; 12]>>,
+    /// The execution traces of the SBF programs invoked since they were last taken, if they are
+    /// recorded
+    execution_traces: Option<Vec<ProgramExecutionTrace>>,
 }
 
 impl<'a> InvokeContext<'a> {
@@ -208,6 +233,7 @@ impl<'a> InvokeContext<'a> {
             lamports_per_signature,
             syscall_context: Vec::new(),
             traces: Vec::new(),
+            execution_traces: None,
         }
     }
 
@@ -640,6 +666,64 @@ impl<'a> InvokeContext<'a> {
     pub fn get_traces(&self) -> &Vec<Vec<[u64; 12]>> {
         &self.traces
     }
+
+    /// Records the register state of the SBF programs invoked from now on, which requires them
+    /// to be compiled with instruction tracing
+    pub fn enable_execution_trace(&mut self) {
+        self.execution_traces.get_or_insert_with(Vec::new);
+    }
+
+    pub fn is_execution_trace_enabled(&self) -> bool {
+        self.execution_traces.is_some()
+    }
+
+    /// Starts the execution trace of an SBF program invocation, so that traces are kept in the
+    /// order in which the invocations start.  Returns the index to finish it with, if execution
+    /// traces are recorded.
+    pub fn start_execution_trace(&mut self, program_id: Pubkey) -> Option<usize> {
+        let stack_height = self.get_stack_height() as u32;
+        let execution_traces = self.execution_traces.as_mut()?;
+        execution_traces.push(ProgramExecutionTrace {
+            program_id,
+            stack_height,
+            ..ProgramExecutionTrace::default()
+        });
+        Some(execution_traces.len().saturating_sub(1))
+    }
+
+    /// Finishes the execution trace started by `start_execution_trace`.  The compute units that
+    /// the invocation consumed are left out of the syscall of the calling program which invoked
+    /// it, so that they are not profiled twice.
+    pub fn finish_execution_trace(
+        &mut self,
+        index: usize,
+        execution_trace: ProgramExecutionTrace,
+        compute_units_consumed: u64,
+    ) {
+        if let Some(started_execution_trace) = self
+            .execution_traces
+            .as_mut()
+            .and_then(|execution_traces| execution_traces.get_mut(index))
+        {
+            *started_execution_trace = execution_trace;
+            let caller_index = self.syscall_context.len().checked_sub(2);
+            if let Some(Some(caller_syscall_context)) =
+                caller_index.and_then(|index| self.syscall_context.get_mut(index))
+            {
+                caller_syscall_context.invoked_compute_units = caller_syscall_context
+                    .invoked_compute_units
+                    .saturating_add(compute_units_consumed);
+            }
+        }
+    }
+
+    /// Takes the execution traces recorded since they were last taken
+    pub fn take_execution_traces(&mut self) -> Vec<ProgramExecutionTrace> {
+        self.execution_traces
+            .as_mut()
+            .map(mem::take)
+            .unwrap_or_default()
+    }
 }
 
 #[macro_export]
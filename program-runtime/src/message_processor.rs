@@ -11,6 +11,7 @@ use {
     solana_measure::measure::Measure,
     solana_sdk::{
         account::WritableAccount,
+        execution_trace::ExecutionTraceList,
         feature_set::FeatureSet,
         hash::Hash,
         message::SanitizedMessage,
@@ -48,6 +49,9 @@ impl MessageProcessor {
     /// For each instruction it calls the program entrypoint method and verifies that the result of
     /// the call does not violate the bank's accounting rules.
     /// The accounts are committed back to the bank only if every instruction succeeds.
+    /// The compute units consumed by each processed instruction are appended to
+    /// `instruction_consumed_units`, if it is provided, and the execution traces of the SBF
+    /// programs that each processed instruction invokes to `execution_traces`.
     #[allow(clippy::too_many_arguments)]
     pub fn process_message(
         message: &SanitizedMessage,
@@ -65,6 +69,8 @@ impl MessageProcessor {
         lamports_per_signature: u64,
         current_accounts_data_len: u64,
         accumulated_consumed_units: &mut u64,
+        mut instruction_consumed_units: Option<&mut Vec<u64>>,
+        mut execution_traces: Option<&mut ExecutionTraceList>,
     ) -> Result<ProcessedMessageInfo, TransactionError> {
         let mut invoke_context = InvokeContext::new(
             transaction_context,
@@ -79,6 +85,9 @@ impl MessageProcessor {
             lamports_per_signature,
             current_accounts_data_len,
         );
+        if execution_traces.is_some() {
+            invoke_context.enable_execution_trace();
+        }
 
         debug_assert_eq!(program_indices.len(), message.instructions().len());
         for (instruction_index, ((program_id, instruction), program_indices)) in message
@@ -128,6 +137,7 @@ impl MessageProcessor {
                 });
             }
 
+            let mut compute_units_consumed = 0;
             let result = if is_precompile {
                 invoke_context
                     .transaction_context
@@ -145,7 +155,6 @@ impl MessageProcessor {
                     })
             } else {
                 let mut time = Measure::start("execute_instruction");
-                let mut compute_units_consumed = 0;
                 let result = invoke_context.process_instruction(
                     &instruction.data,
                     &instruction_accounts,
@@ -172,6 +181,12 @@ impl MessageProcessor {
                 );
                 result
             };
+            if let Some(instruction_consumed_units) = instruction_consumed_units.as_mut() {
+                instruction_consumed_units.push(compute_units_consumed);
+            }
+            if let Some(execution_traces) = execution_traces.as_mut() {
+                execution_traces.push(invoke_context.take_execution_traces());
+            }
 
             result
                 .map_err(|err| TransactionError::InstructionError(instruction_index as u8, err))?;
@@ -303,6 +318,7 @@ mod tests {
         let sysvar_cache = SysvarCache::default();
         let mut programs_modified_by_tx = LoadedProgramsForTxBatch::default();
         let mut programs_updated_only_for_global_cache = LoadedProgramsForTxBatch::default();
+        let mut instruction_consumed_units = Vec::new();
         let result = MessageProcessor::process_message(
             &message,
             &program_indices,
@@ -319,8 +335,11 @@ mod tests {
             0,
             0,
             &mut 0,
+            Some(&mut instruction_consumed_units),
+            None,
         );
         assert!(result.is_ok());
+        assert_eq!(instruction_consumed_units, vec![1]);
         assert_eq!(
             transaction_context
                 .get_account_at_index(0)
@@ -371,6 +390,8 @@ mod tests {
             0,
             0,
             &mut 0,
+            None,
+            None,
         );
         assert_eq!(
             result,
@@ -413,6 +434,8 @@ mod tests {
             0,
             0,
             &mut 0,
+            None,
+            None,
         );
         assert_eq!(
             result,
@@ -545,6 +568,8 @@ mod tests {
             0,
             0,
             &mut 0,
+            None,
+            None,
         );
         assert_eq!(
             result,
@@ -581,6 +606,8 @@ mod tests {
             0,
             0,
             &mut 0,
+            None,
+            None,
         );
         assert!(result.is_ok());
 
@@ -614,6 +641,8 @@ mod tests {
             0,
             0,
             &mut 0,
+            None,
+            None,
         );
         assert!(result.is_ok());
         assert_eq!(
@@ -704,6 +733,8 @@ mod tests {
             0,
             0,
             &mut 0,
+            None,
+            None,
         );
 
         assert_eq!(
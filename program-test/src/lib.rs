@@ -10,7 +10,9 @@ use {
     log::*,
     solana_accounts_db::epoch_accounts_hash::EpochAccountsHash,
     solana_banks_client::start_client,
-    solana_banks_server::banks_server::start_local_server,
+    solana_banks_server::banks_server::{
+        start_local_server, start_local_server_with_impersonated_signers,
+    },
     solana_bpf_loader_program::serialization::serialize_parameters,
     solana_program_runtime::{
         compute_budget::ComputeBudget, ic_msg, invoke_context::BuiltinFunctionWithContext,
@@ -27,7 +29,7 @@ use {
     solana_sdk::{
         account::{create_account_shared_data_for_test, Account, AccountSharedData},
         account_info::AccountInfo,
-        clock::Slot,
+        clock::{Epoch, Slot, UnixTimestamp},
         entrypoint::{deserialize, ProgramResult, SUCCESS},
         feature_set::FEATURE_NAMES,
         fee_calculator::{FeeCalculator, FeeRateGovernor, DEFAULT_TARGET_LAMPORTS_PER_SIGNATURE},
@@ -41,7 +43,7 @@ use {
         rent::Rent,
         signature::{Keypair, Signer},
         stable_layout::stable_instruction::StableInstruction,
-        sysvar::{Sysvar, SysvarId},
+        sysvar::{clock::Clock, Sysvar, SysvarId},
     },
     solana_vote_program::vote_state::{self, VoteState, VoteStateVersions},
     std::{
@@ -81,6 +83,12 @@ pub enum ProgramTestError {
     /// The chosen warp slot is not in the future, so warp is not performed
     #[error("Warp slot not in the future")]
     InvalidWarpSlot,
+    /// The chosen warp epoch is not in the future, so warp is not performed
+    #[error("Warp epoch not in the future")]
+    InvalidWarpEpoch,
+    /// The checkpoint does not exist, or a warp rooted a later bank, so revert is not performed
+    #[error("Checkpoint not found")]
+    InvalidCheckpoint,
 }
 
 thread_local! {
@@ -462,6 +470,7 @@ pub struct ProgramTest {
     prefer_bpf: bool,
     deactivate_feature_set: HashSet<Pubkey>,
     transaction_account_lock_limit: Option<usize>,
+    enable_instruction_tracing: bool,
 }
 
 impl Default for ProgramTest {
@@ -499,6 +508,7 @@ impl Default for ProgramTest {
             prefer_bpf,
             deactivate_feature_set,
             transaction_account_lock_limit: None,
+            enable_instruction_tracing: false,
         }
     }
 }
@@ -540,6 +550,14 @@ impl ProgramTest {
         self.transaction_account_lock_limit = Some(transaction_account_lock_limit);
     }
 
+    /// Record the execution traces of the SBF programs that simulated transactions invoke, which
+    /// `BanksClient::simulate_transaction` returns.  Their compute units by function can be
+    /// written for flamegraphs with `solana_sdk::execution_trace::write_collapsed_stacks`.  Slows
+    /// down the execution of all programs.
+    pub fn enable_instruction_tracing(&mut self, enable_instruction_tracing: bool) {
+        self.enable_instruction_tracing = enable_instruction_tracing;
+    }
+
     /// Override the SBF compute budget
     #[allow(deprecated)]
     #[deprecated(since = "1.8.0", note = "please use `set_compute_max_units` instead")]
@@ -813,6 +831,7 @@ impl ProgramTest {
                     ..ComputeBudget::default()
                 }),
                 transaction_account_lock_limit: self.transaction_account_lock_limit,
+                enable_instruction_tracing: self.enable_instruction_tracing,
                 ..RuntimeConfig::default()
             }),
         );
@@ -902,10 +921,12 @@ impl ProgramTest {
     pub async fn start_with_context(mut self) -> ProgramTestContext {
         let (bank_forks, block_commitment_cache, last_blockhash, gci) = self.setup_bank();
         let target_tick_duration = gci.genesis_config.poh_config.target_tick_duration;
-        let transport = start_local_server(
+        let impersonated_signers = Arc::<RwLock<HashSet<Pubkey>>>::default();
+        let transport = start_local_server_with_impersonated_signers(
             bank_forks.clone(),
             block_commitment_cache.clone(),
             target_tick_duration,
+            impersonated_signers.clone(),
         )
         .await;
         let banks_client = start_client(transport)
@@ -918,6 +939,7 @@ impl ProgramTest {
             banks_client,
             last_blockhash,
             gci,
+            impersonated_signers,
         )
     }
 }
@@ -1007,8 +1029,16 @@ pub struct ProgramTestContext {
     bank_forks: Arc<RwLock<BankForks>>,
     block_commitment_cache: Arc<RwLock<BlockCommitmentCache>>,
     _bank_task: DroppableTask<()>,
+    impersonated_signers: Arc<RwLock<HashSet<Pubkey>>>,
+    checkpoints: HashMap<CheckpointId, Arc<Bank>>,
+    next_checkpoint_id: u64,
+    clock_unix_timestamp: Option<UnixTimestamp>,
 }
 
+/// Identifies a checkpoint of the bank state taken by [`ProgramTestContext::snapshot`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CheckpointId(u64);
+
 impl ProgramTestContext {
     fn new(
         bank_forks: Arc<RwLock<BankForks>>,
@@ -1016,6 +1046,7 @@ impl ProgramTestContext {
         banks_client: BanksClient,
         last_blockhash: Hash,
         genesis_config_info: GenesisConfigInfo,
+        impersonated_signers: Arc<RwLock<HashSet<Pubkey>>>,
     ) -> Self {
         // Run a simulated PohService to provide the client with new blockhashes.  New blockhashes
         // are required when sending multiple otherwise identical transactions in series from a
@@ -1053,6 +1084,10 @@ impl ProgramTestContext {
             bank_forks,
             block_commitment_cache,
             _bank_task: bank_task,
+            impersonated_signers,
+            checkpoints: HashMap::new(),
+            next_checkpoint_id: 0,
+            clock_unix_timestamp: None,
         }
     }
 
@@ -1167,22 +1202,10 @@ impl ProgramTestContext {
             });
 
         // warp_bank is frozen so go forward to get unfrozen bank at warp_slot
-        bank_forks.insert(Bank::new_from_parent(
-            warp_bank,
-            &Pubkey::default(),
-            warp_slot,
-        ));
-
-        // Update block commitment cache, otherwise banks server will poll at
-        // the wrong slot
-        let mut w_block_commitment_cache = self.block_commitment_cache.write().unwrap();
-        // HACK: The root set here should be `pre_warp_slot`, but since we're
-        // in a testing environment, the root bank never updates after a warp.
-        // The ticking thread only updates the working bank, and never the root
-        // bank.
-        w_block_commitment_cache.set_all_slots(warp_slot, warp_slot);
-
-        let bank = bank_forks.working_bank();
+        let bank = self.insert_working_bank(
+            &mut bank_forks,
+            Bank::new_from_parent(warp_bank, &Pubkey::default(), warp_slot),
+        );
         self.last_blockhash = bank.last_blockhash();
         Ok(())
     }
@@ -1208,22 +1231,153 @@ impl ProgramTestContext {
         let mut warp_bank = Bank::new_from_parent(bank, &Pubkey::default(), warp_slot);
 
         warp_bank.force_reward_interval_end_for_tests();
-        bank_forks.insert(warp_bank);
+        let bank = self.insert_working_bank(&mut bank_forks, warp_bank);
+        self.last_blockhash = bank.last_blockhash();
+        Ok(())
+    }
 
-        // Update block commitment cache, otherwise banks server will poll at
-        // the wrong slot
-        let mut w_block_commitment_cache = self.block_commitment_cache.write().unwrap();
-        // HACK: The root set here should be `pre_warp_slot`, but since we're
-        // in a testing environment, the root bank never updates after a warp.
-        // The ticking thread only updates the working bank, and never the root
-        // bank.
-        w_block_commitment_cache.set_all_slots(warp_slot, warp_slot);
+    /// Force the working bank ahead to the first slot of `warp_epoch`, or to the first slot in
+    /// which the rewards of the previous epoch have been distributed when partitioned epoch rewards
+    /// are enabled.
+    ///
+    /// Unlike a single `warp_to_slot`, every epoch in between is warped to in turn, so stakes
+    /// activate and deactivate and the rewards of every epoch are calculated and distributed.
+    pub fn warp_to_epoch(&mut self, warp_epoch: Epoch) -> Result<(), ProgramTestError> {
+        let (epoch_schedule, working_epoch) = {
+            let bank = self.bank_forks.read().unwrap().working_bank();
+            (bank.epoch_schedule().clone(), bank.epoch())
+        };
+        if warp_epoch <= working_epoch {
+            return Err(ProgramTestError::InvalidWarpEpoch);
+        }
+
+        for epoch in working_epoch + 1..=warp_epoch {
+            self.warp_to_slot(epoch_schedule.get_first_slot_in_epoch(epoch))?;
+
+            // Partitioned rewards are distributed over the blocks following the first one
+            let mut bank_forks = self.bank_forks.write().unwrap();
+            let mut bank = bank_forks.working_bank();
+            while bank.is_in_reward_interval() {
+                bank.fill_bank_with_ticks_for_tests();
+                let slot = bank.slot() + 1;
+                bank = self.insert_working_bank(
+                    &mut bank_forks,
+                    Bank::new_from_parent(bank, &Pubkey::default(), slot),
+                );
+            }
+            self.last_blockhash = bank.last_blockhash();
+        }
+        Ok(())
+    }
 
+    /// Checkpoint the whole bank state, so that [`revert`](Self::revert) can restore it.
+    ///
+    /// The working bank is frozen and kept as the checkpoint, and a new working bank continues
+    /// from it at the next slot.  A checkpoint can be reverted to any number of times, unless a
+    /// later warp roots a bank past it.
+    pub fn snapshot(&mut self) -> CheckpointId {
+        let mut bank_forks = self.bank_forks.write().unwrap();
         let bank = bank_forks.working_bank();
+
+        // Fill ticks until a new blockhash is recorded, otherwise retried transactions will have
+        // the same signature
+        bank.fill_bank_with_ticks_for_tests();
+        bank.freeze();
+
+        let checkpoint_id = CheckpointId(self.next_checkpoint_id);
+        self.next_checkpoint_id += 1;
+        self.checkpoints.insert(checkpoint_id, bank.clone());
+
+        let slot = bank.slot() + 1;
+        let bank = self.insert_working_bank(
+            &mut bank_forks,
+            Bank::new_from_parent(bank, &Pubkey::default(), slot),
+        );
+        self.last_blockhash = bank.last_blockhash();
+        checkpoint_id
+    }
+
+    /// Restore the bank state of a checkpoint taken by [`snapshot`](Self::snapshot).
+    ///
+    /// A new working bank continues from the checkpoint at the slot after the highest one so
+    /// far, so slots keep increasing.  Blockhashes from after the checkpoint are no longer
+    /// valid; use the updated `last_blockhash`.
+    pub fn revert(&mut self, checkpoint_id: CheckpointId) -> Result<(), ProgramTestError> {
+        let mut bank_forks = self.bank_forks.write().unwrap();
+        let checkpoint = self
+            .checkpoints
+            .get(&checkpoint_id)
+            .ok_or(ProgramTestError::InvalidCheckpoint)?
+            .clone();
+
+        // Rooted banks past the checkpoint would leak their accounts into its new fork
+        if checkpoint.slot() < bank_forks.root() {
+            self.checkpoints.remove(&checkpoint_id);
+            return Err(ProgramTestError::InvalidCheckpoint);
+        }
+
+        let slot = bank_forks.highest_slot() + 1;
+        let bank = self.insert_working_bank(
+            &mut bank_forks,
+            Bank::new_from_parent(checkpoint, &Pubkey::default(), slot),
+        );
         self.last_blockhash = bank.last_blockhash();
         Ok(())
     }
 
+    /// Accept transactions from `signer` without its signature.
+    ///
+    /// The signatures of impersonated signers can be left unset, or signed by any keypair.  If
+    /// the fee payer is impersonated, the first signature should still be unique, since it
+    /// identifies the transaction.
+    pub fn impersonate(&self, signer: Pubkey) {
+        self.impersonated_signers.write().unwrap().insert(signer);
+    }
+
+    /// Require the signature of a signer passed to [`impersonate`](Self::impersonate) again
+    pub fn stop_impersonating(&self, signer: &Pubkey) {
+        self.impersonated_signers.write().unwrap().remove(signer);
+    }
+
+    /// Override the `unix_timestamp` of the `Clock` sysvar independently of the slot, or stop
+    /// overriding it with `None`.
+    ///
+    /// The override applies to the working bank and to the banks that later warps, snapshots
+    /// and reverts create.
+    pub fn set_clock_unix_timestamp(&mut self, unix_timestamp: Option<UnixTimestamp>) {
+        self.clock_unix_timestamp = unix_timestamp;
+        if let Some(unix_timestamp) = unix_timestamp {
+            let bank_forks = self.bank_forks.read().unwrap();
+            let bank = bank_forks.working_bank();
+            bank.set_sysvar_for_tests(&Clock {
+                unix_timestamp,
+                ..bank.clock()
+            });
+        }
+    }
+
+    /// Make `bank` the working bank, which the banks server processes transactions with
+    fn insert_working_bank(&self, bank_forks: &mut BankForks, bank: Bank) -> Arc<Bank> {
+        if let Some(unix_timestamp) = self.clock_unix_timestamp {
+            bank.set_sysvar_for_tests(&Clock {
+                unix_timestamp,
+                ..bank.clock()
+            });
+        }
+        let slot = bank.slot();
+        let bank = bank_forks.insert(bank).clone_without_scheduler();
+
+        // Update block commitment cache, otherwise banks server will poll at
+        // the wrong slot
+        let mut w_block_commitment_cache = self.block_commitment_cache.write().unwrap();
+        // HACK: The root set here should be the root of `bank_forks`, but since
+        // we're in a testing environment, the root bank never updates after a
+        // warp. The ticking thread only updates the working bank, and never the
+        // root bank.
+        w_block_commitment_cache.set_all_slots(slot, slot);
+        bank
+    }
+
     /// Get a new latest blockhash, similar in spirit to RpcClient::get_latest_blockhash()
     pub async fn get_new_latest_blockhash(&mut self) -> io::Result<Hash> {
         let blockhash = self
@@ -0,0 +1,120 @@
+use {
+    solana_program_test::{ProgramTest, ProgramTestContext, ProgramTestError},
+    solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+async fn transfer(context: &mut ProgramTestContext, recipient: &Pubkey, lamports: u64) {
+    let transaction = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(
+            &context.payer.pubkey(),
+            recipient,
+            lamports,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn revert_to_snapshot() {
+    let program_test = ProgramTest::default();
+    let mut context = program_test.start_with_context().await;
+    let recipient = Pubkey::new_unique();
+
+    let checkpoint = context.snapshot();
+    transfer(&mut context, &recipient, 1_000_000_000).await;
+    assert_eq!(
+        context.banks_client.get_balance(recipient).await.unwrap(),
+        1_000_000_000
+    );
+    let reverted_slot = context.banks_client.get_root_slot().await.unwrap();
+
+    context.revert(checkpoint).unwrap();
+    assert_eq!(
+        context.banks_client.get_balance(recipient).await.unwrap(),
+        0
+    );
+    assert!(context.banks_client.get_root_slot().await.unwrap() > reverted_slot);
+
+    // checkpoints can be reverted to repeatedly
+    transfer(&mut context, &recipient, 2_000_000_000).await;
+    assert_eq!(
+        context.banks_client.get_balance(recipient).await.unwrap(),
+        2_000_000_000
+    );
+    context.revert(checkpoint).unwrap();
+    assert_eq!(
+        context.banks_client.get_balance(recipient).await.unwrap(),
+        0
+    );
+
+    // warping roots a bank past the checkpoint
+    context.warp_to_slot(100).unwrap();
+    assert_eq!(
+        context.revert(checkpoint),
+        Err(ProgramTestError::InvalidCheckpoint)
+    );
+}
+
+#[tokio::test]
+async fn impersonate_signer() {
+    let program_test = ProgramTest::default();
+    let mut context = program_test.start_with_context().await;
+    let impersonated = Keypair::new().pubkey();
+    let recipient = Pubkey::new_unique();
+    transfer(&mut context, &impersonated, 1_000_000_000).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::transfer(
+            &impersonated,
+            &recipient,
+            500_000_000,
+        )],
+        Some(&context.payer.pubkey()),
+    );
+    transaction.partial_sign(&[&context.payer], context.last_blockhash);
+    assert_eq!(
+        context
+            .banks_client
+            .process_transaction(transaction.clone())
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::SignatureFailure
+    );
+
+    context.impersonate(impersonated);
+    context
+        .banks_client
+        .process_transaction(transaction.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        context.banks_client.get_balance(recipient).await.unwrap(),
+        500_000_000
+    );
+
+    context.stop_impersonating(&impersonated);
+    let blockhash = context.get_new_latest_blockhash().await.unwrap();
+    transaction.partial_sign(&[&context.payer], blockhash);
+    assert_eq!(
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::SignatureFailure
+    );
+}
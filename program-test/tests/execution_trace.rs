@@ -0,0 +1,206 @@
+use {
+    solana_banks_interface::TransactionSimulationDetails,
+    solana_program_test::ProgramTest,
+    solana_sdk::{
+        execution_trace::{write_collapsed_stacks, ExecutionTraceList, ProgramExecutionTrace},
+        instruction::{AccountMeta, Instruction, TRANSACTION_LEVEL_STACK_HEIGHT},
+        pubkey::Pubkey,
+        signature::Signer,
+        signer::keypair::Keypair,
+        system_instruction, system_program,
+        sysvar::rent,
+        transaction::Transaction,
+    },
+};
+
+/// Simulates the initialization of a token-2022 mint, optionally followed by the creation of an
+/// associated token account for it, which invokes the token-2022 program
+async fn simulate_transaction(
+    program_test: ProgramTest,
+    create_associated_token_account: bool,
+) -> TransactionSimulationDetails {
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let token_2022_id = Pubkey::try_from("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb").unwrap();
+    let mint = Keypair::new();
+    let rent = banks_client.get_rent().await.unwrap();
+    let space = 82;
+    let mut instructions = vec![
+        system_instruction::create_account(
+            &payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(space),
+            space as u64,
+            &token_2022_id,
+        ),
+        Instruction::new_with_bytes(
+            token_2022_id,
+            &[0; 35], // initialize mint
+            vec![
+                AccountMeta::new(mint.pubkey(), false),
+                AccountMeta::new_readonly(rent::id(), false),
+            ],
+        ),
+    ];
+    if create_associated_token_account {
+        let associated_token_account_id =
+            Pubkey::try_from("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL").unwrap();
+        let (associated_token_address, _bump) = Pubkey::find_program_address(
+            &[
+                payer.pubkey().as_ref(),
+                token_2022_id.as_ref(),
+                mint.pubkey().as_ref(),
+            ],
+            &associated_token_account_id,
+        );
+        instructions.push(Instruction::new_with_bytes(
+            associated_token_account_id,
+            &[], // create
+            vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(associated_token_address, false),
+                AccountMeta::new_readonly(payer.pubkey(), false),
+                AccountMeta::new_readonly(mint.pubkey(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(token_2022_id, false),
+            ],
+        ));
+    }
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &[&payer, &mint],
+        recent_blockhash,
+    );
+
+    let simulation = banks_client
+        .simulate_transaction(transaction)
+        .await
+        .unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+    simulation.simulation_details.unwrap()
+}
+
+async fn simulate_initialize_mint(program_test: ProgramTest) -> Option<ExecutionTraceList> {
+    simulate_transaction(program_test, false)
+        .await
+        .execution_traces
+}
+
+fn profiled_compute_units(execution_trace: &ProgramExecutionTrace) -> u64 {
+    execution_trace
+        .compute_unit_profile
+        .iter()
+        .map(|function_stack| function_stack.compute_units)
+        .sum()
+}
+
+#[tokio::test]
+async fn execution_trace() {
+    let mut program_test = ProgramTest::default();
+    program_test.enable_instruction_tracing(true);
+    let execution_traces = simulate_initialize_mint(program_test).await.unwrap();
+
+    // The system program is not traced
+    assert_eq!(execution_traces.len(), 2);
+    assert!(execution_traces[0].is_empty());
+    assert_eq!(execution_traces[1].len(), 1);
+    let execution_trace = &execution_traces[1][0];
+    assert_eq!(
+        execution_trace.program_id,
+        Pubkey::try_from("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb").unwrap()
+    );
+    assert_eq!(
+        execution_trace.stack_height,
+        TRANSACTION_LEVEL_STACK_HEIGHT as u32
+    );
+    assert!(!execution_trace.registers.is_empty());
+    // The program logs the instruction it processes
+    assert!(execution_trace
+        .syscalls
+        .iter()
+        .any(|syscall| syscall.name == "sol_log_"
+            && (syscall.step as usize) < execution_trace.registers.len()));
+}
+
+#[tokio::test]
+async fn compute_unit_profile() {
+    let mut program_test = ProgramTest::default();
+    program_test.enable_instruction_tracing(true);
+    let execution_traces = simulate_initialize_mint(program_test).await.unwrap();
+    let execution_trace = &execution_traces[1][0];
+
+    // Each instruction consumes one compute unit, syscalls consume more
+    let profiled_compute_units = profiled_compute_units(execution_trace);
+    let syscall_compute_units = execution_trace
+        .syscalls
+        .iter()
+        .map(|syscall| syscall.compute_units)
+        .sum::<u64>();
+    assert!(syscall_compute_units > 0);
+    assert_eq!(
+        profiled_compute_units,
+        execution_trace.registers.len() as u64 + syscall_compute_units
+    );
+    assert!(execution_trace
+        .compute_unit_profile
+        .iter()
+        .all(|function_stack| !function_stack.stack.is_empty()));
+
+    let mut collapsed_stacks = Vec::new();
+    write_collapsed_stacks(&mut collapsed_stacks, execution_traces.iter().flatten()).unwrap();
+    let collapsed_stacks = String::from_utf8(collapsed_stacks).unwrap();
+    assert_eq!(
+        collapsed_stacks.lines().count(),
+        execution_trace.compute_unit_profile.len()
+    );
+    assert!(collapsed_stacks
+        .lines()
+        .all(|line| line.starts_with(&format!("{};", execution_trace.program_id))));
+}
+
+#[tokio::test]
+async fn compute_unit_profile_cross_program_invocation() {
+    let mut program_test = ProgramTest::default();
+    program_test.enable_instruction_tracing(true);
+    let simulation_details = simulate_transaction(program_test, true).await;
+    let execution_traces = simulation_details.execution_traces.unwrap();
+
+    // The associated token account program invokes the token-2022 program, which is traced
+    // separately
+    let execution_traces = &execution_traces[2];
+    let (caller_execution_trace, invoked_execution_traces) =
+        execution_traces.split_first().unwrap();
+    assert_eq!(
+        caller_execution_trace.stack_height,
+        TRANSACTION_LEVEL_STACK_HEIGHT as u32
+    );
+    assert!(!invoked_execution_traces.is_empty());
+    assert!(invoked_execution_traces
+        .iter()
+        .all(|execution_trace| execution_trace.stack_height
+            == TRANSACTION_LEVEL_STACK_HEIGHT as u32 + 1));
+
+    // The compute units of the invoked programs are not counted again by the syscalls invoking
+    // them, so the profiles add up to the compute units the instruction consumed
+    let consumed_message = format!("Program {} consumed ", caller_execution_trace.program_id);
+    let consumed_compute_units = simulation_details
+        .logs
+        .iter()
+        .find_map(|log| log.strip_prefix(&consumed_message))
+        .and_then(|log| log.split_whitespace().next())
+        .and_then(|compute_units| compute_units.parse::<u64>().ok())
+        .unwrap();
+    assert_eq!(
+        execution_traces
+            .iter()
+            .map(profiled_compute_units)
+            .sum::<u64>(),
+        consumed_compute_units
+    );
+}
+
+#[tokio::test]
+async fn no_execution_trace() {
+    assert_eq!(simulate_initialize_mint(ProgramTest::default()).await, None);
+}
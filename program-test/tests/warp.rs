@@ -465,3 +465,66 @@ async fn get_blockhash_post_warp() {
     tx.sign(&[&context.payer], new_blockhash);
     context.banks_client.process_transaction(tx).await.unwrap();
 }
+
+#[tokio::test]
+async fn stake_rewards_from_warp_to_epoch() {
+    // Initialize and start the test network
+    let program_test = ProgramTest::default();
+    let mut context = program_test.start_with_context().await;
+
+    context.warp_to_slot(100).unwrap();
+    let vote_address = setup_vote(&mut context).await;
+
+    let user_keypair = Keypair::new();
+    let stake_lamports = 1_000_000_000_000;
+    let stake_address =
+        setup_stake(&mut context, &user_keypair, &vote_address, stake_lamports).await;
+
+    // warp epoch by epoch until normal inflation, no rewards collected
+    let first_normal_epoch = context.genesis_config().epoch_schedule.first_normal_epoch;
+    context.warp_to_epoch(first_normal_epoch).unwrap();
+    let account = context
+        .banks_client
+        .get_account(stake_address)
+        .await
+        .expect("account exists")
+        .unwrap();
+    assert_eq!(account.lamports, stake_lamports);
+
+    context.increment_vote_account_credits(&vote_address, 100);
+
+    // the rewards are distributed by the time the warp returns
+    context.warp_to_epoch(first_normal_epoch + 1).unwrap();
+    let account = context
+        .banks_client
+        .get_account(stake_address)
+        .await
+        .expect("account exists")
+        .unwrap();
+    assert!(account.lamports > stake_lamports);
+
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    assert_eq!(clock.epoch, first_normal_epoch + 1);
+
+    assert_eq!(
+        context.warp_to_epoch(first_normal_epoch + 1),
+        Err(ProgramTestError::InvalidWarpEpoch)
+    );
+}
+
+#[tokio::test]
+async fn clock_unix_timestamp_override() {
+    let program_test = ProgramTest::default();
+    let mut context = program_test.start_with_context().await;
+    let unix_timestamp = 1_700_000_000;
+
+    context.set_clock_unix_timestamp(Some(unix_timestamp));
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    assert_eq!(clock.unix_timestamp, unix_timestamp);
+
+    // the override outlives warps, which only move the slot
+    context.warp_to_slot(100).unwrap();
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    assert_eq!(clock.slot, 100);
+    assert_eq!(clock.unix_timestamp, unix_timestamp);
+}
@@ -33,6 +33,9 @@ use {
         bpf_loader_upgradeable::{self, UpgradeableLoaderState},
         clock::Slot,
         entrypoint::{MAX_PERMITTED_DATA_INCREASE, SUCCESS},
+        execution_trace::{
+            FunctionStackComputeUnits, ProgramExecutionTrace, RegisterState, SyscallInvocation,
+        },
         feature_set::{
             bpf_account_data_direct_mapping, delay_visibility_of_program_deployment,
             enable_bpf_loader_extend_program_ix, enable_bpf_loader_set_authority_checked_ix,
@@ -54,6 +57,7 @@ use {
     },
     std::{
         cell::RefCell,
+        collections::HashMap,
         mem,
         rc::Rc,
         sync::{atomic::Ordering, Arc},
@@ -265,6 +269,9 @@ pub fn create_vm<'a, 'b>(
         allocator: BpfAllocator::new(heap_size as u64),
         accounts_metadata,
         trace_log: Vec::new(),
+        vm_compute_units: 0,
+        invoked_compute_units: 0,
+        trace_meter_log: Vec::new(),
     })?;
     Ok(EbpfVm::new(
         program.get_loader().clone(),
@@ -1569,6 +1576,7 @@ fn execute<'a, 'b: 'a>(
     let mut execute_time;
     let execution_result = {
         let compute_meter_prev = invoke_context.get_remaining();
+        let execution_trace_index = invoke_context.start_execution_trace(program_id);
         create_vm!(vm, executable, regions, accounts_metadata, invoke_context,);
         let mut vm = match vm {
             Ok(info) => info,
@@ -1582,6 +1590,14 @@ fn execute<'a, 'b: 'a>(
         execute_time = Measure::start("execute");
         let (compute_units_consumed, result) = vm.execute_program(executable, !use_jit);
         drop(vm);
+        if let Some(execution_trace_index) = execution_trace_index {
+            let execution_trace = program_execution_trace(invoke_context, executable, program_id)?;
+            invoke_context.finish_execution_trace(
+                execution_trace_index,
+                execution_trace,
+                compute_units_consumed,
+            );
+        }
         ic_logger_msg!(
             log_collector,
             "Program {} consumed {} of {} compute units",
@@ -1691,6 +1707,171 @@ fn execute<'a, 'b: 'a>(
     execute_or_deserialize_result
 }
 
+/// Builds the execution trace of the program that the VM of the current invocation executed, from
+/// the register states and compute meter it traced
+pub fn program_execution_trace(
+    invoke_context: &InvokeContext,
+    executable: &Executable<InvokeContext>,
+    program_id: Pubkey,
+) -> Result<ProgramExecutionTrace, InstructionError> {
+    let syscall_context = invoke_context.get_syscall_context()?;
+    let final_meter = invoke_context
+        .get_remaining()
+        .saturating_add(syscall_context.vm_compute_units)
+        .saturating_add(syscall_context.invoked_compute_units);
+    let registers = &syscall_context.trace_log;
+    let syscalls = trace_syscalls(
+        executable,
+        registers,
+        &syscall_context.trace_meter_log,
+        final_meter,
+    );
+    let compute_unit_profile = profile_compute_units(executable, registers, &syscalls);
+    Ok(ProgramExecutionTrace {
+        program_id,
+        stack_height: invoke_context.get_stack_height() as u32,
+        // The trace log is kept for the callers of `InvokeContext::get_traces`
+        registers: registers.clone(),
+        syscalls,
+        compute_unit_profile,
+    })
+}
+
+/// Decodes the instruction that executed with the given register state
+fn traced_instruction(text: &[u8], state: &RegisterState) -> Option<ebpf::Insn> {
+    // The last register is the program counter, counted in instructions
+    let pc = usize::try_from(state[11]).ok()?;
+    if pc.saturating_add(1).saturating_mul(ebpf::INSN_SIZE) > text.len() {
+        return None;
+    }
+    Some(ebpf::get_insn_unchecked(text, pc))
+}
+
+/// Finds the syscalls that the instructions of an execution trace invoke, and the compute units
+/// they consume from the trace of the compute meter, except for the ones of the traced programs
+/// they invoke
+fn trace_syscalls(
+    executable: &Executable<InvokeContext>,
+    registers: &[RegisterState],
+    meter_log: &[u64],
+    final_meter: u64,
+) -> Vec<SyscallInvocation> {
+    let (_text_vaddr, text) = executable.get_text_bytes();
+    let static_syscalls = executable.get_sbpf_version().static_syscalls();
+    let function_registry = executable.get_loader().get_function_registry();
+    registers
+        .iter()
+        .enumerate()
+        .filter_map(|(step, state)| {
+            let insn = traced_instruction(text, state)?;
+            // With static syscalls, calls to functions of the program have a non-zero source
+            if insn.opc != ebpf::CALL_IMM || (static_syscalls && insn.src != 0) {
+                return None;
+            }
+            let (name, _function) = function_registry.lookup_by_key(insn.imm as u32)?;
+            let meter_at = |step: usize| meter_log.get(step).copied().unwrap_or(final_meter);
+            Some(SyscallInvocation {
+                step: step as u64,
+                name: String::from_utf8_lossy(name).into_owned(),
+                compute_units: meter_at(step).saturating_sub(meter_at(step.saturating_add(1))),
+            })
+        })
+        .collect()
+}
+
+/// Attributes the compute units of an execution trace to the stacks of functions of the program
+/// that were executing, one per instruction plus the compute units of the syscalls
+fn profile_compute_units(
+    executable: &Executable<InvokeContext>,
+    registers: &[RegisterState],
+    syscalls: &[SyscallInvocation],
+) -> Vec<FunctionStackComputeUnits> {
+    struct StackNode {
+        parent: Option<usize>,
+        function: Option<usize>,
+        compute_units: u64,
+    }
+
+    // Stacks are the nodes of the call tree, identified by their parent and innermost function
+    fn enter(
+        nodes: &mut Vec<StackNode>,
+        node_indexes: &mut HashMap<(Option<usize>, Option<usize>), usize>,
+        parent: Option<usize>,
+        function: Option<usize>,
+    ) -> usize {
+        *node_indexes.entry((parent, function)).or_insert_with(|| {
+            nodes.push(StackNode {
+                parent,
+                function,
+                compute_units: 0,
+            });
+            nodes.len().saturating_sub(1)
+        })
+    }
+
+    let (_text_vaddr, text) = executable.get_text_bytes();
+    let mut functions = executable
+        .get_function_registry()
+        .iter()
+        .map(|(_key, (name, pc))| (pc, name))
+        .collect::<Vec<_>>();
+    functions.sort_unstable();
+    // The index of the last function starting at or before the program counter of a state
+    let function_at = |state: &RegisterState| {
+        functions
+            .partition_point(|(start, _name)| *start as u64 <= state[11])
+            .checked_sub(1)
+    };
+
+    let mut nodes = Vec::new();
+    let mut node_indexes = HashMap::new();
+    let mut current_node = None;
+    let mut syscalls = syscalls.iter().peekable();
+    for (step, state) in registers.iter().enumerate() {
+        let node = current_node
+            .unwrap_or_else(|| enter(&mut nodes, &mut node_indexes, None, function_at(state)));
+        current_node = Some(node);
+        let mut compute_units = 1u64;
+        if let Some(syscall) = syscalls.next_if(|syscall| syscall.step == step as u64) {
+            compute_units = compute_units.saturating_add(syscall.compute_units);
+        } else if let Some(insn) = traced_instruction(text, state) {
+            if insn.opc == ebpf::CALL_IMM || insn.opc == ebpf::CALL_REG {
+                // The next instruction is the first one of the called function
+                if let Some(next_state) = registers.get(step.saturating_add(1)) {
+                    let function = function_at(next_state);
+                    current_node = Some(enter(&mut nodes, &mut node_indexes, Some(node), function));
+                }
+            } else if insn.opc == ebpf::EXIT {
+                current_node = nodes.get(node).and_then(|node| node.parent);
+            }
+        }
+        if let Some(node) = nodes.get_mut(node) {
+            node.compute_units = node.compute_units.saturating_add(compute_units);
+        }
+    }
+
+    nodes
+        .iter()
+        .filter(|node| node.compute_units > 0)
+        .map(|node| {
+            let mut stack = Vec::new();
+            let mut next = Some(node);
+            while let Some(node) = next {
+                stack.push(match node.function.and_then(|index| functions.get(index)) {
+                    Some((_start, name)) => String::from_utf8_lossy(name).into_owned(),
+                    None => "unknown".to_string(),
+                });
+                next = node.parent.and_then(|parent| nodes.get(parent));
+            }
+            stack.reverse();
+            FunctionStackComputeUnits {
+                stack,
+                compute_units: node.compute_units,
+            }
+        })
+        .collect()
+}
+
 pub mod test_utils {
     use {
         super::*, solana_program_runtime::loaded_programs::DELAY_VISIBILITY_SLOT_OFFSET,
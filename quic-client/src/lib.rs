@@ -76,10 +76,11 @@ impl ConnectionPool for QuicPool {
         config: &Self::NewConnectionConfig,
         addr: &SocketAddr,
     ) -> Arc<Self::BaseClientConnection> {
-        Arc::new(Quic(Arc::new(QuicClient::new(
+        Arc::new(Quic(Arc::new(QuicClient::new_with_datagrams(
             self.endpoint.clone(),
             *addr,
             config.compute_max_parallel_streams(),
+            config.use_datagrams(),
         ))))
     }
 }
@@ -93,6 +94,9 @@ pub struct QuicConfig {
     // The optional specified endpoint for the quic based client connections
     // If not specified, the connection cache will create as needed.
     client_endpoint: Option<Endpoint>,
+
+    // Send data as datagrams to servers which accept them, if the client is staked.
+    use_datagrams: bool,
 }
 
 impl NewConnectionConfig for QuicConfig {
@@ -107,6 +111,7 @@ impl NewConnectionConfig for QuicConfig {
             maybe_staked_nodes: None,
             maybe_client_pubkey: None,
             client_endpoint: None,
+            use_datagrams: false,
         })
     }
 }
@@ -137,6 +142,16 @@ impl QuicConfig {
         compute_max_allowed_uni_streams(client_type, stake, total_stake)
     }
 
+    // Servers drop the datagrams of unstaked peers, so unstaked clients always use streams
+    fn use_datagrams(&self) -> bool {
+        self.use_datagrams
+            && self
+                .maybe_client_pubkey
+                .zip(self.maybe_staked_nodes.as_ref())
+                .map(|(pubkey, stakes)| stakes.read().unwrap().get_node_stake(&pubkey).is_some())
+                .unwrap_or_default()
+    }
+
     pub fn update_client_certificate(
         &mut self,
         keypair: &Keypair,
@@ -162,6 +177,14 @@ impl QuicConfig {
     pub fn update_client_endpoint(&mut self, client_endpoint: Endpoint) {
         self.client_endpoint = Some(client_endpoint);
     }
+
+    /// Sends data as QUIC datagrams to servers which accept them, falling
+    /// back to streams for data too large for a datagram. Only takes effect
+    /// once the client is staked, see `set_staked_nodes`; servers only accept
+    /// datagrams from staked peers.
+    pub fn set_use_datagrams(&mut self, use_datagrams: bool) {
+        self.use_datagrams = use_datagrams;
+    }
 }
 
 pub struct Quic(Arc<QuicClient>);
@@ -290,4 +313,25 @@ mod tests {
             QUIC_MIN_STAKED_CONCURRENT_STREAMS
         );
     }
+
+    #[test]
+    fn test_connection_config_use_datagrams() {
+        let mut connection_config = QuicConfig::new().unwrap();
+        connection_config.set_use_datagrams(true);
+        assert!(!connection_config.use_datagrams());
+
+        // Unstaked clients send over streams, since servers drop their datagrams.
+        let staked_nodes = Arc::new(RwLock::new(StakedNodes::default()));
+        let pubkey = Pubkey::new_unique();
+        connection_config.set_staked_nodes(&staked_nodes, &pubkey);
+        assert!(!connection_config.use_datagrams());
+
+        *staked_nodes.write().unwrap() = StakedNodes::new(
+            Arc::new(HashMap::from([(pubkey, 1_000)])),
+            HashMap::default(),
+        );
+        assert!(connection_config.use_datagrams());
+        connection_config.set_use_datagrams(false);
+        assert!(!connection_config.use_datagrams());
+    }
 }
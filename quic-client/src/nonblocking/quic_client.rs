@@ -4,12 +4,13 @@
 use {
     async_mutex::Mutex,
     async_trait::async_trait,
+    bytes::Bytes,
     futures::future::{join_all, TryFutureExt},
     itertools::Itertools,
     log::*,
     quinn::{
         ClientConfig, ConnectError, Connection, ConnectionError, Endpoint, EndpointConfig,
-        IdleTimeout, TokioRuntime, TransportConfig, WriteError,
+        IdleTimeout, SendDatagramError, TokioRuntime, TransportConfig, WriteError,
     },
     solana_connection_cache::{
         client_connection::ClientStats, connection_cache_stats::ConnectionCacheStats,
@@ -30,6 +31,7 @@ use {
         nonblocking::quic::ALPN_TPU_PROTOCOL_ID, tls_certificates::new_self_signed_tls_certificate,
     },
     std::{
+        io,
         net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
         sync::{atomic::Ordering, Arc},
         thread,
@@ -80,6 +82,17 @@ pub enum QuicError {
     ConnectionError(#[from] ConnectionError),
     #[error(transparent)]
     ConnectError(#[from] ConnectError),
+    #[error(transparent)]
+    SendDatagramError(SendDatagramError),
+}
+
+impl From<SendDatagramError> for QuicError {
+    fn from(err: SendDatagramError) -> Self {
+        match err {
+            SendDatagramError::ConnectionLost(err) => Self::ConnectionError(err),
+            err => Self::SendDatagramError(err),
+        }
+    }
 }
 
 impl From<QuicError> for ClientErrorKind {
@@ -144,6 +157,13 @@ impl QuicLazyInitializedEndpoint {
             .await
             .clone()
     }
+
+    /// Switches the endpoint to a new socket, e.g. after the local address
+    /// changed. Established connections migrate to the new socket, keeping
+    /// the client identity they were established with.
+    pub async fn rebind(&self, socket: UdpSocket) -> io::Result<()> {
+        self.get_endpoint().await.rebind(socket)
+    }
 }
 
 impl Default for QuicLazyInitializedEndpoint {
@@ -252,6 +272,7 @@ pub struct QuicClient {
     addr: SocketAddr,
     stats: Arc<ClientStats>,
     chunk_size: usize,
+    use_datagrams: bool,
 }
 
 impl QuicClient {
@@ -259,6 +280,20 @@ impl QuicClient {
         endpoint: Arc<QuicLazyInitializedEndpoint>,
         addr: SocketAddr,
         chunk_size: usize,
+    ) -> Self {
+        Self::new_with_datagrams(endpoint, addr, chunk_size, false)
+    }
+
+    /// If `use_datagrams` is set, data is sent as QUIC datagrams when the
+    /// server accepts them and the data fits in a datagram, which avoids the
+    /// overhead of opening a stream per packet. Datagrams are not
+    /// retransmitted if lost. Servers drop the datagrams of unstaked peers,
+    /// so only set it for clients the server knows to be staked.
+    pub fn new_with_datagrams(
+        endpoint: Arc<QuicLazyInitializedEndpoint>,
+        addr: SocketAddr,
+        chunk_size: usize,
+        use_datagrams: bool,
     ) -> Self {
         Self {
             endpoint,
@@ -266,13 +301,24 @@ impl QuicClient {
             addr,
             stats: Arc::new(ClientStats::default()),
             chunk_size,
+            use_datagrams,
         }
     }
 
     async fn _send_buffer_using_conn(
         data: &[u8],
         connection: &Connection,
+        use_datagrams: bool,
     ) -> Result<(), QuicError> {
+        if use_datagrams
+            && connection
+                .max_datagram_size()
+                .map(|max_datagram_size| data.len() <= max_datagram_size)
+                .unwrap_or_default()
+        {
+            connection.send_datagram(Bytes::copy_from_slice(data))?;
+            return Ok(());
+        }
         let mut send_stream = connection.open_uni().await?;
 
         send_stream.write_all(data).await?;
@@ -395,7 +441,7 @@ impl QuicClient {
             last_connection_id = connection.stable_id();
             measure_prepare_connection.stop();
 
-            match Self::_send_buffer_using_conn(data, &connection).await {
+            match Self::_send_buffer_using_conn(data, &connection, self.use_datagrams).await {
                 Ok(()) => {
                     measure_send_packet.stop();
                     stats.successful_packets.fetch_add(1, Ordering::Relaxed);
@@ -497,11 +543,9 @@ impl QuicClient {
         let futures: Vec<_> = chunks
             .into_iter()
             .map(|buffs| {
-                join_all(
-                    buffs
-                        .into_iter()
-                        .map(|buf| Self::_send_buffer_using_conn(buf.as_ref(), connection_ref)),
-                )
+                join_all(buffs.into_iter().map(|buf| {
+                    Self::_send_buffer_using_conn(buf.as_ref(), connection_ref, self.use_datagrams)
+                }))
             })
             .collect();
 
@@ -10,7 +10,8 @@ mod tests {
         },
         solana_sdk::{net::DEFAULT_TPU_COALESCE, packet::PACKET_DATA_SIZE, signature::Keypair},
         solana_streamer::{
-            nonblocking::quic::DEFAULT_WAIT_FOR_CHUNK_TIMEOUT, streamer::StakedNodes,
+            nonblocking::quic::DEFAULT_WAIT_FOR_CHUNK_TIMEOUT, quic::QuicServerOptions,
+            quic_qos::StakeWeightedQos, streamer::StakedNodes,
             tls_certificates::new_self_signed_tls_certificate,
         },
         std::{
@@ -77,10 +78,10 @@ mod tests {
             exit.clone(),
             1,
             staked_nodes,
-            10,
-            10,
+            Arc::new(StakeWeightedQos::new(10, 10)),
             DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
             DEFAULT_TPU_COALESCE,
+            QuicServerOptions::default(),
         )
         .unwrap();
 
@@ -157,10 +158,10 @@ mod tests {
             exit.clone(),
             1,
             staked_nodes,
-            10,
-            10,
+            Arc::new(StakeWeightedQos::new(10, 10)),
             Duration::from_secs(1), // wait_for_chunk_timeout
             DEFAULT_TPU_COALESCE,
+            QuicServerOptions::default(),
         )
         .unwrap();
 
@@ -185,6 +186,82 @@ mod tests {
         t.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_nonblocking_quic_client_datagrams_and_migration() {
+        use {
+            solana_connection_cache::nonblocking::client_connection::ClientConnection,
+            solana_quic_client::nonblocking::quic_client::{QuicClient, QuicClientConnection},
+            solana_sdk::{quic::QUIC_MAX_UNSTAKED_CONCURRENT_STREAMS, signer::Signer},
+            std::collections::HashMap,
+        };
+        solana_logger::setup();
+        // Datagrams are only accepted from staked peers.
+        let client_keypair = Keypair::new();
+        let staked_nodes = Arc::new(RwLock::new(StakedNodes::new(
+            Arc::new(HashMap::from([(client_keypair.pubkey(), 100_000)])),
+            HashMap::default(), // overrides
+        )));
+        let (sender, receiver) = unbounded();
+        let (s, exit, keypair, ip) = server_args();
+        let (_, _, t) = solana_streamer::nonblocking::quic::spawn_server(
+            "quic_streamer_test",
+            s.try_clone().unwrap(),
+            &keypair,
+            ip,
+            sender,
+            exit.clone(),
+            1,
+            staked_nodes,
+            Arc::new(StakeWeightedQos::new(10, 10)),
+            Duration::from_secs(1), // wait_for_chunk_timeout
+            DEFAULT_TPU_COALESCE,
+            QuicServerOptions {
+                enable_datagrams: true,
+                ..QuicServerOptions::default()
+            },
+        )
+        .unwrap();
+
+        let (certificate, key) =
+            new_self_signed_tls_certificate(&client_keypair, IpAddr::V4(Ipv4Addr::LOCALHOST))
+                .unwrap();
+        let endpoint = Arc::new(QuicLazyInitializedEndpoint::new(
+            Arc::new(QuicClientCertificate { certificate, key }),
+            None,
+        ));
+        let client = QuicClientConnection::new_with_client(
+            Arc::new(QuicClient::new_with_datagrams(
+                endpoint.clone(),
+                s.local_addr().unwrap(),
+                QUIC_MAX_UNSTAKED_CONCURRENT_STREAMS,
+                true, // use_datagrams
+            )),
+            Arc::new(ConnectionCacheStats::default()),
+        );
+
+        // Small packets are sent as datagrams.
+        let num_expected_packets = 10;
+        for _ in 0..num_expected_packets {
+            client.send_data(&[0u8; 100]).await.unwrap();
+        }
+        nonblocking_check_packets(receiver.clone(), 100, num_expected_packets).await;
+        // Packets too large for a datagram are sent over streams.
+        client.send_data(&[0u8; PACKET_DATA_SIZE]).await.unwrap();
+        nonblocking_check_packets(receiver.clone(), PACKET_DATA_SIZE, 1).await;
+
+        // The connection migrates to the new socket of the endpoint.
+        endpoint
+            .rebind(UdpSocket::bind("127.0.0.1:0").unwrap())
+            .await
+            .unwrap();
+        client.send_data(&[0u8; PACKET_DATA_SIZE]).await.unwrap();
+        nonblocking_check_packets(receiver, PACKET_DATA_SIZE, 1).await;
+
+        drop(client);
+        exit.store(true, Ordering::Relaxed);
+        t.await.unwrap();
+    }
+
     #[test]
     fn test_quic_bi_direction() {
         /// This tests bi-directional quic communication. There are the following components
@@ -213,10 +290,10 @@ mod tests {
             request_recv_exit.clone(),
             1,
             staked_nodes.clone(),
-            10,
-            10,
+            Arc::new(StakeWeightedQos::new(10, 10)),
             DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
             DEFAULT_TPU_COALESCE,
+            QuicServerOptions::default(),
         )
         .unwrap();
 
@@ -237,10 +314,10 @@ mod tests {
             response_recv_exit.clone(),
             1,
             staked_nodes,
-            10,
-            10,
+            Arc::new(StakeWeightedQos::new(10, 10)),
             DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
             DEFAULT_TPU_COALESCE,
+            QuicServerOptions::default(),
         )
         .unwrap();
 
@@ -1,11 +1,12 @@
 use {
     crate::filter::RpcFilterType,
-    solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
+    solana_account_decoder::{UiAccountData, UiAccountEncoding, UiDataSliceConfig},
     solana_sdk::{
-        clock::{Epoch, Slot},
+        clock::{Epoch, Slot, UnixTimestamp},
         commitment_config::{CommitmentConfig, CommitmentLevel},
     },
     solana_transaction_status::{TransactionDetails, UiTransactionEncoding},
+    std::collections::HashMap,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -32,6 +33,36 @@ pub struct RpcSimulateTransactionAccountsConfig {
     pub addresses: Vec<String>,
 }
 
+/// Overrides the fields of an account for a simulation.  Fields which are not set keep the
+/// value of the simulated bank's account, or of an empty account if the bank has none.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAccountOverride {
+    pub lamports: Option<u64>,
+    pub owner: Option<String>, // base-58 encoded program id
+    pub executable: Option<bool>,
+    pub data: Option<UiAccountData>,
+}
+
+/// Overrides the fields of the `Clock` sysvar for a simulation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcClockOverride {
+    pub slot: Option<Slot>,
+    pub epoch_start_timestamp: Option<UnixTimestamp>,
+    pub epoch: Option<Epoch>,
+    pub leader_schedule_epoch: Option<Epoch>,
+    pub unix_timestamp: Option<UnixTimestamp>,
+}
+
+/// Overrides sysvars for a simulation.  Other sysvars can be overridden with their account data
+/// through `RpcSimulateTransactionConfig::account_overrides`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSysvarOverrides {
+    pub clock: Option<RpcClockOverride>,
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RpcSimulateTransactionConfig {
@@ -44,6 +75,43 @@ pub struct RpcSimulateTransactionConfig {
     pub encoding: Option<UiTransactionEncoding>,
     pub accounts: Option<RpcSimulateTransactionAccountsConfig>,
     pub min_context_slot: Option<Slot>,
+    #[serde(default)]
+    pub inner_instructions: bool,
+    /// Account overrides keyed by base-58 encoded address
+    pub account_overrides: Option<HashMap<String, RpcAccountOverride>>,
+    pub sysvar_overrides: Option<RpcSysvarOverrides>,
+    pub execution_trace: Option<RpcExecutionTraceEncoding>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSimulateBundleConfig {
+    #[serde(default)]
+    pub sig_verify: bool,
+    #[serde(default)]
+    pub replace_recent_blockhash: bool,
+    #[serde(flatten)]
+    pub commitment: Option<CommitmentConfig>,
+    pub encoding: Option<UiTransactionEncoding>,
+    pub accounts: Option<RpcSimulateTransactionAccountsConfig>,
+    pub min_context_slot: Option<Slot>,
+    #[serde(default)]
+    pub inner_instructions: bool,
+    /// Account overrides keyed by base-58 encoded address
+    pub account_overrides: Option<HashMap<String, RpcAccountOverride>>,
+    pub sysvar_overrides: Option<RpcSysvarOverrides>,
+    pub execution_trace: Option<RpcExecutionTraceEncoding>,
+    #[serde(default)]
+    pub stop_at_first_failure: bool,
+}
+
+/// Encoding of the SBF execution traces returned by a simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RpcExecutionTraceEncoding {
+    Json,
+    /// Base-64 encoded bincode serialization of the traces
+    Base64,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -1,9 +1,12 @@
 use {
-    crate::client_error,
+    crate::{client_error, config::RpcExecutionTraceEncoding},
     serde::{Deserialize, Deserializer, Serialize, Serializer},
     solana_account_decoder::{parse_token::UiTokenAmount, UiAccount},
     solana_sdk::{
         clock::{Epoch, Slot, UnixTimestamp},
+        execution_trace::{
+            FunctionStackComputeUnits, ProgramExecutionTrace, RegisterState, SyscallInvocation,
+        },
         fee_calculator::{FeeCalculator, FeeRateGovernor},
         hash::Hash,
         inflation::Inflation,
@@ -11,7 +14,7 @@ use {
     },
     solana_transaction_status::{
         ConfirmedTransactionStatusWithSignature, TransactionConfirmationStatus, UiConfirmedBlock,
-        UiTransactionReturnData,
+        UiInnerInstructions, UiTransactionReturnData,
     },
     std::{collections::HashMap, fmt, net::SocketAddr, str::FromStr},
     thiserror::Error,
@@ -423,6 +426,88 @@ pub struct RpcSimulateTransactionResult {
     pub accounts: Option<Vec<Option<UiAccount>>>,
     pub units_consumed: Option<u64>,
     pub return_data: Option<UiTransactionReturnData>,
+    pub inner_instructions: Option<Vec<UiInnerInstructions>>,
+    pub instruction_units_consumed: Option<Vec<u64>>,
+    pub execution_trace: Option<RpcExecutionTrace>,
+}
+
+/// SBF execution traces of a simulated transaction, one list per top-level instruction
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum RpcExecutionTrace {
+    Json(Vec<Vec<RpcProgramExecutionTrace>>),
+    Binary(String, RpcExecutionTraceEncoding),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcProgramExecutionTrace {
+    pub program_id: String,
+    pub stack_height: u32,
+    pub registers: Vec<RegisterState>,
+    pub syscalls: Vec<RpcSyscallInvocation>,
+    pub compute_unit_profile: Vec<RpcFunctionStackComputeUnits>,
+}
+
+impl From<ProgramExecutionTrace> for RpcProgramExecutionTrace {
+    fn from(trace: ProgramExecutionTrace) -> Self {
+        Self {
+            program_id: trace.program_id.to_string(),
+            stack_height: trace.stack_height,
+            registers: trace.registers,
+            syscalls: trace.syscalls.into_iter().map(Into::into).collect(),
+            compute_unit_profile: trace
+                .compute_unit_profile
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSyscallInvocation {
+    pub step: u64,
+    pub name: String,
+    pub compute_units: u64,
+}
+
+impl From<SyscallInvocation> for RpcSyscallInvocation {
+    fn from(
+        SyscallInvocation {
+            step,
+            name,
+            compute_units,
+        }: SyscallInvocation,
+    ) -> Self {
+        Self {
+            step,
+            name,
+            compute_units,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcFunctionStackComputeUnits {
+    pub stack: Vec<String>,
+    pub compute_units: u64,
+}
+
+impl From<FunctionStackComputeUnits> for RpcFunctionStackComputeUnits {
+    fn from(
+        FunctionStackComputeUnits {
+            stack,
+            compute_units,
+        }: FunctionStackComputeUnits,
+    ) -> Self {
+        Self {
+            stack,
+            compute_units,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
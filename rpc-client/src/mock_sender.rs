@@ -350,8 +350,30 @@ impl RpcSender for MockSender {
                     accounts: None,
                     units_consumed: None,
                     return_data: None,
+                    inner_instructions: None,
+                    instruction_units_consumed: None,
+                    execution_trace: None,
                 },
             })?,
+            "simulateBundle" => {
+                let transactions = params.as_array().unwrap()[0].as_array().unwrap();
+                serde_json::to_value(Response {
+                    context: RpcResponseContext { slot: 1, api_version: None },
+                    value: vec![
+                        RpcSimulateTransactionResult {
+                            err: None,
+                            logs: None,
+                            accounts: None,
+                            units_consumed: None,
+                            return_data: None,
+                            inner_instructions: None,
+                            instruction_units_consumed: None,
+                            execution_trace: None,
+                        };
+                        transactions.len()
+                    ],
+                })?
+            }
             "getMinimumBalanceForRentExemption" => json![20],
             "getVersion" => {
                 let version = Version::default();
@@ -1151,6 +1151,69 @@ impl RpcClient {
         )
     }
 
+    /// Simulate several transactions one after the other, as if they were
+    /// processed in the same block in that order.
+    ///
+    /// Each transaction sees the accounts written by the transactions before
+    /// it, so flows that span several transactions can be previewed before
+    /// any of them is sent. None of the transactions are committed.
+    ///
+    /// The returned [`RpcSimulateTransactionResult`]s are in the order of the
+    /// transactions. If the [`stop_at_first_failure`] field of
+    /// [`RpcSimulateBundleConfig`] is set, the transactions after the first
+    /// failed one are not simulated and have no result.
+    ///
+    /// [`stop_at_first_failure`]: solana_rpc_client_api::config::RpcSimulateBundleConfig::stop_at_first_failure
+    ///
+    /// # RPC Reference
+    ///
+    /// This method is built on the [`simulateBundle`] RPC method.
+    ///
+    /// [`simulateBundle`]: https://docs.solana.com/developing/clients/jsonrpc-api#simulatebundle
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use solana_rpc_client_api::{
+    /// #     client_error::Error,
+    /// #     config::RpcSimulateBundleConfig,
+    /// # };
+    /// # use solana_rpc_client::rpc_client::RpcClient;
+    /// # use solana_sdk::{
+    /// #     signature::Signer,
+    /// #     signer::keypair::Keypair,
+    /// #     system_transaction,
+    /// # };
+    /// # let rpc_client = RpcClient::new_mock("succeeds".to_string());
+    /// // Alice funds Bob, who then pays Carol
+    /// # let alice = Keypair::new();
+    /// # let bob = Keypair::new();
+    /// # let carol = Keypair::new();
+    /// # let lamports = 50;
+    /// let latest_blockhash = rpc_client.get_latest_blockhash()?;
+    /// let fund_bob =
+    ///     system_transaction::transfer(&alice, &bob.pubkey(), 2 * lamports, latest_blockhash);
+    /// let pay_carol =
+    ///     system_transaction::transfer(&bob, &carol.pubkey(), lamports, latest_blockhash);
+    /// let config = RpcSimulateBundleConfig {
+    ///     stop_at_first_failure: true,
+    ///     .. RpcSimulateBundleConfig::default()
+    /// };
+    /// let results = rpc_client.simulate_bundle_with_config(
+    ///     &[fund_bob, pay_carol],
+    ///     config,
+    /// )?;
+    /// assert!(results.value.iter().all(|result| result.err.is_none()));
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn simulate_bundle_with_config(
+        &self,
+        transactions: &[impl SerializableTransaction],
+        config: RpcSimulateBundleConfig,
+    ) -> RpcResult<Vec<RpcSimulateTransactionResult>> {
+        self.invoke((self.rpc_client.as_ref()).simulate_bundle_with_config(transactions, config))
+    }
+
     /// Returns the highest slot information that the node has snapshots for.
     ///
     /// This will find the highest full snapshot slot, and the highest incremental snapshot slot
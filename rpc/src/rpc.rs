@@ -3,6 +3,7 @@ use {
     crate::{
         max_slots::MaxSlots, optimistically_confirmed_bank_tracker::OptimisticallyConfirmedBank,
         parsed_token_accounts::*, rpc_cache::LargestAccountsCache, rpc_health::*,
+        transaction_status_service::map_inner_instructions,
     },
     base64::{prelude::BASE64_STANDARD, Engine},
     bincode::{config::Options, serialize},
@@ -11,9 +12,10 @@ use {
     jsonrpc_derive::rpc,
     solana_account_decoder::{
         parse_token::{is_known_spl_token_id, token_amount_to_ui_amount, UiTokenAmount},
-        UiAccount, UiAccountEncoding, UiDataSliceConfig, MAX_BASE58_BYTES,
+        UiAccount, UiAccountData, UiAccountEncoding, UiDataSliceConfig, MAX_BASE58_BYTES,
     },
     solana_accounts_db::{
+        account_overrides::AccountOverrides,
         accounts::AccountAddressFilter,
         accounts_index::{AccountIndex, AccountSecondaryIndexes, IndexKey, ScanConfig},
         inline_spl_token::{SPL_TOKEN_ACCOUNT_MINT_OFFSET, SPL_TOKEN_ACCOUNT_OWNER_OFFSET},
@@ -42,7 +44,8 @@ use {
             MAX_GET_CONFIRMED_BLOCKS_RANGE, MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT,
             MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS_SLOT_RANGE, MAX_GET_PROGRAM_ACCOUNT_FILTERS,
             MAX_GET_SIGNATURE_STATUSES_QUERY_ITEMS, MAX_GET_SLOT_LEADERS, MAX_MULTIPLE_ACCOUNTS,
-            MAX_RPC_VOTE_ACCOUNT_INFO_EPOCH_CREDITS_HISTORY, NUM_LARGEST_ACCOUNTS,
+            MAX_RPC_VOTE_ACCOUNT_INFO_EPOCH_CREDITS_HISTORY, MAX_SIMULATE_BUNDLE_TRANSACTIONS,
+            NUM_LARGEST_ACCOUNTS,
         },
         response::{Response as RpcResponse, *},
     },
@@ -57,23 +60,29 @@ use {
         snapshot_utils,
     },
     solana_sdk::{
-        account::{AccountSharedData, ReadableAccount},
+        account::{
+            create_account_shared_data_with_fields, AccountSharedData, ReadableAccount,
+            WritableAccount,
+        },
         account_utils::StateMut,
-        clock::{Slot, UnixTimestamp, MAX_RECENT_BLOCKHASHES},
+        bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable,
+        clock::{Clock, Slot, UnixTimestamp, MAX_RECENT_BLOCKHASHES},
         commitment_config::{CommitmentConfig, CommitmentLevel},
         epoch_info::EpochInfo,
         epoch_schedule::EpochSchedule,
+        execution_trace::ExecutionTraceList,
         exit::Exit,
         feature_set,
         fee_calculator::FeeCalculator,
         hash::Hash,
+        loader_v4,
         message::SanitizedMessage,
         pubkey::{Pubkey, PUBKEY_BYTES},
         signature::{Keypair, Signature, Signer},
         stake::state::{StakeActivationStatus, StakeStateV2},
         stake_history::StakeHistory,
         system_instruction,
-        sysvar::stake_history,
+        sysvar::{self, stake_history},
         transaction::{
             self, AddressLoader, MessageHash, SanitizedTransaction, TransactionError,
             VersionedTransaction, MAX_TX_ACCOUNT_LOCKS,
@@ -90,7 +99,7 @@ use {
         BlockEncodingOptions, ConfirmedBlock, ConfirmedTransactionStatusWithSignature,
         ConfirmedTransactionWithStatusMeta, EncodedConfirmedTransactionWithStatusMeta, Reward,
         RewardType, TransactionBinaryEncoding, TransactionConfirmationStatus, TransactionStatus,
-        UiConfirmedBlock, UiTransactionEncoding,
+        UiConfirmedBlock, UiInnerInstructions, UiTransactionEncoding,
     },
     solana_vote_program::vote_state::{VoteState, MAX_LOCKOUT_HISTORY},
     spl_token_2022::{
@@ -103,6 +112,7 @@ use {
         cmp::{max, min},
         collections::{HashMap, HashSet},
         convert::TryFrom,
+        io::Read,
         net::SocketAddr,
         str::FromStr,
         sync::{
@@ -117,6 +127,10 @@ type RpcCustomResult<T> = std::result::Result<T, RpcCustomError>;
 
 pub const MAX_REQUEST_BODY_SIZE: usize = 50 * (1 << 10); // 50kB
 pub const PERFORMANCE_SAMPLES_LIMIT: usize = 720;
+// Maximum total size of the data of the account overrides of a simulation request, once decoded
+const MAX_ACCOUNT_OVERRIDES_DATA_LENGTH: u64 = 2 * system_instruction::MAX_PERMITTED_DATA_LENGTH;
+// Maximum number of steps returned in the execution traces of a simulated transaction
+const MAX_EXECUTION_TRACE_STEPS: usize = 64 * 1024;
 
 fn new_response<T>(bank: &Bank, value: T) -> RpcResponse<T> {
     RpcResponse {
@@ -152,6 +166,9 @@ pub struct JsonRpcConfig {
     pub max_request_body_size: Option<usize>,
     /// Disable the health check, used for tests and TestValidator
     pub disable_health_check: bool,
+    /// Allow simulations to override the accounts of programs, which are then compiled for the
+    /// simulation
+    pub enable_simulation_program_overrides: bool,
 }
 
 impl JsonRpcConfig {
@@ -2210,6 +2227,239 @@ fn verify_transaction(
     Ok(())
 }
 
+// The size of base58 and base64 encoded data is limited by the request size, but zstd
+// compressed data is only decompressed up to the maximum size of an account, or up to
+// `remaining_len`, what is left of the total size of the data overrides of the request
+fn decode_data_override(
+    pubkey: &Pubkey,
+    data: UiAccountData,
+    remaining_len: u64,
+) -> Result<Vec<u8>> {
+    let max_len = system_instruction::MAX_PERMITTED_DATA_LENGTH.min(remaining_len);
+    let invalid_data =
+        || Error::invalid_params(format!("Invalid data override for account {pubkey}"));
+    let data = match data {
+        UiAccountData::Binary(blob, UiAccountEncoding::Base64Zstd) => {
+            let zstd_data = BASE64_STANDARD.decode(blob).map_err(|_| invalid_data())?;
+            let mut data = vec![];
+            zstd::stream::read::Decoder::new(zstd_data.as_slice())
+                .and_then(|decoder| {
+                    decoder
+                        .take(max_len.saturating_add(1))
+                        .read_to_end(&mut data)
+                })
+                .map_err(|_| invalid_data())?;
+            data
+        }
+        data => data.decode().ok_or_else(invalid_data)?,
+    };
+    if data.len() as u64 > system_instruction::MAX_PERMITTED_DATA_LENGTH {
+        return Err(Error::invalid_params(format!(
+            "Data override for account {pubkey} is too large; max {} bytes",
+            system_instruction::MAX_PERMITTED_DATA_LENGTH
+        )));
+    }
+    if data.len() as u64 > remaining_len {
+        return Err(Error::invalid_params(format!(
+            "Data overrides are too large; max {MAX_ACCOUNT_OVERRIDES_DATA_LENGTH} bytes in total"
+        )));
+    }
+    Ok(data)
+}
+
+/// Builds the account overrides of a simulation.  The fields which are not overridden keep the
+/// values of the bank's accounts.  Accounts owned by a loader can only be overridden if
+/// `enable_program_overrides` is set.
+fn account_overrides_for_simulation(
+    bank: &Bank,
+    account_overrides: Option<HashMap<String, RpcAccountOverride>>,
+    sysvar_overrides: Option<RpcSysvarOverrides>,
+    enable_program_overrides: bool,
+) -> Result<AccountOverrides> {
+    let account_overrides = account_overrides.unwrap_or_default();
+    if account_overrides.len() > MAX_MULTIPLE_ACCOUNTS {
+        return Err(Error::invalid_params(format!(
+            "Too many account overrides provided; max {MAX_MULTIPLE_ACCOUNTS}"
+        )));
+    }
+
+    let mut simulation_account_overrides = AccountOverrides::default();
+    let mut remaining_data_len = MAX_ACCOUNT_OVERRIDES_DATA_LENGTH;
+    for (address, account_override) in account_overrides {
+        let pubkey = verify_pubkey(&address)?;
+        let RpcAccountOverride {
+            lamports,
+            owner,
+            executable,
+            data,
+        } = account_override;
+        let mut account = bank.get_account(&pubkey).unwrap_or_default();
+        if let Some(lamports) = lamports {
+            account.set_lamports(lamports);
+        }
+        if let Some(owner) = owner {
+            account.set_owner(verify_pubkey(&owner)?);
+        }
+        if let Some(executable) = executable {
+            account.set_executable(executable);
+        }
+        if let Some(data) = data {
+            let data = decode_data_override(&pubkey, data, remaining_data_len)?;
+            remaining_data_len = remaining_data_len.saturating_sub(data.len() as u64);
+            account.set_data_from_slice(&data);
+        }
+        let is_program_owned = [
+            bpf_loader_upgradeable::id(),
+            bpf_loader::id(),
+            bpf_loader_deprecated::id(),
+            loader_v4::id(),
+        ]
+        .contains(account.owner());
+        if is_program_owned && !enable_program_overrides {
+            return Err(Error::invalid_params(format!(
+                "Account {pubkey} is owned by a loader; program overrides are not enabled"
+            )));
+        }
+        simulation_account_overrides.set_account(&pubkey, Some(account));
+    }
+
+    if let Some(RpcClockOverride {
+        slot,
+        epoch_start_timestamp,
+        epoch,
+        leader_schedule_epoch,
+        unix_timestamp,
+    }) = sysvar_overrides.and_then(|sysvar_overrides| sysvar_overrides.clock)
+    {
+        let clock = bank.clock();
+        let clock = Clock {
+            slot: slot.unwrap_or(clock.slot),
+            epoch_start_timestamp: epoch_start_timestamp.unwrap_or(clock.epoch_start_timestamp),
+            epoch: epoch.unwrap_or(clock.epoch),
+            leader_schedule_epoch: leader_schedule_epoch.unwrap_or(clock.leader_schedule_epoch),
+            unix_timestamp: unix_timestamp.unwrap_or(clock.unix_timestamp),
+        };
+        let clock_account = bank.get_account(&sysvar::clock::id()).unwrap_or_default();
+        simulation_account_overrides.set_account(
+            &sysvar::clock::id(),
+            Some(create_account_shared_data_with_fields(
+                &clock,
+                (clock_account.lamports(), clock_account.rent_epoch()),
+            )),
+        );
+    }
+
+    Ok(simulation_account_overrides)
+}
+
+/// Converts the result of a simulation, with the post-simulation state of the accounts listed in
+/// `config_accounts`.  Accounts which the transaction does not load are returned as `None`, as
+/// are all of them when the transaction fails.  Execution traces are only returned in the
+/// requested encoding when the bank recorded them.
+fn rpc_simulation_result(
+    simulation_result: TransactionSimulationResult,
+    config_accounts: Option<&RpcSimulateTransactionAccountsConfig>,
+    enable_inner_instructions: bool,
+    execution_trace_encoding: Option<RpcExecutionTraceEncoding>,
+) -> Result<RpcSimulateTransactionResult> {
+    let TransactionSimulationResult {
+        result,
+        logs,
+        post_simulation_accounts,
+        units_consumed,
+        return_data,
+        inner_instructions,
+        instruction_units_consumed,
+        execution_traces,
+    } = simulation_result;
+
+    let accounts = if let Some(config_accounts) = config_accounts {
+        let accounts_encoding = config_accounts
+            .encoding
+            .unwrap_or(UiAccountEncoding::Base64);
+
+        if accounts_encoding == UiAccountEncoding::Binary
+            || accounts_encoding == UiAccountEncoding::Base58
+        {
+            return Err(Error::invalid_params("base58 encoding not supported"));
+        }
+
+        if result.is_err() {
+            Some(vec![None; config_accounts.addresses.len()])
+        } else {
+            Some(
+                config_accounts
+                    .addresses
+                    .iter()
+                    .map(|address_str| {
+                        let address = verify_pubkey(address_str)?;
+                        post_simulation_accounts
+                            .iter()
+                            .find(|(key, _account)| key == &address)
+                            .map(|(pubkey, account)| {
+                                encode_account(account, pubkey, accounts_encoding, None)
+                            })
+                            .transpose()
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            )
+        }
+    } else {
+        None
+    };
+
+    let execution_trace = execution_trace_encoding
+        .zip(execution_traces)
+        .map(|(encoding, mut execution_traces)| {
+            truncate_execution_traces(&mut execution_traces, MAX_EXECUTION_TRACE_STEPS);
+            (encoding, execution_traces)
+        })
+        .map(|(encoding, execution_traces)| match encoding {
+            RpcExecutionTraceEncoding::Json => Ok(RpcExecutionTrace::Json(
+                execution_traces
+                    .into_iter()
+                    .map(|traces| traces.into_iter().map(Into::into).collect())
+                    .collect(),
+            )),
+            RpcExecutionTraceEncoding::Base64 => serialize(&execution_traces)
+                .map(|data| RpcExecutionTrace::Binary(BASE64_STANDARD.encode(data), encoding))
+                .map_err(|_| Error::internal_error()),
+        })
+        .transpose()?;
+
+    Ok(RpcSimulateTransactionResult {
+        err: result.err(),
+        logs: Some(logs),
+        accounts,
+        units_consumed: Some(units_consumed),
+        return_data: return_data.map(|return_data| return_data.into()),
+        inner_instructions: inner_instructions
+            .filter(|_| enable_inner_instructions)
+            .map(|inner_instructions| {
+                map_inner_instructions(inner_instructions)
+                    .into_iter()
+                    .map(UiInnerInstructions::from)
+                    .collect()
+            }),
+        instruction_units_consumed,
+        execution_trace,
+    })
+}
+
+/// Keeps the first `max_steps` steps of the execution traces, in the order the programs were
+/// invoked, along with the syscalls made during those steps
+fn truncate_execution_traces(execution_traces: &mut ExecutionTraceList, max_steps: usize) {
+    let mut remaining_steps = max_steps;
+    for execution_trace in execution_traces.iter_mut().flatten() {
+        execution_trace.registers.truncate(remaining_steps);
+        let num_steps = execution_trace.registers.len();
+        execution_trace
+            .syscalls
+            .retain(|syscall| (syscall.step as usize) < num_steps);
+        remaining_steps = remaining_steps.saturating_sub(num_steps);
+    }
+}
+
 fn verify_filter(input: &RpcFilterType) -> Result<()> {
     input
         .verify()
@@ -3327,6 +3577,14 @@ pub mod rpc_full {
             config: Option<RpcSimulateTransactionConfig>,
         ) -> Result<RpcResponse<RpcSimulateTransactionResult>>;
 
+        #[rpc(meta, name = "simulateBundle")]
+        fn simulate_bundle(
+            &self,
+            meta: Self::Metadata,
+            data: Vec<String>,
+            config: Option<RpcSimulateBundleConfig>,
+        ) -> Result<RpcResponse<Vec<RpcSimulateTransactionResult>>>;
+
         #[rpc(meta, name = "minimumLedgerSlot")]
         fn minimum_ledger_slot(&self, meta: Self::Metadata) -> Result<Slot>;
 
@@ -3675,6 +3933,9 @@ pub mod rpc_full {
                     post_simulation_accounts: _,
                     units_consumed,
                     return_data,
+                    inner_instructions: _,
+                    instruction_units_consumed,
+                    execution_traces: _,
                 } = preflight_bank.simulate_transaction(transaction)
                 {
                     match err {
@@ -3693,6 +3954,9 @@ pub mod rpc_full {
                             accounts: None,
                             units_consumed: Some(units_consumed),
                             return_data: return_data.map(|return_data| return_data.into()),
+                            inner_instructions: None,
+                            instruction_units_consumed,
+                            execution_trace: None,
                         },
                     }
                     .into());
@@ -3723,6 +3987,10 @@ pub mod rpc_full {
                 encoding,
                 accounts: config_accounts,
                 min_context_slot,
+                inner_instructions: enable_inner_instructions,
+                account_overrides,
+                sysvar_overrides,
+                execution_trace: execution_trace_encoding,
             } = config.unwrap_or_default();
             let tx_encoding = encoding.unwrap_or(UiTransactionEncoding::Base58);
             let binary_encoding = tx_encoding.into_binary_encoding().ok_or_else(|| {
@@ -3748,70 +4016,152 @@ pub mod rpc_full {
                     .set_recent_blockhash(bank.last_blockhash());
             }
 
-            let transaction = sanitize_transaction(unsanitized_tx, bank)?;
+            let account_overrides = account_overrides_for_simulation(
+                bank,
+                account_overrides,
+                sysvar_overrides,
+                meta.config.enable_simulation_program_overrides,
+            )?;
+            let transaction = sanitize_transaction(
+                unsanitized_tx,
+                bank.address_loader_with_account_overrides(&account_overrides),
+            )?;
             if sig_verify {
                 verify_transaction(&transaction, &bank.feature_set)?;
             }
             let number_of_accounts = transaction.message().account_keys().len();
 
-            let TransactionSimulationResult {
-                result,
-                logs,
-                post_simulation_accounts,
-                units_consumed,
-                return_data,
-            } = bank.simulate_transaction(transaction);
-
-            let accounts = if let Some(config_accounts) = config_accounts {
-                let accounts_encoding = config_accounts
-                    .encoding
-                    .unwrap_or(UiAccountEncoding::Base64);
-
-                if accounts_encoding == UiAccountEncoding::Binary
-                    || accounts_encoding == UiAccountEncoding::Base58
-                {
-                    return Err(Error::invalid_params("base58 encoding not supported"));
-                }
-
+            if let Some(config_accounts) = &config_accounts {
                 if config_accounts.addresses.len() > number_of_accounts {
                     return Err(Error::invalid_params(format!(
                         "Too many accounts provided; max {number_of_accounts}"
                     )));
                 }
+            }
 
-                if result.is_err() {
-                    Some(vec![None; config_accounts.addresses.len()])
-                } else {
-                    Some(
-                        config_accounts
-                            .addresses
-                            .iter()
-                            .map(|address_str| {
-                                let address = verify_pubkey(address_str)?;
-                                post_simulation_accounts
-                                    .iter()
-                                    .find(|(key, _account)| key == &address)
-                                    .map(|(pubkey, account)| {
-                                        encode_account(account, pubkey, accounts_encoding, None)
-                                    })
-                                    .transpose()
-                            })
-                            .collect::<Result<Vec<_>>>()?,
-                    )
+            let simulation_result = bank.simulate_transaction_with_account_overrides(
+                transaction,
+                &account_overrides,
+                enable_inner_instructions,
+                execution_trace_encoding.is_some(),
+            );
+            Ok(new_response(
+                bank,
+                rpc_simulation_result(
+                    simulation_result,
+                    config_accounts.as_ref(),
+                    enable_inner_instructions,
+                    execution_trace_encoding,
+                )?,
+            ))
+        }
+
+        fn simulate_bundle(
+            &self,
+            meta: Self::Metadata,
+            data: Vec<String>,
+            config: Option<RpcSimulateBundleConfig>,
+        ) -> Result<RpcResponse<Vec<RpcSimulateTransactionResult>>> {
+            debug!(
+                "simulate_bundle rpc request received: {} transactions",
+                data.len()
+            );
+            if data.is_empty() {
+                return Err(Error::invalid_params("No transactions provided"));
+            }
+            if data.len() > MAX_SIMULATE_BUNDLE_TRANSACTIONS {
+                return Err(Error::invalid_params(format!(
+                    "Too many transactions provided; max {MAX_SIMULATE_BUNDLE_TRANSACTIONS}"
+                )));
+            }
+            let RpcSimulateBundleConfig {
+                sig_verify,
+                replace_recent_blockhash,
+                commitment,
+                encoding,
+                accounts: config_accounts,
+                min_context_slot,
+                inner_instructions: enable_inner_instructions,
+                account_overrides,
+                sysvar_overrides,
+                execution_trace: execution_trace_encoding,
+                stop_at_first_failure,
+            } = config.unwrap_or_default();
+            if replace_recent_blockhash && sig_verify {
+                return Err(Error::invalid_params(
+                    "sigVerify may not be used with replaceRecentBlockhash",
+                ));
+            }
+            let tx_encoding = encoding.unwrap_or(UiTransactionEncoding::Base58);
+            let binary_encoding = tx_encoding.into_binary_encoding().ok_or_else(|| {
+                Error::invalid_params(format!(
+                    "unsupported encoding: {tx_encoding}. Supported encodings: base58, base64"
+                ))
+            })?;
+            let unsanitized_txs = data
+                .into_iter()
+                .map(|data| {
+                    decode_and_deserialize::<VersionedTransaction>(data, binary_encoding)
+                        .map(|(_, unsanitized_tx)| unsanitized_tx)
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let bank = &*meta.get_bank_with_config(RpcContextConfig {
+                commitment,
+                min_context_slot,
+            })?;
+            let account_overrides = account_overrides_for_simulation(
+                bank,
+                account_overrides,
+                sysvar_overrides,
+                meta.config.enable_simulation_program_overrides,
+            )?;
+            let transactions = unsanitized_txs
+                .into_iter()
+                .map(|mut unsanitized_tx| {
+                    if replace_recent_blockhash {
+                        unsanitized_tx
+                            .message
+                            .set_recent_blockhash(bank.last_blockhash());
+                    }
+                    let transaction = sanitize_transaction(
+                        unsanitized_tx,
+                        bank.address_loader_with_account_overrides(&account_overrides),
+                    )?;
+                    if sig_verify {
+                        verify_transaction(&transaction, &bank.feature_set)?;
+                    }
+                    Ok(transaction)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if let Some(config_accounts) = &config_accounts {
+                if config_accounts.addresses.len() > MAX_MULTIPLE_ACCOUNTS {
+                    return Err(Error::invalid_params(format!(
+                        "Too many accounts provided; max {MAX_MULTIPLE_ACCOUNTS}"
+                    )));
                 }
-            } else {
-                None
-            };
+            }
 
+            let simulation_results = bank.simulate_bundle(
+                transactions,
+                &account_overrides,
+                stop_at_first_failure,
+                enable_inner_instructions,
+                execution_trace_encoding.is_some(),
+            );
             Ok(new_response(
                 bank,
-                RpcSimulateTransactionResult {
-                    err: result.err(),
-                    logs: Some(logs),
-                    accounts,
-                    units_consumed: Some(units_consumed),
-                    return_data: return_data.map(|return_data| return_data.into()),
-                },
+                simulation_results
+                    .into_iter()
+                    .map(|simulation_result| {
+                        rpc_simulation_result(
+                            simulation_result,
+                            config_accounts.as_ref(),
+                            enable_inner_instructions,
+                            execution_trace_encoding,
+                        )
+                    })
+                    .collect::<Result<Vec<_>>>()?,
             ))
         }
 
@@ -5915,6 +6265,9 @@ pub mod tests {
                     ],
                     "returnData":null,
                     "unitsConsumed":150,
+                    "innerInstructions":null,
+                    "instructionUnitsConsumed":[150],
+                    "executionTrace":null,
                 }
             },
             "id": 1,
@@ -5999,6 +6352,9 @@ pub mod tests {
                     ],
                     "returnData":null,
                     "unitsConsumed":150,
+                    "innerInstructions":null,
+                    "instructionUnitsConsumed":[150],
+                    "executionTrace":null,
                 }
             },
             "id": 1,
@@ -6027,6 +6383,9 @@ pub mod tests {
                     ],
                     "returnData":null,
                     "unitsConsumed":150,
+                    "innerInstructions":null,
+                    "instructionUnitsConsumed":[150],
+                    "executionTrace":null,
                 }
             },
             "id": 1,
@@ -6076,6 +6435,9 @@ pub mod tests {
                     "logs":[],
                     "returnData":null,
                     "unitsConsumed":0,
+                    "innerInstructions":null,
+                    "instructionUnitsConsumed":null,
+                    "executionTrace":null,
                 }
             },
             "id":1
@@ -6105,6 +6467,9 @@ pub mod tests {
                     ],
                     "returnData":null,
                     "unitsConsumed":150,
+                    "innerInstructions":null,
+                    "instructionUnitsConsumed":[150],
+                    "executionTrace":null,
                 }
             },
             "id": 1,
@@ -6117,6 +6482,254 @@ pub mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_rpc_simulate_transaction_with_overrides() {
+        let rpc = RpcHandler::start();
+        let bank = rpc.working_bank();
+        let rent_exempt_amount = bank.get_minimum_balance_for_rent_exemption(0);
+        let payer = Keypair::new();
+        let payer_lamports = 10 * rent_exempt_amount;
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let tx = system_transaction::transfer(
+            &payer,
+            &bob_pubkey,
+            rent_exempt_amount,
+            bank.confirmed_last_blockhash(),
+        );
+        let tx_serialized_encoded = BASE64_STANDARD.encode(serialize(&tx).unwrap());
+
+        // Simulation bank must be frozen
+        bank.freeze();
+
+        // The payer only exists in the overrides
+        let request = create_test_request(
+            "simulateTransaction",
+            Some(json!([tx_serialized_encoded, {"encoding": "base64"}])),
+        );
+        let result: RpcResponse<RpcSimulateTransactionResult> =
+            parse_success_result(rpc.handle_request_sync(request));
+        assert_eq!(result.value.err, Some(TransactionError::AccountNotFound));
+
+        let request = create_test_request(
+            "simulateTransaction",
+            Some(json!([
+                tx_serialized_encoded,
+                {
+                    "encoding": "base64",
+                    "innerInstructions": true,
+                    "accountOverrides": {
+                        payer.pubkey().to_string(): {"lamports": payer_lamports},
+                    },
+                    "sysvarOverrides": {"clock": {"unixTimestamp": 1_700_000_000}},
+                    "accounts": {
+                        "encoding": "base64",
+                        "addresses": [bob_pubkey.to_string()],
+                    },
+                },
+            ])),
+        );
+        let result: RpcResponse<RpcSimulateTransactionResult> =
+            parse_success_result(rpc.handle_request_sync(request));
+        assert_eq!(result.value.err, None);
+        assert_eq!(result.value.inner_instructions, Some(vec![]));
+        assert_eq!(result.value.instruction_units_consumed, Some(vec![150]));
+        let bob_account = result.value.accounts.unwrap().pop().unwrap().unwrap();
+        assert_eq!(bob_account.lamports, rent_exempt_amount);
+        assert_eq!(bank.get_account(&payer.pubkey()), None);
+
+        // Overrides must be valid accounts
+        for account_overrides in [
+            json!({"notAPubkey": {"lamports": payer_lamports}}),
+            json!({payer.pubkey().to_string(): {"owner": "notAPubkey"}}),
+            json!({payer.pubkey().to_string(): {"data": ["!", "base64"]}}),
+            // Program overrides are not enabled
+            json!({payer.pubkey().to_string(): {"owner": bpf_loader::id().to_string()}}),
+        ] {
+            let request = create_test_request(
+                "simulateTransaction",
+                Some(json!([
+                    tx_serialized_encoded,
+                    {"encoding": "base64", "accountOverrides": account_overrides},
+                ])),
+            );
+            let (code, _) = parse_failure_response(rpc.handle_request_sync(request));
+            assert_eq!(code, ErrorCode::InvalidParams.code());
+        }
+    }
+
+    #[test]
+    fn test_truncate_execution_traces() {
+        use solana_sdk::execution_trace::{ProgramExecutionTrace, SyscallInvocation};
+        let new_execution_trace = |num_steps: usize| ProgramExecutionTrace {
+            registers: vec![[0; 12]; num_steps],
+            syscalls: (0..num_steps as u64)
+                .map(|step| SyscallInvocation {
+                    step,
+                    ..SyscallInvocation::default()
+                })
+                .collect(),
+            ..ProgramExecutionTrace::default()
+        };
+        let mut execution_traces = vec![
+            vec![new_execution_trace(3), new_execution_trace(4)],
+            vec![],
+            vec![new_execution_trace(5)],
+        ];
+        truncate_execution_traces(&mut execution_traces, 5);
+        let num_steps: Vec<Vec<_>> = execution_traces
+            .iter()
+            .map(|traces| {
+                traces
+                    .iter()
+                    .map(|trace| (trace.registers.len(), trace.syscalls.len()))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(num_steps, vec![vec![(3, 3), (2, 2)], vec![], vec![(0, 0)]]);
+    }
+
+    #[test]
+    fn test_decode_data_override() {
+        let pubkey = Pubkey::new_unique();
+        let max_len = system_instruction::MAX_PERMITTED_DATA_LENGTH as usize;
+        let zstd_data = |data: &[u8]| {
+            UiAccountData::Binary(
+                BASE64_STANDARD.encode(zstd::encode_all(data, 0).unwrap()),
+                UiAccountEncoding::Base64Zstd,
+            )
+        };
+
+        let data = vec![7; max_len];
+        assert_eq!(
+            decode_data_override(&pubkey, zstd_data(&data), MAX_ACCOUNT_OVERRIDES_DATA_LENGTH)
+                .unwrap(),
+            data
+        );
+        let data = vec![7; 16];
+        assert_eq!(
+            decode_data_override(
+                &pubkey,
+                UiAccountData::Binary(BASE64_STANDARD.encode(&data), UiAccountEncoding::Base64),
+                MAX_ACCOUNT_OVERRIDES_DATA_LENGTH,
+            )
+            .unwrap(),
+            data
+        );
+
+        // Decompression stops once the data exceeds the maximum account size, or what is left of
+        // the total size of the data overrides
+        assert!(decode_data_override(
+            &pubkey,
+            zstd_data(&vec![7; 2 * max_len]),
+            MAX_ACCOUNT_OVERRIDES_DATA_LENGTH
+        )
+        .is_err());
+        assert!(decode_data_override(&pubkey, zstd_data(&vec![7; 17]), 16).is_err());
+        assert!(decode_data_override(
+            &pubkey,
+            UiAccountData::Binary(BASE64_STANDARD.encode(&data), UiAccountEncoding::Base64),
+            15,
+        )
+        .is_err());
+        assert!(decode_data_override(
+            &pubkey,
+            UiAccountData::Binary(
+                BASE64_STANDARD.encode([1, 2, 3]),
+                UiAccountEncoding::Base64Zstd
+            ),
+            MAX_ACCOUNT_OVERRIDES_DATA_LENGTH,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_rpc_simulate_bundle() {
+        let rpc = RpcHandler::start();
+        let bank = rpc.working_bank();
+        let rent_exempt_amount = bank.get_minimum_balance_for_rent_exemption(0);
+        let recent_blockhash = bank.confirmed_last_blockhash();
+        let payer = Keypair::new();
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let fund_payer = system_transaction::transfer(
+            &rpc.mint_keypair,
+            &payer.pubkey(),
+            10 * rent_exempt_amount,
+            recent_blockhash,
+        );
+        let pay_bob =
+            system_transaction::transfer(&payer, &bob_pubkey, rent_exempt_amount, recent_blockhash);
+        let overdraw_payer = system_transaction::transfer(
+            &payer,
+            &bob_pubkey,
+            20 * rent_exempt_amount,
+            recent_blockhash,
+        );
+        let [fund_payer, pay_bob, overdraw_payer] = [fund_payer, pay_bob, overdraw_payer]
+            .map(|tx| BASE64_STANDARD.encode(serialize(&tx).unwrap()));
+
+        // Simulation bank must be frozen
+        bank.freeze();
+
+        // The second transfer is paid by the account that the first one creates
+        let request = create_test_request(
+            "simulateBundle",
+            Some(json!([
+                [fund_payer, pay_bob],
+                {
+                    "encoding": "base64",
+                    "accounts": {
+                        "encoding": "base64",
+                        "addresses": [bob_pubkey.to_string()],
+                    },
+                },
+            ])),
+        );
+        let result: RpcResponse<Vec<RpcSimulateTransactionResult>> =
+            parse_success_result(rpc.handle_request_sync(request));
+        assert_eq!(result.value.len(), 2);
+        assert_eq!(result.value[0].err, None);
+        assert_eq!(result.value[0].accounts, Some(vec![None]));
+        assert_eq!(result.value[1].err, None);
+        assert_eq!(result.value[1].units_consumed, Some(150));
+        let bob_account = result.value[1].accounts.as_ref().unwrap()[0]
+            .as_ref()
+            .unwrap();
+        assert_eq!(bob_account.lamports, rent_exempt_amount);
+        assert_eq!(bank.get_account(&payer.pubkey()), None);
+
+        // Stops at the first failed transaction when asked to
+        for (stop_at_first_failure, expected_len) in [(true, 2), (false, 3)] {
+            let request = create_test_request(
+                "simulateBundle",
+                Some(json!([
+                    [fund_payer, overdraw_payer, pay_bob],
+                    {"encoding": "base64", "stopAtFirstFailure": stop_at_first_failure},
+                ])),
+            );
+            let result: RpcResponse<Vec<RpcSimulateTransactionResult>> =
+                parse_success_result(rpc.handle_request_sync(request));
+            assert_eq!(result.value.len(), expected_len);
+            assert_eq!(result.value[0].err, None);
+            assert_eq!(
+                result.value[1].err,
+                Some(TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(1)
+                ))
+            );
+        }
+
+        // Bundles must not be empty or too large
+        for transactions in [vec![], vec![pay_bob; MAX_SIMULATE_BUNDLE_TRANSACTIONS + 1]] {
+            let request = create_test_request(
+                "simulateBundle",
+                Some(json!([transactions, {"encoding": "base64"}])),
+            );
+            let (code, _) = parse_failure_response(rpc.handle_request_sync(request));
+            assert_eq!(code, ErrorCode::InvalidParams.code());
+        }
+    }
+
     #[test]
     #[should_panic(expected = "simulation bank must be frozen")]
     fn test_rpc_simulate_transaction_panic_on_unfrozen_bank() {
@@ -6479,7 +7092,7 @@ pub mod tests {
         assert_eq!(
             res,
             Some(
-                r#"{"jsonrpc":"2.0","error":{"code":-32002,"message":"Transaction simulation failed: Blockhash not found","data":{"accounts":null,"err":"BlockhashNotFound","logs":[],"returnData":null,"unitsConsumed":0}},"id":1}"#.to_string(),
+                r#"{"jsonrpc":"2.0","error":{"code":-32002,"message":"Transaction simulation failed: Blockhash not found","data":{"accounts":null,"err":"BlockhashNotFound","executionTrace":null,"innerInstructions":null,"instructionUnitsConsumed":null,"logs":[],"returnData":null,"unitsConsumed":0}},"id":1}"#.to_string(),
             )
         );
 
@@ -42,7 +42,9 @@ use {
     solana_send_transaction_service::send_transaction_service::{self, SendTransactionService},
     solana_storage_bigtable::CredentialType,
     std::{
+        io::SeekFrom,
         net::SocketAddr,
+        ops::RangeInclusive,
         path::{Path, PathBuf},
         sync::{
             atomic::{AtomicBool, AtomicU64, Ordering},
@@ -50,6 +52,7 @@ use {
         },
         thread::{self, Builder, JoinHandle},
     },
+    tokio::io::{AsyncReadExt, AsyncSeekExt},
     tokio_util::codec::{BytesCodec, FramedRead},
 };
 
@@ -113,6 +116,17 @@ impl RpcRequestMiddleware {
             .unwrap()
     }
 
+    fn range_not_satisfiable(file_length: u64) -> hyper::Response<hyper::Body> {
+        hyper::Response::builder()
+            .status(hyper::StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(
+                hyper::header::CONTENT_RANGE,
+                format!("bytes */{file_length}"),
+            )
+            .body(hyper::Body::empty())
+            .unwrap()
+    }
+
     #[allow(dead_code)]
     fn internal_server_error() -> hyper::Response<hyper::Body> {
         hyper::Response::builder()
@@ -188,7 +202,52 @@ impl RpcRequestMiddleware {
         }
     }
 
-    fn process_file_get(&self, path: &str) -> RequestMiddlewareAction {
+    /// Parse the value of a `Range` header against a file of `file_length` bytes.
+    ///
+    /// Only a single `bytes=start-end`, `bytes=start-` or `bytes=-suffix` range is supported, any
+    /// other range is ignored and the whole file served.  Returns `Err` if the range cannot be
+    /// satisfied.
+    fn parse_range(range: &str, file_length: u64) -> Result<Option<RangeInclusive<u64>>, ()> {
+        let Some(range) = range.trim().strip_prefix("bytes=") else {
+            return Ok(None);
+        };
+        if range.contains(',') {
+            return Ok(None);
+        }
+        let Some((start, end)) = range.split_once('-') else {
+            return Ok(None);
+        };
+        let (start, end) = match (start.trim(), end.trim()) {
+            ("", suffix) => {
+                let Ok(suffix) = suffix.parse::<u64>() else {
+                    return Ok(None);
+                };
+                if suffix == 0 || file_length == 0 {
+                    return Err(());
+                }
+                (file_length.saturating_sub(suffix), file_length - 1)
+            }
+            (start, end) => {
+                let Ok(start) = start.parse::<u64>() else {
+                    return Ok(None);
+                };
+                let end = match end {
+                    "" => u64::MAX,
+                    end => match end.parse::<u64>() {
+                        Ok(end) if end >= start => end,
+                        _ => return Ok(None),
+                    },
+                };
+                if start >= file_length {
+                    return Err(());
+                }
+                (start, end.min(file_length - 1))
+            }
+        };
+        Ok(Some(start..=end))
+    }
+
+    fn process_file_get(&self, path: &str, range: Option<&str>) -> RequestMiddlewareAction {
         let filename = {
             let stem = Self::strip_leading_slash(path).expect("path already verified");
             match path {
@@ -203,31 +262,55 @@ impl RpcRequestMiddleware {
             }
         };
 
-        let file_length = std::fs::metadata(&filename)
-            .map(|m| m.len())
-            .unwrap_or(0)
-            .to_string();
-        info!("get {} -> {:?} ({} bytes)", path, filename, file_length);
+        let file_length = std::fs::metadata(&filename).map(|m| m.len()).unwrap_or(0);
+        let range = match range.map(|range| Self::parse_range(range, file_length)) {
+            None | Some(Ok(None)) => None,
+            Some(Ok(Some(range))) => Some(range),
+            Some(Err(())) => {
+                return Self::range_not_satisfiable(file_length).into();
+            }
+        };
+        info!(
+            "get {} -> {:?} ({} bytes, range: {:?})",
+            path, filename, file_length, range
+        );
         RequestMiddlewareAction::Respond {
             should_validate_hosts: true,
-            response: Box::pin(async {
-                match Self::open_no_follow(filename).await {
-                    Err(err) => Ok(if err.kind() == std::io::ErrorKind::NotFound {
-                        Self::not_found()
-                    } else {
-                        Self::internal_server_error()
-                    }),
-                    Ok(file) => {
-                        let stream =
-                            FramedRead::new(file, BytesCodec::new()).map_ok(|b| b.freeze());
-                        let body = hyper::Body::wrap_stream(stream);
-
-                        Ok(hyper::Response::builder()
-                            .header(hyper::header::CONTENT_LENGTH, file_length)
-                            .body(body)
-                            .unwrap())
+            response: Box::pin(async move {
+                let mut file = match Self::open_no_follow(filename).await {
+                    Err(err) => {
+                        return Ok(if err.kind() == std::io::ErrorKind::NotFound {
+                            Self::not_found()
+                        } else {
+                            Self::internal_server_error()
+                        });
                     }
+                    Ok(file) => file,
+                };
+                let response =
+                    hyper::Response::builder().header(hyper::header::ACCEPT_RANGES, "bytes");
+                let Some(range) = range else {
+                    let stream = FramedRead::new(file, BytesCodec::new()).map_ok(|b| b.freeze());
+                    return Ok(response
+                        .header(hyper::header::CONTENT_LENGTH, file_length)
+                        .body(hyper::Body::wrap_stream(stream))
+                        .unwrap());
+                };
+                if file.seek(SeekFrom::Start(*range.start())).await.is_err() {
+                    return Ok(Self::internal_server_error());
                 }
+                let range_length = range.end() - range.start() + 1;
+                let stream = FramedRead::new(file.take(range_length), BytesCodec::new())
+                    .map_ok(|b| b.freeze());
+                Ok(response
+                    .status(hyper::StatusCode::PARTIAL_CONTENT)
+                    .header(
+                        hyper::header::CONTENT_RANGE,
+                        format!("bytes {}-{}/{file_length}", range.start(), range.end()),
+                    )
+                    .header(hyper::header::CONTENT_LENGTH, range_length)
+                    .body(hyper::Body::wrap_stream(stream))
+                    .unwrap())
             }),
         }
     }
@@ -298,7 +381,13 @@ impl RequestMiddleware for RpcRequestMiddleware {
                 .unwrap()
                 .into()
         } else if self.is_file_get_path(request.uri().path()) {
-            self.process_file_get(request.uri().path())
+            self.process_file_get(
+                request.uri().path(),
+                request
+                    .headers()
+                    .get(hyper::header::RANGE)
+                    .and_then(|range| range.to_str().ok()),
+            )
         } else if request.uri().path() == "/health" {
             hyper::Response::builder()
                 .status(hyper::StatusCode::OK)
@@ -843,7 +932,7 @@ mod tests {
         );
 
         // File does not exist => request should fail.
-        let action = rrm.process_file_get(DEFAULT_GENESIS_DOWNLOAD_PATH);
+        let action = rrm.process_file_get(DEFAULT_GENESIS_DOWNLOAD_PATH, None);
         if let RequestMiddlewareAction::Respond { response, .. } = action {
             let response = runtime.block_on(response);
             let response = response.unwrap();
@@ -858,7 +947,7 @@ mod tests {
         }
 
         // Normal file exist => request should succeed.
-        let action = rrm.process_file_get(DEFAULT_GENESIS_DOWNLOAD_PATH);
+        let action = rrm.process_file_get(DEFAULT_GENESIS_DOWNLOAD_PATH, None);
         if let RequestMiddlewareAction::Respond { response, .. } = action {
             let response = runtime.block_on(response);
             let response = response.unwrap();
@@ -877,7 +966,7 @@ mod tests {
             symlink::symlink_file("wrong", &genesis_path).unwrap();
 
             // File is a symbolic link => request should fail.
-            let action = rrm.process_file_get(DEFAULT_GENESIS_DOWNLOAD_PATH);
+            let action = rrm.process_file_get(DEFAULT_GENESIS_DOWNLOAD_PATH, None);
             if let RequestMiddlewareAction::Respond { response, .. } = action {
                 let response = runtime.block_on(response);
                 let response = response.unwrap();
@@ -887,4 +976,78 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_parse_range() {
+        let parse_range = RpcRequestMiddleware::parse_range;
+        assert_eq!(parse_range("bytes=0-9", 100), Ok(Some(0..=9)));
+        assert_eq!(parse_range("bytes=90-", 100), Ok(Some(90..=99)));
+        assert_eq!(parse_range("bytes=90-200", 100), Ok(Some(90..=99)));
+        assert_eq!(parse_range("bytes=-10", 100), Ok(Some(90..=99)));
+        assert_eq!(parse_range("bytes=-200", 100), Ok(Some(0..=99)));
+        assert_eq!(parse_range("bytes=100-", 100), Err(()));
+        assert_eq!(parse_range("bytes=-0", 100), Err(()));
+        assert_eq!(parse_range("bytes=-10", 0), Err(()));
+        // Unsupported or malformed ranges serve the whole file
+        assert_eq!(parse_range("bytes=0-9,20-29", 100), Ok(None));
+        assert_eq!(parse_range("bytes=9-0", 100), Ok(None));
+        assert_eq!(parse_range("bytes=a-b", 100), Ok(None));
+        assert_eq!(parse_range("items=0-9", 100), Ok(None));
+    }
+
+    #[test]
+    fn test_process_file_get_range() {
+        let runtime = Runtime::new().unwrap();
+
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let blockstore = Arc::new(Blockstore::open(ledger_path.path()).unwrap());
+        let genesis_path = ledger_path.path().join(DEFAULT_GENESIS_ARCHIVE);
+        let bank_forks = create_bank_forks();
+        let optimistically_confirmed_bank =
+            OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks);
+        let rrm = RpcRequestMiddleware::new(
+            ledger_path.path().to_path_buf(),
+            None,
+            bank_forks,
+            RpcHealth::stub(optimistically_confirmed_bank, blockstore),
+        );
+        std::fs::write(&genesis_path, b"should be ok").unwrap();
+
+        let get = |range: &str| {
+            let RequestMiddlewareAction::Respond { response, .. } =
+                rrm.process_file_get(DEFAULT_GENESIS_DOWNLOAD_PATH, Some(range))
+            else {
+                panic!("Unexpected RequestMiddlewareAction variant");
+            };
+            runtime.block_on(async {
+                let response = response.await.unwrap();
+                let status = response.status();
+                let content_range = response
+                    .headers()
+                    .get(hyper::header::CONTENT_RANGE)
+                    .map(|content_range| content_range.to_str().unwrap().to_string());
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                (status, content_range, body)
+            })
+        };
+
+        let (status, content_range, body) = get("bytes=7-8");
+        assert_eq!(status, hyper::StatusCode::PARTIAL_CONTENT);
+        assert_eq!(content_range.as_deref(), Some("bytes 7-8/12"));
+        assert_eq!(&body[..], b"be");
+
+        let (status, content_range, body) = get("bytes=-2");
+        assert_eq!(status, hyper::StatusCode::PARTIAL_CONTENT);
+        assert_eq!(content_range.as_deref(), Some("bytes 10-11/12"));
+        assert_eq!(&body[..], b"ok");
+
+        let (status, content_range, body) = get("bytes=0-9,10-11");
+        assert_eq!(status, hyper::StatusCode::OK);
+        assert_eq!(content_range, None);
+        assert_eq!(&body[..], b"should be ok");
+
+        let (status, content_range, _body) = get("bytes=12-");
+        assert_eq!(status, hyper::StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(content_range.as_deref(), Some("bytes */12"));
+    }
 }
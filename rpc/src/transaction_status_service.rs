@@ -2,7 +2,9 @@ use {
     crate::transaction_notifier_interface::TransactionNotifierArc,
     crossbeam_channel::{Receiver, RecvTimeoutError},
     itertools::izip,
-    solana_accounts_db::transaction_results::{DurableNonceFee, TransactionExecutionDetails},
+    solana_accounts_db::transaction_results::{
+        DurableNonceFee, InnerInstructionsList, TransactionExecutionDetails,
+    },
     solana_ledger::{
         blockstore::Blockstore,
         blockstore_processor::{TransactionStatusBatch, TransactionStatusMessage},
@@ -20,6 +22,28 @@ use {
     },
 };
 
+/// Converts the inner instructions recorded during execution to those of transaction statuses,
+/// leaving out the instructions which did not invoke any
+pub(crate) fn map_inner_instructions(
+    inner_instructions: InnerInstructionsList,
+) -> Vec<InnerInstructions> {
+    inner_instructions
+        .into_iter()
+        .enumerate()
+        .map(|(index, instructions)| InnerInstructions {
+            index: index as u8,
+            instructions: instructions
+                .into_iter()
+                .map(|info| InnerInstruction {
+                    instruction: info.instruction,
+                    stack_height: Some(u32::from(info.stack_height)),
+                })
+                .collect(),
+        })
+        .filter(|i| !i.instructions.is_empty())
+        .collect()
+}
+
 pub struct TransactionStatusService {
     thread_hdl: JoinHandle<()>,
 }
@@ -120,23 +144,7 @@ impl TransactionStatusService {
                         );
                         let tx_account_locks = transaction.get_account_locks_unchecked();
 
-                        let inner_instructions = inner_instructions.map(|inner_instructions| {
-                            inner_instructions
-                                .into_iter()
-                                .enumerate()
-                                .map(|(index, instructions)| InnerInstructions {
-                                    index: index as u8,
-                                    instructions: instructions
-                                        .into_iter()
-                                        .map(|info| InnerInstruction {
-                                            instruction: info.instruction,
-                                            stack_height: Some(u32::from(info.stack_height)),
-                                        })
-                                        .collect(),
-                                })
-                                .filter(|i| !i.instructions.is_empty())
-                                .collect()
-                        });
+                        let inner_instructions = inner_instructions.map(map_inner_instructions);
 
                         let pre_token_balances = Some(pre_token_balances);
                         let post_token_balances = Some(post_token_balances);
@@ -388,6 +396,8 @@ pub(crate) mod tests {
             )),
             return_data: None,
             executed_units: 0,
+            instruction_consumed_units: None,
+            execution_traces: None,
             accounts_data_len_delta: 0,
         });
 
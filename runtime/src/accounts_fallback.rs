@@ -0,0 +1,72 @@
+//! Loading the accounts that transactions use but a bank does not have from elsewhere, such as
+//! the cluster that a test validator forks.  Only meant for test validators: banks which load
+//! accounts from a fallback do not agree with the rest of their cluster.
+use {
+    solana_sdk::{account::AccountSharedData, pubkey::Pubkey},
+    std::{
+        collections::HashSet,
+        fmt,
+        sync::{Arc, Mutex, MutexGuard, RwLock},
+    },
+};
+
+/// Provides the accounts which are missing from a bank when transactions load them
+pub trait AccountsFallback: fmt::Debug + Send + Sync {
+    /// Returns those of `pubkeys` which the fallback has.  This is called every time a
+    /// transaction loads accounts which the bank does not have, so fallbacks which are slow to
+    /// load accounts should remember the accounts they do not have as well.
+    fn load_accounts(&self, pubkeys: &[Pubkey]) -> Vec<(Pubkey, AccountSharedData)>;
+}
+
+/// An `AccountsFallback` shared by the banks of a `RuntimeConfig`
+#[derive(Clone, Debug)]
+pub struct SharedAccountsFallback {
+    fallback: Arc<dyn AccountsFallback>,
+    // Serializes storing loaded accounts, so an account loaded by two transaction batches at
+    // once only adds to the capitalization once
+    store_lock: Arc<Mutex<()>>,
+    // Accounts which the banks have loaded from the fallback and stored, or had already. They
+    // are never loaded again, so that accounts closed since stay closed.
+    local_pubkeys: Arc<RwLock<HashSet<Pubkey>>>,
+}
+
+impl SharedAccountsFallback {
+    pub fn new(fallback: Arc<dyn AccountsFallback>) -> Self {
+        Self {
+            fallback,
+            store_lock: Arc::default(),
+            local_pubkeys: Arc::default(),
+        }
+    }
+
+    pub(crate) fn load_accounts(&self, pubkeys: &[Pubkey]) -> Vec<(Pubkey, AccountSharedData)> {
+        self.fallback.load_accounts(pubkeys)
+    }
+
+    pub(crate) fn lock_store(&self) -> MutexGuard<()> {
+        self.store_lock.lock().unwrap()
+    }
+
+    pub(crate) fn is_local(&self, pubkey: &Pubkey) -> bool {
+        self.local_pubkeys.read().unwrap().contains(pubkey)
+    }
+
+    pub(crate) fn add_local_pubkeys(&self, pubkeys: impl IntoIterator<Item = Pubkey>) {
+        self.local_pubkeys.write().unwrap().extend(pubkeys);
+    }
+}
+
+#[cfg(RUSTC_WITH_SPECIALIZATION)]
+impl solana_frozen_abi::abi_example::AbiExample for SharedAccountsFallback {
+    fn example() -> Self {
+        // SharedAccountsFallback isn't serializable by definition.
+        #[derive(Debug)]
+        struct MockAccountsFallback;
+        impl AccountsFallback for MockAccountsFallback {
+            fn load_accounts(&self, _pubkeys: &[Pubkey]) -> Vec<(Pubkey, AccountSharedData)> {
+                vec![]
+            }
+        }
+        Self::new(Arc::new(MockAccountsFallback))
+    }
+}
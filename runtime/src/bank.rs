@@ -96,7 +96,7 @@ use {
         storable_accounts::StorableAccounts,
         transaction_error_metrics::TransactionErrorMetrics,
         transaction_results::{
-            inner_instructions_list_from_instruction_trace, DurableNonceFee,
+            inner_instructions_list_from_instruction_trace, DurableNonceFee, InnerInstructionsList,
             TransactionCheckResult, TransactionExecutionDetails, TransactionExecutionResult,
             TransactionResults,
         },
@@ -137,6 +137,7 @@ use {
         },
         epoch_info::EpochInfo,
         epoch_schedule::EpochSchedule,
+        execution_trace::ExecutionTraceList,
         feature,
         feature_set::{
             self, include_loaded_accounts_data_size_in_fee_calculation,
@@ -168,8 +169,9 @@ use {
         sysvar::{self, last_restart_slot::LastRestartSlot, Sysvar, SysvarId},
         timing::years_as_slots,
         transaction::{
-            self, MessageHash, Result, SanitizedTransaction, Transaction, TransactionError,
-            TransactionVerificationMode, VersionedTransaction, MAX_TX_ACCOUNT_LOCKS,
+            self, AddressLoader, MessageHash, Result, SanitizedTransaction, Transaction,
+            TransactionError, TransactionVerificationMode, VersionedTransaction,
+            MAX_TX_ACCOUNT_LOCKS,
         },
         transaction_context::{
             ExecutionRecord, TransactionAccount, TransactionContext, TransactionReturnData,
@@ -337,6 +339,12 @@ pub struct TransactionSimulationResult {
     pub post_simulation_accounts: Vec<TransactionAccount>,
     pub units_consumed: u64,
     pub return_data: Option<TransactionReturnData>,
+    pub inner_instructions: Option<InnerInstructionsList>,
+    /// The compute units consumed by each instruction that was executed
+    pub instruction_units_consumed: Option<Vec<u64>>,
+    /// The execution traces of the SBF programs that each executed instruction invoked, when the
+    /// bank compiles programs with instruction tracing
+    pub execution_traces: Option<ExecutionTraceList>,
 }
 pub struct TransactionBalancesSet {
     pub pre_balances: TransactionBalances,
@@ -1287,6 +1295,11 @@ impl Bank {
         }
     }
 
+    /// Returns true while the bank is distributing the partitioned rewards of the previous epoch
+    pub fn is_in_reward_interval(&self) -> bool {
+        self.get_reward_interval() == RewardInterval::InsideInterval
+    }
+
     /// For testing only
     pub fn force_reward_interval_end_for_tests(&mut self) {
         self.epoch_reward_status = EpochRewardStatus::Inactive;
@@ -1492,12 +1505,12 @@ impl Bank {
                     &feature_set,
                     &new.runtime_config.compute_budget.unwrap_or_default(),
                     false, /* deployment */
-                    false, /* debugging_features */
+                    new.runtime_config.enable_instruction_tracing,
                 )
                 .unwrap();
                 let program_runtime_environment_v2 = create_program_runtime_environment_v2(
                     &new.runtime_config.compute_budget.unwrap_or_default(),
-                    false, /* debugging_features */
+                    new.runtime_config.enable_instruction_tracing,
                 );
                 let mut upcoming_environments = loaded_programs_cache.environments.clone();
                 let changed_program_runtime_v1 =
@@ -4365,18 +4378,198 @@ impl Bank {
     ) -> TransactionSimulationResult {
         assert!(self.is_frozen(), "simulation bank must be frozen");
 
-        self.simulate_transaction_unchecked(transaction)
+        self.simulate_transaction_unchecked_with_account_overrides(
+            transaction,
+            &AccountOverrides::default(),
+            false,
+            false,
+        )
+        .0
+    }
+
+    /// Run a transaction against a frozen bank without committing the results, with the accounts
+    /// of `account_overrides` in place of the bank's own.  Overridden sysvars and programs are
+    /// also seen by the programs that the transaction executes.  The inner instructions are only
+    /// recorded if `enable_cpi_recording` is set, and the execution traces only if
+    /// `enable_execution_trace_recording` is set and the bank traces instructions.
+    pub fn simulate_transaction_with_account_overrides(
+        &self,
+        transaction: SanitizedTransaction,
+        account_overrides: &AccountOverrides,
+        enable_cpi_recording: bool,
+        enable_execution_trace_recording: bool,
+    ) -> TransactionSimulationResult {
+        assert!(self.is_frozen(), "simulation bank must be frozen");
+
+        self.simulate_transaction_unchecked_with_account_overrides(
+            transaction,
+            account_overrides,
+            enable_cpi_recording,
+            enable_execution_trace_recording,
+        )
+        .0
+    }
+
+    /// Run transactions one after the other against a frozen bank without committing the
+    /// results, with the accounts of `account_overrides` in place of the bank's own.  Every
+    /// transaction sees the accounts written by the transactions before it, and failed ones only
+    /// charge their fee; durable nonces used by failed transactions are not advanced though.
+    /// Returns a result for each transaction that was simulated, which stops after the first
+    /// failure if `stop_at_first_failure` is set.
+    ///
+    /// The address lookup tables of each transaction are resolved again against the accounts
+    /// written by the transactions before it, as they would be within one slot: addresses
+    /// appended to a table in the slot of the bank are not usable yet.  Transactions whose
+    /// signature was already simulated fail with `AlreadyProcessed` without being executed.
+    pub fn simulate_bundle(
+        &self,
+        transactions: Vec<SanitizedTransaction>,
+        account_overrides: &AccountOverrides,
+        stop_at_first_failure: bool,
+        enable_cpi_recording: bool,
+        enable_execution_trace_recording: bool,
+    ) -> Vec<TransactionSimulationResult> {
+        assert!(self.is_frozen(), "simulation bank must be frozen");
+
+        let mut account_overrides = account_overrides.clone();
+        let mut simulation_results = Vec::with_capacity(transactions.len());
+        let mut signatures = HashSet::with_capacity(transactions.len());
+        for (index, transaction) in transactions.into_iter().enumerate() {
+            let transaction = if !signatures.insert(*transaction.signature()) {
+                Err(TransactionError::AlreadyProcessed)
+            } else if index > 0
+                && !transaction
+                    .message()
+                    .message_address_table_lookups()
+                    .is_empty()
+            {
+                SanitizedTransaction::try_create(
+                    transaction.to_versioned_transaction(),
+                    *transaction.message_hash(),
+                    Some(transaction.is_simple_vote_transaction()),
+                    self.address_loader_with_account_overrides(&account_overrides),
+                )
+            } else {
+                Ok(transaction)
+            };
+            let transaction = match transaction {
+                Ok(transaction) => transaction,
+                Err(err) => {
+                    simulation_results.push(TransactionSimulationResult {
+                        result: Err(err),
+                        logs: vec![],
+                        post_simulation_accounts: vec![],
+                        units_consumed: 0,
+                        return_data: None,
+                        inner_instructions: None,
+                        instruction_units_consumed: None,
+                        execution_traces: None,
+                    });
+                    if stop_at_first_failure {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            let message = transaction.message();
+            let fee_payer = *message.fee_payer();
+            let fee = self.get_fee_for_message(message).unwrap_or_default();
+            let writable_accounts: Vec<_> = (0..message.account_keys().len())
+                .map(|i| message.is_writable(i))
+                .collect();
+
+            let (simulation_result, executed) = self
+                .simulate_transaction_unchecked_with_account_overrides(
+                    transaction,
+                    &account_overrides,
+                    enable_cpi_recording,
+                    enable_execution_trace_recording,
+                );
+            match &simulation_result.result {
+                Ok(()) => {
+                    for ((pubkey, account), is_writable) in simulation_result
+                        .post_simulation_accounts
+                        .iter()
+                        .zip(writable_accounts)
+                    {
+                        if is_writable {
+                            // Accounts left without lamports are removed once committed
+                            let account = if account.lamports() == 0 {
+                                AccountSharedData::default()
+                            } else {
+                                account.clone()
+                            };
+                            account_overrides.set_account(pubkey, Some(account));
+                        }
+                    }
+                }
+                Err(_) if executed => {
+                    let fee_payer_account = account_overrides
+                        .get(&fee_payer)
+                        .cloned()
+                        .or_else(|| self.get_account_with_fixed_root(&fee_payer));
+                    if let Some(mut fee_payer_account) = fee_payer_account {
+                        fee_payer_account
+                            .set_lamports(fee_payer_account.lamports().saturating_sub(fee));
+                        account_overrides.set_account(&fee_payer, Some(fee_payer_account));
+                    }
+                }
+                Err(_) => {}
+            }
+
+            let failed = simulation_result.result.is_err();
+            simulation_results.push(simulation_result);
+            if failed && stop_at_first_failure {
+                break;
+            }
+        }
+        simulation_results
+    }
+
+    /// Loads the address lookup tables of transactions from `account_overrides`, or from the bank
+    /// if they are not overridden, to sanitize transactions for simulations with the overrides
+    pub fn address_loader_with_account_overrides<'a>(
+        &'a self,
+        account_overrides: &'a AccountOverrides,
+    ) -> impl AddressLoader + 'a {
+        address_lookup_table::AccountOverridesAddressLoader {
+            bank: self,
+            account_overrides,
+        }
     }
 
     /// Run transactions against a bank without committing the results; does not check if the bank
-    /// is frozen, enabling use in single-Bank test frameworks
+    /// is frozen, enabling use in single-Bank test frameworks.  Records the execution traces if the
+    /// bank traces instructions.
     pub fn simulate_transaction_unchecked(
         &self,
         transaction: SanitizedTransaction,
     ) -> TransactionSimulationResult {
+        self.simulate_transaction_unchecked_with_account_overrides(
+            transaction,
+            &AccountOverrides::default(),
+            false,
+            true,
+        )
+        .0
+    }
+
+    /// Also returns whether the transaction was executed, as failed transactions which were
+    /// executed still charge their fee
+    fn simulate_transaction_unchecked_with_account_overrides(
+        &self,
+        transaction: SanitizedTransaction,
+        account_overrides: &AccountOverrides,
+        enable_cpi_recording: bool,
+        enable_execution_trace_recording: bool,
+    ) -> (TransactionSimulationResult, bool) {
         let account_keys = transaction.message().account_keys();
         let number_of_accounts = account_keys.len();
-        let account_overrides = self.get_account_overrides_for_simulation(&account_keys);
+        let mut simulation_account_overrides =
+            self.get_account_overrides_for_simulation(&account_keys);
+        for (pubkey, account) in account_overrides.iter() {
+            simulation_account_overrides.set_account(pubkey, Some(account.clone()));
+        }
         let batch = self.prepare_unlocked_batch_from_single_tx(&transaction);
         let mut timings = ExecuteTimings::default();
 
@@ -4390,11 +4583,12 @@ impl Bank {
             // for processing. During forwarding, the transaction could expire if the
             // delay is not accounted for.
             MAX_PROCESSING_AGE - MAX_TRANSACTION_FORWARDING_DELAY,
-            false,
+            enable_cpi_recording,
             true,
             true,
+            enable_execution_trace_recording,
             &mut timings,
-            Some(&account_overrides),
+            Some(&simulation_account_overrides),
             None,
         );
 
@@ -4425,21 +4619,33 @@ impl Bank {
 
         let execution_result = execution_results.pop().unwrap();
         let flattened_result = execution_result.flattened_result();
-        let (logs, return_data) = match execution_result {
-            TransactionExecutionResult::Executed { details, .. } => {
-                (details.log_messages, details.return_data)
-            }
-            TransactionExecutionResult::NotExecuted(_) => (None, None),
-        };
+        let executed = execution_result.was_executed();
+        let (logs, return_data, inner_instructions, instruction_units_consumed, execution_traces) =
+            match execution_result {
+                TransactionExecutionResult::Executed { details, .. } => (
+                    details.log_messages,
+                    details.return_data,
+                    details.inner_instructions,
+                    details.instruction_consumed_units,
+                    details.execution_traces,
+                ),
+                TransactionExecutionResult::NotExecuted(_) => (None, None, None, None, None),
+            };
         let logs = logs.unwrap_or_default();
 
-        TransactionSimulationResult {
-            result: flattened_result,
-            logs,
-            post_simulation_accounts,
-            units_consumed,
-            return_data,
-        }
+        (
+            TransactionSimulationResult {
+                result: flattened_result,
+                logs,
+                post_simulation_accounts,
+                units_consumed,
+                return_data,
+                inner_instructions,
+                instruction_units_consumed,
+                execution_traces,
+            },
+            executed,
+        )
     }
 
     fn get_account_overrides_for_simulation(&self, account_keys: &AccountKeys) -> AccountOverrides {
@@ -4655,8 +4861,17 @@ impl Bank {
         }
     }
 
-    fn load_program_accounts(&self, pubkey: &Pubkey) -> ProgramAccountLoadResult {
-        let program_account = match self.get_account_with_fixed_root(pubkey) {
+    fn load_program_accounts(
+        &self,
+        pubkey: &Pubkey,
+        account_overrides: Option<&AccountOverrides>,
+    ) -> ProgramAccountLoadResult {
+        let get_account = |pubkey: &Pubkey| {
+            account_overrides
+                .and_then(|account_overrides| account_overrides.get(pubkey).cloned())
+                .or_else(|| self.get_account_with_fixed_root(pubkey))
+        };
+        let program_account = match get_account(pubkey) {
             None => return ProgramAccountLoadResult::AccountNotFound,
             Some(account) => account,
         };
@@ -4683,7 +4898,7 @@ impl Bank {
             programdata_address,
         }) = program_account.state()
         {
-            let programdata_account = match self.get_account_with_fixed_root(&programdata_address) {
+            let programdata_account = match get_account(&programdata_address) {
                 None => return ProgramAccountLoadResult::AccountNotFound,
                 Some(account) => account,
             };
@@ -4708,6 +4923,16 @@ impl Bank {
         pubkey: &Pubkey,
         reload: bool,
         recompile: Option<Arc<LoadedProgram>>,
+    ) -> Arc<LoadedProgram> {
+        self.load_program_with_account_overrides(pubkey, reload, recompile, None)
+    }
+
+    fn load_program_with_account_overrides(
+        &self,
+        pubkey: &Pubkey,
+        reload: bool,
+        recompile: Option<Arc<LoadedProgram>>,
+        account_overrides: Option<&AccountOverrides>,
     ) -> Arc<LoadedProgram> {
         let loaded_programs_cache = self.loaded_programs_cache.read().unwrap();
         let effective_epoch = if recompile.is_some() {
@@ -4721,7 +4946,7 @@ impl Bank {
             ..LoadProgramMetrics::default()
         };
 
-        let mut loaded_program = match self.load_program_accounts(pubkey) {
+        let mut loaded_program = match self.load_program_accounts(pubkey, account_overrides) {
             ProgramAccountLoadResult::AccountNotFound => Ok(LoadedProgram::new_tombstone(
                 self.slot,
                 LoadedProgramType::Closed,
@@ -4838,6 +5063,10 @@ impl Bank {
             loaded_program.ix_usage_counter =
                 AtomicU64::new(recompile.ix_usage_counter.load(Ordering::Relaxed));
         }
+        if account_overrides.is_some() {
+            // Overridden programs preview a deployment, so they are visible right away
+            loaded_program.effective_slot = loaded_program.effective_slot.min(self.slot);
+        }
         Arc::new(loaded_program)
     }
 
@@ -4860,10 +5089,12 @@ impl Bank {
         enable_cpi_recording: bool,
         enable_log_recording: bool,
         enable_return_data_recording: bool,
+        enable_execution_trace_recording: bool,
         timings: &mut ExecuteTimings,
         error_counters: &mut TransactionErrorMetrics,
         log_messages_bytes_limit: Option<usize>,
         programs_loaded_for_tx_batch: &LoadedProgramsForTxBatch,
+        sysvar_cache_with_overrides: Option<&SysvarCache>,
     ) -> TransactionExecutionResult {
         let prev_accounts_data_len = self.load_accounts_data_size();
         let transaction_accounts = std::mem::take(&mut loaded_transaction.accounts);
@@ -4918,6 +5149,11 @@ impl Bank {
         let (blockhash, lamports_per_signature) = self.last_blockhash_and_lamports_per_signature();
 
         let mut executed_units = 0u64;
+        let mut instruction_consumed_units =
+            enable_log_recording.then(|| Vec::with_capacity(tx.message().instructions().len()));
+        let mut execution_traces = (enable_execution_trace_recording
+            && self.runtime_config.enable_instruction_tracing)
+            .then(|| Vec::with_capacity(tx.message().instructions().len()));
         let mut programs_modified_by_tx = LoadedProgramsForTxBatch::new(
             self.slot,
             programs_loaded_for_tx_batch.environments.clone(),
@@ -4926,6 +5162,7 @@ impl Bank {
             self.slot,
             programs_loaded_for_tx_batch.environments.clone(),
         );
+        let sysvar_cache = self.sysvar_cache.read().unwrap();
         let mut process_message_time = Measure::start("process_message_time");
         let process_result = MessageProcessor::process_message(
             tx.message(),
@@ -4938,13 +5175,16 @@ impl Bank {
             self.feature_set.clone(),
             compute_budget,
             timings,
-            &self.sysvar_cache.read().unwrap(),
+            sysvar_cache_with_overrides.unwrap_or(&sysvar_cache),
             blockhash,
             lamports_per_signature,
             prev_accounts_data_len,
             &mut executed_units,
+            instruction_consumed_units.as_mut(),
+            execution_traces.as_mut(),
         );
         process_message_time.stop();
+        drop(sysvar_cache);
 
         saturating_add_assign!(
             timings.execute_accessories.process_message_us,
@@ -5031,6 +5271,8 @@ impl Bank {
                 durable_nonce_fee,
                 return_data,
                 executed_units,
+                instruction_consumed_units,
+                execution_traces,
                 accounts_data_len_delta,
             },
             programs_modified_by_tx: Box::new(programs_modified_by_tx),
@@ -5116,6 +5358,55 @@ impl Bank {
         loaded_programs_for_txs
     }
 
+    /// Compiles the programs of the given transactions whose accounts are overridden into the
+    /// batch specific cache only, so that they never reach the global cache
+    fn load_programs_from_account_overrides(
+        &self,
+        txs: &[SanitizedTransaction],
+        program_owners: &[Pubkey],
+        account_overrides: &AccountOverrides,
+        loaded_programs_for_tx_batch: &mut LoadedProgramsForTxBatch,
+    ) {
+        let overrides_programs = account_overrides
+            .iter()
+            .any(|(_, account)| program_owners.contains(account.owner()));
+        if !overrides_programs {
+            return;
+        }
+        let mut pubkeys = HashSet::new();
+        for tx in txs {
+            pubkeys.extend(tx.message().account_keys().iter().copied());
+        }
+        for pubkey in pubkeys {
+            let is_overridden = match account_overrides.get(&pubkey) {
+                Some(account) => program_owners.contains(account.owner()),
+                // Upgrades only override the program data account
+                None => {
+                    loaded_programs_for_tx_batch.find(&pubkey).is_some()
+                        && self
+                            .get_account_with_fixed_root(&pubkey)
+                            .filter(|account| bpf_loader_upgradeable::check_id(account.owner()))
+                            .and_then(|account| account.state().ok())
+                            .map_or(false, |state| match state {
+                                UpgradeableLoaderState::Program {
+                                    programdata_address,
+                                } => account_overrides.get(&programdata_address).is_some(),
+                                _ => false,
+                            })
+                }
+            };
+            if is_overridden {
+                let program = self.load_program_with_account_overrides(
+                    &pubkey,
+                    false,
+                    None,
+                    Some(account_overrides),
+                );
+                loaded_programs_for_tx_batch.replenish(pubkey, program);
+            }
+        }
+    }
+
     /// Returns a hash map of executable program accounts (program accounts that are not writable
     /// in the given transactions), and their owners, for the transactions with a valid
     /// blockhash or nonce.
@@ -5170,7 +5461,117 @@ impl Bank {
         result
     }
 
+    /// Loads the accounts which `pubkeys` refer to but this bank does not have from the accounts
+    /// fallback of the runtime config, along with the programdata of the upgradeable programs
+    /// among them.  Unfrozen banks store the loaded accounts; the accounts that are returned
+    /// could not be stored because the bank is frozen or freezing.  Accounts which any bank
+    /// stored or had before are not loaded, even if they have been closed since.
+    pub(crate) fn load_missing_accounts_from_fallback(
+        &self,
+        pubkeys: impl IntoIterator<Item = Pubkey>,
+    ) -> Vec<(Pubkey, AccountSharedData)> {
+        let Some(accounts_fallback) = &self.runtime_config.accounts_fallback else {
+            return vec![];
+        };
+        let is_missing = |pubkey: &Pubkey| {
+            !accounts_fallback.is_local(pubkey)
+                && self.get_account_with_fixed_root(pubkey).is_none()
+        };
+        let (mut missing_pubkeys, local_pubkeys): (Vec<_>, Vec<_>) = pubkeys
+            .into_iter()
+            .filter(|pubkey| !sysvar::instructions::check_id(pubkey))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .partition(is_missing);
+        accounts_fallback.add_local_pubkeys(local_pubkeys);
+        let mut loaded_accounts: Vec<(Pubkey, AccountSharedData)> = vec![];
+        while !missing_pubkeys.is_empty() {
+            let accounts = accounts_fallback.load_accounts(&missing_pubkeys);
+            missing_pubkeys = accounts
+                .iter()
+                .filter(|(_, account)| bpf_loader_upgradeable::check_id(account.owner()))
+                .filter_map(|(_, account)| match account.state() {
+                    Ok(UpgradeableLoaderState::Program {
+                        programdata_address,
+                    }) => Some(programdata_address),
+                    _ => None,
+                })
+                .filter(|pubkey| {
+                    !loaded_accounts
+                        .iter()
+                        .chain(&accounts)
+                        .any(|(loaded_pubkey, _)| loaded_pubkey == pubkey)
+                        && is_missing(pubkey)
+                })
+                .collect();
+            loaded_accounts.extend(accounts);
+        }
+        if loaded_accounts.is_empty() {
+            return loaded_accounts;
+        }
+        debug!(
+            "slot {}: loaded {} accounts from the accounts fallback",
+            self.slot(),
+            loaded_accounts.len()
+        );
+
+        let _store_lock = accounts_fallback.lock_store();
+        // Holding the hash lock keeps the bank from freezing while the accounts are stored. If
+        // `freeze()` already holds it, the bank is as good as frozen.
+        match self.hash.try_read() {
+            Ok(hash) if *hash == Hash::default() => {
+                for (pubkey, account) in &loaded_accounts {
+                    // Another batch may have stored the account in the meantime
+                    if is_missing(pubkey) {
+                        self.store_account_and_update_capitalization(pubkey, account);
+                    }
+                }
+                accounts_fallback
+                    .add_local_pubkeys(loaded_accounts.into_iter().map(|(pubkey, _)| pubkey));
+                vec![]
+            }
+            _ => loaded_accounts,
+        }
+    }
+
+    /// Loads the accounts of the transactions which passed their checks from the accounts
+    /// fallback, if the bank is missing any.  Returns `account_overrides` extended with the
+    /// accounts that could not be stored in the bank, if there are any.
+    fn load_missing_transaction_accounts_from_fallback(
+        &self,
+        sanitized_txs: &[SanitizedTransaction],
+        check_results: &[TransactionCheckResult],
+        account_overrides: Option<&AccountOverrides>,
+    ) -> Option<AccountOverrides> {
+        self.runtime_config.accounts_fallback.as_ref()?;
+        let pubkeys: Vec<_> = sanitized_txs
+            .iter()
+            .zip(check_results)
+            .filter(|(_, (check_result, _))| check_result.is_ok())
+            .flat_map(|(tx, _)| {
+                tx.message()
+                    .account_keys()
+                    .iter()
+                    .copied()
+                    .collect::<Vec<_>>()
+            })
+            .filter(|pubkey| {
+                account_overrides.map_or(true, |overrides| overrides.get(pubkey).is_none())
+            })
+            .collect();
+        let unstored_accounts = self.load_missing_accounts_from_fallback(pubkeys);
+        if unstored_accounts.is_empty() {
+            return None;
+        }
+        let mut account_overrides = account_overrides.cloned().unwrap_or_default();
+        for (pubkey, account) in unstored_accounts {
+            account_overrides.set_account(&pubkey, Some(account));
+        }
+        Some(account_overrides)
+    }
+
     #[allow(clippy::type_complexity)]
+    #[allow(clippy::too_many_arguments)]
     pub fn load_and_execute_transactions(
         &self,
         batch: &TransactionBatch,
@@ -5178,6 +5579,7 @@ impl Bank {
         enable_cpi_recording: bool,
         enable_log_recording: bool,
         enable_return_data_recording: bool,
+        enable_execution_trace_recording: bool,
         timings: &mut ExecuteTimings,
         account_overrides: Option<&AccountOverrides>,
         log_messages_bytes_limit: Option<usize>,
@@ -5231,6 +5633,15 @@ impl Bank {
         );
         check_time.stop();
 
+        // Missing programs that a frozen bank loads from the fallback only end up in the
+        // overrides, so the transactions which execute them fail to load
+        let fallback_account_overrides = self.load_missing_transaction_accounts_from_fallback(
+            sanitized_txs,
+            &check_results,
+            account_overrides,
+        );
+        let account_overrides = fallback_account_overrides.as_ref().or(account_overrides);
+
         const PROGRAM_OWNERS: &[Pubkey] = &[
             bpf_loader_upgradeable::id(),
             bpf_loader::id(),
@@ -5249,9 +5660,19 @@ impl Bank {
             program_accounts_map.insert(*builtin_program, (&native_loader, 0));
         }
 
-        let programs_loaded_for_tx_batch = Rc::new(RefCell::new(
-            self.replenish_program_cache(&program_accounts_map),
-        ));
+        let mut programs_loaded_for_tx_batch = self.replenish_program_cache(&program_accounts_map);
+        let mut sysvar_cache_with_overrides = None;
+        if let Some(account_overrides) = account_overrides {
+            self.load_programs_from_account_overrides(
+                sanitized_txs,
+                PROGRAM_OWNERS,
+                account_overrides,
+                &mut programs_loaded_for_tx_batch,
+            );
+            sysvar_cache_with_overrides =
+                self.sysvar_cache_with_account_overrides(account_overrides);
+        }
+        let programs_loaded_for_tx_batch = Rc::new(RefCell::new(programs_loaded_for_tx_batch));
 
         let mut load_time = Measure::start("accounts_load");
         let mut loaded_transactions = self.rc.accounts.load_accounts(
@@ -5310,10 +5731,12 @@ impl Bank {
                         enable_cpi_recording,
                         enable_log_recording,
                         enable_return_data_recording,
+                        enable_execution_trace_recording,
                         timings,
                         &mut error_counters,
                         log_messages_bytes_limit,
                         &programs_loaded_for_tx_batch.borrow(),
+                        sysvar_cache_with_overrides.as_ref(),
                     );
 
                     if let TransactionExecutionResult::Executed {
@@ -6345,6 +6768,7 @@ impl Bank {
             enable_cpi_recording,
             enable_log_recording,
             enable_return_data_recording,
+            false,
             timings,
             None,
             log_messages_bytes_limit,
@@ -6701,14 +7125,14 @@ impl Bank {
                 &self.feature_set,
                 &self.runtime_config.compute_budget.unwrap_or_default(),
                 false, /* deployment */
-                false, /* debugging_features */
+                self.runtime_config.enable_instruction_tracing,
             )
             .unwrap(),
         );
         loaded_programs_cache.environments.program_runtime_v2 =
             Arc::new(create_program_runtime_environment_v2(
                 &self.runtime_config.compute_budget.unwrap_or_default(),
-                false, /* debugging_features */
+                self.runtime_config.enable_instruction_tracing,
             ));
 
         if self
@@ -1,6 +1,8 @@
 use {
     super::Bank,
+    solana_accounts_db::{account_overrides::AccountOverrides, accounts::Accounts},
     solana_sdk::{
+        account::ReadableAccount,
         address_lookup_table::error::AddressLookupError,
         message::{
             v0::{LoadedAddresses, MessageAddressTableLookup},
@@ -22,15 +24,89 @@ impl AddressLoader for &Bank {
             .get_slot_hashes()
             .map_err(|_| AddressLoaderError::SlotHashesSysvarNotFound)?;
 
+        // Lookup tables that a frozen bank loads from the accounts fallback are not stored
+        let unstored_table_accounts = self.load_missing_accounts_from_fallback(
+            address_table_lookups
+                .iter()
+                .map(|address_table_lookup| address_table_lookup.account_key),
+        );
+
         Ok(address_table_lookups
             .iter()
             .map(|address_table_lookup| {
-                self.rc.accounts.load_lookup_table_addresses(
-                    &self.ancestors,
-                    address_table_lookup,
-                    &slot_hashes,
-                )
+                match unstored_table_accounts
+                    .iter()
+                    .find(|(pubkey, _)| pubkey == &address_table_lookup.account_key)
+                {
+                    Some((_, table_account)) => Accounts::lookup_table_addresses(
+                        table_account,
+                        self.slot(),
+                        address_table_lookup,
+                        &slot_hashes,
+                    ),
+                    None => self.rc.accounts.load_lookup_table_addresses(
+                        &self.ancestors,
+                        address_table_lookup,
+                        &slot_hashes,
+                    ),
+                }
             })
             .collect::<Result<_, AddressLookupError>>()?)
     }
 }
+
+/// Loads the addresses of the lookup tables in `account_overrides` from the overridden accounts,
+/// and those of the other lookup tables from the bank
+#[derive(Clone, Copy)]
+pub(super) struct AccountOverridesAddressLoader<'a> {
+    pub(super) bank: &'a Bank,
+    pub(super) account_overrides: &'a AccountOverrides,
+}
+
+impl AddressLoader for AccountOverridesAddressLoader<'_> {
+    fn load_addresses(
+        self,
+        address_table_lookups: &[MessageAddressTableLookup],
+    ) -> Result<LoadedAddresses, AddressLoaderError> {
+        let is_overridden = |address_table_lookup: &MessageAddressTableLookup| {
+            self.account_overrides
+                .get(&address_table_lookup.account_key)
+                .is_some()
+        };
+        if !address_table_lookups.iter().any(is_overridden) {
+            return self.bank.load_addresses(address_table_lookups);
+        }
+        let slot_hashes = self
+            .bank
+            .sysvar_cache
+            .read()
+            .unwrap()
+            .get_slot_hashes()
+            .map_err(|_| AddressLoaderError::SlotHashesSysvarNotFound)?;
+
+        address_table_lookups
+            .iter()
+            .map(|address_table_lookup| {
+                match self
+                    .account_overrides
+                    .get(&address_table_lookup.account_key)
+                {
+                    // Closed accounts are overridden with empty accounts
+                    Some(table_account) if table_account.lamports() == 0 => {
+                        Err(AddressLookupError::LookupTableAccountNotFound.into())
+                    }
+                    Some(table_account) => Accounts::lookup_table_addresses(
+                        table_account,
+                        self.bank.slot(),
+                        address_table_lookup,
+                        &slot_hashes,
+                    )
+                    .map_err(AddressLoaderError::from),
+                    None => self
+                        .bank
+                        .load_addresses(std::slice::from_ref(address_table_lookup)),
+                }
+            })
+            .collect()
+    }
+}
@@ -6,7 +6,7 @@ use {
     log::*,
     serde::{
         de::{self, Deserialize, Deserializer},
-        ser::{Serialize, SerializeSeq, Serializer},
+        ser::{Serialize, SerializeSeq, SerializeStruct, Serializer},
     },
     solana_accounts_db::{
         accounts_db::PubkeyHashAccount,
@@ -18,11 +18,16 @@ use {
         hash::Hash,
         pubkey::Pubkey,
     },
-    std::str::FromStr,
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        str::FromStr,
+        sync::atomic::Ordering::Relaxed,
+    },
 };
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub(crate) struct BankHashDetails {
+pub struct BankHashDetails {
     /// client version
     pub version: String,
     pub account_data_encoding: String,
@@ -32,10 +37,14 @@ pub(crate) struct BankHashDetails {
     pub accounts_delta_hash: String,
     pub signature_count: u64,
     pub last_blockhash: String,
+    /// Files written by older clients do not contain these fields
+    #[serde(default)]
+    pub stats: BankHashStats,
     pub accounts: BankHashAccounts,
 }
 
 impl BankHashDetails {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         slot: Slot,
         bank_hash: Hash,
@@ -43,6 +52,7 @@ impl BankHashDetails {
         accounts_delta_hash: Hash,
         signature_count: u64,
         last_blockhash: Hash,
+        stats: BankHashStats,
         accounts: BankHashAccounts,
     ) -> Self {
         Self {
@@ -54,11 +64,33 @@ impl BankHashDetails {
             accounts_delta_hash: accounts_delta_hash.to_string(),
             signature_count,
             last_blockhash: last_blockhash.to_string(),
+            stats,
             accounts,
         }
     }
 }
 
+/// Transaction, fee and rent totals for a single slot. These are not direct
+/// inputs to the bank hash, but they help narrow down why two hashes differ.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BankHashStats {
+    pub executed_transaction_count: u64,
+    pub transaction_error_count: u64,
+    pub collector_fees: u64,
+    pub collected_rent: u64,
+}
+
+impl From<&Bank> for BankHashStats {
+    fn from(bank: &Bank) -> Self {
+        Self {
+            executed_transaction_count: bank.executed_transaction_count(),
+            transaction_error_count: bank.transaction_error_count(),
+            collector_fees: bank.collector_fees.load(Relaxed),
+            collected_rent: bank.collected_rent.load(Relaxed),
+        }
+    }
+}
+
 impl TryFrom<&Bank> for BankHashDetails {
     type Error = String;
 
@@ -94,14 +126,15 @@ impl TryFrom<&Bank> for BankHashDetails {
             accounts_delta_hash,
             bank.signature_count(),
             bank.last_blockhash(),
+            BankHashStats::from(bank),
             BankHashAccounts { accounts },
         ))
     }
 }
 
 // Wrap the Vec<...> so we can implement custom Serialize/Deserialize traits on the wrapper type
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub(crate) struct BankHashAccounts {
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BankHashAccounts {
     pub accounts: Vec<PubkeyHashAccount>,
 }
 
@@ -193,6 +226,167 @@ impl<'de> Deserialize<'de> for BankHashAccounts {
     }
 }
 
+/// A single top level field that differs between two `BankHashDetails`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct BankHashFieldDiff {
+    pub name: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// An account that was written in both banks but with different contents
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BankHashAccountDiff {
+    pub expected: PubkeyHashAccount,
+    pub actual: PubkeyHashAccount,
+}
+
+impl Serialize for BankHashAccountDiff {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("BankHashAccountDiff", 2)?;
+        state.serialize_field("expected", &SerdeAccount::from(&self.expected))?;
+        state.serialize_field("actual", &SerdeAccount::from(&self.actual))?;
+        state.end()
+    }
+}
+
+/// The differences between an expected and an actual `BankHashDetails` for
+/// the same slot
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct BankHashDetailsDiff {
+    pub slot: Slot,
+    pub fields: Vec<BankHashFieldDiff>,
+    /// Accounts that were only written in the expected bank
+    pub missing_accounts: BankHashAccounts,
+    /// Accounts that were only written in the actual bank
+    pub unexpected_accounts: BankHashAccounts,
+    pub mismatched_accounts: Vec<BankHashAccountDiff>,
+}
+
+impl BankHashDetailsDiff {
+    pub fn new(expected: &BankHashDetails, actual: &BankHashDetails) -> Self {
+        let mut fields = vec![];
+        let mut compare = |name, expected: String, actual: String| {
+            if expected != actual {
+                fields.push(BankHashFieldDiff {
+                    name,
+                    expected,
+                    actual,
+                });
+            }
+        };
+        compare("slot", expected.slot.to_string(), actual.slot.to_string());
+        compare(
+            "bank_hash",
+            expected.bank_hash.clone(),
+            actual.bank_hash.clone(),
+        );
+        compare(
+            "parent_bank_hash",
+            expected.parent_bank_hash.clone(),
+            actual.parent_bank_hash.clone(),
+        );
+        compare(
+            "accounts_delta_hash",
+            expected.accounts_delta_hash.clone(),
+            actual.accounts_delta_hash.clone(),
+        );
+        compare(
+            "signature_count",
+            expected.signature_count.to_string(),
+            actual.signature_count.to_string(),
+        );
+        compare(
+            "last_blockhash",
+            expected.last_blockhash.clone(),
+            actual.last_blockhash.clone(),
+        );
+        compare(
+            "executed_transaction_count",
+            expected.stats.executed_transaction_count.to_string(),
+            actual.stats.executed_transaction_count.to_string(),
+        );
+        compare(
+            "transaction_error_count",
+            expected.stats.transaction_error_count.to_string(),
+            actual.stats.transaction_error_count.to_string(),
+        );
+        compare(
+            "collector_fees",
+            expected.stats.collector_fees.to_string(),
+            actual.stats.collector_fees.to_string(),
+        );
+        compare(
+            "collected_rent",
+            expected.stats.collected_rent.to_string(),
+            actual.stats.collected_rent.to_string(),
+        );
+
+        let mut actual_accounts: BTreeMap<_, _> = actual
+            .accounts
+            .accounts
+            .iter()
+            .map(|account| (account.pubkey, account))
+            .collect();
+        let mut missing_accounts = BankHashAccounts::default();
+        let mut mismatched_accounts = vec![];
+        for expected_account in expected.accounts.accounts.iter() {
+            match actual_accounts.remove(&expected_account.pubkey) {
+                None => missing_accounts.accounts.push(expected_account.clone()),
+                Some(actual_account) if actual_account != expected_account => mismatched_accounts
+                    .push(BankHashAccountDiff {
+                        expected: expected_account.clone(),
+                        actual: actual_account.clone(),
+                    }),
+                Some(_) => {}
+            }
+        }
+        let unexpected_accounts = BankHashAccounts {
+            accounts: actual_accounts.into_values().cloned().collect(),
+        };
+
+        Self {
+            slot: actual.slot,
+            fields,
+            missing_accounts,
+            unexpected_accounts,
+            mismatched_accounts,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+            && self.missing_accounts.accounts.is_empty()
+            && self.unexpected_accounts.accounts.is_empty()
+            && self.mismatched_accounts.is_empty()
+    }
+}
+
+/// Directory that bank hash details files are written to for `bank`
+fn bank_hash_details_dir(bank: &Bank) -> PathBuf {
+    bank.rc
+        .accounts
+        .accounts_db
+        .get_base_working_path()
+        .join("bank_hash_details")
+}
+
+fn write_json_file<T: Serialize>(path: &Path, contents: &T) -> std::result::Result<(), String> {
+    // std::fs::write may fail (depending on platform) if the full directory
+    // path does not exist. So, call std::fs_create_dir_all first.
+    // https://doc.rust-lang.org/std/fs/fn.write.html
+    if let Some(parent_dir) = path.parent() {
+        _ = std::fs::create_dir_all(parent_dir);
+    }
+    let file = std::fs::File::create(path)
+        .map_err(|err| format!("Unable to create file at {}: {err}", path.display()))?;
+    serde_json::to_writer_pretty(file, contents)
+        .map_err(|err| format!("Unable to write file contents to {}: {err}", path.display()))
+}
+
 /// Output the components that comprise bank hash
 pub fn write_bank_hash_details_file(bank: &Bank) -> std::result::Result<(), String> {
     let details = BankHashDetails::try_from(bank)?;
@@ -200,34 +394,45 @@ pub fn write_bank_hash_details_file(bank: &Bank) -> std::result::Result<(), Stri
     let slot = details.slot;
     let hash = &details.bank_hash;
     let file_name = format!("{slot}-{hash}.json");
-    let parent_dir = bank
-        .rc
-        .accounts
-        .accounts_db
-        .get_base_working_path()
-        .join("bank_hash_details");
-    let path = parent_dir.join(file_name);
+    let path = bank_hash_details_dir(bank).join(file_name);
     // A file with the same name implies the same hash for this slot. Skip
     // rewriting a duplicate file in this scenario
     if !path.exists() {
         info!("writing details of bank {} to {}", slot, path.display());
-
-        // std::fs::write may fail (depending on platform) if the full directory
-        // path does not exist. So, call std::fs_create_dir_all first.
-        // https://doc.rust-lang.org/std/fs/fn.write.html
-        _ = std::fs::create_dir_all(parent_dir);
-        let file = std::fs::File::create(&path).map_err(|err| {
-            format!(
-                "Unable to create bank hash file at {}: {err}",
-                path.display()
-            )
-        })?;
-        serde_json::to_writer_pretty(file, &details)
-            .map_err(|err| format!("Unable to write bank hash file contents: {err}"))?;
+        write_json_file(&path, &details)?;
     }
     Ok(())
 }
 
+/// Read a file previously written by `write_bank_hash_details_file()`
+pub fn read_bank_hash_details_file(path: &Path) -> std::result::Result<BankHashDetails, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|err| format!("Unable to open bank hash file {}: {err}", path.display()))?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .map_err(|err| format!("Unable to parse bank hash file {}: {err}", path.display()))
+}
+
+/// Output the differences between `expected` and the components that
+/// comprise the hash of `bank`, returning the path of the written file
+pub fn write_bank_hash_details_diff_file(
+    bank: &Bank,
+    expected: &BankHashDetails,
+) -> std::result::Result<PathBuf, String> {
+    let actual = BankHashDetails::try_from(bank)?;
+    let diff = BankHashDetailsDiff::new(expected, &actual);
+
+    let slot = actual.slot;
+    let file_name = format!("{slot}-{}-diff.json", actual.bank_hash);
+    let path = bank_hash_details_dir(bank).join(file_name);
+    info!(
+        "writing bank hash diff of bank {} to {}",
+        slot,
+        path.display()
+    );
+    write_json_file(&path, &diff)?;
+    Ok(path)
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -268,6 +473,12 @@ pub mod tests {
             accounts_delta_hash,
             signature_count,
             last_blockhash,
+            BankHashStats {
+                executed_transaction_count: 3,
+                transaction_error_count: 1,
+                collector_fees: 15_000,
+                collected_rent: 2_000,
+            },
             accounts,
         );
 
@@ -277,4 +488,83 @@ pub mod tests {
 
         assert_eq!(bank_hash_details, deserialized_bank_hash_details);
     }
+
+    #[test]
+    fn test_bank_hash_details_diff() {
+        use solana_sdk::{account::WritableAccount, hash::hash};
+
+        let new_account = |lamports| PubkeyHashAccount {
+            pubkey: Pubkey::new_unique(),
+            hash: AccountHash(hash(&u64::to_le_bytes(lamports))),
+            account: AccountSharedData::new(lamports, 0, &Pubkey::default()),
+        };
+        let common_account = new_account(1);
+        let missing_account = new_account(2);
+        let unexpected_account = new_account(3);
+        let expected_account = new_account(4);
+        let mut actual_account = expected_account.clone();
+        actual_account.account.set_lamports(5);
+        actual_account.hash = AccountHash(hash(&u64::to_le_bytes(5)));
+
+        let new_details = |bank_hash: &str, stats, accounts| {
+            BankHashDetails::new(
+                42,
+                hash(bank_hash.as_bytes()),
+                hash("parent_bank".as_bytes()),
+                hash("accounts_delta".as_bytes()),
+                2,
+                hash("last_blockhash".as_bytes()),
+                stats,
+                BankHashAccounts { accounts },
+            )
+        };
+        let expected = new_details(
+            "expected",
+            BankHashStats {
+                collector_fees: 10_000,
+                ..BankHashStats::default()
+            },
+            vec![
+                common_account.clone(),
+                missing_account.clone(),
+                expected_account.clone(),
+            ],
+        );
+        let actual = new_details(
+            "actual",
+            BankHashStats {
+                collector_fees: 5_000,
+                ..BankHashStats::default()
+            },
+            vec![
+                common_account,
+                unexpected_account.clone(),
+                actual_account.clone(),
+            ],
+        );
+
+        assert!(BankHashDetailsDiff::new(&expected, &expected).is_empty());
+
+        let diff = BankHashDetailsDiff::new(&expected, &actual);
+        assert!(!diff.is_empty());
+        assert_eq!(diff.slot, 42);
+        assert_eq!(
+            diff.fields
+                .iter()
+                .map(|field| field.name)
+                .collect::<Vec<_>>(),
+            vec!["bank_hash", "collector_fees"]
+        );
+        assert_eq!(diff.missing_accounts.accounts, vec![missing_account]);
+        assert_eq!(diff.unexpected_accounts.accounts, vec![unexpected_account]);
+        assert_eq!(
+            diff.mismatched_accounts,
+            vec![BankHashAccountDiff {
+                expected: expected_account,
+                actual: actual_account,
+            }]
+        );
+        // The diff is written out as json; make sure it serializes
+        serde_json::to_string(&diff).unwrap();
+    }
 }
@@ -1,6 +1,6 @@
 use {
-    super::Bank, solana_program_runtime::sysvar_cache::SysvarCache,
-    solana_sdk::account::ReadableAccount,
+    super::Bank, solana_accounts_db::account_overrides::AccountOverrides,
+    solana_program_runtime::sysvar_cache::SysvarCache, solana_sdk::account::ReadableAccount,
 };
 
 impl Bank {
@@ -13,6 +13,31 @@ impl Bank {
         });
     }
 
+    /// Returns a sysvar cache with the sysvars of `account_overrides` in place of the bank's own,
+    /// or `None` if it does not override any cached sysvar
+    pub(crate) fn sysvar_cache_with_account_overrides(
+        &self,
+        account_overrides: &AccountOverrides,
+    ) -> Option<SysvarCache> {
+        let mut overrides_sysvars = false;
+        let mut sysvar_cache = SysvarCache::default();
+        sysvar_cache.fill_missing_entries(|pubkey, callback| {
+            if let Some(account) = account_overrides.get(pubkey) {
+                overrides_sysvars = true;
+                callback(account.data());
+            }
+        });
+        if !overrides_sysvars {
+            return None;
+        }
+        sysvar_cache.fill_missing_entries(|pubkey, callback| {
+            if let Some(account) = self.get_account_with_fixed_root(pubkey) {
+                callback(account.data());
+            }
+        });
+        Some(sysvar_cache)
+    }
+
     pub(crate) fn reset_sysvar_cache(&self) {
         let mut sysvar_cache = self.sysvar_cache.write().unwrap();
         sysvar_cache.reset();
@@ -28,8 +53,12 @@ mod tests {
     use {
         super::*,
         solana_sdk::{
-            feature_set, genesis_config::create_genesis_config, pubkey::Pubkey,
-            sysvar::epoch_rewards::EpochRewards,
+            account::create_account_shared_data_for_test,
+            clock::Clock,
+            feature_set,
+            genesis_config::create_genesis_config,
+            pubkey::Pubkey,
+            sysvar::{self, epoch_rewards::EpochRewards},
         },
         std::sync::Arc,
     };
@@ -172,4 +201,38 @@ mod tests {
             expected_epoch_rewards,
         );
     }
+
+    #[test]
+    fn test_sysvar_cache_with_account_overrides() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(100_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        let bank_sysvar_cache = bank.get_sysvar_cache_for_tests();
+
+        let mut account_overrides = AccountOverrides::default();
+        account_overrides.set_account(
+            &Pubkey::new_unique(),
+            Some(create_account_shared_data_for_test(&Clock::default())),
+        );
+        assert!(bank
+            .sysvar_cache_with_account_overrides(&account_overrides)
+            .is_none());
+
+        let clock = Clock {
+            unix_timestamp: 1_700_000_000,
+            ..*bank_sysvar_cache.get_clock().unwrap()
+        };
+        account_overrides.set_account(
+            &sysvar::clock::id(),
+            Some(create_account_shared_data_for_test(&clock)),
+        );
+        let sysvar_cache = bank
+            .sysvar_cache_with_account_overrides(&account_overrides)
+            .unwrap();
+        assert_eq!(*sysvar_cache.get_clock().unwrap(), clock);
+        assert_eq!(sysvar_cache.get_rent(), bank_sysvar_cache.get_rent(),);
+        assert_eq!(
+            bank.get_sysvar_cache_for_tests().get_clock(),
+            bank_sysvar_cache.get_clock(),
+        );
+    }
 }
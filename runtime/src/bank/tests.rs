@@ -8,6 +8,7 @@ use {
     },
     crate::{
         accounts_background_service::{PrunedBanksRequestHandler, SendDroppedBankCallback},
+        accounts_fallback::{AccountsFallback, SharedAccountsFallback},
         bank_client::BankClient,
         bank_forks::BankForks,
         epoch_rewards_hasher::hash_rewards_into_partitions,
@@ -201,6 +202,8 @@ fn new_execution_result(
             durable_nonce_fee: nonce.map(DurableNonceFee::from),
             return_data: None,
             executed_units: 0,
+            instruction_consumed_units: None,
+            execution_traces: None,
             accounts_data_len_delta: 0,
         },
         programs_modified_by_tx: Box::<LoadedProgramsForTxBatch>::default(),
@@ -13730,3 +13733,457 @@ fn test_filter_executable_program_accounts_invalid_blockhash() {
     );
     assert_eq!(lock_results[1].0, Err(TransactionError::BlockhashNotFound));
 }
+
+#[derive(Debug, Default)]
+struct TestAccountsFallback {
+    accounts: HashMap<Pubkey, AccountSharedData>,
+    loaded_pubkeys: Mutex<Vec<Pubkey>>,
+}
+
+impl AccountsFallback for TestAccountsFallback {
+    fn load_accounts(&self, pubkeys: &[Pubkey]) -> Vec<(Pubkey, AccountSharedData)> {
+        self.loaded_pubkeys.lock().unwrap().extend(pubkeys);
+        pubkeys
+            .iter()
+            .filter_map(|pubkey| Some((*pubkey, self.accounts.get(pubkey)?.clone())))
+            .collect()
+    }
+}
+
+#[test]
+fn test_load_missing_accounts_from_fallback() {
+    solana_logger::setup();
+    let (genesis_config, mint_keypair) = create_genesis_config(sol_to_lamports(10.));
+    let amount = genesis_config.rent.minimum_balance(0);
+    let fallback_lamports = sol_to_lamports(1.);
+    let stored_pubkey = Pubkey::new_unique();
+    let simulated_pubkey = Pubkey::new_unique();
+    let accounts_fallback = Arc::new(TestAccountsFallback {
+        accounts: [stored_pubkey, simulated_pubkey]
+            .into_iter()
+            .map(|pubkey| {
+                let account = AccountSharedData::new(fallback_lamports, 0, &system_program::id());
+                (pubkey, account)
+            })
+            .collect(),
+        ..TestAccountsFallback::default()
+    });
+    let runtime_config = RuntimeConfig {
+        accounts_fallback: Some(SharedAccountsFallback::new(accounts_fallback.clone())),
+        ..RuntimeConfig::default()
+    };
+    let bank = Bank::new_with_runtime_config_for_tests(&genesis_config, Arc::new(runtime_config));
+    let capitalization = bank.capitalization();
+
+    // Unfrozen banks store the accounts they load from the fallback
+    bank.transfer(amount, &mint_keypair, &stored_pubkey)
+        .unwrap();
+    assert_eq!(bank.get_balance(&stored_pubkey), fallback_lamports + amount);
+    assert_eq!(bank.capitalization(), capitalization + fallback_lamports);
+    assert_eq!(
+        *accounts_fallback.loaded_pubkeys.lock().unwrap(),
+        vec![stored_pubkey]
+    );
+
+    // Accounts the fallback does not have are created as usual
+    let new_pubkey = Pubkey::new_unique();
+    bank.transfer(amount, &mint_keypair, &new_pubkey).unwrap();
+    assert_eq!(bank.get_balance(&new_pubkey), amount);
+    assert_eq!(
+        *accounts_fallback.loaded_pubkeys.lock().unwrap(),
+        vec![stored_pubkey, new_pubkey]
+    );
+
+    // Frozen banks only use the accounts they load from the fallback for simulation
+    bank.freeze();
+    let transaction = system_transaction::transfer(
+        &mint_keypair,
+        &simulated_pubkey,
+        amount,
+        bank.last_blockhash(),
+    );
+    let simulation_result = bank.simulate_transaction(
+        SanitizedTransaction::from_transaction_for_tests(transaction),
+    );
+    assert_eq!(simulation_result.result, Ok(()));
+    let (_, simulated_account) = simulation_result
+        .post_simulation_accounts
+        .iter()
+        .find(|(pubkey, _)| pubkey == &simulated_pubkey)
+        .unwrap();
+    assert_eq!(simulated_account.lamports(), fallback_lamports + amount);
+    assert_eq!(bank.get_account(&simulated_pubkey), None);
+}
+
+#[test]
+fn test_load_closed_account_from_fallback() {
+    solana_logger::setup();
+    let (genesis_config, mint_keypair) = create_genesis_config(sol_to_lamports(10.));
+    let amount = genesis_config.rent.minimum_balance(0);
+    let fallback_lamports = sol_to_lamports(1.);
+    let forked_keypair = Keypair::new();
+    let forked_pubkey = forked_keypair.pubkey();
+    let accounts_fallback = Arc::new(TestAccountsFallback {
+        accounts: HashMap::from([(
+            forked_pubkey,
+            AccountSharedData::new(fallback_lamports, 0, &system_program::id()),
+        )]),
+        ..TestAccountsFallback::default()
+    });
+    let runtime_config = RuntimeConfig {
+        accounts_fallback: Some(SharedAccountsFallback::new(accounts_fallback.clone())),
+        ..RuntimeConfig::default()
+    };
+    let parent = Arc::new(Bank::new_with_runtime_config_for_tests(
+        &genesis_config,
+        Arc::new(runtime_config),
+    ));
+
+    // Close the forked account by moving all of its lamports out
+    let transaction = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(
+            &forked_pubkey,
+            &mint_keypair.pubkey(),
+            fallback_lamports,
+        )],
+        Some(&mint_keypair.pubkey()),
+        &[&mint_keypair, &forked_keypair],
+        parent.last_blockhash(),
+    );
+    parent.process_transaction(&transaction).unwrap();
+    assert_eq!(parent.get_account(&forked_pubkey), None);
+    assert_eq!(
+        *accounts_fallback.loaded_pubkeys.lock().unwrap(),
+        vec![forked_pubkey]
+    );
+
+    // Referencing the closed account again, in the same bank or a child, does not load it from
+    // the fallback again
+    parent
+        .transfer(amount, &mint_keypair, &forked_pubkey)
+        .unwrap();
+    assert_eq!(parent.get_balance(&forked_pubkey), amount);
+    parent.freeze();
+    let bank = Bank::new_from_parent(parent, &Pubkey::default(), 1);
+    let transaction = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(
+            &forked_pubkey,
+            &mint_keypair.pubkey(),
+            amount,
+        )],
+        Some(&mint_keypair.pubkey()),
+        &[&mint_keypair, &forked_keypair],
+        bank.last_blockhash(),
+    );
+    bank.process_transaction(&transaction).unwrap();
+    assert_eq!(bank.get_account(&forked_pubkey), None);
+    bank.transfer(2 * amount, &mint_keypair, &forked_pubkey)
+        .unwrap();
+    assert_eq!(bank.get_balance(&forked_pubkey), 2 * amount);
+    assert_eq!(
+        *accounts_fallback.loaded_pubkeys.lock().unwrap(),
+        vec![forked_pubkey]
+    );
+}
+
+#[test]
+fn test_simulate_transaction_with_account_overrides() {
+    let (genesis_config, mint_keypair) = create_genesis_config(LAMPORTS_PER_SOL);
+    let parent = Arc::new(Bank::new_for_tests(&genesis_config));
+    let bank = Bank::new_from_parent(parent, &Pubkey::default(), 1);
+    bank.freeze();
+    let amount = genesis_config.rent.minimum_balance(0);
+
+    // A fee payer that only exists in the overrides
+    let payer = Keypair::new();
+    let recipient = solana_sdk::pubkey::new_rand();
+    let transaction = SanitizedTransaction::from_transaction_for_tests(
+        system_transaction::transfer(&payer, &recipient, amount, bank.last_blockhash()),
+    );
+    assert_eq!(
+        bank.simulate_transaction(transaction.clone()).result,
+        Err(TransactionError::AccountNotFound)
+    );
+    let mut account_overrides = AccountOverrides::default();
+    account_overrides.set_account(
+        &payer.pubkey(),
+        Some(AccountSharedData::new(
+            LAMPORTS_PER_SOL,
+            0,
+            &system_program::id(),
+        )),
+    );
+    let simulation_result = bank.simulate_transaction_with_account_overrides(
+        transaction.clone(),
+        &account_overrides,
+        true,
+        false,
+    );
+    assert_eq!(simulation_result.result, Ok(()));
+    assert_eq!(
+        simulation_result.instruction_units_consumed,
+        Some(vec![150])
+    );
+    assert_eq!(simulation_result.inner_instructions, Some(vec![vec![]]));
+
+    // Inner instructions are only recorded on request
+    let simulation_result = bank.simulate_transaction_with_account_overrides(
+        transaction,
+        &account_overrides,
+        false,
+        false,
+    );
+    assert_eq!(simulation_result.result, Ok(()));
+    assert_eq!(
+        simulation_result.instruction_units_consumed,
+        Some(vec![150])
+    );
+    assert_eq!(simulation_result.inner_instructions, None);
+    let (_, recipient_account) = simulation_result
+        .post_simulation_accounts
+        .iter()
+        .find(|(pubkey, _)| pubkey == &recipient)
+        .unwrap();
+    assert_eq!(recipient_account.lamports(), amount);
+    assert_eq!(bank.get_account(&payer.pubkey()), None);
+
+    // A program that only exists in the overrides
+    let program_id = solana_sdk::pubkey::new_rand();
+    let transaction = SanitizedTransaction::from_transaction_for_tests(Transaction::new(
+        &[&mint_keypair],
+        Message::new(
+            &[Instruction::new_with_bytes(program_id, &[], vec![])],
+            Some(&mint_keypair.pubkey()),
+        ),
+        bank.last_blockhash(),
+    ));
+    assert_eq!(
+        bank.simulate_transaction(transaction.clone()).result,
+        Err(TransactionError::ProgramAccountNotFound)
+    );
+    let mut elf = Vec::new();
+    File::open("../programs/bpf_loader/test_elfs/out/noop_aligned.so")
+        .unwrap()
+        .read_to_end(&mut elf)
+        .unwrap();
+    let program_account = AccountSharedData::from(Account {
+        lamports: genesis_config.rent.minimum_balance(elf.len()),
+        data: elf,
+        owner: bpf_loader::id(),
+        executable: true,
+        rent_epoch: 0,
+    });
+    let mut account_overrides = AccountOverrides::default();
+    account_overrides.set_account(&program_id, Some(program_account));
+    let simulation_result = bank.simulate_transaction_with_account_overrides(
+        transaction.clone(),
+        &account_overrides,
+        false,
+        false,
+    );
+    assert_eq!(simulation_result.result, Ok(()));
+    assert_matches!(
+        simulation_result.instruction_units_consumed.as_deref(),
+        Some([units]) if *units > 0
+    );
+
+    // The overridden program never reaches the global program cache
+    assert!(!bank
+        .loaded_programs_cache
+        .read()
+        .unwrap()
+        .get_entries_sorted_by_tx_usage(true, true)
+        .iter()
+        .any(|(pubkey, _)| pubkey == &program_id));
+    assert_eq!(
+        bank.simulate_transaction(transaction).result,
+        Err(TransactionError::ProgramAccountNotFound)
+    );
+}
+
+#[test]
+fn test_simulate_bundle() {
+    let (genesis_config, mint_keypair) = create_genesis_config(LAMPORTS_PER_SOL);
+    let bank = Bank::new_for_tests(&genesis_config);
+    bank.freeze();
+    let amount = genesis_config.rent.minimum_balance(0);
+
+    // The second transfer is paid by the account that the first one creates
+    let payer = Keypair::new();
+    let recipient = solana_sdk::pubkey::new_rand();
+    let failed_transfer =
+        SanitizedTransaction::from_transaction_for_tests(system_transaction::transfer(
+            &mint_keypair,
+            &recipient,
+            2 * LAMPORTS_PER_SOL,
+            bank.last_blockhash(),
+        ));
+    let funding_transfer =
+        SanitizedTransaction::from_transaction_for_tests(system_transaction::transfer(
+            &mint_keypair,
+            &payer.pubkey(),
+            3 * amount,
+            bank.last_blockhash(),
+        ));
+    let dependent_transfer = SanitizedTransaction::from_transaction_for_tests(
+        system_transaction::transfer(&payer, &recipient, amount, bank.last_blockhash()),
+    );
+    let fee = bank
+        .get_fee_for_message(funding_transfer.message())
+        .unwrap();
+    assert_eq!(
+        bank.simulate_transaction(dependent_transfer.clone()).result,
+        Err(TransactionError::AccountNotFound)
+    );
+
+    let simulation_results = bank.simulate_bundle(
+        vec![funding_transfer.clone(), dependent_transfer.clone()],
+        &AccountOverrides::default(),
+        true,
+        false,
+        false,
+    );
+    assert_eq!(simulation_results.len(), 2);
+    assert_eq!(simulation_results[0].result, Ok(()));
+    assert_eq!(simulation_results[1].result, Ok(()));
+    let (_, recipient_account) = simulation_results[1]
+        .post_simulation_accounts
+        .iter()
+        .find(|(pubkey, _)| pubkey == &recipient)
+        .unwrap();
+    assert_eq!(recipient_account.lamports(), amount);
+    assert_eq!(bank.get_account(&payer.pubkey()), None);
+    assert_eq!(bank.get_account(&recipient), None);
+
+    // Stops at the failed transaction
+    let simulation_results = bank.simulate_bundle(
+        vec![
+            failed_transfer.clone(),
+            funding_transfer.clone(),
+            dependent_transfer.clone(),
+        ],
+        &AccountOverrides::default(),
+        true,
+        false,
+        false,
+    );
+    assert_eq!(simulation_results.len(), 1);
+    assert_eq!(
+        simulation_results[0].result,
+        Err(TransactionError::InstructionError(
+            0,
+            SystemError::ResultWithNegativeLamports.into()
+        ))
+    );
+
+    // Carries on after the failed transaction, which still charges its fee
+    let simulation_results = bank.simulate_bundle(
+        vec![
+            failed_transfer,
+            funding_transfer.clone(),
+            dependent_transfer.clone(),
+        ],
+        &AccountOverrides::default(),
+        false,
+        false,
+        false,
+    );
+    assert_eq!(simulation_results.len(), 3);
+    assert!(simulation_results[0].result.is_err());
+    assert_eq!(simulation_results[1].result, Ok(()));
+    assert_eq!(simulation_results[2].result, Ok(()));
+    let (_, mint_account) = simulation_results[1]
+        .post_simulation_accounts
+        .iter()
+        .find(|(pubkey, _)| pubkey == &mint_keypair.pubkey())
+        .unwrap();
+    assert_eq!(
+        mint_account.lamports(),
+        bank.get_balance(&mint_keypair.pubkey()) - 2 * fee - 3 * amount
+    );
+
+    // Duplicates of a transaction are not executed
+    let simulation_results = bank.simulate_bundle(
+        vec![
+            funding_transfer.clone(),
+            funding_transfer,
+            dependent_transfer,
+        ],
+        &AccountOverrides::default(),
+        false,
+        false,
+        false,
+    );
+    assert_eq!(simulation_results.len(), 3);
+    assert_eq!(simulation_results[0].result, Ok(()));
+    assert_eq!(
+        simulation_results[1].result,
+        Err(TransactionError::AlreadyProcessed)
+    );
+    assert_eq!(simulation_results[1].units_consumed, 0);
+    assert_eq!(simulation_results[2].result, Ok(()));
+}
+
+#[test]
+fn test_address_loader_with_account_overrides() {
+    use solana_sdk::{
+        address_lookup_table::{
+            self,
+            state::{AddressLookupTable, LookupTableMeta},
+        },
+        message::{
+            v0::{LoadedAddresses, MessageAddressTableLookup},
+            AddressLoaderError,
+        },
+        transaction::AddressLoader,
+    };
+    use std::borrow::Cow;
+
+    let (genesis_config, _mint_keypair) = create_genesis_config(LAMPORTS_PER_SOL);
+    let parent = Arc::new(Bank::new_for_tests(&genesis_config));
+    let bank = Bank::new_from_parent(parent, &Pubkey::default(), 1);
+    let table_key = Pubkey::new_unique();
+    let table_addresses = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+    let table_account = AccountSharedData::create(
+        1,
+        AddressLookupTable {
+            meta: LookupTableMeta::default(),
+            addresses: Cow::Owned(table_addresses.clone()),
+        }
+        .serialize_for_tests()
+        .unwrap(),
+        address_lookup_table::program::id(),
+        false,
+        0,
+    );
+    let address_table_lookups = [MessageAddressTableLookup {
+        account_key: table_key,
+        writable_indexes: vec![0],
+        readonly_indexes: vec![1],
+    }];
+
+    // The table is only in the overrides
+    let mut account_overrides = AccountOverrides::default();
+    assert_eq!(
+        bank.address_loader_with_account_overrides(&account_overrides)
+            .load_addresses(&address_table_lookups),
+        Err(AddressLoaderError::LookupTableAccountNotFound)
+    );
+    account_overrides.set_account(&table_key, Some(table_account));
+    assert_eq!(
+        bank.address_loader_with_account_overrides(&account_overrides)
+            .load_addresses(&address_table_lookups),
+        Ok(LoadedAddresses {
+            writable: vec![table_addresses[0]],
+            readonly: vec![table_addresses[1]],
+        })
+    );
+
+    // Tables closed in the overrides are not found
+    account_overrides.set_account(&table_key, Some(AccountSharedData::default()));
+    assert_eq!(
+        bank.address_loader_with_account_overrides(&account_overrides)
+            .load_addresses(&address_table_lookups),
+        Err(AddressLoaderError::LookupTableAccountNotFound)
+    );
+}
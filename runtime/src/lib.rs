@@ -5,6 +5,7 @@
 extern crate lazy_static;
 
 pub mod accounts_background_service;
+pub mod accounts_fallback;
 pub mod bank;
 pub mod bank_client;
 pub mod bank_forks;
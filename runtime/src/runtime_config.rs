@@ -1,4 +1,7 @@
-use solana_program_runtime::compute_budget::ComputeBudget;
+use {
+    crate::accounts_fallback::SharedAccountsFallback,
+    solana_program_runtime::compute_budget::ComputeBudget,
+};
 
 /// Encapsulates flags that can be used to tweak the runtime behavior.
 #[derive(AbiExample, Debug, Default, Clone)]
@@ -6,4 +9,10 @@ pub struct RuntimeConfig {
     pub compute_budget: Option<ComputeBudget>,
     pub log_messages_bytes_limit: Option<usize>,
     pub transaction_account_lock_limit: Option<usize>,
+    /// Where to load the accounts that transactions use but the bank does not have from, see
+    /// `accounts_fallback`
+    pub accounts_fallback: Option<SharedAccountsFallback>,
+    /// Compile programs with instruction tracing, so that simulations record the execution traces
+    /// of the SBF programs they invoke.  Slows down the execution of all programs.
+    pub enable_instruction_tracing: bool,
 }
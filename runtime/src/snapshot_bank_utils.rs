@@ -276,6 +276,81 @@ pub fn bank_from_snapshot_archives(
     accounts_db_config: Option<AccountsDbConfig>,
     accounts_update_notifier: Option<AccountsUpdateNotifier>,
     exit: Arc<AtomicBool>,
+) -> snapshot_utils::Result<(Bank, BankFromArchiveTimings)> {
+    let snapshot_archive_paths = || {
+        std::iter::once(full_snapshot_archive_info.path().as_path()).chain(
+            incremental_snapshot_archive_info.map(|incremental_snapshot_archive_info| {
+                incremental_snapshot_archive_info.path().as_path()
+            }),
+        )
+    };
+    let has_staged_unpacks =
+        snapshot_archive_paths().any(snapshot_utils::has_staged_snapshot_archive_unpack);
+    let load_bank = |use_staged_unpacks| {
+        load_bank_from_snapshot_archives(
+            account_paths,
+            &bank_snapshots_dir,
+            full_snapshot_archive_info,
+            incremental_snapshot_archive_info,
+            genesis_config,
+            runtime_config,
+            debug_keys.clone(),
+            additional_builtins,
+            account_secondary_indexes.clone(),
+            limit_load_slot_count_from_snapshot,
+            shrink_ratio,
+            test_hash_calculation,
+            accounts_db_skip_shrink,
+            accounts_db_force_initial_clean,
+            verify_index,
+            accounts_db_config.clone(),
+            accounts_update_notifier.clone(),
+            exit.clone(),
+            use_staged_unpacks,
+        )
+    };
+
+    let result = if has_staged_unpacks {
+        // The staged unpacks were made while the archives were still being downloaded, so if
+        // the bank does not verify, load it again from the downloaded archives.
+        load_bank(true).or_else(|err| {
+            warn!(
+                "Failed to load bank from the staged snapshot unpacks: {err}, unpacking the \
+                 snapshot archives instead"
+            );
+            for account_path in account_paths {
+                delete_contents_of_path(account_path);
+            }
+            load_bank(false)
+        })
+    } else {
+        load_bank(false)
+    };
+    snapshot_utils::remove_staged_snapshot_archive_unpacks(snapshot_archive_paths(), account_paths);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_bank_from_snapshot_archives(
+    account_paths: &[PathBuf],
+    bank_snapshots_dir: impl AsRef<Path>,
+    full_snapshot_archive_info: &FullSnapshotArchiveInfo,
+    incremental_snapshot_archive_info: Option<&IncrementalSnapshotArchiveInfo>,
+    genesis_config: &GenesisConfig,
+    runtime_config: &RuntimeConfig,
+    debug_keys: Option<Arc<HashSet<Pubkey>>>,
+    additional_builtins: Option<&[BuiltinPrototype]>,
+    account_secondary_indexes: AccountSecondaryIndexes,
+    limit_load_slot_count_from_snapshot: Option<usize>,
+    shrink_ratio: AccountShrinkThreshold,
+    test_hash_calculation: bool,
+    accounts_db_skip_shrink: bool,
+    accounts_db_force_initial_clean: bool,
+    verify_index: bool,
+    accounts_db_config: Option<AccountsDbConfig>,
+    accounts_update_notifier: Option<AccountsUpdateNotifier>,
+    exit: Arc<AtomicBool>,
+    use_staged_unpacks: bool,
 ) -> snapshot_utils::Result<(Bank, BankFromArchiveTimings)> {
     info!(
         "Loading bank from full snapshot archive: {}, and incremental snapshot archive: {:?}",
@@ -290,11 +365,12 @@ pub fn bank_from_snapshot_archives(
     );
 
     let (unarchived_full_snapshot, mut unarchived_incremental_snapshot, next_append_vec_id) =
-        verify_and_unarchive_snapshots(
+        snapshot_utils::verify_and_unarchive_or_take_staged_snapshots(
             bank_snapshots_dir,
             full_snapshot_archive_info,
             incremental_snapshot_archive_info,
             account_paths,
+            use_staged_unpacks,
         )?;
 
     let mut storage = unarchived_full_snapshot.storage;
@@ -371,6 +447,9 @@ pub fn bank_from_snapshot_archives(
         base,
     ) && limit_load_slot_count_from_snapshot.is_none()
     {
+        if use_staged_unpacks {
+            return Err(SnapshotError::VerifyBank(bank.slot()));
+        }
         panic!("Snapshot bank for slot {} failed to verify", bank.slot());
     }
     measure_verify.stop();
@@ -1340,6 +1419,94 @@ mod tests {
         assert_eq!(original_bank, roundtrip_bank);
     }
 
+    /// Test loading a bank from a full snapshot archive whose unpack was staged while it was being
+    /// "downloaded", both when the staged unpack is intact and when it is corrupt and loading has
+    /// to fall back to unpacking the archive
+    #[test]
+    fn test_bank_from_staged_snapshot_archive_unpack() {
+        for corrupt_staged_unpack in [false, true] {
+            let genesis_config = GenesisConfig::default();
+            let original_bank = Bank::new_for_tests(&genesis_config);
+
+            while !original_bank.is_complete() {
+                original_bank.register_unique_tick();
+            }
+
+            let (_tmp_dir, accounts_dir) = create_tmp_accounts_dir_for_tests();
+            let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+            let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+            let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+            let snapshot_archive_format = ArchiveFormat::TarZstd;
+
+            let snapshot_archive_info = bank_to_full_snapshot_archive(
+                &bank_snapshots_dir,
+                &original_bank,
+                None,
+                full_snapshot_archives_dir.path(),
+                incremental_snapshot_archives_dir.path(),
+                snapshot_archive_format,
+                snapshot_utils::DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+                snapshot_utils::DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            )
+            .unwrap();
+
+            let account_paths = [accounts_dir.clone()];
+            snapshot_utils::stage_snapshot_archive_unpack(
+                fs_err::File::open(snapshot_archive_info.path()).unwrap(),
+                snapshot_archive_format,
+                snapshot_archive_info.path(),
+                &account_paths,
+            )
+            .unwrap();
+            assert!(snapshot_utils::has_staged_snapshot_archive_unpack(
+                snapshot_archive_info.path()
+            ));
+            let staged_account_dir = accounts_dir
+                .parent()
+                .unwrap()
+                .join(snapshot_utils::STAGED_SNAPSHOT_UNPACKS_DIR)
+                .join(snapshot_archive_info.path().file_name().unwrap());
+            let staged_account_files: Vec<_> = fs_err::read_dir(&staged_account_dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect();
+            assert!(!staged_account_files.is_empty());
+            if corrupt_staged_unpack {
+                for staged_account_file in staged_account_files {
+                    fs_err::write(staged_account_file, b"").unwrap();
+                }
+            }
+
+            let (roundtrip_bank, _) = bank_from_snapshot_archives(
+                &account_paths,
+                bank_snapshots_dir.path(),
+                &snapshot_archive_info,
+                None,
+                &genesis_config,
+                &RuntimeConfig::default(),
+                None,
+                None,
+                AccountSecondaryIndexes::default(),
+                None,
+                AccountShrinkThreshold::default(),
+                false,
+                false,
+                false,
+                false,
+                Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+                None,
+                Arc::default(),
+            )
+            .unwrap();
+            roundtrip_bank.wait_for_initial_accounts_hash_verification_completed_for_tests();
+            assert_eq!(original_bank, roundtrip_bank);
+            assert!(!snapshot_utils::has_staged_snapshot_archive_unpack(
+                snapshot_archive_info.path()
+            ));
+            assert!(!staged_account_dir.exists());
+        }
+    }
+
     /// Test roundtrip of bank to a full snapshot, then back again.  This test is more involved
     /// than the simple version above; creating multiple banks over multiple slots and doing
     /// multiple transfers.  So this full snapshot should contain more data.
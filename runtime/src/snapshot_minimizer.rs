@@ -3,7 +3,7 @@
 use {
     crate::{bank::Bank, builtins::BUILTINS, static_ids},
     dashmap::DashSet,
-    log::info,
+    log::{info, warn},
     rayon::{
         iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator},
         prelude::ParallelSlice,
@@ -12,7 +12,10 @@ use {
         accounts_db::{
             AccountStorageEntry, AccountsDb, GetUniqueAccountsResult, PurgeStats, StoreReclaims,
         },
+        accounts_index::ScanConfig,
         accounts_partition,
+        inline_spl_token::{self, GenericTokenAccount},
+        inline_spl_token_2022,
     },
     solana_measure::measure,
     solana_sdk::{
@@ -20,11 +23,12 @@ use {
         account_utils::StateMut,
         bpf_loader_upgradeable::{self, UpgradeableLoaderState},
         clock::Slot,
-        pubkey::Pubkey,
+        pubkey::{Pubkey, MAX_SEEDS},
         sdk_ids,
     },
     std::{
         collections::HashSet,
+        str::FromStr,
         sync::{
             atomic::{AtomicUsize, Ordering},
             Arc, Mutex,
@@ -32,6 +36,120 @@ use {
     },
 };
 
+/// Accounts to keep in, or remove from, a minimized snapshot in addition to those needed to replay
+/// the slot range
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotMinimizerFilters {
+    /// Programs to keep, along with all the accounts they own
+    pub include_programs: HashSet<Pubkey>,
+    /// Program derived addresses to keep
+    pub include_pdas: Vec<PdaSeedPattern>,
+    /// Mints to keep, along with all the token accounts of them
+    pub include_token_mints: HashSet<Pubkey>,
+    /// Accounts to keep
+    pub include_accounts: HashSet<Pubkey>,
+    /// Programs whose accounts are removed
+    pub exclude_programs: HashSet<Pubkey>,
+    /// Accounts to remove
+    pub exclude_accounts: HashSet<Pubkey>,
+}
+
+impl SnapshotMinimizerFilters {
+    pub fn is_empty(&self) -> bool {
+        self.include_programs.is_empty()
+            && self.include_pdas.is_empty()
+            && self.include_token_mints.is_empty()
+            && self.include_accounts.is_empty()
+            && self.exclude_programs.is_empty()
+            && self.exclude_accounts.is_empty()
+    }
+}
+
+/// A seed of a program derived address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PdaSeed {
+    Bytes(Vec<u8>),
+    /// Each of the other accounts kept in the snapshot, e.g. the wallets of the kept token
+    /// accounts
+    AnyAccount,
+}
+
+/// The seeds of program derived addresses of `program_id`, parsed from
+/// `PROGRAM_ID:SEED,SEED,...` where each seed is `str:TEXT`, `pubkey:PUBKEY`, `u64:NUMBER` (little
+/// endian), or `*` for any account kept in the snapshot.  At most one seed may be `*`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PdaSeedPattern {
+    pub program_id: Pubkey,
+    pub seeds: Vec<PdaSeed>,
+}
+
+impl FromStr for PdaSeedPattern {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        let (program_id, seeds) = pattern
+            .split_once(':')
+            .ok_or_else(|| format!("missing seeds in PDA pattern {pattern}"))?;
+        let program_id = Pubkey::from_str(program_id)
+            .map_err(|err| format!("invalid program id {program_id}: {err}"))?;
+        let seeds = seeds
+            .split(',')
+            .map(|seed| match seed.split_once(':') {
+                _ if seed == "*" => Ok(PdaSeed::AnyAccount),
+                Some(("str", text)) => Ok(PdaSeed::Bytes(text.as_bytes().to_vec())),
+                Some(("pubkey", pubkey)) => Pubkey::from_str(pubkey)
+                    .map(|pubkey| PdaSeed::Bytes(pubkey.to_bytes().to_vec()))
+                    .map_err(|err| format!("invalid pubkey seed {pubkey}: {err}")),
+                Some(("u64", number)) => u64::from_str(number)
+                    .map(|number| PdaSeed::Bytes(number.to_le_bytes().to_vec()))
+                    .map_err(|err| format!("invalid u64 seed {number}: {err}")),
+                _ => Err(format!("invalid seed {seed}")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // One more seed is taken by the bump seed
+        if seeds.len() >= MAX_SEEDS {
+            return Err(format!(
+                "more than {} seeds in PDA pattern {pattern}",
+                MAX_SEEDS - 1
+            ));
+        }
+        if seeds
+            .iter()
+            .filter(|seed| **seed == PdaSeed::AnyAccount)
+            .count()
+            > 1
+        {
+            return Err(format!("more than one `*` seed in PDA pattern {pattern}"));
+        }
+        Ok(Self { program_id, seeds })
+    }
+}
+
+impl PdaSeedPattern {
+    /// The addresses matching the pattern, substituting each of `accounts` for a `*` seed
+    fn addresses(&self, accounts: &DashSet<Pubkey>) -> Vec<Pubkey> {
+        let find_address = |any_account: Option<&Pubkey>| {
+            let seeds: Vec<&[u8]> = self
+                .seeds
+                .iter()
+                .map(|seed| match seed {
+                    PdaSeed::Bytes(bytes) => bytes.as_slice(),
+                    PdaSeed::AnyAccount => any_account.unwrap().as_ref(),
+                })
+                .collect();
+            Pubkey::try_find_program_address(&seeds, &self.program_id).map(|(address, _)| address)
+        };
+        if self.seeds.contains(&PdaSeed::AnyAccount) {
+            accounts
+                .par_iter()
+                .filter_map(|account| find_address(Some(account.key())))
+                .collect()
+        } else {
+            find_address(None).into_iter().collect()
+        }
+    }
+}
+
 /// Used to modify bank and accounts_db to create a minimized snapshot
 pub struct SnapshotMinimizer<'a> {
     bank: &'a Bank,
@@ -45,6 +163,11 @@ impl<'a> SnapshotMinimizer<'a> {
     /// `transaction_account_set` should contain accounts used in transactions in the slot range [starting_slot, ending_slot].
     /// This function will accumulate other accounts (rent colleciton, builtins, etc) necessary to replay transactions.
     ///
+    /// The accounts included by `filters` are kept as well, then the accounts excluded by `filters`
+    /// are removed, except for those needed to replay the slot range (the transaction and rent
+    /// collection accounts) and those the bank cannot be loaded without (features, builtins,
+    /// sysvars, vote and stake accounts, and the owners and programdata of the kept accounts).
+    ///
     /// This function will modify accounts_db by removing accounts not needed to replay [starting_slot, ending_slot],
     /// and update the bank's capitalization.
     pub fn minimize(
@@ -52,6 +175,7 @@ impl<'a> SnapshotMinimizer<'a> {
         starting_slot: Slot,
         ending_slot: Slot,
         transaction_account_set: DashSet<Pubkey>,
+        filters: &SnapshotMinimizerFilters,
     ) {
         let minimizer = SnapshotMinimizer {
             bank,
@@ -60,16 +184,41 @@ impl<'a> SnapshotMinimizer<'a> {
             minimized_account_set: transaction_account_set,
         };
 
+        minimizer.add_accounts(
+            Self::get_rent_collection_accounts,
+            "rent collection accounts",
+        );
+        // The accounts needed to replay the slot range are never excluded
+        let replay_account_set: HashSet<_> = minimizer
+            .minimized_account_set
+            .iter()
+            .map(|pubkey| *pubkey)
+            .collect();
+
+        minimizer.add_accounts(
+            |minimizer| minimizer.get_included_accounts(filters),
+            "included accounts",
+        );
+        minimizer.add_accounts(
+            |minimizer| minimizer.get_included_program_accounts(filters),
+            "included program accounts",
+        );
+        minimizer.add_accounts(
+            |minimizer| minimizer.get_included_token_accounts(filters),
+            "included token accounts",
+        );
+        // After the other accounts, so they can be substituted for `*` seeds
+        minimizer.add_accounts(
+            |minimizer| minimizer.get_included_pdas(filters),
+            "included program derived accounts",
+        );
+        minimizer.remove_excluded_accounts(filters, &replay_account_set);
+
         minimizer.add_accounts(Self::get_active_bank_features, "active bank features");
         minimizer.add_accounts(Self::get_inactive_bank_features, "inactive bank features");
         minimizer.add_accounts(Self::get_builtins, "builtin accounts");
         minimizer.add_accounts(Self::get_static_runtime_accounts, "static runtime accounts");
         minimizer.add_accounts(Self::get_sdk_accounts, "sdk accounts");
-
-        minimizer.add_accounts(
-            Self::get_rent_collection_accounts,
-            "rent collection accounts",
-        );
         minimizer.add_accounts(Self::get_vote_accounts, "vote accounts");
         minimizer.add_accounts(Self::get_stake_accounts, "stake accounts");
         minimizer.add_accounts(Self::get_owner_accounts, "owner accounts");
@@ -97,6 +246,121 @@ impl<'a> SnapshotMinimizer<'a> {
         );
     }
 
+    /// Used to get the accounts included by `filters` in `minimize`
+    fn get_included_accounts(&self, filters: &SnapshotMinimizerFilters) {
+        filters.include_accounts.iter().for_each(|pubkey| {
+            self.minimized_account_set.insert(*pubkey);
+        });
+    }
+
+    /// Used to get the programs included by `filters` and the accounts they own in `minimize`
+    fn get_included_program_accounts(&self, filters: &SnapshotMinimizerFilters) {
+        filters.include_programs.par_iter().for_each(|program_id| {
+            self.minimized_account_set.insert(*program_id);
+            self.bank
+                .get_program_accounts(program_id, &ScanConfig::default())
+                .expect("scan program accounts")
+                .into_iter()
+                .for_each(|(pubkey, _account)| {
+                    self.minimized_account_set.insert(pubkey);
+                });
+        });
+    }
+
+    /// Used to get the mints included by `filters` and their token accounts in `minimize`
+    fn get_included_token_accounts(&self, filters: &SnapshotMinimizerFilters) {
+        if filters.include_token_mints.is_empty() {
+            return;
+        }
+        filters.include_token_mints.iter().for_each(|mint| {
+            self.minimized_account_set.insert(*mint);
+        });
+        let is_included_token_account = |account_data: &[u8], valid_account_data: bool| {
+            valid_account_data
+                && filters.include_token_mints.contains(
+                    inline_spl_token::Account::unpack_account_mint_unchecked(account_data),
+                )
+        };
+        [
+            (inline_spl_token::id(), false),
+            (inline_spl_token_2022::id(), true),
+        ]
+        .into_iter()
+        .for_each(|(token_program_id, is_token_2022)| {
+            self.bank
+                .get_filtered_program_accounts(
+                    &token_program_id,
+                    |account| {
+                        let data = account.data();
+                        let valid_account_data = if is_token_2022 {
+                            inline_spl_token_2022::Account::valid_account_data(data)
+                        } else {
+                            inline_spl_token::Account::valid_account_data(data)
+                        };
+                        is_included_token_account(data, valid_account_data)
+                    },
+                    &ScanConfig::default(),
+                )
+                .expect("scan token accounts")
+                .into_iter()
+                .for_each(|(pubkey, _account)| {
+                    self.minimized_account_set.insert(pubkey);
+                });
+        });
+    }
+
+    /// Used to get the program derived addresses included by `filters` in `minimize`
+    /// Addresses of accounts which do not exist are skipped.
+    fn get_included_pdas(&self, filters: &SnapshotMinimizerFilters) {
+        let pdas: Vec<_> = filters
+            .include_pdas
+            .iter()
+            .flat_map(|pattern| pattern.addresses(&self.minimized_account_set))
+            .collect();
+        pdas.into_par_iter()
+            .filter(|pubkey| self.bank.get_account(pubkey).is_some())
+            .for_each(|pubkey| {
+                self.minimized_account_set.insert(pubkey);
+            });
+    }
+
+    /// Removes the accounts excluded by `filters` from `minimized_account_set`, except for those in
+    /// `replay_account_set`
+    fn remove_excluded_accounts(
+        &self,
+        filters: &SnapshotMinimizerFilters,
+        replay_account_set: &HashSet<Pubkey>,
+    ) {
+        let initial_accounts_len = self.minimized_account_set.len();
+        let is_excluded = |pubkey: &Pubkey| {
+            filters.exclude_accounts.contains(pubkey)
+                || filters.exclude_programs.contains(pubkey)
+                || (!filters.exclude_programs.is_empty()
+                    && self.bank.get_account(pubkey).map_or(false, |account| {
+                        filters.exclude_programs.contains(account.owner())
+                    }))
+        };
+        let mut kept_accounts = 0;
+        self.minimized_account_set.retain(|pubkey| {
+            if !is_excluded(pubkey) {
+                true
+            } else if replay_account_set.contains(pubkey) {
+                kept_accounts += 1;
+                true
+            } else {
+                false
+            }
+        });
+        if kept_accounts > 0 {
+            warn!("Kept {kept_accounts} excluded accounts which are needed for replay");
+        }
+        let removed_accounts = initial_accounts_len - self.minimized_account_set.len();
+        info!(
+            "Removed {removed_accounts} excluded accounts for total of {} accounts",
+            self.minimized_account_set.len()
+        );
+    }
+
     /// Used to get active bank feature accounts in `minimize`.
     fn get_active_bank_features(&self) {
         self.bank.feature_set.active.iter().for_each(|(pubkey, _)| {
@@ -407,19 +671,30 @@ impl<'a> SnapshotMinimizer<'a> {
 mod tests {
     use {
         crate::{
-            bank::Bank, genesis_utils::create_genesis_config_with_leader,
-            snapshot_minimizer::SnapshotMinimizer,
+            bank::Bank,
+            genesis_utils::create_genesis_config_with_leader,
+            runtime_config::RuntimeConfig,
+            snapshot_bank_utils::{bank_from_snapshot_archives, bank_to_full_snapshot_archive},
+            snapshot_minimizer::{
+                PdaSeed, PdaSeedPattern, SnapshotMinimizer, SnapshotMinimizerFilters,
+            },
+            snapshot_utils::{self, create_tmp_accounts_dir_for_tests, ArchiveFormat},
         },
         dashmap::DashSet,
+        solana_accounts_db::{
+            accounts_db::{AccountShrinkThreshold, ACCOUNTS_DB_CONFIG_FOR_TESTING},
+            accounts_index::AccountSecondaryIndexes,
+            inline_spl_token, inline_spl_token_2022,
+        },
         solana_sdk::{
             account::{AccountSharedData, ReadableAccount, WritableAccount},
             bpf_loader_upgradeable::{self, UpgradeableLoaderState},
             genesis_config::{create_genesis_config, GenesisConfig},
             pubkey::Pubkey,
             signer::Signer,
-            stake,
+            stake, system_program,
         },
-        std::sync::Arc,
+        std::{str::FromStr, sync::Arc},
     };
 
     #[test]
@@ -628,6 +903,192 @@ mod tests {
             .contains(&programdata_address));
     }
 
+    #[test]
+    fn test_pda_seed_pattern_from_str() {
+        let program_id = solana_sdk::pubkey::new_rand();
+        let pubkey = solana_sdk::pubkey::new_rand();
+        assert_eq!(
+            PdaSeedPattern::from_str(&format!(
+                "{program_id}:str:metadata,pubkey:{pubkey},*,u64:7"
+            ))
+            .unwrap(),
+            PdaSeedPattern {
+                program_id,
+                seeds: vec![
+                    PdaSeed::Bytes(b"metadata".to_vec()),
+                    PdaSeed::Bytes(pubkey.to_bytes().to_vec()),
+                    PdaSeed::AnyAccount,
+                    PdaSeed::Bytes(7u64.to_le_bytes().to_vec()),
+                ],
+            }
+        );
+        assert!(PdaSeedPattern::from_str(&program_id.to_string()).is_err());
+        assert!(PdaSeedPattern::from_str(&format!("{program_id}:*,*")).is_err());
+        assert!(PdaSeedPattern::from_str(&format!("{program_id}:hex:00")).is_err());
+        assert!(PdaSeedPattern::from_str("not-a-pubkey:str:metadata").is_err());
+    }
+
+    #[test]
+    fn test_minimize_with_filters() {
+        solana_logger::setup();
+
+        let (genesis_config, mint_keypair) = create_genesis_config(1_000_000);
+        let bank0 = Arc::new(Bank::new_for_tests(&genesis_config));
+        let new_account = |len: usize, owner: &Pubkey| {
+            AccountSharedData::new(genesis_config.rent.minimum_balance(len), len, owner)
+        };
+
+        // Accounts owned by an included program
+        let program_id = solana_sdk::pubkey::new_rand();
+        let program_accounts: Vec<_> = (0..3).map(|_| solana_sdk::pubkey::new_rand()).collect();
+        for pubkey in &program_accounts {
+            bank0.store_account(pubkey, &new_account(0, &program_id));
+        }
+
+        // Token accounts of an included mint and of another mint
+        let mint = solana_sdk::pubkey::new_rand();
+        let other_mint = solana_sdk::pubkey::new_rand();
+        let wallet = solana_sdk::pubkey::new_rand();
+        bank0.store_account(&wallet, &new_account(0, &system_program::id()));
+        let token_account = |mint: &Pubkey, token_program_id: &Pubkey, len: usize| {
+            let mut account = new_account(len, token_program_id);
+            account.data_as_mut_slice()[..32].copy_from_slice(mint.as_ref());
+            account.data_as_mut_slice()[32..64].copy_from_slice(wallet.as_ref());
+            if len > inline_spl_token::Account::get_packed_len() {
+                account.data_as_mut_slice()[inline_spl_token::Account::get_packed_len()] =
+                    inline_spl_token_2022::ACCOUNTTYPE_ACCOUNT;
+            }
+            account
+        };
+        let token_account_pubkey = solana_sdk::pubkey::new_rand();
+        let token_2022_account_pubkey = solana_sdk::pubkey::new_rand();
+        let other_token_account_pubkey = solana_sdk::pubkey::new_rand();
+        bank0.store_account(
+            &token_account_pubkey,
+            &token_account(&mint, &inline_spl_token::id(), 165),
+        );
+        bank0.store_account(
+            &token_2022_account_pubkey,
+            &token_account(&mint, &inline_spl_token_2022::id(), 200),
+        );
+        bank0.store_account(
+            &other_token_account_pubkey,
+            &token_account(&other_mint, &inline_spl_token::id(), 165),
+        );
+
+        // A PDA derived from the wallet of the token accounts, and one that does not exist
+        let pda_program_id = solana_sdk::pubkey::new_rand();
+        let (pda, _) = Pubkey::find_program_address(&[b"user", wallet.as_ref()], &pda_program_id);
+        bank0.store_account(&pda, &new_account(0, &pda_program_id));
+
+        // Accounts owned by an excluded program
+        let excluded_program_id = solana_sdk::pubkey::new_rand();
+        let excluded_program_accounts: Vec<_> =
+            (0..2).map(|_| solana_sdk::pubkey::new_rand()).collect();
+        for pubkey in &excluded_program_accounts {
+            bank0.store_account(pubkey, &new_account(0, &excluded_program_id));
+        }
+
+        // An account used by a transaction, which is excluded but needed for replay
+        let transaction_account = solana_sdk::pubkey::new_rand();
+        bank0.store_account(&transaction_account, &new_account(0, &program_id));
+
+        // An account which is neither needed nor included
+        let other_account = solana_sdk::pubkey::new_rand();
+        bank0.store_account(&other_account, &new_account(0, &system_program::id()));
+
+        bank0.fill_bank_with_ticks_for_tests();
+        bank0.squash();
+        bank0.force_flush_accounts_cache();
+
+        // Minimize the accounts stored before the snapshot slot
+        let bank1 = Bank::new_from_parent(bank0, &Pubkey::default(), 1);
+        bank1.fill_bank_with_ticks_for_tests();
+
+        let filters = SnapshotMinimizerFilters {
+            include_programs: [program_id].into(),
+            include_pdas: vec![
+                PdaSeedPattern::from_str(&format!("{pda_program_id}:str:user,*")).unwrap(),
+                PdaSeedPattern::from_str(&format!("{pda_program_id}:str:other")).unwrap(),
+            ],
+            include_token_mints: [mint].into(),
+            include_accounts: [wallet, excluded_program_accounts[0]].into(),
+            exclude_programs: [excluded_program_id].into(),
+            exclude_accounts: [program_accounts[0], transaction_account].into(),
+        };
+        let transaction_account_set = DashSet::new();
+        transaction_account_set.insert(mint_keypair.pubkey());
+        transaction_account_set.insert(transaction_account);
+        transaction_account_set.insert(excluded_program_accounts[1]);
+        SnapshotMinimizer::minimize(&bank1, 1, 1, transaction_account_set, &filters);
+
+        let (_tmp_dir, accounts_dir) = create_tmp_accounts_dir_for_tests();
+        let bank_snapshots_dir = tempfile::TempDir::new().unwrap();
+        let full_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let incremental_snapshot_archives_dir = tempfile::TempDir::new().unwrap();
+        let snapshot_archive_info = bank_to_full_snapshot_archive(
+            &bank_snapshots_dir,
+            &bank1,
+            None,
+            full_snapshot_archives_dir.path(),
+            incremental_snapshot_archives_dir.path(),
+            ArchiveFormat::Tar,
+            snapshot_utils::DEFAULT_MAX_FULL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+            snapshot_utils::DEFAULT_MAX_INCREMENTAL_SNAPSHOT_ARCHIVES_TO_RETAIN,
+        )
+        .unwrap();
+        let (minimized_bank, _) = bank_from_snapshot_archives(
+            &[accounts_dir],
+            bank_snapshots_dir.path(),
+            &snapshot_archive_info,
+            None,
+            &genesis_config,
+            &RuntimeConfig::default(),
+            None,
+            None,
+            AccountSecondaryIndexes::default(),
+            None,
+            AccountShrinkThreshold::default(),
+            false,
+            false,
+            false,
+            false,
+            Some(ACCOUNTS_DB_CONFIG_FOR_TESTING),
+            None,
+            Arc::default(),
+        )
+        .unwrap();
+        minimized_bank.wait_for_initial_accounts_hash_verification_completed_for_tests();
+
+        let mut kept_accounts = vec![
+            mint_keypair.pubkey(),
+            wallet,
+            token_account_pubkey,
+            token_2022_account_pubkey,
+            pda,
+            transaction_account,
+            excluded_program_accounts[1],
+        ];
+        kept_accounts.extend(&program_accounts[1..]);
+        for pubkey in kept_accounts {
+            assert!(
+                minimized_bank.get_account(&pubkey).is_some(),
+                "{pubkey} should be kept"
+            );
+        }
+        for pubkey in [
+            program_accounts[0],
+            other_token_account_pubkey,
+            excluded_program_accounts[0],
+            other_account,
+        ] {
+            assert!(
+                minimized_bank.get_account(&pubkey).is_none(),
+                "{pubkey} should be removed"
+            );
+        }
+    }
+
     #[test]
     fn test_minimize_accounts_db() {
         solana_logger::setup();
@@ -37,7 +37,7 @@ use {
         process::ExitStatus,
         str::FromStr,
         sync::{Arc, Mutex},
-        thread::{Builder, JoinHandle},
+        thread::{self, Builder, JoinHandle},
     },
     tar::{self, Archive},
     tempfile::TempDir,
@@ -55,6 +55,8 @@ pub const SNAPSHOT_VERSION_FILENAME: &str = "version";
 pub const SNAPSHOT_STATE_COMPLETE_FILENAME: &str = "state_complete";
 pub const SNAPSHOT_ACCOUNTS_HARDLINKS: &str = "accounts_hardlinks";
 pub const SNAPSHOT_ARCHIVE_DOWNLOAD_DIR: &str = "remote";
+pub const STAGED_SNAPSHOT_UNPACKS_DIR: &str = "staged_snapshot_unpacks";
+const STAGED_SNAPSHOT_UNPACK_COMPLETE_FILENAME: &str = "complete";
 pub const MAX_SNAPSHOT_DATA_FILE_SIZE: u64 = 32 * 1024 * 1024 * 1024; // 32 GiB
 const MAX_SNAPSHOT_VERSION_FILE_SIZE: u64 = 8; // byte
 const VERSION_STRING_V1_2_0: &str = "1.2.0";
@@ -331,6 +333,9 @@ pub enum SnapshotError {
     #[error("snapshot has mismatch: deserialized bank: {:?}, snapshot archive info: {:?}", .0, .1)]
     MismatchedSlotHash((Slot, SnapshotHash), (Slot, SnapshotHash)),
 
+    #[error("snapshot bank for slot {0} failed to verify")]
+    VerifyBank(Slot),
+
     #[error("snapshot slot deltas are invalid: {0}")]
     VerifySlotDeltas(#[from] VerifySlotDeltasError),
 
@@ -1238,6 +1243,29 @@ pub fn verify_and_unarchive_snapshots(
     UnarchivedSnapshot,
     Option<UnarchivedSnapshot>,
     AtomicAppendVecId,
+)> {
+    verify_and_unarchive_or_take_staged_snapshots(
+        bank_snapshots_dir,
+        full_snapshot_archive_info,
+        incremental_snapshot_archive_info,
+        account_paths,
+        false,
+    )
+}
+
+/// Like `verify_and_unarchive_snapshots()`, but if `use_staged_unpacks` is set, the files of
+/// archives already unpacked by `stage_snapshot_archive_unpack()` are taken from the staging
+/// directories instead of unpacking the archives again.
+pub(crate) fn verify_and_unarchive_or_take_staged_snapshots(
+    bank_snapshots_dir: impl AsRef<Path>,
+    full_snapshot_archive_info: &FullSnapshotArchiveInfo,
+    incremental_snapshot_archive_info: Option<&IncrementalSnapshotArchiveInfo>,
+    account_paths: &[PathBuf],
+    use_staged_unpacks: bool,
+) -> Result<(
+    UnarchivedSnapshot,
+    Option<UnarchivedSnapshot>,
+    AtomicAppendVecId,
 )> {
     check_are_snapshots_compatible(
         full_snapshot_archive_info,
@@ -1256,6 +1284,7 @@ pub fn verify_and_unarchive_snapshots(
         full_snapshot_archive_info.archive_format(),
         parallel_divisions,
         next_append_vec_id.clone(),
+        use_staged_unpacks,
     )?;
 
     let unarchived_incremental_snapshot =
@@ -1269,6 +1298,7 @@ pub fn verify_and_unarchive_snapshots(
                 incremental_snapshot_archive_info.archive_format(),
                 parallel_divisions,
                 next_append_vec_id.clone(),
+                use_staged_unpacks,
             )?;
             Some(unarchived_incremental_snapshot)
         } else {
@@ -1383,8 +1413,10 @@ fn create_snapshot_meta_files_for_unarchived_snapshot(unpack_dir: impl AsRef<Pat
 }
 
 /// Perform the common tasks when unarchiving a snapshot.  Handles creating the temporary
-/// directories, untaring, reading the version file, and then returning those fields plus the
+/// directories, untaring (or taking the staged unpack of the archive if `use_staged_unpack` is
+/// set and there is one), reading the version file, and then returning those fields plus the
 /// rebuilt storage
+#[allow(clippy::too_many_arguments)]
 fn unarchive_snapshot(
     bank_snapshots_dir: impl AsRef<Path>,
     unpacked_snapshots_dir_prefix: &'static str,
@@ -1394,6 +1426,7 @@ fn unarchive_snapshot(
     archive_format: ArchiveFormat,
     parallel_divisions: usize,
     next_append_vec_id: Arc<AtomicAppendVecId>,
+    use_staged_unpack: bool,
 ) -> Result<UnarchivedSnapshot> {
     let unpack_dir = tempfile::Builder::new()
         .prefix(unpacked_snapshots_dir_prefix)
@@ -1401,14 +1434,34 @@ fn unarchive_snapshot(
     let unpacked_snapshots_dir = unpack_dir.path().join("snapshots");
 
     let (file_sender, file_receiver) = crossbeam_channel::unbounded();
-    streaming_unarchive_snapshot(
-        file_sender,
-        account_paths.to_vec(),
-        unpack_dir.path().to_path_buf(),
-        snapshot_archive_path.as_ref().to_path_buf(),
-        archive_format,
-        parallel_divisions,
-    );
+    let staged_files = if use_staged_unpack {
+        take_staged_snapshot_archive_unpack(
+            snapshot_archive_path.as_ref(),
+            unpack_dir.path(),
+            account_paths,
+        )?
+    } else {
+        None
+    };
+    if let Some(staged_files) = staged_files {
+        info!(
+            "Taking the staged unpack of {}",
+            snapshot_archive_path.as_ref().display()
+        );
+        for file in staged_files {
+            file_sender.send(file)?;
+        }
+        drop(file_sender);
+    } else {
+        streaming_unarchive_snapshot(
+            file_sender,
+            account_paths.to_vec(),
+            unpack_dir.path().to_path_buf(),
+            snapshot_archive_path.as_ref().to_path_buf(),
+            archive_format,
+            parallel_divisions,
+        );
+    }
 
     let num_rebuilder_threads = num_cpus::get_physical()
         .saturating_sub(parallel_divisions)
@@ -1918,17 +1971,243 @@ fn untar_snapshot_create_shared_buffer(
     snapshot_tar: &Path,
     archive_format: ArchiveFormat,
 ) -> SharedBuffer {
-    let open_file = || fs_err::File::open(snapshot_tar).unwrap();
+    create_shared_buffer(fs_err::File::open(snapshot_tar).unwrap(), archive_format)
+}
+
+/// Decompresses `archive` into a buffer shared by the readers of the tar
+fn create_shared_buffer(
+    archive: impl Read + Send + 'static,
+    archive_format: ArchiveFormat,
+) -> SharedBuffer {
+    let archive = BufReader::new(archive);
     match archive_format {
-        ArchiveFormat::TarBzip2 => SharedBuffer::new(BzDecoder::new(BufReader::new(open_file()))),
-        ArchiveFormat::TarGzip => SharedBuffer::new(GzDecoder::new(BufReader::new(open_file()))),
-        ArchiveFormat::TarZstd => SharedBuffer::new(
-            zstd::stream::read::Decoder::new(BufReader::new(open_file())).unwrap(),
-        ),
-        ArchiveFormat::TarLz4 => {
-            SharedBuffer::new(lz4::Decoder::new(BufReader::new(open_file())).unwrap())
+        ArchiveFormat::TarBzip2 => SharedBuffer::new(BzDecoder::new(archive)),
+        ArchiveFormat::TarGzip => SharedBuffer::new(GzDecoder::new(archive)),
+        ArchiveFormat::TarZstd => {
+            SharedBuffer::new(zstd::stream::read::Decoder::new(archive).unwrap())
+        }
+        ArchiveFormat::TarLz4 => SharedBuffer::new(lz4::Decoder::new(archive).unwrap()),
+        ArchiveFormat::Tar => SharedBuffer::new(archive),
+    }
+}
+
+/// The directory that the files of `snapshot_archive_path` other than account files are staged
+/// in, next to the archive
+fn staged_snapshot_unpack_dir(snapshot_archive_path: &Path) -> Result<PathBuf> {
+    let archive_dir = snapshot_archive_path
+        .parent()
+        .ok_or_else(|| SnapshotError::InvalidSnapshotDirPath(snapshot_archive_path.into()))?;
+    let archive_file_name = snapshot_archive_path
+        .file_name()
+        .ok_or_else(|| SnapshotError::PathToFileNameError(snapshot_archive_path.into()))?;
+    Ok(archive_dir
+        .join(STAGED_SNAPSHOT_UNPACKS_DIR)
+        .join(archive_file_name))
+}
+
+/// The directories that the account files of `snapshot_archive_path` are staged in, one next to
+/// each of `account_paths` so they are on the same file system as the account path they are
+/// later moved into
+fn staged_snapshot_unpack_account_dirs(
+    snapshot_archive_path: &Path,
+    account_paths: &[PathBuf],
+) -> Result<Vec<PathBuf>> {
+    let archive_file_name = snapshot_archive_path
+        .file_name()
+        .ok_or_else(|| SnapshotError::PathToFileNameError(snapshot_archive_path.into()))?;
+    account_paths
+        .iter()
+        .map(|account_path| {
+            let staged_unpacks_dir = account_path
+                .parent()
+                .ok_or_else(|| SnapshotError::InvalidAccountPath(account_path.clone()))?
+                .join(STAGED_SNAPSHOT_UNPACKS_DIR);
+            Ok(staged_unpacks_dir.join(archive_file_name))
+        })
+        .collect()
+}
+
+/// Returns true if `snapshot_archive_path` was completely unpacked by
+/// `stage_snapshot_archive_unpack()`
+pub fn has_staged_snapshot_archive_unpack(snapshot_archive_path: impl AsRef<Path>) -> bool {
+    staged_snapshot_unpack_dir(snapshot_archive_path.as_ref())
+        .map(|staged_dir| {
+            staged_dir
+                .join(STAGED_SNAPSHOT_UNPACK_COMPLETE_FILENAME)
+                .is_file()
+        })
+        .unwrap_or(false)
+}
+
+/// Unpacks the snapshot archive read from `archive`, e.g. while it is still being downloaded, into
+/// staging directories for `snapshot_archive_path`.  Loading a bank from the archive later takes
+/// the unpacked files from the staging directories instead of unpacking the archive again, and
+/// falls back to unpacking the archive if the bank fails to verify.
+pub fn stage_snapshot_archive_unpack(
+    archive: impl Read + Send + 'static,
+    archive_format: ArchiveFormat,
+    snapshot_archive_path: impl AsRef<Path>,
+    account_paths: &[PathBuf],
+) -> Result<()> {
+    let snapshot_archive_path = snapshot_archive_path.as_ref();
+    let staged_dir = staged_snapshot_unpack_dir(snapshot_archive_path)?;
+    let staged_account_dirs =
+        staged_snapshot_unpack_account_dirs(snapshot_archive_path, account_paths)?;
+    for dir in std::iter::once(&staged_dir).chain(&staged_account_dirs) {
+        if dir.exists() {
+            fs_err::remove_dir_all(dir)?;
+        }
+        fs_err::create_dir_all(dir)?;
+    }
+
+    let mut measure_unpack = Measure::start("stage snapshot archive unpack");
+    let parallel_divisions = (num_cpus::get() / 4).clamp(1, PARALLEL_UNTAR_READERS_DEFAULT);
+    let shared_buffer = create_shared_buffer(archive, archive_format);
+    // All shared buffer readers need to be created before the threads are spawned
+    #[allow(clippy::needless_collect)]
+    let archives: Vec<_> = (0..parallel_divisions)
+        .map(|_| Archive::new(SharedBufferReader::new(&shared_buffer)))
+        .collect();
+    let (file_sender, file_receiver) = crossbeam_channel::unbounded();
+    let results: Vec<_> = thread::scope(|scope| {
+        let unpack_threads: Vec<_> = archives
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut archive)| {
+                let (staged_dir, staged_account_dirs) = (&staged_dir, &staged_account_dirs);
+                let file_sender = file_sender.clone();
+                Builder::new()
+                    .name(format!("solStgSnpsht{index:02}"))
+                    .spawn_scoped(scope, move || {
+                        hardened_unpack::streaming_unpack_snapshot(
+                            &mut archive,
+                            staged_dir,
+                            staged_account_dirs,
+                            Some(ParallelSelector {
+                                index,
+                                divisions: parallel_divisions,
+                            }),
+                            &file_sender,
+                        )
+                    })
+                    .unwrap()
+            })
+            .collect();
+        drop(file_sender);
+        // The files are only needed once the staged unpack is taken
+        file_receiver.iter().for_each(drop);
+        unpack_threads
+            .into_iter()
+            .map(|unpack_thread| unpack_thread.join().unwrap())
+            .collect()
+    });
+    for result in results {
+        result?;
+    }
+
+    let num_account_files = count_staged_account_files(&staged_account_dirs)?;
+    fs_err::write(
+        staged_dir.join(STAGED_SNAPSHOT_UNPACK_COMPLETE_FILENAME),
+        num_account_files.to_string(),
+    )?;
+    measure_unpack.stop();
+    info!(
+        "Staged the unpack of {} with {num_account_files} account files: {measure_unpack}",
+        snapshot_archive_path.display()
+    );
+    Ok(())
+}
+
+fn count_staged_account_files(staged_account_dirs: &[PathBuf]) -> Result<usize> {
+    let mut num_account_files = 0;
+    // Account paths may share a parent, and with it a staging directory
+    for staged_account_dir in staged_account_dirs.iter().collect::<HashSet<_>>() {
+        if staged_account_dir.is_dir() {
+            num_account_files += fs_err::read_dir(staged_account_dir)?.count();
+        }
+    }
+    Ok(num_account_files)
+}
+
+/// Moves the files of `snapshot_archive_path` staged by `stage_snapshot_archive_unpack()` into
+/// `unpack_dir` and `account_paths`, returning the paths of all the files.  Returns `None` if the
+/// archive has no complete staged unpack for `account_paths`.
+fn take_staged_snapshot_archive_unpack(
+    snapshot_archive_path: &Path,
+    unpack_dir: &Path,
+    account_paths: &[PathBuf],
+) -> Result<Option<Vec<PathBuf>>> {
+    if !has_staged_snapshot_archive_unpack(snapshot_archive_path) {
+        return Ok(None);
+    }
+    let staged_dir = staged_snapshot_unpack_dir(snapshot_archive_path)?;
+    let staged_account_dirs =
+        staged_snapshot_unpack_account_dirs(snapshot_archive_path, account_paths)?;
+    let expected_num_account_files =
+        fs_err::read_to_string(staged_dir.join(STAGED_SNAPSHOT_UNPACK_COMPLETE_FILENAME))?;
+    let num_account_files = count_staged_account_files(&staged_account_dirs)?;
+    if expected_num_account_files.trim() != num_account_files.to_string() {
+        warn!(
+            "The staged unpack of {} has {num_account_files} account files for the account \
+             paths instead of {expected_num_account_files}, unpacking the archive instead",
+            snapshot_archive_path.display(),
+        );
+        return Ok(None);
+    }
+
+    let mut files = vec![];
+    for file_name in [SNAPSHOT_VERSION_FILENAME, "snapshots"] {
+        fs_err::rename(staged_dir.join(file_name), unpack_dir.join(file_name))?;
+    }
+    files.push(unpack_dir.join(SNAPSHOT_VERSION_FILENAME));
+    for entry in fs_err::read_dir(unpack_dir.join("snapshots"))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            for entry in fs_err::read_dir(path)? {
+                files.push(entry?.path());
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    for (staged_account_dir, account_path) in staged_account_dirs.iter().zip(account_paths) {
+        if !staged_account_dir.is_dir() {
+            continue;
+        }
+        for entry in fs_err::read_dir(staged_account_dir)? {
+            let staged_path = entry?.path();
+            let path = account_path.join(
+                staged_path
+                    .file_name()
+                    .ok_or_else(|| SnapshotError::PathToFileNameError(staged_path.clone()))?,
+            );
+            fs_err::rename(staged_path, &path)?;
+            files.push(path);
+        }
+    }
+    fs_err::remove_dir_all(staged_dir)?;
+    Ok(Some(files))
+}
+
+/// Removes all staged unpacks of snapshot archives in the directories of `snapshot_archive_paths`
+/// and next to `account_paths`
+pub(crate) fn remove_staged_snapshot_archive_unpacks<'a>(
+    snapshot_archive_paths: impl IntoIterator<Item = &'a Path>,
+    account_paths: &[PathBuf],
+) {
+    let archive_dirs = snapshot_archive_paths
+        .into_iter()
+        .filter_map(|snapshot_archive_path| snapshot_archive_path.parent());
+    let account_parent_dirs = account_paths
+        .iter()
+        .filter_map(|account_path| account_path.parent());
+    for dir in archive_dirs.chain(account_parent_dirs) {
+        let staged_unpacks_dir = dir.join(STAGED_SNAPSHOT_UNPACKS_DIR);
+        if staged_unpacks_dir.exists() {
+            if let Err(err) = fs_err::remove_dir_all(&staged_unpacks_dir) {
+                warn!("Failed to remove staged snapshot unpacks: {err}");
+            }
         }
-        ArchiveFormat::Tar => SharedBuffer::new(BufReader::new(open_file())),
     }
 }
 
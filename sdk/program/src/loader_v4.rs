@@ -136,6 +136,28 @@ pub fn write(
     )
 }
 
+/// Returns the instructions required to write program data to a buffer account in chunks of at
+/// most `chunk_size` bytes, skipping the chunks which are equal in `written_data`, the program
+/// data that the account already contains. This allows resuming an interrupted write.
+pub fn write_chunks(
+    program_address: &Pubkey,
+    authority: &Pubkey,
+    program_data: &[u8],
+    written_data: &[u8],
+    chunk_size: usize,
+) -> Vec<Instruction> {
+    program_data
+        .chunks(chunk_size)
+        .enumerate()
+        .filter_map(|(index, chunk)| {
+            let offset = index.saturating_mul(chunk_size);
+            let written_chunk = written_data.get(offset..offset.saturating_add(chunk.len()));
+            (written_chunk != Some(chunk))
+                .then(|| write(program_address, authority, offset as u32, chunk.to_vec()))
+        })
+        .collect()
+}
+
 /// Returns the instructions required to deploy a program.
 pub fn deploy(program_address: &Pubkey, authority: &Pubkey) -> Instruction {
     Instruction::new_with_bincode(
@@ -256,6 +278,34 @@ mod tests {
         assert!(instruction.accounts[1].is_signer);
     }
 
+    #[test]
+    fn test_write_chunks_instructions() {
+        let program = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let program_data = [1, 2, 3, 4, 5, 6, 7];
+
+        let instructions = write_chunks(&program, &authority, &program_data, &[], 3);
+        assert_eq!(
+            instructions,
+            vec![
+                write(&program, &authority, 0, vec![1, 2, 3]),
+                write(&program, &authority, 3, vec![4, 5, 6]),
+                write(&program, &authority, 6, vec![7]),
+            ]
+        );
+
+        // Chunks which were written already are skipped
+        let written_data = [1, 2, 3, 0, 0, 0, 7];
+        let instructions = write_chunks(&program, &authority, &program_data, &written_data, 3);
+        assert_eq!(
+            instructions,
+            vec![write(&program, &authority, 3, vec![4, 5, 6])]
+        );
+
+        let instructions = write_chunks(&program, &authority, &program_data, &program_data, 3);
+        assert!(instructions.is_empty());
+    }
+
     #[test]
     fn test_truncate_instruction() {
         let program = Pubkey::new_unique();
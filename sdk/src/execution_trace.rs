@@ -0,0 +1,98 @@
+//! Instruction level traces of the SBF programs that a transaction executes.
+//!
+//! Traces are only recorded by runtimes that compile programs with instruction tracing, such as
+//! test validators which enable it.  They serialize to a compact binary format with `bincode`.
+//!
+//! The compute units consumed by the functions of the programs can be written in the collapsed
+//! stack format of flamegraph tools with [`write_collapsed_stacks`].
+
+use {crate::pubkey::Pubkey, std::io};
+
+/// The registers `r0` to `r10` followed by the program counter, before an instruction executes
+pub type RegisterState = [u64; 12];
+
+/// A syscall that an SBF program invoked
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct SyscallInvocation {
+    /// The index in `ProgramExecutionTrace::registers` of the instruction invoking the syscall
+    pub step: u64,
+    pub name: String,
+    /// The compute units consumed by the syscall, in addition to the one of the instruction
+    /// invoking it.  The ones of the SBF programs it invokes are only included in their own
+    /// execution traces.
+    pub compute_units: u64,
+}
+
+/// The compute units consumed by a program while a stack of its functions was executing
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct FunctionStackComputeUnits {
+    /// The symbols of the functions, starting with the outermost one
+    pub stack: Vec<String>,
+    /// The compute units consumed by the innermost function, excluding the functions it calls
+    pub compute_units: u64,
+}
+
+/// The trace of one invocation of an SBF program
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ProgramExecutionTrace {
+    pub program_id: Pubkey,
+    /// `TRANSACTION_LEVEL_STACK_HEIGHT` for the instructions of the transaction, higher for
+    /// cross-program invocations
+    pub stack_height: u32,
+    /// The register state before each instruction that the program executed
+    pub registers: Vec<RegisterState>,
+    pub syscalls: Vec<SyscallInvocation>,
+    pub compute_unit_profile: Vec<FunctionStackComputeUnits>,
+}
+
+/// The traces of the SBF programs invoked by each instruction of a transaction, in the order in
+/// which the invocations started
+pub type ExecutionTraceList = Vec<Vec<ProgramExecutionTrace>>;
+
+/// Writes the compute unit profiles of program invocations in the collapsed stack format, one line
+/// per stack of functions prefixed with the program id, followed by its compute units
+pub fn write_collapsed_stacks<'a, W: io::Write>(
+    out: &mut W,
+    execution_traces: impl IntoIterator<Item = &'a ProgramExecutionTrace>,
+) -> io::Result<()> {
+    for execution_trace in execution_traces {
+        for function_stack in &execution_trace.compute_unit_profile {
+            write!(out, "{}", execution_trace.program_id)?;
+            for function in &function_stack.stack {
+                write!(out, ";{function}")?;
+            }
+            writeln!(out, " {}", function_stack.compute_units)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_collapsed_stacks() {
+        let program_id = Pubkey::new_unique();
+        let execution_trace = ProgramExecutionTrace {
+            program_id,
+            compute_unit_profile: vec![
+                FunctionStackComputeUnits {
+                    stack: vec!["entrypoint".to_string()],
+                    compute_units: 10,
+                },
+                FunctionStackComputeUnits {
+                    stack: vec!["entrypoint".to_string(), "process".to_string()],
+                    compute_units: 250,
+                },
+            ],
+            ..ProgramExecutionTrace::default()
+        };
+        let mut out = Vec::new();
+        write_collapsed_stacks(&mut out, [&execution_trace]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{program_id};entrypoint 10\n{program_id};entrypoint;process 250\n"),
+        );
+    }
+}
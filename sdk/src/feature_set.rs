@@ -724,6 +724,18 @@ pub mod validate_fee_collector_account {
     solana_sdk::declare_id!("prpFrMtgNmzaNzkPJg9o753fVvbHKqNrNTm76foJ2wm");
 }
 
+pub mod batch_repair_requests {
+    solana_sdk::declare_id!("CtG9k5pM4EmzHYhYtuR3xV1EPGnivGxcBFGG8iHNrzHC");
+}
+
+pub mod turbine_over_quic {
+    solana_sdk::declare_id!("CVXgA33w9kYZJitKdWtugCKvwxLm93RMTjDP1jAS8tWT");
+}
+
+pub mod repair_over_quic {
+    solana_sdk::declare_id!("6JbrpMjPeqUew4gK2kzRxmXXcMNkUDFX2uY9vF2w782q");
+}
+
 lazy_static! {
     /// Map of feature identifiers to user-visible description
     pub static ref FEATURE_NAMES: HashMap<Pubkey, &'static str> = [
@@ -900,6 +912,9 @@ lazy_static! {
         (update_hashes_per_tick5::id(), "Update desired hashes per tick to 9.2M"),
         (update_hashes_per_tick6::id(), "Update desired hashes per tick to 10M"),
         (validate_fee_collector_account::id(), "validate fee collector account #33888"),
+        (batch_repair_requests::id(), "request contiguous missing shreds in a single repair request"),
+        (turbine_over_quic::id(), "send shreds over QUIC in turbine broadcast and retransmit"),
+        (repair_over_quic::id(), "send repair requests over QUIC"),
         /*************** ADD NEW FEATURES HERE ***************/
     ]
     .iter()
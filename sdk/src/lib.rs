@@ -68,6 +68,7 @@ pub mod ed25519_instruction;
 pub mod entrypoint;
 pub mod entrypoint_deprecated;
 pub mod epoch_info;
+pub mod execution_trace;
 pub mod example_mocks;
 pub mod exit;
 pub mod feature;
@@ -1,6 +1,7 @@
 use {
     crate::{
-        quic::{configure_server, QuicServerError, StreamStats},
+        quic::{configure_server, QuicServerError, QuicServerOptions, StreamStats},
+        quic_qos::{QosPeer, QosPolicy},
         streamer::StakedNodes,
         tls_certificates::get_pubkey_from_tls_certificate,
     },
@@ -56,6 +57,16 @@ const CONNECTION_CLOSE_REASON_EXCEED_MAX_STREAM_COUNT: &[u8] = b"exceed_max_stre
 const CONNECTION_CLOSE_CODE_TOO_MANY: u32 = 4;
 const CONNECTION_CLOSE_REASON_TOO_MANY: &[u8] = b"too_many";
 
+const CONNECTION_CLOSE_CODE_MIGRATED: u32 = 5;
+const CONNECTION_CLOSE_REASON_MIGRATED: &[u8] = b"migrated";
+
+// Datagrams per second accepted from each staked connection, regardless of
+// stake.
+const MIN_STAKED_DATAGRAMS_PER_SECOND: f64 = 1_000.0;
+// Datagrams per second additionally accepted from staked connections in
+// proportion to their stake.
+const STAKE_WEIGHTED_DATAGRAMS_PER_SECOND: f64 = 500_000.0;
+
 // A sequence of bytes that is part of a packet
 // along with where in the packet it is
 struct PacketChunk {
@@ -90,13 +101,13 @@ pub fn spawn_server(
     exit: Arc<AtomicBool>,
     max_connections_per_peer: usize,
     staked_nodes: Arc<RwLock<StakedNodes>>,
-    max_staked_connections: usize,
-    max_unstaked_connections: usize,
+    qos_policy: Arc<dyn QosPolicy>,
     wait_for_chunk_timeout: Duration,
     coalesce: Duration,
+    options: QuicServerOptions,
 ) -> Result<(Endpoint, Arc<StreamStats>, JoinHandle<()>), QuicServerError> {
     info!("Start {name} quic server on {sock:?}");
-    let (config, _cert) = configure_server(keypair, gossip_host)?;
+    let (config, _cert) = configure_server(keypair, gossip_host, options)?;
 
     let endpoint = Endpoint::new(
         EndpointConfig::default(),
@@ -113,11 +124,11 @@ pub fn spawn_server(
         exit,
         max_connections_per_peer,
         staked_nodes,
-        max_staked_connections,
-        max_unstaked_connections,
+        qos_policy,
         stats.clone(),
         wait_for_chunk_timeout,
         coalesce,
+        options.enable_datagrams,
     ));
     Ok((endpoint, stats, handle))
 }
@@ -130,11 +141,11 @@ async fn run_server(
     exit: Arc<AtomicBool>,
     max_connections_per_peer: usize,
     staked_nodes: Arc<RwLock<StakedNodes>>,
-    max_staked_connections: usize,
-    max_unstaked_connections: usize,
+    qos_policy: Arc<dyn QosPolicy>,
     stats: Arc<StreamStats>,
     wait_for_chunk_timeout: Duration,
     coalesce: Duration,
+    enable_datagrams: bool,
 ) {
     const WAIT_FOR_CONNECTION_TIMEOUT: Duration = Duration::from_secs(1);
     debug!("spawn quic server");
@@ -169,10 +180,10 @@ async fn run_server(
                 sender.clone(),
                 max_connections_per_peer,
                 staked_nodes.clone(),
-                max_staked_connections,
-                max_unstaked_connections,
+                qos_policy.clone(),
                 stats.clone(),
                 wait_for_chunk_timeout,
+                enable_datagrams,
             ));
         } else {
             debug!("accept(): Timed out waiting for connection");
@@ -206,20 +217,20 @@ pub fn get_remote_pubkey(connection: &Connection) -> Option<Pubkey> {
         .and_then(get_pubkey_from_tls_certificate)
 }
 
-fn get_connection_stake(
-    connection: &Connection,
-    staked_nodes: &RwLock<StakedNodes>,
-) -> Option<(Pubkey, u64, u64, u64, u64)> {
-    let pubkey = get_remote_pubkey(connection)?;
+fn get_qos_peer(connection: &Connection, staked_nodes: &RwLock<StakedNodes>) -> QosPeer {
+    let pubkey = get_remote_pubkey(connection);
     debug!("Peer public key is {pubkey:?}");
     let staked_nodes = staked_nodes.read().unwrap();
-    Some((
+    QosPeer {
         pubkey,
-        staked_nodes.get_node_stake(&pubkey)?,
-        staked_nodes.total_stake(),
-        staked_nodes.max_stake(),
-        staked_nodes.min_stake(),
-    ))
+        addr: connection.remote_address(),
+        stake: pubkey
+            .and_then(|pubkey| staked_nodes.get_node_stake(&pubkey))
+            .unwrap_or_default(),
+        total_stake: staked_nodes.total_stake(),
+        max_stake: staked_nodes.max_stake(),
+        min_stake: staked_nodes.min_stake(),
+    }
 }
 
 pub fn compute_max_allowed_uni_streams(
@@ -278,6 +289,9 @@ struct NewConnectionHandlerParams {
     stats: Arc<StreamStats>,
     max_stake: u64,
     min_stake: u64,
+    qos_policy: Arc<dyn QosPolicy>,
+    qos_peer: QosPeer,
+    enable_datagrams: bool,
 }
 
 impl NewConnectionHandlerParams {
@@ -285,6 +299,9 @@ impl NewConnectionHandlerParams {
         packet_sender: AsyncSender<PacketAccumulator>,
         max_connections_per_peer: usize,
         stats: Arc<StreamStats>,
+        qos_policy: Arc<dyn QosPolicy>,
+        qos_peer: QosPeer,
+        enable_datagrams: bool,
     ) -> NewConnectionHandlerParams {
         NewConnectionHandlerParams {
             packet_sender,
@@ -295,6 +312,9 @@ impl NewConnectionHandlerParams {
             stats,
             max_stake: 0,
             min_stake: 0,
+            qos_policy,
+            qos_peer,
+            enable_datagrams,
         }
     }
 }
@@ -306,10 +326,10 @@ fn handle_and_cache_new_connection(
     params: &NewConnectionHandlerParams,
     wait_for_chunk_timeout: Duration,
 ) -> Result<(), ConnectionHandlerError> {
-    if let Ok(max_uni_streams) = VarInt::from_u64(compute_max_allowed_uni_streams(
+    if let Ok(max_uni_streams) = VarInt::from_u64(params.qos_policy.max_uni_streams(
         connection_table_l.peer_type,
+        &params.qos_peer,
         params.stake,
-        params.total_stake,
     ) as u64)
     {
         connection.set_max_concurrent_uni_streams(max_uni_streams);
@@ -357,6 +377,10 @@ fn handle_and_cache_new_connection(
                 params.stats.clone(),
                 peer_type,
                 wait_for_chunk_timeout,
+                params
+                    .enable_datagrams
+                    .then(|| DatagramRateLimiter::new(params.stake, params.total_stake)),
+                params.qos_policy.clone(),
             ));
             Ok(())
         } else {
@@ -459,10 +483,10 @@ async fn setup_connection(
     packet_sender: AsyncSender<PacketAccumulator>,
     max_connections_per_peer: usize,
     staked_nodes: Arc<RwLock<StakedNodes>>,
-    max_staked_connections: usize,
-    max_unstaked_connections: usize,
+    qos_policy: Arc<dyn QosPolicy>,
     stats: Arc<StreamStats>,
     wait_for_chunk_timeout: Duration,
+    enable_datagrams: bool,
 ) {
     const PRUNE_RANDOM_SAMPLE_SIZE: usize = 2;
     let from = connecting.remote_address();
@@ -471,25 +495,34 @@ async fn setup_connection(
             Ok(new_connection) => {
                 stats.total_new_connections.fetch_add(1, Ordering::Relaxed);
 
-                let params = get_connection_stake(&new_connection, &staked_nodes).map_or(
+                let qos_peer = get_qos_peer(&new_connection, &staked_nodes);
+                let stake = qos_policy.effective_stake(&qos_peer);
+                let params = if stake > 0 {
+                    NewConnectionHandlerParams {
+                        packet_sender,
+                        remote_pubkey: qos_peer.pubkey,
+                        stake,
+                        total_stake: qos_peer.total_stake,
+                        max_connections_per_peer,
+                        stats: stats.clone(),
+                        max_stake: qos_peer.max_stake,
+                        min_stake: qos_peer.min_stake,
+                        qos_policy: qos_policy.clone(),
+                        qos_peer,
+                        enable_datagrams,
+                    }
+                } else {
                     NewConnectionHandlerParams::new_unstaked(
-                        packet_sender.clone(),
+                        packet_sender,
                         max_connections_per_peer,
                         stats.clone(),
-                    ),
-                    |(pubkey, stake, total_stake, max_stake, min_stake)| {
-                        NewConnectionHandlerParams {
-                            packet_sender,
-                            remote_pubkey: Some(pubkey),
-                            stake,
-                            total_stake,
-                            max_connections_per_peer,
-                            stats: stats.clone(),
-                            max_stake,
-                            min_stake,
-                        }
-                    },
-                );
+                        qos_policy.clone(),
+                        qos_peer,
+                        enable_datagrams,
+                    )
+                };
+                let max_staked_connections = qos_policy.max_staked_connections();
+                let max_unstaked_connections = qos_policy.max_unstaked_connections();
 
                 if params.stake > 0 {
                     let mut connection_table_l = staked_connection_table.lock().unwrap();
@@ -693,6 +726,8 @@ async fn handle_connection(
     stats: Arc<StreamStats>,
     peer_type: ConnectionPeerType,
     wait_for_chunk_timeout: Duration,
+    datagram_rate_limiter: Option<DatagramRateLimiter>,
+    qos_policy: Arc<dyn QosPolicy>,
 ) {
     debug!(
         "quic new connection {} streams: {} connections: {}",
@@ -702,10 +737,44 @@ async fn handle_connection(
     );
     let stable_id = connection.stable_id();
     stats.total_connections.fetch_add(1, Ordering::Relaxed);
+    qos_policy.connection_added(remote_addr, remote_pubkey);
+    if let Some(datagram_rate_limiter) = datagram_rate_limiter {
+        tokio::spawn(handle_datagrams(
+            connection.clone(),
+            packet_sender.clone(),
+            last_update.clone(),
+            stream_exit.clone(),
+            stats.clone(),
+            peer_type,
+            datagram_rate_limiter,
+        ));
+    }
+    // Current address of the peer, which changes if the connection migrates.
+    // The connection table entry remains keyed by the address the connection
+    // was established from.
+    let mut peer_addr = remote_addr;
     while !stream_exit.load(Ordering::Relaxed) {
-        if let Ok(stream) =
-            tokio::time::timeout(WAIT_FOR_STREAM_TIMEOUT, connection.accept_uni()).await
-        {
+        let stream = tokio::time::timeout(WAIT_FOR_STREAM_TIMEOUT, connection.accept_uni()).await;
+        let addr = connection.remote_address();
+        if addr != peer_addr {
+            debug!("quic connection {stable_id} migrated from {peer_addr} to {addr}");
+            stats.connection_migrations.fetch_add(1, Ordering::Relaxed);
+            // Connections without a pubkey are limited per IP address.
+            if remote_pubkey.is_none() && addr.ip() != remote_addr.ip() {
+                connection.close(
+                    CONNECTION_CLOSE_CODE_MIGRATED.into(),
+                    CONNECTION_CLOSE_REASON_MIGRATED,
+                );
+                stats
+                    .connection_migration_closed
+                    .fetch_add(1, Ordering::Relaxed);
+                break;
+            }
+            qos_policy.connection_removed(peer_addr);
+            qos_policy.connection_added(addr, remote_pubkey);
+            peer_addr = addr;
+        }
+        if let Ok(stream) = stream {
             match stream {
                 Ok(mut stream) => {
                     stats.total_streams.fetch_add(1, Ordering::Relaxed);
@@ -734,7 +803,7 @@ async fn handle_connection(
                                 if handle_chunk(
                                     chunk,
                                     &mut maybe_batch,
-                                    &remote_addr,
+                                    &peer_addr,
                                     &packet_sender,
                                     stats.clone(),
                                     peer_type,
@@ -764,6 +833,7 @@ async fn handle_connection(
         }
     }
 
+    qos_policy.connection_removed(peer_addr);
     let removed_connection_count = connection_table.lock().unwrap().remove_connection(
         ConnectionTableKey::new(remote_addr.ip(), remote_pubkey),
         remote_addr.port(),
@@ -781,6 +851,121 @@ async fn handle_connection(
     stats.total_connections.fetch_sub(1, Ordering::Relaxed);
 }
 
+// Token bucket limiting the datagrams read from a connection, holding at
+// most one second worth of datagrams.
+struct DatagramRateLimiter {
+    datagrams_per_second: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl DatagramRateLimiter {
+    fn new(stake: u64, total_stake: u64) -> Self {
+        let datagrams_per_second = if stake == 0 || total_stake == 0 {
+            0.0
+        } else {
+            MIN_STAKED_DATAGRAMS_PER_SECOND
+                + STAKE_WEIGHTED_DATAGRAMS_PER_SECOND * stake.min(total_stake) as f64
+                    / total_stake as f64
+        };
+        Self {
+            datagrams_per_second,
+            tokens: datagrams_per_second,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.datagrams_per_second)
+            .min(self.datagrams_per_second);
+        self.last_refill = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+// Reads the packets sent as datagrams on the connection until it is closed or
+// dropped from the connection table. Datagrams are not subject to the stream
+// limits of the connection, so they are only accepted from staked peers, at a
+// rate proportional to their stake.
+async fn handle_datagrams(
+    connection: Connection,
+    packet_sender: AsyncSender<PacketAccumulator>,
+    last_update: Arc<AtomicU64>,
+    stream_exit: Arc<AtomicBool>,
+    stats: Arc<StreamStats>,
+    peer_type: ConnectionPeerType,
+    mut rate_limiter: DatagramRateLimiter,
+) {
+    while !stream_exit.load(Ordering::Relaxed) {
+        let Ok(datagram) = timeout(WAIT_FOR_STREAM_TIMEOUT, connection.read_datagram()).await
+        else {
+            continue;
+        };
+        let bytes = match datagram {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                debug!("datagram error: {err:?}");
+                break;
+            }
+        };
+        stats
+            .total_datagrams_received
+            .fetch_add(1, Ordering::Relaxed);
+        if matches!(peer_type, ConnectionPeerType::Unstaked) {
+            stats
+                .total_unstaked_datagrams_dropped
+                .fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        if !rate_limiter.take(Instant::now()) {
+            stats
+                .total_datagrams_rate_limited
+                .fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        let size = bytes.len();
+        if size == 0 || size > PACKET_DATA_SIZE {
+            stats
+                .total_invalid_datagrams
+                .fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        let mut meta = Meta::default();
+        meta.size = size;
+        meta.set_socket_addr(&connection.remote_address());
+        let accum = PacketAccumulator {
+            meta,
+            chunks: vec![PacketChunk {
+                bytes,
+                offset: 0,
+                end_of_chunk: size,
+            }],
+        };
+        if let Err(err) = packet_sender.send(accum).await {
+            stats
+                .total_handle_chunk_to_packet_batcher_send_err
+                .fetch_add(1, Ordering::Relaxed);
+            trace!("packet batch send error {:?}", err);
+        } else {
+            stats
+                .total_packets_sent_for_batching
+                .fetch_add(1, Ordering::Relaxed);
+            stats
+                .total_bytes_sent_for_batching
+                .fetch_add(size, Ordering::Relaxed);
+            stats
+                .total_chunks_sent_for_batching
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        last_update.store(timing::timestamp(), Ordering::Relaxed);
+    }
+}
+
 // Return true if the server should drop the stream
 async fn handle_chunk(
     chunk: Result<Option<quinn::Chunk>, quinn::ReadError>,
@@ -1092,9 +1277,8 @@ pub mod test {
     use {
         super::*,
         crate::{
-            nonblocking::quic::compute_max_allowed_uni_streams,
-            quic::{MAX_STAKED_CONNECTIONS, MAX_UNSTAKED_CONNECTIONS},
-            tls_certificates::new_self_signed_tls_certificate,
+            nonblocking::quic::compute_max_allowed_uni_streams, quic::MAX_STAKED_CONNECTIONS,
+            quic_qos::StakeWeightedQos, tls_certificates::new_self_signed_tls_certificate,
         },
         assert_matches::assert_matches,
         async_channel::unbounded as async_unbounded,
@@ -1166,6 +1350,24 @@ pub mod test {
         crossbeam_channel::Receiver<PacketBatch>,
         SocketAddr,
         Arc<StreamStats>,
+    ) {
+        setup_quic_server_with_options(
+            option_staked_nodes,
+            max_connections_per_peer,
+            QuicServerOptions::default(),
+        )
+    }
+
+    fn setup_quic_server_with_options(
+        option_staked_nodes: Option<StakedNodes>,
+        max_connections_per_peer: usize,
+        options: QuicServerOptions,
+    ) -> (
+        JoinHandle<()>,
+        Arc<AtomicBool>,
+        crossbeam_channel::Receiver<PacketBatch>,
+        SocketAddr,
+        Arc<StreamStats>,
     ) {
         let s = UdpSocket::bind("127.0.0.1:0").unwrap();
         let exit = Arc::new(AtomicBool::new(false));
@@ -1183,10 +1385,10 @@ pub mod test {
             exit.clone(),
             max_connections_per_peer,
             staked_nodes,
-            MAX_STAKED_CONNECTIONS,
-            MAX_UNSTAKED_CONNECTIONS,
+            Arc::<StakeWeightedQos>::default(),
             Duration::from_secs(2),
             DEFAULT_TPU_COALESCE,
+            options,
         )
         .unwrap();
         (t, exit, receiver, server_address, stats)
@@ -1347,6 +1549,91 @@ pub mod test {
         assert_eq!(total_packets, num_expected_packets);
     }
 
+    async fn recv_packets(
+        receiver: &Receiver<PacketBatch>,
+        num_expected_packets: usize,
+    ) -> Vec<solana_perf::packet::Packet> {
+        let mut packets = vec![];
+        let now = Instant::now();
+        while now.elapsed().as_secs() < 5 && packets.len() < num_expected_packets {
+            if let Ok(batch) = receiver.try_recv() {
+                packets.extend(batch.iter().cloned());
+            } else {
+                sleep(Duration::from_millis(100)).await;
+            }
+        }
+        packets
+    }
+
+    pub async fn check_datagrams(
+        receiver: Receiver<PacketBatch>,
+        server_address: SocketAddr,
+        client_keypair: &Keypair,
+    ) {
+        let conn = make_client_endpoint(&server_address, Some(client_keypair)).await;
+        assert!(conn.max_datagram_size().unwrap() >= 100);
+        let num_expected_packets = 10;
+        for i in 0..num_expected_packets {
+            conn.send_datagram(Bytes::from(vec![i as u8; 100])).unwrap();
+        }
+        let mut packets = recv_packets(&receiver, num_expected_packets).await;
+        assert_eq!(packets.len(), num_expected_packets);
+        packets.sort_by_key(|packet| packet.data(0).copied());
+        for (i, packet) in packets.iter().enumerate() {
+            assert_eq!(packet.meta().size, 100);
+            assert_eq!(packet.data(..).unwrap(), &[i as u8; 100]);
+        }
+    }
+
+    // Sends a packet, rebinds the client endpoint to a new socket as on NAT
+    // rebinding and sends another packet on the same connection.
+    pub async fn check_connection_migration(
+        receiver: Receiver<PacketBatch>,
+        server_address: SocketAddr,
+        client_keypair: Option<&Keypair>,
+    ) {
+        let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client_address = client_socket.local_addr().unwrap();
+        let mut endpoint = quinn::Endpoint::new(
+            EndpointConfig::default(),
+            None,
+            client_socket,
+            Arc::new(TokioRuntime),
+        )
+        .unwrap();
+        let default_keypair = Keypair::new();
+        endpoint.set_default_client_config(get_client_config(
+            client_keypair.unwrap_or(&default_keypair),
+        ));
+        let conn = endpoint
+            .connect(server_address, "localhost")
+            .unwrap()
+            .await
+            .unwrap();
+        let send = |byte: u8| {
+            let conn = conn.clone();
+            async move {
+                let mut stream = conn.open_uni().await.unwrap();
+                stream.write_all(&[byte; 10]).await.unwrap();
+                stream.finish().await.unwrap();
+            }
+        };
+        send(0).await;
+        let packets = recv_packets(&receiver, 1).await;
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].meta().socket_addr(), client_address);
+
+        let new_client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let new_client_address = new_client_socket.local_addr().unwrap();
+        endpoint.rebind(new_client_socket).unwrap();
+        send(1).await;
+        let packets = recv_packets(&receiver, 1).await;
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].data(..).unwrap(), &[1u8; 10]);
+        assert_eq!(packets[0].meta().socket_addr(), new_client_address);
+        assert_eq!(conn.close_reason(), None);
+    }
+
     pub async fn check_unstaked_node_connect_failure(server_address: SocketAddr) {
         let conn1 = Arc::new(make_client_endpoint(&server_address, None).await);
 
@@ -1600,6 +1887,125 @@ pub mod test {
         assert_eq!(stats.connection_remove_failed.load(Ordering::Relaxed), 0);
     }
 
+    #[tokio::test]
+    async fn test_quic_server_datagrams() {
+        solana_logger::setup();
+        let client_keypair = Keypair::new();
+        let stakes = HashMap::from([(client_keypair.pubkey(), 100_000)]);
+        let staked_nodes = StakedNodes::new(
+            Arc::new(stakes),
+            HashMap::<Pubkey, u64>::default(), // overrides
+        );
+        let options = QuicServerOptions {
+            enable_datagrams: true,
+            ..QuicServerOptions::default()
+        };
+        let (t, exit, receiver, server_address, stats) =
+            setup_quic_server_with_options(Some(staked_nodes), 1, options);
+        check_datagrams(receiver.clone(), server_address, &client_keypair).await;
+        assert_eq!(stats.total_datagrams_received.load(Ordering::Relaxed), 10);
+
+        // Datagrams from unstaked peers are dropped.
+        let conn = make_client_endpoint(&server_address, None).await;
+        conn.send_datagram(Bytes::from(vec![0u8; 100])).unwrap();
+        sleep(Duration::from_millis(500)).await;
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(
+            stats
+                .total_unstaked_datagrams_dropped
+                .load(Ordering::Relaxed),
+            1
+        );
+        exit.store(true, Ordering::Relaxed);
+        t.await.unwrap();
+    }
+
+    #[test]
+    fn test_datagram_rate_limiter() {
+        let now = Instant::now();
+        let mut rate_limiter = DatagramRateLimiter::new(0, 1_000);
+        assert!(!rate_limiter.take(now + Duration::from_secs(1)));
+
+        let mut rate_limiter = DatagramRateLimiter::new(0, 0);
+        assert!(!rate_limiter.take(now + Duration::from_secs(1)));
+
+        let mut rate_limiter = DatagramRateLimiter::new(1, 1_000);
+        let datagrams_per_second = MIN_STAKED_DATAGRAMS_PER_SECOND as usize
+            + STAKE_WEIGHTED_DATAGRAMS_PER_SECOND as usize / 1_000;
+        assert_eq!(
+            (0..2 * datagrams_per_second)
+                .filter(|_| rate_limiter.take(now))
+                .count(),
+            datagrams_per_second
+        );
+        // Tokens refill in proportion to the elapsed time, up to one second
+        // worth of datagrams.
+        let now = now + Duration::from_millis(100);
+        assert_eq!(
+            (0..datagrams_per_second)
+                .filter(|_| rate_limiter.take(now))
+                .count(),
+            datagrams_per_second / 10
+        );
+        let now = now + Duration::from_secs(10);
+        assert_eq!(
+            (0..2 * datagrams_per_second)
+                .filter(|_| rate_limiter.take(now))
+                .count(),
+            datagrams_per_second
+        );
+
+        // A larger share of the stake is allowed more datagrams.
+        let mut rate_limiter = DatagramRateLimiter::new(1_000, 1_000);
+        assert_eq!(
+            (0..1_000_000).filter(|_| rate_limiter.take(now)).count(),
+            (MIN_STAKED_DATAGRAMS_PER_SECOND + STAKE_WEIGHTED_DATAGRAMS_PER_SECOND) as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quic_server_datagrams_disabled() {
+        solana_logger::setup();
+        let (t, exit, _receiver, server_address, _stats) = setup_quic_server(None, 1);
+        let conn = make_client_endpoint(&server_address, None).await;
+        assert_eq!(conn.max_datagram_size(), None);
+        assert_matches!(
+            conn.send_datagram(Bytes::from(vec![0u8; 100])),
+            Err(quinn::SendDatagramError::UnsupportedByPeer)
+        );
+        exit.store(true, Ordering::Relaxed);
+        t.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_quic_server_connection_migration() {
+        solana_logger::setup();
+        let client_keypair = Keypair::new();
+        let stakes = HashMap::from([(client_keypair.pubkey(), 100_000)]);
+        let staked_nodes = StakedNodes::new(
+            Arc::new(stakes),
+            HashMap::<Pubkey, u64>::default(), // overrides
+        );
+        let (t, exit, receiver, server_address, stats) = setup_quic_server(Some(staked_nodes), 1);
+        check_connection_migration(receiver.clone(), server_address, Some(&client_keypair)).await;
+        // Unstaked connections may migrate to a new port of the same IP address.
+        check_connection_migration(receiver, server_address, None).await;
+        assert_eq!(stats.connection_migrations.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.connection_migration_closed.load(Ordering::Relaxed), 0);
+        exit.store(true, Ordering::Relaxed);
+        t.await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        // The staked identity was kept across the migration.
+        assert_eq!(
+            stats
+                .connection_added_from_staked_peer
+                .load(Ordering::Relaxed),
+            1
+        );
+        assert_eq!(stats.connection_removed.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.connection_remove_failed.load(Ordering::Relaxed), 0);
+    }
+
     #[tokio::test]
     async fn test_quic_server_unstaked_node_connect_failure() {
         solana_logger::setup();
@@ -1619,10 +2025,11 @@ pub mod test {
             exit.clone(),
             1,
             staked_nodes,
-            MAX_STAKED_CONNECTIONS,
-            0, // Do not allow any connection from unstaked clients/nodes
+            // Do not allow any connection from unstaked clients/nodes
+            Arc::new(StakeWeightedQos::new(MAX_STAKED_CONNECTIONS, 0)),
             DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
             DEFAULT_TPU_COALESCE,
+            QuicServerOptions::default(),
         )
         .unwrap();
 
@@ -1650,10 +2057,10 @@ pub mod test {
             exit.clone(),
             2,
             staked_nodes,
-            MAX_STAKED_CONNECTIONS,
-            MAX_UNSTAKED_CONNECTIONS,
+            Arc::<StakeWeightedQos>::default(),
             DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
             DEFAULT_TPU_COALESCE,
+            QuicServerOptions::default(),
         )
         .unwrap();
 
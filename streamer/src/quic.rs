@@ -1,6 +1,6 @@
 use {
     crate::{
-        nonblocking::quic::ALPN_TPU_PROTOCOL_ID, streamer::StakedNodes,
+        nonblocking::quic::ALPN_TPU_PROTOCOL_ID, quic_qos::QosPolicy, streamer::StakedNodes,
         tls_certificates::new_self_signed_tls_certificate,
     },
     crossbeam_channel::Sender,
@@ -28,6 +28,33 @@ use {
 pub const MAX_STAKED_CONNECTIONS: usize = 2000;
 pub const MAX_UNSTAKED_CONNECTIONS: usize = 500;
 
+// Datagrams buffered per connection before the oldest ones are dropped.
+const MAX_BUFFERED_DATAGRAMS: usize = 128;
+
+/// Optional transport features of the QUIC server.
+#[derive(Clone, Copy, Debug)]
+pub struct QuicServerOptions {
+    /// Receive packets sent as QUIC datagrams, in addition to uni streams.
+    /// Datagrams are only accepted from peers admitted into the staked
+    /// connection table; packets too large for a datagram are still sent
+    /// over streams.
+    pub enable_datagrams: bool,
+    /// Allow clients to migrate connections to a new address, e.g. on NAT
+    /// rebinding. Connections identified by the client certificate keep
+    /// their stake; connections identified by IP address are closed if
+    /// they migrate to a different IP address.
+    pub enable_migration: bool,
+}
+
+impl Default for QuicServerOptions {
+    fn default() -> Self {
+        Self {
+            enable_datagrams: false,
+            enable_migration: true,
+        }
+    }
+}
+
 pub struct SkipClientVerification;
 
 impl SkipClientVerification {
@@ -56,6 +83,7 @@ impl rustls::server::ClientCertVerifier for SkipClientVerification {
 pub(crate) fn configure_server(
     identity_keypair: &Keypair,
     gossip_host: IpAddr,
+    options: QuicServerOptions,
 ) -> Result<(ServerConfig, String), QuicServerError> {
     let (cert, priv_key) = new_self_signed_tls_certificate(identity_keypair, gossip_host)?;
     let cert_chain_pem_parts = vec![Pem {
@@ -72,6 +100,7 @@ pub(crate) fn configure_server(
 
     let mut server_config = ServerConfig::with_crypto(Arc::new(server_tls_config));
     server_config.use_retry(true);
+    server_config.migration(options.enable_migration);
     let config = Arc::get_mut(&mut server_config.transport).unwrap();
 
     // QUIC_MAX_CONCURRENT_STREAMS doubled, which was found to improve reliability
@@ -87,10 +116,14 @@ pub(crate) fn configure_server(
     let timeout = IdleTimeout::try_from(QUIC_MAX_TIMEOUT).unwrap();
     config.max_idle_timeout(Some(timeout));
 
-    // disable bidi & datagrams
+    // disable bidi, and datagrams unless enabled
     const MAX_CONCURRENT_BIDI_STREAMS: u32 = 0;
     config.max_concurrent_bidi_streams(MAX_CONCURRENT_BIDI_STREAMS.into());
-    config.datagram_receive_buffer_size(None);
+    config.datagram_receive_buffer_size(
+        options
+            .enable_datagrams
+            .then_some(PACKET_DATA_SIZE.saturating_mul(MAX_BUFFERED_DATAGRAMS)),
+    );
 
     Ok((server_config, cert_chain_pem))
 }
@@ -138,6 +171,12 @@ pub struct StreamStats {
     pub(crate) total_chunks_processed_by_batcher: AtomicUsize,
     pub(crate) total_stream_read_errors: AtomicUsize,
     pub(crate) total_stream_read_timeouts: AtomicUsize,
+    pub(crate) total_datagrams_received: AtomicUsize,
+    pub(crate) total_invalid_datagrams: AtomicUsize,
+    pub(crate) total_unstaked_datagrams_dropped: AtomicUsize,
+    pub(crate) total_datagrams_rate_limited: AtomicUsize,
+    pub(crate) connection_migrations: AtomicUsize,
+    pub(crate) connection_migration_closed: AtomicUsize,
     pub(crate) num_evictions: AtomicUsize,
     pub(crate) connection_added_from_staked_peer: AtomicUsize,
     pub(crate) connection_added_from_unstaked_peer: AtomicUsize,
@@ -386,6 +425,37 @@ impl StreamStats {
                 self.total_stream_read_timeouts.swap(0, Ordering::Relaxed),
                 i64
             ),
+            (
+                "datagrams_received",
+                self.total_datagrams_received.swap(0, Ordering::Relaxed),
+                i64
+            ),
+            (
+                "invalid_datagrams",
+                self.total_invalid_datagrams.swap(0, Ordering::Relaxed),
+                i64
+            ),
+            (
+                "unstaked_datagrams_dropped",
+                self.total_unstaked_datagrams_dropped
+                    .swap(0, Ordering::Relaxed),
+                i64
+            ),
+            (
+                "datagrams_rate_limited",
+                self.total_datagrams_rate_limited.swap(0, Ordering::Relaxed),
+                i64
+            ),
+            (
+                "connection_migrations",
+                self.connection_migrations.swap(0, Ordering::Relaxed),
+                i64
+            ),
+            (
+                "connection_migration_closed",
+                self.connection_migration_closed.swap(0, Ordering::Relaxed),
+                i64
+            ),
         );
     }
 }
@@ -400,10 +470,10 @@ pub fn spawn_server(
     exit: Arc<AtomicBool>,
     max_connections_per_peer: usize,
     staked_nodes: Arc<RwLock<StakedNodes>>,
-    max_staked_connections: usize,
-    max_unstaked_connections: usize,
+    qos_policy: Arc<dyn QosPolicy>,
     wait_for_chunk_timeout: Duration,
     coalesce: Duration,
+    options: QuicServerOptions,
 ) -> Result<(Endpoint, thread::JoinHandle<()>), QuicServerError> {
     let runtime = rt();
     let (endpoint, _stats, task) = {
@@ -417,10 +487,10 @@ pub fn spawn_server(
             exit,
             max_connections_per_peer,
             staked_nodes,
-            max_staked_connections,
-            max_unstaked_connections,
+            qos_policy,
             wait_for_chunk_timeout,
             coalesce,
+            options,
         )
     }?;
     let handle = thread::Builder::new()
@@ -438,10 +508,13 @@ pub fn spawn_server(
 mod test {
     use {
         super::*,
-        crate::nonblocking::quic::{test::*, DEFAULT_WAIT_FOR_CHUNK_TIMEOUT},
+        crate::{
+            nonblocking::quic::{test::*, DEFAULT_WAIT_FOR_CHUNK_TIMEOUT},
+            quic_qos::StakeWeightedQos,
+        },
         crossbeam_channel::unbounded,
-        solana_sdk::net::DEFAULT_TPU_COALESCE,
-        std::net::SocketAddr,
+        solana_sdk::{net::DEFAULT_TPU_COALESCE, signer::Signer},
+        std::{collections::HashMap, net::SocketAddr},
     };
 
     fn setup_quic_server() -> (
@@ -466,10 +539,10 @@ mod test {
             exit.clone(),
             1,
             staked_nodes,
-            MAX_STAKED_CONNECTIONS,
-            MAX_UNSTAKED_CONNECTIONS,
+            Arc::<StakeWeightedQos>::default(),
             DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
             DEFAULT_TPU_COALESCE,
+            QuicServerOptions::default(),
         )
         .unwrap();
         (t, exit, receiver, server_address)
@@ -522,10 +595,10 @@ mod test {
             exit.clone(),
             2,
             staked_nodes,
-            MAX_STAKED_CONNECTIONS,
-            MAX_UNSTAKED_CONNECTIONS,
+            Arc::<StakeWeightedQos>::default(),
             DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
             DEFAULT_TPU_COALESCE,
+            QuicServerOptions::default(),
         )
         .unwrap();
 
@@ -546,6 +619,56 @@ mod test {
         t.join().unwrap();
     }
 
+    #[test]
+    fn test_quic_server_datagrams() {
+        solana_logger::setup();
+        let s = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let exit = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = unbounded();
+        let keypair = Keypair::new();
+        let ip = "127.0.0.1".parse().unwrap();
+        let server_address = s.local_addr().unwrap();
+        let client_keypair = Keypair::new();
+        let staked_nodes = Arc::new(RwLock::new(StakedNodes::new(
+            Arc::new(HashMap::from([(client_keypair.pubkey(), 100_000)])),
+            HashMap::default(), // overrides
+        )));
+        let (_, t) = spawn_server(
+            "quic_streamer_test",
+            s,
+            &keypair,
+            ip,
+            sender,
+            exit.clone(),
+            1,
+            staked_nodes,
+            Arc::<StakeWeightedQos>::default(),
+            DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
+            DEFAULT_TPU_COALESCE,
+            QuicServerOptions {
+                enable_datagrams: true,
+                ..QuicServerOptions::default()
+            },
+        )
+        .unwrap();
+
+        let runtime = rt();
+        runtime.block_on(check_datagrams(receiver, server_address, &client_keypair));
+        exit.store(true, Ordering::Relaxed);
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn test_quic_server_connection_migration() {
+        solana_logger::setup();
+        let (t, exit, receiver, server_address) = setup_quic_server();
+
+        let runtime = rt();
+        runtime.block_on(check_connection_migration(receiver, server_address, None));
+        exit.store(true, Ordering::Relaxed);
+        t.join().unwrap();
+    }
+
     #[test]
     fn test_quic_server_unstaked_node_connect_failure() {
         solana_logger::setup();
@@ -565,10 +688,11 @@ mod test {
             exit.clone(),
             1,
             staked_nodes,
-            MAX_STAKED_CONNECTIONS,
-            0, // Do not allow any connection from unstaked clients/nodes
+            // Do not allow any connection from unstaked clients/nodes
+            Arc::new(StakeWeightedQos::new(MAX_STAKED_CONNECTIONS, 0)),
             DEFAULT_WAIT_FOR_CHUNK_TIMEOUT,
             DEFAULT_TPU_COALESCE,
+            QuicServerOptions::default(),
         )
         .unwrap();
 
@@ -0,0 +1,403 @@
+//! Policies for how the QUIC server treats connections from each peer: which
+//! connection table a connection is admitted into, the order connections are
+//! evicted in and how many concurrent streams a peer may open.
+
+use {
+    crate::{
+        nonblocking::quic::{compute_max_allowed_uni_streams, ConnectionPeerType},
+        quic::{MAX_STAKED_CONNECTIONS, MAX_UNSTAKED_CONNECTIONS},
+    },
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::{HashMap, HashSet},
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+};
+
+// Packet counts are halved at this interval so that the validity of a peer
+// reflects its recent traffic.
+const PACKET_VALIDITY_DECAY_INTERVAL: Duration = Duration::from_secs(10);
+// Minimum number of recent packets from a peer before its validity is taken
+// into account.
+const MIN_PACKETS_FOR_VALIDITY: u64 = 64;
+// Bounds the memory used to track peers; packets from new peers are ignored
+// once this many peers are tracked.
+const MAX_PACKET_VALIDITY_PEERS: usize = 100_000;
+// Bounds the memory used to map connections to the pubkeys of their peers.
+const MAX_PACKET_VALIDITY_CONNECTIONS: usize = 100_000;
+
+/// Identity and stake of the peer of a new connection.
+#[derive(Clone, Copy, Debug)]
+pub struct QosPeer {
+    /// Pubkey of the peer's TLS certificate, if any.
+    pub pubkey: Option<Pubkey>,
+    /// Address the connection was established from.
+    pub addr: SocketAddr,
+    /// Zero if the peer is unstaked or its pubkey is unknown.
+    pub stake: u64,
+    pub total_stake: u64,
+    pub max_stake: u64,
+    pub min_stake: u64,
+}
+
+pub trait QosPolicy: Send + Sync {
+    /// Maximum number of connections in the staked connection table.
+    fn max_staked_connections(&self) -> usize;
+
+    /// Maximum number of connections in the unstaked connection table.
+    fn max_unstaked_connections(&self) -> usize;
+
+    /// Stake the peer is treated as having. Peers with non-zero effective
+    /// stake are admitted into the staked connection table, where
+    /// connections with less stake are evicted first, and get stream limits
+    /// and receive windows in proportion to it.
+    fn effective_stake(&self, peer: &QosPeer) -> u64 {
+        peer.stake
+    }
+
+    /// Maximum number of concurrent uni streams of a connection from the peer
+    /// admitted into the connection table of the given type, where `stake`
+    /// is the effective stake of the peer.
+    fn max_uni_streams(&self, peer_type: ConnectionPeerType, peer: &QosPeer, stake: u64) -> usize {
+        compute_max_allowed_uni_streams(peer_type, stake, peer.total_stake)
+    }
+
+    /// Called when a connection from the peer with the given pubkey starts or
+    /// continues being served from the given address, e.g. after the
+    /// connection migrated.
+    fn connection_added(&self, _addr: SocketAddr, _pubkey: Option<Pubkey>) {}
+
+    /// Called when the connection is no longer served from the address.
+    fn connection_removed(&self, _addr: SocketAddr) {}
+}
+
+/// Treats peers according to their stake alone.
+pub struct StakeWeightedQos {
+    max_staked_connections: usize,
+    max_unstaked_connections: usize,
+}
+
+impl StakeWeightedQos {
+    pub fn new(max_staked_connections: usize, max_unstaked_connections: usize) -> Self {
+        Self {
+            max_staked_connections,
+            max_unstaked_connections,
+        }
+    }
+}
+
+impl Default for StakeWeightedQos {
+    fn default() -> Self {
+        Self::new(MAX_STAKED_CONNECTIONS, MAX_UNSTAKED_CONNECTIONS)
+    }
+}
+
+impl QosPolicy for StakeWeightedQos {
+    fn max_staked_connections(&self) -> usize {
+        self.max_staked_connections
+    }
+
+    fn max_unstaked_connections(&self) -> usize {
+        self.max_unstaked_connections
+    }
+}
+
+#[derive(Default)]
+struct PacketCounts {
+    valid: u64,
+    invalid: u64,
+}
+
+// Packets are attributed to the pubkey of the peer of the connection they
+// were received on, or to the connection itself if the peer did not present a
+// pubkey, so that peers behind the same IP address are told apart.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum PeerKey {
+    Pubkey(Pubkey),
+    Connection(SocketAddr),
+}
+
+struct PacketValidityTable {
+    peers: HashMap<PeerKey, PacketCounts>,
+    // Pubkeys of the peers of the connections currently served.
+    connections: HashMap<SocketAddr, Pubkey>,
+    last_decay: Instant,
+}
+
+impl PacketValidityTable {
+    fn peer_key(&self, addr: &SocketAddr) -> PeerKey {
+        self.connections
+            .get(addr)
+            .map(|pubkey| PeerKey::Pubkey(*pubkey))
+            .unwrap_or(PeerKey::Connection(*addr))
+    }
+}
+
+/// Recent counts of valid and invalid packets from each peer, fed back from
+/// the stages processing the packets received by the TPU QUIC server, e.g.
+/// packets failing signature verification or transactions whose fee payer
+/// cannot pay the fee. Packets received from tpu_forwards are not counted
+/// since they were sent by the forwarding node rather than by their origin.
+pub struct PeerPacketValidity {
+    table: Mutex<PacketValidityTable>,
+}
+
+impl Default for PeerPacketValidity {
+    fn default() -> Self {
+        Self {
+            table: Mutex::new(PacketValidityTable {
+                peers: HashMap::default(),
+                connections: HashMap::default(),
+                last_decay: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl PeerPacketValidity {
+    /// Attributes the packets received from the address to the peer with the
+    /// given pubkey until the connection is removed.
+    pub fn add_connection(&self, addr: SocketAddr, pubkey: Pubkey) {
+        let mut table = self.table.lock().unwrap();
+        if table.connections.len() < MAX_PACKET_VALIDITY_CONNECTIONS
+            || table.connections.contains_key(&addr)
+        {
+            table.connections.insert(addr, pubkey);
+        }
+    }
+
+    pub fn remove_connection(&self, addr: &SocketAddr) {
+        self.table.lock().unwrap().connections.remove(addr);
+    }
+
+    /// Records whether each packet received from the given address was valid.
+    pub fn record<I>(&self, packets: I)
+    where
+        I: IntoIterator<Item = (SocketAddr, /*valid:*/ bool)>,
+    {
+        let mut packets = packets.into_iter().peekable();
+        if packets.peek().is_none() {
+            return;
+        }
+        let mut table = self.table.lock().unwrap();
+        if table.last_decay.elapsed() >= PACKET_VALIDITY_DECAY_INTERVAL {
+            table.peers.retain(|_, counts| {
+                counts.valid /= 2;
+                counts.invalid /= 2;
+                counts.valid + counts.invalid > 0
+            });
+            table.last_decay = Instant::now();
+        }
+        for (addr, valid) in packets {
+            let key = table.peer_key(&addr);
+            if table.peers.len() >= MAX_PACKET_VALIDITY_PEERS && !table.peers.contains_key(&key) {
+                continue;
+            }
+            let counts = table.peers.entry(key).or_default();
+            if valid {
+                counts.valid = counts.valid.saturating_add(1);
+            } else {
+                counts.invalid = counts.invalid.saturating_add(1);
+            }
+        }
+    }
+
+    /// Returns the fraction of recent packets from the peer which were valid,
+    /// or None if too few packets were received from it.
+    pub fn validity(&self, peer: &QosPeer) -> Option<f64> {
+        let key = peer
+            .pubkey
+            .map(PeerKey::Pubkey)
+            .unwrap_or(PeerKey::Connection(peer.addr));
+        let table = self.table.lock().unwrap();
+        let counts = table.peers.get(&key)?;
+        let num_packets = counts.valid.saturating_add(counts.invalid);
+        (num_packets >= MIN_PACKETS_FOR_VALIDITY).then(|| counts.valid as f64 / num_packets as f64)
+    }
+}
+
+/// Scales the stake and stream limits of each peer by the fraction of its
+/// recent packets which were valid, so that peers sending invalid
+/// transactions are served last and evicted first. Peers on the allow-list,
+/// e.g. known RPC partners, are exempt and are treated as if they had the
+/// stake of the most staked node.
+pub struct PacketValidityQos {
+    stake_weighted: StakeWeightedQos,
+    allowed_peers: HashSet<Pubkey>,
+    peer_packet_validity: Arc<PeerPacketValidity>,
+}
+
+impl PacketValidityQos {
+    pub fn new(
+        stake_weighted: StakeWeightedQos,
+        allowed_peers: HashSet<Pubkey>,
+        peer_packet_validity: Arc<PeerPacketValidity>,
+    ) -> Self {
+        Self {
+            stake_weighted,
+            allowed_peers,
+            peer_packet_validity,
+        }
+    }
+
+    fn is_allowed(&self, peer: &QosPeer) -> bool {
+        peer.pubkey
+            .map(|pubkey| self.allowed_peers.contains(&pubkey))
+            .unwrap_or_default()
+    }
+}
+
+impl QosPolicy for PacketValidityQos {
+    fn max_staked_connections(&self) -> usize {
+        self.stake_weighted.max_staked_connections()
+    }
+
+    fn max_unstaked_connections(&self) -> usize {
+        self.stake_weighted.max_unstaked_connections()
+    }
+
+    fn effective_stake(&self, peer: &QosPeer) -> u64 {
+        if self.is_allowed(peer) {
+            return peer.stake.max(peer.max_stake);
+        }
+        match self.peer_packet_validity.validity(peer) {
+            None => peer.stake,
+            Some(validity) => (peer.stake as f64 * validity) as u64,
+        }
+    }
+
+    fn max_uni_streams(&self, peer_type: ConnectionPeerType, peer: &QosPeer, stake: u64) -> usize {
+        let max_uni_streams = compute_max_allowed_uni_streams(peer_type, stake, peer.total_stake);
+        // The stake of staked peers is already scaled by their validity.
+        if stake > 0 || self.is_allowed(peer) {
+            return max_uni_streams;
+        }
+        match self.peer_packet_validity.validity(peer) {
+            None => max_uni_streams,
+            Some(validity) => ((max_uni_streams as f64 * validity) as usize).max(1),
+        }
+    }
+
+    fn connection_added(&self, addr: SocketAddr, pubkey: Option<Pubkey>) {
+        if let Some(pubkey) = pubkey {
+            self.peer_packet_validity.add_connection(addr, pubkey);
+        }
+    }
+
+    fn connection_removed(&self, addr: SocketAddr) {
+        self.peer_packet_validity.remove_connection(&addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::quic::{
+            QUIC_MAX_UNSTAKED_CONCURRENT_STREAMS, QUIC_MIN_STAKED_CONCURRENT_STREAMS,
+        },
+        std::{
+            iter::repeat,
+            net::{IpAddr, Ipv4Addr},
+        },
+    };
+
+    fn new_addr(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), port)
+    }
+
+    fn new_peer(addr: SocketAddr, stake: u64) -> QosPeer {
+        QosPeer {
+            pubkey: Some(Pubkey::new_unique()),
+            addr,
+            stake,
+            total_stake: 10_000,
+            max_stake: 1_000,
+            min_stake: 10,
+        }
+    }
+
+    #[test]
+    fn test_peer_packet_validity() {
+        let validity = PeerPacketValidity::default();
+        // Peers without a pubkey behind the same IP address are told apart by
+        // their connection.
+        let peer = QosPeer {
+            pubkey: None,
+            ..new_peer(new_addr(8001), 0)
+        };
+        let other = QosPeer {
+            pubkey: None,
+            ..new_peer(new_addr(8002), 0)
+        };
+        validity.record(repeat((peer.addr, true)).take(30));
+        validity.record(repeat((other.addr, true)).take(100));
+        // Too few packets to tell.
+        assert_eq!(validity.validity(&peer), None);
+        validity.record(repeat((peer.addr, false)).take(90));
+        assert_eq!(validity.validity(&peer), Some(0.25));
+        assert_eq!(validity.validity(&other), Some(1.0));
+
+        // Packets from all connections of a peer with a pubkey count towards
+        // the peer, also after the connections are removed.
+        let staked = new_peer(new_addr(8003), 100);
+        let addr = new_addr(8004);
+        validity.add_connection(staked.addr, staked.pubkey.unwrap());
+        validity.add_connection(addr, staked.pubkey.unwrap());
+        validity.record(repeat((staked.addr, false)).take(50));
+        validity.record(repeat((addr, true)).take(50));
+        validity.remove_connection(&staked.addr);
+        validity.remove_connection(&addr);
+        assert_eq!(validity.validity(&new_peer(addr, 100)), None);
+        assert_eq!(validity.validity(&staked), Some(0.5));
+        // Packets from removed connections are attributed to the connection.
+        validity.record(repeat((addr, true)).take(100));
+        assert_eq!(validity.validity(&staked), Some(0.5));
+    }
+
+    #[test]
+    fn test_packet_validity_qos() {
+        let peer_packet_validity = Arc::<PeerPacketValidity>::default();
+        let good = new_peer(new_addr(8001), 100);
+        let bad = new_peer(new_addr(8002), 100);
+        let unstaked = new_peer(new_addr(8003), 0);
+        let allowed = new_peer(new_addr(8004), 0);
+        let qos = PacketValidityQos::new(
+            StakeWeightedQos::new(10, 5),
+            HashSet::from([allowed.pubkey.unwrap()]),
+            peer_packet_validity.clone(),
+        );
+        for peer in [&good, &bad, &unstaked, &allowed] {
+            qos.connection_added(peer.addr, peer.pubkey);
+        }
+        peer_packet_validity.record(repeat((good.addr, true)).take(100));
+        peer_packet_validity.record(repeat((bad.addr, false)).take(100));
+        peer_packet_validity.record(repeat((unstaked.addr, true)).take(50));
+        peer_packet_validity.record(repeat((unstaked.addr, false)).take(50));
+        peer_packet_validity.record(repeat((allowed.addr, false)).take(100));
+        assert_eq!(qos.max_staked_connections(), 10);
+        assert_eq!(qos.max_unstaked_connections(), 5);
+        assert_eq!(qos.effective_stake(&good), 100);
+        assert_eq!(qos.effective_stake(&bad), 0);
+        assert_eq!(qos.effective_stake(&unstaked), 0);
+        assert_eq!(qos.effective_stake(&allowed), 1_000);
+        assert_eq!(
+            qos.max_uni_streams(ConnectionPeerType::Unstaked, &unstaked, 0),
+            QUIC_MAX_UNSTAKED_CONCURRENT_STREAMS / 2
+        );
+        assert_eq!(
+            qos.max_uni_streams(ConnectionPeerType::Unstaked, &bad, 0),
+            1
+        );
+        assert!(
+            qos.max_uni_streams(ConnectionPeerType::Staked, &allowed, 1_000)
+                > QUIC_MIN_STAKED_CONCURRENT_STREAMS
+        );
+        // The stake weighted policy ignores packet validity.
+        let qos = StakeWeightedQos::default();
+        assert_eq!(qos.effective_stake(&bad), 100);
+        assert_eq!(qos.effective_stake(&allowed), 0);
+    }
+}
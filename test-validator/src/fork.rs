@@ -0,0 +1,404 @@
+//! Forking the accounts of another cluster into a test validator lazily.  Transactions which load
+//! accounts that the test validator does not have load them from the forked cluster instead, so
+//! tests do not have to list every account they use upfront.
+use {
+    log::*,
+    solana_account_decoder::UiAccountEncoding,
+    solana_accounts_db::{
+        accounts_db::{create_accounts_run_and_snapshot_dirs, AccountsDbConfig},
+        accounts_index::AccountSecondaryIndexes,
+        hardened_unpack::{open_genesis_config, MAX_GENESIS_ARCHIVE_UNPACKED_SIZE},
+    },
+    solana_client::{rpc_config::RpcAccountInfoConfig, rpc_request::MAX_MULTIPLE_ACCOUNTS},
+    solana_rpc_client::rpc_client::RpcClient,
+    solana_runtime::{
+        accounts_fallback::AccountsFallback, bank::Bank, runtime_config::RuntimeConfig,
+        snapshot_bank_utils,
+    },
+    solana_sdk::{
+        account::{AccountSharedData, ReadableAccount, WritableAccount},
+        account_utils::StateMut,
+        address_lookup_table::{
+            self,
+            state::{AddressLookupTable, LookupTableMeta},
+        },
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        clock::Slot,
+        commitment_config::CommitmentConfig,
+        feature,
+        pubkey::Pubkey,
+    },
+    std::{
+        collections::HashMap,
+        fmt, fs,
+        path::Path,
+        sync::{Arc, Mutex},
+    },
+};
+
+/// Loads the accounts that a test validator is missing from the RPC service of the cluster it
+/// forks.  RPC services only serve the latest state of their accounts, so the fork is not pinned
+/// to a slot: every account is fetched once, at the minimum slot or after it, and then kept as it
+/// was fetched.  Responses from a state older than the minimum slot are rejected.
+pub struct RpcAccountsFallback {
+    rpc_client: RpcClient,
+    min_slot: Slot,
+    /// The accounts fetched so far, including those which the cluster does not have
+    accounts: Mutex<HashMap<Pubkey, Option<AccountSharedData>>>,
+}
+
+impl fmt::Debug for RpcAccountsFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RpcAccountsFallback")
+            .field("url", &self.rpc_client.url())
+            .field("min_slot", &self.min_slot)
+            .finish()
+    }
+}
+
+impl RpcAccountsFallback {
+    /// Forks the cluster of `rpc_client` at `min_slot` or after it, or at its current slot or
+    /// after it if there is none
+    pub fn new(rpc_client: RpcClient, min_slot: Option<Slot>) -> Result<Self, String> {
+        let min_slot = match min_slot {
+            Some(min_slot) => min_slot,
+            None => rpc_client
+                .get_slot_with_commitment(CommitmentConfig::finalized())
+                .map_err(|err| format!("Failed to get the slot to fork: {err}"))?,
+        };
+        info!("Forking {} at slot {} or after", rpc_client.url(), min_slot);
+        Ok(Self {
+            rpc_client,
+            min_slot,
+            accounts: Mutex::default(),
+        })
+    }
+
+    /// The oldest slot of the cluster that accounts are fetched at
+    pub fn min_slot(&self) -> Slot {
+        self.min_slot
+    }
+
+    fn fetch_accounts(&self, pubkeys: &[Pubkey]) -> Vec<(Pubkey, Option<AccountSharedData>)> {
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64Zstd),
+            commitment: Some(CommitmentConfig::finalized()),
+            min_context_slot: Some(self.min_slot),
+            ..RpcAccountInfoConfig::default()
+        };
+        let mut fetched_accounts = Vec::with_capacity(pubkeys.len());
+        for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            info!("Fetching {:?} over RPC...", chunk);
+            match self
+                .rpc_client
+                .get_multiple_accounts_with_config(chunk, config.clone())
+            {
+                // Not remembered as missing, so the next transaction that loads them retries
+                Ok(response) if response.context.slot < self.min_slot => warn!(
+                    "Failed to fetch {:?}: the cluster served slot {}, older than slot {}",
+                    chunk, response.context.slot, self.min_slot
+                ),
+                Ok(response) => {
+                    if response.context.slot > self.min_slot {
+                        info!(
+                            "Fetched {:?} at slot {}, after slot {}",
+                            chunk, response.context.slot, self.min_slot
+                        );
+                    }
+                    fetched_accounts.extend(chunk.iter().zip(response.value).map(
+                        |(pubkey, account)| {
+                            let account = account
+                                .map(AccountSharedData::from)
+                                .and_then(|account| fork_account(pubkey, account));
+                            (*pubkey, account)
+                        },
+                    ))
+                }
+                Err(err) => warn!("Failed to fetch {:?}: {}", chunk, err),
+            }
+        }
+        fetched_accounts
+    }
+}
+
+impl AccountsFallback for RpcAccountsFallback {
+    fn load_accounts(&self, pubkeys: &[Pubkey]) -> Vec<(Pubkey, AccountSharedData)> {
+        let unfetched_pubkeys: Vec<_> = {
+            let accounts = self.accounts.lock().unwrap();
+            pubkeys
+                .iter()
+                .filter(|pubkey| !accounts.contains_key(pubkey))
+                .copied()
+                .collect()
+        };
+        // The lock is not held while fetching, so that loading accounts which were already
+        // fetched does not wait on the network.  If the same account is fetched concurrently,
+        // the state fetched first is kept.
+        let fetched_accounts = if unfetched_pubkeys.is_empty() {
+            vec![]
+        } else {
+            self.fetch_accounts(&unfetched_pubkeys)
+        };
+        let mut accounts = self.accounts.lock().unwrap();
+        for (pubkey, account) in fetched_accounts {
+            accounts.entry(pubkey).or_insert(account);
+        }
+        pubkeys
+            .iter()
+            .filter_map(|pubkey| Some((*pubkey, accounts.get(pubkey)?.clone()?)))
+            .collect()
+    }
+}
+
+/// Loads the accounts that a test validator is missing from the latest snapshot of the ledger of
+/// the cluster it forks
+pub struct SnapshotAccountsFallback {
+    bank: Bank,
+}
+
+impl fmt::Debug for SnapshotAccountsFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotAccountsFallback")
+            .field("fork_slot", &self.bank.slot())
+            .finish()
+    }
+}
+
+impl SnapshotAccountsFallback {
+    /// Loads the latest snapshot archives in `fork_ledger_path`, which also has to contain the
+    /// genesis of the forked cluster.  The accounts of the snapshot are unpacked in `work_path`,
+    /// replacing whatever it contained.
+    pub fn new(fork_ledger_path: &Path, work_path: &Path) -> Result<Self, String> {
+        if work_path.exists() {
+            fs::remove_dir_all(work_path)
+                .map_err(|err| format!("Failed to remove {}: {err}", work_path.display()))?;
+        }
+        let genesis_config =
+            open_genesis_config(fork_ledger_path, MAX_GENESIS_ARCHIVE_UNPACKED_SIZE);
+        let (account_run_path, _account_snapshot_path) =
+            create_accounts_run_and_snapshot_dirs(work_path.join("accounts"))
+                .map_err(|err| format!("Failed to create accounts directories: {err}"))?;
+        let bank_snapshots_dir = work_path.join("snapshot");
+        fs::create_dir_all(&bank_snapshots_dir)
+            .map_err(|err| format!("Failed to create {}: {err}", bank_snapshots_dir.display()))?;
+
+        let (bank, ..) = snapshot_bank_utils::bank_from_latest_snapshot_archives(
+            &bank_snapshots_dir,
+            fork_ledger_path,
+            fork_ledger_path,
+            &[account_run_path],
+            &genesis_config,
+            &RuntimeConfig::default(),
+            None,
+            None,
+            AccountSecondaryIndexes::default(),
+            None,
+            Default::default(),
+            false,
+            true,
+            false,
+            false,
+            Some(AccountsDbConfig::default()),
+            None,
+            Arc::default(),
+        )
+        .map_err(|err| {
+            format!(
+                "Failed to load snapshot from {}: {err}",
+                fork_ledger_path.display()
+            )
+        })?;
+        info!(
+            "Forking the snapshot in {} at slot {}",
+            fork_ledger_path.display(),
+            bank.slot()
+        );
+        Ok(Self { bank })
+    }
+
+    pub fn fork_slot(&self) -> Slot {
+        self.bank.slot()
+    }
+}
+
+impl AccountsFallback for SnapshotAccountsFallback {
+    fn load_accounts(&self, pubkeys: &[Pubkey]) -> Vec<(Pubkey, AccountSharedData)> {
+        pubkeys
+            .iter()
+            .filter_map(|pubkey| {
+                let account = fork_account(pubkey, self.bank.get_account(pubkey)?)?;
+                Some((*pubkey, account))
+            })
+            .collect()
+    }
+}
+
+/// Adjusts an account of the forked cluster to the test validator, which starts at slot 0.
+/// Feature accounts are not forked, as they would change the features of the test validator.
+fn fork_account(pubkey: &Pubkey, mut account: AccountSharedData) -> Option<AccountSharedData> {
+    if feature::check_id(account.owner()) {
+        debug!("Not forking feature account {}", pubkey);
+        return None;
+    }
+    if bpf_loader_upgradeable::check_id(account.owner()) {
+        // Programs deployed at a later slot than the current one are not visible yet
+        if let Ok(UpgradeableLoaderState::ProgramData {
+            upgrade_authority_address,
+            ..
+        }) = account.state()
+        {
+            account
+                .set_state(&UpgradeableLoaderState::ProgramData {
+                    slot: 0,
+                    upgrade_authority_address,
+                })
+                .ok()?;
+        }
+    } else if address_lookup_table::program::check_id(account.owner()) {
+        // Addresses extended at a later slot than the current one are not usable yet
+        let meta = AddressLookupTable::deserialize(account.data())
+            .ok()
+            .map(|lookup_table| lookup_table.meta)?;
+        AddressLookupTable::overwrite_meta_data(
+            account.data_as_mut_slice(),
+            LookupTableMeta {
+                last_extended_slot: 0,
+                ..meta
+            },
+        )
+        .ok()?;
+    }
+    Some(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_account_decoder::UiAccount,
+        solana_client::{
+            rpc_request::RpcRequest,
+            rpc_response::{Response, RpcResponseContext},
+        },
+        solana_rpc_client::mock_sender::Mocks,
+        solana_sdk::{account::Account, address_lookup_table::state::LOOKUP_TABLE_META_SIZE},
+        std::borrow::Cow,
+    };
+
+    fn rpc_client_with_accounts(slot: Slot, accounts: &[(Pubkey, Option<Account>)]) -> RpcClient {
+        let value: Vec<_> = accounts
+            .iter()
+            .map(|(pubkey, account)| {
+                account.as_ref().map(|account| {
+                    UiAccount::encode(pubkey, account, UiAccountEncoding::Base64, None, None)
+                })
+            })
+            .collect();
+        let mut mocks = Mocks::default();
+        mocks.insert(
+            RpcRequest::GetMultipleAccounts,
+            serde_json::to_value(Response {
+                context: RpcResponseContext {
+                    slot,
+                    api_version: None,
+                },
+                value,
+            })
+            .unwrap(),
+        );
+        RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks)
+    }
+
+    #[test]
+    fn test_rpc_accounts_fallback() {
+        let pubkey = Pubkey::new_unique();
+        let missing_pubkey = Pubkey::new_unique();
+        let account = Account::new(42, 0, &Pubkey::new_unique());
+        let rpc_client = rpc_client_with_accounts(
+            7,
+            &[(pubkey, Some(account.clone())), (missing_pubkey, None)],
+        );
+        let accounts_fallback = RpcAccountsFallback::new(rpc_client, Some(5)).unwrap();
+        assert_eq!(accounts_fallback.min_slot(), 5);
+        assert_eq!(
+            accounts_fallback.load_accounts(&[pubkey, missing_pubkey]),
+            vec![(pubkey, AccountSharedData::from(account.clone()))]
+        );
+
+        // Both accounts are kept as they were fetched, the mock only answers once
+        assert_eq!(
+            accounts_fallback.load_accounts(&[missing_pubkey, pubkey]),
+            vec![(pubkey, AccountSharedData::from(account))]
+        );
+        let accounts = accounts_fallback.accounts.lock().unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts.get(&missing_pubkey), Some(&None));
+    }
+
+    #[test]
+    fn test_rpc_accounts_fallback_rejects_older_slot() {
+        let pubkey = Pubkey::new_unique();
+        let account = Account::new(42, 0, &Pubkey::new_unique());
+        let rpc_client = rpc_client_with_accounts(4, &[(pubkey, Some(account))]);
+        let accounts_fallback = RpcAccountsFallback::new(rpc_client, Some(5)).unwrap();
+        assert!(accounts_fallback.load_accounts(&[pubkey]).is_empty());
+        // Not remembered as missing, so that it is fetched again
+        assert!(accounts_fallback.accounts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fork_account() {
+        let pubkey = Pubkey::new_unique();
+        let account = AccountSharedData::new(42, 10, &Pubkey::new_unique());
+        assert_eq!(fork_account(&pubkey, account.clone()), Some(account));
+
+        let feature_account = AccountSharedData::new(42, 10, &feature::id());
+        assert_eq!(fork_account(&pubkey, feature_account), None);
+
+        let upgrade_authority_address = Some(Pubkey::new_unique());
+        let mut program_data_account = AccountSharedData::new(
+            42,
+            UpgradeableLoaderState::size_of_programdata_metadata(),
+            &bpf_loader_upgradeable::id(),
+        );
+        program_data_account
+            .set_state(&UpgradeableLoaderState::ProgramData {
+                slot: 1_000,
+                upgrade_authority_address,
+            })
+            .unwrap();
+        let program_data_account = fork_account(&pubkey, program_data_account).unwrap();
+        assert_eq!(
+            program_data_account.state(),
+            Ok(UpgradeableLoaderState::ProgramData {
+                slot: 0,
+                upgrade_authority_address,
+            })
+        );
+
+        let addresses = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let lookup_table = AddressLookupTable {
+            meta: LookupTableMeta {
+                last_extended_slot: 1_000,
+                last_extended_slot_start_index: 1,
+                ..LookupTableMeta::default()
+            },
+            addresses: Cow::Owned(addresses.clone()),
+        };
+        let data = lookup_table.serialize_for_tests().unwrap();
+        assert_eq!(data.len(), LOOKUP_TABLE_META_SIZE + 2 * 32);
+        let mut lookup_table_account =
+            AccountSharedData::new(42, data.len(), &address_lookup_table::program::id());
+        lookup_table_account.set_data_from_slice(&data);
+        let lookup_table_account = fork_account(&pubkey, lookup_table_account).unwrap();
+        let lookup_table = AddressLookupTable::deserialize(lookup_table_account.data()).unwrap();
+        assert_eq!(lookup_table.meta.last_extended_slot, 0);
+        assert_eq!(lookup_table.meta.last_extended_slot_start_index, 1);
+        assert_eq!(lookup_table.addresses.as_ref(), addresses.as_slice());
+
+        // Lookup tables which cannot be adjusted are not forked
+        let invalid_lookup_table_account =
+            AccountSharedData::new(42, 10, &address_lookup_table::program::id());
+        assert_eq!(fork_account(&pubkey, invalid_lookup_table_account), None);
+    }
+}
@@ -33,8 +33,11 @@ use {
     solana_rpc::{rpc::JsonRpcConfig, rpc_pubsub_service::PubSubConfig},
     solana_rpc_client::{nonblocking, rpc_client::RpcClient},
     solana_runtime::{
-        bank_forks::BankForks, genesis_utils::create_genesis_config_with_leader_ex,
-        runtime_config::RuntimeConfig, snapshot_config::SnapshotConfig,
+        accounts_fallback::{AccountsFallback, SharedAccountsFallback},
+        bank_forks::BankForks,
+        genesis_utils::create_genesis_config_with_leader_ex,
+        runtime_config::RuntimeConfig,
+        snapshot_config::SnapshotConfig,
     },
     solana_sdk::{
         account::{Account, AccountSharedData},
@@ -72,6 +75,8 @@ use {
     tokio::time::sleep,
 };
 
+pub mod fork;
+
 #[derive(Clone)]
 pub struct AccountInfo<'a> {
     pub address: Option<Pubkey>,
@@ -127,6 +132,7 @@ pub struct TestValidatorGenesis {
     rpc_ports: Option<(u16, u16)>, // (JsonRpc, JsonRpcPubSub), None == random ports
     warp_slot: Option<Slot>,
     accounts: HashMap<Pubkey, AccountSharedData>,
+    accounts_fallback: Option<Arc<dyn AccountsFallback>>,
     #[allow(deprecated)]
     programs: Vec<ProgramInfo>,
     upgradeable_programs: Vec<UpgradeableProgramInfo>,
@@ -144,6 +150,9 @@ pub struct TestValidatorGenesis {
     compute_unit_limit: Option<u64>,
     pub log_messages_bytes_limit: Option<usize>,
     pub transaction_account_lock_limit: Option<usize>,
+    /// Compile programs with instruction tracing, so that simulations can return the execution
+    /// traces of the SBF programs they invoke
+    pub enable_instruction_tracing: bool,
     pub tpu_enable_udp: bool,
     pub geyser_plugin_manager: Arc<RwLock<GeyserPluginManager>>,
     admin_rpc_service_post_init: Arc<RwLock<Option<AdminRpcRequestMetadataPostInit>>>,
@@ -161,6 +170,7 @@ impl Default for TestValidatorGenesis {
             rpc_ports: Option::<(u16, u16)>::default(),
             warp_slot: Option::<Slot>::default(),
             accounts: HashMap::<Pubkey, AccountSharedData>::default(),
+            accounts_fallback: Option::<Arc<dyn AccountsFallback>>::default(),
             #[allow(deprecated)]
             programs: Vec::<ProgramInfo>::default(),
             upgradeable_programs: Vec::<UpgradeableProgramInfo>::default(),
@@ -178,6 +188,7 @@ impl Default for TestValidatorGenesis {
             compute_unit_limit: Option::<u64>::default(),
             log_messages_bytes_limit: Option::<usize>::default(),
             transaction_account_lock_limit: Option::<usize>::default(),
+            enable_instruction_tracing: false,
             tpu_enable_udp: DEFAULT_TPU_ENABLE_UDP,
             geyser_plugin_manager: Arc::new(RwLock::new(GeyserPluginManager::new())),
             admin_rpc_service_post_init:
@@ -365,6 +376,13 @@ impl TestValidatorGenesis {
         Ok(self)
     }
 
+    /// Load the accounts that transactions use but the test validator does not have from
+    /// `accounts_fallback`, such as a [`fork::RpcAccountsFallback`] forking another cluster
+    pub fn accounts_fallback(&mut self, accounts_fallback: Arc<dyn AccountsFallback>) -> &mut Self {
+        self.accounts_fallback = Some(accounts_fallback);
+        self
+    }
+
     pub fn add_accounts_from_json_files(
         &mut self,
         accounts: &[AccountInfo],
@@ -924,6 +942,11 @@ impl TestValidator {
                 }),
             log_messages_bytes_limit: config.log_messages_bytes_limit,
             transaction_account_lock_limit: config.transaction_account_lock_limit,
+            enable_instruction_tracing: config.enable_instruction_tracing,
+            accounts_fallback: config
+                .accounts_fallback
+                .clone()
+                .map(SharedAccountsFallback::new),
         };
 
         let mut validator_config = ValidatorConfig {
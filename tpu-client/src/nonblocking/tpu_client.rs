@@ -10,7 +10,7 @@ use {
     solana_connection_cache::{
         connection_cache::{
             ConnectionCache, ConnectionManager, ConnectionPool, NewConnectionConfig, Protocol,
-            DEFAULT_CONNECTION_POOL_SIZE,
+            UpcomingLeaders, DEFAULT_CONNECTION_POOL_SIZE,
         },
         nonblocking::client_connection::ClientConnection,
     },
@@ -38,7 +38,7 @@ use {
         str::FromStr,
         sync::{
             atomic::{AtomicBool, Ordering},
-            Arc, RwLock,
+            Arc, Mutex, RwLock,
         },
     },
     thiserror::Error,
@@ -159,6 +159,23 @@ impl LeaderTpuCache {
         leader_sockets
     }
 
+    // Get the TPU sockets of the current leader and the next distinct leaders, up to
+    // `num_leaders` in total
+    fn get_upcoming_leader_sockets(
+        &self,
+        estimated_current_slot: Slot,
+        num_leaders: usize,
+    ) -> Vec<SocketAddr> {
+        let mut leader_set = HashSet::new();
+        let current_slot = std::cmp::max(estimated_current_slot, self.first_slot);
+        (current_slot..=self.last_slot())
+            .filter_map(|slot| self.get_slot_leader(slot))
+            .filter(|leader| leader_set.insert(**leader))
+            .filter_map(|leader| self.leader_tpu_map.get(leader).copied())
+            .take(num_leaders)
+            .collect()
+    }
+
     pub fn get_slot_leader(&self, slot: Slot) -> Option<&Pubkey> {
         if slot >= self.first_slot {
             let index = slot - self.first_slot;
@@ -239,6 +256,45 @@ impl LeaderTpuCache {
     }
 }
 
+/// Statistics of the transactions sent to a leader's TPU
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LeaderSendStats {
+    /// Identity of the leader, if its TPU address is still known
+    pub leader: Option<Pubkey>,
+    pub successes: u64,
+    pub failures: u64,
+    /// Sum of the latencies of all sends, including connection handshakes
+    pub total_latency_us: u64,
+    pub max_latency_us: u64,
+}
+
+impl LeaderSendStats {
+    pub fn average_latency_us(&self) -> u64 {
+        let num_sends = self.successes.saturating_add(self.failures);
+        self.total_latency_us
+            .checked_div(num_sends)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+struct LeaderSendStatsTracker(Mutex<HashMap<SocketAddr, LeaderSendStats>>);
+
+impl LeaderSendStatsTracker {
+    fn record(&self, addr: &SocketAddr, result: &TransportResult<()>, latency: Duration) {
+        let latency_us = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        let mut leader_send_stats = self.0.lock().unwrap();
+        let stats = leader_send_stats.entry(*addr).or_default();
+        if result.is_ok() {
+            stats.successes = stats.successes.saturating_add(1);
+        } else {
+            stats.failures = stats.failures.saturating_add(1);
+        }
+        stats.total_latency_us = stats.total_latency_us.saturating_add(latency_us);
+        stats.max_latency_us = stats.max_latency_us.max(latency_us);
+    }
+}
+
 /// Client which sends transactions directly to the current leader's TPU port over UDP.
 /// The client uses RPC to determine the current leader and fetch node contact info
 pub struct TpuClient<
@@ -251,6 +307,7 @@ pub struct TpuClient<
     exit: Arc<AtomicBool>,
     rpc_client: Arc<RpcClient>,
     connection_cache: Arc<ConnectionCache<P, M, C>>,
+    leader_send_stats: LeaderSendStatsTracker,
 }
 
 /// Helper function which generates futures to all be awaited together for maximum
@@ -264,6 +321,7 @@ fn send_wire_transaction_futures<'a, P, M, C>(
     wire_transaction: Vec<u8>,
     leaders: Vec<SocketAddr>,
     connection_cache: &'a ConnectionCache<P, M, C>,
+    leader_send_stats: &'a LeaderSendStatsTracker,
 ) -> Vec<impl Future<Output = TransportResult<()>> + 'a>
 where
     P: ConnectionPool<NewConnectionConfig = C>,
@@ -281,6 +339,7 @@ where
                 sleep_and_send_wire_transaction_to_addr(
                     sleep_duration,
                     connection_cache,
+                    leader_send_stats,
                     addr,
                     wire_transaction.clone(),
                 ),
@@ -334,6 +393,7 @@ async fn sleep_and_set_message(
 async fn sleep_and_send_wire_transaction_to_addr<P, M, C>(
     sleep_duration: Duration,
     connection_cache: &ConnectionCache<P, M, C>,
+    leader_send_stats: &LeaderSendStatsTracker,
     addr: SocketAddr,
     wire_transaction: Vec<u8>,
 ) -> TransportResult<()>
@@ -343,11 +403,13 @@ where
     C: NewConnectionConfig,
 {
     sleep(sleep_duration).await;
-    send_wire_transaction_to_addr(connection_cache, &addr, wire_transaction).await
+    send_wire_transaction_to_addr(connection_cache, leader_send_stats, &addr, wire_transaction)
+        .await
 }
 
 async fn send_wire_transaction_to_addr<P, M, C>(
     connection_cache: &ConnectionCache<P, M, C>,
+    leader_send_stats: &LeaderSendStatsTracker,
     addr: &SocketAddr,
     wire_transaction: Vec<u8>,
 ) -> TransportResult<()>
@@ -356,12 +418,16 @@ where
     M: ConnectionManager<ConnectionPool = P, NewConnectionConfig = C>,
     C: NewConnectionConfig,
 {
+    let start = Instant::now();
     let conn = connection_cache.get_nonblocking_connection(addr);
-    conn.send_data(&wire_transaction).await
+    let result = conn.send_data(&wire_transaction).await;
+    leader_send_stats.record(addr, &result, start.elapsed());
+    result
 }
 
 async fn send_wire_transaction_batch_to_addr<P, M, C>(
     connection_cache: &ConnectionCache<P, M, C>,
+    leader_send_stats: &LeaderSendStatsTracker,
     addr: &SocketAddr,
     wire_transactions: &[Vec<u8>],
 ) -> TransportResult<()>
@@ -370,8 +436,11 @@ where
     M: ConnectionManager<ConnectionPool = P, NewConnectionConfig = C>,
     C: NewConnectionConfig,
 {
+    let start = Instant::now();
     let conn = connection_cache.get_nonblocking_connection(addr);
-    conn.send_data_batch(wire_transactions).await
+    let result = conn.send_data_batch(wire_transactions).await;
+    leader_send_stats.record(addr, &result, start.elapsed());
+    result
 }
 
 impl<P, M, C> TpuClient<P, M, C>
@@ -416,6 +485,7 @@ where
             .map(|addr| {
                 send_wire_transaction_to_addr(
                     &self.connection_cache,
+                    &self.leader_send_stats,
                     addr,
                     wire_transaction.clone(),
                 )
@@ -460,6 +530,7 @@ where
             .map(|addr| {
                 send_wire_transaction_batch_to_addr(
                     &self.connection_cache,
+                    &self.leader_send_stats,
                     addr,
                     &wire_transactions,
                 )
@@ -514,6 +585,14 @@ where
         let leader_tpu_service =
             LeaderTpuService::new(rpc_client.clone(), websocket_url, M::PROTOCOL, exit.clone())
                 .await?;
+        if matches!(M::PROTOCOL, Protocol::QUIC) && config.num_warm_leaders > 0 {
+            // The thread exits shortly after `exit` is set.
+            let _warm_connections_thread = connection_cache.spawn_warm_upcoming_leaders(
+                Arc::new(leader_tpu_service.upcoming_leaders()),
+                config.num_warm_leaders,
+                exit.clone(),
+            );
+        }
 
         Ok(Self {
             fanout_slots: config.fanout_slots.clamp(1, MAX_FANOUT_SLOTS),
@@ -521,9 +600,27 @@ where
             exit,
             rpc_client,
             connection_cache,
+            leader_send_stats: LeaderSendStatsTracker::default(),
         })
     }
 
+    /// Returns the send statistics of each leader TPU address transactions
+    /// were sent to
+    pub fn leader_send_stats(&self) -> HashMap<SocketAddr, LeaderSendStats> {
+        let tpu_leaders = self.leader_tpu_service.tpu_leaders();
+        let leader_send_stats = self.leader_send_stats.0.lock().unwrap();
+        leader_send_stats
+            .iter()
+            .map(|(addr, stats)| {
+                let stats = LeaderSendStats {
+                    leader: tpu_leaders.get(addr).copied(),
+                    ..stats.clone()
+                };
+                (*addr, stats)
+            })
+            .collect()
+    }
+
     #[cfg(feature = "spinner")]
     pub async fn send_and_confirm_messages_with_spinner<T: Signers + ?Sized>(
         &self,
@@ -576,6 +673,7 @@ where
                             wire_transaction,
                             leaders,
                             &self.connection_cache,
+                            &self.leader_send_stats,
                         ));
                     }
 
@@ -760,6 +858,25 @@ impl LeaderTpuService {
             .get_leader_sockets(current_slot, fanout_slots)
     }
 
+    // Map of TPU socket to the identity of the node
+    fn tpu_leaders(&self) -> HashMap<SocketAddr, Pubkey> {
+        let leader_tpu_cache = self.leader_tpu_cache.read().unwrap();
+        leader_tpu_cache
+            .leader_tpu_map
+            .iter()
+            .map(|(leader, addr)| (*addr, *leader))
+            .collect()
+    }
+
+    /// Returns the upcoming leaders tracked by this service, used to keep
+    /// connections to them warm
+    pub fn upcoming_leaders(&self) -> UpcomingLeaderTpus {
+        UpcomingLeaderTpus {
+            recent_slots: self.recent_slots.clone(),
+            leader_tpu_cache: self.leader_tpu_cache.clone(),
+        }
+    }
+
     async fn run(
         rpc_client: Arc<RpcClient>,
         recent_slots: RecentLeaderSlots,
@@ -834,6 +951,23 @@ impl LeaderTpuService {
     }
 }
 
+/// TPU sockets of the upcoming leaders according to the leader schedule
+/// tracked by a `LeaderTpuService`
+pub struct UpcomingLeaderTpus {
+    recent_slots: RecentLeaderSlots,
+    leader_tpu_cache: Arc<RwLock<LeaderTpuCache>>,
+}
+
+impl UpcomingLeaders for UpcomingLeaderTpus {
+    fn upcoming_leader_addrs(&self, num_leaders: usize) -> Vec<SocketAddr> {
+        let current_slot = self.recent_slots.estimated_current_slot();
+        self.leader_tpu_cache
+            .read()
+            .unwrap()
+            .get_upcoming_leader_sockets(current_slot, num_leaders)
+    }
+}
+
 async fn maybe_fetch_cache_info(
     leader_tpu_cache: &Arc<RwLock<LeaderTpuCache>>,
     last_cluster_refresh: Instant,
@@ -879,3 +1013,62 @@ async fn maybe_fetch_cache_info(
         maybe_slot_leaders,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_upcoming_leader_sockets() {
+        let leaders: Vec<_> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let leader_tpu_map: HashMap<_, _> = leaders
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, leader)| {
+                let addr = SocketAddr::from(([127, 0, 0, 1], 8000 + i as u16));
+                (*leader, addr)
+            })
+            .collect();
+        let leader_tpu_cache = LeaderTpuCache {
+            protocol: Protocol::QUIC,
+            first_slot: 10,
+            leaders: leaders
+                .iter()
+                .flat_map(|leader| iter::repeat(*leader).take(4))
+                .collect(),
+            leader_tpu_map: leader_tpu_map.clone(),
+            slots_in_epoch: 32,
+            last_epoch_info_slot: 10,
+        };
+        // The first leader has no known TPU socket.
+        assert_eq!(
+            leader_tpu_cache.get_upcoming_leader_sockets(10, 2),
+            vec![leader_tpu_map[&leaders[1]], leader_tpu_map[&leaders[2]]]
+        );
+        assert_eq!(
+            leader_tpu_cache.get_upcoming_leader_sockets(22, 8),
+            vec![leader_tpu_map[&leaders[3]]]
+        );
+        assert!(leader_tpu_cache
+            .get_upcoming_leader_sockets(26, 8)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_leader_send_stats() {
+        let tracker = LeaderSendStatsTracker::default();
+        let addr = SocketAddr::from(([127, 0, 0, 1], 8000));
+        tracker.record(&addr, &Ok(()), Duration::from_micros(100));
+        tracker.record(
+            &addr,
+            &Err(TransportError::Custom("Timed out".to_string())),
+            Duration::from_micros(500),
+        );
+        let stats = tracker.0.lock().unwrap()[&addr].clone();
+        assert_eq!(stats.successes, 1);
+        assert_eq!(stats.failures, 1);
+        assert_eq!(stats.max_latency_us, 500);
+        assert_eq!(stats.average_latency_us(), 300);
+    }
+}
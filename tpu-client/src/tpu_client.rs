@@ -1,4 +1,4 @@
-pub use crate::nonblocking::tpu_client::TpuSenderError;
+pub use crate::nonblocking::tpu_client::{LeaderSendStats, TpuSenderError};
 use {
     crate::nonblocking::tpu_client::TpuClient as NonblockingTpuClient,
     rayon::iter::{IntoParallelIterator, ParallelIterator},
@@ -8,8 +8,8 @@ use {
     solana_rpc_client::rpc_client::RpcClient,
     solana_sdk::{clock::Slot, transaction::Transaction, transport::Result as TransportResult},
     std::{
-        collections::VecDeque,
-        net::UdpSocket,
+        collections::{HashMap, VecDeque},
+        net::{SocketAddr, UdpSocket},
         sync::{Arc, RwLock},
     },
 };
@@ -38,18 +38,26 @@ pub const DEFAULT_FANOUT_SLOTS: u64 = 12;
 /// Maximum number of slots used to build TPU socket fanout set
 pub const MAX_FANOUT_SLOTS: u64 = 100;
 
+/// Default number of upcoming leaders to keep QUIC connections warm to
+pub const DEFAULT_NUM_WARM_LEADERS: usize = 8;
+
 /// Config params for `TpuClient`
 #[derive(Clone, Debug)]
 pub struct TpuClientConfig {
     /// The range of upcoming slots to include when determining which
     /// leaders to send transactions to (min: 1, max: `MAX_FANOUT_SLOTS`)
     pub fanout_slots: u64,
+    /// The number of upcoming leaders to keep QUIC connections open to, so
+    /// that sends at the start of their leader slots do not wait on
+    /// handshakes (0 disables pre-warming)
+    pub num_warm_leaders: usize,
 }
 
 impl Default for TpuClientConfig {
     fn default() -> Self {
         Self {
             fanout_slots: DEFAULT_FANOUT_SLOTS,
+            num_warm_leaders: DEFAULT_NUM_WARM_LEADERS,
         }
     }
 }
@@ -175,6 +183,12 @@ where
         &self.rpc_client
     }
 
+    /// Returns the send statistics of each leader TPU address transactions
+    /// were sent to
+    pub fn leader_send_stats(&self) -> HashMap<SocketAddr, LeaderSendStats> {
+        self.tpu_client.leader_send_stats()
+    }
+
     fn invoke<T, F: std::future::Future<Output = T>>(&self, f: F) -> T {
         // `block_on()` panics if called within an asynchronous execution context. Whereas
         // `block_in_place()` only panics if called from a current_thread runtime, which is the
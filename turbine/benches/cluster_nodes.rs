@@ -4,7 +4,9 @@ extern crate test;
 
 use {
     rand::{seq::SliceRandom, Rng},
-    solana_gossip::legacy_contact_info::LegacyContactInfo as ContactInfo,
+    solana_gossip::{
+        contact_info::Protocol, legacy_contact_info::LegacyContactInfo as ContactInfo,
+    },
     solana_ledger::shred::{Shred, ShredFlags},
     solana_sdk::{clock::Slot, pubkey::Pubkey},
     solana_turbine::{
@@ -44,8 +46,12 @@ fn get_retransmit_peers_deterministic(
             0,
             0,
         );
-        let _retransmit_peers =
-            cluster_nodes.get_retransmit_peers(slot_leader, &shred.id(), /*fanout:*/ 200);
+        let _retransmit_peers = cluster_nodes.get_retransmit_peers(
+            slot_leader,
+            &shred.id(),
+            /*fanout:*/ 200,
+            Protocol::UDP,
+        );
     }
 }
 
@@ -117,6 +117,7 @@ fn bench_retransmitter(bencher: &mut Bencher) {
     let retransmitter_handles = retransmitter(
         Arc::new(sockets),
         quic_endpoint_sender,
+        Arc::default(), // solana_turbine::quic_endpoint::TurbinePeerStats
         bank_forks,
         leader_schedule_cache,
         cluster_info,
@@ -437,17 +437,18 @@ pub fn broadcast_shreds(
         let bank_forks = bank_forks.read().unwrap();
         (bank_forks.root_bank(), bank_forks.working_bank())
     };
+    let root_bank = &root_bank;
     let (packets, quic_packets): (Vec<_>, Vec<_>) = shreds
         .iter()
         .group_by(|shred| shred.slot())
         .into_iter()
         .flat_map(|(slot, shreds)| {
             let cluster_nodes =
-                cluster_nodes_cache.get(slot, &root_bank, &working_bank, cluster_info);
+                cluster_nodes_cache.get(slot, root_bank, &working_bank, cluster_info);
             update_peer_stats(&cluster_nodes, last_datapoint_submit);
             shreds.filter_map(move |shred| {
                 let key = shred.id();
-                let protocol = cluster_nodes::get_broadcast_protocol(&key);
+                let protocol = cluster_nodes::get_broadcast_protocol(&key, root_bank);
                 cluster_nodes
                     .get_broadcast_peer(&key)?
                     .tvu(protocol)
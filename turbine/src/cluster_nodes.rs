@@ -1,5 +1,8 @@
 use {
-    crate::{broadcast_stage::BroadcastStage, retransmit_stage::RetransmitStage},
+    crate::{
+        broadcast_stage::BroadcastStage, quic_endpoint::TurbinePeerStats,
+        retransmit_stage::RetransmitStage,
+    },
     itertools::Itertools,
     lru::LruCache,
     rand::{seq::SliceRandom, Rng, SeedableRng},
@@ -36,7 +39,7 @@ use {
     thiserror::Error,
 };
 
-const DATA_PLANE_FANOUT: usize = 200;
+pub const DATA_PLANE_FANOUT: usize = 200;
 pub(crate) const MAX_NUM_TURBINE_HOPS: usize = 4;
 
 #[derive(Debug, Error)]
@@ -165,20 +168,29 @@ impl ClusterNodes<RetransmitStage> {
         slot_leader: &Pubkey,
         shred: &ShredId,
         fanout: usize,
+        protocol: Protocol,
+        peer_stats: &TurbinePeerStats,
     ) -> Result<(/*root_distance:*/ usize, Vec<SocketAddr>), Error> {
         let RetransmitPeers {
             root_distance,
             children,
             addrs,
-        } = self.get_retransmit_peers(slot_leader, shred, fanout)?;
-        let protocol = get_broadcast_protocol(shred);
-        let peers = children.into_iter().filter_map(|node| {
-            node.contact_info()?
-                .tvu(protocol)
-                .ok()
-                .filter(|addr| addrs.get(addr) == Some(&node.pubkey()))
-        });
-        Ok((root_distance, peers.collect()))
+        } = self.get_retransmit_peers(slot_leader, shred, fanout, protocol)?;
+        let mut peers: Vec<SocketAddr> = children
+            .into_iter()
+            .filter_map(|node| {
+                node.contact_info()?
+                    .tvu(protocol)
+                    .ok()
+                    .filter(|addr| addrs.get(addr) == Some(&node.pubkey()))
+            })
+            .collect();
+        // Send to slow or congested children last. The tree itself is
+        // unchanged so that all nodes agree on it.
+        if let Protocol::QUIC = protocol {
+            peer_stats.sort_by_delivery(&mut peers);
+        }
+        Ok((root_distance, peers))
     }
 
     pub fn get_retransmit_peers(
@@ -186,6 +198,7 @@ impl ClusterNodes<RetransmitStage> {
         slot_leader: &Pubkey,
         shred: &ShredId,
         fanout: usize,
+        protocol: Protocol,
     ) -> Result<RetransmitPeers, Error> {
         let shred_seed = shred.seed(slot_leader);
         let mut weighted_shuffle = self.weighted_shuffle.clone();
@@ -201,7 +214,6 @@ impl ClusterNodes<RetransmitStage> {
         }
         let mut addrs = HashMap::<SocketAddr, Pubkey>::with_capacity(self.nodes.len());
         let mut rng = ChaChaRng::from_seed(shred_seed);
-        let protocol = get_broadcast_protocol(shred);
         let nodes: Vec<_> = weighted_shuffle
             .shuffle(&mut rng)
             .map(|index| &self.nodes[index])
@@ -309,7 +321,7 @@ fn get_nodes(cluster_info: &ClusterInfo, stakes: &HashMap<Pubkey, u64>) -> Vec<N
 // Each other node retransmits shreds to fanout many nodes in the next layer.
 // For example the node k in the 1st layer will retransmit to nodes:
 // fanout + k, 2*fanout + k, ..., fanout*fanout + k
-fn get_retransmit_peers<T: Copy>(
+pub(crate) fn get_retransmit_peers<T: Copy>(
     fanout: usize,
     index: usize, // Local node's index withing the nodes slice.
     nodes: &[T],
@@ -421,9 +433,16 @@ impl From<Pubkey> for NodeId {
     }
 }
 
-#[inline]
-pub(crate) fn get_broadcast_protocol(_: &ShredId) -> Protocol {
-    Protocol::UDP
+pub(crate) fn get_broadcast_protocol(shred: &ShredId, root_bank: &Bank) -> Protocol {
+    if check_feature_activation(
+        &feature_set::turbine_over_quic::id(),
+        shred.slot(),
+        root_bank,
+    ) {
+        Protocol::QUIC
+    } else {
+        Protocol::UDP
+    }
 }
 
 pub fn make_test_cluster<R: Rng>(
@@ -527,7 +546,7 @@ fn check_feature_activation(feature: &Pubkey, shred_slot: Slot, root_bank: &Bank
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use {super::*, crate::retransmit_tree::RetransmitTree, solana_ledger::shred::ShredType};
 
     #[test]
     fn test_cluster_nodes_retransmit() {
@@ -600,6 +619,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_retransmit_tree_matches_cluster_nodes() {
+        let mut rng = rand::thread_rng();
+        let (nodes, stakes, cluster_info) = make_test_cluster(&mut rng, 1_000, None);
+        let cluster_nodes = new_cluster_nodes::<RetransmitStage>(&cluster_info, &stakes);
+        let stakes: HashMap<Pubkey, u64> = cluster_nodes
+            .nodes
+            .iter()
+            .map(|node| (node.pubkey(), node.stake))
+            .collect();
+        let slot_leader = nodes[1..].choose(&mut rng).unwrap().pubkey();
+        let shred = ShredId::new(5, 3, ShredType::Code);
+        for fanout in [2, 16, DATA_PLANE_FANOUT] {
+            let tree = RetransmitTree::new(&stakes, slot_leader, &shred, fanout);
+            let node = tree.get_node(&cluster_info.id()).unwrap();
+            let RetransmitPeers {
+                root_distance,
+                children,
+                ..
+            } = cluster_nodes
+                .get_retransmit_peers(slot_leader, &shred, fanout, Protocol::UDP)
+                .unwrap();
+            assert_eq!(root_distance, node.layer.min(3));
+            assert!(children
+                .into_iter()
+                .map(Node::pubkey)
+                .eq(node.children.iter().map(|&k| tree.nodes()[k].pubkey)));
+        }
+    }
+
     #[test]
     fn test_get_retransmit_peers() {
         // fanout 2
@@ -25,7 +25,7 @@ use {
             atomic::{AtomicBool, AtomicU64, Ordering},
             Arc, RwLock,
         },
-        time::Duration,
+        time::{Duration, Instant},
     },
     thiserror::Error,
     tokio::{
@@ -51,6 +51,18 @@ const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(4);
 const MAX_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
 const MINIMUM_MAXIMUM_TRANSMISSION_UNIT: u16 = 1280;
 
+// Datagrams per second accepted from each unstaked peer.
+const UNSTAKED_DATAGRAMS_PER_SECOND: f64 = 2_000.0;
+// Datagrams per second accepted from each staked peer, regardless of stake.
+const MIN_STAKED_DATAGRAMS_PER_SECOND: f64 = 20_000.0;
+// Datagrams per second additionally accepted from staked peers in proportion
+// to their stake.
+const STAKE_WEIGHTED_DATAGRAMS_PER_SECOND: f64 = 200_000.0;
+// Round trip times are bucketed so that peers with similar delays keep their
+// relative order.
+const PEER_RTT_BUCKET_MICROS: u64 = 10_000;
+const PEER_STATS_REPORT_INTERVAL: Duration = Duration::from_secs(2);
+
 const CONNECTION_CLOSE_ERROR_CODE_SHUTDOWN: VarInt = VarInt::from_u32(1);
 const CONNECTION_CLOSE_ERROR_CODE_DROPPED: VarInt = VarInt::from_u32(2);
 const CONNECTION_CLOSE_ERROR_CODE_INVALID_IDENTITY: VarInt = VarInt::from_u32(3);
@@ -85,6 +97,140 @@ pub enum Error {
     TlsError(#[from] rustls::Error),
 }
 
+/// Delivery and round trip time stats of the peers connected over QUIC,
+/// keyed by their turbine address. Shreds are retransmitted to slow or
+/// congested children after the others.
+#[derive(Default)]
+pub struct TurbinePeerStats {
+    peers: RwLock<HashMap<SocketAddr, Arc<PeerStats>>>,
+}
+
+#[derive(Default)]
+struct PeerStats {
+    rtt_micros: AtomicU64,
+    num_sent: AtomicU64,
+    // Datagrams dropped since the last report because the peer's queue or
+    // the connection's send buffer was full.
+    num_dropped: AtomicU64,
+    // Value of num_dropped as of the last report.
+    num_dropped_prev: AtomicU64,
+}
+
+impl PeerStats {
+    fn is_congested(&self) -> bool {
+        self.num_dropped.load(Ordering::Relaxed) > 0
+            || self.num_dropped_prev.load(Ordering::Relaxed) > 0
+    }
+}
+
+impl TurbinePeerStats {
+    fn get_peer(&self, addr: &SocketAddr) -> Option<Arc<PeerStats>> {
+        self.peers.read().unwrap().get(addr).cloned()
+    }
+
+    fn add_peer(&self, addr: SocketAddr) -> Arc<PeerStats> {
+        let peer = Arc::<PeerStats>::default();
+        self.peers.write().unwrap().insert(addr, peer.clone());
+        peer
+    }
+
+    fn remove_peer(&self, addr: &SocketAddr, peer: &Arc<PeerStats>) {
+        if let Entry::Occupied(entry) = self.peers.write().unwrap().entry(*addr) {
+            if Arc::ptr_eq(entry.get(), peer) {
+                entry.remove();
+            }
+        }
+    }
+
+    /// Sorts the addresses so that peers which recently dropped datagrams
+    /// come last, and otherwise by their round trip time. Peers not connected
+    /// yet are ordered as if their round trip time was zero.
+    pub fn sort_by_delivery(&self, addrs: &mut [SocketAddr]) {
+        let peers = self.peers.read().unwrap();
+        if peers.is_empty() {
+            return;
+        }
+        addrs.sort_by_cached_key(|addr| match peers.get(addr) {
+            None => (false, 0),
+            Some(peer) => (
+                peer.is_congested(),
+                peer.rtt_micros.load(Ordering::Relaxed) / PEER_RTT_BUCKET_MICROS,
+            ),
+        });
+    }
+
+    fn submit_metrics(&self) {
+        let peers = self.peers.read().unwrap();
+        let mut num_congested_peers = 0;
+        let mut num_sent = 0;
+        let mut num_dropped = 0;
+        let mut rtt_micros_sum = 0;
+        let mut rtt_micros_max = 0;
+        for peer in peers.values() {
+            let dropped = peer.num_dropped.swap(0, Ordering::Relaxed);
+            peer.num_dropped_prev.store(dropped, Ordering::Relaxed);
+            if dropped > 0 {
+                num_congested_peers += 1;
+            }
+            num_dropped += dropped;
+            num_sent += peer.num_sent.swap(0, Ordering::Relaxed);
+            let rtt_micros = peer.rtt_micros.load(Ordering::Relaxed);
+            rtt_micros_sum += rtt_micros;
+            rtt_micros_max = rtt_micros_max.max(rtt_micros);
+        }
+        datapoint_info!(
+            "turbine_quic_peer_stats",
+            ("num_peers", peers.len(), i64),
+            ("num_congested_peers", num_congested_peers, i64),
+            ("num_sent", num_sent, i64),
+            ("num_dropped", num_dropped, i64),
+            (
+                "rtt_micros_mean",
+                rtt_micros_sum / peers.len().max(1) as u64,
+                i64
+            ),
+            ("rtt_micros_max", rtt_micros_max, i64),
+        );
+    }
+}
+
+// Token bucket limiting the datagrams read from a connection, holding at
+// most one second worth of datagrams. Shreds are sent as datagrams, not
+// streams, so peers are limited by their datagram rate.
+struct DatagramRateLimiter {
+    datagrams_per_second: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl DatagramRateLimiter {
+    fn new(stake: u64, total_stake: u64) -> Self {
+        let datagrams_per_second = if stake == 0 || total_stake == 0 {
+            UNSTAKED_DATAGRAMS_PER_SECOND
+        } else {
+            MIN_STAKED_DATAGRAMS_PER_SECOND
+                + STAKE_WEIGHTED_DATAGRAMS_PER_SECOND * stake as f64 / total_stake as f64
+        };
+        Self {
+            datagrams_per_second,
+            tokens: datagrams_per_second,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.datagrams_per_second)
+            .min(self.datagrams_per_second);
+        self.last_refill = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
 macro_rules! add_metric {
     ($metric: expr) => {{
         $metric.fetch_add(1, Ordering::Relaxed);
@@ -99,6 +245,7 @@ pub fn new_quic_endpoint(
     address: IpAddr,
     sender: Sender<(Pubkey, SocketAddr, Bytes)>,
     bank_forks: Arc<RwLock<BankForks>>,
+    peer_stats: Arc<TurbinePeerStats>,
 ) -> Result<
     (
         Endpoint,
@@ -133,6 +280,7 @@ pub fn new_quic_endpoint(
         prune_cache_pending.clone(),
         router.clone(),
         cache.clone(),
+        peer_stats.clone(),
     ));
     let client_task = runtime.spawn(run_client(
         endpoint.clone(),
@@ -142,6 +290,7 @@ pub fn new_quic_endpoint(
         prune_cache_pending,
         router,
         cache,
+        peer_stats,
     ));
     let task = futures::future::try_join(server_task, client_task);
     Ok((endpoint, client_sender, task))
@@ -203,6 +352,7 @@ async fn run_server(
     prune_cache_pending: Arc<AtomicBool>,
     router: Arc<AsyncRwLock<HashMap<SocketAddr, AsyncSender<Bytes>>>>,
     cache: Arc<Mutex<HashMap<Pubkey, Connection>>>,
+    peer_stats: Arc<TurbinePeerStats>,
 ) {
     let stats = Arc::<TurbineQuicStats>::default();
     let report_metrics_task =
@@ -216,6 +366,7 @@ async fn run_server(
             prune_cache_pending.clone(),
             router.clone(),
             cache.clone(),
+            peer_stats.clone(),
             stats.clone(),
         ));
     }
@@ -230,18 +381,26 @@ async fn run_client(
     prune_cache_pending: Arc<AtomicBool>,
     router: Arc<AsyncRwLock<HashMap<SocketAddr, AsyncSender<Bytes>>>>,
     cache: Arc<Mutex<HashMap<Pubkey, Connection>>>,
+    peer_stats: Arc<TurbinePeerStats>,
 ) {
     let stats = Arc::<TurbineQuicStats>::default();
     let report_metrics_task =
         tokio::task::spawn(report_metrics_task("repair_quic_client", stats.clone()));
+    let report_peer_stats_task = tokio::task::spawn(report_peer_stats_task(peer_stats.clone()));
     while let Some((remote_address, bytes)) = receiver.recv().await {
-        let Some(bytes) = try_route_bytes(&remote_address, bytes, &*router.read().await, &stats)
-        else {
+        let Some(bytes) = try_route_bytes(
+            &remote_address,
+            bytes,
+            &*router.read().await,
+            &peer_stats,
+            &stats,
+        ) else {
             continue;
         };
         let receiver = {
             let mut router = router.write().await;
-            let Some(bytes) = try_route_bytes(&remote_address, bytes, &router, &stats) else {
+            let Some(bytes) = try_route_bytes(&remote_address, bytes, &router, &peer_stats, &stats)
+            else {
                 continue;
             };
             let (sender, receiver) = tokio::sync::mpsc::channel(ROUTER_CHANNEL_BUFFER);
@@ -258,6 +417,7 @@ async fn run_client(
             prune_cache_pending.clone(),
             router.clone(),
             cache.clone(),
+            peer_stats.clone(),
             stats.clone(),
         ));
     }
@@ -265,12 +425,14 @@ async fn run_client(
     // Drop sender channels to unblock threads waiting on the receiving end.
     router.write().await.clear();
     report_metrics_task.abort();
+    report_peer_stats_task.abort();
 }
 
 fn try_route_bytes(
     remote_address: &SocketAddr,
     bytes: Bytes,
     router: &HashMap<SocketAddr, AsyncSender<Bytes>>,
+    peer_stats: &TurbinePeerStats,
     stats: &TurbineQuicStats,
 ) -> Option<Bytes> {
     match router.get(remote_address) {
@@ -280,6 +442,9 @@ fn try_route_bytes(
             Err(TrySendError::Full(_)) => {
                 debug!("TrySendError::Full {remote_address}");
                 add_metric!(stats.router_try_send_error_full);
+                if let Some(peer) = peer_stats.get_peer(remote_address) {
+                    add_metric!(peer.num_dropped);
+                }
                 None
             }
             Err(TrySendError::Closed(bytes)) => Some(bytes),
@@ -287,6 +452,7 @@ fn try_route_bytes(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_connecting_task(
     endpoint: Endpoint,
     connecting: Connecting,
@@ -295,6 +461,7 @@ async fn handle_connecting_task(
     prune_cache_pending: Arc<AtomicBool>,
     router: Arc<AsyncRwLock<HashMap<SocketAddr, AsyncSender<Bytes>>>>,
     cache: Arc<Mutex<HashMap<Pubkey, Connection>>>,
+    peer_stats: Arc<TurbinePeerStats>,
     stats: Arc<TurbineQuicStats>,
 ) {
     if let Err(err) = handle_connecting(
@@ -305,6 +472,7 @@ async fn handle_connecting_task(
         prune_cache_pending,
         router,
         cache,
+        peer_stats,
         stats.clone(),
     )
     .await
@@ -314,6 +482,7 @@ async fn handle_connecting_task(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_connecting(
     endpoint: Endpoint,
     connecting: Connecting,
@@ -322,6 +491,7 @@ async fn handle_connecting(
     prune_cache_pending: Arc<AtomicBool>,
     router: Arc<AsyncRwLock<HashMap<SocketAddr, AsyncSender<Bytes>>>>,
     cache: Arc<Mutex<HashMap<Pubkey, Connection>>>,
+    peer_stats: Arc<TurbinePeerStats>,
     stats: Arc<TurbineQuicStats>,
 ) -> Result<(), Error> {
     let connection = connecting.await?;
@@ -343,6 +513,7 @@ async fn handle_connecting(
         prune_cache_pending,
         router,
         cache,
+        peer_stats,
         stats,
     )
     .await;
@@ -361,8 +532,18 @@ async fn handle_connection(
     prune_cache_pending: Arc<AtomicBool>,
     router: Arc<AsyncRwLock<HashMap<SocketAddr, AsyncSender<Bytes>>>>,
     cache: Arc<Mutex<HashMap<Pubkey, Connection>>>,
+    peer_stats: Arc<TurbinePeerStats>,
     stats: Arc<TurbineQuicStats>,
 ) {
+    let rate_limiter = {
+        let root_bank = bank_forks.read().unwrap().root_bank();
+        let staked_nodes = root_bank.staked_nodes();
+        let stake = staked_nodes
+            .get(&remote_pubkey)
+            .copied()
+            .unwrap_or_default();
+        DatagramRateLimiter::new(stake, staked_nodes.values().sum())
+    };
     cache_connection(
         remote_pubkey,
         connection.clone(),
@@ -372,13 +553,19 @@ async fn handle_connection(
         cache.clone(),
     )
     .await;
-    let send_datagram_task = tokio::task::spawn(send_datagram_task(connection.clone(), receiver));
+    let peer = peer_stats.add_peer(remote_address);
+    let send_datagram_task = tokio::task::spawn(send_datagram_task(
+        connection.clone(),
+        receiver,
+        peer.clone(),
+    ));
     let read_datagram_task = tokio::task::spawn(read_datagram_task(
         endpoint,
         remote_address,
         remote_pubkey,
         connection.clone(),
         sender,
+        rate_limiter,
         stats.clone(),
     ));
     match futures::future::try_join(send_datagram_task, read_datagram_task).await {
@@ -395,6 +582,7 @@ async fn handle_connection(
         }
     }
     drop_connection(remote_pubkey, &connection, &cache).await;
+    peer_stats.remove_peer(&remote_address, &peer);
     if let Entry::Occupied(entry) = router.write().await.entry(remote_address) {
         if entry.get().is_closed() {
             entry.remove();
@@ -408,6 +596,7 @@ async fn read_datagram_task(
     remote_pubkey: Pubkey,
     connection: Connection,
     sender: Sender<(Pubkey, SocketAddr, Bytes)>,
+    mut rate_limiter: DatagramRateLimiter,
     stats: Arc<TurbineQuicStats>,
 ) -> Result<(), Error> {
     // Assert that send won't block.
@@ -415,6 +604,10 @@ async fn read_datagram_task(
     loop {
         match connection.read_datagram().await {
             Ok(bytes) => {
+                if !rate_limiter.take(Instant::now()) {
+                    add_metric!(stats.read_datagram_rate_limited);
+                    continue;
+                }
                 if let Err(err) = sender.send((remote_pubkey, remote_address, bytes)) {
                     close_quic_endpoint(&endpoint);
                     return Err(Error::from(err));
@@ -434,13 +627,23 @@ async fn read_datagram_task(
 async fn send_datagram_task(
     connection: Connection,
     mut receiver: AsyncReceiver<Bytes>,
+    peer: Arc<PeerStats>,
 ) -> Result<(), Error> {
     while let Some(bytes) = receiver.recv().await {
+        // If the send buffer is full, the oldest datagrams are dropped to
+        // make room for the new one.
+        if connection.datagram_send_buffer_space() < bytes.len() {
+            add_metric!(peer.num_dropped);
+        }
         connection.send_datagram(bytes)?;
+        add_metric!(peer.num_sent);
+        let rtt_micros = u64::try_from(connection.rtt().as_micros()).unwrap_or(u64::MAX);
+        peer.rtt_micros.store(rtt_micros, Ordering::Relaxed);
     }
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn make_connection_task(
     endpoint: Endpoint,
     remote_address: SocketAddr,
@@ -450,6 +653,7 @@ async fn make_connection_task(
     prune_cache_pending: Arc<AtomicBool>,
     router: Arc<AsyncRwLock<HashMap<SocketAddr, AsyncSender<Bytes>>>>,
     cache: Arc<Mutex<HashMap<Pubkey, Connection>>>,
+    peer_stats: Arc<TurbinePeerStats>,
     stats: Arc<TurbineQuicStats>,
 ) {
     if let Err(err) = make_connection(
@@ -461,6 +665,7 @@ async fn make_connection_task(
         prune_cache_pending,
         router,
         cache,
+        peer_stats,
         stats.clone(),
     )
     .await
@@ -470,6 +675,7 @@ async fn make_connection_task(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn make_connection(
     endpoint: Endpoint,
     remote_address: SocketAddr,
@@ -479,6 +685,7 @@ async fn make_connection(
     prune_cache_pending: Arc<AtomicBool>,
     router: Arc<AsyncRwLock<HashMap<SocketAddr, AsyncSender<Bytes>>>>,
     cache: Arc<Mutex<HashMap<Pubkey, Connection>>>,
+    peer_stats: Arc<TurbinePeerStats>,
     stats: Arc<TurbineQuicStats>,
 ) -> Result<(), Error> {
     let connection = endpoint
@@ -495,6 +702,7 @@ async fn make_connection(
         prune_cache_pending,
         router,
         cache,
+        peer_stats,
         stats,
     )
     .await;
@@ -624,6 +832,7 @@ struct TurbineQuicStats {
     connection_error_transport_error: AtomicU64,
     connection_error_version_mismatch: AtomicU64,
     invalid_identity: AtomicU64,
+    read_datagram_rate_limited: AtomicU64,
     router_try_send_error_full: AtomicU64,
     send_datagram_error_connection_lost: AtomicU64,
     send_datagram_error_too_large: AtomicU64,
@@ -637,6 +846,13 @@ async fn report_metrics_task(name: &'static str, stats: Arc<TurbineQuicStats>) {
     }
 }
 
+async fn report_peer_stats_task(peer_stats: Arc<TurbinePeerStats>) {
+    loop {
+        tokio::time::sleep(PEER_STATS_REPORT_INTERVAL).await;
+        peer_stats.submit_metrics();
+    }
+}
+
 fn record_error(err: &Error, stats: &TurbineQuicStats) {
     match err {
         Error::CertificateError(_) => (),
@@ -757,6 +973,11 @@ fn report_metrics(name: &'static str, stats: &TurbineQuicStats) {
             reset_metric!(stats.invalid_identity),
             i64
         ),
+        (
+            "read_datagram_rate_limited",
+            reset_metric!(stats.read_datagram_rate_limited),
+            i64
+        ),
         (
             "router_try_send_error_full",
             reset_metric!(stats.router_try_send_error_full),
@@ -830,6 +1051,7 @@ mod tests {
                         IpAddr::V4(Ipv4Addr::LOCALHOST),
                         sender,
                         bank_forks.clone(),
+                        Arc::<TurbinePeerStats>::default(),
                     )
                     .unwrap()
                 },
@@ -859,4 +1081,59 @@ mod tests {
             runtime.block_on(task).unwrap();
         }
     }
+
+    #[test]
+    fn test_turbine_peer_stats_sort_by_delivery() {
+        let addrs: Vec<SocketAddr> = (8000..8005)
+            .map(|port| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port))
+            .collect();
+        let peer_stats = TurbinePeerStats::default();
+        // Without any connected peers the order is unchanged.
+        let mut other = addrs.clone();
+        peer_stats.sort_by_delivery(&mut other);
+        assert_eq!(other, addrs);
+        let rtts = [50_000, 5_000, 120_000, 1_000];
+        let peers: Vec<_> = addrs
+            .iter()
+            .zip(rtts)
+            .map(|(&addr, rtt_micros)| {
+                let peer = peer_stats.add_peer(addr);
+                peer.rtt_micros.store(rtt_micros, Ordering::Relaxed);
+                peer
+            })
+            .collect();
+        add_metric!(peers[1].num_dropped);
+        peer_stats.sort_by_delivery(&mut other);
+        // Sorted by rtt, with congested peers last.
+        assert_eq!(other, [addrs[3], addrs[4], addrs[0], addrs[2], addrs[1]]);
+        // Congestion is remembered until the next report.
+        peer_stats.submit_metrics();
+        assert!(peers[1].is_congested());
+        peer_stats.submit_metrics();
+        assert!(!peers[1].is_congested());
+        // Stats of replaced connections are not removed.
+        peer_stats.add_peer(addrs[0]);
+        peer_stats.remove_peer(&addrs[0], &peers[0]);
+        assert!(peer_stats.get_peer(&addrs[0]).is_some());
+        peer_stats.remove_peer(&addrs[1], &peers[1]);
+        assert!(peer_stats.get_peer(&addrs[1]).is_none());
+    }
+
+    #[test]
+    fn test_datagram_rate_limiter() {
+        let now = Instant::now();
+        let count = |rate_limiter: &mut DatagramRateLimiter, now| {
+            (0..100_000).filter(|_| rate_limiter.take(now)).count()
+        };
+        let mut rate_limiter = DatagramRateLimiter::new(0, 1_000);
+        rate_limiter.last_refill = now;
+        assert_eq!(count(&mut rate_limiter, now), 2_000);
+        assert_eq!(
+            count(&mut rate_limiter, now + Duration::from_millis(500)),
+            1_000
+        );
+        let mut rate_limiter = DatagramRateLimiter::new(100, 1_000);
+        rate_limiter.last_refill = now;
+        assert_eq!(count(&mut rate_limiter, now), 40_000);
+    }
 }
@@ -2,7 +2,10 @@
 #![allow(clippy::rc_buffer)]
 
 use {
-    crate::cluster_nodes::{self, ClusterNodes, ClusterNodesCache, Error, MAX_NUM_TURBINE_HOPS},
+    crate::{
+        cluster_nodes::{self, ClusterNodes, ClusterNodesCache, Error, MAX_NUM_TURBINE_HOPS},
+        quic_endpoint::TurbinePeerStats,
+    },
     bytes::Bytes,
     crossbeam_channel::{Receiver, RecvTimeoutError},
     itertools::{izip, Itertools},
@@ -174,6 +177,7 @@ fn retransmit(
     shreds_receiver: &Receiver<Vec</*shred:*/ Vec<u8>>>,
     sockets: &[UdpSocket],
     quic_endpoint_sender: &AsyncSender<(SocketAddr, Bytes)>,
+    quic_peer_stats: &TurbinePeerStats,
     stats: &mut RetransmitStats,
     cluster_nodes_cache: &ClusterNodesCache<RetransmitStage>,
     shred_deduper: &mut ShredDeduper<2>,
@@ -258,6 +262,7 @@ fn retransmit(
                     socket_addr_space,
                     &sockets[index % sockets.len()],
                     quic_endpoint_sender,
+                    quic_peer_stats,
                     stats,
                 )
                 .map_err(|err| {
@@ -283,6 +288,7 @@ fn retransmit(
                         socket_addr_space,
                         &sockets[index % sockets.len()],
                         quic_endpoint_sender,
+                        quic_peer_stats,
                         stats,
                     )
                     .map_err(|err| {
@@ -312,12 +318,19 @@ fn retransmit_shred(
     socket_addr_space: &SocketAddrSpace,
     socket: &UdpSocket,
     quic_endpoint_sender: &AsyncSender<(SocketAddr, Bytes)>,
+    quic_peer_stats: &TurbinePeerStats,
     stats: &RetransmitStats,
 ) -> Result<(/*root_distance:*/ usize, /*num_nodes:*/ usize), Error> {
     let mut compute_turbine_peers = Measure::start("turbine_start");
     let data_plane_fanout = cluster_nodes::get_data_plane_fanout(key.slot(), root_bank);
-    let (root_distance, addrs) =
-        cluster_nodes.get_retransmit_addrs(slot_leader, key, data_plane_fanout)?;
+    let protocol = cluster_nodes::get_broadcast_protocol(key, root_bank);
+    let (root_distance, addrs) = cluster_nodes.get_retransmit_addrs(
+        slot_leader,
+        key,
+        data_plane_fanout,
+        protocol,
+        quic_peer_stats,
+    )?;
     let addrs: Vec<_> = addrs
         .into_iter()
         .filter(|addr| socket_addr_space.check(addr))
@@ -329,7 +342,7 @@ fn retransmit_shred(
 
     let mut retransmit_time = Measure::start("retransmit_to");
     let num_addrs = addrs.len();
-    let num_nodes = match cluster_nodes::get_broadcast_protocol(key) {
+    let num_nodes = match protocol {
         Protocol::QUIC => {
             let shred = Bytes::copy_from_slice(shred);
             addrs
@@ -369,9 +382,11 @@ fn retransmit_shred(
 /// * `leader_schedule_cache` - The leader schedule to verify shreds
 /// * `cluster_info` - This structure needs to be updated and populated by the bank and via gossip.
 /// * `r` - Receive channel for shreds to be retransmitted to all the layer 1 nodes.
+#[allow(clippy::too_many_arguments)]
 pub fn retransmitter(
     sockets: Arc<Vec<UdpSocket>>,
     quic_endpoint_sender: AsyncSender<(SocketAddr, Bytes)>,
+    quic_peer_stats: Arc<TurbinePeerStats>,
     bank_forks: Arc<RwLock<BankForks>>,
     leader_schedule_cache: Arc<LeaderScheduleCache>,
     cluster_info: Arc<ClusterInfo>,
@@ -404,6 +419,7 @@ pub fn retransmitter(
                 &shreds_receiver,
                 &sockets,
                 &quic_endpoint_sender,
+                &quic_peer_stats,
                 &mut stats,
                 &cluster_nodes_cache,
                 &mut shred_deduper,
@@ -423,12 +439,14 @@ pub struct RetransmitStage {
 }
 
 impl RetransmitStage {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         bank_forks: Arc<RwLock<BankForks>>,
         leader_schedule_cache: Arc<LeaderScheduleCache>,
         cluster_info: Arc<ClusterInfo>,
         retransmit_sockets: Arc<Vec<UdpSocket>>,
         quic_endpoint_sender: AsyncSender<(SocketAddr, Bytes)>,
+        quic_peer_stats: Arc<TurbinePeerStats>,
         retransmit_receiver: Receiver<Vec</*shred:*/ Vec<u8>>>,
         max_slots: Arc<MaxSlots>,
         rpc_subscriptions: Option<Arc<RpcSubscriptions>>,
@@ -436,6 +454,7 @@ impl RetransmitStage {
         let retransmit_thread_handle = retransmitter(
             retransmit_sockets,
             quic_endpoint_sender,
+            quic_peer_stats,
             bank_forks,
             leader_schedule_cache,
             cluster_info,
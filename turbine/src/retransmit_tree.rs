@@ -0,0 +1,191 @@
+//! Offline view of the turbine retransmit tree of a shred, laid out the same
+//! way as `ClusterNodes<RetransmitStage>` does, for operators to see where
+//! nodes sit in the tree and how it changes with the fanout or with nodes
+//! failing.
+
+use {
+    crate::cluster_nodes::get_retransmit_peers,
+    rand::{Rng, SeedableRng},
+    rand_chacha::ChaChaRng,
+    solana_gossip::weighted_shuffle::WeightedShuffle,
+    solana_ledger::shred::ShredId,
+    solana_sdk::pubkey::Pubkey,
+    std::{cmp::Reverse, collections::HashMap},
+};
+
+pub struct RetransmitTreeNode {
+    pub pubkey: Pubkey,
+    pub stake: u64,
+    /// Distance from the root node, which receives the shred directly from
+    /// the slot leader.
+    pub layer: usize,
+    /// Index of the node the shred is received from, or None for the root.
+    pub parent: Option<usize>,
+    /// Indices of the nodes the shred is retransmitted to.
+    pub children: Vec<usize>,
+}
+
+pub struct RetransmitTree {
+    slot_leader: Pubkey,
+    // Nodes in the order the tree is laid out, i.e. root first and then each
+    // layer in turn.
+    nodes: Vec<RetransmitTreeNode>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RetransmitTreeStats {
+    pub num_layers: usize,
+    /// Mean number of hops from the slot leader, one for the root node.
+    pub mean_hops: f64,
+    /// Mean number of hops from the slot leader weighted by stake.
+    pub stake_weighted_mean_hops: f64,
+}
+
+impl RetransmitTree {
+    /// Lays out the retransmit tree of the shred over the given nodes, which
+    /// may include unstaked nodes with zero stake. The slot leader is left
+    /// out of the tree.
+    pub fn new(
+        stakes: &HashMap<Pubkey, u64>,
+        slot_leader: &Pubkey,
+        shred: &ShredId,
+        fanout: usize,
+    ) -> Self {
+        assert!(fanout > 0, "fanout must be positive");
+        // Sorted by (stake, pubkey) in descending order, same as ClusterNodes.
+        let mut nodes: Vec<(Pubkey, u64)> = stakes
+            .iter()
+            .map(|(&pubkey, &stake)| (pubkey, stake))
+            .collect();
+        nodes.sort_unstable_by_key(|&(pubkey, stake)| Reverse((stake, pubkey)));
+        let weights: Vec<u64> = nodes.iter().map(|&(_, stake)| stake).collect();
+        let mut weighted_shuffle = WeightedShuffle::new("retransmit-tree", &weights);
+        if let Some(index) = nodes.iter().position(|(pubkey, _)| pubkey == slot_leader) {
+            weighted_shuffle.remove_index(index);
+        }
+        let mut rng = ChaChaRng::from_seed(shred.seed(slot_leader));
+        let mut nodes: Vec<RetransmitTreeNode> = weighted_shuffle
+            .shuffle(&mut rng)
+            .map(|index| RetransmitTreeNode {
+                pubkey: nodes[index].0,
+                stake: nodes[index].1,
+                layer: 0,
+                parent: None,
+                children: Vec::default(),
+            })
+            .collect();
+        let indices: Vec<usize> = (0..nodes.len()).collect();
+        // Children always come after their parent, so the parent's layer is
+        // already known when visiting its children.
+        for index in 0..nodes.len() {
+            let children: Vec<usize> = get_retransmit_peers(fanout, index, &indices).collect();
+            let layer = nodes[index].layer + 1;
+            for &child in &children {
+                nodes[child].layer = layer;
+                nodes[child].parent = Some(index);
+            }
+            nodes[index].children = children;
+        }
+        Self {
+            slot_leader: *slot_leader,
+            nodes,
+        }
+    }
+
+    pub fn slot_leader(&self) -> &Pubkey {
+        &self.slot_leader
+    }
+
+    pub fn nodes(&self) -> &[RetransmitTreeNode] {
+        &self.nodes
+    }
+
+    pub fn get_node(&self, pubkey: &Pubkey) -> Option<&RetransmitTreeNode> {
+        self.nodes.iter().find(|node| &node.pubkey == pubkey)
+    }
+
+    pub fn stats(&self) -> RetransmitTreeStats {
+        let num_layers = self.nodes.last().map(|node| node.layer + 1).unwrap_or(0);
+        let total_stake: u64 = self.nodes.iter().map(|node| node.stake).sum();
+        let total_hops: usize = self.nodes.iter().map(|node| node.layer + 1).sum();
+        let stake_hops: f64 = self
+            .nodes
+            .iter()
+            .map(|node| node.stake as f64 * (node.layer + 1) as f64)
+            .sum();
+        RetransmitTreeStats {
+            num_layers,
+            mean_hops: total_hops as f64 / self.nodes.len().max(1) as f64,
+            stake_weighted_mean_hops: stake_hops / total_stake.max(1) as f64,
+        }
+    }
+
+    /// Returns the fraction of the stake, excluding the slot leader, which
+    /// receives the shred if each node independently fails with the given
+    /// probability. Failed nodes neither receive nor retransmit the shred.
+    pub fn sample_stake_reached<R: Rng>(&self, rng: &mut R, failure_rate: f64) -> f64 {
+        let total_stake: u64 = self.nodes.iter().map(|node| node.stake).sum();
+        if total_stake == 0 {
+            return 0.0;
+        }
+        let mut reached = vec![false; self.nodes.len()];
+        let mut stake_reached = 0u64;
+        for (index, node) in self.nodes.iter().enumerate() {
+            let parent_reached = node.parent.map(|parent| reached[parent]).unwrap_or(true);
+            if parent_reached && !rng.gen_bool(failure_rate) {
+                reached[index] = true;
+                stake_reached += node.stake;
+            }
+        }
+        stake_reached as f64 / total_stake as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_ledger::shred::ShredType, std::iter::repeat_with};
+
+    #[test]
+    fn test_retransmit_tree() {
+        let mut rng = rand::thread_rng();
+        let stakes: HashMap<Pubkey, u64> =
+            repeat_with(|| (Pubkey::new_unique(), rng.gen_range(0..1_000)))
+                .take(1_000)
+                .collect();
+        let slot_leader = *stakes.keys().next().unwrap();
+        let shred = ShredId::new(13, 7, ShredType::Data);
+        let tree = RetransmitTree::new(&stakes, &slot_leader, &shred, /*fanout:*/ 8);
+        assert_eq!(tree.nodes().len(), stakes.len() - 1);
+        assert!(tree.get_node(&slot_leader).is_none());
+        // 1 + 8 + 64 + 512 nodes in the first 4 layers.
+        let stats = tree.stats();
+        assert_eq!(stats.num_layers, 5);
+        for (index, node) in tree.nodes().iter().enumerate() {
+            assert_eq!(node.stake, stakes[&node.pubkey]);
+            match node.parent {
+                None => assert_eq!((index, node.layer), (0, 0)),
+                Some(parent) => {
+                    let parent = &tree.nodes()[parent];
+                    assert_eq!(node.layer, parent.layer + 1);
+                    assert!(parent.children.contains(&index));
+                }
+            }
+            assert!(node.children.len() <= 8);
+        }
+        // The layout is deterministic given the shred and slot leader.
+        let other = RetransmitTree::new(&stakes, &slot_leader, &shred, /*fanout:*/ 8);
+        assert!(tree
+            .nodes()
+            .iter()
+            .map(|node| node.pubkey)
+            .eq(other.nodes().iter().map(|node| node.pubkey)));
+        // Larger fanouts result in shallower trees.
+        let other = RetransmitTree::new(&stakes, &slot_leader, &shred, /*fanout:*/ 200);
+        assert_eq!(other.stats().num_layers, 3);
+        assert!(other.stats().stake_weighted_mean_hops < stats.stake_weighted_mean_hops);
+        // Failures are sampled per node.
+        assert_eq!(tree.sample_stake_reached(&mut rng, 0.0), 1.0);
+        assert_eq!(tree.sample_stake_reached(&mut rng, 1.0), 0.0);
+        assert!(tree.sample_stake_reached(&mut rng, 0.1) < 1.0);
+    }
+}
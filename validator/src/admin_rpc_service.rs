@@ -1,4 +1,5 @@
 use {
+    crate::failover::{FailoverService, FailoverStatus},
     crossbeam_channel::Sender,
     jsonrpc_core::{BoxFuture, ErrorCode, MetaIoHandler, Metadata, Result},
     jsonrpc_core_client::{transports::ipc, RpcError},
@@ -47,6 +48,7 @@ pub struct AdminRpcRequestMetadata {
     pub staked_nodes_overrides: Arc<RwLock<HashMap<Pubkey, u64>>>,
     pub post_init: Arc<RwLock<Option<AdminRpcRequestMetadataPostInit>>>,
     pub rpc_to_plugin_manager_sender: Option<Sender<GeyserPluginManagerRequest>>,
+    pub failover: Option<Arc<FailoverService>>,
 }
 
 impl Metadata for AdminRpcRequestMetadata {}
@@ -64,6 +66,18 @@ impl AdminRpcRequestMetadata {
             ))
         }
     }
+
+    fn with_failover<F, R>(&self, func: F) -> Result<R>
+    where
+        F: FnOnce(&FailoverService) -> std::result::Result<R, String>,
+    {
+        match &self.failover {
+            Some(failover) => func(failover).map_err(jsonrpc_core::error::Error::invalid_params),
+            None => Err(jsonrpc_core::error::Error::invalid_params(
+                "Failover is not enabled, see --failover-role",
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -233,6 +247,15 @@ pub trait AdminRpc {
         meta: Self::Metadata,
         public_tpu_forwards_addr: SocketAddr,
     ) -> Result<()>;
+
+    #[rpc(meta, name = "failoverStatus")]
+    fn failover_status(&self, meta: Self::Metadata) -> Result<FailoverStatus>;
+
+    #[rpc(meta, name = "failoverPromote")]
+    fn failover_promote(&self, meta: Self::Metadata, force: bool) -> Result<()>;
+
+    #[rpc(meta, name = "failoverDemote")]
+    fn failover_demote(&self, meta: Self::Metadata) -> Result<()>;
 }
 
 pub struct AdminRpcImpl;
@@ -643,6 +666,20 @@ impl AdminRpc for AdminRpcImpl {
             Ok(())
         })
     }
+
+    fn failover_status(&self, meta: Self::Metadata) -> Result<FailoverStatus> {
+        meta.with_failover(|failover| Ok(failover.status()))
+    }
+
+    fn failover_promote(&self, meta: Self::Metadata, force: bool) -> Result<()> {
+        debug!("failover_promote request received, force: {force}");
+        meta.with_failover(|failover| failover.promote(force))
+    }
+
+    fn failover_demote(&self, meta: Self::Metadata) -> Result<()> {
+        debug!("failover_demote request received");
+        meta.with_failover(|failover| failover.demote())
+    }
 }
 
 impl AdminRpcImpl {
@@ -891,6 +928,7 @@ mod tests {
                 }))),
                 staked_nodes_overrides: Arc::new(RwLock::new(HashMap::new())),
                 rpc_to_plugin_manager_sender: None,
+                failover: None,
             };
             let mut io = MetaIoHandler::default();
             io.extend_with(AdminRpcImpl.to_delegate());
@@ -381,6 +381,7 @@ fn main() {
     genesis.log_messages_bytes_limit = value_t!(matches, "log_messages_bytes_limit", usize).ok();
     genesis.transaction_account_lock_limit =
         value_t!(matches, "transaction_account_lock_limit", usize).ok();
+    genesis.enable_instruction_tracing = matches.is_present("enable_instruction_tracing");
 
     let tower_storage = Arc::new(FileTowerStorage::new(ledger_path.clone()));
 
@@ -467,6 +468,7 @@ fn main() {
         rpc_bigtable_config,
         faucet_addr: Some(faucet_addr),
         account_indexes,
+        enable_simulation_program_overrides: true,
         ..JsonRpcConfig::default_for_test()
     });
 
@@ -508,6 +510,38 @@ fn main() {
         }
     }
 
+    if matches.is_present("fork") {
+        let rpc_client = RpcClient::new(
+            cluster_rpc_client
+                .as_ref()
+                .expect("bug: --url argument missing?")
+                .url(),
+        );
+        let min_fork_slot = value_t!(matches, "fork_slot", Slot).ok();
+        match fork::RpcAccountsFallback::new(rpc_client, min_fork_slot) {
+            Ok(accounts_fallback) => {
+                genesis.accounts_fallback(Arc::new(accounts_fallback));
+            }
+            Err(e) => {
+                println!("Error: failed to fork the cluster: {e}");
+                exit(1);
+            }
+        }
+    } else if let Some(fork_ledger_path) = matches.value_of("fork_snapshot") {
+        match fork::SnapshotAccountsFallback::new(
+            Path::new(fork_ledger_path),
+            &ledger_path.join("fork-snapshot"),
+        ) {
+            Ok(accounts_fallback) => {
+                genesis.accounts_fallback(Arc::new(accounts_fallback));
+            }
+            Err(e) => {
+                println!("Error: failed to fork the snapshot: {e}");
+                exit(1);
+            }
+        }
+    }
+
     if let Some(warp_slot) = warp_slot {
         genesis.warp_slot(warp_slot);
     }
@@ -4,7 +4,9 @@ use {
     rand::{seq::SliceRandom, thread_rng, Rng},
     rayon::prelude::*,
     solana_core::validator::{ValidatorConfig, ValidatorStartProgress},
-    solana_download_utils::{download_snapshot_archive, DownloadProgressRecord},
+    solana_download_utils::{
+        download_snapshot_archive_from_peers, DownloadProgressRecord, ParallelDownloadConfig,
+    },
     solana_genesis_utils::download_then_check_genesis_hash,
     solana_gossip::{
         cluster_info::{ClusterInfo, Node},
@@ -66,6 +68,10 @@ pub struct RpcBootstrapConfig {
     pub max_genesis_archive_unpacked_size: u64,
     pub check_vote_account: Option<String>,
     pub incremental_snapshot_fetch: bool,
+    /// Maximum number of peers with the same snapshots to download them from at once
+    pub max_snapshot_download_peers: usize,
+    /// Unpack snapshots while they are downloaded instead of after loading starts
+    pub unpack_snapshot_while_downloading: bool,
 }
 
 fn verify_reachable_ports(
@@ -391,6 +397,7 @@ pub fn attempt_download_genesis_and_snapshot(
     maximum_snapshot_download_abort: u64,
     download_abort_count: &mut u64,
     snapshot_hash: Option<SnapshotHash>,
+    other_snapshot_rpc_addrs: &[SocketAddr],
     identity_keypair: &Arc<Keypair>,
     vote_account: &Pubkey,
     authorized_voter_keypairs: Arc<RwLock<Vec<Arc<Keypair>>>>,
@@ -427,6 +434,7 @@ pub fn attempt_download_genesis_and_snapshot(
         download_abort_count,
         snapshot_hash,
         rpc_contact_info,
+        other_snapshot_rpc_addrs,
     )?;
 
     if let Some(url) = bootstrap_config.check_vote_account.as_ref() {
@@ -646,6 +654,23 @@ pub fn rpc_bootstrap(
             &bootstrap_config,
         );
         let (rpc_contact_info, snapshot_hash, rpc_client) = vetted_rpc_nodes.pop().unwrap();
+        // The other vetted known validators with the same snapshots are downloaded from at the
+        // same time.  The hash of the archive is not known ahead of time, and only its size and
+        // ends are compared with the copy of the primary peer, so other peers are not trusted to
+        // serve the rest of it unless they are known validators.
+        let other_snapshot_rpc_addrs: Vec<_> = vetted_rpc_nodes
+            .iter()
+            .filter(|(_, peer_snapshot_hash, _)| {
+                snapshot_hash.is_some() && *peer_snapshot_hash == snapshot_hash
+            })
+            .filter(|(peer_contact_info, _, _)| {
+                is_known_validator(
+                    peer_contact_info.pubkey(),
+                    &validator_config.known_validators,
+                )
+            })
+            .filter_map(|(peer_contact_info, _, _)| peer_contact_info.rpc().ok())
+            .collect();
         get_rpc_nodes_time += get_rpc_nodes_start.elapsed();
 
         let snapshot_download_start = Instant::now();
@@ -665,6 +690,7 @@ pub fn rpc_bootstrap(
             maximum_snapshot_download_abort,
             &mut download_abort_count,
             snapshot_hash,
+            &other_snapshot_rpc_addrs,
             identity_keypair,
             vote_account,
             authorized_voter_keypairs.clone(),
@@ -1130,6 +1156,7 @@ fn download_snapshots(
     download_abort_count: &mut u64,
     snapshot_hash: Option<SnapshotHash>,
     rpc_contact_info: &ContactInfo,
+    other_snapshot_rpc_addrs: &[SocketAddr],
 ) -> Result<(), String> {
     if snapshot_hash.is_none() {
         return Ok(());
@@ -1175,6 +1202,7 @@ fn download_snapshots(
             maximum_snapshot_download_abort,
             download_abort_count,
             rpc_contact_info,
+            other_snapshot_rpc_addrs,
             full_snapshot_hash,
             SnapshotKind::FullSnapshot,
         )?;
@@ -1207,6 +1235,7 @@ fn download_snapshots(
                     maximum_snapshot_download_abort,
                     download_abort_count,
                     rpc_contact_info,
+                    other_snapshot_rpc_addrs,
                     incremental_snapshot_hash,
                     SnapshotKind::IncrementalSnapshot(full_snapshot_hash.0),
                 )?;
@@ -1230,6 +1259,7 @@ fn download_snapshot(
     maximum_snapshot_download_abort: u64,
     download_abort_count: &mut u64,
     rpc_contact_info: &ContactInfo,
+    other_snapshot_rpc_addrs: &[SocketAddr],
     desired_snapshot_hash: (Slot, Hash),
     snapshot_kind: SnapshotKind,
 ) -> Result<(), String> {
@@ -1240,22 +1270,33 @@ fn download_snapshot(
         .snapshot_config
         .maximum_incremental_snapshot_archives_to_retain;
 
+    let rpc_addr = rpc_contact_info.rpc().map_err(|err| format!("{err:?}"))?;
     *start_progress.write().unwrap() = ValidatorStartProgress::DownloadingSnapshot {
         slot: desired_snapshot_hash.0,
-        rpc_addr: rpc_contact_info.rpc().map_err(|err| format!("{err:?}"))?,
+        rpc_addr,
     };
     let desired_snapshot_hash = (
         desired_snapshot_hash.0,
         solana_runtime::snapshot_hash::SnapshotHash(desired_snapshot_hash.1),
     );
-    download_snapshot_archive(
-        &rpc_contact_info.rpc().map_err(|err| format!("{err:?}"))?,
+    let rpc_addrs: Vec<_> = std::iter::once(rpc_addr)
+        .chain(other_snapshot_rpc_addrs.iter().copied())
+        .collect();
+    download_snapshot_archive_from_peers(
+        &rpc_addrs,
         full_snapshot_archives_dir,
         incremental_snapshot_archives_dir,
         desired_snapshot_hash,
         snapshot_kind,
         maximum_full_snapshot_archives_to_retain,
         maximum_incremental_snapshot_archives_to_retain,
+        &ParallelDownloadConfig {
+            max_peers: bootstrap_config.max_snapshot_download_peers,
+            ..ParallelDownloadConfig::default()
+        },
+        bootstrap_config
+            .unpack_snapshot_while_downloading
+            .then_some(validator_config.account_paths.as_slice()),
         use_progress_bar,
         &mut Some(Box::new(|download_progress: &DownloadProgressRecord| {
             debug!("Download progress: {download_progress:?}");
@@ -21,9 +21,11 @@ use {
     },
     solana_core::{
         banking_trace::{DirByteLimit, BANKING_TRACE_DIR_DEFAULT_BYTE_LIMIT},
-        validator::{BlockProductionMethod, BlockVerificationMethod},
+        validator::{BlockProductionMethod, BlockVerificationMethod, TpuQosPolicy},
     },
+    solana_download_utils::DEFAULT_MAX_DOWNLOAD_PEERS,
     solana_faucet::faucet::{self, FAUCET_PORT},
+    solana_gossip::crds_data_budget::{parse_crds_data_budget, parse_crds_data_name},
     solana_ledger::use_snapshot_archives_at_startup,
     solana_net_utils::{MINIMUM_VALIDATOR_PORT_RANGE_WIDTH, VALIDATOR_PORT_RANGE},
     solana_rpc::{rpc::MAX_REQUEST_BODY_SIZE, rpc_pubsub_service::PubSubConfig},
@@ -40,7 +42,10 @@ use {
         },
     },
     solana_sdk::{
-        clock::Slot, epoch_schedule::MINIMUM_SLOTS_PER_EPOCH, hash::Hash, quic::QUIC_PORT_OFFSET,
+        clock::{Slot, DEFAULT_SLOTS_PER_EPOCH},
+        epoch_schedule::MINIMUM_SLOTS_PER_EPOCH,
+        hash::Hash,
+        quic::QUIC_PORT_OFFSET,
         rpc_port,
     },
     solana_send_transaction_service::send_transaction_service::{
@@ -327,10 +332,79 @@ pub fn app<'a>(version: &'a str, default_args: &'a DefaultArgs) -> App<'a, 'a> {
         .arg(
             Arg::with_name("tower_storage")
                 .long("tower-storage")
-                .possible_values(&["file", "etcd"])
+                .possible_values(&["file", "etcd", "file-lease"])
                 .default_value(&default_args.tower_storage)
                 .takes_value(true)
-                .help("Where to store the tower"),
+                .help("Where to store the tower. With file-lease, the tower is stored in a \
+                       directory that may be shared by an active and a passive validator; \
+                       loading the tower takes an exclusive lease on it and each vote \
+                       renews the lease, so that only one of them can vote at a time"),
+        )
+        .arg(
+            Arg::with_name("tower_lease_duration")
+                .long("tower-lease-duration")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .default_value(&default_args.tower_lease_duration)
+                .help("With --tower-storage file-lease, how long the tower lease stays valid \
+                       after the last vote. Another validator can only take over the tower \
+                       once the lease has expired"),
+        )
+        .arg(
+            Arg::with_name("failover_role")
+                .long("failover-role")
+                .value_name("ROLE")
+                .takes_value(true)
+                .possible_values(&["primary", "standby"])
+                .requires_all(&["failover_address", "failover_peer"])
+                .help("Pair this validator with a hot standby. The primary votes with the \
+                       failover identity and sends heartbeats to its peer. The standby \
+                       assumes the failover identity once the primary has stopped sending \
+                       heartbeats and its vote account has stopped voting for the grace \
+                       period. The standby must be started with the same --vote-account \
+                       and authorized voter. Requires --tower-storage etcd, or file-lease \
+                       with a tower directory shared by both validators, so that the standby \
+                       takes over the primary's tower and fences off the primary"),
+        )
+        .arg(
+            Arg::with_name("failover_address")
+                .long("failover-address")
+                .value_name("HOST:PORT")
+                .takes_value(true)
+                .requires("failover_role")
+                .validator(solana_net_utils::is_host_port)
+                .help("Local address to receive failover heartbeats on"),
+        )
+        .arg(
+            Arg::with_name("failover_peer")
+                .long("failover-peer")
+                .value_name("HOST:PORT")
+                .takes_value(true)
+                .requires("failover_role")
+                .validator(solana_net_utils::is_host_port)
+                .help("Failover address of the other validator of the pair"),
+        )
+        .arg(
+            Arg::with_name("failover_identity")
+                .long("failover-identity")
+                .value_name("KEYPAIR")
+                .takes_value(true)
+                .required_if("failover_role", "standby")
+                .validator(is_keypair_or_ask_keyword)
+                .help("Identity shared by the primary and the standby \
+                       [default: --identity value on the primary]"),
+        )
+        .arg(
+            Arg::with_name("failover_grace_period")
+                .long("failover-grace-period")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .requires("failover_role")
+                .validator(is_parsable::<u64>)
+                .default_value(&default_args.failover_grace_period)
+                .help("How long the primary must have been silent and not voting before \
+                       the standby takes over"),
         )
         .arg(
             Arg::with_name("etcd_endpoint")
@@ -517,6 +591,26 @@ pub fn app<'a>(version: &'a str, default_args: &'a DefaultArgs) -> App<'a, 'a> {
                 .help("The maximum number of times to abort and retry when encountering a \
                       slow snapshot download."),
         )
+        .arg(
+            Arg::with_name("maximum_snapshot_download_peers")
+                .long("maximum-snapshot-download-peers")
+                .value_name("NUMBER")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .default_value(&default_args.max_snapshot_download_peers)
+                .help("The maximum number of peers with the same snapshot to download it from \
+                      at once. Peers serving the snapshot in ranges are downloaded from in \
+                      parallel, and an interrupted download resumes where it left off. \
+                      Only known validators are downloaded from besides the chosen RPC node."),
+        )
+        .arg(
+            Arg::with_name("unpack_snapshot_while_downloading")
+                .long("unpack-snapshot-while-downloading")
+                .takes_value(false)
+                .help("Unpack snapshots downloaded from several peers while they are being \
+                      downloaded. If the unpacked snapshot fails to verify, it is unpacked \
+                      again from the downloaded archive."),
+        )
         .arg(
             Arg::with_name("contact_debug_interval")
                 .long("contact-debug-interval")
@@ -525,6 +619,67 @@ pub fn app<'a>(version: &'a str, default_args: &'a DefaultArgs) -> App<'a, 'a> {
                 .default_value(&default_args.contact_debug_interval)
                 .help("Milliseconds between printing contact debug from gossip."),
         )
+        .arg(
+            Arg::with_name("gossip_capture_dir")
+                .long("gossip-capture-dir")
+                .value_name("DIR")
+                .takes_value(true)
+                .help("Record all gossip packets sent and received into capture files in \
+                       this directory, for inspection with `solana-gossip capture`"),
+        )
+        .arg(
+            Arg::with_name("gossip_capture_file_size")
+                .long("gossip-capture-file-size")
+                .value_name("MEGABYTES")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .default_value(&default_args.gossip_capture_file_size)
+                .help("Start a new gossip capture file once the current one reaches this size"),
+        )
+        .arg(
+            Arg::with_name("gossip_capture_max_files")
+                .long("gossip-capture-max-files")
+                .value_name("NUMBER")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .default_value(&default_args.gossip_capture_max_files)
+                .help("Remove the oldest gossip capture files to keep at most this many"),
+        )
+        .arg(
+            Arg::with_name("gossip_egress_budget")
+                .long("gossip-egress-budget")
+                .value_name("VARIANT:BYTES_PER_SEC")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|arg| parse_crds_data_budget(&arg).map(|_| ()))
+                .help("Limit the bytes per second of this kind of crds value sent in gossip \
+                       push messages and pull responses, e.g. EpochSlots:65536"),
+        )
+        .arg(
+            Arg::with_name("gossip_ingress_budget")
+                .long("gossip-ingress-budget")
+                .value_name("VARIANT:BYTES_PER_SEC")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|arg| parse_crds_data_budget(&arg).map(|_| ()))
+                .help("Limit the bytes per second of this kind of crds value accepted from \
+                       gossip push messages and pull responses, e.g. EpochSlots:65536. \
+                       Unstaked origins share a tenth of it and staked origins split \
+                       the rest by stake"),
+        )
+        .arg(
+            Arg::with_name("gossip_priority")
+                .long("gossip-priority")
+                .value_name("VARIANT")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|arg| parse_crds_data_name(&arg).map(|_| ()))
+                .help("Send and process this kind of crds value ahead of all others in \
+                       gossip. May be specified multiple times, highest priority first"),
+        )
         .arg(
             Arg::with_name("no_poh_speed_test")
                 .long("no-poh-speed-test")
@@ -580,6 +735,36 @@ pub fn app<'a>(version: &'a str, default_args: &'a DefaultArgs) -> App<'a, 'a> {
                 /* .default_value() intentionally not used here! */
                 .help("Keep this amount of shreds in root slots."),
         )
+        .arg(
+            Arg::with_name("purge_vote_transaction_history")
+                .long("purge-vote-transaction-history")
+                .takes_value(false)
+                .help("Drop the transaction status, address signatures and memos of vote \
+                       transactions once they are older than \
+                       --transaction-history-retention-slots. The blocks are kept"),
+        )
+        .arg(
+            Arg::with_name("purge_transaction_history_program")
+                .long("purge-transaction-history-program")
+                .value_name("PROGRAM_ID")
+                .takes_value(true)
+                .multiple(true)
+                .validator(is_pubkey)
+                .help("Drop the transaction status, address signatures and memos of \
+                       transactions that only invoke this program once they are older than \
+                       --transaction-history-retention-slots. May be specified multiple times"),
+        )
+        .arg(
+            Arg::with_name("transaction_history_retention_slots")
+                .long("transaction-history-retention-slots")
+                .value_name("SLOTS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .default_value(&default_args.transaction_history_retention_slots)
+                .help("Number of slots behind the root to keep the history of transactions \
+                       selected by --purge-vote-transaction-history and \
+                       --purge-transaction-history-program"),
+        )
         .arg(
             Arg::with_name("rocksdb_shred_compaction")
                 .long("rocksdb-shred-compaction")
@@ -1043,6 +1228,13 @@ pub fn app<'a>(version: &'a str, default_args: &'a DefaultArgs) -> App<'a, 'a> {
                 .requires("enable_rpc_transaction_history")
                 .help("Verifies blockstore roots on boot and fixes any gaps"),
         )
+        .arg(
+            Arg::with_name("rpc_enable_simulation_program_overrides")
+                .long("rpc-enable-simulation-program-overrides")
+                .takes_value(false)
+                .help("Allow transaction simulations to override the accounts of programs, \
+                       which are then compiled for each simulation"),
+        )
         .arg(
             Arg::with_name("rpc_max_request_body_size")
                 .long("rpc-max-request-body-size")
@@ -1387,6 +1579,44 @@ pub fn app<'a>(version: &'a str, default_args: &'a DefaultArgs) -> App<'a, 'a> {
                 .possible_values(BlockProductionMethod::cli_names())
                 .help(BlockProductionMethod::cli_message())
         )
+        .arg(
+            Arg::with_name("tpu_qos_policy")
+                .long("tpu-qos-policy")
+                .hidden(hidden_unless_forced())
+                .value_name("POLICY")
+                .takes_value(true)
+                .possible_values(TpuQosPolicy::cli_names())
+                .help(TpuQosPolicy::cli_message())
+        )
+        .arg(
+            Arg::with_name("tpu_qos_allowed_peers")
+                .long("tpu-qos-allowed-peer")
+                .hidden(hidden_unless_forced())
+                .validator(is_pubkey)
+                .value_name("PUBKEY")
+                .multiple(true)
+                .takes_value(true)
+                .requires("tpu_qos_policy")
+                .help("Serve TPU connections from this peer, e.g. an RPC partner, as if it had \
+                       the stake of the most staked node, regardless of the validity of the \
+                       packets it sends. Requires the packet-validity TPU QoS policy. \
+                       May be specified multiple times"),
+        )
+        .arg(
+            Arg::with_name("tpu_enable_quic_datagrams")
+                .long("tpu-enable-quic-datagrams")
+                .hidden(hidden_unless_forced())
+                .takes_value(false)
+                .help("Accept transactions sent as QUIC datagrams on the TPU from staked peers"),
+        )
+        .arg(
+            Arg::with_name("tpu_disable_quic_migration")
+                .long("tpu-disable-quic-migration")
+                .hidden(hidden_unless_forced())
+                .takes_value(false)
+                .help("Close TPU QUIC connections whose peer address changes instead of \
+                       migrating them to the new address"),
+        )
         .arg(
             Arg::with_name("wen_restart")
                 .long("wen-restart")
@@ -1620,6 +1850,39 @@ pub fn app<'a>(version: &'a str, default_args: &'a DefaultArgs) -> App<'a, 'a> {
                 .after_help("Note: the new identity only applies to the \
                          currently running validator instance")
         )
+        .subcommand(
+            SubCommand::with_name("failover")
+                .about("Manage hot-standby failover, see --failover-role")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .setting(AppSettings::InferSubcommands)
+                .subcommand(
+                    SubCommand::with_name("status")
+                        .about("Display the failover role and the state of the peer")
+                        .arg(
+                            Arg::with_name("output")
+                                .long("output")
+                                .takes_value(true)
+                                .value_name("MODE")
+                                .possible_values(&["json", "json-compact"])
+                                .help("Output display mode")
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("promote")
+                        .about("Take over the failover identity without waiting for the grace period")
+                        .arg(
+                            Arg::with_name("force")
+                                .long("force")
+                                .takes_value(false)
+                                .help("Take over even if the primary is still sending heartbeats, \
+                                       instead of asking it to step down first")
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("demote")
+                        .about("Give up the failover identity and become the standby")
+                )
+        )
         .subcommand(
             SubCommand::with_name("set-log-filter")
                 .about("Adjust the validator log filter")
@@ -1924,6 +2187,9 @@ pub struct DefaultArgs {
     pub genesis_archive_unpacked_size: String,
     pub health_check_slot_distance: String,
     pub tower_storage: String,
+    pub tower_lease_duration: String,
+    pub failover_grace_period: String,
+    pub transaction_history_retention_slots: String,
     pub etcd_domain_name: String,
     pub send_transaction_service_config: send_transaction_service::Config,
 
@@ -1952,8 +2218,11 @@ pub struct DefaultArgs {
     pub incremental_snapshot_archive_interval_slots: String,
     pub min_snapshot_download_speed: String,
     pub max_snapshot_download_abort: String,
+    pub max_snapshot_download_peers: String,
 
     pub contact_debug_interval: String,
+    pub gossip_capture_file_size: String,
+    pub gossip_capture_max_files: String,
 
     pub accounts_filler_count: String,
     pub accounts_filler_size: String,
@@ -1996,6 +2265,9 @@ impl DefaultArgs {
             rpc_max_multiple_accounts: MAX_MULTIPLE_ACCOUNTS.to_string(),
             health_check_slot_distance: "150".to_string(),
             tower_storage: "file".to_string(),
+            tower_lease_duration: "5000".to_string(),
+            failover_grace_period: "30".to_string(),
+            transaction_history_retention_slots: DEFAULT_SLOTS_PER_EPOCH.to_string(),
             etcd_domain_name: "localhost".to_string(),
             rpc_pubsub_max_active_subscriptions: PubSubConfig::default()
                 .max_active_subscriptions
@@ -2043,8 +2315,11 @@ impl DefaultArgs {
                 DEFAULT_INCREMENTAL_SNAPSHOT_ARCHIVE_INTERVAL_SLOTS.to_string(),
             min_snapshot_download_speed: DEFAULT_MIN_SNAPSHOT_DOWNLOAD_SPEED.to_string(),
             max_snapshot_download_abort: MAX_SNAPSHOT_DOWNLOAD_ABORT.to_string(),
+            max_snapshot_download_peers: DEFAULT_MAX_DOWNLOAD_PEERS.to_string(),
             snapshot_archive_format: DEFAULT_ARCHIVE_COMPRESSION.to_string(),
             contact_debug_interval: "120000".to_string(),
+            gossip_capture_file_size: "256".to_string(),
+            gossip_capture_max_files: "16".to_string(),
             snapshot_version: SnapshotVersion::default(),
             rocksdb_shred_compaction: "level".to_string(),
             rocksdb_ledger_compression: "none".to_string(),
@@ -2431,6 +2706,42 @@ pub fn test_app<'a>(version: &'a str, default_args: &'a DefaultTestArgs) -> App<
                      If the ledger already exists then this parameter is silently ignored",
                 ),
         )
+        .arg(
+            Arg::with_name("fork")
+                .long("fork")
+                .takes_value(false)
+                .requires("json_rpc_url")
+                .conflicts_with("fork_snapshot")
+                .help(
+                    "Fork the cluster referenced by the --url argument lazily: transactions \
+                     which use accounts that the test validator does not have fetch them from \
+                     the cluster the first time, as of --fork-slot or later",
+                ),
+        )
+        .arg(
+            Arg::with_name("fork_slot")
+                .long("fork-slot")
+                .value_name("SLOT")
+                .takes_value(true)
+                .validator(is_slot)
+                .requires("fork")
+                .help(
+                    "The oldest slot of the cluster to fork with --fork. Accounts are fetched \
+                     at this slot or after it, as RPC services only serve their latest state \
+                     [default: the current finalized slot of the cluster]",
+                ),
+        )
+        .arg(
+            Arg::with_name("fork_snapshot")
+                .long("fork-snapshot")
+                .value_name("LEDGER_DIR")
+                .takes_value(true)
+                .help(
+                    "Fork the latest snapshot in LEDGER_DIR lazily: transactions which use \
+                     accounts that the test validator does not have load them from the \
+                     snapshot. LEDGER_DIR must also contain the genesis of the snapshot",
+                ),
+        )
         .arg(
             Arg::with_name("warp_slot")
                 .required(false)
@@ -2540,6 +2851,15 @@ pub fn test_app<'a>(version: &'a str, default_args: &'a DefaultTestArgs) -> App<
                 .validator(is_parsable::<u64>)
                 .takes_value(true)
                 .help("Override the runtime's account lock limit per transaction")
+        )
+        .arg(
+            Arg::with_name("enable_instruction_tracing")
+                .long("enable-instruction-tracing")
+                .takes_value(false)
+                .help(
+                    "Compile programs with instruction tracing, so that simulateTransaction can \
+                     return the execution traces of SBF programs. Slows down all programs",
+                ),
         );
 }
 
@@ -0,0 +1,999 @@
+//! Hot-standby failover between two validators sharing one identity.
+//!
+//! The primary votes with the shared identity and sends heartbeats to the
+//! standby. The standby runs with a throwaway identity and keeps replaying. It
+//! only assumes the shared identity once the primary has stopped sending
+//! heartbeats *and* the vote account has not landed a vote for the grace
+//! period while the cluster kept making progress. Both nodes have to keep the
+//! tower in storage they share, so that the standby takes over the primary's
+//! tower. With a lease based tower storage, restoring the tower on takeover
+//! also fences off the previous primary.
+//!
+//! Messages are signed with the shared identity and carry a sequence number,
+//! so that only the two nodes can send them and they can't be replayed. Every
+//! takeover starts a new term. Should both nodes be primary at the same time,
+//! the node with the lower term steps down, or with equal terms the node with
+//! the higher node id.
+
+use {
+    log::*,
+    serde::{Deserialize, Serialize},
+    solana_core::{
+        admin_rpc_post_init::AdminRpcRequestMetadataPostInit,
+        consensus::{tower_storage::TowerStorage, Tower},
+    },
+    solana_metrics::{datapoint_error, datapoint_warn},
+    solana_sdk::{
+        clock::Slot,
+        exit::Exit,
+        pubkey::Pubkey,
+        signature::{Keypair, Signature, Signer},
+        timing::timestamp,
+    },
+    std::{
+        fmt::{self, Display},
+        io,
+        net::{SocketAddr, UdpSocket},
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc, Mutex, RwLock,
+        },
+        thread::Builder,
+        time::{Duration, Instant},
+    },
+};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(400);
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(100);
+// A primary that is still voting lands a vote well within this many slots
+const MIN_SLOTS_WITHOUT_VOTE: Slot = 32;
+const MAX_MESSAGE_SIZE: usize = 512;
+// The primary's tower lease may take a while to expire, so a failed takeover
+// is retried this often, up to MAX_TAKEOVER_ATTEMPTS times.
+const TAKEOVER_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_TAKEOVER_ATTEMPTS: usize = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailoverRole {
+    Primary,
+    Standby,
+}
+
+impl Display for FailoverRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Primary => write!(f, "primary"),
+            Self::Standby => write!(f, "standby"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum FailoverMessage {
+    Heartbeat {
+        role: FailoverRole,
+        identity: Pubkey,
+        last_vote_slot: Option<Slot>,
+        /// Term of the sender, which increases with every takeover
+        term: u64,
+        wallclock: u64,
+    },
+    /// Sent by a standby that was promoted manually, asking the primary to
+    /// give up the shared identity first
+    StepDown,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct SignedFailoverMessage {
+    /// Signature of the shared identity over the other fields
+    signature: Signature,
+    /// Random id of the sending node, as both nodes sign with the same keypair
+    node_id: u64,
+    /// Increases with every message sent, and across restarts
+    sequence: u64,
+    message: FailoverMessage,
+}
+
+impl SignedFailoverMessage {
+    fn new(keypair: &Keypair, node_id: u64, sequence: u64, message: FailoverMessage) -> Self {
+        let signature = keypair.sign_message(&Self::signable_data(node_id, sequence, &message));
+        Self {
+            signature,
+            node_id,
+            sequence,
+            message,
+        }
+    }
+
+    fn signable_data(node_id: u64, sequence: u64, message: &FailoverMessage) -> Vec<u8> {
+        bincode::serialize(&(node_id, sequence, message)).expect("serialize failover message")
+    }
+
+    fn verify(&self, pubkey: &Pubkey) -> bool {
+        self.signature.verify(
+            pubkey.as_ref(),
+            &Self::signable_data(self.node_id, self.sequence, &self.message),
+        )
+    }
+}
+
+pub struct FailoverConfig {
+    pub role: FailoverRole,
+    /// Local address heartbeats are received on
+    pub bind_address: SocketAddr,
+    pub peer_address: SocketAddr,
+    /// How long the primary must have been silent and not voting before the
+    /// standby takes over
+    pub grace_period: Duration,
+    /// The identity shared by the primary and the standby
+    pub identity: Arc<Keypair>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PeerHeartbeat {
+    role: FailoverRole,
+    last_vote_slot: Option<Slot>,
+    received: Instant,
+}
+
+/// Collects the evidence a standby needs before it may consider the primary
+/// down
+#[derive(Debug)]
+struct PrimaryMonitor {
+    grace_period: Duration,
+    peer: Option<PeerHeartbeat>,
+    last_vote_slot: Option<Slot>,
+    last_vote_change: Instant,
+    // Working bank slot when the last vote change was observed
+    last_vote_change_working_slot: Option<Slot>,
+    working_slot: Slot,
+}
+
+impl PrimaryMonitor {
+    fn new(grace_period: Duration, now: Instant) -> Self {
+        Self {
+            grace_period,
+            peer: None,
+            last_vote_slot: None,
+            last_vote_change: now,
+            last_vote_change_working_slot: None,
+            working_slot: 0,
+        }
+    }
+
+    fn on_heartbeat(&mut self, role: FailoverRole, last_vote_slot: Option<Slot>, now: Instant) {
+        self.peer = Some(PeerHeartbeat {
+            role,
+            last_vote_slot,
+            received: now,
+        });
+    }
+
+    fn on_bank(&mut self, last_vote_slot: Option<Slot>, working_slot: Slot, now: Instant) {
+        if last_vote_slot != self.last_vote_slot || self.last_vote_change_working_slot.is_none() {
+            self.last_vote_slot = last_vote_slot;
+            self.last_vote_change = now;
+            self.last_vote_change_working_slot = Some(working_slot);
+        }
+        self.working_slot = working_slot;
+    }
+
+    fn peer_is_primary(&self, now: Instant) -> bool {
+        self.peer.is_some_and(|peer| {
+            peer.role == FailoverRole::Primary
+                && now.saturating_duration_since(peer.received) < self.grace_period
+        })
+    }
+
+    /// Returns why the primary can't be considered down yet, if anything
+    fn check_primary_down(&self, now: Instant) -> Result<(), String> {
+        if let Some(peer) = self.peer {
+            let since_heartbeat = now.saturating_duration_since(peer.received);
+            if peer.role == FailoverRole::Primary && since_heartbeat < self.grace_period {
+                return Err(format!(
+                    "primary heartbeat received {}ms ago",
+                    since_heartbeat.as_millis()
+                ));
+            }
+        }
+        let since_vote = now.saturating_duration_since(self.last_vote_change);
+        if since_vote < self.grace_period {
+            return Err(format!(
+                "vote account last changed {}ms ago",
+                since_vote.as_millis()
+            ));
+        }
+        let slots_without_vote = self.working_slot.saturating_sub(
+            self.last_vote_change_working_slot
+                .unwrap_or(self.working_slot),
+        );
+        if slots_without_vote < MIN_SLOTS_WITHOUT_VOTE {
+            return Err(format!(
+                "only {slots_without_vote} slots replayed since the last vote, \
+                the cluster may not be making progress"
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Promotion {
+    force: bool,
+}
+
+struct FailoverState {
+    role: FailoverRole,
+    monitor: PrimaryMonitor,
+    promotion: Option<Promotion>,
+    /// Random id of this node, which breaks ties between equal terms
+    node_id: u64,
+    term: u64,
+    // Highest term and sequence number received from the peer
+    peer_term: u64,
+    peer_sequence: u64,
+    takeover_attempts: usize,
+    last_takeover_attempt: Option<Instant>,
+    /// Why the last takeover failed after MAX_TAKEOVER_ATTEMPTS attempts.
+    /// Blocks further takeovers until a manual promotion.
+    takeover_failure: Option<String>,
+}
+
+impl FailoverState {
+    fn new(role: FailoverRole, grace_period: Duration, node_id: u64, now: Instant) -> Self {
+        Self {
+            role,
+            monitor: PrimaryMonitor::new(grace_period, now),
+            promotion: None,
+            node_id,
+            term: 0,
+            peer_term: 0,
+            peer_sequence: 0,
+            takeover_attempts: 0,
+            last_takeover_attempt: None,
+            takeover_failure: None,
+        }
+    }
+
+    /// Returns whether the standby should attempt to take over now, in which
+    /// case the attempt is recorded
+    fn start_take_over(&mut self, now: Instant) -> bool {
+        if self.role != FailoverRole::Standby {
+            return false;
+        }
+        if self
+            .last_takeover_attempt
+            .is_some_and(|attempt| now.saturating_duration_since(attempt) < TAKEOVER_RETRY_INTERVAL)
+        {
+            return false;
+        }
+        if self.promotion.is_none()
+            && (self.takeover_failure.is_some() || self.monitor.check_primary_down(now).is_err())
+        {
+            return false;
+        }
+        self.last_takeover_attempt = Some(now);
+        true
+    }
+
+    /// Records a failed takeover, returns whether the standby gives up
+    fn on_take_over_failed(&mut self, err: String) -> bool {
+        self.takeover_attempts = self.takeover_attempts.saturating_add(1);
+        if self.takeover_attempts < MAX_TAKEOVER_ATTEMPTS {
+            return false;
+        }
+        self.takeover_attempts = 0;
+        self.promotion = None;
+        self.takeover_failure = Some(err);
+        true
+    }
+
+    /// Returns whether a message passes the replay protection, and records
+    /// its sequence number if so
+    fn accept(&mut self, message: &SignedFailoverMessage) -> bool {
+        if message.node_id == self.node_id || message.sequence <= self.peer_sequence {
+            return false;
+        }
+        self.peer_sequence = message.sequence;
+        true
+    }
+
+    /// Records a heartbeat of the peer, returns whether this node has to step
+    /// down because the peer holds the shared identity with precedence
+    fn on_heartbeat(
+        &mut self,
+        peer_node_id: u64,
+        role: FailoverRole,
+        holds_identity: bool,
+        last_vote_slot: Option<Slot>,
+        term: u64,
+        now: Instant,
+    ) -> bool {
+        self.monitor.on_heartbeat(role, last_vote_slot, now);
+        self.peer_term = self.peer_term.max(term);
+        role == FailoverRole::Primary
+            && holds_identity
+            && self.role == FailoverRole::Primary
+            && (term, self.node_id) > (self.term, peer_node_id)
+    }
+
+    fn on_take_over(&mut self) {
+        self.role = FailoverRole::Primary;
+        self.promotion = None;
+        self.term = self.term.max(self.peer_term).saturating_add(1);
+        self.takeover_attempts = 0;
+        self.last_takeover_attempt = None;
+        self.takeover_failure = None;
+    }
+
+    fn on_step_down(&mut self, now: Instant) {
+        self.role = FailoverRole::Standby;
+        self.promotion = None;
+        self.takeover_attempts = 0;
+        self.last_takeover_attempt = None;
+        // Wait for a full grace period before considering a takeover again
+        self.monitor = PrimaryMonitor::new(self.monitor.grace_period, now);
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FailoverStatus {
+    pub role: FailoverRole,
+    pub identity: String,
+    pub peer_address: SocketAddr,
+    pub peer_role: Option<FailoverRole>,
+    pub peer_last_vote_slot: Option<Slot>,
+    pub last_peer_heartbeat_ms: Option<u64>,
+    pub last_vote_slot: Option<Slot>,
+    pub promotion_pending: bool,
+    pub term: u64,
+    /// Why a standby would not take over right now
+    pub takeover_blocked_by: Option<String>,
+}
+
+impl Display for FailoverStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Role: {}", self.role)?;
+        writeln!(f, "Failover identity: {}", self.identity)?;
+        writeln!(f, "Peer: {}", self.peer_address)?;
+        match (self.peer_role, self.last_peer_heartbeat_ms) {
+            (Some(peer_role), Some(last_peer_heartbeat_ms)) => writeln!(
+                f,
+                "Peer role: {peer_role} (last heartbeat {last_peer_heartbeat_ms}ms ago, \
+                last vote slot {:?})",
+                self.peer_last_vote_slot,
+            )?,
+            _ => writeln!(f, "Peer role: unknown (no heartbeat received)")?,
+        }
+        writeln!(f, "Last vote slot: {:?}", self.last_vote_slot)?;
+        writeln!(f, "Term: {}", self.term)?;
+        if self.promotion_pending {
+            writeln!(f, "Promotion pending, waiting for the primary to step down")?;
+        }
+        match &self.takeover_blocked_by {
+            Some(reason) => writeln!(f, "Takeover blocked by: {reason}"),
+            None => writeln!(f, "Takeover blocked by: nothing"),
+        }
+    }
+}
+
+pub struct FailoverService {
+    config: FailoverConfig,
+    tower_storage: Arc<dyn TowerStorage>,
+    post_init: Arc<RwLock<Option<AdminRpcRequestMetadataPostInit>>>,
+    state: Mutex<FailoverState>,
+    sequence: AtomicU64,
+}
+
+impl FailoverService {
+    pub fn new(
+        config: FailoverConfig,
+        tower_storage: Arc<dyn TowerStorage>,
+        post_init: Arc<RwLock<Option<AdminRpcRequestMetadataPostInit>>>,
+    ) -> Arc<Self> {
+        let state = Mutex::new(FailoverState::new(
+            config.role,
+            config.grace_period,
+            rand::random(),
+            Instant::now(),
+        ));
+        Arc::new(Self {
+            config,
+            tower_storage,
+            post_init,
+            state,
+            // Microseconds of the wallclock, so that the sequence numbers keep
+            // increasing across restarts
+            sequence: AtomicU64::new(timestamp().saturating_mul(1_000)),
+        })
+    }
+
+    /// Binds the heartbeat socket and starts the failover thread, which runs
+    /// until the validator exits
+    pub fn start(self: &Arc<Self>, validator_exit: &Arc<RwLock<Exit>>) -> io::Result<()> {
+        let socket = UdpSocket::bind(self.config.bind_address)?;
+        socket.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+        let exit = Arc::new(AtomicBool::new(false));
+        validator_exit.write().unwrap().register_exit(Box::new({
+            let exit = exit.clone();
+            move || exit.store(true, Ordering::Relaxed)
+        }));
+        info!(
+            "Failover started as {} for {}, heartbeats on {} to {}",
+            self.config.role,
+            self.config.identity.pubkey(),
+            self.config.bind_address,
+            self.config.peer_address,
+        );
+        let service = self.clone();
+        Builder::new()
+            .name("solFailover".to_string())
+            .spawn(move || service.run(socket, &exit))?;
+        Ok(())
+    }
+
+    fn run(&self, socket: UdpSocket, exit: &AtomicBool) {
+        let mut last_heartbeat_sent: Option<Instant> = None;
+        let mut buffer = [0u8; MAX_MESSAGE_SIZE];
+        while !exit.load(Ordering::Relaxed) {
+            match socket.recv_from(&mut buffer) {
+                Ok((size, from)) => self.on_packet(&socket, &buffer[..size], from),
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(err) => warn!("Failover heartbeat socket error: {err}"),
+            }
+
+            let Some(post_init) = self.post_init.read().unwrap().clone() else {
+                // Validator start up isn't complete yet
+                continue;
+            };
+            let last_vote_slot = self.observe_bank(&post_init);
+            self.step(&socket, &post_init);
+
+            if last_heartbeat_sent.map_or(true, |sent| sent.elapsed() >= HEARTBEAT_INTERVAL) {
+                let message = self.heartbeat(post_init.cluster_info.id(), last_vote_slot);
+                self.send(&socket, message);
+                last_heartbeat_sent = Some(Instant::now());
+            }
+        }
+    }
+
+    fn on_packet(&self, socket: &UdpSocket, packet: &[u8], from: SocketAddr) {
+        if from != self.config.peer_address {
+            debug!("Ignoring failover message from unknown address {from}");
+            return;
+        }
+        let message = match bincode::deserialize::<SignedFailoverMessage>(packet) {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("Invalid failover message from {from}: {err}");
+                return;
+            }
+        };
+        if !message.verify(&self.config.identity.pubkey()) {
+            warn!("Failover message from {from} has an invalid signature");
+            return;
+        }
+        if !self.state.lock().unwrap().accept(&message) {
+            warn!(
+                "Ignoring replayed failover message from {from}, sequence {}",
+                message.sequence
+            );
+            return;
+        }
+        match message.message {
+            FailoverMessage::Heartbeat {
+                role,
+                identity,
+                last_vote_slot,
+                term,
+                wallclock: _,
+            } => {
+                let step_down = self.state.lock().unwrap().on_heartbeat(
+                    message.node_id,
+                    role,
+                    identity == self.config.identity.pubkey(),
+                    last_vote_slot,
+                    term,
+                    Instant::now(),
+                );
+                if step_down {
+                    error!(
+                        "Peer {from} is also primary for {identity} and takes precedence \
+                        with term {term}, stepping down"
+                    );
+                    self.step_down();
+                }
+            }
+            FailoverMessage::StepDown => {
+                if self.role() == FailoverRole::Primary {
+                    warn!("Peer {from} requested this node to step down");
+                    self.step_down();
+                }
+                // Let the peer know right away
+                let message = self.heartbeat(self.current_identity(), None);
+                self.send(socket, message);
+            }
+        }
+    }
+
+    fn observe_bank(&self, post_init: &AdminRpcRequestMetadataPostInit) -> Option<Slot> {
+        let working_bank = post_init.bank_forks.read().unwrap().working_bank();
+        let last_vote_slot = working_bank
+            .get_vote_account(&post_init.vote_account)
+            .and_then(|vote_account| {
+                vote_account
+                    .vote_state()
+                    .ok()
+                    .and_then(|vote_state| vote_state.last_voted_slot())
+            });
+        self.state.lock().unwrap().monitor.on_bank(
+            last_vote_slot,
+            working_bank.slot(),
+            Instant::now(),
+        );
+        last_vote_slot
+    }
+
+    // Takes over the shared identity when the primary is down, or when a
+    // manual promotion may proceed
+    fn step(&self, socket: &UdpSocket, post_init: &AdminRpcRequestMetadataPostInit) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        if !state.start_take_over(now) {
+            return;
+        }
+        match state.promotion {
+            Some(Promotion { force }) => {
+                if !force && state.monitor.peer_is_primary(now) {
+                    drop(state);
+                    self.send(socket, FailoverMessage::StepDown);
+                    return;
+                }
+            }
+            None => {
+                if state.takeover_attempts == 0 {
+                    warn!(
+                        "Primary has been silent and not voting for {:?}, taking over",
+                        self.config.grace_period
+                    );
+                }
+            }
+        }
+        // Restoring the tower waits on the tower storage, during which admin
+        // requests, e.g. for the failover status, must not block on the state.
+        drop(state);
+        let result = self.take_over(post_init);
+        let mut state = self.state.lock().unwrap();
+        match result {
+            Ok(()) => {
+                state.on_take_over();
+                info!("Failover term is now {}", state.term);
+                datapoint_warn!("failover-takeover", ("count", 1, i64));
+            }
+            Err(err) => {
+                if state.on_take_over_failed(err.clone()) {
+                    error!(
+                        "Failover takeover failed {MAX_TAKEOVER_ATTEMPTS} times, not retrying \
+                        until promoted with `solana-validator failover promote`: {err}"
+                    );
+                    datapoint_error!("failover-takeover_failed", ("error", err, String));
+                } else {
+                    // Most likely the tower lease of the primary hasn't expired yet
+                    warn!(
+                        "Failover takeover attempt {} failed, will retry: {err}",
+                        state.takeover_attempts
+                    );
+                }
+            }
+        }
+    }
+
+    fn take_over(&self, post_init: &AdminRpcRequestMetadataPostInit) -> Result<(), String> {
+        self.restore_tower()?;
+        let identity = self.config.identity.clone();
+        solana_metrics::set_host_id(identity.pubkey().to_string());
+        post_init.cluster_info.set_keypair(identity);
+        warn!("Identity set to {}", post_init.cluster_info.id());
+        Ok(())
+    }
+
+    // Loads the tower of the shared identity, which also takes over its lease
+    // with a lease based tower storage
+    fn restore_tower(&self) -> Result<Tower, String> {
+        let identity = self.config.identity.pubkey();
+        Tower::restore(self.tower_storage.as_ref(), &identity)
+            .map_err(|err| format!("Unable to load tower for identity {identity}: {err}"))
+    }
+
+    // Switches to a fresh identity so that this node stops voting
+    fn step_down(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(post_init) = self.post_init.read().unwrap().as_ref() {
+            let identity = Arc::new(Keypair::new());
+            solana_metrics::set_host_id(identity.pubkey().to_string());
+            post_init.cluster_info.set_keypair(identity);
+            warn!(
+                "Stepped down to standby, identity set to {}",
+                post_init.cluster_info.id()
+            );
+        }
+        state.on_step_down(Instant::now());
+        datapoint_warn!("failover-step_down", ("count", 1, i64));
+    }
+
+    fn heartbeat(&self, identity: Pubkey, last_vote_slot: Option<Slot>) -> FailoverMessage {
+        let state = self.state.lock().unwrap();
+        FailoverMessage::Heartbeat {
+            role: state.role,
+            identity,
+            last_vote_slot,
+            term: state.term,
+            wallclock: timestamp(),
+        }
+    }
+
+    fn send(&self, socket: &UdpSocket, message: FailoverMessage) {
+        let node_id = self.state.lock().unwrap().node_id;
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let message = SignedFailoverMessage::new(&self.config.identity, node_id, sequence, message);
+        let packet = bincode::serialize(&message).expect("serialize failover message");
+        if let Err(err) = socket.send_to(&packet, self.config.peer_address) {
+            debug!(
+                "Unable to send failover message to {}: {err}",
+                self.config.peer_address
+            );
+        }
+    }
+
+    fn role(&self) -> FailoverRole {
+        self.state.lock().unwrap().role
+    }
+
+    fn current_identity(&self) -> Pubkey {
+        match self.post_init.read().unwrap().as_ref() {
+            Some(post_init) => post_init.cluster_info.id(),
+            None => Pubkey::default(),
+        }
+    }
+
+    /// Requests that this standby takes over the shared identity without
+    /// waiting for the grace period. Unless `force` is set, a primary that is
+    /// still sending heartbeats is asked to step down first.
+    pub fn promote(&self, force: bool) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.role == FailoverRole::Primary {
+            return Err("This node is already the primary".to_string());
+        }
+        state.promotion = Some(Promotion { force });
+        state.takeover_attempts = 0;
+        state.takeover_failure = None;
+        Ok(())
+    }
+
+    /// Gives up the shared identity, this node becomes the standby
+    pub fn demote(&self) -> Result<(), String> {
+        if self.post_init.read().unwrap().is_none() {
+            return Err("Retry once validator start up is complete".to_string());
+        }
+        if self.role() == FailoverRole::Standby {
+            return Err("This node is already the standby".to_string());
+        }
+        self.step_down();
+        Ok(())
+    }
+
+    pub fn status(&self) -> FailoverStatus {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        let peer = state.monitor.peer;
+        FailoverStatus {
+            role: state.role,
+            identity: self.config.identity.pubkey().to_string(),
+            peer_address: self.config.peer_address,
+            peer_role: peer.map(|peer| peer.role),
+            peer_last_vote_slot: peer.and_then(|peer| peer.last_vote_slot),
+            last_peer_heartbeat_ms: peer.map(|peer| {
+                u64::try_from(now.saturating_duration_since(peer.received).as_millis())
+                    .unwrap_or(u64::MAX)
+            }),
+            last_vote_slot: state.monitor.last_vote_slot,
+            promotion_pending: state.promotion.is_some(),
+            term: state.term,
+            takeover_blocked_by: match &state.takeover_failure {
+                Some(err) => Some(format!("takeover failed: {err}")),
+                None => state.monitor.check_primary_down(now).err(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_core::consensus::tower_storage::{FileLeaseStore, LeasedTowerStorage},
+        tempfile::TempDir,
+    };
+
+    #[test]
+    fn test_primary_monitor() {
+        let grace_period = Duration::from_secs(10);
+        let start = Instant::now();
+        let mut monitor = PrimaryMonitor::new(grace_period, start);
+        monitor.on_bank(Some(100), 101, start);
+        monitor.on_heartbeat(FailoverRole::Primary, Some(100), start);
+        assert!(monitor.peer_is_primary(start));
+        assert!(monitor.check_primary_down(start).is_err());
+
+        // Heartbeats stopped, but the primary is still voting
+        let now = start + grace_period;
+        monitor.on_bank(Some(120), 121, now - Duration::from_secs(1));
+        assert!(!monitor.peer_is_primary(now));
+        assert!(monitor.check_primary_down(now).is_err());
+
+        // Votes stopped for the grace period, but the cluster has not made
+        // enough progress to tell
+        let now = now + grace_period;
+        monitor.on_bank(Some(120), 130, now);
+        assert!(monitor.check_primary_down(now).is_err());
+
+        monitor.on_bank(Some(120), 121 + MIN_SLOTS_WITHOUT_VOTE, now);
+        assert_eq!(monitor.check_primary_down(now), Ok(()));
+
+        // A late heartbeat from the primary blocks the takeover again
+        monitor.on_heartbeat(FailoverRole::Primary, Some(120), now);
+        assert!(monitor.check_primary_down(now).is_err());
+
+        // A standby peer does not
+        monitor.on_heartbeat(FailoverRole::Standby, None, now);
+        assert_eq!(monitor.check_primary_down(now), Ok(()));
+    }
+
+    #[test]
+    fn test_failover_message_size() {
+        let message = FailoverMessage::Heartbeat {
+            role: FailoverRole::Primary,
+            identity: Pubkey::new_unique(),
+            last_vote_slot: Some(Slot::MAX),
+            term: u64::MAX,
+            wallclock: u64::MAX,
+        };
+        let message = SignedFailoverMessage::new(&Keypair::new(), u64::MAX, u64::MAX, message);
+        let packet = bincode::serialize(&message).unwrap();
+        assert!(packet.len() <= MAX_MESSAGE_SIZE);
+        assert_eq!(
+            bincode::deserialize::<SignedFailoverMessage>(&packet).unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn test_failover_message_authentication() {
+        let identity = Keypair::new();
+        let grace_period = Duration::from_secs(10);
+        let mut state = FailoverState::new(FailoverRole::Primary, grace_period, 1, Instant::now());
+
+        let message = SignedFailoverMessage::new(&identity, 2, 100, FailoverMessage::StepDown);
+        assert!(message.verify(&identity.pubkey()));
+        assert!(!message.verify(&Keypair::new().pubkey()));
+        assert!(
+            !SignedFailoverMessage::new(&Keypair::new(), 2, 100, FailoverMessage::StepDown)
+                .verify(&identity.pubkey())
+        );
+
+        // A signature does not carry over to a different sequence number
+        let mut tampered = SignedFailoverMessage::new(&identity, 2, 100, FailoverMessage::StepDown);
+        tampered.sequence = 101;
+        assert!(!tampered.verify(&identity.pubkey()));
+
+        assert!(state.accept(&message));
+        // Replayed messages are rejected
+        assert!(!state.accept(&message));
+        assert!(!state.accept(&SignedFailoverMessage::new(
+            &identity,
+            2,
+            99,
+            FailoverMessage::StepDown
+        )));
+        // So are the node's own messages sent back to it
+        assert!(!state.accept(&SignedFailoverMessage::new(
+            &identity,
+            1,
+            200,
+            FailoverMessage::StepDown
+        )));
+        // A restarted peer has a new node id and higher sequence numbers
+        assert!(state.accept(&SignedFailoverMessage::new(
+            &identity,
+            3,
+            1_000,
+            FailoverMessage::StepDown
+        )));
+    }
+
+    struct TestNode {
+        state: FailoverState,
+        identity: Pubkey,
+    }
+
+    impl TestNode {
+        fn new(node_id: u64, role: FailoverRole, shared_identity: Pubkey, now: Instant) -> Self {
+            Self {
+                state: FailoverState::new(role, Duration::from_secs(10), node_id, now),
+                identity: match role {
+                    FailoverRole::Primary => shared_identity,
+                    FailoverRole::Standby => Pubkey::new_unique(),
+                },
+            }
+        }
+
+        fn take_over(&mut self, shared_identity: Pubkey) {
+            self.state.on_take_over();
+            self.identity = shared_identity;
+        }
+
+        // Delivers a heartbeat of `self` to `peer`, stepping `peer` down if it has to
+        fn send_heartbeat(&self, peer: &mut TestNode, shared_identity: Pubkey, now: Instant) {
+            if peer.state.on_heartbeat(
+                self.state.node_id,
+                self.state.role,
+                self.identity == shared_identity,
+                None,
+                self.state.term,
+                now,
+            ) {
+                peer.state.on_step_down(now);
+                peer.identity = Pubkey::new_unique();
+            }
+        }
+    }
+
+    fn primaries(nodes: &[&TestNode]) -> usize {
+        nodes
+            .iter()
+            .filter(|node| node.state.role == FailoverRole::Primary)
+            .count()
+    }
+
+    #[test]
+    fn test_failover_conflicting_primaries() {
+        let shared_identity = Pubkey::new_unique();
+        let now = Instant::now();
+        let mut a = TestNode::new(1, FailoverRole::Primary, shared_identity, now);
+        let mut b = TestNode::new(2, FailoverRole::Standby, shared_identity, now);
+
+        // The standby takes over while the primary is still alive but cut off
+        b.take_over(shared_identity);
+        assert_eq!(b.state.term, 1);
+        assert_eq!(primaries(&[&a, &b]), 2);
+
+        // Once they hear from each other again, the stale primary with the
+        // lower term steps down, no matter who hears first
+        b.send_heartbeat(&mut a, shared_identity, now);
+        a.send_heartbeat(&mut b, shared_identity, now);
+        assert_eq!(a.state.role, FailoverRole::Standby);
+        assert_eq!(b.state.role, FailoverRole::Primary);
+        assert_eq!(b.identity, shared_identity);
+        assert_ne!(a.identity, shared_identity);
+
+        // The new standby takes over the next term after having seen term 1
+        a.take_over(shared_identity);
+        assert_eq!(a.state.term, 2);
+        a.send_heartbeat(&mut b, shared_identity, now);
+        b.send_heartbeat(&mut a, shared_identity, now);
+        assert_eq!(primaries(&[&a, &b]), 1);
+        assert_eq!(a.state.role, FailoverRole::Primary);
+
+        // With equal terms, the node with the lower node id keeps the identity
+        let mut a = TestNode::new(1, FailoverRole::Standby, shared_identity, now);
+        let mut b = TestNode::new(2, FailoverRole::Standby, shared_identity, now);
+        a.take_over(shared_identity);
+        b.take_over(shared_identity);
+        assert_eq!(a.state.term, b.state.term);
+        a.send_heartbeat(&mut b, shared_identity, now);
+        b.send_heartbeat(&mut a, shared_identity, now);
+        assert_eq!(a.state.role, FailoverRole::Primary);
+        assert_eq!(b.state.role, FailoverRole::Standby);
+
+        // Repeated heartbeats don't flip flop the roles
+        for _ in 0..3 {
+            a.send_heartbeat(&mut b, shared_identity, now);
+            b.send_heartbeat(&mut a, shared_identity, now);
+            assert_eq!(a.state.role, FailoverRole::Primary);
+            assert_eq!(b.state.role, FailoverRole::Standby);
+        }
+    }
+
+    #[test]
+    fn test_failover_take_over_attempts() {
+        let grace_period = Duration::from_secs(10);
+        let start = Instant::now();
+        let mut state = FailoverState::new(FailoverRole::Standby, grace_period, 1, start);
+        state.monitor.on_bank(Some(100), 101, start);
+        // Nothing to do while the primary may still be up
+        assert!(!state.start_take_over(start));
+
+        let mut now = start + grace_period;
+        state
+            .monitor
+            .on_bank(Some(100), 101 + MIN_SLOTS_WITHOUT_VOTE, now);
+        let mut attempts = 0;
+        while state.start_take_over(now) {
+            attempts += 1;
+            // Attempts are spaced out by the retry interval
+            assert!(!state.start_take_over(now));
+            if state.on_take_over_failed("lease held".to_string()) {
+                break;
+            }
+            now += TAKEOVER_RETRY_INTERVAL;
+        }
+        assert_eq!(attempts, MAX_TAKEOVER_ATTEMPTS);
+        assert_eq!(state.takeover_failure.as_deref(), Some("lease held"));
+        // The standby gave up until it is promoted manually
+        now += TAKEOVER_RETRY_INTERVAL;
+        assert!(!state.start_take_over(now));
+        state.promotion = Some(Promotion { force: true });
+        assert!(state.start_take_over(now));
+        state.on_take_over();
+        assert_eq!(state.role, FailoverRole::Primary);
+        assert_eq!(state.takeover_failure, None);
+        assert!(!state.start_take_over(now + TAKEOVER_RETRY_INTERVAL));
+    }
+
+    #[test]
+    fn test_failover_restore_primary_tower() {
+        let tower_path = TempDir::new().unwrap();
+        let identity = Arc::new(Keypair::new());
+        let new_tower_storage = |lease_duration| {
+            let lease_store = FileLeaseStore::new(tower_path.path().to_path_buf()).unwrap();
+            Arc::new(LeasedTowerStorage::new(
+                Box::new(lease_store),
+                lease_duration,
+            ))
+        };
+        let new_service = |role, tower_storage: Arc<LeasedTowerStorage>| {
+            FailoverService::new(
+                FailoverConfig {
+                    role,
+                    bind_address: "127.0.0.1:0".parse().unwrap(),
+                    peer_address: "127.0.0.1:1".parse().unwrap(),
+                    grace_period: Duration::from_secs(10),
+                    identity: identity.clone(),
+                },
+                tower_storage,
+                Arc::default(),
+            )
+        };
+
+        // The primary's lease expires as soon as it stops renewing it
+        let primary_tower_storage = new_tower_storage(Duration::ZERO);
+        let primary = new_service(FailoverRole::Primary, primary_tower_storage.clone());
+        assert!(primary.restore_tower().is_err());
+        let tower = Tower {
+            node_pubkey: identity.pubkey(),
+            ..Tower::default()
+        };
+        tower
+            .save(primary_tower_storage.as_ref(), &identity)
+            .unwrap();
+
+        // The standby takes over the primary's tower, which fences off the
+        // primary
+        let standby_tower_storage = new_tower_storage(Duration::from_secs(60));
+        let standby = new_service(FailoverRole::Standby, standby_tower_storage.clone());
+        assert_eq!(standby.restore_tower().unwrap(), tower);
+        assert!(standby_tower_storage
+            .check_fence(&identity.pubkey())
+            .is_ok());
+        assert!(primary_tower_storage
+            .check_fence(&identity.pubkey())
+            .is_err());
+        assert!(tower
+            .save(primary_tower_storage.as_ref(), &identity)
+            .is_err());
+    }
+}
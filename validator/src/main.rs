@@ -25,17 +25,24 @@ use {
         system_monitor_service::SystemMonitorService,
         tpu::DEFAULT_TPU_COALESCE,
         validator::{
-            is_snapshot_config_valid, BlockProductionMethod, BlockVerificationMethod, Validator,
-            ValidatorConfig, ValidatorStartProgress,
+            is_snapshot_config_valid, BlockProductionMethod, BlockVerificationMethod, TpuQosPolicy,
+            Validator, ValidatorConfig, ValidatorStartProgress,
         },
     },
-    solana_gossip::{cluster_info::Node, legacy_contact_info::LegacyContactInfo as ContactInfo},
+    solana_gossip::{
+        cluster_info::Node,
+        crds_data_budget::{parse_crds_data_budget, CrdsDataBudgetConfig},
+        gossip_capture::GossipCaptureConfig,
+        legacy_contact_info::LegacyContactInfo as ContactInfo,
+    },
     solana_ledger::{
+        blockstore::TransactionHistoryFilter,
         blockstore_cleanup_service::{DEFAULT_MAX_LEDGER_SHREDS, DEFAULT_MIN_MAX_LEDGER_SHREDS},
         blockstore_options::{
             BlockstoreCompressionType, BlockstoreRecoveryMode, LedgerColumnOptions,
             ShredStorageType,
         },
+        transaction_history_purge_service::TransactionHistoryPurgeConfig,
         use_snapshot_archives_at_startup::{self, UseSnapshotArchivesAtStartup},
     },
     solana_perf::recycler::enable_recycler_warming,
@@ -63,7 +70,7 @@ use {
         signature::{read_keypair, Keypair, Signer},
     },
     solana_send_transaction_service::send_transaction_service,
-    solana_streamer::socket::SocketAddrSpace,
+    solana_streamer::{quic::QuicServerOptions, socket::SocketAddrSpace},
     solana_tpu_client::tpu_client::DEFAULT_TPU_ENABLE_UDP,
     solana_validator::{
         admin_rpc_service,
@@ -71,6 +78,7 @@ use {
         bootstrap,
         cli::{app, warn_for_deprecated_arguments, DefaultArgs},
         dashboard::Dashboard,
+        failover::{FailoverConfig, FailoverRole, FailoverService},
         ledger_lockfile, lock_ledger, new_spinner_progress_bar, println_name_value,
         redirect_stderr_to_file,
     },
@@ -711,6 +719,60 @@ pub fn main() {
                 });
             return;
         }
+        ("failover", Some(failover_subcommand_matches)) => {
+            match failover_subcommand_matches.subcommand() {
+                ("status", Some(subcommand_matches)) => {
+                    let output_mode = subcommand_matches.value_of("output");
+                    let admin_client = admin_rpc_service::connect(&ledger_path);
+                    let failover_status = admin_rpc_service::runtime()
+                        .block_on(async move { admin_client.await?.failover_status().await })
+                        .unwrap_or_else(|err| {
+                            eprintln!("Failover status query failed: {err}");
+                            exit(1);
+                        });
+                    if let Some(mode) = output_mode {
+                        match mode {
+                            "json" => println!(
+                                "{}",
+                                serde_json::to_string_pretty(&failover_status).unwrap()
+                            ),
+                            "json-compact" => {
+                                print!("{}", serde_json::to_string(&failover_status).unwrap())
+                            }
+                            _ => unreachable!(),
+                        }
+                    } else {
+                        print!("{failover_status}");
+                    }
+                    return;
+                }
+                ("promote", Some(subcommand_matches)) => {
+                    let force = subcommand_matches.is_present("force");
+                    let admin_client = admin_rpc_service::connect(&ledger_path);
+                    admin_rpc_service::runtime()
+                        .block_on(async move { admin_client.await?.failover_promote(force).await })
+                        .unwrap_or_else(|err| {
+                            eprintln!("failoverPromote request failed: {err}");
+                            exit(1);
+                        });
+                    println!(
+                        "Promotion requested, see `solana-validator failover status` for progress"
+                    );
+                    return;
+                }
+                ("demote", _) => {
+                    let admin_client = admin_rpc_service::connect(&ledger_path);
+                    admin_rpc_service::runtime()
+                        .block_on(async move { admin_client.await?.failover_demote().await })
+                        .unwrap_or_else(|err| {
+                            eprintln!("failoverDemote request failed: {err}");
+                            exit(1);
+                        });
+                    return;
+                }
+                _ => unreachable!(),
+            }
+        }
         ("set-identity", Some(subcommand_matches)) => {
             let require_tower = subcommand_matches.is_present("require_tower");
 
@@ -964,6 +1026,12 @@ pub fn main() {
             u64
         ),
         incremental_snapshot_fetch: !matches.is_present("no_incremental_snapshots"),
+        max_snapshot_download_peers: value_t_or_exit!(
+            matches,
+            "maximum_snapshot_download_peers",
+            usize
+        ),
+        unpack_snapshot_while_downloading: matches.is_present("unpack_snapshot_while_downloading"),
     };
 
     let private_rpc = matches.is_present("private_rpc");
@@ -1136,6 +1204,23 @@ pub fn main() {
                         }),
                 )
             }
+            "file-lease" => {
+                let tower_path = value_t!(matches, "tower", PathBuf)
+                    .ok()
+                    .unwrap_or_else(|| ledger_path.clone());
+                let lease_duration =
+                    Duration::from_millis(value_t_or_exit!(matches, "tower_lease_duration", u64));
+                let lease_store =
+                    tower_storage::FileLeaseStore::new(tower_path).unwrap_or_else(|err| {
+                        eprintln!("Failed to open tower lease storage: {err}");
+                        exit(1);
+                    });
+
+                Arc::new(tower_storage::LeasedTowerStorage::new(
+                    Box::new(lease_store),
+                    lease_duration,
+                ))
+            }
             _ => unreachable!(),
         };
 
@@ -1306,6 +1391,8 @@ pub fn main() {
             rpc_niceness_adj: value_t_or_exit!(matches, "rpc_niceness_adj", i8),
             account_indexes: account_indexes.clone(),
             rpc_scan_and_fix_roots: matches.is_present("rpc_scan_and_fix_roots"),
+            enable_simulation_program_overrides: matches
+                .is_present("rpc_enable_simulation_program_overrides"),
             max_request_body_size: Some(value_t_or_exit!(
                 matches,
                 "rpc_max_request_body_size",
@@ -1622,6 +1709,47 @@ pub fn main() {
         validator_config.max_ledger_shreds = Some(limit_ledger_size);
     }
 
+    let transaction_history_filter = TransactionHistoryFilter {
+        votes: matches.is_present("purge_vote_transaction_history"),
+        program_ids: values_t!(matches, "purge_transaction_history_program", Pubkey)
+            .unwrap_or_default()
+            .into_iter()
+            .collect(),
+    };
+    if !transaction_history_filter.is_empty() {
+        validator_config.transaction_history_purge = Some(TransactionHistoryPurgeConfig {
+            filter: transaction_history_filter,
+            retention_slots: value_t_or_exit!(matches, "transaction_history_retention_slots", Slot),
+        });
+    }
+
+    if let Some(dir) = matches.value_of("gossip_capture_dir") {
+        validator_config.gossip_capture = Some(GossipCaptureConfig {
+            dir: PathBuf::from(dir),
+            max_file_size: value_t_or_exit!(matches, "gossip_capture_file_size", u64)
+                .saturating_mul(1024 * 1024),
+            max_files: value_t_or_exit!(matches, "gossip_capture_max_files", usize),
+        });
+    }
+    let parse_crds_data_budgets = |name: &str| {
+        matches
+            .values_of(name)
+            .into_iter()
+            .flatten()
+            .map(|arg| parse_crds_data_budget(arg).unwrap())
+            .collect()
+    };
+    validator_config.gossip_crds_data_budget = CrdsDataBudgetConfig {
+        egress_bytes_per_sec: parse_crds_data_budgets("gossip_egress_budget"),
+        ingress_bytes_per_sec: parse_crds_data_budgets("gossip_ingress_budget"),
+        priority: matches
+            .values_of("gossip_priority")
+            .into_iter()
+            .flatten()
+            .map(String::from)
+            .collect(),
+    };
+
     configure_banking_trace_dir_byte_limit(&mut validator_config, &matches);
     validator_config.block_verification_method = value_t!(
         matches,
@@ -1635,6 +1763,27 @@ pub fn main() {
         BlockProductionMethod
     )
     .unwrap_or_default();
+    validator_config.tpu_qos_policy =
+        value_t!(matches, "tpu_qos_policy", TpuQosPolicy).unwrap_or_default();
+    validator_config.tpu_qos_allowed_peers = values_t!(matches, "tpu_qos_allowed_peers", Pubkey)
+        .map(HashSet::from_iter)
+        .unwrap_or_default();
+    if !validator_config.tpu_qos_allowed_peers.is_empty()
+        && !matches!(
+            validator_config.tpu_qos_policy,
+            TpuQosPolicy::PacketValidity
+        )
+    {
+        clap::Error::with_description(
+            "The --tpu-qos-allowed-peer argument requires --tpu-qos-policy packet-validity",
+            clap::ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+    validator_config.tpu_quic_server_options = QuicServerOptions {
+        enable_datagrams: matches.is_present("tpu_enable_quic_datagrams"),
+        enable_migration: !matches.is_present("tpu_disable_quic_migration"),
+    };
 
     validator_config.ledger_column_options = LedgerColumnOptions {
         compression_type: match matches.value_of("rocksdb_ledger_compression") {
@@ -1699,6 +1848,61 @@ pub fn main() {
         } else {
             (None, None)
         };
+    let failover = matches.value_of("failover_role").map(|role| {
+        let role = match role {
+            "primary" => FailoverRole::Primary,
+            "standby" => FailoverRole::Standby,
+            _ => unreachable!(),
+        };
+        // The standby can only take over with the tower of the primary
+        if value_t_or_exit!(matches, "tower_storage", String) == "file" {
+            eprintln!(
+                "--failover-role requires --tower-storage etcd or file-lease, with the tower \
+                shared by the primary and the standby"
+            );
+            exit(1);
+        }
+        let parse_address = |name| {
+            solana_net_utils::parse_host_port(matches.value_of(name).unwrap()).unwrap_or_else(
+                |err| {
+                    eprintln!("Failed to parse --{}: {err}", name.replace('_', "-"));
+                    exit(1);
+                },
+            )
+        };
+        let identity = keypair_of(&matches, "failover_identity")
+            .or_else(|| keypair_of(&matches, "identity"))
+            .expect("failover identity");
+        if role == FailoverRole::Standby && identity.pubkey() == identity_keypair.pubkey() {
+            eprintln!(
+                "A standby must be started with an --identity other than --failover-identity"
+            );
+            exit(1);
+        }
+        FailoverService::new(
+            FailoverConfig {
+                role,
+                bind_address: parse_address("failover_address"),
+                peer_address: parse_address("failover_peer"),
+                grace_period: Duration::from_secs(value_t_or_exit!(
+                    matches,
+                    "failover_grace_period",
+                    u64
+                )),
+                identity: Arc::new(identity),
+            },
+            validator_config.tower_storage.clone(),
+            admin_service_post_init.clone(),
+        )
+    });
+    if let Some(failover) = &failover {
+        failover
+            .start(&validator_config.validator_exit)
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to start failover: {err}");
+                exit(1);
+            });
+    }
     admin_rpc_service::run(
         &ledger_path,
         admin_rpc_service::AdminRpcRequestMetadata {
@@ -1711,6 +1915,7 @@ pub fn main() {
             tower_storage: validator_config.tower_storage.clone(),
             staked_nodes_overrides,
             rpc_to_plugin_manager_sender,
+            failover,
         },
     );
 